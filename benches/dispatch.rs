@@ -0,0 +1,20 @@
+use criterion::{Criterion, criterion_group, criterion_main};
+use microcvm_rs::bench::BENCH_PROGRAM;
+use microcvm_rs::cpu::MicroCVMCpu;
+use std::hint::black_box;
+
+fn arithmetic_loop(c: &mut Criterion) {
+    let mut cpu = MicroCVMCpu::empty();
+    cpu.load_raw(BENCH_PROGRAM, 0).unwrap();
+
+    c.bench_function("arithmetic loop, 1000 instructions", |b| {
+        b.iter(|| {
+            for _ in 0..1000 {
+                black_box(cpu.execute_instruction()).unwrap();
+            }
+        })
+    });
+}
+
+criterion_group!(benches, arithmetic_loop);
+criterion_main!(benches);
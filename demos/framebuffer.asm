@@ -0,0 +1,40 @@
+; Draws a moving gradient on a 256x256 screen by writing bytes straight into video memory
+; through the framebuffer window, which the host maps at 0x8000.
+;
+; The window shows 16 KiB of the framebuffer at a time as red, green, blue bytes, so a
+; 256x256 frame takes 12 banks. Every byte gets 85 times its offset within the page, plus
+; the page number and a phase that moves each frame, which spreads red, green and blue of
+; each pixel apart. The address of the `store` at `poke` is
+; patched for every byte.
+
+        .equ VSYNC, 0x05
+        .equ fb_bank, 0xFF11
+        .equ window_hi, 0x80
+        .equ window_end_hi, 0xC0
+        .equ banks, 12
+
+        mov r6, 0               ; phase
+frame:  mov r5, 0               ; bank
+bank:   store [fb_bank], r5
+        mov r3, window_hi       ; page, the high byte of the address
+page:   store [poke+2], r3
+        mov r2, 0               ; the low byte of the address
+byte:   store [poke+1], r2
+        mov r0, r2
+        mul r0, 85              ; a third of the way round for each channel
+        add r0, r3
+        add r0, r6
+poke:   store [0x8000], r0
+        inc r2
+        jrnz byte
+        inc r3
+        mov r4, r3
+        sub r4, window_end_hi
+        jrnz page
+        inc r5
+        mov r4, r5
+        sub r4, banks
+        jrnz bank
+        video VSYNC, r0
+        add r6, 3
+        jmp frame
@@ -0,0 +1,737 @@
+; Exercises every instruction and halts with exit code 0, r0, if all of them behaved, or
+; with the number of the last failing test otherwise.
+;
+; Each test leaves r1 = 0 on success. r1 indexes the page-aligned `nonzero` table
+; by patching the low byte of the `load` address, giving r4 = 0 or 1, and then
+; r0 += (n - r0) * r4 records test n as failed without needing a branch.
+;
+; The host has to register hcall SELF_TEST_HCALL to double r1.
+
+        .equ SELF_TEST_HCALL, 1
+        .equ SETPIXEL, 0x03
+        .equ buffer, 0x0E00
+        .equ buffer_lo, 0x00
+        .equ buffer_hi, 0x0E
+        .equ stack_top, 0xFF00
+
+        mov r0, 0
+
+; test 1: mov with an immediate
+        mov r1, 42
+        sub r1, 42
+        store [check1+2], r1
+check1: load r4, [nonzero]
+        mov r5, 1
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 2: mov with a register
+        mov r2, 17
+        mov r1, r2
+        sub r1, 17
+        store [check2+2], r1
+check2: load r4, [nonzero]
+        mov r5, 2
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 3: add with an immediate
+        mov r1, 40
+        add r1, 2
+        sub r1, 42
+        store [check3+2], r1
+check3: load r4, [nonzero]
+        mov r5, 3
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 4: add with a register, wrapping
+        mov r1, 200
+        mov r2, 100
+        add r1, r2
+        sub r1, 44
+        store [check4+2], r1
+check4: load r4, [nonzero]
+        mov r5, 4
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 5: sub with a register, wrapping
+        mov r1, 10
+        mov r2, 20
+        sub r1, r2
+        sub r1, 246
+        store [check5+2], r1
+check5: load r4, [nonzero]
+        mov r5, 5
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 6: inc, wrapping
+        mov r1, 255
+        inc r1
+        store [check6+2], r1
+check6: load r4, [nonzero]
+        mov r5, 6
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 7: mul, wrapping
+        mov r1, 20
+        mul r1, 20
+        sub r1, 144
+        store [check7+2], r1
+check7: load r4, [nonzero]
+        mov r5, 7
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 8: div with an immediate
+        mov r1, 200
+        div r1, 7
+        sub r1, 28
+        store [check8+2], r1
+check8: load r4, [nonzero]
+        mov r5, 8
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 9: div with a register
+        mov r1, 100
+        mov r2, 10
+        div r1, r2
+        sub r1, 10
+        store [check9+2], r1
+check9: load r4, [nonzero]
+        mov r5, 9
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 10: store and load
+        mov r2, 0x5A
+        store [scratch], r2
+        mov r1, 0
+        load r1, [scratch]
+        sub r1, 0x5A
+        store [check10+2], r1
+check10: load r4, [nonzero]
+        mov r5, 10
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 11: jmp
+        mov r1, 0
+        jmp skip11
+        mov r1, 1
+skip11:
+        store [check11+2], r1
+check11: load r4, [nonzero]
+        mov r5, 11
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 12: nop
+        mov r1, 3
+        nop
+        sub r1, 3
+        store [check12+2], r1
+check12: load r4, [nonzero]
+        mov r5, 12
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 13: hcall
+        mov r1, 21
+        hcall SELF_TEST_HCALL
+        sub r1, 42
+        store [check13+2], r1
+check13: load r4, [nonzero]
+        mov r5, 13
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 14: video
+        mov r2, 0
+        mov r3, 0
+        mov r4, 255
+        mov r5, 255
+        mov r6, 255
+        video SETPIXEL, r2
+        mov r1, 0
+        store [check14+2], r1
+check14: load r4, [nonzero]
+        mov r5, 14
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 15: jr forward
+        mov r1, 0
+        jr skip15
+        mov r1, 1
+skip15:
+        store [check15+2], r1
+check15: load r4, [nonzero]
+        mov r5, 15
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 16: jr backward
+        mov r1, 1
+        jr forward16
+back16: mov r1, 0
+        jr done16
+forward16:
+        jr back16
+done16:
+        store [check16+2], r1
+check16: load r4, [nonzero]
+        mov r5, 16
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 17: jrz and jrnz after a zero result
+        mov r1, 0
+        mov r2, 5
+        sub r2, 5
+        jrnz fail17
+        jrz done17
+fail17: mov r1, 1
+done17:
+        store [check17+2], r1
+check17: load r4, [nonzero]
+        mov r5, 17
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 18: jrz and jrnz after a nonzero result
+        mov r1, 0
+        inc r2
+        jrz fail18
+        jrnz done18
+fail18: mov r1, 1
+done18:
+        store [check18+2], r1
+check18: load r4, [nonzero]
+        mov r5, 18
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 19: djnz counts a loop down to zero
+        mov r1, 5
+        mov r2, 0
+loop19: inc r2
+        djnz r1, loop19
+        mov r1, r2
+        sub r1, 5
+        store [check19+2], r1
+check19: load r4, [nonzero]
+        mov r5, 19
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 20: djnz leaves the zero flag alone
+        mov r2, 1
+        sub r2, 1
+        mov r3, 2
+        djnz r3, next20
+next20: mov r1, 0
+        jrz done20
+        mov r1, 1
+done20:
+        store [check20+2], r1
+check20: load r4, [nonzero]
+        mov r5, 20
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 21: test sets the zero flag without writing the register
+        mov r2, 0x0A
+        test r2, 0x05
+        mov r1, r2
+        jrz zero21
+        mov r1, 0xFF
+zero21: sub r1, 0x0A
+        store [check21+2], r1
+check21: load r4, [nonzero]
+        mov r5, 21
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 22: test clears the zero flag on a nonzero result
+        mov r2, 0x0A
+        test r2, 0x02
+        mov r1, 0
+        jrnz done22
+        mov r1, 1
+done22:
+        store [check22+2], r1
+check22: load r4, [nonzero]
+        mov r5, 22
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 23: bset and bclr
+        mov r1, 0x0F
+        bset r1, 7
+        bclr r1, 0
+        sub r1, 0x8E
+        store [check23+2], r1
+check23: load r4, [nonzero]
+        mov r5, 23
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 24: btst sets the zero flag when the bit is clear
+        mov r2, 0x04
+        mov r1, 0
+        btst r2, 2
+        jrz fail24
+        btst r2, 3
+        jrz done24
+fail24: mov r1, 1
+done24:
+        store [check24+2], r1
+check24: load r4, [nonzero]
+        mov r5, 24
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 25: memset fills exactly len bytes
+        mov r2, buffer_lo
+        mov r3, buffer_hi
+        mov r4, 0x77
+        mov r5, 4
+        mov r6, 0
+        memset r2
+        load r1, [buffer+3]
+        sub r1, 0x77
+        load r2, [buffer+4]
+        add r1, r2
+        store [check25+2], r1
+check25: load r4, [nonzero]
+        mov r5, 25
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 26: memcpy to an overlapping range above the source
+        mov r2, 1
+        store [buffer], r2
+        mov r2, 2
+        store [buffer+1], r2
+        mov r2, 3
+        store [buffer+2], r2
+        mov r2, buffer_lo+1
+        mov r3, buffer_hi
+        mov r4, buffer_lo
+        mov r5, buffer_hi
+        mov r6, 3
+        mov r7, 0
+        memcpy r2
+        load r1, [buffer+3]
+        sub r1, 3
+        store [check26+2], r1
+check26: load r4, [nonzero]
+        mov r5, 26
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 27: memcpy to an overlapping range below the source
+        mov r2, buffer_lo
+        mov r3, buffer_hi
+        mov r4, buffer_lo+1
+        mov r5, buffer_hi
+        mov r6, 3
+        mov r7, 0
+        memcpy r2
+        load r1, [buffer]
+        sub r1, 1
+        store [check27+2], r1
+check27: load r4, [nonzero]
+        mov r5, 27
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 28: zero-length block operations don't fault, even aimed at MMIO
+        mov r2, 0x00
+        mov r3, 0xFF
+        mov r4, 0
+        mov r5, 0
+        mov r6, 0
+        mov r7, 0
+        memset r2
+        memcpy r2
+        mov r1, 0
+        store [check28+2], r1
+check28: load r4, [nonzero]
+        mov r5, 28
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 29: nested call and ret
+        mov r1, 3
+        call outer29
+        sub r1, 1
+        jr done29
+outer29: sub r1, 1
+        call inner29
+        ret
+inner29: sub r1, 1
+        ret
+done29:
+        store [check29+2], r1
+check29: load r4, [nonzero]
+        mov r5, 29
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; The stack is empty from here on, so pushf stores the flags at stack_top - 1.
+
+; test 30: add sets the carry flag when it wraps
+        mov r1, 200
+        add r1, 56
+        pushf
+        load r1, [stack_top-1]
+        popf
+        sub r1, 0x03
+        store [check30+2], r1
+check30: load r4, [nonzero]
+        mov r5, 30
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 31: sub sets the carry flag when it borrows
+        mov r1, 5
+        sub r1, 6
+        pushf
+        load r1, [stack_top-1]
+        popf
+        sub r1, 0x02
+        store [check31+2], r1
+check31: load r4, [nonzero]
+        mov r5, 31
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 32: stc sets the carry flag and cmc flips it
+        mov r1, 1
+        add r1, 0
+        stc
+        cmc
+        cmc
+        pushf
+        load r1, [stack_top-1]
+        popf
+        sub r1, 0x02
+        store [check32+2], r1
+check32: load r4, [nonzero]
+        mov r5, 32
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 33: clc clears the carry flag
+        mov r1, 0
+        sub r1, 1
+        clc
+        pushf
+        load r1, [stack_top-1]
+        popf
+        store [check33+2], r1
+check33: load r4, [nonzero]
+        mov r5, 33
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 34: popf restores every bit, interrupt enable included
+        pushf
+        mov r2, 0x07
+        store [stack_top-1], r2
+        popf
+        pushf
+        load r1, [stack_top-1]
+        mov r2, 0
+        store [stack_top-1], r2
+        popf
+        sub r1, 0x07
+        store [check34+2], r1
+check34: load r4, [nonzero]
+        mov r5, 34
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 35: mul sets the carry flag when the product doesn't fit
+        mov r1, 20
+        mul r1, 13
+        pushf
+        load r1, [stack_top-1]
+        popf
+        sub r1, 0x02
+        store [check35+2], r1
+check35: load r4, [nonzero]
+        mov r5, 35
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 36: div clears the carry flag
+        stc
+        mov r1, 9
+        div r1, 2
+        pushf
+        load r1, [stack_top-1]
+        popf
+        store [check36+2], r1
+check36: load r4, [nonzero]
+        mov r5, 36
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 37: ei sets the interrupt flag
+        mov r1, 1
+        add r1, 0
+        ei
+        pushf
+        di
+        pushf
+        load r3, [stack_top-2]
+        load r1, [stack_top-1]
+        popf
+        popf
+        di
+        sub r1, 0x04
+        store [check37+2], r1
+check37: load r4, [nonzero]
+        mov r5, 37
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 38: di clears it
+        mov r1, r3
+        store [check38+2], r1
+check38: load r4, [nonzero]
+        mov r5, 38
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 39: int enters the vector's handler and iret comes back after it
+        mov r2, 0x00
+        store [0xFF90], r2
+        mov r2, 0x10
+        store [0xFF91], r2
+        mov r1, 5
+        int 1
+        store [check39+2], r1
+check39: load r4, [nonzero]
+        mov r5, 39
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 40: fxmul multiplies register pairs as 8.8, high byte first
+        mov r2, 0x01
+        mov r3, 0x80
+        mov r6, 0xFD
+        mov r7, 0xC0
+        fxmul r2, r6
+        mov r1, r2
+        sub r1, 0xFC
+        add r1, r3
+        sub r1, 0xA0
+        store [check40+2], r1
+check40: load r4, [nonzero]
+        mov r5, 40
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 41: fxdiv divides them back and fxmuli takes an 8.8 immediate
+        fxdiv r2, r6
+        fxmuli r2, #fx(-0.25)
+        mov r1, r2
+        sub r1, 0xFF
+        add r1, r3
+        sub r1, 0xA0
+        store [check41+2], r1
+check41: load r4, [nonzero]
+        mov r5, 41
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 42: fxmuli sets the carry flag when the integer part overflows
+        mov r2, 0x40
+        mov r3, 0x00
+        fxmuli r2, #fx(2)
+        pushf
+        load r1, [stack_top-1]
+        popf
+        sub r1, 0x02
+        add r1, r2
+        sub r1, 0x80
+        store [check42+2], r1
+check42: load r4, [nonzero]
+        mov r5, 42
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 43: dec wraps 0 to 255 and sets the carry flag
+        mov r1, 0
+        dec r1
+        pushf
+        load r2, [stack_top-1]
+        popf
+        sub r1, 0xFF
+        add r1, r2
+        sub r1, 0x02
+        store [check43+2], r1
+check43: load r4, [nonzero]
+        mov r5, 43
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 44: clr zeroes a register and leaves the flags alone
+        mov r1, 1
+        dec r1
+        mov r1, 9
+        clr r1
+        jrz clear44
+        mov r1, 1
+clear44:
+        store [check44+2], r1
+check44: load r4, [nonzero]
+        mov r5, 44
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 45: movm copies a byte between addresses without touching a register
+        mov r1, 7
+        movm [scratch], [nonzero+1]
+        load r2, [scratch]
+        sub r1, 7
+        add r1, r2
+        sub r1, 1
+        store [check45+2], r1
+check45: load r4, [nonzero]
+        mov r5, 45
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 46: [rN+] pointers step through a pair, carrying into its high byte
+        mov r2, buffer_hi
+        mov r3, 0xFF
+        mov r1, 5
+        store [r2+], r1
+        load r1, [r2+]
+        sub r2, 0x0F
+        add r1, r2
+        sub r3, 0x01
+        add r1, r3
+        load r2, [buffer+0xFF]
+        sub r2, 5
+        add r1, r2
+        store [check46+2], r1
+check46: load r4, [nonzero]
+        mov r5, 46
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 47: [sp + offset] counts from sp as it is, so a slot moves up after a pushf
+        mov r1, 9
+        pushf
+        store [sp], r1
+        pushf
+        load r1, [sp + 1]
+        popf
+        popf
+        sub r1, 9
+        store [check47+2], r1
+check47: load r4, [nonzero]
+        mov r5, 47
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+; test 48: jtab jumps to the entry its register indexes
+        mov r1, 2
+        jtab r1, table48
+miss48: mov r1, 1
+        jr done48
+hit48:  mov r1, 0
+done48: store [check48+2], r1
+check48: load r4, [nonzero]
+        mov r5, 48
+        sub r5, r0
+        mul r5, r4
+        add r0, r5
+
+        hlt
+
+service39: sub r1, 5
+        iret
+
+scratch: .db 0
+table48: .jumptable miss48, miss48, hit48, miss48
+
+        .org 0x0F00
+nonzero:
+        .db 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1
+        .db 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1
+        .db 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1
+        .db 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1
+        .db 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1
+        .db 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1
+        .db 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1
+        .db 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1
+        .db 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1
+        .db 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1
+        .db 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1
+        .db 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1
+        .db 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1
+        .db 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1
+        .db 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1
+        .db 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1
+
+        .org 0x1000
+vectors:
+        .dw 0, service39
@@ -0,0 +1,424 @@
+; The self-test for 16-bit registers. Exercises every instruction and halts with exit
+; code 0, r0, if all of them behaved, or with the number of the last failing test otherwise.
+;
+; Each test leaves r1 = 0 on success and then calls `check` with its number in r7.
+;
+; The machine has to be built with 16-bit registers, and the host has to register hcall
+; SELF_TEST_HCALL to double r1.
+
+        .width 16
+
+        .equ SELF_TEST_HCALL, 1
+        .equ SETPIXEL, 0x03
+        .equ buffer, 0x0E00
+        .equ buffer_lo, 0x00
+        .equ buffer_hi, 0x0E
+        .equ stack_top, 0xFF00
+
+        mov r0, 0
+
+; test 1: mov with a two-byte immediate
+        mov r1, 0x1234
+        sub r1, 0x1234
+        mov r7, 1
+        call check
+
+; test 2: mov with a register
+        mov r2, 0xBEEF
+        mov r1, r2
+        sub r1, 0xBEEF
+        mov r7, 2
+        call check
+
+; test 3: add carries past the low byte
+        mov r1, 200
+        add r1, 100
+        sub r1, 300
+        mov r7, 3
+        call check
+
+; test 4: add with a register, wrapping at 16 bits
+        mov r1, 60000
+        mov r2, 10000
+        add r1, r2
+        sub r1, 4464
+        mov r7, 4
+        call check
+
+; test 5: sub with a register, wrapping
+        mov r1, 10
+        mov r2, 20
+        sub r1, r2
+        sub r1, 65526
+        mov r7, 5
+        call check
+
+; test 6: inc carries past the low byte
+        mov r1, 255
+        inc r1
+        sub r1, 256
+        mov r7, 6
+        call check
+
+; test 7: inc, wrapping
+        mov r1, -1
+        inc r1
+        mov r7, 7
+        call check
+
+; test 8: mul, wrapping
+        mov r1, 300
+        mul r1, 300
+        sub r1, 24464
+        mov r7, 8
+        call check
+
+; test 9: div with an immediate
+        mov r1, 50000
+        div r1, 7
+        sub r1, 7142
+        mov r7, 9
+        call check
+
+; test 10: div with a register
+        mov r1, 1000
+        mov r2, 10
+        div r1, r2
+        sub r1, 100
+        mov r7, 10
+        call check
+
+; test 11: store and load move both bytes
+        mov r2, 0x5AA5
+        store [scratch], r2
+        mov r1, 0
+        load r1, [scratch]
+        sub r1, 0x5AA5
+        mov r7, 11
+        call check
+
+; test 12: the low byte is stored first
+        load r1, [scratch+1]
+        sub r1, 0x5A
+        mov r7, 12
+        call check
+
+; test 13: jmp
+        mov r1, 0
+        jmp skip13
+        mov r1, 1
+skip13: mov r7, 13
+        call check
+
+; test 14: nop
+        mov r1, 3
+        nop
+        sub r1, 3
+        mov r7, 14
+        call check
+
+; test 15: hcall sees the whole register
+        mov r1, 1000
+        hcall SELF_TEST_HCALL
+        sub r1, 2000
+        mov r7, 15
+        call check
+
+; test 16: video takes the low byte of each register
+        mov r2, 0x0100
+        mov r3, 0
+        mov r4, 255
+        mov r5, 0
+        mov r6, 255
+        video SETPIXEL, r2
+        mov r1, 0
+        mov r7, 16
+        call check
+
+; test 17: jr forward and backward
+        mov r1, 1
+        jr forward17
+back17: mov r1, 0
+        jr done17
+forward17:
+        jr back17
+done17: mov r7, 17
+        call check
+
+; test 18: a result is only zero if all 16 bits are
+        mov r1, 0
+        mov r2, 0x0100
+        add r2, 0
+        jrz fail18
+        sub r2, 0x0100
+        jrz done18
+fail18: mov r1, 1
+done18: mov r7, 18
+        call check
+
+; test 19: djnz counts a loop of more than 256 down to zero
+        mov r1, 300
+        mov r2, 0
+loop19: inc r2
+        djnz r1, loop19
+        mov r1, r2
+        sub r1, 300
+        mov r7, 19
+        call check
+
+; test 20: test looks at the high byte too
+        mov r2, 0x0A00
+        mov r1, 0
+        test r2, 0x0500
+        jrnz fail20
+        test r2, 0x0200
+        jrnz done20
+fail20: mov r1, 1
+done20: mov r7, 20
+        call check
+
+; test 21: bset and bclr reach bit 15
+        mov r1, 0x000F
+        bset r1, 15
+        bclr r1, 0
+        sub r1, 0x800E
+        mov r7, 21
+        call check
+
+; test 22: btst reaches the high byte
+        mov r2, 0x1000
+        mov r1, 0
+        btst r2, 12
+        jrz fail22
+        btst r2, 11
+        jrz done22
+fail22: mov r1, 1
+done22: mov r7, 22
+        call check
+
+; test 23: memset takes the low byte of each register
+        mov r2, buffer_lo
+        mov r3, buffer_hi
+        mov r4, 0x1277
+        mov r5, 0x0104
+        mov r6, 0
+        memset r2
+        load r1, [buffer+3]
+        sub r1, 0x77
+        mov r7, 23
+        call check
+
+; test 24: memcpy to an overlapping range above the source
+        mov r2, 0x0201
+        store [buffer], r2
+        mov r2, 0x0003
+        store [buffer+2], r2
+        mov r2, buffer_lo+1
+        mov r3, buffer_hi
+        mov r4, buffer_lo
+        mov r5, buffer_hi
+        mov r6, 3
+        mov r7, 0
+        memcpy r2
+        load r1, [buffer+3]
+        sub r1, 3
+        mov r7, 24
+        call check
+
+; test 25: nested call and ret
+        mov r1, 3
+        call outer25
+        sub r1, 1
+        jr done25
+outer25: sub r1, 1
+        call inner25
+        ret
+inner25: sub r1, 1
+        ret
+done25: mov r7, 25
+        call check
+
+; The stack is empty between tests, so two pushf store the flags in both bytes of the word
+; at stack_top - 2, where a load can see them.
+
+; test 26: add sets the carry flag when it wraps at 16 bits
+        mov r1, 60000
+        add r1, 5536
+        pushf
+        pushf
+        load r1, [stack_top-2]
+        popf
+        popf
+        sub r1, 0x0303
+        mov r7, 26
+        call check
+
+; test 27: add past the low byte does not carry
+        mov r1, 200
+        add r1, 100
+        pushf
+        pushf
+        load r1, [stack_top-2]
+        popf
+        popf
+        mov r7, 27
+        call check
+
+; test 28: sub sets the carry flag when it borrows
+        mov r1, 5
+        sub r1, 6
+        pushf
+        pushf
+        load r1, [stack_top-2]
+        popf
+        popf
+        sub r1, 0x0202
+        mov r7, 28
+        call check
+
+; test 29: stc, cmc and clc
+        mov r1, 1
+        add r1, 0
+        stc
+        cmc
+        cmc
+        pushf
+        clc
+        pushf
+        load r1, [stack_top-2]
+        popf
+        popf
+        sub r1, 0x0200
+        mov r7, 29
+        call check
+
+; test 30: popf restores every bit, interrupt enable included
+        pushf
+        pushf
+        mov r2, 0x0707
+        store [stack_top-2], r2
+        popf
+        popf
+        pushf
+        pushf
+        load r1, [stack_top-2]
+        mov r2, 0
+        store [stack_top-2], r2
+        popf
+        popf
+        sub r1, 0x0707
+        mov r7, 30
+        call check
+
+; test 31: ei sets the interrupt flag and di clears it
+        mov r1, 1
+        add r1, 0
+        ei
+        pushf
+        di
+        pushf
+        load r1, [stack_top-2]
+        popf
+        popf
+        di
+        sub r1, 0x0400
+        mov r7, 31
+        call check
+
+; test 32: register pairs take the low byte of each register
+        mov r2, 0x1201
+        mov r3, 0x0080
+        mov r4, 0x00FD
+        mov r5, 0xFFC0
+        fxmul r2, r4
+        mov r1, r2
+        sub r1, 0x00FC
+        add r1, r3
+        sub r1, 0x00A0
+        mov r7, 32
+        call check
+
+; test 33: fxdiv, and fxmuli setting the carry flag on overflow
+        fxdiv r2, r4
+        fxmuli r2, #fx(100)
+        pushf
+        pushf
+        load r1, [stack_top-2]
+        popf
+        popf
+        sub r1, 0x0202
+        add r1, r2
+        sub r1, 0x0096
+        add r1, r3
+        mov r7, 33
+        call check
+
+; test 34: dec wraps 0 to 0xFFFF and sets the carry flag, clr zeroes all 16 bits
+        mov r1, 0
+        dec r1
+        pushf
+        pushf
+        load r2, [stack_top-2]
+        popf
+        popf
+        sub r1, 0xFFFF
+        add r1, r2
+        sub r1, 0x0202
+        mov r3, 0x1234
+        clr r3
+        add r1, r3
+        mov r7, 34
+        call check
+
+; test 35: movm copies one byte, not a register's worth
+        mov r1, 0xFFFF
+        store [scratch], r1
+        movm [scratch], [scratch+2]
+        load r1, [scratch]
+        sub r1, 0xFF00
+        mov r7, 35
+        call check
+
+; test 36: a [rN+] pointer steps by two, past the word it moved
+        mov r1, buffer+0xFE
+        mov r3, 0x1234
+        store [r1+], r3
+        sub r1, 2
+        load r2, [r1+]
+        sub r2, 0x1234
+        sub r1, buffer+0x100
+        add r1, r2
+        mov r7, 36
+        call check
+
+; test 37: [sp + offset] slots hold a whole register
+        mov r2, 0x1234
+        pushf
+        pushf
+        store [sp], r2
+        load r1, [sp]
+        popf
+        popf
+        sub r1, 0x1234
+        mov r7, 37
+        call check
+
+; test 38: jtab takes its index from all 16 bits of the register
+        mov r1, 0x0101
+        jtab r1, table38-0x0200
+miss38: mov r1, 1
+        jr done38
+hit38:  mov r1, 0
+done38: mov r7, 38
+        call check
+
+        hlt
+
+; Records test r7 as failed unless r1 is 0.
+check:  test r1, 0xFFFF
+        jrz passed
+        mov r0, r7
+passed: ret
+
+scratch: .dw 0, 0
+table38: .jumptable miss38, hit38
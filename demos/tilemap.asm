@@ -0,0 +1,70 @@
+; Scrolls a checkerboard across a 256x256 screen in tile mode, without drawing anything
+; after the first frame.
+;
+; Tile 0 is dark and tile 1 light, and the map alternates them. A 256x256 screen has a
+; 33x33 map, so cell parity alternates from row to row as well and the map comes out as a
+; checkerboard. Its size is odd, so the pattern doesn't line up where the map wraps around.
+; Instead the scroll registers move one tile down and to the right and start over, which
+; looks the same as moving on because the checkerboard repeats every two tiles. The
+; address of the `store` at `poke` is patched for every cell.
+
+        .equ VSYNC, 0x05
+        .equ video_control, 0xFF13
+        .equ tile_set, 0xFFB0
+        .equ tile_map, 0xFFB3
+        .equ scroll_x, 0xFFB6
+        .equ scroll_y, 0xFFB8
+
+        .equ tiles_hi, 0x90     ; tiles at 0x9000, 192 bytes each
+        .equ map_hi, 0xA0       ; map at 0xA000, 1089 cells rounded up to 5 pages
+        .equ map_end_hi, 0xA5
+
+        mov r0, 0x00
+        mov r1, tiles_hi
+        mov r2, 0x30
+        mov r3, 192
+        mov r4, 0
+        memset r0               ; tile 0
+        mov r0, 192
+        mov r2, 0xD0
+        memset r0               ; tile 1
+
+        mov r3, map_hi          ; page, the high byte of the address
+page:   store [poke+2], r3
+        mov r2, 0               ; the low byte of the address
+cell:   store [poke+1], r2
+        mov r0, r2
+        div r0, 2
+        mul r0, 2
+        mov r1, r2
+        sub r1, r0              ; 0 for even cells, 1 for odd ones
+poke:   store [0xA000], r1
+        inc r2
+        jrnz cell
+        inc r3
+        mov r4, r3
+        sub r4, map_end_hi
+        jrnz page
+
+        mov r0, 0
+        store [tile_set], r0
+        store [tile_set+2], r0
+        store [tile_map], r0
+        store [tile_map+2], r0
+        mov r0, tiles_hi
+        store [tile_set+1], r0
+        mov r0, map_hi
+        store [tile_map+1], r0
+        mov r0, 1
+        store [video_control], r0
+
+        mov r5, 0               ; scroll, in pixels
+frame:  store [scroll_x], r5
+        store [scroll_y], r5
+        video VSYNC, r0
+        inc r5
+        mov r4, r5
+        sub r4, 8
+        jrnz frame
+        mov r5, 0
+        jmp frame
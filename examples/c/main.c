@@ -0,0 +1,48 @@
+#include <stdio.h>
+
+#include "microcvm.h"
+
+/* mov r0, 20; mul r0, 3; inc r0; store [0x0100], r0; hlt */
+static const uint8_t PROGRAM[] = {
+    0x06, 0x00, 0x14,
+    0x09, 0x00, 0x03,
+    0x07, 0x00,
+    0x02, 0x00, 0x01, 0x00,
+    0xFF,
+};
+
+int main(void) {
+    MicroCvm *vm = microcvm_new();
+    if (vm == NULL) {
+        fprintf(stderr, "could not create the VM\n");
+        return 1;
+    }
+
+    int status = microcvm_load(vm, PROGRAM, sizeof(PROGRAM), 0);
+    if (status != MICROCVM_OK) {
+        fprintf(stderr, "load failed: %d\n", status);
+        microcvm_free(vm);
+        return 1;
+    }
+
+    status = microcvm_run(vm, 1000);
+    if (status != MICROCVM_HALTED) {
+        fprintf(stderr, "program did not halt: %d\n", status);
+        microcvm_free(vm);
+        return 1;
+    }
+
+    uint8_t r0 = 0;
+    uint8_t stored = 0;
+    microcvm_get_register(vm, 0, &r0);
+    microcvm_read_mem(vm, 0x0100, &stored, 1);
+    printf("r0 = %u, [0x0100] = %u\n", r0, stored);
+
+    uint8_t pixel[4];
+    microcvm_read_framebuffer(vm, 0, pixel, 1);
+    printf("first pixel = %u %u %u %u (%zu pixels)\n", pixel[0], pixel[1], pixel[2], pixel[3],
+           microcvm_framebuffer_len(vm));
+
+    microcvm_free(vm);
+    return r0 == 61 && stored == 61 ? 0 : 1;
+}
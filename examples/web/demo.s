@@ -0,0 +1,28 @@
+; Paints a 256x256 framebuffer one row per DMA transfer. Row n is copied from
+; 0x1000 + n + offset, so the gradient the page stores at 0x1000 turns into a
+; diagonal pattern. Every key event adds its scancode to the offset.
+
+        .equ dma_src, 0xFF20
+        .equ dma_dst, 0xFF23
+        .equ dma_len, 0xFF26
+        .equ dma_ctrl, 0xFF29
+        .equ key_status, 0xFF30
+        .equ key_code, 0xFF31
+
+        mov r0, 0x10
+        store [dma_src+1], r0
+        mov r0, 1
+        store [dma_len+1], r0   ; 256 pixels per transfer
+
+row:    mov r2, r1
+        add r2, r4
+        store [dma_src], r2
+        store [dma_dst+1], r1
+        mov r0, 1
+        store [dma_ctrl], r0
+        inc r1
+
+        load r5, [key_code]     ; 0 when no event is pending
+        add r4, r5
+        store [key_status], r0  ; pop the event
+        jmp row
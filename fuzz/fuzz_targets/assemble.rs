@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use microcvm_rs::asm::{assemble, assemble_segments};
+
+fuzz_target!(|source: &str| {
+    let _ = assemble(source);
+    let _ = assemble_segments(source);
+});
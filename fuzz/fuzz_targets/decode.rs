@@ -0,0 +1,43 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use microcvm_rs::MicroCvm;
+use microcvm_rs::cpu::RegisterWidth;
+use microcvm_rs::disasm::decode;
+
+// Enough to reach every kind of instruction, few enough that a run stays quick.
+const MAX_INSTRUCTIONS: u64 = 10_000;
+
+// The first byte picks the register width and the rest is the program, run from 0 on a
+// small machine. Every instruction in it has to come back the same through encode.
+fuzz_target!(|data: &[u8]| {
+    let Some((&mode, program)) = data.split_first() else {
+        return;
+    };
+    let width = match mode & 1 {
+        0 => RegisterWidth::Eight,
+        _ => RegisterWidth::Sixteen,
+    };
+
+    let mut offset = 0;
+    while offset < program.len() {
+        let Some(opcode) = decode(&program[offset..], width) else {
+            offset += 1;
+            continue;
+        };
+        let encoded = opcode.encode();
+        assert_eq!(encoded.len(), opcode.length() as usize);
+        assert_eq!(encoded, program[offset..offset + encoded.len()]);
+        assert_eq!(decode(&encoded, width), Some(opcode));
+        offset += encoded.len();
+    }
+
+    let mut vm = MicroCvm::builder()
+        .memory_size(4096)
+        .resolution(32, 24)
+        .register_width(width)
+        .build();
+    if vm.load_program(program).is_ok() {
+        let _ = vm.cpu_mut().run_with_limits(MAX_INSTRUCTIONS);
+    }
+});
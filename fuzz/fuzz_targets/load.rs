@@ -0,0 +1,20 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use microcvm_rs::MicroCvm;
+use microcvm_rs::program::{Program, ProgramHeader};
+
+// Program files, headers, compressed payloads and segment tables alike, loaded with and
+// without checking the payload against its checksum.
+fuzz_target!(|data: &[u8]| {
+    if let Ok((header, payload)) = ProgramHeader::split(data) {
+        let _ = header.verify(payload);
+        let _ = header.loaded_length(payload);
+    }
+    let _ = Program::parse(data);
+    let _ = Program::extent(data);
+    for verify in [true, false] {
+        let mut vm = MicroCvm::builder().verify_checksums(verify).build();
+        let _ = vm.load_program(data);
+    }
+});
@@ -0,0 +1,53 @@
+#![no_main]
+
+use libfuzzer_sys::arbitrary::Unstructured;
+use libfuzzer_sys::fuzz_target;
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble_instruction;
+use microcvm_rs::cpu::RegisterWidth;
+use microcvm_rs::disasm::{decode, format_instruction};
+use microcvm_rs::generate::{opcode, valid_program};
+use microcvm_rs::symbols::SymbolTable;
+
+const MAX_LEN: usize = 256;
+const MAX_INSTRUCTIONS: u64 = 10_000;
+
+// Builds instructions that are valid by construction rather than hoping random bytes decode,
+// and checks each comes back the same through encode and decode and through the disassembler
+// and assembler. Then a whole generated program has to run without panicking.
+fuzz_target!(|data: &[u8]| {
+    let mut u = Unstructured::new(data);
+    let Ok(wide) = u.arbitrary::<bool>() else {
+        return;
+    };
+    let width = if wide {
+        RegisterWidth::Sixteen
+    } else {
+        RegisterWidth::Eight
+    };
+
+    let address = u.arbitrary().unwrap_or(0);
+    let Ok(opcode) = opcode(&mut u, width) else {
+        return;
+    };
+    assert_eq!(decode(&opcode.encode(), width).as_ref(), Some(&opcode));
+    let text = format_instruction(&opcode, address, &SymbolTable::new());
+    assert_eq!(
+        assemble_instruction(&text, address, width).as_ref(),
+        Ok(&opcode),
+        "`{}`",
+        text
+    );
+
+    let Ok(program) = valid_program(&mut u, MAX_LEN, width) else {
+        return;
+    };
+    let mut vm = MicroCvm::builder()
+        .memory_size(4096)
+        .resolution(32, 24)
+        .register_width(width)
+        .build();
+    if vm.load_program(&program).is_ok() {
+        let _ = vm.cpu_mut().run_with_limits(MAX_INSTRUCTIONS);
+    }
+});
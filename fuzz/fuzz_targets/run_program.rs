@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use microcvm_rs::cpu::MicroCVMCpu;
+
+fuzz_target!(|data: &[u8]| {
+    let mut cpu = MicroCVMCpu::empty();
+    let len = data.len().min(cpu.memory().len());
+    cpu.memory_mut()[..len].copy_from_slice(&data[..len]);
+
+    let _ = cpu.run_with_limits(10_000);
+});
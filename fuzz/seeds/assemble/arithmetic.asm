@@ -0,0 +1,13 @@
+; Add, subtract, multiply and divide on 8-bit registers, wrapping past 255 and below 0.
+
+        mov r0, 200
+        add r0, 100             ; 300 wraps to 44
+        mov r1, 5
+        sub r1, 7               ; -2 wraps to 254
+        mov r2, 12
+        mul r2, 11
+        mov r3, 100
+        div r3, 7
+        mov r4, 3
+        sub r4, 3               ; 0, and Z
+        hlt
@@ -0,0 +1,49 @@
+; Bounces a 32x32 rectangle around a 256x256 screen.
+;
+; The position comes from a triangle-wave table indexed by a frame counter. There
+; are no indirect loads, so the low byte of each `load` address is patched with the
+; index before it runs; the table is page-aligned to make that work.
+
+        .equ CLEAR, 0x02
+        .equ FILLRECT, 0x04
+        .equ VSYNC, 0x05
+        .equ SIZE, 32
+
+frame:  inc r7                  ; r7 is the frame counter
+        store [load_x+2], r7
+load_x: load r0, [bounce]
+        mov r6, r7              ; y moves three times as fast as x
+        mul r6, 3
+        store [load_y+2], r6
+load_y: load r1, [bounce]
+
+        mov r2, SIZE
+        mov r3, SIZE
+        mov r4, r7              ; fade from blue to red and back
+        mov r5, 96
+        mov r6, 255
+        sub r6, r7
+
+        video CLEAR, r0
+        video FILLRECT, r0      ; x, y, w, h, r, g, b in r0..r6
+        video VSYNC, r0
+        jmp frame
+
+        .org 0x100
+bounce:
+        .db 0, 2, 4, 5, 7, 9, 10, 12, 14, 16, 18, 19, 21, 23, 24, 26
+        .db 28, 30, 32, 33, 35, 37, 38, 40, 42, 44, 46, 47, 49, 51, 52, 54
+        .db 56, 58, 60, 61, 63, 65, 66, 68, 70, 72, 74, 75, 77, 79, 80, 82
+        .db 84, 86, 88, 89, 91, 93, 94, 96, 98, 100, 102, 103, 105, 107, 108, 110
+        .db 112, 114, 116, 117, 119, 121, 122, 124, 126, 128, 130, 131, 133, 135, 136, 138
+        .db 140, 142, 144, 145, 147, 149, 150, 152, 154, 156, 158, 159, 161, 163, 164, 166
+        .db 168, 170, 172, 173, 175, 177, 178, 180, 182, 184, 186, 187, 189, 191, 192, 194
+        .db 196, 198, 200, 201, 203, 205, 206, 208, 210, 212, 214, 215, 217, 219, 220, 222
+        .db 224, 222, 220, 219, 217, 215, 214, 212, 210, 208, 206, 205, 203, 201, 200, 198
+        .db 196, 194, 192, 191, 189, 187, 186, 184, 182, 180, 178, 177, 175, 173, 172, 170
+        .db 168, 166, 164, 163, 161, 159, 158, 156, 154, 152, 150, 149, 147, 145, 144, 142
+        .db 140, 138, 136, 135, 133, 131, 130, 128, 126, 124, 122, 121, 119, 117, 116, 114
+        .db 112, 110, 108, 107, 105, 103, 102, 100, 98, 96, 94, 93, 91, 89, 88, 86
+        .db 84, 82, 80, 79, 77, 75, 74, 72, 70, 68, 66, 65, 63, 61, 60, 58
+        .db 56, 54, 52, 51, 49, 47, 46, 44, 42, 40, 38, 37, 35, 33, 32, 30
+        .db 28, 26, 24, 23, 21, 19, 18, 16, 14, 12, 10, 9, 7, 5, 4, 2
@@ -0,0 +1,18 @@
+; Nested calls come back where they were made from, and pushf and popf keep the carry
+; across them.
+
+        mov r0, 1
+        stc
+        pushf
+        call double
+        call double
+        popf
+        hlt
+
+double: add r0, r0              ; clears the carry
+        call one_more
+        ret
+
+one_more:
+        inc r0
+        ret
@@ -0,0 +1,155 @@
+; Shows the real-time clock as HH:MM:SS in 7-segment digits on a 256x256 screen.
+;
+; Each frame latches the time with a write to the RTC capture register, then draws
+; every digit segment by segment with `video fillrect`. The segment masks are looked
+; up by patching the low byte of a `load` address, so the table is page-aligned.
+
+        .equ CLEAR, 0x02
+        .equ FILLRECT, 0x04
+        .equ VSYNC, 0x05
+
+        .equ rtc_capture, 0xFF70
+        .equ rtc_seconds, 0xFF71
+        .equ rtc_minutes, 0xFF72
+        .equ rtc_hours, 0xFF73
+
+        .equ Y0, 107            ; top of the digits, each is 24x42
+
+frame:  store [rtc_capture], r0
+        video CLEAR, r0
+        load r0, [rtc_hours]
+        mov r1, 20
+        call draw_pair
+        load r0, [rtc_minutes]
+        mov r1, 92
+        call draw_pair
+        load r0, [rtc_seconds]
+        mov r1, 164
+        call draw_pair
+        mov r1, 83
+        call draw_colon
+        mov r1, 155
+        call draw_colon
+        video VSYNC, r0
+        jmp frame
+
+; Draws r0 as two decimal digits, the first at x = r1.
+draw_pair:
+        store [digit_x], r1
+        mov r2, r0
+        div r2, 10
+        store [tens], r2
+        mul r2, 10
+        sub r0, r2
+        store [ones], r0
+        load r0, [tens]
+        call draw_digit
+        load r1, [digit_x]
+        add r1, 30
+        store [digit_x], r1
+        load r0, [ones]
+        call draw_digit
+        ret
+
+; Draws the digit in r0 at x = [digit_x].
+draw_digit:
+        store [mask_load+2], r0
+mask_load:
+        load r0, [segments]
+        store [mask], r0
+        mov r5, 255             ; r5..r7 stay the colour for every segment
+        mov r6, 160
+        mov r7, 32
+        load r0, [mask]
+        btst r0, 0
+        jrz no_a
+        load r1, [digit_x]
+        add r1, 4
+        mov r2, Y0
+        mov r3, 16
+        mov r4, 4
+        video FILLRECT, r1
+no_a:
+        load r0, [mask]
+        btst r0, 1
+        jrz no_b
+        load r1, [digit_x]
+        add r1, 20
+        mov r2, Y0+4
+        mov r3, 4
+        mov r4, 16
+        video FILLRECT, r1
+no_b:
+        load r0, [mask]
+        btst r0, 2
+        jrz no_c
+        load r1, [digit_x]
+        add r1, 20
+        mov r2, Y0+22
+        mov r3, 4
+        mov r4, 16
+        video FILLRECT, r1
+no_c:
+        load r0, [mask]
+        btst r0, 3
+        jrz no_d
+        load r1, [digit_x]
+        add r1, 4
+        mov r2, Y0+38
+        mov r3, 16
+        mov r4, 4
+        video FILLRECT, r1
+no_d:
+        load r0, [mask]
+        btst r0, 4
+        jrz no_e
+        load r1, [digit_x]
+                mov r2, Y0+22
+        mov r3, 4
+        mov r4, 16
+        video FILLRECT, r1
+no_e:
+        load r0, [mask]
+        btst r0, 5
+        jrz no_f
+        load r1, [digit_x]
+                mov r2, Y0+4
+        mov r3, 4
+        mov r4, 16
+        video FILLRECT, r1
+no_f:
+        load r0, [mask]
+        btst r0, 6
+        jrz no_g
+        load r1, [digit_x]
+        add r1, 4
+        mov r2, Y0+19
+        mov r3, 16
+        mov r4, 4
+        video FILLRECT, r1
+no_g:
+        ret
+
+; Draws the two dots of a colon at x = r1.
+draw_colon:
+        mov r2, Y0+12
+        mov r3, 4
+        mov r4, 4
+        mov r5, 255
+        mov r6, 160
+        mov r7, 32
+        video FILLRECT, r1
+        mov r2, Y0+26
+        video FILLRECT, r1
+        ret
+
+        .org 0x0D00
+digit_x: .db 0
+tens:   .db 0
+ones:   .db 0
+mask:   .db 0
+
+; Bit 0 is the top segment, then clockwise, bit 6 is the middle one.
+        .org 0x0E00
+segments:
+        .db 0x3F, 0x06, 0x5B, 0x4F, 0x66, 0x6D, 0x7D, 0x07, 0x7F, 0x6F
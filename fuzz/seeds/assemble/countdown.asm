@@ -0,0 +1,11 @@
+; A counted delay loop. The inner `djnz` runs 255 times for each of the 200 outer
+; passes, so the whole program takes exactly 2 + 200 * 258 + 1 = 51603 instructions
+; and halts with r2 = 200.
+
+        mov r2, 0
+        mov r0, 200
+outer:  mov r1, 255
+inner:  djnz r1, inner
+        inc r2
+        djnz r0, outer
+        hlt
@@ -0,0 +1,6 @@
+; Dividing by 0 faults rather than halting.
+
+        mov r0, 7
+        mov r1, 0
+        div r0, r1
+        hlt
@@ -0,0 +1,20 @@
+; Draws a rectangle each frame for three frames, moving it right, then halts.
+
+        .equ CLEAR, 0x02
+        .equ FILLRECT, 0x04
+        .equ VSYNC, 0x05
+
+        mov r7, 3
+frame:  mov r0, r7              ; x, y, w, h, r, g, b in r0..r6
+        mul r0, 20
+        mov r1, 40
+        mov r2, 16
+        mov r3, 24
+        mov r4, 255
+        mov r5, 128
+        mov r6, 0
+        video CLEAR, r0
+        video FILLRECT, r0
+        video VSYNC, r0
+        djnz r7, frame
+        hlt
@@ -0,0 +1,18 @@
+; Sends a string through the serial port a byte at a time, up to the 0 on its end. There
+; are no indirect loads, so the low byte of the `load` address is patched with the index.
+
+        .equ SERIAL, 1
+
+        mov r1, 0
+next:   store [char+2], r1
+char:   load r0, [message]
+        test r0, r0
+        jrz done
+        hcall SERIAL
+        inc r1
+        jr next
+done:   hlt
+
+        .org 0x100
+message:
+        .db "Hello, serial!", 10, 0
@@ -0,0 +1,134 @@
+#ifndef MICROCVM_H
+#define MICROCVM_H
+
+/* Generated with cbindgen from src/ffi.rs, do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define MICROCVM_OK 0
+
+#define MICROCVM_HALTED 1
+
+#define MICROCVM_ERR_NULL -1
+
+#define MICROCVM_ERR_PANIC -2
+
+#define MICROCVM_ERR_INVALID_ARGUMENT -3
+
+#define MICROCVM_ERR_INVALID_OPCODE -10
+
+#define MICROCVM_ERR_INVALID_REGISTER -11
+
+#define MICROCVM_ERR_ADDRESS_OUT_OF_BOUNDS -12
+
+#define MICROCVM_ERR_UNREGISTERED_HCALL -13
+
+#define MICROCVM_ERR_DIVISION_BY_ZERO -14
+
+#define MICROCVM_ERR_WRITE_PROTECTED -15
+
+#define MICROCVM_ERR_INVALID_HEADER -16
+
+#define MICROCVM_ERR_PROGRAM_TOO_LARGE -17
+
+#define MICROCVM_ERR_INVALID_BIT_INDEX -18
+
+#define MICROCVM_ERR_RANGE_OUT_OF_BOUNDS -19
+
+#define MICROCVM_ERR_CALL_DEPTH_EXCEEDED -20
+
+#define MICROCVM_ERR_STACK_OVERFLOW -21
+
+#define MICROCVM_ERR_STACK_UNDERFLOW -22
+
+#define MICROCVM_ERR_INVALID_VECTOR -23
+
+#define MICROCVM_ERR_IO -24
+
+#define MICROCVM_ERR_CHECKSUM_MISMATCH -25
+
+#define MICROCVM_ERR_BAD_SEGMENT -26
+
+#define MICROCVM_ERR_BAD_ENTRY -27
+
+#define MICROCVM_ERR_WATCHDOG_EXPIRED -28
+
+#define MICROCVM_ERR_INVALID_VIDEO_OPCODE -29
+
+#define MICROCVM_ERR_EXECUTE_PROTECTED -30
+
+typedef struct MicroCvm MicroCvm;
+
+/**
+ * Creates a VM with empty memory. Returns null if allocation panicked.
+ */
+struct MicroCvm *microcvm_new(void);
+
+/**
+ * # Safety
+ * `vm` must be null or a pointer from `microcvm_new` that has not been freed yet.
+ */
+void microcvm_free(struct MicroCvm *vm);
+
+/**
+ * Copies `len` bytes from `bytes` into physical memory at `offset`.
+ *
+ * # Safety
+ * `vm` must come from `microcvm_new`, and `bytes` must point to `len` readable bytes.
+ */
+int microcvm_load(struct MicroCvm *vm, const uint8_t *bytes, size_t len, uint16_t offset);
+
+/**
+ * Executes one instruction. Returns MICROCVM_HALTED once the program has halted.
+ *
+ * # Safety
+ * `vm` must come from `microcvm_new`.
+ */
+int microcvm_step(struct MicroCvm *vm);
+
+/**
+ * Executes up to `max_instructions` instructions. Returns MICROCVM_OK if the limit was
+ * reached and MICROCVM_HALTED if the program halted first.
+ *
+ * # Safety
+ * `vm` must come from `microcvm_new`.
+ */
+int microcvm_run(struct MicroCvm *vm, uint64_t max_instructions);
+
+/**
+ * Writes register `index` (0-7) to `out`.
+ *
+ * # Safety
+ * `vm` must come from `microcvm_new`, and `out` must be writable.
+ */
+int microcvm_get_register(struct MicroCvm *vm, uint8_t index, uint8_t *out);
+
+/**
+ * Reads `len` bytes of guest memory starting at `addr`, as the program would see them.
+ *
+ * # Safety
+ * `vm` must come from `microcvm_new`, and `out` must point to `len` writable bytes.
+ */
+int microcvm_read_mem(struct MicroCvm *vm, uint16_t addr, uint8_t *out, size_t len);
+
+/**
+ * Returns the number of pixels in video memory, or 0 if `vm` is null.
+ *
+ * # Safety
+ * `vm` must be null or come from `microcvm_new`.
+ */
+size_t microcvm_framebuffer_len(const struct MicroCvm *vm);
+
+/**
+ * Copies `pixels` pixels of video memory starting at pixel `start` into `out` as RGBA.
+ *
+ * # Safety
+ * `vm` must come from `microcvm_new`, and `out` must point to `pixels * 4` writable bytes.
+ */
+int microcvm_read_framebuffer(struct MicroCvm *vm, size_t start, uint8_t *out, size_t pixels);
+
+#endif  /* MICROCVM_H */
@@ -0,0 +1,1141 @@
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Display;
+
+use crate::cpu::{
+    MicroCVMCpu, Opcode, OpcodeArg1, OpcodeArg2, OpcodeType, Register, RegisterWidth,
+    VideoOpcodeType,
+};
+use crate::error::VmError;
+use crate::fixed;
+use crate::isa::{self, OperandKind};
+use crate::segment::{self, Segment, SegmentKind, SegmentedProgram};
+use crate::stdlib;
+use crate::symbols::SymbolTable;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for AsmError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl core::error::Error for AsmError {}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Number(i64),
+    Symbol(String),
+    Sum(Box<Expr>, Box<Expr>),
+    Difference(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone)]
+enum Operand {
+    Register(Register),
+    Value(Expr),
+    Memory(Expr),
+    // `[rN+]`.
+    PostIncrement(Register),
+    // `[sp + offset]`, or `[sp]`.
+    Stack(Expr),
+}
+
+enum Statement {
+    Instruction(OpcodeType, Vec<Operand>),
+    Org(Expr),
+    Bytes(Vec<DataItem>),
+    Words(Vec<Expr>),
+    Equ(String, Expr),
+    Width(RegisterWidth),
+    Section(SegmentKind),
+    // Zeroes, or just the length in a bss section.
+    Space(Expr),
+    Entry(Expr),
+    Include(String),
+}
+
+enum DataItem {
+    Value(Expr),
+    Text(Vec<u8>),
+}
+
+struct Line {
+    // For an included line, the number of the `.include` that brought it in.
+    number: usize,
+    // Where an included line is in the files it came through, to go in front of its errors.
+    origin: String,
+    address: u16,
+    // Set by the last `.width` before the line, which decides how immediates encode.
+    width: RegisterWidth,
+    section: SegmentKind,
+    statement: Statement,
+}
+
+// Assembles a whole source file into a flat image starting at address 0. Gaps left by
+// `.org` are zero-filled, and so are bss sections; video sections and `.entry` need
+// `assemble_segments`.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    Assembler::new().assemble(source)
+}
+
+// Like `assemble`, also returning every label and its address for debuggers and the
+// disassembler. `.equ` constants are not addresses and are left out.
+pub fn assemble_with_symbols(source: &str) -> Result<(Vec<u8>, SymbolTable), AsmError> {
+    Assembler::new().assemble_with_symbols(source)
+}
+
+// Assembles a source file into a segmented program, a segment for each run of bytes in
+// one section. Code, data and bss sections share an address counter, which `.org` moves,
+// and video sections count from 0 in video memory on their own. The program starts at
+// `.entry`, or else at the first instruction in a code section.
+pub fn assemble_segments(source: &str) -> Result<SegmentedProgram, AsmError> {
+    Assembler::new().assemble_segments(source)
+}
+
+// Like `assemble_segments`, also returning the labels outside video sections, as
+// `assemble_with_symbols` does.
+pub fn assemble_segments_with_symbols(
+    source: &str,
+) -> Result<(SegmentedProgram, SymbolTable), AsmError> {
+    Assembler::new().assemble_segments_with_symbols(source)
+}
+
+// The source with what each line assembled to beside it: the line number, the address, with
+// a `v` in front in a video section, and the bytes, four to a row. `.space` shows how much it
+// reserves rather than its zeroes. An `.include` has the bytes of the whole file it included
+// beneath it. Anything `assemble_segments` accepts lists.
+pub fn listing(source: &str) -> Result<String, AsmError> {
+    Assembler::new().listing(source)
+}
+
+// Assembles like the functions above, which use `Assembler::new()`, with `.include` able to
+// reach files past the standard library. A name starting `std/` is always one of the modules
+// in `stdlib`; any other goes to the resolver, which returns the file's text, or an error
+// saying why there isn't one.
+pub struct Assembler {
+    resolver: Option<Box<Resolver>>,
+}
+
+pub type Resolver = dyn Fn(&str) -> Result<String, String>;
+
+impl Assembler {
+    // Only the standard library can be included.
+    pub fn new() -> Self {
+        Self { resolver: None }
+    }
+
+    pub fn resolver(mut self, resolver: Box<Resolver>) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
+
+    pub fn assemble(&self, source: &str) -> Result<Vec<u8>, AsmError> {
+        self.assemble_with_symbols(source).map(|(image, _)| image)
+    }
+
+    pub fn assemble_with_symbols(&self, source: &str) -> Result<(Vec<u8>, SymbolTable), AsmError> {
+        let mut symbols = BTreeMap::new();
+        let mut labels = Vec::new();
+        let lines = self.first_pass(source, &mut symbols, &mut labels)?;
+
+        let mut image = Vec::new();
+        for line in &lines {
+            if line.section == SegmentKind::Video || matches!(line.statement, Statement::Entry(_)) {
+                let message =
+                    "a flat image has no video sections or entry point, assemble it into segments";
+                return Err(located(error(line.number, message), &line.origin));
+            }
+            let bytes = emit(line, &symbols)?;
+            let start = line.address as usize;
+            if image.len() < start + bytes.len() {
+                image.resize(start + bytes.len(), 0);
+            }
+            image[start..start + bytes.len()].copy_from_slice(&bytes);
+        }
+        Ok((image, symbol_table(labels)))
+    }
+
+    pub fn assemble_segments(&self, source: &str) -> Result<SegmentedProgram, AsmError> {
+        self.assemble_segments_with_symbols(source)
+            .map(|(program, _)| program)
+    }
+
+    pub fn assemble_segments_with_symbols(
+        &self,
+        source: &str,
+    ) -> Result<(SegmentedProgram, SymbolTable), AsmError> {
+        let mut symbols = BTreeMap::new();
+        let mut labels = Vec::new();
+        let lines = self.first_pass(source, &mut symbols, &mut labels)?;
+
+        let mut program = SegmentedProgram::new(0);
+        program.entry = lines
+            .iter()
+            .find(|line| {
+                line.section == SegmentKind::Code
+                    && matches!(line.statement, Statement::Instruction(..))
+            })
+            .map_or(0, |line| line.address);
+        // The line each segment starts on, for pointing at overlaps
+        let mut starts = Vec::new();
+        for line in &lines {
+            if let Statement::Entry(expr) = &line.statement {
+                let entry = evaluate(expr, &symbols, line.number)
+                    .and_then(|entry| check_range(entry, 0, 0xFFFF, line.number))
+                    .map_err(|e| located(e, &line.origin))?;
+                program.entry = entry as u16;
+                continue;
+            }
+            let bytes = emit(line, &symbols)?;
+            if bytes.is_empty() {
+                continue;
+            }
+            let address = line.address as u32;
+            match program.segments.last_mut() {
+                Some(last) if last.kind == line.section && last.end() == address as u64 => {
+                    last.length += bytes.len() as u32;
+                    if line.section != SegmentKind::Bss {
+                        last.bytes.extend_from_slice(&bytes);
+                    }
+                }
+                _ => {
+                    program.segments.push(match line.section {
+                        SegmentKind::Bss => Segment::bss(address, bytes.len() as u32),
+                        kind => Segment::new(kind, address, bytes),
+                    });
+                    starts.push(line.number);
+                }
+            }
+        }
+
+        // Nothing the assembler emits runs past 64 KiB, so overlaps are all this can find
+        if let Err(VmError::SegmentOverlap { first, second, at }) =
+            segment::check_segments(&program.segments, usize::MAX, usize::MAX)
+        {
+            return Err(error(
+                starts[second],
+                format!(
+                    "output overlaps what line {} assembled, at {:#06x}",
+                    starts[first], at
+                ),
+            ));
+        }
+        Ok((program, symbol_table(labels)))
+    }
+
+    pub fn listing(&self, source: &str) -> Result<String, AsmError> {
+        const PER_ROW: usize = 4;
+
+        let mut symbols = BTreeMap::new();
+        let lines = self.first_pass(source, &mut symbols, &mut Vec::new())?;
+        let mut lines = lines.iter().peekable();
+        let mut listing = String::new();
+        for (index, text) in source.lines().enumerate() {
+            let number = index + 1;
+            // The source goes beside the first row only, however many lines an `.include`
+            // brought in.
+            let mut text = Some(text);
+            while let Some(line) = lines.next_if(|line| line.number == number) {
+                let prefix = if line.section == SegmentKind::Video {
+                    "v"
+                } else {
+                    ""
+                };
+                let address =
+                    |offset: usize| format!("{}{:04x}", prefix, line.address as usize + offset);
+                let bytes = emit(line, &symbols)?;
+                // `.entry`, which places nothing.
+                if bytes.is_empty() {
+                    continue;
+                }
+                if matches!(line.statement, Statement::Space(_)) {
+                    let reserved = format!("({} bytes)", bytes.len());
+                    let (number, text) = first_row(&mut text, number);
+                    push_row(&mut listing, number, &address(0), &reserved, text);
+                    continue;
+                }
+                for (row, chunk) in bytes.chunks(PER_ROW).enumerate() {
+                    let hex: Vec<String> =
+                        chunk.iter().map(|byte| format!("{:02x}", byte)).collect();
+                    let (number, text) = first_row(&mut text, number);
+                    push_row(
+                        &mut listing,
+                        number,
+                        &address(row * PER_ROW),
+                        &hex.join(" "),
+                        text,
+                    );
+                }
+            }
+            if let Some(text) = text {
+                push_row(&mut listing, number, "", "", text);
+            }
+        }
+        Ok(listing)
+    }
+
+    fn first_pass(
+        &self,
+        source: &str,
+        symbols: &mut BTreeMap<String, i64>,
+        labels: &mut Vec<(String, u32)>,
+    ) -> Result<Vec<Line>, AsmError> {
+        let mut pass = Pass {
+            assembler: self,
+            symbols,
+            labels,
+            lines: Vec::new(),
+            address: 0,
+            width: RegisterWidth::Eight,
+            section: SegmentKind::Code,
+            other_address: 0,
+            included: Vec::new(),
+        };
+        pass.file(source, None)?;
+        Ok(pass.lines)
+    }
+
+    fn resolve(&self, name: &str) -> Result<String, String> {
+        if name.starts_with(stdlib::PREFIX) {
+            return stdlib::module(name)
+                .map(String::from)
+                .ok_or_else(|| format!("the standard library has no `{}`", name));
+        }
+        match &self.resolver {
+            Some(resolver) => resolver(name),
+            None => Err(format!(
+                "only the standard library can be included, not `{}`",
+                name
+            )),
+        }
+    }
+}
+
+impl Default for Assembler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The line number and text for a row, the source line's own for its first row and nothing for
+// the rest.
+fn first_row<'a>(text: &mut Option<&'a str>, number: usize) -> (usize, &'a str) {
+    match text.take() {
+        Some(text) => (number, text),
+        None => (0, ""),
+    }
+}
+
+// A line number of 0 is a continuation row and leaves it out.
+fn push_row(listing: &mut String, number: usize, address: &str, bytes: &str, text: &str) {
+    let number = if number == 0 {
+        String::new()
+    } else {
+        number.to_string()
+    };
+    let row = format!("{:>5}  {:>5}  {:<11}  {}", number, address, bytes, text);
+    listing.push_str(row.trim_end());
+    listing.push('\n');
+}
+
+fn symbol_table(labels: Vec<(String, u32)>) -> SymbolTable {
+    let mut table = SymbolTable::new();
+    for (name, address) in labels {
+        // A label after the last byte of a full address space has nothing to name.
+        if let Ok(address) = u16::try_from(address) {
+            table.insert(&name, address);
+        }
+    }
+    table
+}
+
+// Assembles a single instruction with no labels available, as used by the REPL.
+// `address` is where it will be placed, which relative jump targets are measured from.
+pub fn assemble_instruction(
+    text: &str,
+    address: u16,
+    width: RegisterWidth,
+) -> Result<Opcode, AsmError> {
+    let symbols = BTreeMap::new();
+    match parse_statement(strip_comment(text).trim(), 1)? {
+        Some(Statement::Instruction(opcode_type, operands)) => {
+            build_opcode(opcode_type, &operands, address, width, &symbols, 1)
+        }
+        Some(_) => Err(error(1, "expected an instruction, found a directive")),
+        None => Err(error(1, "expected an instruction")),
+    }
+}
+
+// The first pass's state, which an included file carries on with where the `.include` was.
+struct Pass<'a> {
+    assembler: &'a Assembler,
+    symbols: &'a mut BTreeMap<String, i64>,
+    labels: &'a mut Vec<(String, u32)>,
+    lines: Vec<Line>,
+    address: u32,
+    width: RegisterWidth,
+    section: SegmentKind,
+    // The counter for whichever of memory and video memory `address` isn't counting.
+    other_address: u32,
+    // Every file included so far, by the name it was included as.
+    included: Vec<String>,
+}
+
+impl Pass<'_> {
+    // Every line of `source`. The lines of an included file, `name`, are numbered as the
+    // `.include` that brought it in, with where they are in it added to `origin`.
+    fn file(&mut self, source: &str, within: Option<(&str, usize, &str)>) -> Result<(), AsmError> {
+        for (index, raw) in source.lines().enumerate() {
+            let (number, origin) = match within {
+                Some((name, number, outer)) => (
+                    number,
+                    format!("{}in `{}` line {}: ", outer, name, index + 1),
+                ),
+                None => (index + 1, String::new()),
+            };
+            let include = self
+                .line(raw, number, &origin)
+                .map_err(|e| located(e, &origin))?;
+            if let Some(name) = include {
+                self.include(&name, number, &origin)?;
+            }
+        }
+        Ok(())
+    }
+
+    // A file is only included once, so modules can include what they use without clashing
+    // with another `.include` of it, and what it changes of the width and section ends with it.
+    fn include(&mut self, name: &str, number: usize, origin: &str) -> Result<(), AsmError> {
+        if self.included.iter().any(|included| included == name) {
+            return Ok(());
+        }
+        let source = self
+            .assembler
+            .resolve(name)
+            .map_err(|message| error(number, format!("{}{}", origin, message)))?;
+        self.included.push(name.to_string());
+        let (width, section) = (self.width, self.section);
+        self.file(&source, Some((name, number, origin)))?;
+        self.width = width;
+        self.set_section(section);
+        Ok(())
+    }
+
+    // The file a line names, if it's an `.include`, which the caller includes.
+    fn line(&mut self, raw: &str, number: usize, origin: &str) -> Result<Option<String>, AsmError> {
+        let mut text = strip_comment(raw).trim();
+
+        while let Some((label, rest)) = split_label(text) {
+            define(self.symbols, label, self.address as i64, number)?;
+            // Video offsets aren't addresses a debugger could show
+            if self.section != SegmentKind::Video {
+                self.labels.push((label.to_string(), self.address));
+            }
+            text = rest.trim();
+        }
+
+        let Some(statement) = parse_statement(text, number)? else {
+            return Ok(None);
+        };
+
+        let size = match &statement {
+            Statement::Instruction(opcode_type, operands) => {
+                instruction_length(*opcode_type, operands, self.width) as u32
+            }
+            Statement::Org(expr) => {
+                let target = evaluate(expr, self.symbols, number)?;
+                self.address = check_range(target, 0, 0xFFFF, number)? as u32;
+                return Ok(None);
+            }
+            Statement::Bytes(items) => items
+                .iter()
+                .map(|item| match item {
+                    DataItem::Value(_) => 1,
+                    DataItem::Text(text) => text.len() as u32,
+                })
+                .sum(),
+            Statement::Words(items) => items.len() as u32 * 2,
+            Statement::Equ(name, expr) => {
+                let value = evaluate(expr, self.symbols, number)?;
+                define(self.symbols, name, value, number)?;
+                return Ok(None);
+            }
+            Statement::Width(width) => {
+                self.width = *width;
+                return Ok(None);
+            }
+            Statement::Section(kind) => {
+                self.set_section(*kind);
+                return Ok(None);
+            }
+            Statement::Space(expr) => {
+                let size = evaluate(expr, self.symbols, number)?;
+                check_range(size, 0, 0x10000, number)? as u32
+            }
+            Statement::Entry(_) => 0,
+            Statement::Include(name) => return Ok(Some(name.clone())),
+        };
+        if self.section == SegmentKind::Bss && !matches!(statement, Statement::Space(_)) {
+            return Err(error(
+                number,
+                "a bss section only reserves space, with `.space`",
+            ));
+        }
+
+        if self.address + size > 0x10000 {
+            return Err(error(
+                number,
+                "program does not fit in the 64 KiB address space",
+            ));
+        }
+        self.lines.push(Line {
+            number,
+            origin: origin.to_string(),
+            address: self.address as u16,
+            width: self.width,
+            section: self.section,
+            statement,
+        });
+        self.address += size;
+        Ok(None)
+    }
+
+    fn set_section(&mut self, kind: SegmentKind) {
+        if (kind == SegmentKind::Video) != (self.section == SegmentKind::Video) {
+            core::mem::swap(&mut self.address, &mut self.other_address);
+        }
+        self.section = kind;
+    }
+}
+
+// Puts where an included line is in front of an error on it.
+fn located(error: AsmError, origin: &str) -> AsmError {
+    AsmError {
+        line: error.line,
+        message: format!("{}{}", origin, error.message),
+    }
+}
+
+fn emit(line: &Line, symbols: &BTreeMap<String, i64>) -> Result<Vec<u8>, AsmError> {
+    emit_statement(line, symbols).map_err(|e| located(e, &line.origin))
+}
+
+fn emit_statement(line: &Line, symbols: &BTreeMap<String, i64>) -> Result<Vec<u8>, AsmError> {
+    let number = line.number;
+    match &line.statement {
+        Statement::Instruction(opcode_type, operands) => {
+            let opcode = build_opcode(
+                *opcode_type,
+                operands,
+                line.address,
+                line.width,
+                symbols,
+                number,
+            )?;
+            Ok(opcode.encode())
+        }
+        Statement::Bytes(items) => {
+            let mut bytes = Vec::new();
+            for item in items {
+                match item {
+                    DataItem::Value(expr) => {
+                        let value = evaluate(expr, symbols, number)?;
+                        bytes.push(check_range(value, -128, 255, number)? as u8);
+                    }
+                    DataItem::Text(text) => bytes.extend_from_slice(text),
+                }
+            }
+            Ok(bytes)
+        }
+        Statement::Words(items) => {
+            let mut bytes = Vec::new();
+            for expr in items {
+                let value = evaluate(expr, symbols, number)?;
+                let word = check_range(value, -32768, 65535, number)? as u16;
+                bytes.extend_from_slice(&word.to_le_bytes());
+            }
+            Ok(bytes)
+        }
+        Statement::Space(expr) => {
+            let size = evaluate(expr, symbols, number)?;
+            Ok(vec![0; size as usize])
+        }
+        Statement::Org(_)
+        | Statement::Equ(..)
+        | Statement::Width(_)
+        | Statement::Section(_)
+        | Statement::Entry(_)
+        | Statement::Include(_) => Ok(Vec::new()),
+    }
+}
+
+fn build_opcode(
+    opcode_type: OpcodeType,
+    operands: &[Operand],
+    address: u16,
+    width: RegisterWidth,
+    symbols: &BTreeMap<String, i64>,
+    line: usize,
+) -> Result<Opcode, AsmError> {
+    let kinds = isa::info(opcode_type).operands;
+    if operands.len() != kinds.len() {
+        return Err(error(
+            line,
+            format!(
+                "`{}` takes {} operand(s), found {}",
+                opcode_type.mnemonic(),
+                kinds.len(),
+                operands.len()
+            ),
+        ));
+    }
+
+    let mut opcode = Opcode::empty();
+    opcode.opcode_type = opcode_type;
+    opcode.argument_count = MicroCVMCpu::get_opcode_argument_count(opcode_type);
+
+    let next_pc = address as i64 + instruction_length(opcode_type, operands, width) as i64;
+    for (index, (operand, kind)) in operands.iter().zip(kinds).enumerate() {
+        let resolved = resolve(operand, *kind, next_pc, width, symbols, line)?;
+        if index == 0 {
+            opcode.arg1 = Some(match resolved {
+                Resolved::Register(reg) => OpcodeArg1::Register(reg),
+                Resolved::Immediate(imm) => OpcodeArg1::Immediate(imm),
+                Resolved::WideImmediate(imm) => OpcodeArg1::Immediate(imm as u8),
+                Resolved::Address(addr) => OpcodeArg1::Address(addr),
+                Resolved::Offset(offset) => OpcodeArg1::Offset(offset),
+            });
+        } else {
+            opcode.arg2 = Some(match resolved {
+                Resolved::Register(reg) => OpcodeArg2::Register(reg),
+                Resolved::Immediate(imm) => OpcodeArg2::Immediate(imm),
+                Resolved::WideImmediate(imm) => OpcodeArg2::WideImmediate(imm),
+                Resolved::Address(addr) => OpcodeArg2::Address(addr),
+                Resolved::Offset(offset) => OpcodeArg2::Offset(offset),
+            });
+        }
+    }
+
+    Ok(opcode)
+}
+
+// With 16-bit registers a `reg, src` immediate takes two bytes instead of one.
+fn instruction_length(opcode_type: OpcodeType, operands: &[Operand], width: RegisterWidth) -> u16 {
+    let wide_immediate = width == RegisterWidth::Sixteen
+        && opcode_type.takes_source_operand()
+        && matches!(operands.get(1), Some(Operand::Value(_)));
+    MicroCVMCpu::get_opcode_length(opcode_type) + wide_immediate as u16
+}
+
+enum Resolved {
+    Register(Register),
+    Immediate(u8),
+    WideImmediate(u16),
+    Address(u16),
+    Offset(i8),
+}
+
+fn resolve(
+    operand: &Operand,
+    kind: OperandKind,
+    next_pc: i64,
+    width: RegisterWidth,
+    symbols: &BTreeMap<String, i64>,
+    line: usize,
+) -> Result<Resolved, AsmError> {
+    match (kind, operand) {
+        (OperandKind::Register | OperandKind::Source, Operand::Register(reg)) => {
+            Ok(Resolved::Register(*reg))
+        }
+        (OperandKind::Source, Operand::Value(expr)) if width == RegisterWidth::Sixteen => {
+            let value = evaluate(expr, symbols, line)?;
+            Ok(Resolved::WideImmediate(
+                check_range(value, -32768, 65535, line)? as u16,
+            ))
+        }
+        (OperandKind::Immediate | OperandKind::Source, Operand::Value(expr)) => {
+            let value = evaluate(expr, symbols, line)?;
+            Ok(Resolved::Immediate(
+                check_range(value, -128, 255, line)? as u8
+            ))
+        }
+        (OperandKind::Address, Operand::Value(expr) | Operand::Memory(expr)) => {
+            let value = evaluate(expr, symbols, line)?;
+            Ok(Resolved::Address(
+                check_range(value, 0, 0xFFFF, line)? as u16
+            ))
+        }
+        (OperandKind::Pair, Operand::Register(reg)) if (*reg as u8).is_multiple_of(2) => {
+            Ok(Resolved::Register(*reg))
+        }
+        (OperandKind::Pair, Operand::Register(reg)) => Err(error(
+            line,
+            format!(
+                "`{}` can't start a register pair, which takes an even register",
+                reg
+            ),
+        )),
+        (OperandKind::Pointer, Operand::PostIncrement(reg))
+            if width == RegisterWidth::Sixteen || (*reg as u8).is_multiple_of(2) =>
+        {
+            Ok(Resolved::Register(*reg))
+        }
+        (OperandKind::Pointer, Operand::PostIncrement(reg)) => Err(error(
+            line,
+            format!(
+                "with 8-bit registers a pointer is a register pair, and `{}` can't start one",
+                reg
+            ),
+        )),
+        (OperandKind::StackOffset, Operand::Stack(expr)) => {
+            let value = evaluate(expr, symbols, line)?;
+            Ok(Resolved::Immediate(check_range(value, 0, 255, line)? as u8))
+        }
+        (OperandKind::VideoOperation, Operand::Value(expr)) => {
+            // A symbol of the same name wins over an operation's name.
+            if let Expr::Symbol(name) = expr
+                && !symbols.contains_key(name)
+                && let Some(operation) = VideoOpcodeType::from_mnemonic(name)
+            {
+                return Ok(Resolved::Immediate(operation as u8));
+            }
+            let value = evaluate(expr, symbols, line)?;
+            let operation = check_range(value, 0, 255, line)? as u8;
+            match VideoOpcodeType::try_from(operation) {
+                Ok(_) => Ok(Resolved::Immediate(operation)),
+                Err(_) => Err(error(line, format!("{} is not a video operation", value))),
+            }
+        }
+        (OperandKind::Fixed, Operand::Value(expr)) => {
+            let value = evaluate(expr, symbols, line)?;
+            Ok(Resolved::WideImmediate(
+                check_range(value, -32768, 65535, line)? as u16,
+            ))
+        }
+        (OperandKind::Bit, Operand::Value(expr)) => {
+            let value = evaluate(expr, symbols, line)?;
+            let max = width.bits() as i64 - 1;
+            Ok(Resolved::Immediate(check_range(value, 0, max, line)? as u8))
+        }
+        (OperandKind::Offset, Operand::Value(expr)) => {
+            let target = evaluate(expr, symbols, line)?;
+            check_range(target, 0, 0xFFFF, line)?;
+            // Measured the way the pc wraps, so a jump can reach across the end of memory.
+            let offset = (target as u16).wrapping_sub(next_pc as u16) as i16 as i64;
+            if !(-128..=127).contains(&offset) {
+                return Err(error(
+                    line,
+                    format!(
+                        "jump target {:#06x} is {} bytes away, out of range for a relative jump (-128..=127)",
+                        target, offset
+                    ),
+                ));
+            }
+            Ok(Resolved::Offset(offset as i8))
+        }
+        (OperandKind::Register, _) => Err(error(line, "expected a register")),
+        (OperandKind::Immediate, _) => Err(error(line, "expected an immediate value")),
+        (OperandKind::Address, _) => Err(error(line, "expected an address")),
+        (OperandKind::Source, _) => Err(error(line, "expected a register or an immediate")),
+        (OperandKind::Offset, _) => Err(error(line, "expected a jump target")),
+        (OperandKind::Bit, _) => Err(error(line, "expected a bit index")),
+        (OperandKind::Pair, _) => Err(error(line, "expected a register pair")),
+        (OperandKind::Fixed, _) => Err(error(line, "expected a fixed-point value")),
+        (OperandKind::Pointer, _) => Err(error(line, "expected a `[rN+]` pointer")),
+        (OperandKind::StackOffset, _) => Err(error(line, "expected a `[sp + offset]` slot")),
+        (OperandKind::VideoOperation, _) => Err(error(line, "expected a video operation")),
+    }
+}
+
+fn parse_statement(text: &str, line: usize) -> Result<Option<Statement>, AsmError> {
+    if text.is_empty() {
+        return Ok(None);
+    }
+
+    let (head, rest) = match text.find(char::is_whitespace) {
+        Some(split) => (&text[..split], text[split..].trim()),
+        None => (text, ""),
+    };
+    let args = split_operands(rest, line)?;
+
+    if let Some(directive) = head.strip_prefix('.') {
+        return match directive.to_ascii_lowercase().as_str() {
+            "org" => Ok(Some(Statement::Org(single_expr(&args, ".org", line)?))),
+            "db" | "byte" => {
+                let items = args
+                    .iter()
+                    .map(|arg| parse_data_item(arg, line))
+                    .collect::<Result<_, _>>()?;
+                Ok(Some(Statement::Bytes(items)))
+            }
+            // A jump table is words like any other, named for what they hold.
+            "dw" | "word" | "jumptable" => {
+                let items = args
+                    .iter()
+                    .map(|arg| parse_expr(arg, line))
+                    .collect::<Result<_, _>>()?;
+                Ok(Some(Statement::Words(items)))
+            }
+            "equ" => {
+                let [name, value] = args.as_slice() else {
+                    return Err(error(line, "`.equ` takes a name and a value"));
+                };
+                if !is_identifier(name) {
+                    return Err(error(line, format!("invalid symbol name `{}`", name)));
+                }
+                Ok(Some(Statement::Equ(name.clone(), parse_expr(value, line)?)))
+            }
+            "section" => match args.as_slice() {
+                [name] => match name.to_ascii_lowercase().as_str() {
+                    "code" => Ok(Some(Statement::Section(SegmentKind::Code))),
+                    "data" => Ok(Some(Statement::Section(SegmentKind::Data))),
+                    "bss" => Ok(Some(Statement::Section(SegmentKind::Bss))),
+                    "video" => Ok(Some(Statement::Section(SegmentKind::Video))),
+                    _ => Err(error(line, format!("unknown section `{}`", name))),
+                },
+                _ => Err(error(line, "`.section` takes code, data, bss or video")),
+            },
+            "space" => Ok(Some(Statement::Space(single_expr(&args, ".space", line)?))),
+            "entry" => Ok(Some(Statement::Entry(single_expr(&args, ".entry", line)?))),
+            "include" => match args.as_slice() {
+                [name] if name.len() > 1 && name.starts_with('"') && name.ends_with('"') => Ok(
+                    Some(Statement::Include(name[1..name.len() - 1].to_string())),
+                ),
+                _ => Err(error(line, "`.include` takes a file name in quotes")),
+            },
+            "width" => match args.as_slice() {
+                [bits] if bits == "8" => Ok(Some(Statement::Width(RegisterWidth::Eight))),
+                [bits] if bits == "16" => Ok(Some(Statement::Width(RegisterWidth::Sixteen))),
+                _ => Err(error(line, "`.width` takes 8 or 16")),
+            },
+            _ => Err(error(line, format!("unknown directive `{}`", head))),
+        };
+    }
+
+    let Some(opcode_type) = OpcodeType::from_mnemonic(head) else {
+        return Err(error(line, format!("unknown instruction `{}`", head)));
+    };
+    let operands: Vec<Operand> = args
+        .iter()
+        .map(|arg| parse_operand(arg, line))
+        .collect::<Result<_, _>>()?;
+
+    // `load` and `store` through a `[rN+]` pointer or at `[sp + offset]` are instructions of
+    // their own.
+    let opcode_type = isa::variants(head)
+        .find(|info| {
+            info.operands.len() == operands.len()
+                && info
+                    .operands
+                    .iter()
+                    .zip(&operands)
+                    .all(|(kind, operand)| written_as(operand, *kind))
+        })
+        .map_or(opcode_type, |info| info.opcode);
+
+    Ok(Some(Statement::Instruction(opcode_type, operands)))
+}
+
+// Whether `operand` is written the way an operand of `kind` has to be, for telling apart the
+// instructions sharing a mnemonic.
+fn written_as(operand: &Operand, kind: OperandKind) -> bool {
+    matches!(operand, Operand::PostIncrement(_)) == (kind == OperandKind::Pointer)
+        && matches!(operand, Operand::Stack(_)) == (kind == OperandKind::StackOffset)
+}
+
+fn parse_operand(text: &str, line: usize) -> Result<Operand, AsmError> {
+    if let Some(reg) = parse_register(text) {
+        return Ok(Operand::Register(reg));
+    }
+    if let Some(inner) = text.strip_prefix('[') {
+        let Some(inner) = inner.strip_suffix(']') else {
+            return Err(error(line, format!("missing `]` in `{}`", text)));
+        };
+        if let Some(reg) = inner
+            .trim()
+            .strip_suffix('+')
+            .and_then(|pointer| parse_register(pointer.trim_end()))
+        {
+            return Ok(Operand::PostIncrement(reg));
+        }
+        if let Some(offset) = parse_stack_slot(inner.trim(), line)? {
+            return Ok(Operand::Stack(offset));
+        }
+        return Ok(Operand::Memory(parse_expr(inner.trim(), line)?));
+    }
+    let value = text.strip_prefix('#').unwrap_or(text);
+    Ok(Operand::Value(parse_expr(value.trim(), line)?))
+}
+
+// `sp` or `sp + offset`, the offset an expression of its own.
+fn parse_stack_slot(text: &str, line: usize) -> Result<Option<Expr>, AsmError> {
+    let Some(rest) = text
+        .get(..2)
+        .filter(|head| head.eq_ignore_ascii_case("sp"))
+        .map(|_| text[2..].trim_start())
+    else {
+        return Ok(None);
+    };
+    if rest.is_empty() {
+        return Ok(Some(Expr::Number(0)));
+    }
+    match rest.strip_prefix('+') {
+        Some(offset) => Ok(Some(parse_expr(offset, line)?)),
+        None => Ok(None),
+    }
+}
+
+pub fn parse_register(text: &str) -> Option<Register> {
+    Register::ALL
+        .into_iter()
+        .find(|reg| reg.name().eq_ignore_ascii_case(text))
+}
+
+fn parse_data_item(text: &str, line: usize) -> Result<DataItem, AsmError> {
+    match text.strip_prefix('"') {
+        Some(inner) => {
+            let Some(inner) = inner.strip_suffix('"') else {
+                return Err(error(line, "unterminated string"));
+            };
+            Ok(DataItem::Text(unescape(inner, line)?))
+        }
+        None => Ok(DataItem::Value(parse_expr(text, line)?)),
+    }
+}
+
+fn parse_expr(text: &str, line: usize) -> Result<Expr, AsmError> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(error(line, "expected a value"));
+    }
+
+    // Split at the last top-level `+` or `-` that isn't a sign, giving left associativity.
+    let bytes = text.as_bytes();
+    let mut quoted = false;
+    let mut depth = 0;
+    for index in (1..bytes.len()).rev() {
+        match bytes[index] {
+            b'\'' => quoted = !quoted,
+            b')' if !quoted => depth += 1,
+            b'(' if !quoted => depth -= 1,
+            b'+' | b'-' if !quoted && depth == 0 => {
+                let left = text[..index].trim_end();
+                if left.is_empty() || left.ends_with(['+', '-']) {
+                    continue;
+                }
+                let lhs = Box::new(parse_expr(left, line)?);
+                let rhs = Box::new(parse_expr(&text[index + 1..], line)?);
+                return Ok(if bytes[index] == b'+' {
+                    Expr::Sum(lhs, rhs)
+                } else {
+                    Expr::Difference(lhs, rhs)
+                });
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(value) = parse_number(text) {
+        return Ok(Expr::Number(value));
+    }
+    if let Some(value) = text
+        .strip_prefix("fx(")
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        return parse_fixed(value.trim(), line).map(Expr::Number);
+    }
+    if is_identifier(text) {
+        return Ok(Expr::Symbol(text.to_string()));
+    }
+    Err(error(line, format!("invalid value `{}`", text)))
+}
+
+// `fx(1.5)`, the 8.8 value nearest a decimal number, as the 16 bits that hold it.
+fn parse_fixed(text: &str, line: usize) -> Result<i64, AsmError> {
+    let value: f64 = text
+        .parse()
+        .map_err(|_| error(line, format!("invalid fixed-point value `{}`", text)))?;
+    match fixed::from_f64(value) {
+        Some(value) => Ok(value as i64),
+        None => Err(error(
+            line,
+            format!(
+                "fixed-point value {} out of range (-128 to {})",
+                text,
+                fixed::to_f64(i16::MAX)
+            ),
+        )),
+    }
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest.trim_start()),
+        None => (false, text),
+    };
+
+    let value = if let Some(hex) = digits.strip_prefix("0x").or(digits.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(bin) = digits.strip_prefix("0b").or(digits.strip_prefix("0B")) {
+        i64::from_str_radix(bin, 2).ok()?
+    } else if let Some(ch) = digits.strip_prefix('\'').and_then(|c| c.strip_suffix('\'')) {
+        let mut chars = ch.chars();
+        let c = chars.next()?;
+        if chars.next().is_some() || !c.is_ascii() {
+            return None;
+        }
+        c as i64
+    } else {
+        digits.parse().ok()?
+    };
+
+    Some(if negative { -value } else { value })
+}
+
+fn evaluate(expr: &Expr, symbols: &BTreeMap<String, i64>, line: usize) -> Result<i64, AsmError> {
+    match expr {
+        Expr::Number(value) => Ok(*value),
+        Expr::Symbol(name) => symbols
+            .get(name)
+            .copied()
+            .ok_or_else(|| error(line, format!("undefined symbol `{}`", name))),
+        Expr::Sum(lhs, rhs) => Ok(evaluate(lhs, symbols, line)? + evaluate(rhs, symbols, line)?),
+        Expr::Difference(lhs, rhs) => {
+            Ok(evaluate(lhs, symbols, line)? - evaluate(rhs, symbols, line)?)
+        }
+    }
+}
+
+fn single_expr(args: &[String], directive: &str, line: usize) -> Result<Expr, AsmError> {
+    match args {
+        [value] => parse_expr(value, line),
+        _ => Err(error(line, format!("`{}` takes one value", directive))),
+    }
+}
+
+fn define(
+    symbols: &mut BTreeMap<String, i64>,
+    name: &str,
+    value: i64,
+    line: usize,
+) -> Result<(), AsmError> {
+    if parse_register(name).is_some() {
+        return Err(error(line, format!("`{}` is a register name", name)));
+    }
+    if symbols.insert(name.to_string(), value).is_some() {
+        return Err(error(line, format!("symbol `{}` is defined twice", name)));
+    }
+    Ok(())
+}
+
+fn check_range(value: i64, min: i64, max: i64, line: usize) -> Result<i64, AsmError> {
+    if value < min || value > max {
+        return Err(error(
+            line,
+            format!("value {} is out of range ({}..={})", value, min, max),
+        ));
+    }
+    Ok(value)
+}
+
+fn split_label(text: &str) -> Option<(&str, &str)> {
+    let colon = text.find(':')?;
+    let label = text[..colon].trim();
+    is_identifier(label).then(|| (label, &text[colon + 1..]))
+}
+
+fn split_operands(text: &str, line: usize) -> Result<Vec<String>, AsmError> {
+    let mut operands = Vec::new();
+    let mut current = String::new();
+    let mut in_string = false;
+    let mut in_char = false;
+
+    for c in text.chars() {
+        match c {
+            '"' if !in_char => in_string = !in_string,
+            '\'' if !in_string => in_char = !in_char,
+            ',' if !in_string && !in_char => {
+                operands.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+
+    if in_string {
+        return Err(error(line, "unterminated string"));
+    }
+    if !current.trim().is_empty() || !operands.is_empty() {
+        operands.push(current.trim().to_string());
+    }
+    if operands.iter().any(String::is_empty) {
+        return Err(error(line, "empty operand"));
+    }
+
+    Ok(operands)
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut in_char = false;
+    for (index, c) in line.char_indices() {
+        match c {
+            '"' if !in_char => in_string = !in_string,
+            '\'' if !in_string => in_char = !in_char,
+            ';' if !in_string && !in_char => return &line[..index],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn unescape(text: &str, line: usize) -> Result<Vec<u8>, AsmError> {
+    let mut bytes = Vec::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        let c = match c {
+            '\\' => match chars.next() {
+                Some('n') => '\n',
+                Some('r') => '\r',
+                Some('t') => '\t',
+                Some('0') => '\0',
+                Some('\\') => '\\',
+                Some('"') => '"',
+                _ => return Err(error(line, "invalid escape sequence")),
+            },
+            c => c,
+        };
+        if !c.is_ascii() {
+            return Err(error(line, "strings may only contain ASCII characters"));
+        }
+        bytes.push(c as u8);
+    }
+    Ok(bytes)
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+fn error(line: usize, message: impl Into<String>) -> AsmError {
+    AsmError {
+        line,
+        message: message.into(),
+    }
+}
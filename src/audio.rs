@@ -0,0 +1,389 @@
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicU8, AtomicU16, AtomicU32, Ordering};
+
+// The divider counts ticks of this clock, so the tone frequency is AUDIO_CLOCK_HZ / divider.
+pub const AUDIO_CLOCK_HZ: u32 = 1_000_000;
+
+pub const AUDIO_DIVIDER_LO: u8 = 0x00;
+pub const AUDIO_DIVIDER_HI: u8 = 0x01;
+pub const AUDIO_VOLUME: u8 = 0x02;
+pub const AUDIO_GATE: u8 = 0x03;
+pub const AUDIO_REGISTER_COUNT: u8 = 4;
+
+#[derive(Debug, Default)]
+pub struct AudioRegisters {
+    pub divider: AtomicU16,
+    pub volume: AtomicU8,
+    pub gate: AtomicBool,
+    // Set by the host to silence playback, tone and PCM alike, without the guest seeing.
+    pub muted: AtomicBool,
+}
+
+impl AudioRegisters {
+    pub fn read(&self, offset: u8) -> u8 {
+        match offset {
+            AUDIO_DIVIDER_LO => self.divider.load(Ordering::Relaxed) as u8,
+            AUDIO_DIVIDER_HI => (self.divider.load(Ordering::Relaxed) >> 8) as u8,
+            AUDIO_VOLUME => self.volume.load(Ordering::Relaxed),
+            AUDIO_GATE => self.gate.load(Ordering::Relaxed) as u8,
+            _ => 0,
+        }
+    }
+
+    pub fn write(&self, offset: u8, value: u8) {
+        match offset {
+            AUDIO_DIVIDER_LO => {
+                let divider = self.divider.load(Ordering::Relaxed);
+                self.divider
+                    .store((divider & 0xFF00) | value as u16, Ordering::Relaxed);
+            }
+            AUDIO_DIVIDER_HI => {
+                let divider = self.divider.load(Ordering::Relaxed);
+                self.divider.store(
+                    (divider & 0x00FF) | ((value as u16) << 8),
+                    Ordering::Relaxed,
+                );
+            }
+            AUDIO_VOLUME => self.volume.store(value, Ordering::Relaxed),
+            AUDIO_GATE => self.gate.store(value & 1 != 0, Ordering::Relaxed),
+            _ => {}
+        }
+    }
+}
+
+pub struct SquareVoice {
+    registers: Arc<AudioRegisters>,
+    sample_rate: u32,
+    // Position inside the current period, measured in AUDIO_CLOCK_HZ * sample_rate units
+    // so that both the clock and the output rate advance it by whole numbers.
+    phase: u64,
+}
+
+impl SquareVoice {
+    pub fn new(registers: Arc<AudioRegisters>, sample_rate: u32) -> Self {
+        Self {
+            registers,
+            sample_rate,
+            phase: 0,
+        }
+    }
+
+    pub fn render_audio(&mut self, samples: &mut [f32]) {
+        // Registers are latched once per buffer, so changes land on the next callback.
+        let divider = self.registers.divider.load(Ordering::Relaxed);
+        let volume = self.registers.volume.load(Ordering::Relaxed);
+        let gate = self.registers.gate.load(Ordering::Relaxed);
+
+        if !gate || divider == 0 || volume == 0 {
+            samples.fill(0.0);
+            return;
+        }
+
+        let amplitude = volume as f32 / 255.0;
+        let period = divider as u64 * self.sample_rate as u64;
+        self.phase %= period;
+
+        for sample in samples {
+            *sample = if self.phase < period / 2 {
+                amplitude
+            } else {
+                -amplitude
+            };
+            self.phase = (self.phase + AUDIO_CLOCK_HZ as u64) % period;
+        }
+    }
+}
+
+pub const PCM_RING: u8 = 0x00; // 3 bytes: physical address of the ring of samples
+pub const PCM_SIZE: u8 = 0x03; // 2 bytes: length of the ring, at most PCM_RING_MAX
+pub const PCM_RATE: u8 = 0x05; // 2 bytes: samples per second, 0 is silent
+pub const PCM_WRITE: u8 = 0x07; // 2 bytes: offset the guest writes next. Storing the high byte hands the samples before it to the host
+pub const PCM_READ: u8 = 0x09; // 2 bytes: offset the host plays next
+pub const PCM_REMAINING: u8 = 0x0B; // 2 bytes: samples handed over and not played yet
+pub const PCM_CONTROL: u8 = 0x0D; // bit 0: play
+pub const PCM_STATUS: u8 = 0x0E; // bit 0: the ring ran dry while playing. Any write clears it
+pub const PCM_REGISTER_COUNT: u8 = 15;
+
+pub const PCM_PLAY: u8 = 0x01;
+pub const PCM_UNDERRUN: u8 = 0x01;
+
+pub const PCM_RING_MAX: usize = 8192;
+
+// Sampled sound, 8-bit unsigned PCM from a ring in guest memory. The audio thread can't see
+// guest memory, so handing samples over copies them into `samples`, a mirror of the ring at
+// the same offsets. The ring holds at most `size - 1` samples, read meeting write means empty.
+#[derive(Debug)]
+pub struct PcmRegisters {
+    pub ring: AtomicU32,
+    pub size: AtomicU16,
+    pub rate: AtomicU16,
+    pub write: AtomicU16,
+    // Where the last hand-over ended, the end of what the host may play.
+    pub committed: AtomicU16,
+    pub read: AtomicU16,
+    pub playing: AtomicBool,
+    pub underrun: AtomicBool,
+    pub samples: Box<[AtomicU8]>,
+}
+
+impl PcmRegisters {
+    pub fn new() -> Self {
+        Self {
+            ring: AtomicU32::new(0),
+            size: AtomicU16::new(0),
+            rate: AtomicU16::new(0),
+            write: AtomicU16::new(0),
+            committed: AtomicU16::new(0),
+            read: AtomicU16::new(0),
+            playing: AtomicBool::new(false),
+            underrun: AtomicBool::new(false),
+            samples: (0..PCM_RING_MAX).map(|_| AtomicU8::new(0)).collect(),
+        }
+    }
+
+    pub fn remaining(&self) -> u16 {
+        let size = self.size.load(Ordering::Relaxed);
+        if size == 0 {
+            return 0;
+        }
+        let committed = self.committed.load(Ordering::Acquire) % size;
+        let read = self.read.load(Ordering::Relaxed) % size;
+        (committed + size - read) % size
+    }
+
+    pub fn read(&self, offset: u8) -> u8 {
+        let word = |value: u16, byte: u8| value.to_le_bytes()[byte as usize];
+        match offset {
+            PCM_RING..PCM_SIZE => {
+                (self.ring.load(Ordering::Relaxed) >> ((offset - PCM_RING) * 8)) as u8
+            }
+            PCM_SIZE..PCM_RATE => word(self.size.load(Ordering::Relaxed), offset - PCM_SIZE),
+            PCM_RATE..PCM_WRITE => word(self.rate.load(Ordering::Relaxed), offset - PCM_RATE),
+            PCM_WRITE..PCM_READ => word(self.write.load(Ordering::Relaxed), offset - PCM_WRITE),
+            PCM_READ..PCM_REMAINING => word(self.read.load(Ordering::Relaxed), offset - PCM_READ),
+            PCM_REMAINING..PCM_CONTROL => word(self.remaining(), offset - PCM_REMAINING),
+            PCM_CONTROL => self.playing.load(Ordering::Relaxed) as u8 * PCM_PLAY,
+            PCM_STATUS => self.underrun.load(Ordering::Relaxed) as u8 * PCM_UNDERRUN,
+            _ => 0,
+        }
+    }
+
+    // Returns true when the store hands samples over, for the CPU to `commit` them.
+    pub fn write(&self, offset: u8, value: u8) -> bool {
+        let set_byte = |register: &AtomicU16, byte: u8| {
+            let mut bytes = register.load(Ordering::Relaxed).to_le_bytes();
+            bytes[byte as usize] = value;
+            register.store(u16::from_le_bytes(bytes), Ordering::Relaxed);
+        };
+        match offset {
+            PCM_RING..PCM_SIZE => {
+                let shift = (offset - PCM_RING) * 8;
+                let ring = self.ring.load(Ordering::Relaxed);
+                let ring = (ring & !(0xFF << shift)) | (value as u32) << shift;
+                self.ring.store(ring, Ordering::Relaxed);
+            }
+            PCM_SIZE..PCM_RATE => {
+                set_byte(&self.size, offset - PCM_SIZE);
+                let size = self.size.load(Ordering::Relaxed).min(PCM_RING_MAX as u16);
+                self.size.store(size, Ordering::Relaxed);
+            }
+            PCM_RATE..PCM_WRITE => set_byte(&self.rate, offset - PCM_RATE),
+            PCM_WRITE..PCM_READ => {
+                set_byte(&self.write, offset - PCM_WRITE);
+                return offset == PCM_WRITE + 1;
+            }
+            PCM_READ..PCM_REMAINING => set_byte(&self.read, offset - PCM_READ),
+            PCM_CONTROL => self.playing.store(value & PCM_PLAY != 0, Ordering::Relaxed),
+            PCM_STATUS => self.underrun.store(false, Ordering::Relaxed),
+            _ => {}
+        }
+        false
+    }
+
+    // Copies the ring from the last hand-over up to the write offset out of `memory` for
+    // the audio thread, and returns how many samples that was. Bytes past the end of
+    // `memory` read as silence.
+    pub fn commit(&self, memory: &[u8]) -> usize {
+        let size = self.size.load(Ordering::Relaxed) as usize;
+        if size == 0 {
+            return 0;
+        }
+        let ring = self.ring.load(Ordering::Relaxed) as usize;
+        let end = self.write.load(Ordering::Relaxed) as usize % size;
+        let mut offset = self.committed.load(Ordering::Relaxed) as usize % size;
+        let mut copied = 0;
+        while offset != end {
+            let sample = memory.get(ring + offset).copied().unwrap_or(0x80);
+            self.samples[offset].store(sample, Ordering::Relaxed);
+            offset = (offset + 1) % size;
+            copied += 1;
+        }
+        self.committed.store(end as u16, Ordering::Release);
+        copied
+    }
+}
+
+impl Default for PcmRegisters {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// What `mix_pcm` used up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PcmMix {
+    pub consumed: usize,
+    pub phase: u32,
+    pub underrun: bool,
+}
+
+// Adds `samples`, 8-bit unsigned PCM at `source_rate`, to `out` at `output_rate`, holding
+// each sample until the next one is due. `phase` is how far into the first sample the
+// previous call got, in 1/`output_rate` of a sample. Once `samples` runs out the rest of
+// `out` is left as it was, silent as far as this voice goes, and the mix reports an underrun.
+pub fn mix_pcm(
+    samples: &[u8],
+    source_rate: u32,
+    output_rate: u32,
+    phase: u32,
+    out: &mut [f32],
+) -> PcmMix {
+    if source_rate == 0 || output_rate == 0 {
+        return PcmMix {
+            consumed: 0,
+            phase,
+            underrun: false,
+        };
+    }
+    let (mut index, mut phase) = (0, phase as u64);
+    for sample in out {
+        let Some(&pcm) = samples.get(index) else {
+            return PcmMix {
+                consumed: samples.len(),
+                phase: 0,
+                underrun: true,
+            };
+        };
+        *sample = (*sample + (pcm as f32 - 128.0) / 128.0).clamp(-1.0, 1.0);
+        phase += source_rate as u64;
+        index += (phase / output_rate as u64) as usize;
+        phase %= output_rate as u64;
+    }
+    PcmMix {
+        consumed: index.min(samples.len()),
+        phase: phase as u32,
+        underrun: false,
+    }
+}
+
+pub struct PcmVoice {
+    registers: Arc<PcmRegisters>,
+    sample_rate: u32,
+    phase: u32,
+    pending: Vec<u8>,
+}
+
+impl PcmVoice {
+    pub fn new(registers: Arc<PcmRegisters>, sample_rate: u32) -> Self {
+        Self {
+            registers,
+            sample_rate,
+            phase: 0,
+            pending: Vec::new(),
+        }
+    }
+
+    // Mixes what the guest handed over into `samples` and moves the read offset past it.
+    pub fn render_audio(&mut self, samples: &mut [f32]) {
+        let registers = &self.registers;
+        let size = registers.size.load(Ordering::Relaxed);
+        if !registers.playing.load(Ordering::Relaxed) || size == 0 {
+            return;
+        }
+        let read = registers.read.load(Ordering::Relaxed) % size;
+        let remaining = registers.remaining();
+        self.pending.clear();
+        self.pending.extend(
+            (0..remaining)
+                .map(|i| registers.samples[((read + i) % size) as usize].load(Ordering::Relaxed)),
+        );
+        let rate = registers.rate.load(Ordering::Relaxed) as u32;
+        let mix = mix_pcm(&self.pending, rate, self.sample_rate, self.phase, samples);
+        self.phase = mix.phase;
+        let read = (read as usize + mix.consumed) % size as usize;
+        registers.read.store(read as u16, Ordering::Relaxed);
+        if mix.underrun {
+            registers.underrun.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(feature = "audio")]
+pub fn start_playback(
+    registers: Arc<AudioRegisters>,
+    pcm: Arc<PcmRegisters>,
+) -> Result<cpal::Stream, cpal::Error> {
+    use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+    let host = cpal::default_host();
+    let Some(device) = host.default_output_device() else {
+        return Err(cpal::Error::new(cpal::ErrorKind::DeviceNotAvailable));
+    };
+    let supported = device.default_output_config()?;
+    let config = supported.config();
+
+    let stream = match supported.sample_format() {
+        cpal::SampleFormat::F32 => build_stream::<f32>(&device, config, registers, pcm)?,
+        cpal::SampleFormat::I16 => build_stream::<i16>(&device, config, registers, pcm)?,
+        cpal::SampleFormat::U16 => build_stream::<u16>(&device, config, registers, pcm)?,
+        _ => return Err(cpal::Error::new(cpal::ErrorKind::UnsupportedConfig)),
+    };
+    stream.play()?;
+    log::debug!(
+        "playing audio at {} Hz, {} channels",
+        config.sample_rate,
+        config.channels
+    );
+
+    Ok(stream)
+}
+
+#[cfg(feature = "audio")]
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: cpal::StreamConfig,
+    registers: Arc<AudioRegisters>,
+    pcm: Arc<PcmRegisters>,
+) -> Result<cpal::Stream, cpal::Error>
+where
+    T: cpal::SizedSample + cpal::FromSample<f32>,
+{
+    use cpal::traits::DeviceTrait;
+
+    let channels = config.channels as usize;
+    let audio = registers.clone();
+    let mut voice = SquareVoice::new(registers, config.sample_rate);
+    let mut pcm = PcmVoice::new(pcm, config.sample_rate);
+    let mut mono = Vec::new();
+
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            mono.resize(data.len() / channels, 0.0);
+            // Muted, the PCM ring isn't played either, so it picks up where it left off.
+            if audio.muted.load(Ordering::Relaxed) {
+                mono.fill(0.0);
+            } else {
+                voice.render_audio(&mut mono);
+                pcm.render_audio(&mut mono);
+            }
+            for (frame, sample) in data.chunks_exact_mut(channels).zip(&mono) {
+                frame.fill(T::from_sample(*sample));
+            }
+        },
+        |err| log::error!("audio stream error: {}", err),
+        None,
+    )
+}
@@ -0,0 +1,195 @@
+use core::fmt::Display;
+use std::time::{Duration, Instant};
+
+use crate::asm::assemble;
+use crate::cpu::{MicroCVMCpu, RegisterWidth};
+use crate::profile::ProfileReport;
+
+// An endless loop of arithmetic whose values never overflow a register.
+pub const BENCH_PROGRAM: &[u8] = &[
+    0x06, 0x00, 0xC8, // mov r0, 200
+    0x04, 0x00, 0x64, // sub r0, 100
+    0x08, 0x00, 0x0A, // div r0, 10
+    0x09, 0x00, 0x0C, // mul r0, 12
+    0x03, 0x00, 0x64, // add r0, 100
+    0x06, 0x81, 0x00, // mov r1, r0
+    0x04, 0x81, 0x00, // sub r1, r0
+    0x05, 0x00, 0x00, // jmp 0x0000
+];
+
+// Loads and stores, mostly to RAM that no device covers, one to a device on the bus.
+pub const BENCH_MEMORY_PROGRAM: &[u8] = &[
+    0x06, 0x00, 0x2A, // mov r0, 42
+    0x02, 0x00, 0x20, 0x00, // store [0x2000], r0
+    0x01, 0x01, 0x00, 0x20, // load r1, [0x2000]
+    0x02, 0x01, 0x20, 0x01, // store [0x2001], r1
+    0x01, 0x02, 0x01, 0x20, // load r2, [0x2001]
+    0x01, 0x03, 0x30, 0xFF, // load r3, [0xFF30], the keyboard status
+    0x05, 0x00, 0x00, // jmp 0x0000
+];
+
+// The frame bench_frame_copy copies, 64x64 pixels of packed RGB.
+pub const COPY_BENCH_PIXELS: usize = 64 * 64;
+// Where the frame is in memory, and where the store loop finds video memory.
+pub const COPY_BENCH_SOURCE: u16 = 0x1000;
+pub const COPY_BENCH_WINDOW: u16 = 0x8000;
+
+// One frame copied with a guest loop of loads and stores through the framebuffer window,
+// two bytes at a time with 16-bit registers.
+const STORE_LOOP_COPY: &str = "
+        .width 16
+        mov r1, 0x1000
+        mov r2, 0x8000
+        mov r4, 6144
+copy:   load r6, [r1+]
+        store [r2+], r6
+        djnz r4, copy
+        hlt
+";
+
+// The same frame copied with one DMA transfer.
+const DMA_COPY: &str = "
+        mov r0, 0x10
+        store [0xFF21], r0
+        mov r0, 0x10
+        store [0xFF27], r0
+        mov r0, 1
+        store [0xFF29], r0
+        hlt
+";
+
+#[derive(Debug, Clone)]
+pub struct BenchReport {
+    pub instructions: u64,
+    pub elapsed: Duration,
+    pub profile: Option<ProfileReport>,
+}
+
+// What copying one frame cost, on average.
+#[derive(Debug, Clone)]
+pub struct CopyReport {
+    pub name: &'static str,
+    pub frames: u64,
+    pub instructions: u64,
+    pub cycles: u64,
+    pub elapsed: Duration,
+}
+
+impl CopyReport {
+    pub fn per_frame(&self) -> Duration {
+        self.elapsed / self.frames.max(1) as u32
+    }
+}
+
+impl Display for CopyReport {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let frames = self.frames.max(1);
+        write!(
+            f,
+            "{:<11} {:>8} instructions, {:>8} cycles, {:>10.2?} per frame",
+            self.name,
+            self.instructions / frames,
+            self.cycles / frames,
+            self.per_frame()
+        )
+    }
+}
+
+impl BenchReport {
+    pub fn instructions_per_second(&self) -> f64 {
+        self.instructions as f64 / self.elapsed.as_secs_f64()
+    }
+
+    pub fn ns_per_instruction(&self) -> f64 {
+        self.elapsed.as_nanos() as f64 / self.instructions as f64
+    }
+}
+
+impl Display for BenchReport {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        writeln!(f, "instructions:      {}", self.instructions)?;
+        writeln!(f, "elapsed:           {:.3?}", self.elapsed)?;
+        writeln!(
+            f,
+            "instructions/sec:  {:.0}",
+            self.instructions_per_second()
+        )?;
+        write!(f, "ns/instruction:    {:.2}", self.ns_per_instruction())?;
+        if let Some(profile) = &self.profile {
+            write!(f, "\n\n{}", profile)?;
+        }
+        Ok(())
+    }
+}
+
+impl MicroCVMCpu {
+    // Runs `program` from address 0 for exactly `iterations` instructions, restarting it
+    // whenever it halts or faults. Profiling slows the run down, so its timings are only
+    // useful relative to each other.
+    pub fn bench(
+        program: &[u8],
+        iterations: u64,
+        profile: bool,
+        decode_cache: bool,
+    ) -> BenchReport {
+        let mut cpu = MicroCVMCpu::empty();
+        cpu.load_raw(program, 0).unwrap();
+        cpu.set_decode_cache(decode_cache);
+        if profile {
+            cpu.enable_profiler();
+        }
+
+        let start = Instant::now();
+        for _ in 0..iterations {
+            if cpu.halted || cpu.execute_instruction().is_err() {
+                cpu.halted = false;
+                cpu.pc = 0;
+            }
+        }
+        let elapsed = start.elapsed();
+
+        BenchReport {
+            instructions: iterations,
+            elapsed,
+            profile: cpu.profiler().map(|profiler| profiler.report()),
+        }
+    }
+
+    // Copies a COPY_BENCH_PIXELS frame of packed RGB from memory into video memory `frames`
+    // times, with a guest store loop and then with DMA, which is what DMA is there to beat.
+    // Both leave the same pixels behind.
+    pub fn bench_frame_copy(frames: u64, decode_cache: bool) -> [CopyReport; 2] {
+        [("store loop", STORE_LOOP_COPY), ("dma", DMA_COPY)].map(|(name, source)| {
+            let mut cpu = MicroCVMCpu::empty();
+            cpu.set_decode_cache(decode_cache);
+            cpu.register_width = if name == "dma" {
+                RegisterWidth::Eight
+            } else {
+                RegisterWidth::Sixteen
+            };
+            let bytes = COPY_BENCH_PIXELS * 3;
+            cpu.map_framebuffer(COPY_BENCH_WINDOW, bytes as u16)
+                .unwrap();
+            cpu.load_raw(&assemble(source).unwrap(), 0).unwrap();
+            let frame: Vec<u8> = (0..bytes).map(|i| (i * 7 % 251) as u8).collect();
+            cpu.load_raw(&frame, COPY_BENCH_SOURCE).unwrap();
+
+            let (instructions, cycles) = (cpu.instructions, cpu.cycles);
+            let start = Instant::now();
+            for _ in 0..frames {
+                cpu.pc = 0;
+                cpu.halted = false;
+                while !cpu.halted {
+                    cpu.execute_instruction().unwrap();
+                }
+            }
+            CopyReport {
+                name,
+                frames,
+                instructions: cpu.instructions - instructions,
+                cycles: cpu.cycles - cycles,
+                elapsed: start.elapsed(),
+            }
+        })
+    }
+}
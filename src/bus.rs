@@ -0,0 +1,184 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::fmt::Display;
+
+// A memory-mapped device. Offsets are relative to the start of the range it is mapped at.
+pub trait Device: Any + Send {
+    // A load the program executes, which may change the device's state.
+    fn read(&mut self, offset: u16) -> u8;
+
+    fn write(&mut self, offset: u16, value: u8);
+
+    // The cycles that passed since the device was last ticked. The bus ticks a device just
+    // before each access rather than after every instruction, since the program can only see
+    // the device through its registers, and whenever the host calls `Bus::tick`.
+    fn tick(&mut self, _cycles: u64) {}
+
+    // What `read` would return, without changing anything, for debuggers and hosts looking
+    // at memory. Devices that don't implement it read as 0 to them.
+    fn peek(&self, _offset: u16) -> u8 {
+        0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusError {
+    // The range is empty or runs past the end of the address space.
+    BadRange { start: u16, len: u16 },
+    // The range covers addresses another device is already mapped at.
+    Overlap { start: u16, existing: u16 },
+}
+
+struct Mapping {
+    start: u16,
+    // Exclusive, so a range can end at the top of the address space.
+    end: u32,
+    ticked: u64,
+    device: Box<dyn Device>,
+}
+
+// The devices mapped into the address space, consulted by the CPU before RAM and the
+// built-in registers.
+pub struct Bus {
+    mappings: Vec<Mapping>,
+    // Whether any device covers an address in each 256-byte page, so an access no device
+    // covers costs a single lookup.
+    pages: [bool; 256],
+}
+
+impl Bus {
+    pub fn new() -> Self {
+        Self {
+            mappings: Vec::new(),
+            pages: [false; 256],
+        }
+    }
+
+    // Maps `device` at `len` addresses from `start`, refusing ranges that overlap one
+    // already mapped.
+    pub fn map(&mut self, start: u16, len: u16, device: Box<dyn Device>) -> Result<(), BusError> {
+        let end = start as u32 + len as u32;
+        if len == 0 || end > 0x1_0000 {
+            return Err(BusError::BadRange { start, len });
+        }
+        if let Some(existing) = self
+            .mappings
+            .iter()
+            .find(|mapping| (start as u32) < mapping.end && (mapping.start as u32) < end)
+        {
+            return Err(BusError::Overlap {
+                start,
+                existing: existing.start,
+            });
+        }
+        for page in start >> 8..=((end - 1) >> 8) as u16 {
+            self.pages[page as usize] = true;
+        }
+        self.mappings.push(Mapping {
+            start,
+            end,
+            ticked: 0,
+            device,
+        });
+        Ok(())
+    }
+
+    // The first mapped device of type `T`.
+    pub fn get<T: Device>(&self) -> Option<&T> {
+        self.mappings
+            .iter()
+            .find_map(|mapping| (&*mapping.device as &dyn Any).downcast_ref())
+    }
+
+    pub fn get_mut<T: Device>(&mut self) -> Option<&mut T> {
+        self.mappings
+            .iter_mut()
+            .find_map(|mapping| (&mut *mapping.device as &mut dyn Any).downcast_mut())
+    }
+
+    // Brings every device up to `cycles`.
+    pub fn tick(&mut self, cycles: u64) {
+        for mapping in &mut self.mappings {
+            mapping.catch_up(cycles);
+        }
+    }
+
+    // `cycles` is the CPU's cycle count, for ticking the device first.
+    #[inline]
+    pub fn read(&mut self, addr: u16, cycles: u64) -> Option<u8> {
+        if !self.pages[(addr >> 8) as usize] {
+            return None;
+        }
+        let mapping = self.mapping_mut(addr)?;
+        mapping.catch_up(cycles);
+        Some(mapping.device.read(addr - mapping.start))
+    }
+
+    // Returns false when no device is mapped at `addr`.
+    #[inline]
+    pub fn write(&mut self, addr: u16, value: u8, cycles: u64) -> bool {
+        if !self.pages[(addr >> 8) as usize] {
+            return false;
+        }
+        let Some(mapping) = self.mapping_mut(addr) else {
+            return false;
+        };
+        mapping.catch_up(cycles);
+        mapping.device.write(addr - mapping.start, value);
+        true
+    }
+
+    #[inline]
+    pub fn peek(&self, addr: u16) -> Option<u8> {
+        if !self.pages[(addr >> 8) as usize] {
+            return None;
+        }
+        let mapping = self
+            .mappings
+            .iter()
+            .find(|mapping| mapping.contains(addr))?;
+        Some(mapping.device.peek(addr - mapping.start))
+    }
+
+    fn mapping_mut(&mut self, addr: u16) -> Option<&mut Mapping> {
+        self.mappings
+            .iter_mut()
+            .find(|mapping| mapping.contains(addr))
+    }
+}
+
+impl Mapping {
+    fn contains(&self, addr: u16) -> bool {
+        addr >= self.start && (addr as u32) < self.end
+    }
+
+    fn catch_up(&mut self, cycles: u64) {
+        let elapsed = cycles.saturating_sub(self.ticked);
+        self.ticked = cycles;
+        if elapsed > 0 {
+            self.device.tick(elapsed);
+        }
+    }
+}
+
+impl Default for Bus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Display for BusError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            BusError::BadRange { start, len } => {
+                write!(f, "Cannot map {} bytes at {:#06x}", len, start)
+            }
+            BusError::Overlap { start, existing } => write!(
+                f,
+                "Device at {:#06x} overlaps the device mapped at {:#06x}",
+                start, existing
+            ),
+        }
+    }
+}
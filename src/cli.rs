@@ -0,0 +1,736 @@
+use std::path::{Path, PathBuf};
+
+use microcvm_rs::config::{Config, DEFAULT_FILE};
+use microcvm_rs::cpu::RegisterWidth;
+use microcvm_rs::crt::CrtEffect;
+use microcvm_rs::demo::{DEMO_HEIGHT, DEMO_WIDTH, FRAMEBUFFER_DEMO_WINDOW};
+use microcvm_rs::input::{RepeatRate, RepeatUnit};
+use microcvm_rs::letterbox::Letterbox;
+use microcvm_rs::record::{RecordConfig, RecordFormat};
+use microcvm_rs::types::Color;
+use microcvm_rs::vm::CYCLES_PER_FRAME;
+
+pub const USAGE: &str = "\
+Usage: microcvm <command> [options]
+
+Commands:
+  run <file>        Run a program image (MCVM header or raw bytes)
+  run --demo        Run the built-in bouncing rectangle demo
+  run --demo clock  Run the built-in real-time clock demo
+  run --demo framebuffer
+                    Run the built-in demo that draws through the framebuffer window
+  run --demo tilemap
+                    Run the built-in demo that scrolls a tile map
+  monitor <file>    Step through a program in the terminal, with the run options
+                    (needs the `tui` feature)
+  repl              Assemble and execute instructions interactively
+  inspect <dump>    Look at a core dump in the REPL, read-only, from the faulting instruction
+  isa [--json]      Print the instruction set table
+  config --print-default
+                    Print a config file with every setting commented out at its default
+  asm <file>        Assemble a source file, with the assemble options
+  disasm <file>     Disassemble a program file, with the disassemble options
+
+Assemble options:
+  -o <file>                 Write the program to file (default the source with .bin, .mcvm or
+                            .hex in place of its extension)
+  --format <format>         raw (default) for a flat image from 0, header for an MCVM program
+                            file with an entry point and any video sections, or ihex for
+                            Intel HEX
+  --symbols <file>          Also write the labels to a symbol file
+  --listing <file>          Also write the source beside each line's address and bytes
+  -I <dir>                  Look for `.include` files in dir too, after the source file's own
+                            directory (may repeat)
+
+Disassemble options:
+  --org <addr>              Disassemble as if loaded at addr, rather than at 0 or where the
+                            header loads it
+  --range <start>:<end>     Only disassemble from start up to but not including end
+  --symbols <file>          Name addresses from a symbol file, as well as the `L_` labels made
+                            up for jump targets
+  --bytes                   Show each instruction's bytes beside its address
+  --raw                     Treat the file as raw bytes even if it starts with a header
+  --register-width <bits>   Decode for 8-bit (default) or 16-bit registers
+
+Run options:
+  --config <file>           Read settings from a TOML file before the options, rather than
+                            from ./microcvm.toml if there is one
+  --width <n>               Framebuffer width in pixels (default 384)
+  --height <n>              Framebuffer height in pixels (default 288)
+  --scale <n>               Open the window n logical pixels per VM pixel (default 2)
+  --monitor <n>             Open the window centred on monitor n, counting from 0, rather than
+                            the primary one
+  --position <x>,<y>        Open the window with its top left corner at x, y in desktop pixels
+  --headless                Run without opening a window and print the halt reason
+  --max-instructions <n>    Stop after executing n instructions
+  --cycles-per-frame <n>    Run the machine for n cycles each frame (default 500000)
+  --trace                   Print every executed instruction to stderr
+  --symbols <file>          Name addresses in the trace from a symbol file
+  --entry <addr>            Start executing at addr instead of 0
+  --register-width <bits>   Run with 8-bit (default) or 16-bit registers
+  --nvram <file>            Keep the 256 bytes at 0x3F00 in file from one run to the next
+  --core-dump <file>        Write the machine state to file if the program faults
+  --profile-out <file>      Profile a headless run and write the statistics to file as JSON
+                            (needs the `json` feature)
+  --no-decode-cache         Decode every instruction each time it runs
+  --no-verify               Load programs whose payload doesn't match the header checksum
+  --framebuffer-window <addr>
+                            Map 16 KiB of video memory into the address space at addr
+  --crt                     Darken alternate window lines like a CRT, F2 toggles it
+  --crt-dim <percent>       How much darker those lines are (default 30), implies --crt
+  --crt-smear               Smear pixels slightly to the right, implies --crt
+  --letterbox <rrggbb>      Fill the window around the frame with this color (default 000000)
+  --border <rrggbb>         Draw a 1-pixel border of this color around the frame
+  --present-mode <mode>     vsync (default) never tears, mailbox cuts latency without tearing
+                            and immediate cuts it most but can tear
+  --gpu-backend <backend>   Draw through vulkan, gl, metal or dx12 rather than the platform's
+                            choice (auto)
+  --gpu <adapter>           Draw on a low-power or high-performance GPU, or the first one
+                            whose name contains adapter
+  --record <dir>            Write every frame to dir as frame-000000.png and on, F4 pauses and
+                            resumes it (and starts it in `frames` without this option)
+  --record-every <n>        Record every nth frame rather than every one
+  --record-format <format>  png (default), or raw RGBA with an index.txt of sizes
+  --debugger                Open a second window with registers, disassembly and memory
+                            that steps, continues and sets breakpoints
+  --key-repeat <delay>,<interval>
+                            Repeat held keys after delay, then every interval, in
+                            milliseconds or with an `f` after both in frames (default 500,33)
+  --no-key-repeat           Send each key press to the program once however long it is held
+  --pause-in-background     Pause and mute the program while its window doesn't have focus
+  --relative-mouse          Capture the pointer and report motion rather than position, as
+                            if the program had asked; Escape lets go
+  --keymap <file>           Change the scancodes keys send with `KeyName = scancode` lines
+
+Other options:
+  --bench [--profile] [--no-decode-cache]
+                            Measure interpreter throughput and exit, optionally with a
+                            per-opcode and per-pc profile
+  --self-test               Run the built-in instruction self-test and exit
+  -h, --help                Print this help";
+
+pub enum Command {
+    Help,
+    Bench { profile: bool, decode_cache: bool },
+    SelfTest,
+    Repl,
+    Inspect { dump: String },
+    Isa { json: bool },
+    PrintConfig,
+    Asm(AsmOptions),
+    Disasm(DisasmOptions),
+    Run(Box<RunOptions>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsmFormat {
+    Raw,
+    Header,
+    Ihex,
+}
+
+pub struct AsmOptions {
+    pub input: String,
+    pub output: PathBuf,
+    pub format: AsmFormat,
+    pub symbols: Option<String>,
+    pub listing: Option<String>,
+    // Where `.include` looks after the source file's own directory, in order.
+    pub include_dirs: Vec<PathBuf>,
+}
+
+pub enum Source {
+    File(String),
+    Demo,
+    ClockDemo,
+    FramebufferDemo,
+    TilemapDemo,
+}
+
+pub struct RunOptions {
+    pub source: Source,
+    pub width: u32,
+    pub height: u32,
+    pub scale: u32,
+    pub window_monitor: Option<usize>,
+    pub window_position: Option<(i32, i32)>,
+    pub headless: bool,
+    pub max_instructions: Option<u64>,
+    pub cycles_per_frame: u64,
+    pub trace: bool,
+    pub symbols: Option<String>,
+    pub entry: Option<u16>,
+    pub nvram: Option<String>,
+    pub core_dump: Option<String>,
+    pub profile_out: Option<String>,
+    pub decode_cache: bool,
+    pub verify: bool,
+    pub framebuffer_window: Option<u16>,
+    pub register_width: RegisterWidth,
+    pub crt: CrtEffect,
+    pub crt_enabled: bool,
+    pub debugger: bool,
+    pub letterbox: Letterbox,
+    pub present_mode: Option<String>,
+    pub gpu_backend: Option<String>,
+    pub gpu_adapter: Option<String>,
+    pub record: RecordConfig,
+    // Whether frames are recorded from the start, rather than once F4 is pressed.
+    pub recording: bool,
+    pub key_repeat: Option<RepeatRate>,
+    pub keymap: Option<String>,
+    pub relative_mouse: bool,
+    pub pause_on_focus_loss: bool,
+    // Set by the monitor command rather than an option.
+    pub monitor: bool,
+}
+
+pub struct DisasmOptions {
+    pub input: String,
+    pub origin: Option<u16>,
+    // Start and exclusive end.
+    pub range: Option<(u16, u32)>,
+    pub symbols: Option<String>,
+    pub bytes: bool,
+    pub raw: bool,
+    pub register_width: RegisterWidth,
+}
+
+impl AsmFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            AsmFormat::Raw => "raw",
+            AsmFormat::Header => "header",
+            AsmFormat::Ihex => "ihex",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [AsmFormat::Raw, AsmFormat::Header, AsmFormat::Ihex]
+            .into_iter()
+            .find(|format| format.name() == name)
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            AsmFormat::Raw => "bin",
+            AsmFormat::Header => "mcvm",
+            AsmFormat::Ihex => "hex",
+        }
+    }
+}
+
+impl RunOptions {
+    pub fn new(source: Source) -> Self {
+        Self {
+            source,
+            width: 384,
+            height: 288,
+            scale: 2,
+            window_monitor: None,
+            window_position: None,
+            headless: false,
+            max_instructions: None,
+            cycles_per_frame: CYCLES_PER_FRAME,
+            trace: false,
+            symbols: None,
+            entry: None,
+            nvram: None,
+            core_dump: None,
+            profile_out: None,
+            decode_cache: true,
+            verify: true,
+            framebuffer_window: None,
+            register_width: RegisterWidth::Eight,
+            crt: CrtEffect::new(),
+            crt_enabled: false,
+            debugger: false,
+            letterbox: Letterbox::new(),
+            present_mode: None,
+            gpu_backend: None,
+            gpu_adapter: None,
+            record: RecordConfig::new(),
+            recording: false,
+            key_repeat: Some(RepeatRate::new()),
+            keymap: None,
+            relative_mouse: false,
+            pause_on_focus_loss: false,
+            monitor: false,
+        }
+    }
+}
+
+pub fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let Some(command) = args.next() else {
+        return Err(String::from("missing command"));
+    };
+
+    match command.as_str() {
+        "-h" | "--help" | "help" => Ok(Command::Help),
+        "--bench" => {
+            let (mut profile, mut decode_cache) = (false, true);
+            for arg in args {
+                match arg.as_str() {
+                    "--profile" => profile = true,
+                    "--no-decode-cache" => decode_cache = false,
+                    _ => return Err(format!("unexpected argument `{}`", arg)),
+                }
+            }
+            Ok(Command::Bench {
+                profile,
+                decode_cache,
+            })
+        }
+        "--self-test" => Ok(Command::SelfTest),
+        "run" => parse_run(args).map(|options| Command::Run(Box::new(options))),
+        "monitor" => parse_run(args).map(|options| {
+            Command::Run(Box::new(RunOptions {
+                monitor: true,
+                ..options
+            }))
+        }),
+        "isa" => match args.next().as_deref() {
+            None => Ok(Command::Isa { json: false }),
+            Some("--json") => Ok(Command::Isa { json: true }),
+            Some(arg) => Err(format!("unexpected argument `{}`", arg)),
+        },
+        "repl" => match args.next() {
+            Some(arg) => Err(format!("unexpected argument `{}`", arg)),
+            None => Ok(Command::Repl),
+        },
+        "asm" => parse_asm(args).map(Command::Asm),
+        "config" => match args.next().as_deref() {
+            Some("--print-default") => Ok(Command::PrintConfig),
+            Some(arg) => Err(format!("unexpected argument `{}`", arg)),
+            None => Err(String::from("`config` needs `--print-default`")),
+        },
+        "disasm" => parse_disasm(args).map(Command::Disasm),
+        "inspect" => match (args.next(), args.next()) {
+            (Some(dump), None) => Ok(Command::Inspect { dump }),
+            (None, _) => Err(String::from("`inspect` needs a core dump file")),
+            (Some(_), Some(arg)) => Err(format!("unexpected argument `{}`", arg)),
+        },
+        other => Err(format!("unknown command `{}`", other)),
+    }
+}
+
+fn parse_run(args: impl Iterator<Item = String>) -> Result<RunOptions, String> {
+    let mut args: Vec<String> = args.collect();
+    let mut file = None;
+    let mut demo = None;
+    let mut options = RunOptions::new(Source::Demo);
+    // The file goes first, so the options after it win.
+    let config = match args.iter().position(|arg| arg == "--config") {
+        Some(index) if index + 1 < args.len() => {
+            let path = args.remove(index + 1);
+            args.remove(index);
+            Some(PathBuf::from(path))
+        }
+        Some(_) => return Err(String::from("`--config` needs a value")),
+        None => Some(PathBuf::from(DEFAULT_FILE)).filter(|path| path.exists()),
+    };
+    let mut resolution_set = false;
+    if let Some(path) = config {
+        let config = read_config(&path)?;
+        resolution_set = config.width.is_some() || config.height.is_some();
+        apply_config(&mut options, config, path.parent().unwrap_or(Path::new("")));
+    }
+    let mut args = args.into_iter().peekable();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--width" => {
+                options.width = parse_value(&arg, args.next())?;
+                resolution_set = true;
+            }
+            "--height" => {
+                options.height = parse_value(&arg, args.next())?;
+                resolution_set = true;
+            }
+            "--demo" => {
+                demo = Some(
+                    match args.next_if(|name| {
+                        matches!(
+                            name.as_str(),
+                            "clock" | "framebuffer" | "tilemap" | "bounce"
+                        )
+                    }) {
+                        Some(name) if name == "clock" => Source::ClockDemo,
+                        Some(name) if name == "framebuffer" => Source::FramebufferDemo,
+                        Some(name) if name == "tilemap" => Source::TilemapDemo,
+                        _ => Source::Demo,
+                    },
+                )
+            }
+            "--scale" => options.scale = parse_value(&arg, args.next())?,
+            "--monitor" => options.window_monitor = Some(parse_value(&arg, args.next())?),
+            "--position" => options.window_position = Some(parse_position(&arg, args.next())?),
+            "--max-instructions" => {
+                options.max_instructions = Some(parse_value(&arg, args.next())?)
+            }
+            "--cycles-per-frame" => options.cycles_per_frame = parse_value(&arg, args.next())?,
+            "--entry" => options.entry = Some(parse_value(&arg, args.next())?),
+            "--framebuffer-window" => {
+                options.framebuffer_window = Some(parse_value(&arg, args.next())?)
+            }
+            "--register-width" => options.register_width = parse_register_width(&arg, args.next())?,
+            "--crt" => options.crt_enabled = true,
+            "--crt-dim" => {
+                options.crt.scanline_dim = parse_value(&arg, args.next())?;
+                options.crt_enabled = true;
+            }
+            "--crt-smear" => {
+                options.crt.smear = true;
+                options.crt_enabled = true;
+            }
+            "--debugger" => options.debugger = true,
+            "--letterbox" => options.letterbox.color = parse_color(&arg, args.next())?,
+            "--border" => options.letterbox.border = Some(parse_color(&arg, args.next())?),
+            "--key-repeat" => options.key_repeat = Some(parse_repeat_rate(&arg, args.next())?),
+            "--no-key-repeat" => options.key_repeat = None,
+            "--relative-mouse" => options.relative_mouse = true,
+            "--pause-in-background" => options.pause_on_focus_loss = true,
+            "--headless" => options.headless = true,
+            "--trace" => options.trace = true,
+            "--no-decode-cache" => options.decode_cache = false,
+            "--no-verify" => options.verify = false,
+            "--symbols" => match args.next() {
+                Some(file) => options.symbols = Some(file),
+                None => return Err(String::from("`--symbols` needs a value")),
+            },
+            "--nvram" => match args.next() {
+                Some(file) => options.nvram = Some(file),
+                None => return Err(String::from("`--nvram` needs a value")),
+            },
+            "--present-mode" => match args.next() {
+                Some(mode) => options.present_mode = Some(mode),
+                None => return Err(String::from("`--present-mode` needs a value")),
+            },
+            "--gpu-backend" => match args.next() {
+                Some(backend) => options.gpu_backend = Some(backend),
+                None => return Err(String::from("`--gpu-backend` needs a value")),
+            },
+            "--gpu" => match args.next() {
+                Some(adapter) => options.gpu_adapter = Some(adapter),
+                None => return Err(String::from("`--gpu` needs a value")),
+            },
+            "--record" => match args.next() {
+                Some(dir) => {
+                    options.record.dir = PathBuf::from(dir);
+                    options.recording = true;
+                }
+                None => return Err(String::from("`--record` needs a value")),
+            },
+            "--record-every" => options.record.every = parse_value(&arg, args.next())?,
+            "--record-format" => match args.next() {
+                Some(name) => match RecordFormat::from_name(&name) {
+                    Some(format) => options.record.format = format,
+                    None => {
+                        return Err(format!(
+                            "invalid value `{}` for `{}`, expected png or raw",
+                            name, arg
+                        ));
+                    }
+                },
+                None => return Err(format!("`{}` needs a value", arg)),
+            },
+            "--keymap" => match args.next() {
+                Some(file) => options.keymap = Some(file),
+                None => return Err(String::from("`--keymap` needs a value")),
+            },
+            "--core-dump" => match args.next() {
+                Some(file) => options.core_dump = Some(file),
+                None => return Err(String::from("`--core-dump` needs a value")),
+            },
+            "--profile-out" => match args.next() {
+                Some(file) => options.profile_out = Some(file),
+                None => return Err(String::from("`--profile-out` needs a value")),
+            },
+            flag if flag.starts_with('-') => return Err(format!("unknown option `{}`", flag)),
+            _ if file.is_some() => return Err(format!("unexpected argument `{}`", arg)),
+            _ => file = Some(arg),
+        }
+    }
+
+    let is_demo = demo.is_some();
+    if matches!(demo, Some(Source::FramebufferDemo)) && options.framebuffer_window.is_none() {
+        options.framebuffer_window = Some(FRAMEBUFFER_DEMO_WINDOW);
+    }
+    options.source = match (file, demo) {
+        (Some(file), None) => Source::File(file),
+        (None, Some(demo)) => demo,
+        (Some(_), Some(_)) => return Err(String::from("`--demo` does not take a program file")),
+        (None, None) => return Err(String::from("`run` needs a program file or `--demo`")),
+    };
+    // The demos are drawn for a 256x256 screen.
+    if is_demo && !resolution_set {
+        options.width = DEMO_WIDTH;
+        options.height = DEMO_HEIGHT;
+    }
+    if options.crt.scanline_dim > 100 {
+        return Err(String::from("--crt-dim must be at most 100"));
+    }
+    if options.window_monitor.is_some() && options.window_position.is_some() {
+        return Err(String::from(
+            "`--monitor` and `--position` can't be used together",
+        ));
+    }
+    if options.profile_out.is_some() && !options.headless {
+        return Err(String::from("`--profile-out` needs `--headless`"));
+    }
+    if options.cycles_per_frame == 0 {
+        return Err(String::from("--cycles-per-frame must be nonzero"));
+    }
+    if options.record.every == 0 {
+        return Err(String::from("--record-every must be nonzero"));
+    }
+    if options.width == 0 || options.height == 0 || options.scale == 0 {
+        return Err(String::from(
+            "--width, --height and --scale must be nonzero",
+        ));
+    }
+    Ok(options)
+}
+
+fn read_config(path: &Path) -> Result<Config, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("could not read `{}`: {}", path.display(), e))?;
+    Config::parse(&text).map_err(|e| format!("`{}` {}", path.display(), e))
+}
+
+// Puts what the file sets in place of the defaults. Paths in it are relative to `dir`, where
+// the file is.
+fn apply_config(options: &mut RunOptions, config: Config, dir: &Path) {
+    let path = |file: String| dir.join(file).to_string_lossy().into_owned();
+    let Config {
+        width,
+        height,
+        max_instructions,
+        cycles_per_frame,
+        register_width,
+        decode_cache,
+        verify_checksums,
+        core_dump,
+        scale,
+        present_mode,
+        letterbox,
+        border,
+        crt,
+        keymap,
+        pause_in_background,
+        nvram,
+        framebuffer_window,
+        relative_mouse,
+        trace,
+        symbols,
+    } = config;
+    options.width = width.unwrap_or(options.width);
+    options.height = height.unwrap_or(options.height);
+    options.max_instructions = max_instructions.or(options.max_instructions);
+    options.cycles_per_frame = cycles_per_frame.unwrap_or(options.cycles_per_frame);
+    options.register_width = register_width.unwrap_or(options.register_width);
+    options.decode_cache = decode_cache.unwrap_or(options.decode_cache);
+    options.verify = verify_checksums.unwrap_or(options.verify);
+    options.core_dump = core_dump.map(path).or(options.core_dump.take());
+    options.scale = scale.unwrap_or(options.scale);
+    options.present_mode = present_mode.or(options.present_mode.take());
+    options.letterbox.color = letterbox.unwrap_or(options.letterbox.color);
+    options.letterbox.border = border.or(options.letterbox.border);
+    options.crt_enabled = crt.unwrap_or(options.crt_enabled);
+    options.keymap = keymap.map(path).or(options.keymap.take());
+    options.pause_on_focus_loss = pause_in_background.unwrap_or(options.pause_on_focus_loss);
+    options.nvram = nvram.map(path).or(options.nvram.take());
+    options.framebuffer_window = framebuffer_window.or(options.framebuffer_window);
+    options.relative_mouse = relative_mouse.unwrap_or(options.relative_mouse);
+    options.trace = trace.unwrap_or(options.trace);
+    options.symbols = symbols.map(path).or(options.symbols.take());
+}
+
+fn parse_asm(mut args: impl Iterator<Item = String>) -> Result<AsmOptions, String> {
+    let (mut input, mut output, mut symbols, mut listing) = (None, None, None, None);
+    let mut format = AsmFormat::Raw;
+    let mut include_dirs = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => match args.next() {
+                Some(file) => output = Some(PathBuf::from(file)),
+                None => return Err(String::from("`-o` needs a value")),
+            },
+            "--format" => match args.next() {
+                Some(name) => match AsmFormat::from_name(&name) {
+                    Some(chosen) => format = chosen,
+                    None => {
+                        return Err(format!(
+                            "invalid value `{}` for `{}`, expected raw, header or ihex",
+                            name, arg
+                        ));
+                    }
+                },
+                None => return Err(format!("`{}` needs a value", arg)),
+            },
+            "--symbols" => match args.next() {
+                Some(file) => symbols = Some(file),
+                None => return Err(String::from("`--symbols` needs a value")),
+            },
+            "--listing" => match args.next() {
+                Some(file) => listing = Some(file),
+                None => return Err(String::from("`--listing` needs a value")),
+            },
+            "-I" => match args.next() {
+                Some(dir) => include_dirs.push(PathBuf::from(dir)),
+                None => return Err(String::from("`-I` needs a value")),
+            },
+            flag if flag.starts_with('-') => return Err(format!("unknown option `{}`", flag)),
+            _ if input.is_some() => return Err(format!("unexpected argument `{}`", arg)),
+            _ => input = Some(arg),
+        }
+    }
+
+    let Some(input) = input else {
+        return Err(String::from("`asm` needs a source file"));
+    };
+    let output = output.unwrap_or_else(|| Path::new(&input).with_extension(format.extension()));
+    if output == Path::new(&input) {
+        return Err(format!(
+            "the output would overwrite `{}`, choose another with `-o`",
+            input
+        ));
+    }
+    Ok(AsmOptions {
+        input,
+        output,
+        format,
+        symbols,
+        listing,
+        include_dirs,
+    })
+}
+
+fn parse_disasm(mut args: impl Iterator<Item = String>) -> Result<DisasmOptions, String> {
+    let mut input = None;
+    let mut options = DisasmOptions {
+        input: String::new(),
+        origin: None,
+        range: None,
+        symbols: None,
+        bytes: false,
+        raw: false,
+        register_width: RegisterWidth::Eight,
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--org" => options.origin = Some(parse_value(&arg, args.next())?),
+            "--range" => options.range = Some(parse_range(&arg, args.next())?),
+            "--symbols" => match args.next() {
+                Some(file) => options.symbols = Some(file),
+                None => return Err(String::from("`--symbols` needs a value")),
+            },
+            "--bytes" => options.bytes = true,
+            "--raw" => options.raw = true,
+            "--register-width" => options.register_width = parse_register_width(&arg, args.next())?,
+            flag if flag.starts_with('-') => return Err(format!("unknown option `{}`", flag)),
+            _ if input.is_some() => return Err(format!("unexpected argument `{}`", arg)),
+            _ => input = Some(arg),
+        }
+    }
+    let Some(input) = input else {
+        return Err(String::from("`disasm` needs a program file"));
+    };
+    options.input = input;
+    Ok(options)
+}
+
+// `start:end`, with end up to 0x10000 to reach the last byte.
+fn parse_range(flag: &str, value: Option<String>) -> Result<(u16, u32), String> {
+    let Some(value) = value else {
+        return Err(format!("`{}` needs a value", flag));
+    };
+    let invalid = || {
+        format!(
+            "invalid value `{}` for `{}`, expected start:end",
+            value, flag
+        )
+    };
+    let (start, end) = value.split_once(':').ok_or_else(invalid)?;
+    let start: u16 = parse_value(flag, Some(String::from(start))).map_err(|_| invalid())?;
+    let end: u32 = parse_value(flag, Some(String::from(end))).map_err(|_| invalid())?;
+    if end > 0x10000 || end <= start as u32 {
+        return Err(format!(
+            "the end of `{}` must be past its start and at most 0x10000",
+            flag
+        ));
+    }
+    Ok((start, end))
+}
+
+fn parse_register_width(flag: &str, value: Option<String>) -> Result<RegisterWidth, String> {
+    match value.as_deref() {
+        Some("8") => Ok(RegisterWidth::Eight),
+        Some("16") => Ok(RegisterWidth::Sixteen),
+        Some(value) => Err(format!("invalid value `{}` for `{}`", value, flag)),
+        None => Err(format!("`{}` needs a value", flag)),
+    }
+}
+
+// `500,33` in milliseconds or `30f,2f` in frames.
+fn parse_repeat_rate(flag: &str, value: Option<String>) -> Result<RepeatRate, String> {
+    let Some(value) = value else {
+        return Err(format!("`{}` needs a value", flag));
+    };
+    let invalid = || format!("invalid value `{}` for `{}`", value, flag);
+    let (delay, interval) = value.split_once(',').ok_or_else(invalid)?;
+    let (delay, interval, unit) = match (delay.strip_suffix('f'), interval.strip_suffix('f')) {
+        (Some(delay), Some(interval)) => (delay, interval, RepeatUnit::Frames),
+        (None, None) => (delay, interval, RepeatUnit::Milliseconds),
+        _ => return Err(invalid()),
+    };
+    let (Ok(delay), Ok(interval)) = (delay.parse(), interval.parse()) else {
+        return Err(invalid());
+    };
+    if interval == 0 {
+        return Err(format!("the interval of `{}` must be nonzero", flag));
+    }
+    Ok(RepeatRate {
+        delay,
+        interval,
+        unit,
+    })
+}
+
+// `x,y`, either of which may be negative for a monitor left of or above the primary one.
+fn parse_position(flag: &str, value: Option<String>) -> Result<(i32, i32), String> {
+    let Some(value) = value else {
+        return Err(format!("`{}` needs a value", flag));
+    };
+    let position = value
+        .split_once(',')
+        .and_then(|(x, y)| Some((x.trim().parse().ok()?, y.trim().parse().ok()?)));
+    position.ok_or_else(|| format!("invalid value `{}` for `{}`, expected x,y", value, flag))
+}
+
+// `rrggbb` in hex, with or without a leading `#`.
+fn parse_color(flag: &str, value: Option<String>) -> Result<Color, String> {
+    let Some(value) = value else {
+        return Err(format!("`{}` needs a value", flag));
+    };
+    Color::from_hex(&value)
+        .ok_or_else(|| format!("invalid color `{}` for `{}`, expected rrggbb", value, flag))
+}
+
+fn parse_value<T: TryFrom<u64>>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let Some(value) = value else {
+        return Err(format!("`{}` needs a value", flag));
+    };
+    let parsed = match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+
+    parsed
+        .ok()
+        .and_then(|n| T::try_from(n).ok())
+        .ok_or_else(|| format!("invalid value `{}` for `{}`", value, flag))
+}
@@ -0,0 +1,401 @@
+use std::ops::{Range, RangeInclusive};
+
+use toml::de::{DeTable, DeValue};
+
+use crate::cpu::RegisterWidth;
+use crate::types::Color;
+
+// The name `run` looks for in the working directory when it isn't given `--config`.
+pub const DEFAULT_FILE: &str = "microcvm.toml";
+
+// Every key, under the table it goes in.
+const SECTIONS: [(&str, &[&str]); 4] = [
+    (
+        "vm",
+        &[
+            "width",
+            "height",
+            "max_instructions",
+            "cycles_per_frame",
+            "register_width",
+            "decode_cache",
+            "verify_checksums",
+            "core_dump",
+        ],
+    ),
+    (
+        "window",
+        &[
+            "scale",
+            "present_mode",
+            "letterbox",
+            "border",
+            "crt",
+            "keymap",
+            "pause_in_background",
+        ],
+    ),
+    (
+        "devices",
+        &["nvram", "framebuffer_window", "relative_mouse"],
+    ),
+    ("trace", &["enabled", "symbols"]),
+];
+
+/// A template with every setting commented out at its default, for `microcvm config
+/// --print-default`. It parses to an empty [`Config`], and uncommenting any line keeps it
+/// valid:
+///
+/// ```
+/// use microcvm_rs::config::{Config, TEMPLATE};
+///
+/// assert_eq!(Config::parse(TEMPLATE), Ok(Config::new()));
+///
+/// let uncommented: String = TEMPLATE
+///     .lines()
+///     .map(|line| line.strip_prefix("# ").filter(|line| line.contains(" = ")).unwrap_or(line))
+///     .map(|line| format!("{}\n", line))
+///     .collect();
+/// let config = Config::parse(&uncommented).unwrap();
+/// assert_eq!((config.width, config.height, config.scale), (Some(384), Some(288), Some(2)));
+/// assert_eq!(config.cycles_per_frame, Some(microcvm_rs::vm::CYCLES_PER_FRAME));
+/// ```
+pub const TEMPLATE: &str = r#"# Settings for `microcvm run` and `microcvm monitor`, read from ./microcvm.toml or the file
+# given with --config. Options on the command line win over these, and these over the
+# defaults shown. Uncomment a line to change it. Paths are relative to this file.
+
+[vm]
+# Framebuffer size in pixels.
+# width = 384
+# height = 288
+# Stop after this many instructions, as --max-instructions does. There is no limit by default.
+# max_instructions = 1000000
+# How many cycles the machine runs for each frame it presents, 60 times a second.
+# cycles_per_frame = 500000
+# 8 or 16.
+# register_width = 8
+# Cache decoded instructions, which only ever matters for measuring the interpreter.
+# decode_cache = true
+# Refuse programs whose payload doesn't match the checksum in their header.
+# verify_checksums = true
+# Write the machine state to this file if the program faults.
+# core_dump = "crash.dump"
+
+[window]
+# Logical window pixels per VM pixel.
+# scale = 2
+# vsync, mailbox or immediate.
+# present_mode = "vsync"
+# What fills the window around the frame, and an optional border just outside it, as rrggbb.
+# letterbox = "000000"
+# border = "404040"
+# Darken alternate lines like a CRT. F2 toggles it.
+# crt = false
+# A file changing the scancodes keys send, as --keymap reads.
+# keymap = "keys.txt"
+# Pause and mute the program while the window doesn't have focus.
+# pause_in_background = false
+
+[devices]
+# Keep the 256 bytes at 0x3F00 in this file from one run to the next.
+# nvram = "game.nv"
+# Map 16 KiB of video memory into the address space here.
+# framebuffer_window = 0x8000
+# Report mouse motion rather than position from the start.
+# relative_mouse = false
+
+[trace]
+# Print every executed instruction to stderr.
+# enabled = false
+# Name addresses in the trace from this symbol file.
+# symbols = "game.sym"
+"#;
+
+// What a config file sets for `run`, None for whatever it leaves out.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub max_instructions: Option<u64>,
+    pub cycles_per_frame: Option<u64>,
+    pub register_width: Option<RegisterWidth>,
+    pub decode_cache: Option<bool>,
+    pub verify_checksums: Option<bool>,
+    pub core_dump: Option<String>,
+    pub scale: Option<u32>,
+    pub present_mode: Option<String>,
+    pub letterbox: Option<Color>,
+    pub border: Option<Color>,
+    pub crt: Option<bool>,
+    pub keymap: Option<String>,
+    pub pause_in_background: Option<bool>,
+    pub nvram: Option<String>,
+    pub framebuffer_window: Option<u16>,
+    pub relative_mouse: Option<bool>,
+    pub trace: Option<bool>,
+    pub symbols: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads a config file's text. Anything it doesn't know or can't use is an error naming
+    /// the line, with a guess at what was meant for a misspelled key:
+    ///
+    /// ```
+    /// use microcvm_rs::config::Config;
+    ///
+    /// let config = Config::parse("
+    ///     [vm]
+    ///     width = 320
+    ///     cycles_per_frame = 250_000
+    ///     [window]
+    ///     letterbox = '#102030'
+    /// ").unwrap();
+    /// assert_eq!(config.width, Some(320));
+    /// assert_eq!(config.cycles_per_frame, Some(250_000));
+    /// assert_eq!(config.letterbox.map(|color| color.to_rgba()), Some([0x10, 0x20, 0x30, 0xFF]));
+    /// assert_eq!(config.height, None);
+    ///
+    /// let error = |text| Config::parse(text).unwrap_err().to_string();
+    /// assert_eq!(
+    ///     error("[window]\nscale = 2\nscalefactor = 3"),
+    ///     "line 3: unknown key `scalefactor` in [window], did you mean `scale`?"
+    /// );
+    /// assert_eq!(
+    ///     error("[window]\nmax_instructions = 5"),
+    ///     "line 2: `max_instructions` goes in [vm], not [window]"
+    /// );
+    /// assert_eq!(
+    ///     error("[devcies]\nnvram = 'a.nv'"),
+    ///     "line 1: unknown table [devcies], did you mean [devices]?"
+    /// );
+    /// assert_eq!(
+    ///     error("[vm]\nwidth = 0"),
+    ///     "line 2: `width` must be a whole number from 1 to 4294967295"
+    /// );
+    /// assert_eq!(error("[vm]\nregister_width = 32"), "line 2: `register_width` must be 8 or 16");
+    /// assert_eq!(error("[trace]\nenabled = 'yes'"), "line 2: `enabled` must be true or false");
+    /// assert_eq!(error("scale = 2"), "line 1: `scale` goes in [window]");
+    /// assert!(error("[vm]\nwidth = ").starts_with("line 2: "));
+    /// ```
+    pub fn parse(text: &str) -> Result<Self, ConfigError> {
+        let root = DeTable::parse(text).map_err(|e| ConfigError {
+            line: e.span().map_or(1, |span| line_of(text, span)),
+            message: e.message().to_string(),
+        })?;
+        let mut config = Config::new();
+        for (name, table) in in_file_order(root.get_ref()) {
+            let (name, name_span) = (name.get_ref().as_ref(), name.span());
+            let line = line_of(text, name_span);
+            let Some(&(section, keys)) = SECTIONS.iter().find(|(section, _)| *section == name)
+            else {
+                if let Some(section) = section_of(name) {
+                    return Err(error(line, format!("`{}` goes in [{}]", name, section)));
+                }
+                let names = SECTIONS.map(|(section, _)| section);
+                return Err(error(
+                    line,
+                    match closest(name, &names) {
+                        Some(guess) => {
+                            format!("unknown table [{}], did you mean [{}]?", name, guess)
+                        }
+                        None => format!("unknown table [{}]", name),
+                    },
+                ));
+            };
+            let DeValue::Table(table) = table.get_ref() else {
+                return Err(error(line, format!("[{}] must be a table", name)));
+            };
+            for (key, value) in in_file_order(table) {
+                let line = line_of(text, key.span());
+                let key = key.get_ref().as_ref();
+                if !keys.contains(&key) {
+                    return Err(error(
+                        line,
+                        match (section_of(key), closest(key, keys)) {
+                            (Some(other), _) => {
+                                format!("`{}` goes in [{}], not [{}]", key, other, section)
+                            }
+                            (None, Some(guess)) => format!(
+                                "unknown key `{}` in [{}], did you mean `{}`?",
+                                key, section, guess
+                            ),
+                            (None, None) => format!("unknown key `{}` in [{}]", key, section),
+                        },
+                    ));
+                }
+                config
+                    .set(key, value.get_ref())
+                    .map_err(|message| error(line, message))?;
+            }
+        }
+        Ok(config)
+    }
+
+    fn set(&mut self, key: &str, value: &DeValue) -> Result<(), String> {
+        match key {
+            "width" => self.width = Some(positive_u32(key, value)?),
+            "height" => self.height = Some(positive_u32(key, value)?),
+            "max_instructions" => self.max_instructions = Some(integer(key, value, 0..=u64::MAX)?),
+            "cycles_per_frame" => self.cycles_per_frame = Some(integer(key, value, 1..=u64::MAX)?),
+            "register_width" => {
+                self.register_width = Some(match integer(key, value, 0..=u64::MAX) {
+                    Ok(8) => RegisterWidth::Eight,
+                    Ok(16) => RegisterWidth::Sixteen,
+                    _ => return Err(format!("`{}` must be 8 or 16", key)),
+                })
+            }
+            "decode_cache" => self.decode_cache = Some(boolean(key, value)?),
+            "verify_checksums" => self.verify_checksums = Some(boolean(key, value)?),
+            "core_dump" => self.core_dump = Some(string(key, value)?),
+            "scale" => self.scale = Some(positive_u32(key, value)?),
+            "present_mode" => self.present_mode = Some(string(key, value)?),
+            "letterbox" => self.letterbox = Some(color(key, value)?),
+            "border" => self.border = Some(color(key, value)?),
+            "crt" => self.crt = Some(boolean(key, value)?),
+            "keymap" => self.keymap = Some(string(key, value)?),
+            "pause_in_background" => self.pause_in_background = Some(boolean(key, value)?),
+            "nvram" => self.nvram = Some(string(key, value)?),
+            "framebuffer_window" => {
+                self.framebuffer_window = Some(integer(key, value, 0..=0xFFFF)? as u16)
+            }
+            "relative_mouse" => self.relative_mouse = Some(boolean(key, value)?),
+            "enabled" => self.trace = Some(boolean(key, value)?),
+            "symbols" => self.symbols = Some(string(key, value)?),
+            _ => unreachable!("parse only passes on keys in SECTIONS"),
+        }
+        Ok(())
+    }
+}
+
+fn error(line: usize, message: impl Into<String>) -> ConfigError {
+    ConfigError {
+        line,
+        message: message.into(),
+    }
+}
+
+// The entries of a table in the order the file has them, so the first mistake is the one
+// reported.
+fn in_file_order<'a, 'i>(
+    table: &'a DeTable<'i>,
+) -> Vec<(
+    &'a toml::Spanned<toml::de::DeString<'i>>,
+    &'a toml::Spanned<DeValue<'i>>,
+)> {
+    let mut entries: Vec<_> = table.iter().collect();
+    entries.sort_by_key(|(key, _)| key.span().start);
+    entries
+}
+
+fn line_of(text: &str, span: Range<usize>) -> usize {
+    let start = span.start.min(text.len());
+    text.as_bytes()[..start]
+        .iter()
+        .filter(|&&byte| byte == b'\n')
+        .count()
+        + 1
+}
+
+fn section_of(key: &str) -> Option<&'static str> {
+    SECTIONS
+        .iter()
+        .find(|(_, keys)| keys.contains(&key))
+        .map(|(section, _)| *section)
+}
+
+// The candidate `name` is most likely a misspelling of, if any is close enough.
+fn closest<'a>(name: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    let squashed = |text: &str| text.replace(['_', '-'], "").to_lowercase();
+    candidates
+        .iter()
+        .map(|&candidate| {
+            let distance = if squashed(candidate) == squashed(name) {
+                0
+            } else {
+                edit_distance(&squashed(name), &squashed(candidate))
+            };
+            (distance, candidate)
+        })
+        .filter(|&(distance, candidate)| {
+            distance <= candidate.len().max(name.len()) / 3
+                || name.starts_with(candidate)
+                || candidate.starts_with(name)
+        })
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = (diagonal + (ca != cb) as usize)
+                .min(row[j] + 1)
+                .min(above + 1);
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+fn integer(key: &str, value: &DeValue, range: RangeInclusive<u64>) -> Result<u64, String> {
+    let parsed = match value {
+        DeValue::Integer(integer) => u64::from_str_radix(integer.as_str(), integer.radix()).ok(),
+        _ => None,
+    };
+    parsed.filter(|n| range.contains(n)).ok_or_else(|| {
+        format!(
+            "`{}` must be a whole number from {} to {}",
+            key,
+            range.start(),
+            range.end()
+        )
+    })
+}
+
+// From 1 up, for sizes and counts where 0 makes no sense.
+fn positive_u32(key: &str, value: &DeValue) -> Result<u32, String> {
+    integer(key, value, 1..=u32::MAX as u64).map(|n| n as u32)
+}
+
+fn boolean(key: &str, value: &DeValue) -> Result<bool, String> {
+    match value {
+        DeValue::Boolean(value) => Ok(*value),
+        _ => Err(format!("`{}` must be true or false", key)),
+    }
+}
+
+fn string(key: &str, value: &DeValue) -> Result<String, String> {
+    match value {
+        DeValue::String(value) => Ok(value.to_string()),
+        _ => Err(format!("`{}` must be a string", key)),
+    }
+}
+
+fn color(key: &str, value: &DeValue) -> Result<Color, String> {
+    string(key, value).and_then(|text| {
+        Color::from_hex(&text).ok_or_else(|| format!("`{}` must be a color like \"rrggbb\"", key))
+    })
+}
+
+impl core::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl core::error::Error for ConfigError {}
@@ -0,0 +1,120 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::Display;
+
+use crate::cpu::RegisterWidth;
+use crate::snapshot::{Reader, SnapshotError};
+use crate::vm::Snapshot;
+
+// File layout: magic, version, then the metadata header, little endian: the faulting pc,
+// the register width in bits, the screen width and height as u32, and the crash report as
+// a u32 length and UTF-8. A serialized snapshot, magic included, makes up the rest.
+const MAGIC: [u8; 4] = *b"MCVD";
+pub const DUMP_VERSION: u8 = 1;
+
+// The machine as it was when a fault stopped it, for looking at after the fact.
+#[derive(Clone)]
+pub struct CoreDump {
+    pub pc: u16,
+    pub register_width: RegisterWidth,
+    pub width: u32,
+    pub height: u32,
+    // The report as it printed.
+    pub report: String,
+    pub snapshot: Snapshot,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DumpError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    BadRegisterWidth(u8),
+    BadReport,
+    Snapshot(SnapshotError),
+}
+
+impl CoreDump {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&MAGIC);
+        bytes.push(DUMP_VERSION);
+        bytes.extend_from_slice(&self.pc.to_le_bytes());
+        bytes.push(self.register_width.bits());
+        bytes.extend_from_slice(&self.width.to_le_bytes());
+        bytes.extend_from_slice(&self.height.to_le_bytes());
+        bytes.extend_from_slice(&(self.report.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(self.report.as_bytes());
+        bytes.extend_from_slice(&self.snapshot.to_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<CoreDump, DumpError> {
+        let mut reader = Reader::new(bytes);
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(DumpError::BadMagic);
+        }
+        let version = reader.u8()?;
+        if version != DUMP_VERSION {
+            return Err(DumpError::UnsupportedVersion(version));
+        }
+        let pc = reader.u16()?;
+        let register_width = match reader.u8()? {
+            8 => RegisterWidth::Eight,
+            16 => RegisterWidth::Sixteen,
+            bits => return Err(DumpError::BadRegisterWidth(bits)),
+        };
+        let (width, height) = (reader.u32()?, reader.u32()?);
+        let report_len = reader.u32()? as usize;
+        let report = core::str::from_utf8(reader.take(report_len)?)
+            .map_err(|_| DumpError::BadReport)?
+            .to_string();
+        let snapshot = Snapshot::from_bytes(reader.rest())?;
+        Ok(CoreDump {
+            pc,
+            register_width,
+            width,
+            height,
+            report,
+            snapshot,
+        })
+    }
+
+    #[cfg(feature = "std")]
+    pub fn write(&self, path: &std::path::Path) -> std::io::Result<()> {
+        std::fs::write(path, self.to_bytes())
+    }
+
+    // A file that isn't a dump is an InvalidData error.
+    #[cfg(feature = "std")]
+    pub fn read(path: &std::path::Path) -> std::io::Result<CoreDump> {
+        let bytes = std::fs::read(path)?;
+        CoreDump::from_bytes(&bytes)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))
+    }
+}
+
+impl From<SnapshotError> for DumpError {
+    fn from(e: SnapshotError) -> Self {
+        DumpError::Snapshot(e)
+    }
+}
+
+impl Display for DumpError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            DumpError::BadMagic => write!(f, "not a core dump"),
+            DumpError::UnsupportedVersion(version) => {
+                write!(f, "unsupported core dump version {}", version)
+            }
+            DumpError::BadRegisterWidth(bits) => {
+                write!(
+                    f,
+                    "core dump has an invalid register width of {} bits",
+                    bits
+                )
+            }
+            DumpError::BadReport => write!(f, "core dump has a report that is not UTF-8"),
+            DumpError::Snapshot(e) => write!(f, "{}", e),
+        }
+    }
+}
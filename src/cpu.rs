@@ -1,23 +1,236 @@
-use std::fmt::Display;
-use std::fs::File;
-use std::io;
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Display;
+use core::ops::{Index, IndexMut, Range};
 
-use crate::types::Color;
+use log::Level;
 
-const FREE_MEMORY: usize = 2048 * 1024;
-const VIDEO_MEMORY: usize = 1728 * 1024;
+use crate::audio::{AUDIO_REGISTER_COUNT, AudioRegisters, PCM_REGISTER_COUNT, PcmRegisters};
+use crate::bus::{Bus, BusError, Device};
+use crate::decode_cache::{DecodeCache, Predecoded};
+use crate::dma::{
+    DMA_BYTES_PER_PIXEL, DMA_CYCLES_PER_PIXEL, DMA_REGISTER_COUNT, DMA_STATUS_CLIPPED, DmaRegisters,
+};
+use crate::error::{FaultReport, VmError};
+use crate::extension::{CpuView, EXTENSION_OPCODES, ExtensionDef, ExtensionError, Extensions};
+use crate::fault::{
+    FAULT_REGISTER_COUNT, FaultAction, FaultInfo, FaultPolicy, FaultRegisters, VECTOR_FAULT,
+};
+use crate::fixed;
+use crate::framebuffer::FramebufferWindow;
+use crate::gamepad::{Gamepad, PAD_REGISTER_COUNT};
+use crate::hcall::{HcallContext, HcallHandler};
+use crate::interrupt::{
+    INT_REGISTER_COUNT, INTERRUPT_VECTOR_COUNT, InterruptController, VECTOR_VBLANK,
+};
+use crate::isa::{self, OperandKind};
+use crate::keyboard::{KEYBOARD_REGISTER_COUNT, Keyboard, VECTOR_KEYBOARD};
+use crate::mailbox::{MAILBOX_REGISTER_COUNT, Mailbox};
+use crate::mouse::{MOUSE_REGISTER_COUNT, Mouse};
+use crate::perf::{PERF_REGISTER_COUNT, PerfCounters};
+use crate::profile::Profiler;
+use crate::program::ProgramHeader;
+use crate::protect::{PagedRangeSet, Protection, RangeSet};
+use crate::rtc::{RTC_REGISTER_COUNT, Rtc};
+use crate::segment::{self, SegmentKind};
+use crate::sprite::{SPRITE_REGISTER_COUNT, SpriteTable};
+use crate::tilemap::{TILE_REGISTER_COUNT, TileLayer};
+use crate::trace::{TraceEntry, TraceRecord, TraceSink};
+use crate::video::{ColorDepth, Fade, Rect, VideoMemory};
+use crate::watchdog::{VECTOR_WATCHDOG, WDT_REGISTER_COUNT, Watchdog, WatchdogAction};
+
+pub const FREE_MEMORY: usize = 2048 * 1024;
+pub const VIDEO_MEMORY: usize = 1728 * 1024;
+pub const DEFAULT_VIDEO_WIDTH: u32 = 384;
+
+// Set in the register byte of `reg, src` instructions when `src` names a register
+// rather than an immediate.
+pub const SRC_REGISTER: u8 = 0x80;
+
+// Set when add, sub, inc, dec, mul or div produce zero, cleared when they don't. `test` and
+// `btst` set it from the bits they look at.
+pub const FLAG_ZERO: u8 = 0x01;
+// Set when the result of add, sub, dec or mul doesn't fit the register, cleared when it does.
+// div always clears it.
+pub const FLAG_CARRY: u8 = 0x02;
+// Set by `ei`, cleared by `di` and on entry to an interrupt handler. popf restores it
+// along with the other flags.
+pub const FLAG_INTERRUPT_ENABLE: u8 = 0x04;
+
+// ZCI, each flag showing as its letter when set and '-' when clear.
+pub fn flag_letters(flags: u8) -> String {
+    [
+        (FLAG_ZERO, 'Z'),
+        (FLAG_CARRY, 'C'),
+        (FLAG_INTERRUPT_ENABLE, 'I'),
+    ]
+    .iter()
+    .map(|&(flag, letter)| if flags & flag != 0 { letter } else { '-' })
+    .collect()
+}
+
+// Width of the general registers. Sixteen-bit registers widen arithmetic, flags, loads
+// and stores with them, and their immediate operands take two bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RegisterWidth {
+    #[default]
+    Eight,
+    Sixteen,
+}
+
+// Guest address space: 0x4000..0x8000 is a window onto the selected 16 KiB bank of
+// physical memory, 0xFF00.. is MMIO, and everything else maps 1:1 onto physical memory.
+pub const BANK_SIZE: usize = 16 * 1024;
+pub const BANK_COUNT: usize = FREE_MEMORY / BANK_SIZE;
+pub const BANK_WINDOW_START: u16 = 0x4000;
+pub const BANK_WINDOW_END: u16 = BANK_WINDOW_START + BANK_SIZE as u16;
+
+pub const MMIO_BASE: u16 = 0xFF00;
+pub const AUDIO_BASE: u16 = 0xFF00;
+const AUDIO_END: u16 = AUDIO_BASE + AUDIO_REGISTER_COUNT as u16;
+pub const WATCHDOG_BASE: u16 = 0xFF04;
+const WATCHDOG_END: u16 = WATCHDOG_BASE + WDT_REGISTER_COUNT as u16;
+pub const BANK_SELECT: u16 = 0xFF10;
+pub const FB_BANK_SELECT: u16 = 0xFF11;
+// Bit 0 is set when a frame has been presented, a guest load clears it.
+pub const VIDEO_STATUS: u16 = 0xFF12;
+pub const VIDEO_STATUS_VBLANK: u8 = 0x01;
+// Bit 0 shows the tile map instead of the framebuffer.
+pub const VIDEO_CONTROL: u16 = 0xFF13;
+pub const VIDEO_CONTROL_TILES: u8 = 0x01;
+pub const VIDEO_CONTROL_LETTERBOX: u8 = 0x02;
+// Bits per pixel of video memory: 1, 4, 8 or 24. Other values are ignored.
+pub const VIDEO_DEPTH: u16 = 0xFF14;
+// PALETTE_DATA holds the red, green and blue of the entry PALETTE_INDEX picks.
+pub const PALETTE_INDEX: u16 = 0xFF15;
+pub const PALETTE_DATA: u16 = 0xFF16;
+const PALETTE_DATA_END: u16 = PALETTE_DATA + 3;
+// Scales every presented pixel by `value / 255`, 255 by default. A write ends a fade.
+pub const VIDEO_BRIGHTNESS: u16 = 0xFF19;
+// The red, green and blue of the bars around the frame in a window it doesn't fill, used
+// instead of the host's while bit 1 of VIDEO_CONTROL is set.
+pub const VIDEO_LETTERBOX: u16 = 0xFF1A;
+const VIDEO_LETTERBOX_END: u16 = VIDEO_LETTERBOX + 3;
+pub const DMA_BASE: u16 = 0xFF20;
+const DMA_END: u16 = DMA_BASE + DMA_REGISTER_COUNT as u16;
+// Devices on the bus, see `builtin_bus`.
+pub const KEYBOARD_BASE: u16 = 0xFF30;
+pub const MAILBOX_BASE: u16 = 0xFF40;
+pub const RTC_BASE: u16 = 0xFF70;
+pub const INTERRUPT_BASE: u16 = 0xFF90;
+const INTERRUPT_END: u16 = INTERRUPT_BASE + INT_REGISTER_COUNT as u16;
+// Read only. Loads latch them, so `guest_read` handles them rather than `read_mmio`.
+pub const PERF_BASE: u16 = 0xFF94;
+const PERF_END: u16 = PERF_BASE + PERF_REGISTER_COUNT as u16;
+pub const SPRITE_BASE: u16 = 0xFFA0;
+pub const TILE_BASE: u16 = 0xFFB0;
+// Read only, writes are dropped.
+pub const GAMEPAD_BASE: u16 = 0xFFC0;
+pub const PCM_BASE: u16 = 0xFFD0;
+pub const MOUSE_BASE: u16 = 0xFFE0;
+const PCM_END: u16 = PCM_BASE + PCM_REGISTER_COUNT as u16;
+// Read only, like the gamepad.
+pub const FAULT_BASE: u16 = 0xFFF0;
+const FAULT_END: u16 = FAULT_BASE + FAULT_REGISTER_COUNT as u16;
+#[cfg(feature = "net")]
+pub const NET_BASE: u16 = 0xFF50;
+#[cfg(feature = "std")]
+pub const FS_BASE: u16 = 0xFF60;
+#[cfg(feature = "std")]
+pub const NVRAM_BASE: u16 = 0xFF80;
+#[cfg(feature = "std")]
+const NVRAM_END: u16 = NVRAM_BASE + crate::nvram::NVRAM_REGISTER_COUNT as u16;
+#[cfg(feature = "std")]
+const FS_END: u16 = FS_BASE + crate::files::FS_REGISTER_COUNT as u16;
+#[cfg(feature = "net")]
+const NET_END: u16 = NET_BASE + crate::net::NET_REGISTER_COUNT as u16;
+
+// The stack fills the top of the flat region, growing down from MMIO towards the bank
+// window. `call` pushes the return address low byte first.
+pub const STACK_TOP: u16 = MMIO_BASE;
+pub const STACK_LIMIT: u16 = BANK_WINDOW_END;
+// How many return addresses a CallDepthExceeded error carries.
+pub const CALL_CHAIN_LIMIT: usize = 16;
 
 pub struct MicroCVMCpu {
-    pub memory: Vec<u8>,
-    pub video_memory: Vec<super::types::Color>,
-    pub registers: [u8; 8],
-    pub sp: u8,
-    pub pc: u8,
+    // Allocated once at its full size. Private so every write goes through `memory_mut` or
+    // the CPU's own stores, which keep the decode cache in step with it.
+    memory: Box<[u8]>,
+    pub video_memory: VideoMemory,
+    pub video_width: u32,
+    // Never hold more bits than register_width allows.
+    pub registers: [u16; 8],
+    pub register_width: RegisterWidth,
+    pub sp: u16,
+    pub pc: u16,
     pub flags: u8,
+    pub bank: u8,
+    // Guest addresses that alias video memory, see `map_framebuffer`.
+    pub framebuffer_window: Option<FramebufferWindow>,
+    // The part of the screen changed since the last `take_dirty`, see `mark_dirty`.
+    dirty: Option<Rect>,
+    // Set by `tick_frame`, cleared when the guest loads VIDEO_STATUS.
+    pub vblank: bool,
+    pub video_control: u8,
+    pub palette_index: u8,
+    pub brightness: u8,
+    pub letterbox: [u8; 3],
+    // Advanced by `tick_frame`.
+    pub fade: Option<Fade>,
+    pub interrupts: InterruptController,
+    pub halted: bool,
+    // Set by `video vsync`, frontends present a frame and clear it.
+    pub frame_done: bool,
+    pub cycles: u64,
+    // Instructions that ran to completion, for PERF_BASE.
+    pub instructions: u64,
+    // Calls deeper than this fault instead of running on until the stack overflows.
+    pub max_call_depth: Option<u32>,
+    // Whether load_program refuses payloads that don't match their header's checksum.
+    pub verify_checksums: bool,
+    pub(crate) call_depth: u32,
+    pub audio: Arc<AudioRegisters>,
+    pub pcm: Arc<PcmRegisters>,
+    pub dma: DmaRegisters,
+    // Consulted before RAM and the registers below. Holds the keyboard, mailbox, RTC,
+    // sprites, tiles, gamepad and mouse besides whatever the host maps. Private so the
+    // built-in devices can't be taken away, see `map_device` and `device`.
+    bus: Bus,
+    pub fault: FaultRegisters,
+    pub fault_policy: FaultPolicy,
+    // Checked before each instruction, see `expire_watchdog`.
+    pub watchdog: Watchdog,
+    pub perf: PerfCounters,
+    #[cfg(feature = "net")]
+    pub net: crate::net::UdpDevice,
+    #[cfg(feature = "std")]
+    pub files: crate::files::FileDevice,
+    // Saved on halt, on a guest commit and when the CPU is dropped.
+    #[cfg(feature = "std")]
+    pub nvram: Option<crate::nvram::Nvram>,
+    hcalls: BTreeMap<u8, HcallHandler>,
+    extensions: Extensions,
+    write_protected: RangeSet,
+    no_execute: PagedRangeSet,
+    // Memory that loads have written program bytes to, and an entry point waiting to be
+    // checked against it.
+    loaded: RangeSet,
+    entry: Option<u16>,
+    trace: Option<Box<dyn TraceSink>>,
+    profiler: Option<Box<Profiler>>,
+    // The last `history_len` instructions, for fault reports.
+    history: VecDeque<TraceRecord>,
+    history_len: usize,
+    // Instructions the fast path has decoded, see `set_decode_cache`.
+    decode_cache: Option<Box<DecodeCache>>,
 }
 
 #[repr(u8)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum OpcodeType {
     Load = 0x01,
     Store = 0x02,
@@ -29,17 +242,56 @@ pub enum OpcodeType {
     Inc = 0x07,
     Div = 0x08,
     Mul = 0x09,
+    Hcall = 0x0A,
+    Video = 0x0B,
+    Jr = 0x0C,
+    Jrz = 0x0D,
+    Jrnz = 0x0E,
+    Djnz = 0x0F,
+    Test = 0x10,
+    Bset = 0x11,
+    Bclr = 0x12,
+    Btst = 0x13,
+    Memset = 0x14,
+    Memcpy = 0x15,
+    Call = 0x16,
+    Ret = 0x17,
+    Clc = 0x18,
+    Stc = 0x19,
+    Cmc = 0x1A,
+    Pushf = 0x1B,
+    Popf = 0x1C,
+    Ei = 0x1D,
+    Di = 0x1E,
+    Int = 0x1F,
+    Iret = 0x20,
+    FxMul = 0x21,
+    FxDiv = 0x22,
+    FxMulImm = 0x23,
+    Dec = 0x24,
+    Clr = 0x25,
+    Movm = 0x26,
+    LoadInc = 0x27,
+    StoreInc = 0x28,
+    LoadSp = 0x29,
+    StoreSp = 0x2A,
+    Jtab = 0x2B,
     Nop = 0x90,
 }
 
 #[repr(u8)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum VideoOpcodeType {
     Fill = 0x01,
     Clear = 0x02,
+    SetPixel = 0x03,
+    FillRect = 0x04,
+    Vsync = 0x05,
+    FadeTo = 0x06,
+    CopyRect = 0x07,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Register {
     R0 = 0x00,
     R1 = 0x01,
@@ -51,6 +303,7 @@ pub enum Register {
     R7 = 0x07,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Opcode {
     pub opcode_type: OpcodeType,
     pub argument_count: u8,
@@ -58,10 +311,42 @@ pub struct Opcode {
     pub arg2: Option<OpcodeArg2>,
 }
 
+// Traces, the fault history and the debugger copy decoded instructions around by value.
+const _: () = assert!(
+    core::mem::size_of::<Opcode>() <= 12,
+    "Opcode grew past 12 bytes"
+);
+
+// The operation and register of a `video` instruction, as the bytes after its opcode
+// encode them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct VideoOpcode {
     pub opcode_type: VideoOpcodeType,
-    pub arg1: Option<u8>,
-    pub arg2: Option<u8>,
+    // The first of the registers the operation reads its parameters from.
+    pub base: Register,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HaltReason {
+    // `hlt` ran, and the program's exit code is what it left in r0; see `exit_code`.
+    Halted { code: u8 },
+    InstructionLimit,
+    FrameComplete,
+    Paused,
+    Breakpoint,
+}
+
+impl Display for HaltReason {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            HaltReason::Halted { code: 0 } => write!(f, "Halted"),
+            HaltReason::Halted { code } => write!(f, "Halted with exit code {}", code),
+            HaltReason::InstructionLimit => write!(f, "Instruction limit reached"),
+            HaltReason::FrameComplete => write!(f, "Frame complete"),
+            HaltReason::Paused => write!(f, "Paused"),
+            HaltReason::Breakpoint => write!(f, "Breakpoint"),
+        }
+    }
 }
 
 #[repr(transparent)]
@@ -69,132 +354,641 @@ pub struct VideoOpcode {
 pub struct InvalidOpcode(pub u8);
 
 impl Display for InvalidOpcode {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "Invalid Opcode: {}", self.0)
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct InvalidVideoOpcode(pub u8);
+
+impl Display for InvalidVideoOpcode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Invalid Video Opcode: {}", self.0)
+    }
+}
+
 #[derive(Debug)]
 pub struct InvalidRegister(pub u8);
 
 impl Display for InvalidRegister {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "Invalid Register: {}", self.0)
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum OpcodeArg1 {
     Register(Register),
-    Address(u8),
+    Immediate(u8),
+    Address(u16),
+    // Relative to the pc of the next instruction.
+    Offset(i8),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum OpcodeArg2 {
     Register(Register),
     Immediate(u8),
-    Address(u8),
+    // A `reg, src` immediate with 16-bit registers.
+    WideImmediate(u16),
+    Address(u16),
+    Offset(i8),
 }
 
+// Opcode bytes, for matching on in the fast path.
+const LOAD: u8 = OpcodeType::Load as u8;
+const STORE: u8 = OpcodeType::Store as u8;
+const ADD: u8 = OpcodeType::Add as u8;
+const SUB: u8 = OpcodeType::Sub as u8;
+const JMP: u8 = OpcodeType::Jmp as u8;
+const MOV: u8 = OpcodeType::Mov as u8;
+const INC: u8 = OpcodeType::Inc as u8;
+const DIV: u8 = OpcodeType::Div as u8;
+const MUL: u8 = OpcodeType::Mul as u8;
+const HCALL: u8 = OpcodeType::Hcall as u8;
+const VIDEO: u8 = OpcodeType::Video as u8;
+const JR: u8 = OpcodeType::Jr as u8;
+const JRZ: u8 = OpcodeType::Jrz as u8;
+const JRNZ: u8 = OpcodeType::Jrnz as u8;
+const DJNZ: u8 = OpcodeType::Djnz as u8;
+const TEST: u8 = OpcodeType::Test as u8;
+const BSET: u8 = OpcodeType::Bset as u8;
+const BCLR: u8 = OpcodeType::Bclr as u8;
+const BTST: u8 = OpcodeType::Btst as u8;
+const MEMSET: u8 = OpcodeType::Memset as u8;
+const MEMCPY: u8 = OpcodeType::Memcpy as u8;
+const CALL: u8 = OpcodeType::Call as u8;
+const RET: u8 = OpcodeType::Ret as u8;
+const CLC: u8 = OpcodeType::Clc as u8;
+const STC: u8 = OpcodeType::Stc as u8;
+const CMC: u8 = OpcodeType::Cmc as u8;
+const PUSHF: u8 = OpcodeType::Pushf as u8;
+const POPF: u8 = OpcodeType::Popf as u8;
+const EI: u8 = OpcodeType::Ei as u8;
+const DI: u8 = OpcodeType::Di as u8;
+const INT: u8 = OpcodeType::Int as u8;
+const IRET: u8 = OpcodeType::Iret as u8;
+const FXMUL: u8 = OpcodeType::FxMul as u8;
+const FXDIV: u8 = OpcodeType::FxDiv as u8;
+const FXMULI: u8 = OpcodeType::FxMulImm as u8;
+const DEC: u8 = OpcodeType::Dec as u8;
+const CLR: u8 = OpcodeType::Clr as u8;
+const MOVM: u8 = OpcodeType::Movm as u8;
+const LOAD_INC: u8 = OpcodeType::LoadInc as u8;
+const STORE_INC: u8 = OpcodeType::StoreInc as u8;
+const LOAD_SP: u8 = OpcodeType::LoadSp as u8;
+const STORE_SP: u8 = OpcodeType::StoreSp as u8;
+const JTAB: u8 = OpcodeType::Jtab as u8;
+const NOP: u8 = OpcodeType::Nop as u8;
+const HLT: u8 = OpcodeType::Hlt as u8;
+const EXTENSION_FIRST: u8 = *EXTENSION_OPCODES.start();
+const EXTENSION_LAST: u8 = *EXTENSION_OPCODES.end();
+
 impl MicroCVMCpu {
     pub fn empty() -> Self {
+        Self::with_memory(FREE_MEMORY, VIDEO_MEMORY)
+    }
+
+    // `video_pixels` is the size of video memory in pixels, not bytes.
+    pub fn with_memory(memory_size: usize, video_pixels: usize) -> Self {
         Self {
-            memory: vec![0; FREE_MEMORY],
-            video_memory: vec![Color::new(0, 0, 0); VIDEO_MEMORY],
+            memory: vec![0; memory_size].into_boxed_slice(),
+            video_memory: VideoMemory::new(video_pixels, ColorDepth::default()),
+            video_width: DEFAULT_VIDEO_WIDTH,
             registers: [0; 8],
-            sp: 0,
+            register_width: RegisterWidth::Eight,
+            sp: STACK_TOP,
             pc: 0,
             flags: 0,
+            bank: 1,
+            framebuffer_window: None,
+            dirty: None,
+            vblank: false,
+            video_control: 0,
+            palette_index: 0,
+            brightness: 255,
+            letterbox: [0; 3],
+            fade: None,
+            interrupts: InterruptController::default(),
+            halted: false,
+            frame_done: false,
+            cycles: 0,
+            instructions: 0,
+            max_call_depth: None,
+            call_depth: 0,
+            audio: Arc::new(AudioRegisters::default()),
+            pcm: Arc::new(PcmRegisters::new()),
+            dma: DmaRegisters::default(),
+            bus: builtin_bus(),
+            fault: FaultRegisters::default(),
+            fault_policy: FaultPolicy::Halt,
+            watchdog: Watchdog::default(),
+            perf: PerfCounters::default(),
+            #[cfg(feature = "net")]
+            net: crate::net::UdpDevice::default(),
+            #[cfg(feature = "std")]
+            files: crate::files::FileDevice::default(),
+            #[cfg(feature = "std")]
+            nvram: None,
+            hcalls: BTreeMap::new(),
+            extensions: Extensions::new(),
+            write_protected: RangeSet::new(),
+            no_execute: PagedRangeSet::new(),
+            trace: None,
+            profiler: None,
+            history: VecDeque::new(),
+            history_len: 0,
+            decode_cache: Some(Box::new(DecodeCache::new())),
+            verify_checksums: true,
+            loaded: RangeSet::new(),
+            entry: None,
         }
     }
     pub fn get_opcode_argument_count(opcode_type: OpcodeType) -> u8 {
-        match opcode_type {
-            OpcodeType::Inc => 1,
-            OpcodeType::Mov => 2,
-            OpcodeType::Add => 2,
-            OpcodeType::Sub => 2,
-            OpcodeType::Div => 2,
-            OpcodeType::Mul => 2,
-            _ => 0,
-        }
+        isa::info(opcode_type).operands.len() as u8
+    }
+
+    pub fn get_opcode_length(opcode_type: OpcodeType) -> u16 {
+        isa::length(opcode_type)
+    }
+
+    pub fn get_video_opcode_argument_count(opcode_type: VideoOpcodeType) -> u8 {
+        isa::video_info(opcode_type).parameters.len() as u8
+    }
+
+    // Decodes the two bytes after a `video` opcode.
+    pub fn create_video_opcode(operation: u8, base: u8) -> Result<VideoOpcode, VmError> {
+        Ok(VideoOpcode {
+            opcode_type: VideoOpcodeType::try_from(operation)?,
+            base: Register::try_from(base)?,
+        })
     }
 
-    pub fn create_opcode(&mut self) -> Opcode {
+    pub fn create_opcode(&mut self) -> Result<Opcode, VmError> {
         let mut current_instruction = Opcode::empty();
 
-        let opcode_byte: u8 = self.memory[self.pc as usize];
-        current_instruction.opcode_type =
-            OpcodeType::try_from(opcode_byte).unwrap_or(OpcodeType::Nop);
+        let opcode_byte: u8 = self.fetch(self.pc)?;
+        let info = isa::decode(opcode_byte).ok_or(InvalidOpcode(opcode_byte))?;
+        current_instruction.opcode_type = info.opcode;
+        current_instruction.argument_count = info.operands.len() as u8;
+
+        let pc = self.pc;
+        match current_instruction.opcode_type {
+            OpcodeType::Load | OpcodeType::Jtab => {
+                let dst = self.fetch(pc.wrapping_add(1))?;
+                let addr = self.fetch_u16(pc.wrapping_add(2))?;
+                current_instruction.arg1 = Some(OpcodeArg1::Register(Register::try_from(dst)?));
+                current_instruction.arg2 = Some(OpcodeArg2::Address(addr));
+            }
+            OpcodeType::Store => {
+                let addr = self.fetch_u16(pc.wrapping_add(1))?;
+                let src = self.fetch(pc.wrapping_add(3))?;
+                current_instruction.arg1 = Some(OpcodeArg1::Address(addr));
+                current_instruction.arg2 = Some(OpcodeArg2::Register(Register::try_from(src)?));
+            }
+            OpcodeType::LoadInc => {
+                let dst = self.fetch(pc.wrapping_add(1))?;
+                let pointer = self.fetch(pc.wrapping_add(2))?;
+                current_instruction.arg1 = Some(OpcodeArg1::Register(Register::try_from(dst)?));
+                current_instruction.arg2 = Some(OpcodeArg2::Register(self.pointer(pointer)?));
+            }
+            OpcodeType::StoreInc => {
+                let pointer = self.fetch(pc.wrapping_add(1))?;
+                let src = self.fetch(pc.wrapping_add(2))?;
+                current_instruction.arg1 = Some(OpcodeArg1::Register(self.pointer(pointer)?));
+                current_instruction.arg2 = Some(OpcodeArg2::Register(Register::try_from(src)?));
+            }
+            OpcodeType::LoadSp => {
+                let dst = self.fetch(pc.wrapping_add(1))?;
+                let offset = self.fetch(pc.wrapping_add(2))?;
+                current_instruction.arg1 = Some(OpcodeArg1::Register(Register::try_from(dst)?));
+                current_instruction.arg2 = Some(OpcodeArg2::Immediate(offset));
+            }
+            OpcodeType::StoreSp => {
+                let offset = self.fetch(pc.wrapping_add(1))?;
+                let src = self.fetch(pc.wrapping_add(2))?;
+                current_instruction.arg1 = Some(OpcodeArg1::Immediate(offset));
+                current_instruction.arg2 = Some(OpcodeArg2::Register(Register::try_from(src)?));
+            }
+            OpcodeType::Movm => {
+                let dst = self.fetch_u16(pc.wrapping_add(1))?;
+                let src = self.fetch_u16(pc.wrapping_add(3))?;
+                current_instruction.arg1 = Some(OpcodeArg1::Address(dst));
+                current_instruction.arg2 = Some(OpcodeArg2::Address(src));
+            }
+            OpcodeType::Jmp | OpcodeType::Call => {
+                let target = self.fetch_u16(pc.wrapping_add(1))?;
+                current_instruction.arg1 = Some(OpcodeArg1::Address(target));
+            }
+            OpcodeType::Hcall | OpcodeType::Int => {
+                let number = self.fetch(pc.wrapping_add(1))?;
+                current_instruction.arg1 = Some(OpcodeArg1::Immediate(number));
+            }
+            OpcodeType::Jr | OpcodeType::Jrz | OpcodeType::Jrnz => {
+                let offset = self.fetch(pc.wrapping_add(1))?;
+                current_instruction.arg1 = Some(OpcodeArg1::Offset(offset as i8));
+            }
+            OpcodeType::Djnz => {
+                let reg = self.fetch(pc.wrapping_add(1))?;
+                let offset = self.fetch(pc.wrapping_add(2))?;
+                current_instruction.arg1 = Some(OpcodeArg1::Register(Register::try_from(reg)?));
+                current_instruction.arg2 = Some(OpcodeArg2::Offset(offset as i8));
+            }
+            OpcodeType::Bset | OpcodeType::Bclr | OpcodeType::Btst => {
+                let reg = self.fetch(pc.wrapping_add(1))?;
+                let bit = self.fetch(pc.wrapping_add(2))?;
+                current_instruction.arg1 = Some(OpcodeArg1::Register(Register::try_from(reg)?));
+                current_instruction.arg2 = Some(OpcodeArg2::Immediate(self.bit_index(bit)?));
+            }
+            OpcodeType::Inc
+            | OpcodeType::Dec
+            | OpcodeType::Clr
+            | OpcodeType::Memset
+            | OpcodeType::Memcpy => {
+                let reg = self.fetch(pc.wrapping_add(1))?;
+                current_instruction.arg1 = Some(OpcodeArg1::Register(Register::try_from(reg)?));
+            }
+            OpcodeType::Video => {
+                let operation = self.fetch(pc.wrapping_add(1))?;
+                let base = self.fetch(pc.wrapping_add(2))?;
+                let video = Self::create_video_opcode(operation, base)?;
+                current_instruction.arg1 = Some(OpcodeArg1::Immediate(video.opcode_type as u8));
+                current_instruction.arg2 = Some(OpcodeArg2::Register(video.base));
+            }
+            OpcodeType::Mov
+            | OpcodeType::Add
+            | OpcodeType::Sub
+            | OpcodeType::Div
+            | OpcodeType::Mul
+            | OpcodeType::Test => {
+                let dst = self.fetch(pc.wrapping_add(1))?;
+                let src = self.fetch(pc.wrapping_add(2))?;
+                let dst_reg = Register::try_from(dst & !SRC_REGISTER)?;
+                current_instruction.arg1 = Some(OpcodeArg1::Register(dst_reg));
+                current_instruction.arg2 = Some(if dst & SRC_REGISTER != 0 {
+                    OpcodeArg2::Register(Register::try_from(src)?)
+                } else if self.register_width == RegisterWidth::Sixteen {
+                    let hi = self.fetch(pc.wrapping_add(3))?;
+                    OpcodeArg2::WideImmediate(u16::from_le_bytes([src, hi]))
+                } else {
+                    OpcodeArg2::Immediate(src)
+                });
+            }
+            OpcodeType::FxMul | OpcodeType::FxDiv => {
+                let dst = self.fetch(pc.wrapping_add(1))?;
+                let src = self.fetch(pc.wrapping_add(2))?;
+                current_instruction.arg1 = Some(OpcodeArg1::Register(pair(dst)?));
+                current_instruction.arg2 = Some(OpcodeArg2::Register(pair(src)?));
+            }
+            OpcodeType::FxMulImm => {
+                let dst = self.fetch(pc.wrapping_add(1))?;
+                let value = self.fetch_u16(pc.wrapping_add(2))?;
+                current_instruction.arg1 = Some(OpcodeArg1::Register(pair(dst)?));
+                current_instruction.arg2 = Some(OpcodeArg2::WideImmediate(value));
+            }
+            OpcodeType::Nop
+            | OpcodeType::Hlt
+            | OpcodeType::Ret
+            | OpcodeType::Clc
+            | OpcodeType::Stc
+            | OpcodeType::Cmc
+            | OpcodeType::Pushf
+            | OpcodeType::Popf
+            | OpcodeType::Ei
+            | OpcodeType::Di
+            | OpcodeType::Iret => {}
+        }
+
+        Ok(current_instruction)
+    }
 
-        current_instruction.argument_count =
-            Self::get_opcode_argument_count(current_instruction.opcode_type);
+    fn fetch(&self, addr: u16) -> Result<u8, VmError> {
+        self.memory
+            .get(self.translate(addr))
+            .copied()
+            .ok_or(VmError::AddressOutOfBounds { addr, pc: self.pc })
+    }
 
-        if current_instruction.argument_count >= 1 {
-            let arg1 = self.memory[(self.pc + 1) as usize];
-            current_instruction.arg1 = Some(if arg1 < 8 {
-                OpcodeArg1::Register(Register::try_from(arg1).unwrap())
-            } else {
-                OpcodeArg1::Address(arg1)
-            });
+    fn fetch_u16(&self, addr: u16) -> Result<u16, VmError> {
+        Ok(u16::from_le_bytes([
+            self.fetch(addr)?,
+            self.fetch(addr.wrapping_add(1))?,
+        ]))
+    }
+
+    fn operand_value(&mut self, arg: OpcodeArg2) -> Result<u16, VmError> {
+        let mask = self.register_width.mask();
+        match arg {
+            OpcodeArg2::Register(src) => Ok(self[src]),
+            OpcodeArg2::Immediate(imm) => Ok(imm as u16),
+            OpcodeArg2::WideImmediate(imm) => Ok(imm & mask),
+            OpcodeArg2::Address(addr) => self.load_register(addr),
+            OpcodeArg2::Offset(offset) => Ok(offset as u8 as u16),
         }
+    }
 
-        if current_instruction.argument_count >= 2 {
-            let arg2 = self.memory[(self.pc + 2) as usize];
-            current_instruction.arg2 = Some(if arg2 < 8 {
-                OpcodeArg2::Register(Register::try_from(arg2).unwrap())
-            } else {
-                OpcodeArg2::Address(arg2)
-            });
+    // A fault is handled as `fault_policy` says. Any error that still comes back is a
+    // VmError::Fault, reporting the state it left behind.
+    pub fn execute_instruction(&mut self) -> Result<(), VmError> {
+        if self.cycles >= self.watchdog.deadline {
+            return self.expire_watchdog();
+        }
+        let pc = self.pc;
+        match self.execute_unreported() {
+            Ok(()) => {
+                self.instructions += 1;
+                Ok(())
+            }
+            Err(error) => self.handle_fault(pc, error),
+        }
+    }
+
+    #[cold]
+    fn handle_fault(&mut self, pc: u16, error: VmError) -> Result<(), VmError> {
+        let next_pc = pc.wrapping_add(self.instruction_length(pc).unwrap_or(1));
+        let action = match &mut self.fault_policy {
+            FaultPolicy::Halt => FaultAction::Halt,
+            FaultPolicy::Trap => return self.trap(pc, next_pc, error),
+            FaultPolicy::Host(handler) => handler(&FaultInfo {
+                error: &error,
+                pc,
+                next_pc,
+                registers: self.registers,
+                flags: self.flags,
+            }),
+        };
+        match action {
+            FaultAction::Skip => self.pc = next_pc,
+            FaultAction::Retry => self.pc = pc,
+            FaultAction::Halt => return Err(self.fault_report(pc, error)),
+        }
+        log::debug!("fault at {:#06x} handled by the host: {:?}", pc, action);
+        Ok(())
+    }
+
+    // Enters the VECTOR_FAULT handler like an interrupt that returns past the faulting
+    // instruction, whatever the I flag says, after recording the fault at FAULT_BASE.
+    fn trap(&mut self, pc: u16, next_pc: u16, error: VmError) -> Result<(), VmError> {
+        let entry = self.interrupts.entry(VECTOR_FAULT);
+        let handler = match (self.read_mem(entry), self.read_mem(entry.wrapping_add(1))) {
+            (Ok(lo), Ok(hi)) => u16::from_le_bytes([lo, hi]),
+            _ => 0,
+        };
+        if handler == 0 {
+            return Err(self.fault_report(pc, error));
+        }
+        self.pc = pc;
+        // A fault while entering the handler is reported as the one that led to it.
+        if self.enter_handler(handler, next_pc).is_err() {
+            return Err(self.fault_report(pc, error));
+        }
+        self.fault.record(&error, pc);
+        self.cycles += 1;
+        log::debug!(
+            "fault at {:#06x} trapped to {:#06x}: {}",
+            pc,
+            handler,
+            error
+        );
+        Ok(())
+    }
+
+    // Fires at the first instruction boundary at or past the deadline, before the
+    // instruction there runs. No instruction faulted, so the fault policy has no say.
+    #[cold]
+    fn expire_watchdog(&mut self) -> Result<(), VmError> {
+        let pc = self.pc;
+        self.watchdog.expire(pc);
+        if self.watchdog.action == WatchdogAction::Interrupt {
+            let handler = self.vector_handler(VECTOR_WATCHDOG).unwrap_or(0);
+            if handler != 0 && self.enter_handler(handler, pc).is_ok() {
+                self.cycles += 1;
+                log::debug!("watchdog expired at {:#06x}, entering {:#06x}", pc, handler);
+                return Ok(());
+            }
+        }
+        Err(self.fault_report(pc, VmError::WatchdogExpired { pc }))
+    }
+
+    fn instruction_length(&self, pc: u16) -> Option<u16> {
+        if let Some(length) = self
+            .read_mem(pc)
+            .ok()
+            .and_then(|op| self.extensions.length(op))
+        {
+            return Some(length);
+        }
+        let readable: Vec<u8> = (0..isa::MAX_LENGTH)
+            .map_while(|offset| self.read_mem(pc.wrapping_add(offset)).ok())
+            .collect();
+        crate::disasm::decode(&readable, self.register_width).map(|opcode| opcode.length())
+    }
+
+    // Tracing needs the decoded Opcode, so only the untraced path takes the shortcut. Trace
+    // level logging and the fault history count as tracing.
+    fn execute_unreported(&mut self) -> Result<(), VmError> {
+        if self.interrupts.pending != 0 && self.flags & FLAG_INTERRUPT_ENABLE != 0 {
+            self.deliver_interrupt()?;
         }
+        // Checked before decoding, so data is never run as whatever it happens to decode to.
+        if self.no_execute.contains(self.translate(self.pc)) {
+            return Err(VmError::ExecuteProtected { pc: self.pc });
+        }
+        if self.trace.is_none()
+            && self.profiler.is_none()
+            && self.history_len == 0
+            && !log::log_enabled!(Level::Trace)
+        {
+            return self.execute_fast();
+        }
+        self.execute_instrumented()
+    }
+
+    // Only built once something has gone wrong, so running pays nothing for it.
+    #[cold]
+    fn fault_report(&self, pc: u16, error: VmError) -> VmError {
+        let readable: Vec<u8> = (0..isa::MAX_LENGTH)
+            .map_while(|offset| self.read_mem(pc.wrapping_add(offset)).ok())
+            .collect();
+        let length = self
+            .instruction_length(pc)
+            .map_or(readable.len(), |length| length as usize);
+        VmError::Fault(Box::new(FaultReport {
+            error,
+            pc,
+            bytes: readable[..length.min(readable.len())].to_vec(),
+            registers: self.registers,
+            sp: self.sp,
+            flags: self.flags,
+            history: self.history.iter().cloned().collect(),
+        }))
+    }
 
-        current_instruction
+    // Instructions that fault while decoding never ran, so they cost no cycles and are
+    // left out of the profile.
+    #[cold]
+    fn execute_instrumented(&mut self) -> Result<(), VmError> {
+        let pc = self.pc;
+        let cycles = self.cycles;
+        let opcode = self.fetch(pc).unwrap_or(0);
+        let result =
+            if self.trace.is_some() || self.history_len > 0 || log::log_enabled!(Level::Trace) {
+                self.execute_decoded()
+            } else {
+                self.execute_fast()
+            };
+        if self.cycles > cycles
+            && let Some(profiler) = self.profiler.as_mut()
+        {
+            profiler.record(pc, opcode, self.cycles - cycles);
+        }
+        result
     }
 
-    pub fn execute_instruction(&mut self) {
-        let opcode = self.create_opcode();
+    // Decodes into an Opcode first. This is the reference behaviour `execute_fast` has
+    // to match exactly, including which error wins and what state a fault leaves behind.
+    pub fn execute_decoded(&mut self) -> Result<(), VmError> {
+        // Extension instructions have no Opcode to trace, so they run as the fast path runs
+        // them.
+        let pc = self.pc;
+        let byte = self.fetch(pc)?;
+        if EXTENSION_OPCODES.contains(&byte) && self.extensions.get(byte).is_some() {
+            let instruction = decode_extension(self, pc, byte)?;
+            return execute_extension(self, pc, instruction);
+        }
+        let opcode = self.create_opcode()?;
+        let entry = TraceEntry {
+            pc: self.pc,
+            cycles: self.cycles,
+            opcode: &opcode,
+            registers: &self.registers,
+        };
+        if log::log_enabled!(Level::Trace) {
+            log::trace!("{}", entry);
+        }
+        if let Some(trace) = self.trace.as_mut() {
+            trace.trace(&entry);
+        }
+        if self.history_len > 0 {
+            if self.history.len() == self.history_len {
+                self.history.pop_front();
+            }
+            self.history.push_back(TraceRecord::from(&entry));
+        }
+        let next_pc = self.pc.wrapping_add(opcode.length());
+        let mask = self.register_width.mask();
+        self.cycles += 1;
 
         match opcode.opcode_type {
             OpcodeType::Inc => {
                 if let Some(OpcodeArg1::Register(reg)) = opcode.arg1 {
-                    self.registers[reg as usize] += 1;
+                    self[reg] = self[reg].wrapping_add(1) & mask;
+                    self.update_zero_flag(self[reg]);
+                }
+            }
+
+            // Borrows, setting carry, only from 0, like `sub reg, 1`.
+            OpcodeType::Dec => {
+                if let Some(OpcodeArg1::Register(reg)) = opcode.arg1 {
+                    let value = self[reg];
+                    self[reg] = value.wrapping_sub(1) & mask;
+                    self.update_zero_and_carry_flags(self[reg], value == 0);
+                }
+            }
+
+            // Leaves the flags alone, like `mov reg, 0`.
+            OpcodeType::Clr => {
+                if let Some(OpcodeArg1::Register(reg)) = opcode.arg1 {
+                    self[reg] = 0;
                 }
             }
 
             OpcodeType::Mov => {
-                if let (Some(OpcodeArg1::Register(dst)), Some(OpcodeArg2::Address(imm))) =
-                    (opcode.arg1, opcode.arg2)
-                {
-                    self.registers[dst as usize] = imm;
+                if let (Some(OpcodeArg1::Register(dst)), Some(src)) = (opcode.arg1, opcode.arg2) {
+                    let value = self.operand_value(src)?;
+                    self[dst] = value;
                 }
             }
 
             OpcodeType::Add => {
-                if let (Some(OpcodeArg1::Register(dst)), Some(OpcodeArg2::Address(imm))) =
-                    (opcode.arg1, opcode.arg2)
-                {
-                    self.registers[dst as usize] += imm;
+                if let (Some(OpcodeArg1::Register(dst)), Some(src)) = (opcode.arg1, opcode.arg2) {
+                    let value = self.operand_value(src)?;
+                    let current = self[dst] as u32;
+                    let full = current + value as u32;
+                    self[dst] = full as u16 & mask;
+                    self.update_zero_flag(self[dst]);
+                    self.update_carry_flag(full > mask as u32);
                 }
             }
 
             OpcodeType::Sub => {
-                if let (Some(OpcodeArg1::Register(dst)), Some(OpcodeArg2::Address(imm))) =
-                    (opcode.arg1, opcode.arg2)
-                {
-                    self.registers[dst as usize] -= imm;
+                if let (Some(OpcodeArg1::Register(dst)), Some(src)) = (opcode.arg1, opcode.arg2) {
+                    let value = self.operand_value(src)?;
+                    let current = self[dst] as u32;
+                    let full = current.wrapping_sub(value as u32);
+                    self[dst] = full as u16 & mask;
+                    self.update_zero_flag(self[dst]);
+                    self.update_carry_flag(full > mask as u32);
                 }
             }
 
             OpcodeType::Div => {
-                if let (Some(OpcodeArg1::Register(dst)), Some(OpcodeArg2::Address(imm))) =
-                    (opcode.arg1, opcode.arg2)
-                {
-                    self.registers[dst as usize] /= imm;
+                if let (Some(OpcodeArg1::Register(dst)), Some(src)) = (opcode.arg1, opcode.arg2) {
+                    let value = self.operand_value(src)?;
+                    self[dst] = self[dst]
+                        .checked_div(value)
+                        .ok_or(VmError::DivisionByZero { pc: self.pc })?;
+                    self.update_zero_flag(self[dst]);
+                    self.update_carry_flag(false);
                 }
             }
 
             OpcodeType::Mul => {
-                if let (Some(OpcodeArg1::Register(dst)), Some(OpcodeArg2::Address(imm))) =
+                if let (Some(OpcodeArg1::Register(dst)), Some(src)) = (opcode.arg1, opcode.arg2) {
+                    let value = self.operand_value(src)?;
+                    let full = self[dst] as u32 * value as u32;
+                    self[dst] = full as u16 & mask;
+                    self.update_zero_flag(self[dst]);
+                    self.update_carry_flag(full > mask as u32);
+                }
+            }
+
+            OpcodeType::Test => {
+                if let (Some(OpcodeArg1::Register(dst)), Some(src)) = (opcode.arg1, opcode.arg2) {
+                    let value = self.operand_value(src)?;
+                    self.update_zero_flag(self[dst] & value);
+                }
+            }
+
+            OpcodeType::FxMul | OpcodeType::FxDiv | OpcodeType::FxMulImm => {
+                if let (Some(OpcodeArg1::Register(dst)), Some(src)) = (opcode.arg1, opcode.arg2) {
+                    let value = match src {
+                        OpcodeArg2::Register(src) => self.read_pair(src as usize),
+                        OpcodeArg2::WideImmediate(value) => value,
+                        _ => 0,
+                    };
+                    self.fixed_operation(opcode.opcode_type as u8, dst as usize, value)?;
+                }
+            }
+
+            OpcodeType::Bset | OpcodeType::Bclr | OpcodeType::Btst => {
+                if let (Some(OpcodeArg1::Register(reg)), Some(OpcodeArg2::Immediate(bit))) =
                     (opcode.arg1, opcode.arg2)
                 {
-                    self.registers[dst as usize] *= imm;
+                    self.bit_operation(opcode.opcode_type as u8, reg as usize, bit);
+                }
+            }
+
+            OpcodeType::Memset => {
+                if let Some(OpcodeArg1::Register(base)) = opcode.arg1 {
+                    self.memset(base)?;
+                }
+            }
+
+            OpcodeType::Memcpy => {
+                if let Some(OpcodeArg1::Register(base)) = opcode.arg1 {
+                    self.memcpy(base)?;
                 }
             }
 
@@ -202,7 +996,7 @@ impl MicroCVMCpu {
                 if let (Some(OpcodeArg1::Register(dst)), Some(OpcodeArg2::Address(addr))) =
                     (opcode.arg1, opcode.arg2)
                 {
-                    self.registers[dst as usize] = self.memory[addr as usize];
+                    self[dst] = self.load_register(addr)?;
                 }
             }
 
@@ -210,59 +1004,2684 @@ impl MicroCVMCpu {
                 if let (Some(OpcodeArg1::Address(addr)), Some(OpcodeArg2::Register(src))) =
                     (opcode.arg1, opcode.arg2)
                 {
-                    self.memory[addr as usize] = self.registers[src as usize];
+                    self.store_register(addr, self[src])?;
+                }
+            }
+
+            OpcodeType::LoadInc => {
+                if let (Some(OpcodeArg1::Register(dst)), Some(OpcodeArg2::Register(pointer))) =
+                    (opcode.arg1, opcode.arg2)
+                {
+                    self.load_post_increment(dst as usize, pointer as usize)?;
+                }
+            }
+
+            OpcodeType::StoreInc => {
+                if let (Some(OpcodeArg1::Register(pointer)), Some(OpcodeArg2::Register(src))) =
+                    (opcode.arg1, opcode.arg2)
+                {
+                    self.store_post_increment(pointer as usize, src as usize)?;
+                }
+            }
+
+            OpcodeType::LoadSp => {
+                if let (Some(OpcodeArg1::Register(dst)), Some(OpcodeArg2::Immediate(offset))) =
+                    (opcode.arg1, opcode.arg2)
+                {
+                    self[dst] = self.load_register(self.stack_slot(offset)?)?;
+                }
+            }
+
+            OpcodeType::StoreSp => {
+                if let (Some(OpcodeArg1::Immediate(offset)), Some(OpcodeArg2::Register(src))) =
+                    (opcode.arg1, opcode.arg2)
+                {
+                    self.store_register(self.stack_slot(offset)?, self[src])?;
+                }
+            }
+
+            OpcodeType::Movm => {
+                if let (Some(OpcodeArg1::Address(dst)), Some(OpcodeArg2::Address(src))) =
+                    (opcode.arg1, opcode.arg2)
+                {
+                    self.cycles += 1;
+                    self.move_byte(dst, src)?;
                 }
             }
 
             OpcodeType::Jmp => {
                 if let Some(OpcodeArg1::Address(target)) = opcode.arg1 {
                     self.pc = target;
+                    return Ok(());
+                }
+            }
+
+            OpcodeType::Jtab => {
+                if let (Some(OpcodeArg1::Register(index)), Some(OpcodeArg2::Address(table))) =
+                    (opcode.arg1, opcode.arg2)
+                {
+                    self.pc = self.jump_table_entry(table, self[index])?;
+                    return Ok(());
+                }
+            }
+
+            OpcodeType::Jr | OpcodeType::Jrz | OpcodeType::Jrnz => {
+                if let Some(OpcodeArg1::Offset(offset)) = opcode.arg1
+                    && self.branch_taken(opcode.opcode_type as u8)
+                {
+                    self.pc = next_pc.wrapping_add_signed(offset as i16);
+                    return Ok(());
+                }
+            }
+
+            OpcodeType::Call => {
+                if let Some(OpcodeArg1::Address(target)) = opcode.arg1 {
+                    self.call(target, next_pc)?;
+                    return Ok(());
+                }
+            }
+
+            OpcodeType::Ret => {
+                self.ret()?;
+                return Ok(());
+            }
+
+            // Leaves the flags alone so a loop can test a condition across iterations.
+            OpcodeType::Djnz => {
+                if let (Some(OpcodeArg1::Register(reg)), Some(OpcodeArg2::Offset(offset))) =
+                    (opcode.arg1, opcode.arg2)
+                {
+                    let count = self[reg].wrapping_sub(1) & mask;
+                    self[reg] = count;
+                    if count != 0 {
+                        self.pc = next_pc.wrapping_add_signed(offset as i16);
+                        return Ok(());
+                    }
                 }
             }
 
+            OpcodeType::Hcall => {
+                if let Some(OpcodeArg1::Immediate(number)) = opcode.arg1 {
+                    self.hcall(number)?;
+                }
+            }
+
+            OpcodeType::Int => {
+                if let Some(OpcodeArg1::Immediate(vector)) = opcode.arg1 {
+                    self.int(vector, next_pc)?;
+                    return Ok(());
+                }
+            }
+
+            OpcodeType::Iret => {
+                self.iret()?;
+                return Ok(());
+            }
+
+            OpcodeType::Video => {
+                if let (Some(OpcodeArg1::Immediate(operation)), Some(OpcodeArg2::Register(base))) =
+                    (opcode.arg1, opcode.arg2)
+                {
+                    self.video(Self::create_video_opcode(operation, base as u8)?)?;
+                }
+            }
+
+            OpcodeType::Clc => self.flags &= !FLAG_CARRY,
+            OpcodeType::Stc => self.flags |= FLAG_CARRY,
+            OpcodeType::Cmc => self.flags ^= FLAG_CARRY,
+            OpcodeType::Pushf => self.pushf()?,
+            OpcodeType::Popf => self.popf()?,
+            OpcodeType::Ei => self.flags |= FLAG_INTERRUPT_ENABLE,
+            OpcodeType::Di => self.flags &= !FLAG_INTERRUPT_ENABLE,
+
             OpcodeType::Nop => {}
-            OpcodeType::Hlt => {}
+            OpcodeType::Hlt => {
+                self.halt();
+                return Ok(());
+            }
+        }
+
+        self.pc = next_pc;
+        Ok(())
+    }
+
+    // Dispatches on the opcode byte and reads operands straight into locals.
+    pub fn execute_fast(&mut self) -> Result<(), VmError> {
+        match self.register_width {
+            RegisterWidth::Eight => self.execute_fast_with::<false>(),
+            RegisterWidth::Sixteen => self.execute_fast_with::<true>(),
         }
     }
 
-    pub fn read_memory_from_file(&mut self, file_path: &str) -> io::Result<u64> {
-        let mut file = File::open(file_path)?;
-        self.memory.clear();
-        let read = std::io::copy(&mut file, &mut self.memory).unwrap();
+    // One copy per register width, so the width never has to be checked per operation.
+    #[inline(always)]
+    fn execute_fast_with<const WIDE: bool>(&mut self) -> Result<(), VmError> {
+        let pc = self.pc;
+        let cached = match self.decode_cache.as_deref_mut() {
+            Some(cache) => cache.get(pc, WIDE),
+            None => Predecoded::EMPTY,
+        };
+        let instruction = if cached.opcode != 0 {
+            cached
+        } else {
+            let opcode = self.fetch(pc)?;
+            let decoded = decode::<WIDE>(self, pc, opcode)?;
+            if let Some(cache) = self.decode_cache.as_deref_mut() {
+                cache.insert(pc, decoded);
+            }
+            decoded
+        };
+        execute::<WIDE>(self, pc, instruction)
+    }
 
-        Ok(read)
+    // Starts counting executions and cycles per opcode and per pc. The profiler costs
+    // nothing until this is called.
+    pub fn enable_profiler(&mut self) {
+        self.profiler
+            .get_or_insert_with(|| Box::new(Profiler::new()));
     }
-}
 
-impl Opcode {
-    pub fn empty() -> Self {
-        Self {
-            opcode_type: OpcodeType::Nop,
-            argument_count: 0,
-            arg1: None,
-            arg2: None,
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_deref()
+    }
+
+    pub fn profiler_mut(&mut self) -> Option<&mut Profiler> {
+        self.profiler.as_deref_mut()
+    }
+
+    // Stops profiling and hands back what was collected.
+    pub fn take_profiler(&mut self) -> Option<Box<Profiler>> {
+        self.profiler.take()
+    }
+
+    // Keeps instructions the fast path has decoded, so running one again skips fetching
+    // and checking its operands. On by default. Writes the CPU makes forget whatever they
+    // overwrite; anything writing `memory` directly has to call `flush_decode_cache`.
+    pub fn set_decode_cache(&mut self, enabled: bool) {
+        match (enabled, self.decode_cache.is_some()) {
+            (true, false) => self.decode_cache = Some(Box::new(DecodeCache::new())),
+            (false, true) => self.decode_cache = None,
+            _ => {}
         }
     }
-}
 
-impl TryFrom<u8> for OpcodeType {
-    type Error = InvalidOpcode;
+    pub fn decode_cache_enabled(&self) -> bool {
+        self.decode_cache.is_some()
+    }
 
-    fn try_from(value: u8) -> Result<Self, Self::Error> {
-        Ok(match value {
-            0x01 => OpcodeType::Load,
-            0x02 => OpcodeType::Store,
-            0x03 => OpcodeType::Add,
-            0x04 => OpcodeType::Sub,
-            0x05 => OpcodeType::Jmp,
-            0x06 => OpcodeType::Mov,
-            0x07 => OpcodeType::Inc,
-            0x08 => OpcodeType::Div,
-            0x09 => OpcodeType::Mul,
-            0xFF => OpcodeType::Hlt,
-            0x90 => OpcodeType::Nop,
-            invalid => return Err(InvalidOpcode(invalid)),
-        })
+    pub fn flush_decode_cache(&mut self) {
+        if let Some(cache) = self.decode_cache.as_deref_mut() {
+            cache.flush();
+        }
+    }
+
+    #[inline]
+    fn invalidate_decoded(&mut self, physical: Range<usize>) {
+        if let Some(cache) = self.decode_cache.as_deref_mut() {
+            cache.invalidate(physical);
+        }
+    }
+
+    pub fn call_depth(&self) -> u32 {
+        self.call_depth
+    }
+
+    // Return addresses on the stack, innermost first. Only meaningful while everything
+    // on the stack was pushed by `call`.
+    pub fn call_chain(&self) -> Vec<u16> {
+        let mut chain = Vec::new();
+        let mut sp = self.sp as u32;
+        while chain.len() < self.call_depth as usize && sp + 2 <= STACK_TOP as u32 {
+            let (Ok(lo), Ok(hi)) = (self.read_mem(sp as u16), self.read_mem(sp as u16 + 1)) else {
+                break;
+            };
+            chain.push(u16::from_le_bytes([lo, hi]));
+            sp += 2;
+        }
+        chain
+    }
+
+    fn call(&mut self, target: u16, return_address: u16) -> Result<(), VmError> {
+        if let Some(max) = self.max_call_depth
+            && self.call_depth >= max
+        {
+            let mut chain = self.call_chain();
+            chain.truncate(CALL_CHAIN_LIMIT);
+            return Err(VmError::CallDepthExceeded {
+                depth: self.call_depth + 1,
+                pc: self.pc,
+                chain,
+            });
+        }
+        if self.sp > STACK_TOP || self.sp < STACK_LIMIT + 2 {
+            return Err(VmError::StackOverflow {
+                sp: self.sp,
+                pc: self.pc,
+            });
+        }
+
+        let sp = self.sp - 2;
+        let [lo, hi] = return_address.to_le_bytes();
+        self.write_mem(sp, lo)?;
+        self.write_mem(sp + 1, hi)?;
+        self.sp = sp;
+        self.call_depth += 1;
+        self.pc = target;
+        Ok(())
+    }
+
+    fn ret(&mut self) -> Result<(), VmError> {
+        if self.sp > STACK_TOP - 2 || self.sp < STACK_LIMIT {
+            return Err(VmError::StackUnderflow {
+                sp: self.sp,
+                pc: self.pc,
+            });
+        }
+
+        let lo = self.read_mem(self.sp)?;
+        let hi = self.read_mem(self.sp + 1)?;
+        self.sp += 2;
+        self.call_depth = self.call_depth.saturating_sub(1);
+        self.pc = u16::from_le_bytes([lo, hi]);
+        Ok(())
+    }
+
+    // Enters the handler for the lowest pending vector as if the interrupted instruction
+    // had been a `call`.
+    fn deliver_interrupt(&mut self) -> Result<(), VmError> {
+        let Some(vector) = self.interrupts.next() else {
+            return Ok(());
+        };
+        self.enter_handler(self.vector_handler(vector)?, self.pc)?;
+        self.interrupts.pending &= !(1 << vector);
+        self.cycles += 1;
+        Ok(())
+    }
+
+    fn vector_handler(&self, vector: u8) -> Result<u16, VmError> {
+        let entry = self.interrupts.entry(vector);
+        Ok(u16::from_le_bytes([
+            self.read_mem(entry)?,
+            self.read_mem(entry.wrapping_add(1))?,
+        ]))
+    }
+
+    // Every way into a handler builds the same frame: `call`s to it with `return_address`,
+    // then pushes the flags. From `sp` up the handler finds the flags and then the return
+    // address, low byte first, so `iret` (or `popf` followed by `ret`) goes back. Interrupts
+    // stay disabled until then, or until the handler runs `ei`.
+    fn enter_handler(&mut self, handler: u16, return_address: u16) -> Result<(), VmError> {
+        self.call(handler, return_address)?;
+        self.pushf()?;
+        self.flags &= !FLAG_INTERRUPT_ENABLE;
+        Ok(())
+    }
+
+    // A software interrupt enters the handler for `vector` whatever the I flag says,
+    // returning to the instruction after the `int`.
+    fn int(&mut self, vector: u8, return_address: u16) -> Result<(), VmError> {
+        if vector >= INTERRUPT_VECTOR_COUNT {
+            return Err(VmError::InvalidVector {
+                vector,
+                pc: self.pc,
+            });
+        }
+        self.enter_handler(self.vector_handler(vector)?, return_address)
+    }
+
+    fn iret(&mut self) -> Result<(), VmError> {
+        self.popf()?;
+        self.ret()
+    }
+
+    // Called by frontends once per presented frame. Sets the vblank flag and raises the
+    // vblank interrupt, which waits until interrupts are enabled if they aren't, and shows
+    // the guest the frame's mouse motion.
+    pub fn tick_frame(&mut self) {
+        if let Some(fade) = self.fade {
+            (self.brightness, self.fade) = fade.step(self.brightness);
+        }
+        self.mouse_mut().end_frame();
+        self.vblank = true;
+        self.interrupts.raise(VECTOR_VBLANK);
+        self.bus.tick(self.cycles);
+    }
+
+    // The flags take one byte on the stack, so a `pushf` inside a call must be undone
+    // with `popf` before `ret`.
+    fn pushf(&mut self) -> Result<(), VmError> {
+        if self.sp > STACK_TOP || self.sp < STACK_LIMIT + 1 {
+            return Err(VmError::StackOverflow {
+                sp: self.sp,
+                pc: self.pc,
+            });
+        }
+
+        self.write_mem(self.sp - 1, self.flags)?;
+        self.sp -= 1;
+        Ok(())
+    }
+
+    // Restores every bit, including the interrupt-enable one, so `pushf` ... `popf` around
+    // a critical section gives back the caller's interrupt state as well.
+    fn popf(&mut self) -> Result<(), VmError> {
+        if self.sp > STACK_TOP - 1 || self.sp < STACK_LIMIT {
+            return Err(VmError::StackUnderflow {
+                sp: self.sp,
+                pc: self.pc,
+            });
+        }
+
+        self.flags = self.read_mem(self.sp)?;
+        self.sp += 1;
+        Ok(())
+    }
+
+    // The value of a `reg, src` source operand, read from its register if it names one.
+    #[inline(always)]
+    fn source_value(&self, instruction: Predecoded) -> u16 {
+        if instruction.src_register {
+            self.registers[instruction.operand as usize]
+        } else {
+            instruction.operand
+        }
+    }
+
+    // Registers load and store as many bytes as they are wide, low byte first.
+    fn load_register(&mut self, addr: u16) -> Result<u16, VmError> {
+        let lo = self.guest_read(addr)?;
+        let hi = match self.register_width {
+            RegisterWidth::Eight => 0,
+            RegisterWidth::Sixteen => self.guest_read(addr.wrapping_add(1))?,
+        };
+        Ok(u16::from_le_bytes([lo, hi]))
+    }
+
+    // A load the program executes. Unlike `read_mem`, which debuggers and hosts use too,
+    // it may change device state.
+    fn guest_read(&mut self, addr: u16) -> Result<u8, VmError> {
+        if let Some(value) = self.bus.read(addr, self.cycles) {
+            return Ok(value);
+        }
+        if (PERF_BASE..PERF_END).contains(&addr) {
+            let offset = (addr - PERF_BASE) as u8;
+            return Ok(self.perf.read(offset, self.cycles, self.instructions));
+        }
+        let value = self.read_mem(addr)?;
+        if addr == VIDEO_STATUS {
+            self.vblank = false;
+        }
+        Ok(value)
+    }
+
+    fn store_register(&mut self, addr: u16, value: u16) -> Result<(), VmError> {
+        let [lo, hi] = value.to_le_bytes();
+        self.write_mem(addr, lo)?;
+        if self.register_width == RegisterWidth::Sixteen {
+            self.write_mem(addr.wrapping_add(1), hi)?;
+        }
+        Ok(())
+    }
+
+    // Pointers are a register pair with 8-bit registers, so they reach all 64 KiB either way.
+    fn pointer(&self, byte: u8) -> Result<Register, VmError> {
+        match self.register_width {
+            RegisterWidth::Eight => pair(byte),
+            RegisterWidth::Sixteen => Ok(Register::try_from(byte)?),
+        }
+    }
+
+    fn read_pointer(&self, index: usize) -> u16 {
+        match self.register_width {
+            RegisterWidth::Eight => self.read_pair(index),
+            RegisterWidth::Sixteen => self.registers[index],
+        }
+    }
+
+    fn write_pointer(&mut self, index: usize, value: u16) {
+        match self.register_width {
+            RegisterWidth::Eight => self.write_pair(index, value),
+            RegisterWidth::Sixteen => self.registers[index] = value,
+        }
+    }
+
+    // Loads through the pointer at `pointer` and steps it past what was read. The pointer
+    // is written last, so it wins over the value when `dst` is part of it.
+    fn load_post_increment(&mut self, dst: usize, pointer: usize) -> Result<(), VmError> {
+        let addr = self.read_pointer(pointer);
+        self.registers[dst] = self.load_register(addr)?;
+        self.write_pointer(pointer, addr.wrapping_add(self.register_width.bytes()));
+        Ok(())
+    }
+
+    // Stores `src` as it was before the pointer moved, should it be part of the pointer.
+    fn store_post_increment(&mut self, pointer: usize, src: usize) -> Result<(), VmError> {
+        let addr = self.read_pointer(pointer);
+        self.store_register(addr, self.registers[src])?;
+        self.write_pointer(pointer, addr.wrapping_add(self.register_width.bytes()));
+        Ok(())
+    }
+
+    // `sp + offset` against sp as it is now, which has to leave a whole register's worth of
+    // stack at the address. Past STACK_TOP nothing was pushed, so reaching it underflows.
+    fn stack_slot(&self, offset: u8) -> Result<u16, VmError> {
+        let addr = self.sp as u32 + offset as u32;
+        if self.sp < STACK_LIMIT || addr + self.register_width.bytes() as u32 > STACK_TOP as u32 {
+            return Err(VmError::StackUnderflow {
+                sp: self.sp,
+                pc: self.pc,
+            });
+        }
+        Ok(addr as u16)
+    }
+
+    // Entry `index` of the table of little-endian targets at `table`. A slot past the end of
+    // the address space faults rather than wrapping around to the start.
+    fn jump_table_entry(&mut self, table: u16, index: u16) -> Result<u16, VmError> {
+        let slot = table as u32 + index as u32 * 2;
+        if slot + 1 > 0xFFFF {
+            return Err(VmError::RangeOutOfBounds {
+                start: table,
+                len: (index as u32 * 2 + 2).min(u16::MAX as u32) as u16,
+                pc: self.pc,
+            });
+        }
+        let lo = self.guest_read(slot as u16)?;
+        let hi = self.guest_read(slot as u16 + 1)?;
+        Ok(u16::from_le_bytes([lo, hi]))
+    }
+
+    // One byte whatever the register width, through the bus and MMIO on both sides.
+    fn move_byte(&mut self, dst: u16, src: u16) -> Result<(), VmError> {
+        let value = self.guest_read(src)?;
+        self.write_mem(dst, value)
+    }
+
+    fn update_zero_flag(&mut self, result: u16) {
+        self.flags = (self.flags & !FLAG_ZERO) | if result == 0 { FLAG_ZERO } else { 0 };
+    }
+
+    fn update_carry_flag(&mut self, carry: bool) {
+        self.flags = (self.flags & !FLAG_CARRY) | if carry { FLAG_CARRY } else { 0 };
+    }
+
+    fn update_zero_and_carry_flags(&mut self, result: u16, carry: bool) {
+        self.flags = (self.flags & !(FLAG_ZERO | FLAG_CARRY))
+            | ((result == 0) as u8 * FLAG_ZERO)
+            | (carry as u8 * FLAG_CARRY);
+    }
+
+    fn bit_index(&self, bit: u8) -> Result<u8, VmError> {
+        if bit < self.register_width.bits() {
+            Ok(bit)
+        } else {
+            Err(VmError::InvalidBitIndex { bit, pc: self.pc })
+        }
+    }
+
+    // `btst` sets the zero flag when the bit is clear, so `jrnz` follows a set bit.
+    fn bit_operation(&mut self, opcode: u8, index: usize, bit: u8) {
+        let mask = 1u16 << bit;
+        match opcode {
+            op if op == OpcodeType::Bset as u8 => self.registers[index] |= mask,
+            op if op == OpcodeType::Bclr as u8 => self.registers[index] &= !mask,
+            _ => self.update_zero_flag(self.registers[index] & mask),
+        }
+    }
+
+    // The 8.8 value in the pair starting at `index`, one byte from each register whatever
+    // the register width.
+    fn read_pair(&self, index: usize) -> u16 {
+        u16::from_be_bytes([self.registers[index] as u8, self.registers[index + 1] as u8])
+    }
+
+    fn write_pair(&mut self, index: usize, value: u16) {
+        let [high, low] = value.to_be_bytes();
+        self.registers[index] = high as u16;
+        self.registers[index + 1] = low as u16;
+    }
+
+    // fxmul, fxdiv or fxmuli of the pair at `index` by `value`, which sets carry when the
+    // integer part overflows and keeps the low 16 bits of the result.
+    fn fixed_operation(&mut self, opcode: u8, index: usize, value: u16) -> Result<(), VmError> {
+        let current = self.read_pair(index) as i16;
+        let (result, carry) = if opcode == FXDIV {
+            fixed::div(current, value as i16).ok_or(VmError::DivisionByZero { pc: self.pc })?
+        } else {
+            fixed::mul(current, value as i16)
+        };
+        self.write_pair(index, result as u16);
+        self.update_zero_and_carry_flags(result as u16, carry);
+        Ok(())
+    }
+
+    fn branch_taken(&self, opcode: u8) -> bool {
+        let zero = self.flags & FLAG_ZERO != 0;
+        match opcode {
+            op if op == OpcodeType::Jrz as u8 => zero,
+            op if op == OpcodeType::Jrnz as u8 => !zero,
+            _ => true,
+        }
+    }
+
+    pub fn run(&mut self) -> Result<(), VmError> {
+        while !self.halted {
+            self.execute_instruction()?;
+        }
+        Ok(())
+    }
+
+    pub fn run_with_limits(&mut self, max_instructions: u64) -> Result<HaltReason, VmError> {
+        for _ in 0..max_instructions {
+            if self.halted {
+                return Ok(self.halt_reason());
+            }
+            self.execute_instruction()?;
+        }
+
+        Ok(if self.halted {
+            self.halt_reason()
+        } else {
+            HaltReason::InstructionLimit
+        })
+    }
+
+    pub fn get_reg(&self, reg: Register) -> u16 {
+        self[reg]
+    }
+
+    // Bits above the register width are dropped.
+    pub fn set_reg(&mut self, reg: Register, value: u16) {
+        self[reg] = value & self.register_width.mask();
+    }
+
+    /// Each register with its value, r0 first.
+    ///
+    /// ```
+    /// use microcvm_rs::MicroCvm;
+    /// use microcvm_rs::cpu::Register;
+    ///
+    /// let mut vm = MicroCvm::builder().build();
+    /// vm.cpu_mut()[Register::R2] = 42;
+    /// let (reg, value) = vm.cpu().registers().nth(2).unwrap();
+    /// assert_eq!((reg.name(), value), ("r2", 42));
+    /// ```
+    pub fn registers(&self) -> impl Iterator<Item = (Register, u16)> + '_ {
+        Register::ALL.into_iter().map(|reg| (reg, self[reg]))
+    }
+
+    // A program reports how it went by halting with a code in r0, 0 for success. Nothing
+    // runs after `hlt` to change it. A 16-bit r0 is clamped to 255, so no failure reads as 0.
+    pub fn exit_code(&self) -> u8 {
+        self.registers[0].min(u8::MAX as u16) as u8
+    }
+
+    pub fn halt_reason(&self) -> HaltReason {
+        HaltReason::Halted {
+            code: self.exit_code(),
+        }
+    }
+
+    pub fn set_trace_sink(&mut self, sink: Option<Box<dyn TraceSink>>) {
+        self.trace = sink;
+    }
+
+    // Keeps the last `len` instructions for fault reports, none when `len` is 0. Like a
+    // trace sink, a history takes every instruction down the decoding path.
+    pub fn set_fault_history(&mut self, len: usize) {
+        self.history_len = len;
+        let excess = self.history.len().saturating_sub(len);
+        self.history.drain(..excess);
+        self.history.shrink_to(len);
+    }
+
+    pub fn register_hcall(&mut self, number: u8, handler: HcallHandler) {
+        self.hcalls.insert(number, handler);
+    }
+
+    /// Gives `opcode`, one of [`EXTENSION_OPCODES`], to `def.handler`. The decoder takes
+    /// `def.operand_bytes` bytes after the opcode as the instruction's operands and passes
+    /// them to the handler each time it runs.
+    ///
+    /// ```
+    /// use microcvm_rs::MicroCvm;
+    /// use microcvm_rs::cpu::Register;
+    /// use microcvm_rs::extension::{ExtensionDef, ExtensionError};
+    ///
+    /// let mut vm = MicroCvm::builder().build();
+    /// let double = ExtensionDef::new(1, Box::new(|cpu, operands| {
+    ///     let reg = Register::try_from(operands[0]).unwrap();
+    ///     cpu.set_reg(reg, cpu.reg(reg) * 2);
+    ///     Ok(())
+    /// }));
+    /// vm.cpu_mut().register_extension(0xE3, double).unwrap();
+    /// // mov r2, 21; double r2; hlt
+    /// vm.load_program(&[0x06, 0x02, 21, 0xE3, 0x02, 0xFF]).unwrap();
+    /// vm.run().unwrap();
+    /// assert_eq!(vm.cpu().registers[2], 42);
+    ///
+    /// let again = ExtensionDef::new(0, Box::new(|_, _| Ok(())));
+    /// assert_eq!(
+    ///     vm.cpu_mut().register_extension(0xE3, again).unwrap_err(),
+    ///     ExtensionError::AlreadyRegistered(0xE3)
+    /// );
+    /// ```
+    pub fn register_extension(
+        &mut self,
+        opcode: u8,
+        def: ExtensionDef,
+    ) -> Result<(), ExtensionError> {
+        self.extensions.register(opcode, def)
+    }
+
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    fn extension(&mut self, opcode: u8, operands: &[u8]) -> Result<(), VmError> {
+        let Some(mut def) = self.extensions.defs.remove(&opcode) else {
+            return Err(VmError::InvalidOpcode(InvalidOpcode(opcode)));
+        };
+        let result = (def.handler)(&mut CpuView::new(self), operands);
+        self.extensions.defs.insert(opcode, def);
+        result
+    }
+
+    fn hcall(&mut self, number: u8) -> Result<(), VmError> {
+        let Some(mut handler) = self.hcalls.remove(&number) else {
+            return Err(VmError::UnregisteredHcall {
+                number,
+                pc: self.pc,
+            });
+        };
+        let result = handler(&mut HcallContext::new(self));
+        // The handler may have registered a replacement for itself.
+        self.hcalls.entry(number).or_insert(handler);
+        result
+    }
+
+    pub fn translate(&self, addr: u16) -> usize {
+        match addr {
+            BANK_WINDOW_START..BANK_WINDOW_END => {
+                self.bank as usize * BANK_SIZE + (addr - BANK_WINDOW_START) as usize
+            }
+            _ => addr as usize,
+        }
+    }
+
+    // Physical memory, every bank included.
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    // Flushes the decode cache, since whatever is written could be code.
+    pub fn memory_mut(&mut self) -> &mut [u8] {
+        self.flush_decode_cache();
+        &mut self.memory
+    }
+
+    // Replaces memory with `memory`, resizing it if that is another size, for restoring a
+    // snapshot of a machine built with a different one.
+    pub(crate) fn replace_memory(&mut self, memory: &[u8]) {
+        if self.memory.len() == memory.len() {
+            self.memory_mut().copy_from_slice(memory);
+        } else {
+            self.memory = memory.into();
+            self.flush_decode_cache();
+        }
+    }
+
+    pub fn read_mem(&self, addr: u16) -> Result<u8, VmError> {
+        if let Some(value) = self.bus.peek(addr) {
+            return Ok(value);
+        }
+        match addr {
+            MMIO_BASE.. => Ok(self.read_mmio(addr)),
+            _ if self.in_framebuffer_window(addr) => self.read_framebuffer(addr),
+            _ => self
+                .memory
+                .get(self.translate(addr))
+                .copied()
+                .ok_or(VmError::AddressOutOfBounds { addr, pc: self.pc }),
+        }
+    }
+
+    pub fn write_mem(&mut self, addr: u16, value: u8) -> Result<(), VmError> {
+        if self.bus.write(addr, value, self.cycles) {
+            return Ok(());
+        }
+        match addr {
+            MMIO_BASE.. => self.write_mmio(addr, value),
+            _ if self.in_framebuffer_window(addr) => self.write_framebuffer(addr, value)?,
+            _ => {
+                let physical = self.translate(addr);
+                if self.write_protected.contains(physical) {
+                    return Err(VmError::WriteProtected { addr, pc: self.pc });
+                }
+                match self.memory.get_mut(physical) {
+                    Some(byte) => *byte = value,
+                    None => return Err(VmError::AddressOutOfBounds { addr, pc: self.pc }),
+                }
+                self.invalidate_decoded(physical..physical + 1);
+            }
+        }
+        Ok(())
+    }
+
+    fn in_framebuffer_window(&self, addr: u16) -> bool {
+        self.framebuffer_window
+            .as_ref()
+            .is_some_and(|window| window.contains(addr))
+    }
+
+    // The byte of video memory behind a window address, checked against its size since the
+    // last bank may only be partly covered.
+    fn framebuffer_byte(&self, addr: u16) -> Result<usize, VmError> {
+        let offset = self
+            .framebuffer_window
+            .as_ref()
+            .map_or(usize::MAX, |window| window.byte_offset(addr));
+        if offset >= self.video_memory.bytes().len() {
+            return Err(VmError::AddressOutOfBounds { addr, pc: self.pc });
+        }
+        Ok(offset)
+    }
+
+    fn read_framebuffer(&self, addr: u16) -> Result<u8, VmError> {
+        let offset = self.framebuffer_byte(addr)?;
+        Ok(self.video_memory.bytes()[offset])
+    }
+
+    fn write_framebuffer(&mut self, addr: u16, value: u8) -> Result<(), VmError> {
+        let offset = self.framebuffer_byte(addr)?;
+        self.video_memory.bytes_mut()[offset] = value;
+        self.mark_bytes_dirty(offset..offset + 1);
+        Ok(())
+    }
+
+    fn read_mmio(&self, addr: u16) -> u8 {
+        match addr {
+            AUDIO_BASE..AUDIO_END => self.audio.read((addr - AUDIO_BASE) as u8),
+            WATCHDOG_BASE..WATCHDOG_END => self
+                .watchdog
+                .read((addr - WATCHDOG_BASE) as u8, self.cycles),
+            BANK_SELECT => self.bank,
+            FB_BANK_SELECT => self
+                .framebuffer_window
+                .as_ref()
+                .map_or(0, |window| window.bank),
+            VIDEO_STATUS => self.vblank as u8 * VIDEO_STATUS_VBLANK,
+            VIDEO_CONTROL => self.video_control,
+            VIDEO_DEPTH => self.video_memory.depth.bits(),
+            PALETTE_INDEX => self.palette_index,
+            VIDEO_BRIGHTNESS => self.brightness,
+            VIDEO_LETTERBOX..VIDEO_LETTERBOX_END => {
+                self.letterbox[(addr - VIDEO_LETTERBOX) as usize]
+            }
+            PALETTE_DATA..PALETTE_DATA_END => {
+                let color = self.video_memory.palette[self.palette_index as usize];
+                color.to_rgba()[(addr - PALETTE_DATA) as usize]
+            }
+            DMA_BASE..DMA_END => self.dma.read((addr - DMA_BASE) as u8),
+            INTERRUPT_BASE..INTERRUPT_END => self.interrupts.read((addr - INTERRUPT_BASE) as u8),
+            PERF_BASE..PERF_END => self.perf.peek((addr - PERF_BASE) as u8),
+            PCM_BASE..PCM_END => self.pcm.read((addr - PCM_BASE) as u8),
+            FAULT_BASE..FAULT_END => self.fault.read((addr - FAULT_BASE) as u8),
+            #[cfg(feature = "net")]
+            NET_BASE..NET_END => self.net.read((addr - NET_BASE) as u8),
+            #[cfg(feature = "std")]
+            FS_BASE..FS_END => self.files.read((addr - FS_BASE) as u8),
+            #[cfg(feature = "std")]
+            NVRAM_BASE..NVRAM_END => self
+                .nvram
+                .as_ref()
+                .map_or(0, |nvram| nvram.read((addr - NVRAM_BASE) as u8)),
+            _ => 0,
+        }
+    }
+
+    fn write_mmio(&mut self, addr: u16, value: u8) {
+        match addr {
+            AUDIO_BASE..AUDIO_END => self.audio.write((addr - AUDIO_BASE) as u8, value),
+            WATCHDOG_BASE..WATCHDOG_END => {
+                self.watchdog
+                    .write((addr - WATCHDOG_BASE) as u8, value, self.cycles)
+            }
+            // Bank numbers wrap around the available physical memory.
+            BANK_SELECT => self.bank = (value as usize % BANK_COUNT) as u8,
+            FB_BANK_SELECT => {
+                let bytes = self.video_memory.bytes().len();
+                if let Some(window) = self.framebuffer_window.as_mut() {
+                    window.bank = (value as usize % window.bank_count(bytes)) as u8;
+                }
+            }
+            VIDEO_CONTROL => {
+                let control = value & (VIDEO_CONTROL_TILES | VIDEO_CONTROL_LETTERBOX);
+                // The screen switches between video memory and the map.
+                if (control ^ self.video_control) & VIDEO_CONTROL_TILES != 0 {
+                    self.mark_screen_dirty();
+                }
+                self.video_control = control;
+            }
+            VIDEO_LETTERBOX..VIDEO_LETTERBOX_END => {
+                self.letterbox[(addr - VIDEO_LETTERBOX) as usize] = value
+            }
+            VIDEO_DEPTH => {
+                if let Some(depth) = ColorDepth::from_bits(value) {
+                    self.video_memory.set_depth(depth);
+                    self.mark_screen_dirty();
+                }
+            }
+            PALETTE_INDEX => self.palette_index = value,
+            VIDEO_BRIGHTNESS => {
+                self.brightness = value;
+                self.fade = None;
+            }
+            PALETTE_DATA..PALETTE_DATA_END => {
+                let color = &mut self.video_memory.palette[self.palette_index as usize];
+                match addr - PALETTE_DATA {
+                    0 => color.r = value,
+                    1 => color.g = value,
+                    _ => color.b = value,
+                }
+                // Any pixel may show the entry, and only 4 and 8 bits use the palette.
+                if matches!(
+                    self.video_memory.depth,
+                    ColorDepth::Four | ColorDepth::Eight
+                ) {
+                    self.mark_screen_dirty();
+                }
+            }
+            DMA_BASE..DMA_END => {
+                let start = self.dma.write((addr - DMA_BASE) as u8, value);
+                if start {
+                    self.run_dma();
+                }
+            }
+            INTERRUPT_BASE..INTERRUPT_END => {
+                self.interrupts.write((addr - INTERRUPT_BASE) as u8, value)
+            }
+            // Handing samples over costs a cycle each, like a DMA transfer.
+            PCM_BASE..PCM_END => {
+                let commit = self.pcm.write((addr - PCM_BASE) as u8, value);
+                if commit {
+                    self.cycles += self.pcm.commit(&self.memory) as u64;
+                }
+            }
+            #[cfg(feature = "net")]
+            NET_BASE..NET_END => {
+                if let Some(command) = self.net.write((addr - NET_BASE) as u8, value) {
+                    self.net.status = self.run_net_command(command);
+                }
+            }
+            #[cfg(feature = "std")]
+            FS_BASE..FS_END => {
+                if let Some(command) = self.files.write((addr - FS_BASE) as u8, value) {
+                    self.files.status = self.run_file_command(command);
+                }
+            }
+            #[cfg(feature = "std")]
+            NVRAM_BASE..NVRAM_END => {
+                let commit = self
+                    .nvram
+                    .as_mut()
+                    .is_some_and(|nvram| nvram.write((addr - NVRAM_BASE) as u8, value));
+                if commit {
+                    let _ = self.commit_nvram();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Video operations take their parameters from consecutive registers starting at
+    // `base`, e.g. `video fillrect, r0` reads x, y, w, h, r, g, b from r0..r6.
+    fn video(&mut self, video: VideoOpcode) -> Result<(), VmError> {
+        let base = video.base;
+        match video.opcode_type {
+            VideoOpcodeType::Fill => {
+                let [r, g, b] = self.register_block(base)?;
+                let value = self.video_memory.encode(r, g, b);
+                self.video_memory.fill(0, self.video_memory.len(), value);
+                self.cycles += self.video_memory.len() as u64;
+                self.mark_screen_dirty();
+            }
+            VideoOpcodeType::Clear => {
+                self.video_memory.fill(0, self.video_memory.len(), 0);
+                self.cycles += self.video_memory.len() as u64;
+                self.mark_screen_dirty();
+            }
+            VideoOpcodeType::SetPixel => {
+                let [x, y, r, g, b] = self.register_block(base)?;
+                let value = self.video_memory.encode(r, g, b);
+                self.fill_rect(x as u32, y as u32, 1, 1, value);
+            }
+            VideoOpcodeType::FillRect => {
+                let [x, y, w, h, r, g, b] = self.register_block(base)?;
+                let value = self.video_memory.encode(r, g, b);
+                self.fill_rect(x as u32, y as u32, w as u32, h as u32, value);
+            }
+            VideoOpcodeType::Vsync => self.frame_done = true,
+            VideoOpcodeType::FadeTo => {
+                let [target, steps] = self.register_block(base)?;
+                if steps == 0 {
+                    self.brightness = target;
+                    self.fade = None;
+                } else {
+                    self.fade = Some(Fade { target, steps });
+                }
+            }
+            VideoOpcodeType::CopyRect => {
+                let [src_x, src_y, dst_x, dst_y, w, h] = self.register_block(base)?;
+                let (src, dst) = ((src_x as u32, src_y as u32), (dst_x as u32, dst_y as u32));
+                self.copy_rect(src, dst, w as u32, h as u32);
+            }
+        }
+        Ok(())
+    }
+
+    // `memset rN` reads dst (lo, hi), value and len (lo, hi) from rN..rN+4.
+    fn memset(&mut self, base: Register) -> Result<(), VmError> {
+        let [dst_lo, dst_hi, value, len_lo, len_hi] = self.register_block(base)?;
+        let dst = u16::from_le_bytes([dst_lo, dst_hi]);
+        let len = u16::from_le_bytes([len_lo, len_hi]);
+        let target = self.writable_range(dst, len)?;
+        self.memory[target.clone()].fill(value);
+        self.invalidate_decoded(target);
+        self.cycles += len as u64;
+        Ok(())
+    }
+
+    // `memcpy rN` reads dst (lo, hi), src (lo, hi) and len (lo, hi) from rN..rN+5.
+    // Overlapping ranges copy as if through a temporary buffer.
+    fn memcpy(&mut self, base: Register) -> Result<(), VmError> {
+        let [dst_lo, dst_hi, src_lo, src_hi, len_lo, len_hi] = self.register_block(base)?;
+        let dst = u16::from_le_bytes([dst_lo, dst_hi]);
+        let src = u16::from_le_bytes([src_lo, src_hi]);
+        let len = u16::from_le_bytes([len_lo, len_hi]);
+        let source = self.block_range(src, len)?;
+        let target = self.writable_range(dst, len)?;
+        self.memory.copy_within(source, target.start);
+        self.invalidate_decoded(target);
+        self.cycles += len as u64;
+        Ok(())
+    }
+
+    // Physical range behind `len` guest bytes at `start`. The bytes have to map onto
+    // one contiguous stretch of memory, so a range may not run into MMIO or across
+    // either edge of the bank window.
+    fn block_range(&self, start: u16, len: u16) -> Result<Range<usize>, VmError> {
+        let fault = VmError::RangeOutOfBounds {
+            start,
+            len,
+            pc: self.pc,
+        };
+        if len == 0 {
+            return Ok(0..0);
+        }
+        let end = start as u32 + len as u32;
+        let region_end = match start {
+            ..BANK_WINDOW_START => BANK_WINDOW_START,
+            BANK_WINDOW_START..BANK_WINDOW_END => BANK_WINDOW_END,
+            _ => MMIO_BASE,
+        };
+        if end > region_end as u32 {
+            return Err(fault);
+        }
+        if let Some(window) = &self.framebuffer_window
+            && (start as u32) < window.range().end as u32
+            && end > window.start as u32
+        {
+            return Err(fault);
+        }
+        let physical = self.translate(start);
+        let range = physical..physical + len as usize;
+        if range.end > self.memory.len() {
+            return Err(fault);
+        }
+        Ok(range)
+    }
+
+    fn writable_range(&self, start: u16, len: u16) -> Result<Range<usize>, VmError> {
+        let range = self.block_range(start, len)?;
+        if let Some(physical) = self.write_protected.first_in(range.clone()) {
+            return Err(VmError::WriteProtected {
+                addr: start.wrapping_add((physical - range.start) as u16),
+                pc: self.pc,
+            });
+        }
+        Ok(range)
+    }
+
+    fn register_block<const N: usize>(&self, base: Register) -> Result<[u8; N], VmError> {
+        let start = base as usize;
+        let Some(block) = self.registers.get(start..start + N) else {
+            return Err(VmError::InvalidRegister(InvalidRegister(
+                (start + N - 1) as u8,
+            )));
+        };
+        Ok(core::array::from_fn(|i| block[i] as u8))
+    }
+
+    // Fills the part of the rectangle that lies on screen, at one cycle per pixel. `value`
+    // is a pixel as `VideoMemory::encode` returns it.
+    pub fn fill_rect(&mut self, x: u32, y: u32, width: u32, height: u32, value: u32) {
+        let screen_width = self.video_width as usize;
+        if screen_width == 0 {
+            return;
+        }
+        let screen_height = self.video_memory.len() / screen_width;
+        let (x, y) = (x as usize, y as usize);
+        let x_end = (x + width as usize).min(screen_width);
+        let y_end = (y + height as usize).min(screen_height);
+
+        for row in y..y_end {
+            let start = row * screen_width;
+            if x < x_end {
+                self.video_memory.fill(start + x, start + x_end, value);
+                self.cycles += (x_end - x) as u64;
+            }
+        }
+        if x < x_end && y < y_end {
+            let rect = Rect::new(x as u32, y as u32, (x_end - x) as u32, (y_end - y) as u32);
+            self.mark_dirty(rect);
+        }
+    }
+
+    // The smallest rectangle holding every pixel the guest changed since the last call, or
+    // `None` if it changed none. Pixels count as changed when something wrote them, even
+    // with the value they already had. Writes a host makes through `video_memory` itself
+    // aren't seen.
+    pub fn take_dirty(&mut self) -> Option<Rect> {
+        self.dirty.take()
+    }
+
+    // What `take_dirty` would return, without resetting it.
+    pub fn dirty(&self) -> Option<Rect> {
+        self.dirty
+    }
+
+    // Adds `rect`, which has to lie on screen, to the dirty region.
+    pub fn mark_dirty(&mut self, rect: Rect) {
+        if rect.is_empty() {
+            return;
+        }
+        self.dirty = Some(self.dirty.map_or(rect, |dirty| dirty.union(rect)));
+    }
+
+    pub fn mark_screen_dirty(&mut self) {
+        let width = self.video_width;
+        if width > 0 {
+            let height = (self.video_memory.len() / width as usize) as u32;
+            self.mark_dirty(Rect::new(0, 0, width, height));
+        }
+    }
+
+    // Marks pixels `start..end`, counted along the rows. Pixels on one row mark just their
+    // span, pixels on more than one mark the whole width of every row they reach.
+    fn mark_pixels_dirty(&mut self, pixels: Range<usize>) {
+        let width = self.video_width as usize;
+        let end = pixels.end.min(self.video_memory.len());
+        if width == 0 || pixels.start >= end {
+            return;
+        }
+        let (first, last) = (pixels.start / width, (end - 1) / width);
+        let rect = if first == last {
+            Rect::new(
+                (pixels.start % width) as u32,
+                first as u32,
+                (end - pixels.start) as u32,
+                1,
+            )
+        } else {
+            Rect::new(0, first as u32, width as u32, (last - first + 1) as u32)
+        };
+        self.mark_dirty(rect);
+    }
+
+    // Marks every pixel stored partly or wholly in `bytes` of video memory.
+    fn mark_bytes_dirty(&mut self, bytes: Range<usize>) {
+        let bits = self.video_memory.depth.bits() as usize;
+        self.mark_pixels_dirty(bytes.start * 8 / bits..(bytes.end * 8).div_ceil(bits));
+    }
+
+    // Copies a rectangle of video memory from `src` to `dst`, both (x, y), at one cycle per
+    // pixel. Only the part that lies on screen at both ends is copied, and only the
+    // destination is dirtied.
+    pub fn copy_rect(&mut self, src: (u32, u32), dst: (u32, u32), width: u32, height: u32) {
+        let screen_width = self.video_width as usize;
+        if screen_width == 0 {
+            return;
+        }
+        let screen_height = self.video_memory.len() / screen_width;
+        let (src_x, src_y) = (src.0 as usize, src.1 as usize);
+        let (dst_x, dst_y) = (dst.0 as usize, dst.1 as usize);
+        let requested_width = width as usize;
+        let width = requested_width
+            .min(screen_width.saturating_sub(src_x))
+            .min(screen_width.saturating_sub(dst_x));
+        let clipped_height = (height as usize)
+            .min(screen_height.saturating_sub(src_y))
+            .min(screen_height.saturating_sub(dst_y));
+        if width < requested_width || clipped_height < height as usize {
+            log::warn!(
+                "copyrect of {}x{} from {:?} to {:?} clipped to {}x{} (pc {:#06x})",
+                requested_width,
+                height,
+                src,
+                dst,
+                width,
+                clipped_height,
+                self.pc
+            );
+        }
+        let height = clipped_height;
+
+        // Like memmove: when the rectangles overlap, start at the edge the copy moves
+        // towards, so every pixel is read before anything is written over it.
+        for j in 0..height {
+            let row = if dst_y > src_y { height - 1 - j } else { j };
+            for i in 0..width {
+                let column = if dst_y == src_y && dst_x > src_x {
+                    width - 1 - i
+                } else {
+                    i
+                };
+                let from = (src_y + row) * screen_width + src_x + column;
+                let to = (dst_y + row) * screen_width + dst_x + column;
+                let value = self.video_memory.raw(from);
+                self.video_memory.set_raw(to, value);
+            }
+        }
+        self.cycles += (width * height) as u64;
+        if width > 0 && height > 0 {
+            self.mark_dirty(Rect::new(
+                dst_x as u32,
+                dst_y as u32,
+                width as u32,
+                height as u32,
+            ));
+        }
+    }
+
+    // Sends from or receives into the NET_LEN bytes at NET_ADDR. Every failure becomes a
+    // status code for the guest, none of them fault.
+    #[cfg(feature = "net")]
+    fn run_net_command(&mut self, command: u8) -> u8 {
+        use crate::net::{
+            NET_BAD_BUFFER, NET_BAD_COMMAND, NET_OK, NET_RECEIVE, NET_SEND, NET_TRUNCATED,
+        };
+
+        let (addr, len) = (self.net.addr, self.net.len);
+        match command {
+            NET_SEND => {
+                let Ok(range) = self.block_range(addr, len) else {
+                    return NET_BAD_BUFFER;
+                };
+                self.cycles += len as u64;
+                self.net.send(&self.memory[range])
+            }
+            NET_RECEIVE => {
+                let Ok(target) = self.writable_range(addr, len) else {
+                    return NET_BAD_BUFFER;
+                };
+                let packet = match self.net.receive() {
+                    Ok(packet) => packet,
+                    Err(status) => return status,
+                };
+                let copied = packet.len().min(len as usize);
+                self.memory[target.start..target.start + copied].copy_from_slice(&packet[..copied]);
+                let received = packet.len();
+                self.invalidate_decoded(target.start..target.start + copied);
+                self.net.received = received as u16;
+                self.cycles += copied as u64;
+                if received > copied {
+                    NET_TRUNCATED
+                } else {
+                    NET_OK
+                }
+            }
+            _ => NET_BAD_COMMAND,
+        }
+    }
+
+    // Paths and data live in the FS_LEN bytes at FS_ADDR, like a DMA block. As with the
+    // network device, failures are reported to the guest and never fault.
+    #[cfg(feature = "std")]
+    fn run_file_command(&mut self, command: u8) -> u8 {
+        use crate::files::{
+            FS_BAD_BUFFER, FS_BAD_COMMAND, FS_CLOSE, FS_CREATE, FS_OPEN, FS_READ, FS_SEEK, FS_WRITE,
+        };
+
+        let (addr, len) = (self.files.addr, self.files.len);
+        match command {
+            FS_OPEN | FS_CREATE => match self.block_range(addr, len) {
+                Ok(range) => self.files.open(&self.memory[range], command == FS_CREATE),
+                Err(_) => FS_BAD_BUFFER,
+            },
+            FS_READ => match self.writable_range(addr, len) {
+                Ok(range) => {
+                    let status = self.files.read_file(&mut self.memory[range.clone()]);
+                    self.invalidate_decoded(range);
+                    self.cycles += self.files.result as u64;
+                    status
+                }
+                Err(_) => FS_BAD_BUFFER,
+            },
+            FS_WRITE => match self.block_range(addr, len) {
+                Ok(range) => {
+                    let status = self.files.write_file(&self.memory[range]);
+                    self.cycles += self.files.result as u64;
+                    status
+                }
+                Err(_) => FS_BAD_BUFFER,
+            },
+            FS_SEEK => self.files.seek(),
+            FS_CLOSE => self.files.close(),
+            _ => FS_BAD_COMMAND,
+        }
+    }
+
+    // Gives the guest the files under `root` through the registers at FS_BASE.
+    #[cfg(feature = "std")]
+    pub fn attach_fs(
+        &mut self,
+        root: impl AsRef<std::path::Path>,
+        read_only: bool,
+    ) -> std::io::Result<()> {
+        self.files.attach(root, read_only)
+    }
+
+    // Makes `len` bytes at guest address `start` read and write video memory, three bytes
+    // (red, green, blue) per pixel, starting with the first slice. FB_BANK_SELECT moves
+    // the window along the framebuffer. The window has to end before MMIO.
+    pub fn map_framebuffer(&mut self, start: u16, len: u16) -> Result<(), VmError> {
+        if len == 0 || start as u32 + len as u32 > MMIO_BASE as u32 {
+            return Err(VmError::RangeOutOfBounds {
+                start,
+                len,
+                pc: self.pc,
+            });
+        }
+        self.framebuffer_window = Some(FramebufferWindow::new(start, len));
+        Ok(())
+    }
+
+    // Backs `len` bytes at guest address `start` with `path`, loading what was saved there.
+    // A damaged file is not an error: the region starts zeroed and the warning says why.
+    #[cfg(feature = "std")]
+    pub fn attach_nvram_at(
+        &mut self,
+        path: impl Into<std::path::PathBuf>,
+        start: u16,
+        len: u16,
+    ) -> std::io::Result<Option<crate::nvram::NvramWarning>> {
+        let physical = match self.block_range(start, len) {
+            Ok(range) if len > 0 => range,
+            _ => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "NVRAM region is not in memory",
+                ));
+            }
+        };
+        // Whatever was attached before keeps what the guest wrote to it.
+        self.commit_nvram()?;
+        let path = path.into();
+        let (contents, warning) = crate::nvram::Nvram::load(&path, len as usize)?;
+        self.memory[physical.clone()].copy_from_slice(&contents);
+        self.invalidate_decoded(physical.clone());
+        log::debug!(
+            "attached NVRAM {} at {:#06x}, {} bytes",
+            path.display(),
+            start,
+            len
+        );
+        self.nvram = Some(crate::nvram::Nvram::new(path, start, len, physical));
+        Ok(warning)
+    }
+
+    // The default 256 byte region at DEFAULT_NVRAM_START.
+    #[cfg(feature = "std")]
+    pub fn attach_nvram(
+        &mut self,
+        path: impl Into<std::path::PathBuf>,
+    ) -> std::io::Result<Option<crate::nvram::NvramWarning>> {
+        use crate::nvram::{DEFAULT_NVRAM_LEN, DEFAULT_NVRAM_START};
+        self.attach_nvram_at(path, DEFAULT_NVRAM_START, DEFAULT_NVRAM_LEN)
+    }
+
+    // Writes the NVRAM region out to its file. Does nothing if none is attached.
+    #[cfg(feature = "std")]
+    pub fn commit_nvram(&mut self) -> std::io::Result<()> {
+        let Some(nvram) = self.nvram.as_mut() else {
+            return Ok(());
+        };
+        let result = nvram.save(&self.memory[nvram.physical.clone()]);
+        nvram.status = match result {
+            Ok(()) => crate::nvram::NVRAM_OK,
+            Err(_) => crate::nvram::NVRAM_COMMIT_FAILED,
+        };
+        result
+    }
+
+    fn halt(&mut self) {
+        self.halted = true;
+        #[cfg(feature = "std")]
+        let _ = self.commit_nvram();
+    }
+
+    // Lets the guest exchange datagrams with `remote` through the registers at NET_BASE.
+    #[cfg(feature = "net")]
+    pub fn attach_udp(
+        &mut self,
+        local: impl std::net::ToSocketAddrs,
+        remote: impl std::net::ToSocketAddrs,
+    ) -> std::io::Result<()> {
+        self.net.attach(local, remote)?;
+        if let Some(addr) = self.net.local_addr() {
+            log::debug!("attached UDP device on {}", addr);
+        }
+        Ok(())
+    }
+
+    // Maps `device` at `len` addresses from `start`, next to the built-in devices.
+    pub fn map_device(
+        &mut self,
+        start: u16,
+        len: u16,
+        device: Box<dyn Device>,
+    ) -> Result<(), BusError> {
+        self.bus.map(start, len, device)
+    }
+
+    // The first mapped device of type `T`, built-in or the host's.
+    pub fn device<T: Device>(&self) -> Option<&T> {
+        self.bus.get()
+    }
+
+    pub fn device_mut<T: Device>(&mut self) -> Option<&mut T> {
+        self.bus.get_mut()
+    }
+
+    // The registers at MAILBOX_BASE read as empty and drop writes until one is attached.
+    pub fn attach_mailbox(&mut self, mailbox: Mailbox) -> Result<(), BusError> {
+        self.bus.map(
+            MAILBOX_BASE,
+            MAILBOX_REGISTER_COUNT as u16,
+            Box::new(mailbox),
+        )
+    }
+
+    pub fn mailbox(&self) -> Option<&Mailbox> {
+        self.bus.get()
+    }
+
+    pub fn keyboard(&self) -> &Keyboard {
+        self.bus.get().expect(BUILTIN_DEVICE)
+    }
+
+    pub fn keyboard_mut(&mut self) -> &mut Keyboard {
+        self.bus.get_mut().expect(BUILTIN_DEVICE)
+    }
+
+    // Queues a key event stamped with the current cycle count, raising the keyboard
+    // interrupt if the guest has enabled it.
+    pub fn push_key(&mut self, scancode: u8, pressed: bool) {
+        let cycles = self.cycles;
+        if self.keyboard_mut().push(scancode, pressed, cycles) {
+            self.interrupts.raise(VECTOR_KEYBOARD);
+        }
+    }
+
+    pub fn rtc(&self) -> &Rtc {
+        self.bus.get().expect(BUILTIN_DEVICE)
+    }
+
+    pub fn rtc_mut(&mut self) -> &mut Rtc {
+        self.bus.get_mut().expect(BUILTIN_DEVICE)
+    }
+
+    pub fn sprites(&self) -> &SpriteTable {
+        self.bus.get().expect(BUILTIN_DEVICE)
+    }
+
+    pub fn sprites_mut(&mut self) -> &mut SpriteTable {
+        self.bus.get_mut().expect(BUILTIN_DEVICE)
+    }
+
+    pub fn tiles(&self) -> &TileLayer {
+        self.bus.get().expect(BUILTIN_DEVICE)
+    }
+
+    pub fn tiles_mut(&mut self) -> &mut TileLayer {
+        self.bus.get_mut().expect(BUILTIN_DEVICE)
+    }
+
+    // Brings the tile layer's image up to date for a `width` by `height` frame, adding
+    // what of the screen it changed to the dirty region.
+    pub fn update_tiles(&mut self, width: usize, height: usize) {
+        let tiles: &mut TileLayer = self.bus.get_mut().expect(BUILTIN_DEVICE);
+        tiles.update(&self.memory, width, height);
+        if let Some(rect) = tiles.dirty() {
+            self.mark_dirty(rect);
+        }
+    }
+
+    pub fn gamepad(&self) -> &Gamepad {
+        self.bus.get().expect(BUILTIN_DEVICE)
+    }
+
+    pub fn gamepad_mut(&mut self) -> &mut Gamepad {
+        self.bus.get_mut().expect(BUILTIN_DEVICE)
+    }
+
+    pub fn mouse(&self) -> &Mouse {
+        self.bus.get().expect(BUILTIN_DEVICE)
+    }
+
+    pub fn mouse_mut(&mut self) -> &mut Mouse {
+        self.bus.get_mut().expect(BUILTIN_DEVICE)
+    }
+
+    // Copies packed RGB bytes from physical memory into video memory, clipping the
+    // transfer to whichever buffer ends first.
+    pub fn run_dma(&mut self) {
+        let src = self.dma.src as usize;
+        let dst = self.dma.dst as usize;
+        let requested = self.dma.len as usize;
+
+        let src_pixels = self.memory.len().saturating_sub(src) / DMA_BYTES_PER_PIXEL;
+        let dst_pixels = self.video_memory.len().saturating_sub(dst);
+        let len = requested.min(src_pixels).min(dst_pixels);
+
+        if len > 0 {
+            let source = &self.memory[src..src + len * DMA_BYTES_PER_PIXEL];
+            for (pixel, rgb) in (dst..).zip(source.chunks_exact(DMA_BYTES_PER_PIXEL)) {
+                let value = self.video_memory.encode(rgb[0], rgb[1], rgb[2]);
+                self.video_memory.set_raw(pixel, value);
+            }
+            self.mark_pixels_dirty(dst..dst + len);
+        }
+
+        self.dma.status = if len < requested {
+            log::warn!(
+                "DMA of {} pixels from {:#06x} to pixel {} clipped to {} (pc {:#06x})",
+                requested,
+                src,
+                dst,
+                len,
+                self.pc
+            );
+            DMA_STATUS_CLIPPED
+        } else {
+            0
+        };
+        self.cycles += len as u64 * DMA_CYCLES_PER_PIXEL;
+    }
+
+    pub fn protect(&mut self, range: Range<usize>, protection: Protection) {
+        match protection {
+            Protection::ReadOnly => self.write_protected.insert(range),
+            Protection::NoExecute => self.no_execute.insert(range),
+        }
+    }
+
+    // Lifts every kind of protection from the range.
+    pub fn unprotect(&mut self, range: Range<usize>) {
+        self.write_protected.remove(range.clone());
+        self.no_execute.remove(range);
+    }
+
+    pub fn load_program(&mut self, bytes: &[u8]) -> Result<(), VmError> {
+        let (header, payload) = ProgramHeader::split(bytes)?;
+        if self.verify_checksums {
+            header.verify(payload)?;
+        }
+        if header.segmented() {
+            return self.load_segments(header, payload);
+        }
+        // A compressed payload is held to the length it claims, checked before any of it is
+        // written
+        let length = header.loaded_length(payload)?;
+        let start = header.load_address as usize;
+        let end = start + length;
+        if end > self.memory.len() {
+            return Err(VmError::ProgramTooLarge {
+                load_address: header.load_address,
+                length,
+            });
+        }
+        if header.compressed() {
+            self.unpack(start, length, payload)?;
+        } else {
+            self.memory[start..end].copy_from_slice(payload);
+            self.invalidate_decoded(start..end);
+        }
+        self.loaded.insert(start..end);
+
+        if header.protect_code() {
+            self.protect(start..end, Protection::ReadOnly);
+        }
+        if let Some(entry) = header.entry {
+            self.set_entry(entry);
+        }
+        log::debug!(
+            "loaded a {} byte program at {:#06x}{}",
+            length,
+            start,
+            if header.protect_code() {
+                ", code protected"
+            } else {
+                ""
+            }
+        );
+
+        Ok(())
+    }
+
+    // Places every segment of a segmented program, once all of them are known to fit
+    // without overlapping, and starts execution at the header's entry point.
+    fn load_segments(&mut self, header: ProgramHeader, payload: &[u8]) -> Result<(), VmError> {
+        let unpacked;
+        let table = if header.compressed() {
+            // The table can't usefully hold more than it has somewhere to put
+            let length = header.loaded_length(payload)?;
+            let limit = 2
+                + u16::MAX as usize * segment::ENTRY_LEN
+                + self.memory.len()
+                + self.video_memory.len();
+            if length > limit {
+                return Err(VmError::ProgramTooLarge {
+                    load_address: header.load_address,
+                    length,
+                });
+            }
+            unpacked = crate::program::unpack(length, payload)?;
+            &unpacked[..]
+        } else {
+            payload
+        };
+        let segments = segment::parse_table(table)?;
+        segment::check_segments(&segments, self.memory.len(), self.video_memory.len())?;
+
+        for segment in &segments {
+            let range = segment.offset as usize..segment.end() as usize;
+            match segment.kind {
+                SegmentKind::Code | SegmentKind::Data => {
+                    self.memory[range.clone()].copy_from_slice(&segment.bytes);
+                    self.loaded.insert(range.clone());
+                }
+                SegmentKind::Bss => self.memory[range.clone()].fill(0),
+                SegmentKind::Video => {
+                    self.video_memory.bytes_mut()[range.clone()].copy_from_slice(&segment.bytes);
+                    self.mark_bytes_dirty(range);
+                    continue;
+                }
+            }
+            self.invalidate_decoded(range.clone());
+            match segment.kind {
+                SegmentKind::Code if header.protect_code() => {
+                    self.protect(range, Protection::ReadOnly)
+                }
+                SegmentKind::Data | SegmentKind::Bss if header.no_execute_data() => {
+                    self.protect(range, Protection::NoExecute)
+                }
+                _ => {}
+            }
+        }
+        self.set_entry(header.segmented_entry());
+        log::debug!(
+            "loaded {} segments, entry at {:#06x}{}",
+            segments.len(),
+            header.segmented_entry(),
+            if header.protect_code() {
+                ", code protected"
+            } else {
+                ""
+            }
+        );
+        Ok(())
+    }
+
+    pub fn load_raw(&mut self, bytes: &[u8], offset: u16) -> Result<(), VmError> {
+        // The whole image is known up front, so refuse it before copying any of it
+        if offset as usize + bytes.len() > self.memory.len() {
+            return Err(VmError::ProgramTooLarge {
+                load_address: offset,
+                length: bytes.len(),
+            });
+        }
+        let mut rest = bytes;
+        self.fill_memory(offset as usize, bytes.len(), |buffer| {
+            let length = buffer.len().min(rest.len());
+            buffer[..length].copy_from_slice(&rest[..length]);
+            rest = &rest[length..];
+            Ok(length)
+        })?;
+        log::debug!("loaded {} raw bytes at {:#06x}", bytes.len(), offset);
+        Ok(())
+    }
+
+    // Streams everything `reader` holds into memory from `offset`, returning how many bytes
+    // that was. A reader holding more than fits stops with ImageTooLarge as soon as that
+    // shows, leaving memory filled up to the end.
+    #[cfg(feature = "std")]
+    pub fn load_from_reader(
+        &mut self,
+        mut reader: impl std::io::Read,
+        offset: usize,
+    ) -> Result<u64, VmError> {
+        let capacity = self.memory.len().saturating_sub(offset);
+        let loaded = self.fill_memory(offset, capacity, |buffer| {
+            read_retrying(&mut reader, buffer)
+        })?;
+        log::debug!("loaded {} bytes at {:#06x} from a reader", loaded, offset);
+        Ok(loaded as u64)
+    }
+
+    // Loads the file at address 0, like `load_from_reader`.
+    #[cfg(feature = "std")]
+    pub fn read_memory_from_file(&mut self, file_path: &str) -> Result<u64, VmError> {
+        self.load_from_reader(std::fs::File::open(file_path)?, 0)
+    }
+
+    // Streams a compressed payload's `length` bytes into memory from `start`, which the
+    // caller has checked they fit at. A stream that holds more stops at the end of them.
+    #[cfg(feature = "flate2")]
+    fn unpack(&mut self, start: usize, length: usize, payload: &[u8]) -> Result<(), VmError> {
+        let mut decoder = crate::program::gzip_decoder(payload);
+        let unpacked = self
+            .fill_memory(start, length, |buffer| read_retrying(&mut decoder, buffer))
+            .map_err(|e| match e {
+                VmError::ImageTooLarge { .. } => crate::program::length_mismatch(true),
+                e => e,
+            })?;
+        if unpacked != length {
+            return Err(crate::program::length_mismatch(false));
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "flate2"))]
+    fn unpack(&mut self, _start: usize, _length: usize, _payload: &[u8]) -> Result<(), VmError> {
+        Err(crate::program::NEEDS_FLATE2)
+    }
+
+    // Copies up to `capacity` bytes into memory from `offset`, which the caller has kept
+    // within memory, until `read`, which fills what it can of the buffer it is given like
+    // io::Read::read, returns 0. Only asks for one byte past the end, to tell a source that
+    // fits exactly from one that doesn't.
+    fn fill_memory(
+        &mut self,
+        offset: usize,
+        capacity: usize,
+        mut read: impl FnMut(&mut [u8]) -> Result<usize, VmError>,
+    ) -> Result<usize, VmError> {
+        let mut filled = 0;
+        let result = loop {
+            if filled == capacity {
+                break match read(&mut [0]) {
+                    Ok(0) => Ok(filled),
+                    Ok(_) => Err(VmError::ImageTooLarge { offset, capacity }),
+                    Err(e) => Err(e),
+                };
+            }
+            match read(&mut self.memory[offset + filled..offset + capacity]) {
+                Ok(0) => break Ok(filled),
+                Ok(read) => filled += read,
+                Err(e) => break Err(e),
+            }
+        };
+        self.invalidate_decoded(offset..offset + filled);
+        self.loaded.insert(offset..offset + filled);
+        result
+    }
+
+    // Starts execution at `entry`, which has to be in bytes some load put in memory by
+    // the time the machine runs, see `check_entry`.
+    pub fn set_entry(&mut self, entry: u16) {
+        self.pc = entry;
+        self.entry = Some(entry);
+    }
+
+    // Refuses to start at an entry point no load has put bytes at. Checked until it passes,
+    // so running again without fixing it fails again.
+    pub fn check_entry(&mut self) -> Result<(), VmError> {
+        if let Some(entry) = self.entry {
+            if !self.loaded.contains(entry as usize) {
+                return Err(VmError::EntryOutsideProgram { entry });
+            }
+            self.entry = None;
+        }
+        Ok(())
+    }
+}
+
+// An io::Read::read that tries again when interrupted.
+#[cfg(feature = "std")]
+fn read_retrying(reader: &mut impl std::io::Read, buffer: &mut [u8]) -> Result<usize, VmError> {
+    loop {
+        match reader.read(buffer) {
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            result => return Ok(result?),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for MicroCVMCpu {
+    fn drop(&mut self) {
+        let _ = self.commit_nvram();
+    }
+}
+
+impl Opcode {
+    pub fn empty() -> Self {
+        Self {
+            opcode_type: OpcodeType::Nop,
+            argument_count: 0,
+            arg1: None,
+            arg2: None,
+        }
+    }
+
+    // Encoded size in bytes. Only a wide `src` immediate makes it differ from the table.
+    pub fn length(&self) -> u16 {
+        let wide = self.opcode_type.takes_source_operand()
+            && matches!(self.arg2, Some(OpcodeArg2::WideImmediate(_)));
+        isa::length(self.opcode_type) + wide as u16
+    }
+
+    // The inverse of create_opcode: arguments are written in order, registers and
+    // immediates as one byte and addresses and wide immediates as two little-endian bytes.
+    pub fn encode(&self) -> Vec<u8> {
+        let src_register = self.opcode_type.takes_source_operand()
+            && matches!(self.arg2, Some(OpcodeArg2::Register(_)));
+
+        let mut bytes = vec![self.opcode_type as u8];
+        if let Some(arg1) = &self.arg1 {
+            match *arg1 {
+                OpcodeArg1::Register(reg) if src_register => bytes.push(reg as u8 | SRC_REGISTER),
+                OpcodeArg1::Register(reg) => bytes.push(reg as u8),
+                OpcodeArg1::Immediate(imm) => bytes.push(imm),
+                OpcodeArg1::Address(addr) => bytes.extend_from_slice(&addr.to_le_bytes()),
+                OpcodeArg1::Offset(offset) => bytes.push(offset as u8),
+            }
+        }
+        if let Some(arg2) = &self.arg2 {
+            match *arg2 {
+                OpcodeArg2::Register(reg) => bytes.push(reg as u8),
+                OpcodeArg2::Immediate(imm) => bytes.push(imm),
+                OpcodeArg2::WideImmediate(imm) => bytes.extend_from_slice(&imm.to_le_bytes()),
+                OpcodeArg2::Address(addr) => bytes.extend_from_slice(&addr.to_le_bytes()),
+                OpcodeArg2::Offset(offset) => bytes.push(offset as u8),
+            }
+        }
+        bytes
+    }
+}
+
+impl OpcodeType {
+    pub fn mnemonic(self) -> &'static str {
+        isa::info(self).mnemonic
+    }
+
+    pub fn from_mnemonic(mnemonic: &str) -> Option<Self> {
+        isa::from_mnemonic(mnemonic).map(|info| info.opcode)
+    }
+
+    // Instructions of the form `reg, src` where src is a register or an immediate.
+    pub fn takes_source_operand(self) -> bool {
+        isa::info(self).operands.contains(&OperandKind::Source)
+    }
+}
+
+impl Register {
+    pub const ALL: [Register; 8] = [
+        Register::R0,
+        Register::R1,
+        Register::R2,
+        Register::R3,
+        Register::R4,
+        Register::R5,
+        Register::R6,
+        Register::R7,
+    ];
+
+    // As the assembler spells it.
+    pub fn name(self) -> &'static str {
+        ["r0", "r1", "r2", "r3", "r4", "r5", "r6", "r7"][self as usize]
+    }
+}
+
+impl Display for Register {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+// The value is kept to the register width by whoever writes it, as `set_reg` does.
+impl Index<Register> for MicroCVMCpu {
+    type Output = u16;
+
+    fn index(&self, reg: Register) -> &u16 {
+        &self.registers[reg as usize]
+    }
+}
+
+impl IndexMut<Register> for MicroCVMCpu {
+    fn index_mut(&mut self, reg: Register) -> &mut u16 {
+        &mut self.registers[reg as usize]
+    }
+}
+
+impl Display for OpcodeArg1 {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            OpcodeArg1::Register(reg) => write!(f, "{}", reg),
+            OpcodeArg1::Immediate(imm) => write!(f, "{}", imm),
+            OpcodeArg1::Address(addr) => write!(f, "{:#06x}", addr),
+            OpcodeArg1::Offset(offset) => write!(f, "{:+}", offset),
+        }
+    }
+}
+
+impl Display for OpcodeArg2 {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            OpcodeArg2::Register(reg) => write!(f, "{}", reg),
+            OpcodeArg2::Immediate(imm) => write!(f, "{}", imm),
+            OpcodeArg2::WideImmediate(imm) => write!(f, "{}", imm),
+            OpcodeArg2::Address(addr) => write!(f, "{:#06x}", addr),
+            OpcodeArg2::Offset(offset) => write!(f, "{:+}", offset),
+        }
+    }
+}
+
+impl Display for Opcode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.opcode_type.mnemonic())?;
+        if let Some(arg1) = &self.arg1 {
+            write!(f, " {}", arg1)?;
+        }
+        if let Some(arg2) = &self.arg2 {
+            write!(f, ", {}", arg2)?;
+        }
+        Ok(())
+    }
+}
+
+impl RegisterWidth {
+    pub fn bits(self) -> u8 {
+        match self {
+            RegisterWidth::Eight => 8,
+            RegisterWidth::Sixteen => 16,
+        }
+    }
+
+    pub fn mask(self) -> u16 {
+        match self {
+            RegisterWidth::Eight => 0xFF,
+            RegisterWidth::Sixteen => 0xFFFF,
+        }
+    }
+
+    // What a load or store of a register moves.
+    pub fn bytes(self) -> u16 {
+        match self {
+            RegisterWidth::Eight => 1,
+            RegisterWidth::Sixteen => 2,
+        }
+    }
+}
+
+impl TryFrom<u8> for OpcodeType {
+    type Error = InvalidOpcode;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        isa::decode(value)
+            .map(|info| info.opcode)
+            .ok_or(InvalidOpcode(value))
+    }
+}
+
+impl VideoOpcodeType {
+    pub fn mnemonic(self) -> &'static str {
+        isa::video_info(self).mnemonic
+    }
+
+    pub fn from_mnemonic(mnemonic: &str) -> Option<Self> {
+        isa::video_from_mnemonic(mnemonic).map(|info| info.operation)
+    }
+}
+
+impl TryFrom<u8> for VideoOpcodeType {
+    type Error = InvalidVideoOpcode;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        isa::decode_video(value)
+            .map(|info| info.operation)
+            .ok_or(InvalidVideoOpcode(value))
+    }
+}
+
+const BUILTIN_DEVICE: &str = "built-in devices are mapped from the start";
+
+// The devices that only ever touch their own registers. The rest act on memory or the
+// CPU itself, so `read_mmio` and `write_mmio` handle them.
+fn builtin_bus() -> Bus {
+    let devices: [(u16, u8, Box<dyn Device>); 6] = [
+        (
+            KEYBOARD_BASE,
+            KEYBOARD_REGISTER_COUNT,
+            Box::new(Keyboard::default()),
+        ),
+        (RTC_BASE, RTC_REGISTER_COUNT, Box::new(Rtc::default())),
+        (
+            SPRITE_BASE,
+            SPRITE_REGISTER_COUNT,
+            Box::new(SpriteTable::default()),
+        ),
+        (
+            TILE_BASE,
+            TILE_REGISTER_COUNT,
+            Box::new(TileLayer::default()),
+        ),
+        (
+            GAMEPAD_BASE,
+            PAD_REGISTER_COUNT,
+            Box::new(Gamepad::default()),
+        ),
+        (MOUSE_BASE, MOUSE_REGISTER_COUNT, Box::new(Mouse::default())),
+    ];
+    let mut bus = Bus::new();
+    for (start, len, device) in devices {
+        bus.map(start, len as u16, device)
+            .expect("built-in devices don't overlap");
+    }
+    bus
+}
+
+// Lists how the fast path decodes and executes each opcode byte, once, and expands to the
+// two dense matches over it. Decoding fetches and checks every operand, leaving only
+// register sources to read when the instruction runs. Bytes in the extension range decode
+// to whatever the host registered there, and other bytes that aren't opcodes to an
+// InvalidOpcode fault.
+macro_rules! dispatch {
+    ($($opcode:ident => $decode:expr, $execute:expr;)*) => {
+        #[inline(always)]
+        fn decode<const WIDE: bool>(
+            cpu: &MicroCVMCpu,
+            pc: u16,
+            opcode: u8,
+        ) -> Result<Predecoded, VmError> {
+            match opcode {
+                $($opcode => $decode(cpu, pc, opcode),)*
+                EXTENSION_FIRST..=EXTENSION_LAST => decode_extension(cpu, pc, opcode),
+                _ => decode_invalid(cpu, pc, opcode),
+            }
+        }
+
+        #[inline(always)]
+        fn execute<const WIDE: bool>(
+            cpu: &mut MicroCVMCpu,
+            pc: u16,
+            instruction: Predecoded,
+        ) -> Result<(), VmError> {
+            match instruction.opcode {
+                $($opcode => $execute(cpu, pc, instruction),)*
+                EXTENSION_FIRST..=EXTENSION_LAST => execute_extension(cpu, pc, instruction),
+                _ => execute_invalid(cpu, pc, instruction),
+            }
+        }
+    };
+}
+
+dispatch! {
+    LOAD => decode_load, execute_load;
+    STORE => decode_store, execute_store;
+    MOVM => decode_movm, execute_movm;
+    LOAD_SP => decode_stack::<LOAD_SP>, execute_load_sp;
+    STORE_SP => decode_stack::<STORE_SP>, execute_store_sp;
+    LOAD_INC => decode_post_increment::<WIDE, LOAD_INC>, execute_load_inc;
+    STORE_INC => decode_post_increment::<WIDE, STORE_INC>, execute_store_inc;
+    ADD => decode_source::<WIDE>, execute_arithmetic::<WIDE, ADD>;
+    SUB => decode_source::<WIDE>, execute_arithmetic::<WIDE, SUB>;
+    JMP => decode_address, execute_jmp;
+    MOV => decode_source::<WIDE>, execute_arithmetic::<WIDE, MOV>;
+    INC => decode_register, execute_inc::<WIDE>;
+    DEC => decode_register, execute_dec::<WIDE>;
+    CLR => decode_register, execute_clr;
+    DIV => decode_source::<WIDE>, execute_arithmetic::<WIDE, DIV>;
+    MUL => decode_source::<WIDE>, execute_arithmetic::<WIDE, MUL>;
+    HCALL => decode_byte, execute_hcall;
+    VIDEO => decode_video, execute_video;
+    JR => decode_byte, execute_branch::<JR>;
+    JRZ => decode_byte, execute_branch::<JRZ>;
+    JRNZ => decode_byte, execute_branch::<JRNZ>;
+    DJNZ => decode_djnz, execute_djnz::<WIDE>;
+    TEST => decode_source::<WIDE>, execute_test;
+    BSET => decode_bit, execute_bit::<BSET>;
+    BCLR => decode_bit, execute_bit::<BCLR>;
+    BTST => decode_bit, execute_bit::<BTST>;
+    MEMSET => decode_register, execute_block::<MEMSET>;
+    MEMCPY => decode_register, execute_block::<MEMCPY>;
+    CALL => decode_address, execute_call;
+    JTAB => decode_load, execute_jtab;
+    RET => decode_none, execute_ret;
+    CLC => decode_none, execute_flag::<CLC>;
+    STC => decode_none, execute_flag::<STC>;
+    CMC => decode_none, execute_flag::<CMC>;
+    PUSHF => decode_none, execute_pushf;
+    POPF => decode_none, execute_popf;
+    EI => decode_none, execute_flag::<EI>;
+    DI => decode_none, execute_flag::<DI>;
+    INT => decode_byte, execute_int;
+    IRET => decode_none, execute_iret;
+    FXMUL => decode_pairs, execute_fixed::<FXMUL>;
+    FXDIV => decode_pairs, execute_fixed::<FXDIV>;
+    FXMULI => decode_fixed, execute_fixed::<FXMULI>;
+    NOP => decode_none, execute_nop;
+    HLT => decode_none, execute_hlt;
+}
+
+#[inline(always)]
+fn decode_invalid(_: &MicroCVMCpu, _: u16, opcode: u8) -> Result<Predecoded, VmError> {
+    Err(VmError::InvalidOpcode(InvalidOpcode(opcode)))
+}
+
+#[inline(always)]
+fn execute_invalid(_: &mut MicroCVMCpu, _: u16, instruction: Predecoded) -> Result<(), VmError> {
+    Err(VmError::InvalidOpcode(InvalidOpcode(instruction.opcode)))
+}
+
+#[inline(always)]
+fn decoded(opcode: u8, length: u8) -> Predecoded {
+    Predecoded {
+        opcode,
+        length,
+        ..Predecoded::EMPTY
+    }
+}
+
+#[inline(always)]
+fn decode_none(_: &MicroCVMCpu, _: u16, opcode: u8) -> Result<Predecoded, VmError> {
+    Ok(decoded(opcode, 1))
+}
+
+// `reg, src`, where src is a register or an immediate as wide as the registers.
+#[inline(always)]
+fn decode_source<const WIDE: bool>(
+    cpu: &MicroCVMCpu,
+    pc: u16,
+    opcode: u8,
+) -> Result<Predecoded, VmError> {
+    let dst = cpu.fetch(pc.wrapping_add(1))?;
+    let src = cpu.fetch(pc.wrapping_add(2))?;
+    let mut instruction = decoded(opcode, 3);
+    instruction.reg = register_index(dst & !SRC_REGISTER)? as u8;
+    if dst & SRC_REGISTER != 0 {
+        instruction.src_register = true;
+        instruction.operand = register_index(src)? as u16;
+    } else if WIDE {
+        let hi = cpu.fetch(pc.wrapping_add(3))?;
+        instruction.operand = u16::from_le_bytes([src, hi]);
+        instruction.length = 4;
+    } else {
+        instruction.operand = src as u16;
+    }
+    Ok(instruction)
+}
+
+#[inline(always)]
+fn decode_bit(cpu: &MicroCVMCpu, pc: u16, opcode: u8) -> Result<Predecoded, VmError> {
+    let reg = cpu.fetch(pc.wrapping_add(1))?;
+    let bit = cpu.fetch(pc.wrapping_add(2))?;
+    let mut instruction = decoded(opcode, 3);
+    instruction.reg = register_index(reg)? as u8;
+    instruction.operand = cpu.bit_index(bit)? as u16;
+    Ok(instruction)
+}
+
+// `reg, addr`, as load and jtab take them.
+#[inline(always)]
+fn decode_load(cpu: &MicroCVMCpu, pc: u16, opcode: u8) -> Result<Predecoded, VmError> {
+    let dst = cpu.fetch(pc.wrapping_add(1))?;
+    let addr = cpu.fetch_u16(pc.wrapping_add(2))?;
+    let mut instruction = decoded(opcode, 4);
+    instruction.reg = register_index(dst)? as u8;
+    instruction.operand = addr;
+    Ok(instruction)
+}
+
+#[inline(always)]
+fn decode_store(cpu: &MicroCVMCpu, pc: u16, opcode: u8) -> Result<Predecoded, VmError> {
+    let addr = cpu.fetch_u16(pc.wrapping_add(1))?;
+    let src = cpu.fetch(pc.wrapping_add(3))?;
+    let mut instruction = decoded(opcode, 4);
+    instruction.reg = register_index(src)? as u8;
+    instruction.operand = addr;
+    Ok(instruction)
+}
+
+// The pointer goes in `operand` and the other register in `reg`, whichever order the
+// instruction has them in. They are checked in that order too, so a bad one faults the way
+// create_opcode says.
+#[inline(always)]
+fn decode_post_increment<const WIDE: bool, const OPCODE: u8>(
+    cpu: &MicroCVMCpu,
+    pc: u16,
+    opcode: u8,
+) -> Result<Predecoded, VmError> {
+    let first = cpu.fetch(pc.wrapping_add(1))?;
+    let second = cpu.fetch(pc.wrapping_add(2))?;
+    let pointer = |pointer| -> Result<usize, VmError> {
+        if WIDE {
+            register_index(pointer)
+        } else {
+            Ok(pair(pointer)? as usize)
+        }
+    };
+    let (reg, pointer) = if OPCODE == LOAD_INC {
+        let reg = register_index(first)?;
+        (reg, pointer(second)?)
+    } else {
+        let pointer = pointer(first)?;
+        (register_index(second)?, pointer)
+    };
+    Ok(Predecoded {
+        reg: reg as u8,
+        operand: pointer as u16,
+        ..decoded(opcode, 3)
+    })
+}
+
+// The register goes in `reg` and the offset in `operand`, as for `[rN+]` pointers.
+#[inline(always)]
+fn decode_stack<const OPCODE: u8>(
+    cpu: &MicroCVMCpu,
+    pc: u16,
+    opcode: u8,
+) -> Result<Predecoded, VmError> {
+    let first = cpu.fetch(pc.wrapping_add(1))?;
+    let second = cpu.fetch(pc.wrapping_add(2))?;
+    let (reg, offset) = if OPCODE == LOAD_SP {
+        (first, second)
+    } else {
+        (second, first)
+    };
+    Ok(Predecoded {
+        reg: register_index(reg)? as u8,
+        operand: offset as u16,
+        ..decoded(opcode, 3)
+    })
+}
+
+#[inline(always)]
+fn decode_movm(cpu: &MicroCVMCpu, pc: u16, opcode: u8) -> Result<Predecoded, VmError> {
+    Ok(Predecoded {
+        operand: cpu.fetch_u16(pc.wrapping_add(1))?,
+        second: cpu.fetch_u16(pc.wrapping_add(3))?,
+        ..decoded(opcode, 5)
+    })
+}
+
+#[inline(always)]
+fn decode_address(cpu: &MicroCVMCpu, pc: u16, opcode: u8) -> Result<Predecoded, VmError> {
+    let mut instruction = decoded(opcode, 3);
+    instruction.operand = cpu.fetch_u16(pc.wrapping_add(1))?;
+    Ok(instruction)
+}
+
+// A single byte operand: a branch offset, host call number or interrupt vector.
+#[inline(always)]
+fn decode_byte(cpu: &MicroCVMCpu, pc: u16, opcode: u8) -> Result<Predecoded, VmError> {
+    let mut instruction = decoded(opcode, 2);
+    instruction.operand = cpu.fetch(pc.wrapping_add(1))? as u16;
+    Ok(instruction)
+}
+
+#[inline(always)]
+fn decode_djnz(cpu: &MicroCVMCpu, pc: u16, opcode: u8) -> Result<Predecoded, VmError> {
+    let reg = cpu.fetch(pc.wrapping_add(1))?;
+    let offset = cpu.fetch(pc.wrapping_add(2))?;
+    let mut instruction = decoded(opcode, 3);
+    instruction.reg = register_index(reg)? as u8;
+    instruction.operand = offset as u16;
+    Ok(instruction)
+}
+
+#[inline(always)]
+fn decode_register(cpu: &MicroCVMCpu, pc: u16, opcode: u8) -> Result<Predecoded, VmError> {
+    let mut instruction = decoded(opcode, 2);
+    instruction.reg = register_index(cpu.fetch(pc.wrapping_add(1))?)? as u8;
+    Ok(instruction)
+}
+
+#[inline(always)]
+fn decode_video(cpu: &MicroCVMCpu, pc: u16, opcode: u8) -> Result<Predecoded, VmError> {
+    let operation = cpu.fetch(pc.wrapping_add(1))?;
+    let base = cpu.fetch(pc.wrapping_add(2))?;
+    let video = MicroCVMCpu::create_video_opcode(operation, base)?;
+    let mut instruction = decoded(opcode, 3);
+    instruction.reg = video.base as u8;
+    instruction.operand = video.opcode_type as u16;
+    Ok(instruction)
+}
+
+#[inline(always)]
+fn next_pc(pc: u16, instruction: Predecoded) -> u16 {
+    pc.wrapping_add(instruction.length as u16)
+}
+
+#[inline(always)]
+fn mask<const WIDE: bool>() -> u16 {
+    if WIDE { 0xFFFF } else { 0xFF }
+}
+
+#[inline(always)]
+fn execute_arithmetic<const WIDE: bool, const OPCODE: u8>(
+    cpu: &mut MicroCVMCpu,
+    pc: u16,
+    instruction: Predecoded,
+) -> Result<(), VmError> {
+    let index = instruction.reg as usize;
+    let value = cpu.source_value(instruction) as u32;
+    cpu.cycles += 1;
+
+    // Worked out at 32 bits, so whatever doesn't fit the register is the carry.
+    let current = cpu.registers[index] as u32;
+    let full = match OPCODE {
+        MOV => value,
+        ADD => current + value,
+        SUB => current.wrapping_sub(value),
+        MUL => current * value,
+        _ => current
+            .checked_div(value)
+            .ok_or(VmError::DivisionByZero { pc })?,
+    };
+    let mask = mask::<WIDE>();
+    let result = full as u16 & mask;
+    cpu.registers[index] = result;
+    if OPCODE != MOV {
+        cpu.update_zero_and_carry_flags(result, full > mask as u32);
+    }
+    cpu.pc = next_pc(pc, instruction);
+    Ok(())
+}
+
+#[inline(always)]
+fn execute_test(cpu: &mut MicroCVMCpu, pc: u16, instruction: Predecoded) -> Result<(), VmError> {
+    let value = cpu.source_value(instruction);
+    cpu.cycles += 1;
+    cpu.update_zero_flag(cpu.registers[instruction.reg as usize] & value);
+    cpu.pc = next_pc(pc, instruction);
+    Ok(())
+}
+
+#[inline(always)]
+fn execute_bit<const OPCODE: u8>(
+    cpu: &mut MicroCVMCpu,
+    pc: u16,
+    instruction: Predecoded,
+) -> Result<(), VmError> {
+    cpu.cycles += 1;
+    cpu.bit_operation(OPCODE, instruction.reg as usize, instruction.operand as u8);
+    cpu.pc = next_pc(pc, instruction);
+    Ok(())
+}
+
+#[inline(always)]
+fn execute_load(cpu: &mut MicroCVMCpu, pc: u16, instruction: Predecoded) -> Result<(), VmError> {
+    cpu.cycles += 1;
+    cpu.registers[instruction.reg as usize] = cpu.load_register(instruction.operand)?;
+    cpu.pc = next_pc(pc, instruction);
+    Ok(())
+}
+
+#[inline(always)]
+fn execute_store(cpu: &mut MicroCVMCpu, pc: u16, instruction: Predecoded) -> Result<(), VmError> {
+    cpu.cycles += 1;
+    cpu.store_register(instruction.operand, cpu.registers[instruction.reg as usize])?;
+    cpu.pc = next_pc(pc, instruction);
+    Ok(())
+}
+
+#[inline(always)]
+fn execute_load_inc(
+    cpu: &mut MicroCVMCpu,
+    pc: u16,
+    instruction: Predecoded,
+) -> Result<(), VmError> {
+    cpu.cycles += 1;
+    cpu.load_post_increment(instruction.reg as usize, instruction.operand as usize)?;
+    cpu.pc = next_pc(pc, instruction);
+    Ok(())
+}
+
+#[inline(always)]
+fn execute_store_inc(
+    cpu: &mut MicroCVMCpu,
+    pc: u16,
+    instruction: Predecoded,
+) -> Result<(), VmError> {
+    cpu.cycles += 1;
+    cpu.store_post_increment(instruction.operand as usize, instruction.reg as usize)?;
+    cpu.pc = next_pc(pc, instruction);
+    Ok(())
+}
+
+#[inline(always)]
+fn execute_load_sp(cpu: &mut MicroCVMCpu, pc: u16, instruction: Predecoded) -> Result<(), VmError> {
+    cpu.cycles += 1;
+    let addr = cpu.stack_slot(instruction.operand as u8)?;
+    cpu.registers[instruction.reg as usize] = cpu.load_register(addr)?;
+    cpu.pc = next_pc(pc, instruction);
+    Ok(())
+}
+
+#[inline(always)]
+fn execute_store_sp(
+    cpu: &mut MicroCVMCpu,
+    pc: u16,
+    instruction: Predecoded,
+) -> Result<(), VmError> {
+    cpu.cycles += 1;
+    let addr = cpu.stack_slot(instruction.operand as u8)?;
+    cpu.store_register(addr, cpu.registers[instruction.reg as usize])?;
+    cpu.pc = next_pc(pc, instruction);
+    Ok(())
+}
+
+#[inline(always)]
+fn execute_movm(cpu: &mut MicroCVMCpu, pc: u16, instruction: Predecoded) -> Result<(), VmError> {
+    cpu.cycles += 2;
+    cpu.move_byte(instruction.operand, instruction.second)?;
+    cpu.pc = next_pc(pc, instruction);
+    Ok(())
+}
+
+#[inline(always)]
+fn execute_jmp(cpu: &mut MicroCVMCpu, _: u16, instruction: Predecoded) -> Result<(), VmError> {
+    cpu.cycles += 1;
+    cpu.pc = instruction.operand;
+    Ok(())
+}
+
+#[inline(always)]
+fn execute_jtab(cpu: &mut MicroCVMCpu, _: u16, instruction: Predecoded) -> Result<(), VmError> {
+    cpu.cycles += 1;
+    let index = cpu.registers[instruction.reg as usize];
+    cpu.pc = cpu.jump_table_entry(instruction.operand, index)?;
+    Ok(())
+}
+
+#[inline(always)]
+fn execute_call(cpu: &mut MicroCVMCpu, pc: u16, instruction: Predecoded) -> Result<(), VmError> {
+    cpu.cycles += 1;
+    cpu.call(instruction.operand, next_pc(pc, instruction))
+}
+
+#[inline(always)]
+fn execute_ret(cpu: &mut MicroCVMCpu, _: u16, _: Predecoded) -> Result<(), VmError> {
+    cpu.cycles += 1;
+    cpu.ret()
+}
+
+#[inline(always)]
+fn execute_branch<const OPCODE: u8>(
+    cpu: &mut MicroCVMCpu,
+    pc: u16,
+    instruction: Predecoded,
+) -> Result<(), VmError> {
+    cpu.cycles += 1;
+    let next_pc = next_pc(pc, instruction);
+    cpu.pc = if cpu.branch_taken(OPCODE) {
+        next_pc.wrapping_add_signed(instruction.operand as u8 as i8 as i16)
+    } else {
+        next_pc
+    };
+    Ok(())
+}
+
+#[inline(always)]
+fn execute_djnz<const WIDE: bool>(
+    cpu: &mut MicroCVMCpu,
+    pc: u16,
+    instruction: Predecoded,
+) -> Result<(), VmError> {
+    let index = instruction.reg as usize;
+    cpu.cycles += 1;
+    let count = cpu.registers[index].wrapping_sub(1) & mask::<WIDE>();
+    cpu.registers[index] = count;
+    let next_pc = next_pc(pc, instruction);
+    cpu.pc = if count != 0 {
+        next_pc.wrapping_add_signed(instruction.operand as u8 as i8 as i16)
+    } else {
+        next_pc
+    };
+    Ok(())
+}
+
+#[inline(always)]
+fn execute_inc<const WIDE: bool>(
+    cpu: &mut MicroCVMCpu,
+    pc: u16,
+    instruction: Predecoded,
+) -> Result<(), VmError> {
+    let index = instruction.reg as usize;
+    cpu.cycles += 1;
+    cpu.registers[index] = cpu.registers[index].wrapping_add(1) & mask::<WIDE>();
+    cpu.update_zero_flag(cpu.registers[index]);
+    cpu.pc = next_pc(pc, instruction);
+    Ok(())
+}
+
+#[inline(always)]
+fn execute_dec<const WIDE: bool>(
+    cpu: &mut MicroCVMCpu,
+    pc: u16,
+    instruction: Predecoded,
+) -> Result<(), VmError> {
+    let index = instruction.reg as usize;
+    let value = cpu.registers[index];
+    cpu.cycles += 1;
+    cpu.registers[index] = value.wrapping_sub(1) & mask::<WIDE>();
+    cpu.update_zero_and_carry_flags(cpu.registers[index], value == 0);
+    cpu.pc = next_pc(pc, instruction);
+    Ok(())
+}
+
+#[inline(always)]
+fn execute_clr(cpu: &mut MicroCVMCpu, pc: u16, instruction: Predecoded) -> Result<(), VmError> {
+    cpu.cycles += 1;
+    cpu.registers[instruction.reg as usize] = 0;
+    cpu.pc = next_pc(pc, instruction);
+    Ok(())
+}
+
+// The operand bytes go in `reg` and then `operand`, low byte first.
+#[inline(always)]
+fn decode_extension(cpu: &MicroCVMCpu, pc: u16, opcode: u8) -> Result<Predecoded, VmError> {
+    let Some(length) = cpu.extensions.length(opcode) else {
+        return decode_invalid(cpu, pc, opcode);
+    };
+    let mut operands = [0; 3];
+    for (offset, byte) in (1..length).zip(operands.iter_mut()) {
+        *byte = cpu.fetch(pc.wrapping_add(offset))?;
+    }
+    Ok(Predecoded {
+        reg: operands[0],
+        operand: u16::from_le_bytes([operands[1], operands[2]]),
+        ..decoded(opcode, length as u8)
+    })
+}
+
+fn execute_extension(
+    cpu: &mut MicroCVMCpu,
+    pc: u16,
+    instruction: Predecoded,
+) -> Result<(), VmError> {
+    let [low, high] = instruction.operand.to_le_bytes();
+    let operands = [instruction.reg, low, high];
+    cpu.cycles += 1;
+    cpu.extension(
+        instruction.opcode,
+        &operands[..instruction.length as usize - 1],
+    )?;
+    cpu.pc = next_pc(pc, instruction);
+    Ok(())
+}
+
+#[inline(always)]
+fn decode_pairs(cpu: &MicroCVMCpu, pc: u16, opcode: u8) -> Result<Predecoded, VmError> {
+    let dst = pair(cpu.fetch(pc.wrapping_add(1))?)?;
+    let src = pair(cpu.fetch(pc.wrapping_add(2))?)?;
+    Ok(Predecoded {
+        reg: dst as u8,
+        src_register: true,
+        operand: src as u16,
+        ..decoded(opcode, 3)
+    })
+}
+
+#[inline(always)]
+fn decode_fixed(cpu: &MicroCVMCpu, pc: u16, opcode: u8) -> Result<Predecoded, VmError> {
+    let dst = pair(cpu.fetch(pc.wrapping_add(1))?)?;
+    Ok(Predecoded {
+        reg: dst as u8,
+        operand: cpu.fetch_u16(pc.wrapping_add(2))?,
+        ..decoded(opcode, 4)
+    })
+}
+
+#[inline(always)]
+fn execute_fixed<const OPCODE: u8>(
+    cpu: &mut MicroCVMCpu,
+    pc: u16,
+    instruction: Predecoded,
+) -> Result<(), VmError> {
+    let value = if instruction.src_register {
+        cpu.read_pair(instruction.operand as usize)
+    } else {
+        instruction.operand
+    };
+    cpu.cycles += 1;
+    cpu.fixed_operation(OPCODE, instruction.reg as usize, value)?;
+    cpu.pc = next_pc(pc, instruction);
+    Ok(())
+}
+
+#[inline(always)]
+fn execute_hcall(cpu: &mut MicroCVMCpu, pc: u16, instruction: Predecoded) -> Result<(), VmError> {
+    cpu.cycles += 1;
+    cpu.hcall(instruction.operand as u8)?;
+    cpu.pc = next_pc(pc, instruction);
+    Ok(())
+}
+
+#[inline(always)]
+fn execute_video(cpu: &mut MicroCVMCpu, pc: u16, instruction: Predecoded) -> Result<(), VmError> {
+    cpu.cycles += 1;
+    cpu.video(MicroCVMCpu::create_video_opcode(
+        instruction.operand as u8,
+        instruction.reg,
+    )?)?;
+    cpu.pc = next_pc(pc, instruction);
+    Ok(())
+}
+
+#[inline(always)]
+fn execute_block<const OPCODE: u8>(
+    cpu: &mut MicroCVMCpu,
+    pc: u16,
+    instruction: Predecoded,
+) -> Result<(), VmError> {
+    let base = Register::try_from(instruction.reg)?;
+    cpu.cycles += 1;
+    if OPCODE == MEMSET {
+        cpu.memset(base)?;
+    } else {
+        cpu.memcpy(base)?;
+    }
+    cpu.pc = next_pc(pc, instruction);
+    Ok(())
+}
+
+#[inline(always)]
+fn execute_flag<const OPCODE: u8>(
+    cpu: &mut MicroCVMCpu,
+    pc: u16,
+    instruction: Predecoded,
+) -> Result<(), VmError> {
+    cpu.cycles += 1;
+    cpu.flags = match OPCODE {
+        CLC => cpu.flags & !FLAG_CARRY,
+        STC => cpu.flags | FLAG_CARRY,
+        CMC => cpu.flags ^ FLAG_CARRY,
+        EI => cpu.flags | FLAG_INTERRUPT_ENABLE,
+        _ => cpu.flags & !FLAG_INTERRUPT_ENABLE,
+    };
+    cpu.pc = next_pc(pc, instruction);
+    Ok(())
+}
+
+#[inline(always)]
+fn execute_pushf(cpu: &mut MicroCVMCpu, pc: u16, instruction: Predecoded) -> Result<(), VmError> {
+    cpu.cycles += 1;
+    cpu.pushf()?;
+    cpu.pc = next_pc(pc, instruction);
+    Ok(())
+}
+
+#[inline(always)]
+fn execute_popf(cpu: &mut MicroCVMCpu, pc: u16, instruction: Predecoded) -> Result<(), VmError> {
+    cpu.cycles += 1;
+    cpu.popf()?;
+    cpu.pc = next_pc(pc, instruction);
+    Ok(())
+}
+
+#[inline(always)]
+fn execute_int(cpu: &mut MicroCVMCpu, pc: u16, instruction: Predecoded) -> Result<(), VmError> {
+    cpu.cycles += 1;
+    cpu.int(instruction.operand as u8, next_pc(pc, instruction))
+}
+
+#[inline(always)]
+fn execute_iret(cpu: &mut MicroCVMCpu, _: u16, _: Predecoded) -> Result<(), VmError> {
+    cpu.cycles += 1;
+    cpu.iret()
+}
+
+#[inline(always)]
+fn execute_nop(cpu: &mut MicroCVMCpu, pc: u16, instruction: Predecoded) -> Result<(), VmError> {
+    cpu.cycles += 1;
+    cpu.pc = next_pc(pc, instruction);
+    Ok(())
+}
+
+#[inline(always)]
+fn execute_hlt(cpu: &mut MicroCVMCpu, _: u16, _: Predecoded) -> Result<(), VmError> {
+    cpu.cycles += 1;
+    cpu.halt();
+    Ok(())
+}
+
+// The register a pair operand names, which has to be even.
+fn pair(byte: u8) -> Result<Register, VmError> {
+    if !byte.is_multiple_of(2) {
+        return Err(VmError::InvalidRegister(InvalidRegister(byte)));
+    }
+    Ok(Register::try_from(byte)?)
+}
+
+fn register_index(byte: u8) -> Result<usize, VmError> {
+    if byte < 8 {
+        Ok(byte as usize)
+    } else {
+        Err(VmError::InvalidRegister(InvalidRegister(byte)))
     }
 }
 
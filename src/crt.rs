@@ -0,0 +1,74 @@
+// How much darker a scanline is when no percentage is given.
+pub const DEFAULT_SCANLINE_DIM: u8 = 30;
+
+// A look of an old monitor, applied to the output pixels after scaling so video memory
+// never sees it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CrtEffect {
+    // Percentage taken off each channel of a dimmed line, at most 100.
+    pub scanline_dim: u8,
+    // Blends each output pixel with a quarter of its left neighbour.
+    pub smear: bool,
+}
+
+impl CrtEffect {
+    pub fn new() -> Self {
+        Self {
+            scanline_dim: DEFAULT_SCANLINE_DIM,
+            smear: false,
+        }
+    }
+
+    // At 1x every other line is dim. Larger scales dim the last output line of every VM
+    // row, so 2x alternates and 3x shows two bright lines and a dim one.
+    pub fn is_dim(scale: usize, line: usize) -> bool {
+        match scale {
+            1 => line % 2 == 1,
+            _ => line % scale == scale - 1,
+        }
+    }
+}
+
+impl Default for CrtEffect {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Scales the RGBA frame `src`, `width` pixels wide, up by `scale` in both directions into
+// `out`, then applies `crt` if there is one. `out` holds `width * scale` pixels a line.
+pub fn scale_frame(src: &[u8], width: usize, scale: usize, crt: Option<CrtEffect>, out: &mut [u8]) {
+    if width == 0 || scale == 0 {
+        return;
+    }
+    let out_width = width * scale;
+    for (line, out) in out.chunks_exact_mut(out_width * 4).enumerate() {
+        let Some(row) = src.chunks_exact(width * 4).nth(line / scale) else {
+            break;
+        };
+        for (x, pixel) in out.chunks_exact_mut(4).enumerate() {
+            pixel.copy_from_slice(&row[x / scale * 4..x / scale * 4 + 4]);
+        }
+        let Some(crt) = crt else {
+            continue;
+        };
+        if crt.smear {
+            // Right to left, so every pixel blends with its neighbour before the smear.
+            for x in (1..out_width).rev() {
+                for channel in 0..3 {
+                    let left = out[(x - 1) * 4 + channel] as u16;
+                    let pixel = &mut out[x * 4 + channel];
+                    *pixel = ((*pixel as u16 * 3 + left + 2) / 4) as u8;
+                }
+            }
+        }
+        if CrtEffect::is_dim(scale, line) {
+            let keep = 100 - crt.scanline_dim.min(100) as u16;
+            for pixel in out.chunks_exact_mut(4) {
+                for channel in &mut pixel[..3] {
+                    *channel = ((*channel as u16 * keep + 50) / 100) as u8;
+                }
+            }
+        }
+    }
+}
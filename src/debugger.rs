@@ -0,0 +1,253 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::cpu::MicroCVMCpu;
+use crate::disasm;
+use crate::font::{self, GLYPH_SIZE};
+use crate::isa;
+use crate::overlay::layout;
+use crate::symbols::SymbolTable;
+use crate::vm::MicroCvm;
+
+// Instructions shown before and from pc on, the one at pc included.
+pub const DISASSEMBLY_BEFORE: usize = 4;
+pub const DISASSEMBLY_AFTER: usize = 8;
+pub const HEXDUMP_ROWS: usize = 8;
+pub const HEXDUMP_ROW_BYTES: usize = 8;
+
+const PADDING: usize = 4;
+// Glyphs plus a pixel above and below for the highlight bar.
+const LINE_HEIGHT: usize = GLYPH_SIZE + 2;
+// Registers, disassembly, hexdump and the key help, with a blank line between each.
+const DEBUGGER_LINES: usize = 3 + 1 + DISASSEMBLY_BEFORE + DISASSEMBLY_AFTER + 1 + HEXDUMP_ROWS + 3;
+
+pub const DEBUGGER_WIDTH: u32 = 320;
+pub const DEBUGGER_HEIGHT: u32 = (DEBUGGER_LINES * LINE_HEIGHT + 2 * PADDING) as u32;
+
+const BACKGROUND: [u8; 4] = [16, 16, 24, 255];
+const CURRENT: [u8; 4] = [40, 60, 140, 255];
+const SELECTED: [u8; 4] = [64, 64, 64, 255];
+const TEXT: [u8; 4] = [255, 255, 255, 255];
+
+const HELP: [&str; 2] = [
+    "S STEP  C CONTINUE  P PAUSE  B BREAK",
+    "UP/DOWN SELECT  PGUP/PGDN/HOME MEMORY",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineStyle {
+    Normal,
+    // The instruction at pc.
+    Current,
+    // The instruction the breakpoint key acts on.
+    Selected,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebuggerLine {
+    pub text: String,
+    pub style: LineStyle,
+}
+
+impl DebuggerLine {
+    fn normal(text: String) -> Self {
+        Self {
+            text,
+            style: LineStyle::Normal,
+        }
+    }
+}
+
+// What the debugger window shows besides the machine itself: which memory the hexdump
+// starts at and which disassembly line is selected.
+#[derive(Debug, Clone, Default)]
+pub struct Debugger {
+    pub memory_start: u16,
+    // Relative to the line at pc.
+    pub cursor: isize,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Keeps the selection on one of the lines `lines` would show for `cpu`.
+    pub fn move_cursor(&mut self, cpu: &MicroCVMCpu, delta: isize) {
+        let (addresses, pc_line) = disassembly_window(cpu);
+        let line = (pc_line as isize + self.cursor + delta).clamp(0, addresses.len() as isize - 1);
+        self.cursor = line - pc_line as isize;
+    }
+
+    pub fn scroll_memory(&mut self, rows: i16) {
+        let bytes = rows.wrapping_mul(HEXDUMP_ROW_BYTES as i16);
+        self.memory_start = self.memory_start.wrapping_add_signed(bytes);
+    }
+
+    // The address of the selected disassembly line.
+    pub fn selected(&self, cpu: &MicroCVMCpu) -> u16 {
+        let (addresses, pc_line) = disassembly_window(cpu);
+        let line = (pc_line as isize + self.cursor).clamp(0, addresses.len() as isize - 1);
+        addresses[line as usize]
+    }
+
+    // Everything the window shows, a line apiece.
+    pub fn lines(&self, vm: &MicroCvm) -> Vec<DebuggerLine> {
+        let mut lines = register_lines(vm);
+        lines.push(DebuggerLine::normal(String::new()));
+        lines.extend(self.disassembly_lines(vm));
+        lines.push(DebuggerLine::normal(String::new()));
+        lines.extend(self.hexdump_lines(vm.cpu(), HEXDUMP_ROWS));
+        lines.push(DebuggerLine::normal(String::new()));
+        lines.extend(HELP.map(|help| DebuggerLine::normal(String::from(help))));
+        lines
+    }
+
+    // The instructions around pc. Each line starts with '>' at pc and '*' on a breakpoint.
+    pub fn disassembly_lines(&self, vm: &MicroCvm) -> Vec<DebuggerLine> {
+        let cpu = vm.cpu();
+        let selected = self.selected(cpu);
+        disassembly_window(cpu)
+            .0
+            .into_iter()
+            .map(|addr| {
+                let current = if addr == cpu.pc { '>' } else { ' ' };
+                let breakpoint = if vm.breakpoints().contains(&addr) {
+                    '*'
+                } else {
+                    ' '
+                };
+                let (text, _) = instruction(cpu, addr);
+                let style = match addr {
+                    _ if addr == cpu.pc => LineStyle::Current,
+                    _ if addr == selected => LineStyle::Selected,
+                    _ => LineStyle::Normal,
+                };
+                DebuggerLine {
+                    text: format!("{}{}{:#06x}: {}", current, breakpoint, addr, text),
+                    style,
+                }
+            })
+            .collect()
+    }
+
+    // `rows` rows of memory from `memory_start` on, in hex and as ASCII. Bytes that can't
+    // be read show as `--`.
+    pub fn hexdump_lines(&self, cpu: &MicroCVMCpu, rows: usize) -> Vec<DebuggerLine> {
+        (0..rows)
+            .map(|row| {
+                let start = self
+                    .memory_start
+                    .wrapping_add((row * HEXDUMP_ROW_BYTES) as u16);
+                let bytes: Vec<_> = (0..HEXDUMP_ROW_BYTES as u16)
+                    .map(|i| cpu.read_mem(start.wrapping_add(i)).ok())
+                    .collect();
+                let hex: Vec<_> = bytes
+                    .iter()
+                    .map(|byte| byte.map_or(String::from("--"), |byte| format!("{:02X}", byte)))
+                    .collect();
+                let ascii: String = bytes
+                    .iter()
+                    .map(|byte| match byte {
+                        Some(byte @ 0x20..0x7F) => *byte as char,
+                        _ => '.',
+                    })
+                    .collect();
+                DebuggerLine::normal(format!("{:04X}: {} {}", start, hex.join(" "), ascii))
+            })
+            .collect()
+    }
+}
+
+// Registers, flags and pc, as the overlay shows them.
+pub fn register_lines(vm: &MicroCvm) -> Vec<DebuggerLine> {
+    layout(&vm.debug_stats())
+        .into_iter()
+        .take(3)
+        .map(DebuggerLine::normal)
+        .collect()
+}
+
+// Paints `lines` over a plain background into `rgba`, a frame `width` pixels wide, such as
+// the `DEBUGGER_WIDTH` by `DEBUGGER_HEIGHT` surface of the window. Lines that don't fit are
+// clipped.
+pub fn paint(lines: &[DebuggerLine], width: usize, rgba: &mut [u8]) {
+    if width == 0 {
+        return;
+    }
+    for pixel in rgba.chunks_exact_mut(4) {
+        pixel.copy_from_slice(&BACKGROUND);
+    }
+    for (row, line) in lines.iter().enumerate() {
+        let top = PADDING + row * LINE_HEIGHT;
+        let bar = match line.style {
+            LineStyle::Normal => None,
+            LineStyle::Current => Some(CURRENT),
+            LineStyle::Selected => Some(SELECTED),
+        };
+        if let Some(bar) = bar {
+            for y in top..top + LINE_HEIGHT {
+                let Some(line) = rgba.get_mut(y * width * 4..(y + 1) * width * 4) else {
+                    break;
+                };
+                for pixel in line.chunks_exact_mut(4) {
+                    pixel.copy_from_slice(&bar);
+                }
+            }
+        }
+        font::draw_text(&line.text, PADDING, top + 1, TEXT, width, rgba);
+    }
+}
+
+// The addresses of the disassembly lines around pc, and which of them is pc's. Decoding
+// backwards is ambiguous, so the lines before pc come from the earliest nearby address that
+// decodes forward to exactly pc, and there are none if no address does.
+fn disassembly_window(cpu: &MicroCVMCpu) -> (Vec<u16>, usize) {
+    let pc = cpu.pc;
+    let mut before = Vec::new();
+    // The longest instruction is four bytes.
+    for back in (1..=DISASSEMBLY_BEFORE as u16 * 4).rev() {
+        let Some(start) = pc.checked_sub(back) else {
+            continue;
+        };
+        let (mut addr, mut seen) = (start as u32, Vec::new());
+        while addr < pc as u32 {
+            seen.push(addr as u16);
+            addr += instruction(cpu, addr as u16).1 as u32;
+        }
+        if addr == pc as u32 {
+            before = seen;
+            break;
+        }
+    }
+    let mut addresses: Vec<_> = before[before.len().saturating_sub(DISASSEMBLY_BEFORE)..].into();
+    let pc_line = addresses.len();
+    let mut addr = pc;
+    for _ in 0..DISASSEMBLY_AFTER {
+        addresses.push(addr);
+        addr = addr.wrapping_add(instruction(cpu, addr).1);
+    }
+    (addresses, pc_line)
+}
+
+// The text of the instruction at `addr` and its length, a byte that doesn't decode being a
+// `.db` of its own.
+fn instruction(cpu: &MicroCVMCpu, addr: u16) -> (String, u16) {
+    let bytes: Vec<u8> = (0..isa::MAX_LENGTH)
+        .map_while(|offset| cpu.read_mem(addr.wrapping_add(offset)).ok())
+        .collect();
+    let Some(&first) = bytes.first() else {
+        return (String::from("out of bounds"), 1);
+    };
+    if let Some(extension) = disasm::format_extension(&bytes, cpu.extensions()) {
+        return extension;
+    }
+    match disasm::decode(&bytes, cpu.register_width) {
+        Some(opcode) => (
+            disasm::format_instruction(&opcode, addr, &SymbolTable::new()),
+            opcode.length(),
+        ),
+        None => (format!(".db {:#04x}", first), 1),
+    }
+}
@@ -0,0 +1,118 @@
+use alloc::boxed::Box;
+use alloc::vec;
+use core::ops::Range;
+
+use crate::cpu::{BANK_WINDOW_END, BANK_WINDOW_START};
+use crate::isa;
+
+// Addresses the cache has room for, one entry per possible pc.
+const ENTRIES: usize = 0x1_0000;
+// Instructions are at most this long, so a write can only land in one that starts this
+// many bytes before it.
+const MAX_LENGTH: usize = isa::MAX_LENGTH as usize;
+
+// An instruction with its operands fetched and checked, ready to execute without going
+// back to memory. What each field holds depends on the opcode, as `MicroCVMCpu::predecode`
+// fills them in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Predecoded {
+    // 0 for an empty entry, which no instruction uses.
+    pub opcode: u8,
+    pub length: u8,
+    pub reg: u8,
+    // The operand names a register to read when the instruction runs.
+    pub src_register: bool,
+    pub operand: u16,
+    // movm's source address, with the destination in `operand`.
+    pub second: u16,
+}
+
+impl Predecoded {
+    pub const EMPTY: Predecoded = Predecoded {
+        opcode: 0,
+        length: 0,
+        reg: 0,
+        src_register: false,
+        operand: 0,
+        second: 0,
+    };
+}
+
+// Decoded instructions by pc. Only addresses that always mean the same byte of memory are
+// cached, which leaves out the bank window, so entries are keyed by the physical address
+// a write lands on as much as by pc.
+pub struct DecodeCache {
+    entries: Box<[Predecoded; ENTRIES]>,
+    // Whether any entry covers a byte in each 256-byte page, so writes to pages holding no
+    // code cost one lookup.
+    pages: [bool; 256],
+    // What the entries were decoded for, since an immediate's length depends on it.
+    wide: bool,
+}
+
+impl DecodeCache {
+    pub fn new() -> Self {
+        Self {
+            entries: vec![Predecoded::EMPTY; ENTRIES]
+                .into_boxed_slice()
+                .try_into()
+                .expect("cache has one entry per address"),
+            pages: [false; 256],
+            wide: false,
+        }
+    }
+
+    // The entry for `pc`, emptied first when it was decoded for the other register width.
+    #[inline]
+    pub fn get(&mut self, pc: u16, wide: bool) -> Predecoded {
+        if self.wide != wide {
+            self.flush();
+            self.wide = wide;
+        }
+        self.entries[pc as usize]
+    }
+
+    #[inline]
+    pub fn insert(&mut self, pc: u16, instruction: Predecoded) {
+        let end = pc as usize + instruction.length as usize;
+        let banked = BANK_WINDOW_START as usize..BANK_WINDOW_END as usize;
+        if end > ENTRIES || (pc as usize) < banked.end && end > banked.start {
+            return;
+        }
+        self.pages[pc as usize >> 8] = true;
+        self.pages[(end - 1) >> 8] = true;
+        self.entries[pc as usize] = instruction;
+    }
+
+    // Forgets every instruction that might cover a byte in `range` of physical memory.
+    #[inline]
+    pub fn invalidate(&mut self, range: Range<usize>) {
+        let end = range.end.min(ENTRIES);
+        if range.start >= end {
+            return;
+        }
+        let start = range.start.saturating_sub(MAX_LENGTH - 1);
+        for page in start >> 8..=(end - 1) >> 8 {
+            if self.pages[page] {
+                let page_start = page << 8;
+                let covered = start.max(page_start)..end.min(page_start + 0x100);
+                self.entries[covered].fill(Predecoded::EMPTY);
+            }
+        }
+    }
+
+    pub fn flush(&mut self) {
+        for page in 0..self.pages.len() {
+            if self.pages[page] {
+                self.entries[page << 8..][..0x100].fill(Predecoded::EMPTY);
+                self.pages[page] = false;
+            }
+        }
+    }
+}
+
+impl Default for DecodeCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,79 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use crate::asm::assemble;
+use crate::cpu::{Register, RegisterWidth};
+use crate::vm::MicroCvm;
+
+pub const BOUNCE_SOURCE: &str = include_str!("../demos/bounce.asm");
+pub const SELF_TEST_SOURCE: &str = include_str!("../demos/selftest.asm");
+pub const SELF_TEST_16_SOURCE: &str = include_str!("../demos/selftest16.asm");
+pub const COUNTDOWN_SOURCE: &str = include_str!("../demos/countdown.asm");
+pub const CLOCK_SOURCE: &str = include_str!("../demos/clock.asm");
+pub const FRAMEBUFFER_SOURCE: &str = include_str!("../demos/framebuffer.asm");
+pub const TILEMAP_SOURCE: &str = include_str!("../demos/tilemap.asm");
+
+pub const DEMO_WIDTH: u32 = 256;
+pub const DEMO_HEIGHT: u32 = 256;
+
+// Where demos/framebuffer.asm expects the framebuffer window.
+pub const FRAMEBUFFER_DEMO_WINDOW: u16 = 0x8000;
+
+// The self-test calls this to check hcall dispatch, the handler doubles r1.
+pub const SELF_TEST_HCALL: u8 = 1;
+
+// Enough for every test plus the table lookups, so a broken jmp can't spin forever.
+pub const SELF_TEST_MAX_INSTRUCTIONS: u64 = 10_000;
+
+// The shipped sources are known to assemble, so failing here is a bug in the crate.
+pub fn bounce_program() -> Vec<u8> {
+    assemble(BOUNCE_SOURCE).expect("demos/bounce.asm should assemble")
+}
+
+pub fn self_test_program() -> Vec<u8> {
+    assemble(SELF_TEST_SOURCE).expect("demos/selftest.asm should assemble")
+}
+
+pub fn self_test_16_program() -> Vec<u8> {
+    assemble(SELF_TEST_16_SOURCE).expect("demos/selftest16.asm should assemble")
+}
+
+pub fn countdown_program() -> Vec<u8> {
+    assemble(COUNTDOWN_SOURCE).expect("demos/countdown.asm should assemble")
+}
+
+pub fn clock_program() -> Vec<u8> {
+    assemble(CLOCK_SOURCE).expect("demos/clock.asm should assemble")
+}
+
+pub fn framebuffer_program() -> Vec<u8> {
+    assemble(FRAMEBUFFER_SOURCE).expect("demos/framebuffer.asm should assemble")
+}
+
+pub fn tilemap_program() -> Vec<u8> {
+    assemble(TILEMAP_SOURCE).expect("demos/tilemap.asm should assemble")
+}
+
+// The self-test for `width`, each width has its own program.
+pub fn self_test_vm(width: RegisterWidth) -> MicroCvm {
+    let mut vm = MicroCvm::builder()
+        .resolution(DEMO_WIDTH, DEMO_HEIGHT)
+        .register_width(width)
+        .max_instructions(SELF_TEST_MAX_INSTRUCTIONS)
+        .hcall(
+            SELF_TEST_HCALL,
+            Box::new(|ctx| {
+                let value = ctx.reg(Register::R1);
+                ctx.set_reg(Register::R1, value.wrapping_mul(2));
+                Ok(())
+            }),
+        )
+        .build();
+    let program = match width {
+        RegisterWidth::Eight => self_test_program(),
+        RegisterWidth::Sixteen => self_test_16_program(),
+    };
+    // The image starts at address 0 and is far smaller than memory.
+    let _ = vm.load_program(&program);
+    vm
+}
@@ -0,0 +1,278 @@
+use alloc::boxed::Box;
+
+use crate::crc32::crc32;
+use crate::gamepad::GamepadState;
+use crate::snapshot::SnapshotDiff;
+use crate::vm::{CYCLES_PER_FRAME, MicroCvm, MicroCvmBuilder};
+
+// How many instructions apart verify_deterministic compares the two runs.
+pub const CHECK_INTERVAL: u64 = 1024;
+// What the real-time clock reads in a deterministic run, in seconds since the Unix epoch:
+// 2024-01-01 00:00:00 UTC.
+pub const DETERMINISTIC_TIME: u64 = 1_704_067_200;
+
+// Something a frontend hands the machine between frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+    Key { scancode: u8, pressed: bool },
+    Gamepad(GamepadState),
+    // One of MOUSE_LEFT and on.
+    MouseButton { mask: u8, pressed: bool },
+    MousePosition { x: u16, y: u16 },
+}
+
+// An input given at the start of a frame, counting from 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScriptedInput {
+    pub frame: u64,
+    pub input: Input,
+}
+
+// Where two runs that should have matched stopped matching.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    // How many instructions both runs had executed when their state first differed, so the
+    // last of them is where they went apart; 0 if they differed before the first.
+    pub instruction: u64,
+    // False if running again didn't differ in the same place, when `instruction` is only
+    // the first check that saw a difference.
+    pub exact: bool,
+    // From the first run to the second, boxed to keep the Result small.
+    pub diff: Box<SnapshotDiff>,
+}
+
+// One run: the machine, with frames ending the way run_frame ends them and the scripted
+// inputs given as each one starts.
+struct Run<'a> {
+    vm: MicroCvm,
+    inputs: &'a [ScriptedInput],
+    frame: u64,
+    frame_end: u64,
+    executed: u64,
+    // Halted, faulted or out of instructions.
+    stopped: bool,
+}
+
+impl ScriptedInput {
+    pub fn new(frame: u64, input: Input) -> Self {
+        Self { frame, input }
+    }
+}
+
+impl<'a> Run<'a> {
+    fn new(vm: MicroCvm, inputs: &'a [ScriptedInput]) -> Self {
+        let frame_end = vm.cpu().cycles + CYCLES_PER_FRAME;
+        let mut run = Self {
+            vm,
+            inputs,
+            frame: 0,
+            frame_end,
+            executed: 0,
+            stopped: false,
+        };
+        run.start_frame();
+        run
+    }
+
+    fn start_frame(&mut self) {
+        self.vm.cpu_mut().frame_done = false;
+        for scripted in self.inputs.iter().filter(|input| input.frame == self.frame) {
+            match scripted.input {
+                Input::Key { scancode, pressed } => self.vm.push_key(scancode, pressed),
+                Input::Gamepad(state) => self.vm.inject_gamepad_state(state),
+                Input::MouseButton { mask, pressed } => {
+                    self.vm.cpu_mut().mouse_mut().button(mask, pressed)
+                }
+                Input::MousePosition { x, y } => {
+                    let mouse = self.vm.cpu_mut().mouse_mut();
+                    (mouse.x, mouse.y) = (x, y);
+                }
+            }
+        }
+    }
+
+    fn step(&mut self) {
+        if self.stopped {
+            return;
+        }
+        let before = self.vm.instructions();
+        let result = self.vm.run_for(1);
+        if self.vm.instructions() == before {
+            self.stopped = true;
+            return;
+        }
+        self.executed += 1;
+        if result.is_err() {
+            self.stopped = true;
+            return;
+        }
+        let cpu = self.vm.cpu();
+        if cpu.cycles >= self.frame_end || cpu.frame_done {
+            self.vm.tick_frame();
+            self.frame += 1;
+            self.frame_end = self.vm.cpu().cycles + CYCLES_PER_FRAME;
+            self.start_frame();
+        }
+    }
+
+    fn run_to(&mut self, instruction: u64) {
+        while self.executed < instruction && !self.stopped {
+            self.step();
+        }
+    }
+
+    fn hash(&self) -> u32 {
+        crc32(&self.vm.snapshot().to_bytes())
+    }
+}
+
+/// A builder for a machine that only ever sees what it is given: the real-time clock is
+/// pinned to [`DETERMINISTIC_TIME`] rather than following the host's. Frames, key events
+/// and the rest already only depend on the cycles the program runs and the inputs the host
+/// passes on.
+pub fn deterministic_builder() -> MicroCvmBuilder {
+    MicroCvm::builder().clock(Box::new(|| DETERMINISTIC_TIME))
+}
+
+/// Runs `program` twice for `steps` instructions each, on machines from
+/// [`deterministic_builder`] with `inputs` given at the start of their frames, and checks
+/// the runs match every [`CHECK_INTERVAL`] instructions. A program that doesn't load runs
+/// as the empty machine that leaves, the same both times.
+///
+/// ```
+/// use microcvm_rs::demo::bounce_program;
+/// use microcvm_rs::determinism::{Input, ScriptedInput, verify_deterministic};
+/// use microcvm_rs::gamepad::{BUTTON_A, GamepadState};
+///
+/// let press = GamepadState { buttons: BUTTON_A, ..GamepadState::default() };
+/// let inputs = [
+///     ScriptedInput::new(2, Input::Key { scancode: 0x1E, pressed: true }),
+///     ScriptedInput::new(3, Input::Gamepad(press)),
+///     ScriptedInput::new(5, Input::Key { scancode: 0x1E, pressed: false }),
+/// ];
+/// assert_eq!(verify_deterministic(&bounce_program(), &inputs, 20_000), Ok(()));
+/// ```
+pub fn verify_deterministic(
+    program: &[u8],
+    inputs: &[ScriptedInput],
+    steps: u64,
+) -> Result<(), Divergence> {
+    let build = || {
+        let mut vm = deterministic_builder().build();
+        let _ = vm.load_program(program);
+        vm
+    };
+    verify_deterministic_with(build, inputs, steps, CHECK_INTERVAL)
+}
+
+/// Like [`verify_deterministic`], on machines from `build`, which also has whatever devices
+/// and host calls the program expects set up, checking every `interval` instructions. The
+/// checks compare hashes of the snapshots, and only when two differ are both runs replayed
+/// from the check before, comparing whole snapshots after every instruction, to find where
+/// they went apart. Snapshots hold the registers, memory and video memory, so state that
+/// only a device holds shows once it reaches one of those.
+///
+/// ```
+/// use microcvm_rs::MicroCvm;
+/// use microcvm_rs::asm::assemble;
+/// use microcvm_rs::determinism::verify_deterministic_with;
+///
+/// // Reads the seconds from the real-time clock after a while.
+/// let program = assemble("
+///         mov r2, 200
+/// spin:   djnz r2, spin
+///         store [0xFF70], r0
+///         load r1, [0xFF71]
+///         hlt
+/// ").unwrap();
+/// // Each machine's clock reads a second later than the one before, like the host's would.
+/// let mut boots = 0;
+/// let build = || {
+///     boots += 1;
+///     let time = 1_709_210_040 + boots;
+///     let mut vm = MicroCvm::builder().clock(Box::new(move || time)).build();
+///     vm.load_program(&program).unwrap();
+///     vm
+/// };
+/// let divergence = verify_deterministic_with(build, &[], 10_000, 64).unwrap_err();
+/// // mov, 200 times djnz, store, then the load sees a different second.
+/// assert_eq!(divergence.instruction, 203);
+/// assert!(divergence.exact);
+/// // Found by replaying on the third and fourth machines, at 12:34:03 and 12:34:04.
+/// assert_eq!(divergence.diff.registers, [(1, 3, 4)]);
+/// ```
+pub fn verify_deterministic_with(
+    mut build: impl FnMut() -> MicroCvm,
+    inputs: &[ScriptedInput],
+    steps: u64,
+    interval: u64,
+) -> Result<(), Divergence> {
+    let interval = interval.max(1);
+    let (mut first, mut second) = (Run::new(build(), inputs), Run::new(build(), inputs));
+    let (mut agreed, mut checked) = (0, 0);
+    loop {
+        if first.hash() != second.hash() {
+            let fallback = Divergence {
+                instruction: checked,
+                exact: false,
+                diff: Box::new(first.vm.snapshot().diff(&second.vm.snapshot())),
+            };
+            return Err(locate(&mut build, inputs, agreed, fallback));
+        }
+        if checked >= steps || (first.stopped && second.stopped) {
+            return Ok(());
+        }
+        agreed = checked;
+        checked = (checked + interval).min(steps);
+        first.run_to(checked);
+        second.run_to(checked);
+    }
+}
+
+// Replays two fresh runs to `agreed`, where they last matched, and steps them together to
+// the check in `fallback`, which is what comes back if they don't differ on the way.
+fn locate(
+    build: &mut impl FnMut() -> MicroCvm,
+    inputs: &[ScriptedInput],
+    agreed: u64,
+    fallback: Divergence,
+) -> Divergence {
+    let (mut first, mut second) = (Run::new(build(), inputs), Run::new(build(), inputs));
+    first.run_to(agreed);
+    second.run_to(agreed);
+    if first.hash() != second.hash() && agreed > 0 {
+        return fallback;
+    }
+    for instruction in agreed..=fallback.instruction {
+        let (before, after) = (first.vm.snapshot(), second.vm.snapshot());
+        if before.to_bytes() != after.to_bytes() {
+            return Divergence {
+                instruction,
+                exact: true,
+                diff: Box::new(before.diff(&after)),
+            };
+        }
+        first.step();
+        second.step();
+    }
+    fallback
+}
+
+impl core::fmt::Display for Divergence {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if self.exact {
+            write!(
+                f,
+                "the runs went apart after {} instructions",
+                self.instruction
+            )?;
+        } else {
+            write!(
+                f,
+                "the runs had gone apart by {} instructions",
+                self.instruction
+            )?;
+        }
+        write!(f, ":\n{}", self.diff)
+    }
+}
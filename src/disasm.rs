@@ -0,0 +1,290 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::cpu::{
+    Opcode, OpcodeArg1, OpcodeArg2, OpcodeType, Register, RegisterWidth, SRC_REGISTER,
+    VideoOpcodeType,
+};
+use crate::extension::Extensions;
+use crate::fixed;
+use crate::isa::{self, OperandKind};
+use crate::symbols::SymbolTable;
+
+enum Operand {
+    Register(Register),
+    Immediate(u8),
+    WideImmediate(u16),
+    Address(u16),
+    Offset(i8),
+}
+
+// Decodes the instruction at the start of `bytes` the way a CPU with `width` registers
+// would, or None if the bytes are not a valid instruction.
+pub fn decode(bytes: &[u8], width: RegisterWidth) -> Option<Opcode> {
+    let info = isa::decode(*bytes.first()?)?;
+    let takes_source = info.opcode.takes_source_operand();
+    let source_register = takes_source && *bytes.get(1)? & SRC_REGISTER != 0;
+    let wide = takes_source && !source_register && width == RegisterWidth::Sixteen;
+    let operand_bytes = bytes.get(1..info.length as usize + wide as usize)?;
+
+    let mut operands = Vec::new();
+    let mut offset = 0;
+    for kind in info.operands {
+        let byte = operand_bytes[offset];
+        operands.push(match kind {
+            OperandKind::Register if takes_source => {
+                Operand::Register(Register::try_from(byte & !SRC_REGISTER).ok()?)
+            }
+            OperandKind::Register => Operand::Register(Register::try_from(byte).ok()?),
+            OperandKind::Source if source_register => {
+                Operand::Register(Register::try_from(byte).ok()?)
+            }
+            OperandKind::Source if wide => {
+                Operand::WideImmediate(u16::from_le_bytes([byte, operand_bytes[offset + 1]]))
+            }
+            OperandKind::Source | OperandKind::Immediate => Operand::Immediate(byte),
+            OperandKind::Bit if byte < width.bits() => Operand::Immediate(byte),
+            OperandKind::Bit => return None,
+            OperandKind::Address => {
+                Operand::Address(u16::from_le_bytes([byte, operand_bytes[offset + 1]]))
+            }
+            OperandKind::Offset => Operand::Offset(byte as i8),
+            OperandKind::Pair if byte.is_multiple_of(2) => {
+                Operand::Register(Register::try_from(byte).ok()?)
+            }
+            OperandKind::Pair => return None,
+            OperandKind::Fixed => {
+                Operand::WideImmediate(u16::from_le_bytes([byte, operand_bytes[offset + 1]]))
+            }
+            OperandKind::Pointer if width == RegisterWidth::Eight && !byte.is_multiple_of(2) => {
+                return None;
+            }
+            OperandKind::Pointer => Operand::Register(Register::try_from(byte).ok()?),
+            OperandKind::StackOffset => Operand::Immediate(byte),
+            OperandKind::VideoOperation => {
+                Operand::Immediate(VideoOpcodeType::try_from(byte).ok()? as u8)
+            }
+        });
+        offset += kind.size() as usize;
+    }
+
+    let mut operands = operands.into_iter();
+    Some(Opcode {
+        opcode_type: info.opcode,
+        argument_count: info.operands.len() as u8,
+        arg1: operands.next().map(|operand| match operand {
+            Operand::Register(reg) => OpcodeArg1::Register(reg),
+            Operand::Immediate(imm) => OpcodeArg1::Immediate(imm),
+            Operand::WideImmediate(imm) => OpcodeArg1::Immediate(imm as u8),
+            Operand::Address(addr) => OpcodeArg1::Address(addr),
+            Operand::Offset(offset) => OpcodeArg1::Offset(offset),
+        }),
+        arg2: operands.next().map(|operand| match operand {
+            Operand::Register(reg) => OpcodeArg2::Register(reg),
+            Operand::Immediate(imm) => OpcodeArg2::Immediate(imm),
+            Operand::WideImmediate(imm) => OpcodeArg2::WideImmediate(imm),
+            Operand::Address(addr) => OpcodeArg2::Address(addr),
+            Operand::Offset(offset) => OpcodeArg2::Offset(offset),
+        }),
+    })
+}
+
+// The text of the extension instruction at the start of `bytes` and its length, if its
+// opcode is registered in `extensions`: its mnemonic, or `ext` and the opcode, then the
+// operand bytes.
+pub fn format_extension(bytes: &[u8], extensions: &Extensions) -> Option<(String, u16)> {
+    let opcode = *bytes.first()?;
+    let def = extensions.get(opcode)?;
+    let length = 1 + def.operand_bytes as usize;
+    let operands: Vec<String> = bytes
+        .get(1..length)?
+        .iter()
+        .map(|byte| format!("{:#04x}", byte))
+        .collect();
+    let mut text = match &def.mnemonic {
+        Some(mnemonic) => mnemonic.clone(),
+        None => format!("ext {:#04x}", opcode),
+    };
+    if !operands.is_empty() {
+        text.push(' ');
+        text.push_str(&operands.join(", "));
+    }
+    Some((text, length as u16))
+}
+
+// Formats an instruction placed at `address` in assembler syntax. Addresses and relative
+// jump targets are printed by name when `symbols` has one, otherwise as absolute hex, so
+// the text assembles back to the same bytes.
+pub fn format_instruction(opcode: &Opcode, address: u16, symbols: &SymbolTable) -> String {
+    let next_pc = address.wrapping_add(opcode.length());
+    let memory = matches!(
+        opcode.opcode_type,
+        OpcodeType::Load | OpcodeType::Store | OpcodeType::Movm
+    );
+    let name = |addr: u16| match symbols.name(addr) {
+        Some(name) => name.to_string(),
+        None => format!("{:#06x}", addr),
+    };
+    let address_operand = |addr: u16| {
+        if memory {
+            format!("[{}]", name(addr))
+        } else {
+            name(addr)
+        }
+    };
+
+    let mut operands = Vec::new();
+    match opcode.arg1 {
+        Some(OpcodeArg1::Address(addr)) => operands.push(address_operand(addr)),
+        Some(OpcodeArg1::Register(reg)) if opcode.opcode_type == OpcodeType::StoreInc => {
+            operands.push(format!("[{}+]", reg))
+        }
+        Some(OpcodeArg1::Immediate(offset)) if opcode.opcode_type == OpcodeType::StoreSp => {
+            operands.push(format!("[sp + {}]", offset))
+        }
+        Some(OpcodeArg1::Immediate(operation)) if opcode.opcode_type == OpcodeType::Video => {
+            match VideoOpcodeType::try_from(operation) {
+                Ok(operation) => operands.push(operation.mnemonic().to_string()),
+                Err(_) => operands.push(operation.to_string()),
+            }
+        }
+        Some(OpcodeArg1::Offset(offset)) => {
+            operands.push(name(next_pc.wrapping_add_signed(offset as i16)))
+        }
+        Some(ref arg) => operands.push(arg.to_string()),
+        None => {}
+    }
+    match opcode.arg2 {
+        Some(OpcodeArg2::Address(addr)) => operands.push(address_operand(addr)),
+        Some(OpcodeArg2::Register(reg)) if opcode.opcode_type == OpcodeType::LoadInc => {
+            operands.push(format!("[{}+]", reg))
+        }
+        Some(OpcodeArg2::Immediate(offset)) if opcode.opcode_type == OpcodeType::LoadSp => {
+            operands.push(format!("[sp + {}]", offset))
+        }
+        Some(OpcodeArg2::WideImmediate(value)) if opcode.opcode_type == OpcodeType::FxMulImm => {
+            operands.push(format!("#fx({})", fixed::to_f64(value as i16)))
+        }
+        Some(OpcodeArg2::Offset(offset)) => {
+            operands.push(name(next_pc.wrapping_add_signed(offset as i16)))
+        }
+        Some(ref arg) => operands.push(arg.to_string()),
+        None => {}
+    }
+
+    let mnemonic = opcode.opcode_type.mnemonic();
+    if operands.is_empty() {
+        mnemonic.to_string()
+    } else {
+        format!("{} {}", mnemonic, operands.join(", "))
+    }
+}
+
+// Disassembles an image loaded at `origin` into source the assembler accepts. Each
+// instruction is commented with its address and bytes. Bytes that do not decode, or
+// whose instruction would swallow a labelled address, are emitted as `.db`.
+pub fn disassemble(
+    image: &[u8],
+    origin: u16,
+    symbols: &SymbolTable,
+    width: RegisterWidth,
+) -> String {
+    let mut text = String::new();
+    if width == RegisterWidth::Sixteen {
+        text.push_str(".width 16\n");
+    }
+    if origin != 0 {
+        text.push_str(&format!(".org {:#06x}\n", origin));
+    }
+    disassemble_into(&mut text, image, origin, symbols, width, true);
+    text
+}
+
+// The lines `disassemble` writes for the image itself, appended to `text` without the
+// `.width` and `.org` in front. The comments only show the bytes if `bytes` is set.
+pub fn disassemble_into(
+    text: &mut String,
+    image: &[u8],
+    origin: u16,
+    symbols: &SymbolTable,
+    width: RegisterWidth,
+    bytes: bool,
+) {
+    let mut offset = 0;
+    while offset < image.len() {
+        let address = origin.wrapping_add(offset as u16);
+        if let Some(name) = symbols.name(address) {
+            text.push_str(&format!("{}:\n", name));
+        }
+
+        let decoded = decode(&image[offset..], width).filter(|opcode| {
+            let length = opcode.length();
+            (1..length).all(|i| symbols.name(address.wrapping_add(i)).is_none())
+        });
+        let (line, length) = match decoded {
+            Some(opcode) => (
+                format_instruction(&opcode, address, symbols),
+                opcode.length() as usize,
+            ),
+            None => (format!(".db {:#04x}", image[offset]), 1),
+        };
+        if bytes {
+            let bytes: Vec<String> = image[offset..offset + length]
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect();
+            text.push_str(&format!(
+                "    {:<24} ; {:#06x}  {}\n",
+                line,
+                address,
+                bytes.join(" ")
+            ));
+        } else {
+            text.push_str(&format!("    {:<24} ; {:#06x}\n", line, address));
+        }
+        offset += length;
+    }
+}
+
+// Names the targets of the jumps, calls, relative jumps and jump tables in `regions`, each an image and
+// the address it is loaded at, `L_` and the address in hex, if `symbols` has no name for
+// them and they fall inside one of the regions, where the disassembly can define them.
+pub fn synthesize_labels(
+    symbols: &mut SymbolTable,
+    regions: &[(u16, &[u8])],
+    width: RegisterWidth,
+) {
+    let inside = |target: u16| {
+        regions.iter().any(|&(origin, image)| {
+            (origin as usize..origin as usize + image.len()).contains(&(target as usize))
+        })
+    };
+    for &(origin, image) in regions {
+        let mut offset = 0;
+        while offset < image.len() {
+            let Some(opcode) = decode(&image[offset..], width) else {
+                offset += 1;
+                continue;
+            };
+            let length = opcode.length();
+            let next_pc = origin.wrapping_add(offset as u16).wrapping_add(length);
+            let target = match (opcode.opcode_type, opcode.arg1, opcode.arg2) {
+                (OpcodeType::Jmp | OpcodeType::Call, Some(OpcodeArg1::Address(addr)), _)
+                | (OpcodeType::Jtab, _, Some(OpcodeArg2::Address(addr))) => Some(addr),
+                (_, Some(OpcodeArg1::Offset(offset)), _)
+                | (_, _, Some(OpcodeArg2::Offset(offset))) => {
+                    Some(next_pc.wrapping_add_signed(offset as i16))
+                }
+                _ => None,
+            };
+            if let Some(target) = target
+                && inside(target)
+                && symbols.name(target).is_none()
+            {
+                symbols.insert(&format!("L_{:04x}", target), target);
+            }
+            offset += length as usize;
+        }
+    }
+}
@@ -1,3 +1,7 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
 const DISK_SIZE: usize = 1024 * 1024 * 8;
 
 pub struct MicroCVMDisk {
@@ -0,0 +1,53 @@
+// Command block registers, each multi-byte field little-endian.
+pub const DMA_SRC: u8 = 0x00; // 3 bytes: physical address of packed RGB data in memory
+pub const DMA_DST: u8 = 0x03; // 3 bytes: pixel offset into video memory
+pub const DMA_LEN: u8 = 0x06; // 3 bytes: number of pixels to copy
+pub const DMA_CTRL: u8 = 0x09; // write 1 to start, reads back the status of the last transfer
+pub const DMA_REGISTER_COUNT: u8 = 10;
+
+pub const DMA_START: u8 = 0x01;
+pub const DMA_STATUS_CLIPPED: u8 = 0x01;
+
+pub const DMA_BYTES_PER_PIXEL: usize = 3;
+pub const DMA_CYCLES_PER_PIXEL: u64 = 1;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DmaRegisters {
+    pub src: u32,
+    pub dst: u32,
+    pub len: u32,
+    pub status: u8,
+}
+
+impl DmaRegisters {
+    pub fn read(&self, offset: u8) -> u8 {
+        match offset {
+            DMA_SRC..DMA_DST => field_byte(self.src, offset - DMA_SRC),
+            DMA_DST..DMA_LEN => field_byte(self.dst, offset - DMA_DST),
+            DMA_LEN..DMA_CTRL => field_byte(self.len, offset - DMA_LEN),
+            DMA_CTRL => self.status,
+            _ => 0,
+        }
+    }
+
+    // Returns true when the write asks for a transfer to start.
+    pub fn write(&mut self, offset: u8, value: u8) -> bool {
+        match offset {
+            DMA_SRC..DMA_DST => set_field_byte(&mut self.src, offset - DMA_SRC, value),
+            DMA_DST..DMA_LEN => set_field_byte(&mut self.dst, offset - DMA_DST, value),
+            DMA_LEN..DMA_CTRL => set_field_byte(&mut self.len, offset - DMA_LEN, value),
+            DMA_CTRL => return value & DMA_START != 0,
+            _ => {}
+        }
+        false
+    }
+}
+
+fn field_byte(field: u32, byte: u8) -> u8 {
+    (field >> (byte * 8)) as u8
+}
+
+fn set_field_byte(field: &mut u32, byte: u8, value: u8) {
+    let shift = byte * 8;
+    *field = (*field & !(0xFF << shift)) | ((value as u32) << shift);
+}
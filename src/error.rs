@@ -0,0 +1,336 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt::Display;
+
+use crate::cpu::{InvalidOpcode, InvalidRegister, InvalidVideoOpcode, flag_letters};
+use crate::segment::SegmentKind;
+use crate::trace::TraceRecord;
+
+#[derive(Debug)]
+pub enum VmError {
+    InvalidOpcode(InvalidOpcode),
+    InvalidRegister(InvalidRegister),
+    InvalidVideoOpcode(InvalidVideoOpcode),
+    AddressOutOfBounds {
+        addr: u16,
+        pc: u16,
+    },
+    RangeOutOfBounds {
+        start: u16,
+        len: u16,
+        pc: u16,
+    },
+    UnregisteredHcall {
+        number: u8,
+        pc: u16,
+    },
+    DivisionByZero {
+        pc: u16,
+    },
+    InvalidBitIndex {
+        bit: u8,
+        pc: u16,
+    },
+    InvalidVector {
+        vector: u8,
+        pc: u16,
+    },
+    // `chain` holds the most recent return addresses, innermost first.
+    CallDepthExceeded {
+        depth: u32,
+        pc: u16,
+        chain: Vec<u16>,
+    },
+    StackOverflow {
+        sp: u16,
+        pc: u16,
+    },
+    StackUnderflow {
+        sp: u16,
+        pc: u16,
+    },
+    WriteProtected {
+        addr: u16,
+        pc: u16,
+    },
+    // The instruction at `pc` starts in a no-execute range.
+    ExecuteProtected {
+        pc: u16,
+    },
+    // The watchdog ran out before the instruction at `pc`.
+    WatchdogExpired {
+        pc: u16,
+    },
+    InvalidHeader {
+        reason: &'static str,
+    },
+    ProgramTooLarge {
+        load_address: u16,
+        length: usize,
+    },
+    // The payload's CRC-32 is not the one its header records.
+    ChecksumMismatch {
+        expected: u32,
+        actual: u32,
+    },
+    // Segment `index` of a segmented program runs past the `capacity` bytes of memory, or
+    // of video memory for a video segment.
+    SegmentOutOfRange {
+        index: usize,
+        kind: SegmentKind,
+        offset: u32,
+        length: u32,
+        capacity: usize,
+    },
+    // Two segments of a segmented program cover the same byte, the first at `at`.
+    SegmentOverlap {
+        first: usize,
+        second: usize,
+        at: u32,
+    },
+    // The machine was started at an entry point no load put program bytes at.
+    EntryOutsideProgram {
+        entry: u16,
+    },
+    // A streamed image held more than the `capacity` bytes of memory from `offset` on.
+    ImageTooLarge {
+        offset: usize,
+        capacity: usize,
+    },
+    // Reading an image failed.
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+    // An error raised while executing, with the machine state at the moment it happened.
+    Fault(Box<FaultReport>),
+}
+
+// What a crash report shows. `pc` is where the faulting instruction starts, which for a
+// fault while delivering an interrupt is the instruction that was about to run.
+#[derive(Debug)]
+pub struct FaultReport {
+    pub error: VmError,
+    pub pc: u16,
+    // The instruction's bytes, or as many as could be read when it doesn't decode.
+    pub bytes: Vec<u8>,
+    pub registers: [u16; 8],
+    pub sp: u16,
+    pub flags: u8,
+    // The instructions leading up to the fault, oldest first. Empty unless the CPU keeps
+    // a fault history.
+    pub history: Vec<TraceRecord>,
+}
+
+impl VmError {
+    // The error itself, without the report around it.
+    pub fn cause(&self) -> &VmError {
+        match self {
+            VmError::Fault(report) => &report.error,
+            error => error,
+        }
+    }
+
+    pub fn report(&self) -> Option<&FaultReport> {
+        match self {
+            VmError::Fault(report) => Some(report),
+            _ => None,
+        }
+    }
+
+    // The pc the error names, if it happened while executing.
+    pub fn pc(&self) -> Option<u16> {
+        match self {
+            VmError::AddressOutOfBounds { pc, .. }
+            | VmError::RangeOutOfBounds { pc, .. }
+            | VmError::UnregisteredHcall { pc, .. }
+            | VmError::DivisionByZero { pc }
+            | VmError::InvalidBitIndex { pc, .. }
+            | VmError::InvalidVector { pc, .. }
+            | VmError::CallDepthExceeded { pc, .. }
+            | VmError::StackOverflow { pc, .. }
+            | VmError::StackUnderflow { pc, .. }
+            | VmError::WriteProtected { pc, .. }
+            | VmError::ExecuteProtected { pc }
+            | VmError::WatchdogExpired { pc } => Some(*pc),
+            VmError::Fault(report) => Some(report.pc),
+            VmError::InvalidOpcode(_)
+            | VmError::InvalidRegister(_)
+            | VmError::InvalidVideoOpcode(_)
+            | VmError::InvalidHeader { .. }
+            | VmError::ProgramTooLarge { .. }
+            | VmError::ChecksumMismatch { .. }
+            | VmError::SegmentOutOfRange { .. }
+            | VmError::SegmentOverlap { .. }
+            | VmError::EntryOutsideProgram { .. }
+            | VmError::ImageTooLarge { .. } => None,
+            #[cfg(feature = "std")]
+            VmError::Io(_) => None,
+        }
+    }
+}
+
+impl Display for VmError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            VmError::InvalidOpcode(e) => write!(f, "{}", e),
+            VmError::InvalidRegister(e) => write!(f, "{}", e),
+            VmError::InvalidVideoOpcode(e) => write!(f, "{}", e),
+            VmError::AddressOutOfBounds { addr, pc } => {
+                write!(f, "Address out of bounds: {:#06x} (pc {:#06x})", addr, pc)
+            }
+            VmError::RangeOutOfBounds { start, len, pc } => write!(
+                f,
+                "Memory range out of bounds: {} bytes at {:#06x} (pc {:#06x})",
+                len, start, pc
+            ),
+            VmError::UnregisteredHcall { number, pc } => {
+                write!(f, "Unregistered host call: {} (pc {:#06x})", number, pc)
+            }
+            VmError::DivisionByZero { pc } => write!(f, "Division by zero (pc {:#06x})", pc),
+            VmError::InvalidBitIndex { bit, pc } => {
+                write!(f, "Invalid bit index: {} (pc {:#06x})", bit, pc)
+            }
+            VmError::InvalidVector { vector, pc } => {
+                write!(f, "Invalid interrupt vector: {} (pc {:#06x})", vector, pc)
+            }
+            VmError::CallDepthExceeded { depth, pc, chain } => {
+                write!(f, "Call depth exceeded: {} (pc {:#06x})", depth, pc)?;
+                for (i, addr) in chain.iter().enumerate() {
+                    let separator = if i == 0 { ", returning to" } else { " <-" };
+                    write!(f, "{} {:#06x}", separator, addr)?;
+                }
+                Ok(())
+            }
+            VmError::StackOverflow { sp, pc } => {
+                write!(f, "Stack overflow: sp {:#06x} (pc {:#06x})", sp, pc)
+            }
+            VmError::StackUnderflow { sp, pc } => {
+                write!(f, "Stack underflow: sp {:#06x} (pc {:#06x})", sp, pc)
+            }
+            VmError::WriteProtected { addr, pc } => {
+                write!(
+                    f,
+                    "Write to protected address: {:#06x} (pc {:#06x})",
+                    addr, pc
+                )
+            }
+            VmError::ExecuteProtected { pc } => {
+                write!(f, "Execute from protected address: {:#06x}", pc)
+            }
+            VmError::WatchdogExpired { pc } => write!(f, "Watchdog expired (pc {:#06x})", pc),
+            VmError::InvalidHeader { reason } => write!(f, "Invalid program header: {}", reason),
+            VmError::ProgramTooLarge {
+                load_address,
+                length,
+            } => write!(
+                f,
+                "Program of {} bytes does not fit at load address {:#06x}",
+                length, load_address
+            ),
+            VmError::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "Program checksum mismatch: header says {:#010x}, payload is {:#010x}",
+                expected, actual
+            ),
+            VmError::SegmentOutOfRange {
+                index,
+                kind,
+                offset,
+                length,
+                capacity,
+            } => write!(
+                f,
+                "Segment {} ({}, {} bytes at {:#06x}) runs past the {} bytes of {}",
+                index,
+                kind.name(),
+                length,
+                offset,
+                capacity,
+                if *kind == SegmentKind::Video {
+                    "video memory"
+                } else {
+                    "memory"
+                }
+            ),
+            VmError::SegmentOverlap { first, second, at } => write!(
+                f,
+                "Segments {} and {} overlap at {:#06x}",
+                first, second, at
+            ),
+            VmError::EntryOutsideProgram { entry } => write!(
+                f,
+                "Entry point {:#06x} is outside the loaded program",
+                entry
+            ),
+            VmError::ImageTooLarge { offset, capacity } => write!(
+                f,
+                "Image does not fit in the {} bytes of memory from {:#06x}",
+                capacity, offset
+            ),
+            #[cfg(feature = "std")]
+            VmError::Io(e) => write!(f, "Could not read image: {}", e),
+            VmError::Fault(report) => write!(f, "{}", report),
+        }
+    }
+}
+
+impl Display for FaultReport {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        writeln!(f, "Fault at {:#06x}: {}", self.pc, self.error)?;
+        write!(f, "  instruction:")?;
+        for byte in &self.bytes {
+            write!(f, " {:02x}", byte)?;
+        }
+        write!(f, "\n  registers:")?;
+        for (i, value) in self.registers.iter().enumerate() {
+            write!(f, " r{} {:04x}", i, value)?;
+        }
+        write!(
+            f,
+            "\n  sp {:04x} flags {}",
+            self.sp,
+            flag_letters(self.flags)
+        )?;
+        if !self.history.is_empty() {
+            write!(f, "\n  last {} instructions:", self.history.len())?;
+            for record in &self.history {
+                write!(f, "\n    {}", record)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl core::error::Error for VmError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "std")]
+            VmError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for VmError {
+    fn from(e: std::io::Error) -> Self {
+        VmError::Io(e)
+    }
+}
+
+impl From<InvalidOpcode> for VmError {
+    fn from(e: InvalidOpcode) -> Self {
+        VmError::InvalidOpcode(e)
+    }
+}
+
+impl From<InvalidRegister> for VmError {
+    fn from(e: InvalidRegister) -> Self {
+        VmError::InvalidRegister(e)
+    }
+}
+
+impl From<InvalidVideoOpcode> for VmError {
+    fn from(e: InvalidVideoOpcode) -> Self {
+        VmError::InvalidVideoOpcode(e)
+    }
+}
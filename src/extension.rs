@@ -0,0 +1,156 @@
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use core::fmt::Display;
+use core::ops::RangeInclusive;
+
+use crate::cpu::{MicroCVMCpu, Register};
+use crate::error::VmError;
+
+// Opcode bytes no built-in instruction will ever take, left for hosts to give meaning to.
+pub const EXTENSION_OPCODES: RangeInclusive<u8> = 0xE0..=0xEF;
+// Extension instructions are at most 4 bytes long, leaving room in isa::MAX_LENGTH.
+pub const MAX_OPERAND_BYTES: u8 = 3;
+
+// Called with the operand bytes that follow the opcode. The pc moves past the instruction
+// once it returns Ok; an error faults the way any other instruction's would.
+pub type ExtensionHandler = Box<dyn FnMut(&mut CpuView, &[u8]) -> Result<(), VmError> + Send>;
+
+pub struct ExtensionDef {
+    pub operand_bytes: u8,
+    pub handler: ExtensionHandler,
+    // What disassembly calls the instruction, rather than `ext` and its opcode.
+    pub mnemonic: Option<String>,
+}
+
+impl ExtensionDef {
+    pub fn new(operand_bytes: u8, handler: ExtensionHandler) -> Self {
+        Self {
+            operand_bytes,
+            handler,
+            mnemonic: None,
+        }
+    }
+
+    pub fn with_mnemonic(mut self, mnemonic: &str) -> Self {
+        self.mnemonic = Some(String::from(mnemonic));
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtensionError {
+    // The opcode isn't in EXTENSION_OPCODES.
+    OutOfRange(u8),
+    AlreadyRegistered(u8),
+    // More than MAX_OPERAND_BYTES.
+    TooLong { opcode: u8, operand_bytes: u8 },
+}
+
+impl Display for ExtensionError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ExtensionError::OutOfRange(opcode) => write!(
+                f,
+                "Opcode {:#04x} is outside the extension range {:#04x}-{:#04x}",
+                opcode,
+                EXTENSION_OPCODES.start(),
+                EXTENSION_OPCODES.end()
+            ),
+            ExtensionError::AlreadyRegistered(opcode) => {
+                write!(f, "Opcode {:#04x} is already registered", opcode)
+            }
+            ExtensionError::TooLong {
+                opcode,
+                operand_bytes,
+            } => write!(
+                f,
+                "Opcode {:#04x} can't take {} operand bytes, at most {}",
+                opcode, operand_bytes, MAX_OPERAND_BYTES
+            ),
+        }
+    }
+}
+
+// The extension instructions a CPU has, by opcode.
+#[derive(Default)]
+pub struct Extensions {
+    pub(crate) defs: BTreeMap<u8, ExtensionDef>,
+}
+
+impl Extensions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, opcode: u8, def: ExtensionDef) -> Result<(), ExtensionError> {
+        if !EXTENSION_OPCODES.contains(&opcode) {
+            return Err(ExtensionError::OutOfRange(opcode));
+        }
+        if def.operand_bytes > MAX_OPERAND_BYTES {
+            return Err(ExtensionError::TooLong {
+                opcode,
+                operand_bytes: def.operand_bytes,
+            });
+        }
+        if self.defs.contains_key(&opcode) {
+            return Err(ExtensionError::AlreadyRegistered(opcode));
+        }
+        self.defs.insert(opcode, def);
+        Ok(())
+    }
+
+    pub fn get(&self, opcode: u8) -> Option<&ExtensionDef> {
+        self.defs.get(&opcode)
+    }
+
+    // The whole instruction's length, opcode included, if `opcode` is registered.
+    pub fn length(&self, opcode: u8) -> Option<u16> {
+        self.get(opcode).map(|def| 1 + def.operand_bytes as u16)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.defs.is_empty()
+    }
+}
+
+// What an extension handler can see and change of the CPU running it.
+pub struct CpuView<'a> {
+    cpu: &'a mut MicroCVMCpu,
+}
+
+impl<'a> CpuView<'a> {
+    pub fn new(cpu: &'a mut MicroCVMCpu) -> Self {
+        Self { cpu }
+    }
+
+    pub fn reg(&self, reg: Register) -> u16 {
+        self.cpu.get_reg(reg)
+    }
+
+    // Bits above the register width are dropped.
+    pub fn set_reg(&mut self, reg: Register, value: u16) {
+        self.cpu.set_reg(reg, value);
+    }
+
+    // The address of the extension instruction itself.
+    pub fn pc(&self) -> u16 {
+        self.cpu.pc
+    }
+
+    pub fn flags(&self) -> u8 {
+        self.cpu.flags
+    }
+
+    pub fn set_flags(&mut self, flags: u8) {
+        self.cpu.flags = flags;
+    }
+
+    pub fn read_mem(&self, addr: u16) -> Result<u8, VmError> {
+        self.cpu.read_mem(addr)
+    }
+
+    pub fn write_mem(&mut self, addr: u16, value: u8) -> Result<(), VmError> {
+        self.cpu.write_mem(addr, value)
+    }
+}
@@ -0,0 +1,124 @@
+use alloc::boxed::Box;
+
+use crate::error::VmError;
+
+pub const FAULT_CODE: u8 = 0x00; // what went wrong, see FAULT_*; 0 before the first trap
+pub const FAULT_PC: u8 = 0x01; // 2 bytes: where the faulting instruction starts
+pub const FAULT_ADDR: u8 = 0x03; // 2 bytes: the address involved, 0 if there is none
+pub const FAULT_REGISTER_COUNT: u8 = 5;
+
+pub const FAULT_INVALID_OPCODE: u8 = 1;
+pub const FAULT_INVALID_REGISTER: u8 = 2;
+pub const FAULT_ADDRESS_OUT_OF_BOUNDS: u8 = 3;
+pub const FAULT_RANGE_OUT_OF_BOUNDS: u8 = 4;
+pub const FAULT_UNREGISTERED_HCALL: u8 = 5;
+pub const FAULT_DIVISION_BY_ZERO: u8 = 6;
+pub const FAULT_INVALID_BIT_INDEX: u8 = 7;
+pub const FAULT_CALL_DEPTH_EXCEEDED: u8 = 8;
+pub const FAULT_STACK_OVERFLOW: u8 = 9;
+pub const FAULT_STACK_UNDERFLOW: u8 = 10;
+pub const FAULT_WRITE_PROTECTED: u8 = 11;
+pub const FAULT_INVALID_VECTOR: u8 = 12;
+pub const FAULT_INVALID_VIDEO_OPCODE: u8 = 13;
+pub const FAULT_EXECUTE_PROTECTED: u8 = 14;
+
+// The interrupt vector whose handler a trapped fault enters.
+pub const VECTOR_FAULT: u8 = 1;
+
+// What the CPU does when an instruction faults.
+#[derive(Default)]
+pub enum FaultPolicy {
+    // Stops with the error.
+    #[default]
+    Halt,
+    // Enters the guest's handler at VECTOR_FAULT, or stops like Halt if the table entry
+    // is 0 or entering it faults too.
+    Trap,
+    // Asks the embedder.
+    Host(Box<dyn FnMut(&FaultInfo) -> FaultAction + Send>),
+}
+
+// A fault about to be handled by a FaultPolicy::Host callback.
+#[derive(Debug)]
+pub struct FaultInfo<'a> {
+    pub error: &'a VmError,
+    pub pc: u16,
+    // Where execution continues if the instruction is skipped.
+    pub next_pc: u16,
+    pub registers: [u16; 8],
+    pub flags: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultAction {
+    // Carries on after the faulting instruction, leaving whatever it did before faulting.
+    Skip,
+    // Runs the faulting instruction again, for an embedder that has fixed what was wrong.
+    Retry,
+    Halt,
+}
+
+// The registers at FAULT_BASE, describing the last fault that trapped.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FaultRegisters {
+    pub code: u8,
+    pub pc: u16,
+    pub addr: u16,
+}
+
+impl FaultRegisters {
+    pub fn record(&mut self, error: &VmError, pc: u16) {
+        self.code = fault_code(error);
+        self.pc = pc;
+        self.addr = match error.cause() {
+            VmError::AddressOutOfBounds { addr, .. } | VmError::WriteProtected { addr, .. } => {
+                *addr
+            }
+            VmError::RangeOutOfBounds { start, .. } => *start,
+            _ => 0,
+        };
+    }
+
+    pub fn read(&self, offset: u8) -> u8 {
+        match offset {
+            FAULT_CODE => self.code,
+            FAULT_PC => self.pc as u8,
+            0x02 => (self.pc >> 8) as u8,
+            FAULT_ADDR => self.addr as u8,
+            0x04 => (self.addr >> 8) as u8,
+            _ => 0,
+        }
+    }
+}
+
+// The FAULT_* code the guest sees for `error`, 0 for errors that never come from executing.
+pub fn fault_code(error: &VmError) -> u8 {
+    match error.cause() {
+        VmError::InvalidOpcode(_) => FAULT_INVALID_OPCODE,
+        VmError::InvalidRegister(_) => FAULT_INVALID_REGISTER,
+        VmError::AddressOutOfBounds { .. } => FAULT_ADDRESS_OUT_OF_BOUNDS,
+        VmError::RangeOutOfBounds { .. } => FAULT_RANGE_OUT_OF_BOUNDS,
+        VmError::UnregisteredHcall { .. } => FAULT_UNREGISTERED_HCALL,
+        VmError::DivisionByZero { .. } => FAULT_DIVISION_BY_ZERO,
+        VmError::InvalidBitIndex { .. } => FAULT_INVALID_BIT_INDEX,
+        VmError::CallDepthExceeded { .. } => FAULT_CALL_DEPTH_EXCEEDED,
+        VmError::StackOverflow { .. } => FAULT_STACK_OVERFLOW,
+        VmError::StackUnderflow { .. } => FAULT_STACK_UNDERFLOW,
+        VmError::WriteProtected { .. } => FAULT_WRITE_PROTECTED,
+        VmError::ExecuteProtected { .. } => FAULT_EXECUTE_PROTECTED,
+        VmError::InvalidVector { .. } => FAULT_INVALID_VECTOR,
+        VmError::InvalidVideoOpcode(_) => FAULT_INVALID_VIDEO_OPCODE,
+        // Raised between instructions, and never trapped.
+        VmError::WatchdogExpired { .. }
+        | VmError::InvalidHeader { .. }
+        | VmError::ProgramTooLarge { .. }
+        | VmError::ChecksumMismatch { .. }
+        | VmError::SegmentOutOfRange { .. }
+        | VmError::SegmentOverlap { .. }
+        | VmError::EntryOutsideProgram { .. }
+        | VmError::ImageTooLarge { .. }
+        | VmError::Fault(_) => 0,
+        #[cfg(feature = "std")]
+        VmError::Io(_) => 0,
+    }
+}
@@ -0,0 +1,259 @@
+use std::ffi::c_int;
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::cpu::MicroCVMCpu;
+use crate::error::VmError;
+
+pub const MICROCVM_OK: c_int = 0;
+pub const MICROCVM_HALTED: c_int = 1;
+
+pub const MICROCVM_ERR_NULL: c_int = -1;
+pub const MICROCVM_ERR_PANIC: c_int = -2;
+pub const MICROCVM_ERR_INVALID_ARGUMENT: c_int = -3;
+pub const MICROCVM_ERR_INVALID_OPCODE: c_int = -10;
+pub const MICROCVM_ERR_INVALID_REGISTER: c_int = -11;
+pub const MICROCVM_ERR_ADDRESS_OUT_OF_BOUNDS: c_int = -12;
+pub const MICROCVM_ERR_UNREGISTERED_HCALL: c_int = -13;
+pub const MICROCVM_ERR_DIVISION_BY_ZERO: c_int = -14;
+pub const MICROCVM_ERR_WRITE_PROTECTED: c_int = -15;
+pub const MICROCVM_ERR_INVALID_HEADER: c_int = -16;
+pub const MICROCVM_ERR_PROGRAM_TOO_LARGE: c_int = -17;
+pub const MICROCVM_ERR_INVALID_BIT_INDEX: c_int = -18;
+pub const MICROCVM_ERR_RANGE_OUT_OF_BOUNDS: c_int = -19;
+pub const MICROCVM_ERR_CALL_DEPTH_EXCEEDED: c_int = -20;
+pub const MICROCVM_ERR_STACK_OVERFLOW: c_int = -21;
+pub const MICROCVM_ERR_STACK_UNDERFLOW: c_int = -22;
+pub const MICROCVM_ERR_INVALID_VECTOR: c_int = -23;
+pub const MICROCVM_ERR_IO: c_int = -24;
+pub const MICROCVM_ERR_CHECKSUM_MISMATCH: c_int = -25;
+pub const MICROCVM_ERR_BAD_SEGMENT: c_int = -26;
+pub const MICROCVM_ERR_BAD_ENTRY: c_int = -27;
+pub const MICROCVM_ERR_WATCHDOG_EXPIRED: c_int = -28;
+pub const MICROCVM_ERR_INVALID_VIDEO_OPCODE: c_int = -29;
+pub const MICROCVM_ERR_EXECUTE_PROTECTED: c_int = -30;
+
+pub struct MicroCvm {
+    cpu: MicroCVMCpu,
+}
+
+fn error_code(error: &VmError) -> c_int {
+    match error {
+        VmError::InvalidOpcode(_) => MICROCVM_ERR_INVALID_OPCODE,
+        VmError::InvalidRegister(_) => MICROCVM_ERR_INVALID_REGISTER,
+        VmError::InvalidVideoOpcode(_) => MICROCVM_ERR_INVALID_VIDEO_OPCODE,
+        VmError::AddressOutOfBounds { .. } => MICROCVM_ERR_ADDRESS_OUT_OF_BOUNDS,
+        VmError::UnregisteredHcall { .. } => MICROCVM_ERR_UNREGISTERED_HCALL,
+        VmError::DivisionByZero { .. } => MICROCVM_ERR_DIVISION_BY_ZERO,
+        VmError::WriteProtected { .. } => MICROCVM_ERR_WRITE_PROTECTED,
+        VmError::ExecuteProtected { .. } => MICROCVM_ERR_EXECUTE_PROTECTED,
+        VmError::InvalidHeader { .. } => MICROCVM_ERR_INVALID_HEADER,
+        VmError::ProgramTooLarge { .. } | VmError::ImageTooLarge { .. } => {
+            MICROCVM_ERR_PROGRAM_TOO_LARGE
+        }
+        VmError::InvalidBitIndex { .. } => MICROCVM_ERR_INVALID_BIT_INDEX,
+        VmError::RangeOutOfBounds { .. } => MICROCVM_ERR_RANGE_OUT_OF_BOUNDS,
+        VmError::CallDepthExceeded { .. } => MICROCVM_ERR_CALL_DEPTH_EXCEEDED,
+        VmError::StackOverflow { .. } => MICROCVM_ERR_STACK_OVERFLOW,
+        VmError::StackUnderflow { .. } => MICROCVM_ERR_STACK_UNDERFLOW,
+        VmError::InvalidVector { .. } => MICROCVM_ERR_INVALID_VECTOR,
+        VmError::Io(_) => MICROCVM_ERR_IO,
+        VmError::ChecksumMismatch { .. } => MICROCVM_ERR_CHECKSUM_MISMATCH,
+        VmError::SegmentOutOfRange { .. } | VmError::SegmentOverlap { .. } => {
+            MICROCVM_ERR_BAD_SEGMENT
+        }
+        VmError::EntryOutsideProgram { .. } => MICROCVM_ERR_BAD_ENTRY,
+        VmError::WatchdogExpired { .. } => MICROCVM_ERR_WATCHDOG_EXPIRED,
+        VmError::Fault(report) => error_code(&report.error),
+    }
+}
+
+// Panics must not unwind into the host, so every entry point runs inside this.
+fn guarded(vm: *mut MicroCvm, f: impl FnOnce(&mut MicroCvm) -> c_int) -> c_int {
+    // SAFETY: callers promise `vm` is null or came from `microcvm_new` and is not in use
+    // elsewhere.
+    let Some(vm) = (unsafe { vm.as_mut() }) else {
+        return MICROCVM_ERR_NULL;
+    };
+    panic::catch_unwind(AssertUnwindSafe(|| f(vm))).unwrap_or(MICROCVM_ERR_PANIC)
+}
+
+fn status(result: Result<bool, VmError>) -> c_int {
+    match result {
+        Ok(true) => MICROCVM_HALTED,
+        Ok(false) => MICROCVM_OK,
+        Err(e) => error_code(&e),
+    }
+}
+
+/// Creates a VM with empty memory. Returns null if allocation panicked.
+#[unsafe(no_mangle)]
+pub extern "C" fn microcvm_new() -> *mut MicroCvm {
+    panic::catch_unwind(|| {
+        Box::into_raw(Box::new(MicroCvm {
+            cpu: MicroCVMCpu::empty(),
+        }))
+    })
+    .unwrap_or(std::ptr::null_mut())
+}
+
+/// # Safety
+/// `vm` must be null or a pointer from `microcvm_new` that has not been freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn microcvm_free(vm: *mut MicroCvm) {
+    if !vm.is_null() {
+        // SAFETY: upheld by the caller.
+        let vm = unsafe { Box::from_raw(vm) };
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(vm)));
+    }
+}
+
+/// Copies `len` bytes from `bytes` into physical memory at `offset`.
+///
+/// # Safety
+/// `vm` must come from `microcvm_new`, and `bytes` must point to `len` readable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn microcvm_load(
+    vm: *mut MicroCvm,
+    bytes: *const u8,
+    len: usize,
+    offset: u16,
+) -> c_int {
+    if bytes.is_null() && len > 0 {
+        return MICROCVM_ERR_NULL;
+    }
+    guarded(vm, |vm| {
+        let bytes = match len {
+            0 => &[][..],
+            // SAFETY: upheld by the caller.
+            _ => unsafe { std::slice::from_raw_parts(bytes, len) },
+        };
+        match vm.cpu.load_raw(bytes, offset) {
+            Ok(()) => MICROCVM_OK,
+            Err(e) => error_code(&e),
+        }
+    })
+}
+
+/// Executes one instruction. Returns MICROCVM_HALTED once the program has halted.
+///
+/// # Safety
+/// `vm` must come from `microcvm_new`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn microcvm_step(vm: *mut MicroCvm) -> c_int {
+    guarded(vm, |vm| {
+        if vm.cpu.halted {
+            return MICROCVM_HALTED;
+        }
+        status(vm.cpu.execute_instruction().map(|()| vm.cpu.halted))
+    })
+}
+
+/// Executes up to `max_instructions` instructions. Returns MICROCVM_OK if the limit was
+/// reached and MICROCVM_HALTED if the program halted first.
+///
+/// # Safety
+/// `vm` must come from `microcvm_new`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn microcvm_run(vm: *mut MicroCvm, max_instructions: u64) -> c_int {
+    guarded(vm, |vm| {
+        status(
+            vm.cpu
+                .run_with_limits(max_instructions)
+                .map(|_| vm.cpu.halted),
+        )
+    })
+}
+
+/// Writes register `index` (0-7) to `out`.
+///
+/// # Safety
+/// `vm` must come from `microcvm_new`, and `out` must be writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn microcvm_get_register(
+    vm: *mut MicroCvm,
+    index: u8,
+    out: *mut u8,
+) -> c_int {
+    if out.is_null() {
+        return MICROCVM_ERR_NULL;
+    }
+    guarded(vm, |vm| match vm.cpu.registers.get(index as usize) {
+        Some(value) => {
+            // Machines made through the C API always have 8-bit registers.
+            // SAFETY: upheld by the caller.
+            unsafe { out.write(*value as u8) };
+            MICROCVM_OK
+        }
+        None => MICROCVM_ERR_INVALID_REGISTER,
+    })
+}
+
+/// Reads `len` bytes of guest memory starting at `addr`, as the program would see them.
+///
+/// # Safety
+/// `vm` must come from `microcvm_new`, and `out` must point to `len` writable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn microcvm_read_mem(
+    vm: *mut MicroCvm,
+    addr: u16,
+    out: *mut u8,
+    len: usize,
+) -> c_int {
+    if out.is_null() && len > 0 {
+        return MICROCVM_ERR_NULL;
+    }
+    guarded(vm, |vm| {
+        for i in 0..len {
+            let Ok(offset) = u16::try_from(i) else {
+                return MICROCVM_ERR_INVALID_ARGUMENT;
+            };
+            match vm.cpu.read_mem(addr.wrapping_add(offset)) {
+                // SAFETY: upheld by the caller.
+                Ok(byte) => unsafe { out.add(i).write(byte) },
+                Err(e) => return error_code(&e),
+            }
+        }
+        MICROCVM_OK
+    })
+}
+
+/// Returns the number of pixels in video memory, or 0 if `vm` is null.
+///
+/// # Safety
+/// `vm` must be null or come from `microcvm_new`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn microcvm_framebuffer_len(vm: *const MicroCvm) -> usize {
+    // SAFETY: upheld by the caller.
+    let Some(vm) = (unsafe { vm.as_ref() }) else {
+        return 0;
+    };
+    panic::catch_unwind(AssertUnwindSafe(|| vm.cpu.video_memory.len())).unwrap_or(0)
+}
+
+/// Copies `pixels` pixels of video memory starting at pixel `start` into `out` as RGBA.
+///
+/// # Safety
+/// `vm` must come from `microcvm_new`, and `out` must point to `pixels * 4` writable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn microcvm_read_framebuffer(
+    vm: *mut MicroCvm,
+    start: usize,
+    out: *mut u8,
+    pixels: usize,
+) -> c_int {
+    if out.is_null() && pixels > 0 {
+        return MICROCVM_ERR_NULL;
+    }
+    guarded(vm, |vm| {
+        let in_bounds = start
+            .checked_add(pixels)
+            .is_some_and(|end| end <= vm.cpu.video_memory.len());
+        if !in_bounds {
+            return MICROCVM_ERR_INVALID_ARGUMENT;
+        }
+        // SAFETY: upheld by the caller.
+        let out = unsafe { std::slice::from_raw_parts_mut(out, pixels * 4) };
+        vm.cpu.video_memory.copy_rgba(start, out);
+        MICROCVM_OK
+    })
+}
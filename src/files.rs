@@ -0,0 +1,275 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+
+// Command block registers, multi-byte fields little-endian.
+pub const FS_ADDR: u8 = 0x00; // 2 bytes: guest address of the path or data buffer
+pub const FS_LEN: u8 = 0x02; // 2 bytes: path length, or bytes to read or write
+pub const FS_FD: u8 = 0x04; // file the command works on, set by open and create
+pub const FS_CTRL: u8 = 0x05; // write a command, reads back the status of the last one
+pub const FS_RESULT: u8 = 0x06; // 2 bytes: bytes the last read or write transferred
+pub const FS_POS: u8 = 0x08; // 4 bytes: offset to seek to, the new offset afterwards
+pub const FS_REGISTER_COUNT: u8 = 12;
+
+pub const FS_OPEN: u8 = 0x01;
+pub const FS_READ: u8 = 0x02;
+pub const FS_SEEK: u8 = 0x03;
+pub const FS_CLOSE: u8 = 0x04;
+pub const FS_WRITE: u8 = 0x05;
+// Opens for writing, creating or truncating the file.
+pub const FS_CREATE: u8 = 0x06;
+
+// Status codes left in FS_CTRL by the last command.
+pub const FS_OK: u8 = 0;
+pub const FS_NOT_FOUND: u8 = 1;
+// The path leaves the sandbox root.
+pub const FS_DENIED: u8 = 2;
+pub const FS_BAD_PATH: u8 = 3;
+pub const FS_TOO_MANY_FILES: u8 = 4;
+pub const FS_BAD_FD: u8 = 5;
+pub const FS_READ_ONLY: u8 = 6;
+pub const FS_IO_ERROR: u8 = 7;
+pub const FS_BAD_BUFFER: u8 = 8;
+pub const FS_BAD_COMMAND: u8 = 9;
+pub const FS_NOT_ATTACHED: u8 = 10;
+
+pub const FS_MAX_FILES: usize = 8;
+
+struct Sandbox {
+    // Canonical, so resolved paths can be checked with a prefix test.
+    root: PathBuf,
+    read_only: bool,
+}
+
+#[derive(Default)]
+pub struct FileDevice {
+    pub addr: u16,
+    pub len: u16,
+    pub fd: u8,
+    pub status: u8,
+    pub result: u16,
+    pub pos: u32,
+    sandbox: Option<Sandbox>,
+    files: [Option<File>; FS_MAX_FILES],
+}
+
+impl FileDevice {
+    // Gives the guest the files under `root`. Nothing outside it can be reached, through
+    // `..` or through symlinks.
+    pub fn attach(&mut self, root: impl AsRef<Path>, read_only: bool) -> io::Result<()> {
+        let root = root.as_ref().canonicalize()?;
+        if !root.is_dir() {
+            return Err(io::Error::new(
+                ErrorKind::NotADirectory,
+                "sandbox root is not a directory",
+            ));
+        }
+        self.detach();
+        self.sandbox = Some(Sandbox { root, read_only });
+        Ok(())
+    }
+
+    // Closes every open file.
+    pub fn detach(&mut self) {
+        self.sandbox = None;
+        self.files = Default::default();
+    }
+
+    pub fn read(&self, offset: u8) -> u8 {
+        match offset {
+            FS_ADDR..FS_LEN => self.addr.to_le_bytes()[(offset - FS_ADDR) as usize],
+            FS_LEN..FS_FD => self.len.to_le_bytes()[(offset - FS_LEN) as usize],
+            FS_FD => self.fd,
+            FS_CTRL => self.status,
+            FS_RESULT..FS_POS => self.result.to_le_bytes()[(offset - FS_RESULT) as usize],
+            FS_POS..FS_REGISTER_COUNT => self.pos.to_le_bytes()[(offset - FS_POS) as usize],
+            _ => 0,
+        }
+    }
+
+    // Returns the command when the write issues one.
+    pub fn write(&mut self, offset: u8, value: u8) -> Option<u8> {
+        match offset {
+            FS_ADDR..FS_LEN => set_byte(&mut self.addr, offset - FS_ADDR, value),
+            FS_LEN..FS_FD => set_byte(&mut self.len, offset - FS_LEN, value),
+            FS_FD => self.fd = value,
+            FS_CTRL => return Some(value),
+            FS_RESULT..FS_POS => {}
+            FS_POS..FS_REGISTER_COUNT => {
+                let mut bytes = self.pos.to_le_bytes();
+                bytes[(offset - FS_POS) as usize] = value;
+                self.pos = u32::from_le_bytes(bytes);
+            }
+            _ => {}
+        }
+        None
+    }
+
+    // `path` is relative to the sandbox root and uses `/` between components.
+    pub fn open(&mut self, path: &[u8], create: bool) -> u8 {
+        let Some(sandbox) = &self.sandbox else {
+            return FS_NOT_ATTACHED;
+        };
+        if create && sandbox.read_only {
+            return FS_READ_ONLY;
+        }
+        let Some(slot) = self.files.iter().position(Option::is_none) else {
+            return FS_TOO_MANY_FILES;
+        };
+        let path = match sandbox.resolve(path, create) {
+            Ok(path) => path,
+            Err(status) => return status,
+        };
+
+        let file = if create {
+            OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(path)
+        } else {
+            File::open(path)
+        };
+        match file {
+            Ok(file) => {
+                self.files[slot] = Some(file);
+                self.fd = slot as u8;
+                FS_OK
+            }
+            Err(e) => io_status(&e),
+        }
+    }
+
+    pub fn read_file(&mut self, buffer: &mut [u8]) -> u8 {
+        self.result = 0;
+        let file = match self.file() {
+            Ok(file) => file,
+            Err(status) => return status,
+        };
+        // Keep reading until the buffer is full or the file ends, like a blocking read.
+        let mut total = 0;
+        while total < buffer.len() {
+            match file.read(&mut buffer[total..]) {
+                Ok(0) => break,
+                Ok(n) => total += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return io_status(&e),
+            }
+        }
+        self.result = total as u16;
+        FS_OK
+    }
+
+    pub fn write_file(&mut self, data: &[u8]) -> u8 {
+        self.result = 0;
+        if self
+            .sandbox
+            .as_ref()
+            .is_some_and(|sandbox| sandbox.read_only)
+        {
+            return FS_READ_ONLY;
+        }
+        let file = match self.file() {
+            Ok(file) => file,
+            Err(status) => return status,
+        };
+        match file.write_all(data) {
+            Ok(()) => {
+                self.result = data.len() as u16;
+                FS_OK
+            }
+            Err(e) => io_status(&e),
+        }
+    }
+
+    pub fn seek(&mut self) -> u8 {
+        let pos = self.pos;
+        let file = match self.file() {
+            Ok(file) => file,
+            Err(status) => return status,
+        };
+        match file.seek(SeekFrom::Start(pos as u64)) {
+            Ok(new) => {
+                self.pos = new as u32;
+                FS_OK
+            }
+            Err(e) => io_status(&e),
+        }
+    }
+
+    pub fn close(&mut self) -> u8 {
+        match self.files.get_mut(self.fd as usize) {
+            Some(file @ Some(_)) => {
+                *file = None;
+                FS_OK
+            }
+            _ => FS_BAD_FD,
+        }
+    }
+
+    fn file(&mut self) -> Result<&mut File, u8> {
+        if self.sandbox.is_none() {
+            return Err(FS_NOT_ATTACHED);
+        }
+        self.files
+            .get_mut(self.fd as usize)
+            .and_then(Option::as_mut)
+            .ok_or(FS_BAD_FD)
+    }
+}
+
+impl Sandbox {
+    // Checks the path before touching the filesystem: only plain names may appear, so
+    // `..`, absolute paths and drive prefixes are rejected outright. The result is then
+    // canonicalized and checked again so a symlink cannot point outside the root.
+    fn resolve(&self, path: &[u8], create: bool) -> Result<PathBuf, u8> {
+        let path = core::str::from_utf8(path).map_err(|_| FS_BAD_PATH)?;
+        if path.is_empty() || path.contains('\0') || path.contains('\\') {
+            return Err(FS_BAD_PATH);
+        }
+        let relative = Path::new(path);
+        for component in relative.components() {
+            match component {
+                Component::Normal(_) | Component::CurDir => {}
+                Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                    return Err(FS_DENIED);
+                }
+            }
+        }
+
+        let joined = self.root.join(relative);
+        let resolved = match joined.canonicalize() {
+            Ok(path) => path,
+            // A file that does not exist yet resolves through the directory it goes into.
+            // A dangling symlink is not created through, that would follow the link.
+            Err(e)
+                if create
+                    && e.kind() == ErrorKind::NotFound
+                    && joined.symlink_metadata().is_err() =>
+            {
+                let name = joined.file_name().ok_or(FS_BAD_PATH)?;
+                let parent = joined.parent().ok_or(FS_BAD_PATH)?;
+                parent.canonicalize().map_err(|e| io_status(&e))?.join(name)
+            }
+            Err(e) => return Err(io_status(&e)),
+        };
+        if !resolved.starts_with(&self.root) || resolved == self.root {
+            return Err(FS_DENIED);
+        }
+        Ok(resolved)
+    }
+}
+
+fn io_status(error: &io::Error) -> u8 {
+    match error.kind() {
+        ErrorKind::NotFound => FS_NOT_FOUND,
+        ErrorKind::PermissionDenied => FS_DENIED,
+        _ => FS_IO_ERROR,
+    }
+}
+
+fn set_byte(field: &mut u16, byte: u8, value: u8) {
+    let mut bytes = field.to_le_bytes();
+    bytes[byte as usize] = value;
+    *field = u16::from_le_bytes(bytes);
+}
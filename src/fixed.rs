@@ -0,0 +1,82 @@
+// Signed 8.8 fixed point, as `fxmul`, `fxdiv` and `fxmuli` work in it: an i16 counting
+// 256ths, so the integer part is the high byte and runs from -128 to 127.
+
+// 1.0.
+pub const ONE: i16 = 256;
+
+/// The product of two 8.8 values, rounded to the nearest 256th with halves away from zero,
+/// and whether it overflowed the integer part. An overflowed product keeps its low 16 bits.
+///
+/// ```
+/// use microcvm_rs::fixed::{from_f64, mul};
+///
+/// let fx = |value| from_f64(value).unwrap();
+/// assert_eq!(mul(fx(1.5), fx(-2.25)), (fx(-3.375), false));
+/// // 1/256 squared rounds to nothing.
+/// assert_eq!(mul(1, 1), (0, false));
+/// assert!(mul(fx(16.0), fx(8.0)).1);
+/// ```
+pub fn mul(a: i16, b: i16) -> (i16, bool) {
+    narrow(rounded_div(a as i32 * b as i32, ONE as i32))
+}
+
+/// `a / b` rounded and checked the way [`mul`] is, or None when `b` is 0.
+///
+/// ```
+/// use microcvm_rs::fixed::{div, from_f64};
+///
+/// let fx = |value| from_f64(value).unwrap();
+/// assert_eq!(div(fx(1.0), fx(3.0)), Some((0x0055, false)));
+/// assert_eq!(div(fx(-7.5), fx(2.5)), Some((fx(-3.0), false)));
+/// assert_eq!(div(fx(100.0), fx(0.5)).map(|(_, carry)| carry), Some(true));
+/// assert_eq!(div(1, 0), None);
+/// ```
+pub fn div(a: i16, b: i16) -> Option<(i16, bool)> {
+    if b == 0 {
+        return None;
+    }
+    Some(narrow(rounded_div((a as i32) << 8, b as i32)))
+}
+
+/// The 8.8 value nearest to `value`, halves away from zero, or None outside -128 to
+/// 127.99609375.
+///
+/// ```
+/// use microcvm_rs::fixed::from_f64;
+///
+/// assert_eq!(from_f64(1.5), Some(0x0180));
+/// assert_eq!(from_f64(-0.5), Some(-0x0080));
+/// assert_eq!(from_f64(128.0), None);
+/// ```
+pub fn from_f64(value: f64) -> Option<i16> {
+    let scaled = value * ONE as f64;
+    // Far enough out that rounding can't overflow, and still out of range.
+    if scaled.is_nan() || scaled.abs() > 65536.0 {
+        return None;
+    }
+    // Rounded by hand, since core has no f64::round.
+    let whole = scaled as i64;
+    let fraction = scaled - whole as f64;
+    let rounded = if fraction >= 0.5 {
+        whole + 1
+    } else if fraction <= -0.5 {
+        whole - 1
+    } else {
+        whole
+    };
+    i16::try_from(rounded).ok()
+}
+
+// Exact, since every 8.8 value is a whole number of 256ths.
+pub fn to_f64(value: i16) -> f64 {
+    value as f64 / ONE as f64
+}
+
+// `n / d` to the nearest whole number, halves away from zero.
+fn rounded_div(n: i32, d: i32) -> i32 {
+    (n + n.signum() * (d.abs() / 2)) / d
+}
+
+fn narrow(value: i32) -> (i16, bool) {
+    (value as i16, i16::try_from(value).is_err())
+}
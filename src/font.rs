@@ -0,0 +1,111 @@
+// 8x8 glyphs for ASCII 0x20 to 0x5F, a byte per row, top row first and the leftmost pixel
+// in the high bit. Lowercase letters draw with the uppercase glyphs and anything else
+// without a glyph draws as '?'.
+pub const GLYPH_SIZE: usize = 8;
+
+const FIRST_GLYPH: u8 = 0x20;
+
+const GLYPHS: [[u8; GLYPH_SIZE]; 64] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x18, 0x18, 0x18, 0x18, 0x18, 0x00, 0x18, 0x00], // '!'
+    [0x6C, 0x6C, 0x48, 0x00, 0x00, 0x00, 0x00, 0x00], // '"'
+    [0x6C, 0x6C, 0xFE, 0x6C, 0xFE, 0x6C, 0x6C, 0x00], // '#'
+    [0x18, 0x7E, 0xD8, 0x7C, 0x1A, 0xFC, 0x18, 0x00], // '$'
+    [0xC6, 0xCC, 0x18, 0x30, 0x60, 0xCC, 0x8C, 0x00], // '%'
+    [0x70, 0xD8, 0x70, 0x76, 0xDC, 0xCC, 0x76, 0x00], // '&'
+    [0x18, 0x18, 0x30, 0x00, 0x00, 0x00, 0x00, 0x00], // '\''
+    [0x0C, 0x18, 0x30, 0x30, 0x30, 0x18, 0x0C, 0x00], // '('
+    [0x30, 0x18, 0x0C, 0x0C, 0x0C, 0x18, 0x30, 0x00], // ')'
+    [0x00, 0x66, 0x3C, 0xFF, 0x3C, 0x66, 0x00, 0x00], // '*'
+    [0x00, 0x18, 0x18, 0x7E, 0x18, 0x18, 0x00, 0x00], // '+'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x30], // ','
+    [0x00, 0x00, 0x00, 0x7E, 0x00, 0x00, 0x00, 0x00], // '-'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x00], // '.'
+    [0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xC0, 0x00], // '/'
+    [0x7C, 0xC6, 0xCE, 0xDE, 0xF6, 0xE6, 0x7C, 0x00], // '0'
+    [0x18, 0x38, 0x18, 0x18, 0x18, 0x18, 0x7E, 0x00], // '1'
+    [0x7C, 0xC6, 0x06, 0x1C, 0x70, 0xC0, 0xFE, 0x00], // '2'
+    [0x7C, 0xC6, 0x06, 0x1C, 0x06, 0xC6, 0x7C, 0x00], // '3'
+    [0x0E, 0x1E, 0x36, 0x66, 0xFE, 0x06, 0x06, 0x00], // '4'
+    [0xFE, 0xC0, 0xFC, 0x06, 0x06, 0xC6, 0x7C, 0x00], // '5'
+    [0x3C, 0x60, 0xC0, 0xFC, 0xC6, 0xC6, 0x7C, 0x00], // '6'
+    [0xFE, 0x06, 0x0C, 0x18, 0x30, 0x30, 0x30, 0x00], // '7'
+    [0x7C, 0xC6, 0xC6, 0x7C, 0xC6, 0xC6, 0x7C, 0x00], // '8'
+    [0x7C, 0xC6, 0xC6, 0x7E, 0x06, 0x0C, 0x78, 0x00], // '9'
+    [0x00, 0x18, 0x18, 0x00, 0x00, 0x18, 0x18, 0x00], // ':'
+    [0x00, 0x18, 0x18, 0x00, 0x00, 0x18, 0x18, 0x30], // ';'
+    [0x0C, 0x18, 0x30, 0x60, 0x30, 0x18, 0x0C, 0x00], // '<'
+    [0x00, 0x00, 0x7E, 0x00, 0x7E, 0x00, 0x00, 0x00], // '='
+    [0x60, 0x30, 0x18, 0x0C, 0x18, 0x30, 0x60, 0x00], // '>'
+    [0x7C, 0xC6, 0x0C, 0x18, 0x18, 0x00, 0x18, 0x00], // '?'
+    [0x7C, 0xC6, 0xDE, 0xDE, 0xDE, 0xC0, 0x7C, 0x00], // '@'
+    [0x38, 0x6C, 0xC6, 0xC6, 0xFE, 0xC6, 0xC6, 0x00], // 'A'
+    [0xFC, 0xC6, 0xC6, 0xFC, 0xC6, 0xC6, 0xFC, 0x00], // 'B'
+    [0x7C, 0xC6, 0xC0, 0xC0, 0xC0, 0xC6, 0x7C, 0x00], // 'C'
+    [0xF8, 0xCC, 0xC6, 0xC6, 0xC6, 0xCC, 0xF8, 0x00], // 'D'
+    [0xFE, 0xC0, 0xC0, 0xFC, 0xC0, 0xC0, 0xFE, 0x00], // 'E'
+    [0xFE, 0xC0, 0xC0, 0xFC, 0xC0, 0xC0, 0xC0, 0x00], // 'F'
+    [0x7C, 0xC6, 0xC0, 0xDE, 0xC6, 0xC6, 0x7E, 0x00], // 'G'
+    [0xC6, 0xC6, 0xC6, 0xFE, 0xC6, 0xC6, 0xC6, 0x00], // 'H'
+    [0x7E, 0x18, 0x18, 0x18, 0x18, 0x18, 0x7E, 0x00], // 'I'
+    [0x1E, 0x0C, 0x0C, 0x0C, 0xCC, 0xCC, 0x78, 0x00], // 'J'
+    [0xC6, 0xCC, 0xD8, 0xF0, 0xD8, 0xCC, 0xC6, 0x00], // 'K'
+    [0xC0, 0xC0, 0xC0, 0xC0, 0xC0, 0xC0, 0xFE, 0x00], // 'L'
+    [0xC6, 0xEE, 0xFE, 0xD6, 0xC6, 0xC6, 0xC6, 0x00], // 'M'
+    [0xC6, 0xE6, 0xF6, 0xDE, 0xCE, 0xC6, 0xC6, 0x00], // 'N'
+    [0x7C, 0xC6, 0xC6, 0xC6, 0xC6, 0xC6, 0x7C, 0x00], // 'O'
+    [0xFC, 0xC6, 0xC6, 0xFC, 0xC0, 0xC0, 0xC0, 0x00], // 'P'
+    [0x7C, 0xC6, 0xC6, 0xC6, 0xD6, 0xCC, 0x76, 0x00], // 'Q'
+    [0xFC, 0xC6, 0xC6, 0xFC, 0xD8, 0xCC, 0xC6, 0x00], // 'R'
+    [0x7C, 0xC6, 0xC0, 0x7C, 0x06, 0xC6, 0x7C, 0x00], // 'S'
+    [0x7E, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x00], // 'T'
+    [0xC6, 0xC6, 0xC6, 0xC6, 0xC6, 0xC6, 0x7C, 0x00], // 'U'
+    [0xC6, 0xC6, 0xC6, 0xC6, 0x6C, 0x38, 0x10, 0x00], // 'V'
+    [0xC6, 0xC6, 0xC6, 0xD6, 0xFE, 0xEE, 0xC6, 0x00], // 'W'
+    [0xC6, 0x6C, 0x38, 0x38, 0x38, 0x6C, 0xC6, 0x00], // 'X'
+    [0x66, 0x66, 0x66, 0x3C, 0x18, 0x18, 0x18, 0x00], // 'Y'
+    [0xFE, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFE, 0x00], // 'Z'
+    [0x3C, 0x30, 0x30, 0x30, 0x30, 0x30, 0x3C, 0x00], // '['
+    [0xC0, 0x60, 0x30, 0x18, 0x0C, 0x06, 0x03, 0x00], // '\\'
+    [0x3C, 0x0C, 0x0C, 0x0C, 0x0C, 0x0C, 0x3C, 0x00], // ']'
+    [0x10, 0x38, 0x6C, 0xC6, 0x00, 0x00, 0x00, 0x00], // '^'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF], // '_'
+];
+
+pub fn glyph(c: char) -> [u8; GLYPH_SIZE] {
+    let c = c.to_ascii_uppercase() as u32;
+    let index = c
+        .checked_sub(FIRST_GLYPH as u32)
+        .filter(|&index| (index as usize) < GLYPHS.len())
+        .unwrap_or((b'?' - FIRST_GLYPH) as u32);
+    GLYPHS[index as usize]
+}
+
+// Draws `text` in `rgba`, a frame `width` pixels wide, with its top left corner at `left,
+// top`. Only the set pixels of each glyph are drawn, and whatever falls outside the frame is
+// clipped.
+pub fn draw_text(
+    text: &str,
+    left: usize,
+    top: usize,
+    color: [u8; 4],
+    width: usize,
+    rgba: &mut [u8],
+) {
+    if width == 0 {
+        return;
+    }
+    let height = rgba.len() / 4 / width;
+    for (column, c) in text.chars().enumerate() {
+        let left = left + column * GLYPH_SIZE;
+        for (dy, bits) in glyph(c).into_iter().enumerate() {
+            for dx in 0..GLYPH_SIZE {
+                let (x, y) = (left + dx, top + dy);
+                if bits & (0x80 >> dx) != 0 && x < width && y < height {
+                    let pixel = (y * width + x) * 4;
+                    rgba[pixel..pixel + 4].copy_from_slice(&color);
+                }
+            }
+        }
+    }
+}
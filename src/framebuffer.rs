@@ -0,0 +1,42 @@
+use core::ops::Range;
+
+// Big enough for a few rows per bank, small enough to leave room for code and the stack.
+pub const DEFAULT_FRAMEBUFFER_WINDOW_LEN: u16 = 0x4000;
+
+// A range of guest addresses that reads and writes the bytes of video memory instead of
+// RAM, packed at its color depth. Video memory is larger than the address space, so the
+// window shows one `len` byte slice of it at a time and `bank` picks which.
+#[derive(Debug, Clone)]
+pub struct FramebufferWindow {
+    pub start: u16,
+    pub len: u16,
+    pub bank: u8,
+}
+
+impl FramebufferWindow {
+    pub fn new(start: u16, len: u16) -> Self {
+        Self {
+            start,
+            len,
+            bank: 0,
+        }
+    }
+
+    pub fn range(&self) -> Range<u16> {
+        self.start..self.start + self.len
+    }
+
+    pub fn contains(&self, addr: u16) -> bool {
+        self.range().contains(&addr)
+    }
+
+    // Offset of `addr` into the bytes of video memory.
+    pub fn byte_offset(&self, addr: u16) -> usize {
+        self.bank as usize * self.len as usize + (addr - self.start) as usize
+    }
+
+    // Number of slices it takes to cover `bytes`, at least 1.
+    pub fn bank_count(&self, bytes: usize) -> usize {
+        bytes.div_ceil(self.len as usize).clamp(1, 256)
+    }
+}
@@ -0,0 +1,169 @@
+use crate::bus::Device;
+
+pub const PAD_BUTTONS: u8 = 0x00; // 2 bytes: one bit per held button, see BUTTON_*
+pub const PAD_LEFT_X: u8 = 0x02; // left stick, signed: negative is left
+pub const PAD_LEFT_Y: u8 = 0x03; // left stick, signed: negative is up
+pub const PAD_RIGHT_X: u8 = 0x04;
+pub const PAD_RIGHT_Y: u8 = 0x05;
+pub const PAD_REGISTER_COUNT: u8 = 6;
+
+pub const BUTTON_UP: u16 = 0x0001;
+pub const BUTTON_DOWN: u16 = 0x0002;
+pub const BUTTON_LEFT: u16 = 0x0004;
+pub const BUTTON_RIGHT: u16 = 0x0008;
+pub const BUTTON_A: u16 = 0x0010;
+pub const BUTTON_B: u16 = 0x0020;
+pub const BUTTON_X: u16 = 0x0040;
+pub const BUTTON_Y: u16 = 0x0080;
+pub const BUTTON_START: u16 = 0x0100;
+pub const BUTTON_SELECT: u16 = 0x0200;
+
+// How far a stick is pushed when the keyboard stands in for it.
+pub const AXIS_FULL: i8 = 127;
+
+// What the host's controllers hold, merged into one: buttons held on any of them and, for
+// each stick, whichever one is pushed furthest. Sticks are `[x, y]`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GamepadState {
+    pub buttons: u16,
+    pub left_stick: [i8; 2],
+    pub right_stick: [i8; 2],
+}
+
+// The controller state and the buttons held on the keyboard, kept apart so a controller
+// coming or going never drops a key and the other way around. The guest sees both at once.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Gamepad {
+    pub pad: GamepadState,
+    pub keys: u16,
+}
+
+impl Gamepad {
+    // Holds or releases the buttons in `mask` from the keyboard.
+    pub fn key(&mut self, mask: u16, pressed: bool) {
+        if pressed {
+            self.keys |= mask;
+        } else {
+            self.keys &= !mask;
+        }
+    }
+
+    // What the registers show. A centred left stick follows the keyboard's direction keys,
+    // so a guest that only reads the stick can still be played without a controller.
+    pub fn state(&self) -> GamepadState {
+        let axis = |stick: i8, negative: u16, positive: u16| {
+            if stick != 0 {
+                return stick;
+            }
+            match (self.keys & negative != 0, self.keys & positive != 0) {
+                (true, false) => -AXIS_FULL,
+                (false, true) => AXIS_FULL,
+                _ => 0,
+            }
+        };
+        let [x, y] = self.pad.left_stick;
+        GamepadState {
+            buttons: self.pad.buttons | self.keys,
+            left_stick: [
+                axis(x, BUTTON_LEFT, BUTTON_RIGHT),
+                axis(y, BUTTON_UP, BUTTON_DOWN),
+            ],
+            right_stick: self.pad.right_stick,
+        }
+    }
+}
+
+impl Device for Gamepad {
+    fn read(&mut self, offset: u16) -> u8 {
+        self.peek(offset)
+    }
+
+    fn write(&mut self, _offset: u16, _value: u8) {}
+
+    fn peek(&self, offset: u16) -> u8 {
+        let offset = offset as u8;
+        let state = self.state();
+        match offset {
+            PAD_BUTTONS..PAD_LEFT_X => state.buttons.to_le_bytes()[offset as usize],
+            PAD_LEFT_X => state.left_stick[0] as u8,
+            PAD_LEFT_Y => state.left_stick[1] as u8,
+            PAD_RIGHT_X => state.right_stick[0] as u8,
+            PAD_RIGHT_Y => state.right_stick[1] as u8,
+            _ => 0,
+        }
+    }
+}
+
+// A stick position from -1.0 to 1.0 as a register value.
+pub fn axis_byte(value: f32) -> i8 {
+    (value.clamp(-1.0, 1.0) * AXIS_FULL as f32) as i8
+}
+
+#[cfg(feature = "gamepad")]
+const BUTTON_MAP: [(gilrs::Button, u16); 10] = [
+    (gilrs::Button::DPadUp, BUTTON_UP),
+    (gilrs::Button::DPadDown, BUTTON_DOWN),
+    (gilrs::Button::DPadLeft, BUTTON_LEFT),
+    (gilrs::Button::DPadRight, BUTTON_RIGHT),
+    (gilrs::Button::South, BUTTON_A),
+    (gilrs::Button::East, BUTTON_B),
+    (gilrs::Button::West, BUTTON_X),
+    (gilrs::Button::North, BUTTON_Y),
+    (gilrs::Button::Start, BUTTON_START),
+    (gilrs::Button::Select, BUTTON_SELECT),
+];
+
+// The BUTTON_* bit a controller button sets, 0 for the ones the guest can't see.
+#[cfg(feature = "gamepad")]
+pub fn button_mask(button: gilrs::Button) -> u16 {
+    BUTTON_MAP
+        .iter()
+        .find(|&&(mapped, _)| mapped == button)
+        .map_or(0, |&(_, mask)| mask)
+}
+
+// The connected controllers, for a frontend to poll once a frame.
+#[cfg(feature = "gamepad")]
+pub struct Controllers {
+    gilrs: gilrs::Gilrs,
+}
+
+#[cfg(feature = "gamepad")]
+impl Controllers {
+    pub fn new() -> Result<Self, alloc::string::String> {
+        use alloc::string::ToString;
+
+        let gilrs = gilrs::Gilrs::new().map_err(|e| e.to_string())?;
+        Ok(Self { gilrs })
+    }
+
+    // Handles what happened since the last poll, controllers plugged in or pulled out
+    // included, and merges every connected one.
+    pub fn poll(&mut self) -> GamepadState {
+        use gilrs::Axis;
+
+        while self.gilrs.next_event().is_some() {}
+        let mut state = GamepadState::default();
+        for (_, pad) in self.gilrs.gamepads() {
+            for (button, mask) in BUTTON_MAP {
+                if pad.is_pressed(button) {
+                    state.buttons |= mask;
+                }
+            }
+            // gilrs has up as positive, the guest has it as negative like screen rows.
+            let axes = [
+                pad.value(Axis::LeftStickX),
+                -pad.value(Axis::LeftStickY),
+                pad.value(Axis::RightStickX),
+                -pad.value(Axis::RightStickY),
+            ];
+            let merged = state.left_stick.iter_mut().chain(&mut state.right_stick);
+            for (merged, axis) in merged.zip(axes.map(axis_byte)) {
+                if axis.unsigned_abs() > merged.unsigned_abs() {
+                    *merged = axis;
+                }
+            }
+        }
+        state
+    }
+}
@@ -0,0 +1,229 @@
+use alloc::vec::Vec;
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+use crate::cpu::{Opcode, OpcodeArg1, OpcodeArg2, OpcodeType, Register, RegisterWidth};
+use crate::isa::{self, OperandKind};
+use crate::program::{ProgramHeader, VERSION};
+use crate::types::Color;
+
+pub(crate) const PAIRS: [Register; 4] = [Register::R0, Register::R2, Register::R4, Register::R6];
+
+// A decoded operand, before it goes in the slot its position picks.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Operand {
+    Register(Register),
+    Immediate(u8),
+    WideImmediate(u16),
+    Address(u16),
+    Offset(i8),
+}
+
+impl<'a> Arbitrary<'a> for Register {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        u.choose(&Register::ALL).copied()
+    }
+
+    fn size_hint(_depth: usize) -> (usize, Option<usize>) {
+        (1, Some(1))
+    }
+}
+
+// Only the opcodes in the instruction table.
+impl<'a> Arbitrary<'a> for OpcodeType {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(u.choose(isa::instruction_table())?.opcode)
+    }
+
+    fn size_hint(_depth: usize) -> (usize, Option<usize>) {
+        (1, Some(1))
+    }
+}
+
+// An instruction for 8-bit registers; see `opcode` for 16-bit ones.
+impl<'a> Arbitrary<'a> for Opcode {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        opcode(u, RegisterWidth::Eight)
+    }
+
+    fn size_hint(_depth: usize) -> (usize, Option<usize>) {
+        // The opcode, whether a source is a register, and up to three operand bytes.
+        (1, Some(5))
+    }
+}
+
+impl<'a> Arbitrary<'a> for Color {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let [r, g, b, a] = u.arbitrary()?;
+        Ok(Color { r, g, b, a })
+    }
+
+    fn size_hint(_depth: usize) -> (usize, Option<usize>) {
+        (4, Some(4))
+    }
+}
+
+// A header any version could have written: no checksum before version 2 and no entry point
+// before version 3. The flags, length and checksum needn't match any payload.
+impl<'a> Arbitrary<'a> for ProgramHeader {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let version = u.int_in_range(1..=VERSION)?;
+        Ok(ProgramHeader {
+            version,
+            flags: u.arbitrary()?,
+            load_address: u.arbitrary()?,
+            length: u.arbitrary()?,
+            checksum: if version >= 2 {
+                Some(u.arbitrary()?)
+            } else {
+                None
+            },
+            entry: if version >= 3 {
+                Some(u.arbitrary()?)
+            } else {
+                None
+            },
+        })
+    }
+
+    fn size_hint(_depth: usize) -> (usize, Option<usize>) {
+        (1, Some(14))
+    }
+}
+
+/// An instruction a CPU with `width` registers could decode, with operands of the kinds
+/// the instruction table gives it: registers, bit indices below the width and, for a
+/// `reg, src` instruction with 16-bit registers, a 16-bit immediate source. It encodes to
+/// bytes that decode back to it.
+///
+/// ```
+/// use arbitrary::Unstructured;
+/// use microcvm_rs::cpu::RegisterWidth;
+/// use microcvm_rs::disasm::decode;
+/// use microcvm_rs::generate::opcode;
+///
+/// let mut u = Unstructured::new(&[3, 1, 2, 0x34, 0x12, 0xFF, 0xFF, 0xFF, 0xFF]);
+/// for width in [RegisterWidth::Eight, RegisterWidth::Sixteen] {
+///     let opcode = opcode(&mut u, width).unwrap();
+///     assert_eq!(decode(&opcode.encode(), width), Some(opcode));
+/// }
+/// ```
+pub fn opcode(u: &mut Unstructured, width: RegisterWidth) -> Result<Opcode> {
+    let opcode_type = OpcodeType::arbitrary(u)?;
+    instruction(u, opcode_type, width)
+}
+
+/// Like [`opcode`], with the instruction picked by the caller.
+pub fn instruction(
+    u: &mut Unstructured,
+    opcode_type: OpcodeType,
+    width: RegisterWidth,
+) -> Result<Opcode> {
+    let info = isa::info(opcode_type);
+    let mut operands = Vec::with_capacity(info.operands.len());
+    for kind in info.operands {
+        operands.push(match kind {
+            OperandKind::Register => Operand::Register(u.arbitrary()?),
+            OperandKind::Source if u.arbitrary()? => Operand::Register(u.arbitrary()?),
+            OperandKind::Source if width == RegisterWidth::Sixteen => {
+                Operand::WideImmediate(u.arbitrary()?)
+            }
+            OperandKind::Source | OperandKind::Immediate | OperandKind::StackOffset => {
+                Operand::Immediate(u.arbitrary()?)
+            }
+            OperandKind::Bit => Operand::Immediate(u.int_in_range(0..=width.bits() - 1)?),
+            OperandKind::Address => Operand::Address(u.arbitrary()?),
+            OperandKind::Offset => Operand::Offset(u.arbitrary()?),
+            OperandKind::Pair => Operand::Register(*u.choose(&PAIRS)?),
+            OperandKind::Fixed => Operand::WideImmediate(u.arbitrary()?),
+            OperandKind::Pointer if width == RegisterWidth::Eight => {
+                Operand::Register(*u.choose(&PAIRS)?)
+            }
+            OperandKind::Pointer => Operand::Register(u.arbitrary()?),
+            OperandKind::VideoOperation => {
+                Operand::Immediate(u.choose(isa::video_operation_table())?.operation as u8)
+            }
+        });
+    }
+    Ok(with_operands(opcode_type, operands))
+}
+
+// `opcode_type` with `operands`, one for each the instruction table gives it.
+pub(crate) fn with_operands(opcode_type: OpcodeType, operands: Vec<Operand>) -> Opcode {
+    let argument_count = operands.len() as u8;
+    let mut operands = operands.into_iter();
+    Opcode {
+        opcode_type,
+        argument_count,
+        arg1: operands.next().map(|operand| match operand {
+            Operand::Register(reg) => OpcodeArg1::Register(reg),
+            Operand::Immediate(imm) => OpcodeArg1::Immediate(imm),
+            Operand::WideImmediate(imm) => OpcodeArg1::Immediate(imm as u8),
+            Operand::Address(addr) => OpcodeArg1::Address(addr),
+            Operand::Offset(offset) => OpcodeArg1::Offset(offset),
+        }),
+        arg2: operands.next().map(|operand| match operand {
+            Operand::Register(reg) => OpcodeArg2::Register(reg),
+            Operand::Immediate(imm) => OpcodeArg2::Immediate(imm),
+            Operand::WideImmediate(imm) => OpcodeArg2::WideImmediate(imm),
+            Operand::Address(addr) => OpcodeArg2::Address(addr),
+            Operand::Offset(offset) => OpcodeArg2::Offset(offset),
+        }),
+    }
+}
+
+/// A program of at most `max_len` bytes: valid instructions for `width` registers, one
+/// after another, ending in `hlt`. Each instruction costs a byte of `u` saying whether
+/// another follows, so a fuzzer shrinking its input shrinks the program an instruction at a
+/// time, and an exhausted `u` just ends it. A `max_len` of 0 leaves no room for the `hlt`
+/// and is an [`IncorrectFormat`](arbitrary::Error::IncorrectFormat) error.
+///
+/// ```
+/// use arbitrary::Unstructured;
+/// use microcvm_rs::cpu::{OpcodeType, RegisterWidth};
+/// use microcvm_rs::disasm::decode;
+/// use microcvm_rs::generate::valid_program;
+///
+/// let data: Vec<u8> = (0..64u8).map(|i| i.wrapping_mul(37)).collect();
+/// let program = valid_program(&mut Unstructured::new(&data), 32, RegisterWidth::Eight).unwrap();
+/// assert!(program.len() <= 32);
+///
+/// let mut offset = 0;
+/// let mut last = None;
+/// while offset < program.len() {
+///     let opcode = decode(&program[offset..], RegisterWidth::Eight).unwrap();
+///     offset += opcode.length() as usize;
+///     last = Some(opcode.opcode_type);
+/// }
+/// assert_eq!(last, Some(OpcodeType::Hlt));
+///
+/// // Nothing to go on is the smallest program there is.
+/// let empty = valid_program(&mut Unstructured::new(&[]), 32, RegisterWidth::Eight).unwrap();
+/// assert_eq!(empty, [0xFF]);
+/// assert!(valid_program(&mut Unstructured::new(&data), 0, RegisterWidth::Eight).is_err());
+/// ```
+pub fn valid_program(
+    u: &mut Unstructured,
+    max_len: usize,
+    width: RegisterWidth,
+) -> Result<Vec<u8>> {
+    if max_len == 0 {
+        return Err(arbitrary::Error::IncorrectFormat);
+    }
+    let mut program = Vec::new();
+    // Room for the hlt on the end.
+    let room = max_len - 1;
+    while !u.is_empty() && u.arbitrary::<bool>()? {
+        let opcode = opcode(u, width)?;
+        if opcode.opcode_type == OpcodeType::Hlt {
+            continue;
+        }
+        let bytes = opcode.encode();
+        if program.len() + bytes.len() > room {
+            break;
+        }
+        program.extend_from_slice(&bytes);
+    }
+    program.push(OpcodeType::Hlt as u8);
+    Ok(program)
+}
@@ -0,0 +1,158 @@
+use std::path::{Path, PathBuf};
+
+use crate::png::{self, Image, PngError};
+
+// Set to write the frames golden images are checked against in place of the images, for
+// when the screen is meant to have changed.
+pub const BLESS_VAR: &str = "MICROCVM_BLESS";
+
+// How close a frame has to be to its golden image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tolerance {
+    Exact,
+    // Every channel of every pixel within this much of the golden image's.
+    Within(u8),
+}
+
+// How a frame differs from its golden image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Difference {
+    // Pixels outside the tolerance.
+    pub pixels: usize,
+    // The first of them, row by row.
+    pub first: (u32, u32),
+    // The furthest any channel is from the golden image's.
+    pub largest: u8,
+}
+
+#[derive(Debug)]
+pub enum GoldenError {
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    Png {
+        path: PathBuf,
+        error: PngError,
+    },
+    Size {
+        expected: (u32, u32),
+        actual: (u32, u32),
+    },
+    // `actual` is where the frame was written for a look.
+    Mismatch {
+        difference: Difference,
+        actual: PathBuf,
+    },
+}
+
+// Compares an RGBA frame with an image.
+pub fn compare(
+    expected: &Image,
+    width: u32,
+    height: u32,
+    rgba: &[u8],
+    tolerance: Tolerance,
+) -> Result<(), Difference> {
+    let allowed = match tolerance {
+        Tolerance::Exact => 0,
+        Tolerance::Within(allowed) => allowed,
+    };
+    let mut difference = Difference {
+        pixels: 0,
+        first: (0, 0),
+        largest: 0,
+    };
+    let pixels = expected.rgba.chunks_exact(4).zip(rgba.chunks_exact(4));
+    for (index, (expected, actual)) in pixels.enumerate() {
+        let largest = expected
+            .iter()
+            .zip(actual)
+            .map(|(&expected, &actual)| expected.abs_diff(actual))
+            .max()
+            .unwrap_or(0);
+        if largest > allowed {
+            if difference.pixels == 0 {
+                let index = index as u32;
+                difference.first = (index % width.max(1), index / width.max(1));
+            }
+            difference.pixels += 1;
+        }
+        difference.largest = difference.largest.max(largest);
+    }
+    let same_size = (expected.width, expected.height) == (width, height);
+    if difference.pixels == 0 && same_size {
+        return Ok(());
+    }
+    Err(difference)
+}
+
+// Checks an RGBA frame against the PNG at `path`. With BLESS_VAR set it writes the frame
+// there instead and passes, which is also how a golden image is made in the first place. A
+// frame that doesn't match is written next to the golden image with `.actual.png` on the end.
+pub fn check(
+    path: impl AsRef<Path>,
+    width: u32,
+    height: u32,
+    rgba: &[u8],
+    tolerance: Tolerance,
+) -> Result<(), GoldenError> {
+    let path = path.as_ref();
+    if std::env::var_os(BLESS_VAR).is_some() {
+        return write(path, width, height, rgba);
+    }
+    let bytes = std::fs::read(path).map_err(|error| GoldenError::Io {
+        path: path.to_path_buf(),
+        error,
+    })?;
+    let expected = png::decode(&bytes).map_err(|error| GoldenError::Png {
+        path: path.to_path_buf(),
+        error,
+    })?;
+    if (expected.width, expected.height) != (width, height) {
+        return Err(GoldenError::Size {
+            expected: (expected.width, expected.height),
+            actual: (width, height),
+        });
+    }
+    let Err(difference) = compare(&expected, width, height, rgba, tolerance) else {
+        return Ok(());
+    };
+    let mut actual = path.as_os_str().to_owned();
+    actual.push(".actual.png");
+    let actual = PathBuf::from(actual);
+    write(&actual, width, height, rgba)?;
+    Err(GoldenError::Mismatch { difference, actual })
+}
+
+fn write(path: &Path, width: u32, height: u32, rgba: &[u8]) -> Result<(), GoldenError> {
+    std::fs::write(path, png::encode(width, height, rgba)).map_err(|error| GoldenError::Io {
+        path: path.to_path_buf(),
+        error,
+    })
+}
+
+impl core::fmt::Display for GoldenError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            GoldenError::Io { path, error } => write!(f, "`{}`: {}", path.display(), error),
+            GoldenError::Png { path, error } => write!(f, "`{}`: {}", path.display(), error),
+            GoldenError::Size { expected, actual } => write!(
+                f,
+                "the frame is {}x{}, the golden image {}x{}",
+                actual.0, actual.1, expected.0, expected.1
+            ),
+            GoldenError::Mismatch { difference, actual } => write!(
+                f,
+                "{} pixels differ, the first at {}, {} and the largest by {}; the frame is in `{}`, \
+                 set {} to accept it",
+                difference.pixels,
+                difference.first.0,
+                difference.first.1,
+                difference.largest,
+                actual.display(),
+                BLESS_VAR
+            ),
+        }
+    }
+}
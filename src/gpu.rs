@@ -0,0 +1,298 @@
+use pixels::{Pixels, PixelsBuilder, SurfaceTexture, wgpu};
+use std::sync::Arc;
+use winit::error::OsError;
+use winit::window::Window;
+
+// The graphics API the window draws through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GpuBackend {
+    // Whatever the platform does best, or `WGPU_BACKEND` if it is set.
+    Auto,
+    Vulkan,
+    Gl,
+    Metal,
+    Dx12,
+}
+
+// Which GPU draws the window when there's more than one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdapterPreference {
+    // wgpu's choice, or `WGPU_POWER_PREF` if it is set.
+    Default,
+    LowPower,
+    HighPerformance,
+    // The first adapter whose name contains this, ignoring case.
+    Named(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GpuConfig {
+    pub backend: GpuBackend,
+    pub adapter: AdapterPreference,
+}
+
+#[derive(Debug)]
+pub enum GpuError {
+    // No adapter on `backend` fits `adapter`. `available` names every adapter on every
+    // backend, with its backend.
+    NoAdapter {
+        backend: GpuBackend,
+        adapter: AdapterPreference,
+        available: Vec<String>,
+    },
+    Pixels(pixels::Error),
+    // The platform wouldn't open a window to draw in.
+    Window(OsError),
+}
+
+// What a GpuConfig comes to on this machine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selection {
+    pub backends: wgpu::Backends,
+    pub power_preference: wgpu::PowerPreference,
+    // The adapter a Named preference found.
+    pub name: Option<String>,
+}
+
+impl GpuBackend {
+    pub const ALL: [GpuBackend; 5] = [
+        GpuBackend::Auto,
+        GpuBackend::Vulkan,
+        GpuBackend::Gl,
+        GpuBackend::Metal,
+        GpuBackend::Dx12,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GpuBackend::Auto => "auto",
+            GpuBackend::Vulkan => "vulkan",
+            GpuBackend::Gl => "gl",
+            GpuBackend::Metal => "metal",
+            GpuBackend::Dx12 => "dx12",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|backend| backend.name() == name)
+    }
+
+    fn backends(self) -> wgpu::Backends {
+        match self {
+            GpuBackend::Auto => {
+                wgpu::util::backend_bits_from_env().unwrap_or(wgpu::Backends::PRIMARY)
+            }
+            GpuBackend::Vulkan => wgpu::Backends::VULKAN,
+            GpuBackend::Gl => wgpu::Backends::GL,
+            GpuBackend::Metal => wgpu::Backends::METAL,
+            GpuBackend::Dx12 => wgpu::Backends::DX12,
+        }
+    }
+}
+
+impl AdapterPreference {
+    // `low-power`, `high-performance` or `default`, and anything else is part of a name.
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "default" => AdapterPreference::Default,
+            "low-power" => AdapterPreference::LowPower,
+            "high-performance" => AdapterPreference::HighPerformance,
+            name => AdapterPreference::Named(String::from(name)),
+        }
+    }
+}
+
+impl GpuConfig {
+    pub fn new() -> Self {
+        Self {
+            backend: GpuBackend::Auto,
+            adapter: AdapterPreference::Default,
+        }
+    }
+
+    // Picks from the adapters this machine has.
+    pub fn select(&self) -> Result<Selection, GpuError> {
+        let adapters = instance(wgpu::Backends::all()).enumerate_adapters(wgpu::Backends::all());
+        let adapters: Vec<wgpu::AdapterInfo> =
+            adapters.iter().map(|adapter| adapter.get_info()).collect();
+        self.select_from(&adapters)
+    }
+
+    // Picks from `adapters`, every adapter on every backend, of which only the ones on the
+    // configured backend count.
+    pub fn select_from(&self, adapters: &[wgpu::AdapterInfo]) -> Result<Selection, GpuError> {
+        let backends = self.backend.backends();
+        let no_adapter = || GpuError::NoAdapter {
+            backend: self.backend,
+            adapter: self.adapter.clone(),
+            available: adapters.iter().map(describe).collect(),
+        };
+        let mut candidates = adapters
+            .iter()
+            .filter(|info| backends.contains(wgpu::Backends::from(info.backend)))
+            .peekable();
+        if candidates.peek().is_none() {
+            return Err(no_adapter());
+        }
+        let power_preference = match &self.adapter {
+            AdapterPreference::Default => {
+                wgpu::util::power_preference_from_env().unwrap_or_default()
+            }
+            AdapterPreference::LowPower => wgpu::PowerPreference::LowPower,
+            AdapterPreference::HighPerformance => wgpu::PowerPreference::HighPerformance,
+            AdapterPreference::Named(name) => {
+                let name = name.to_lowercase();
+                let Some(info) = candidates.find(|info| info.name.to_lowercase().contains(&name))
+                else {
+                    return Err(no_adapter());
+                };
+                // wgpu can't be handed an adapter, but narrowed to its backend and asked for
+                // its kind of GPU it picks that one unless there are two alike.
+                return Ok(Selection {
+                    backends: info.backend.into(),
+                    power_preference: match info.device_type {
+                        wgpu::DeviceType::IntegratedGpu => wgpu::PowerPreference::LowPower,
+                        _ => wgpu::PowerPreference::HighPerformance,
+                    },
+                    name: Some(info.name.clone()),
+                });
+            }
+        };
+        Ok(Selection {
+            backends,
+            power_preference,
+            name: None,
+        })
+    }
+
+    // Pixels for `surface_texture` on the configured backend and adapter.
+    pub fn build_pixels(
+        &self,
+        width: u32,
+        height: u32,
+        surface_texture: SurfaceTexture<Arc<Window>>,
+        present_mode: wgpu::PresentMode,
+    ) -> Result<Pixels<'static>, GpuError> {
+        let selection = self.select()?;
+        let pixels = PixelsBuilder::new(width, height, surface_texture)
+            .wgpu_backend(selection.backends)
+            .request_adapter_options(wgpu::RequestAdapterOptions {
+                power_preference: selection.power_preference,
+                force_fallback_adapter: false,
+                compatible_surface: None,
+            })
+            .present_mode(present_mode)
+            .build()
+            .map_err(|e| match e {
+                pixels::Error::AdapterNotFound => GpuError::NoAdapter {
+                    backend: self.backend,
+                    adapter: self.adapter.clone(),
+                    available: available_adapters(),
+                },
+                e => GpuError::Pixels(e),
+            })?;
+        if let Some(name) = selection.name
+            && pixels.adapter().get_info().name != name
+        {
+            log::warn!(
+                "asked for the adapter `{}`, but wgpu picked another like it",
+                name
+            );
+        }
+        Ok(pixels)
+    }
+
+    // The present modes a surface for `window` supports, asked of a throwaway surface on the
+    // adapter build_pixels will pick. Empty if there's no adapter, in which case
+    // build_pixels fails too.
+    pub fn supported_present_modes(&self, window: &Arc<Window>) -> Vec<wgpu::PresentMode> {
+        let Ok(selection) = self.select() else {
+            return Vec::new();
+        };
+        let instance = instance(selection.backends);
+        let Ok(surface) = instance.create_surface(window.clone()) else {
+            return Vec::new();
+        };
+        let adapter =
+            wgpu::util::initialize_adapter_from_env(&instance, Some(&surface)).or_else(|| {
+                pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+                    compatible_surface: Some(&surface),
+                    force_fallback_adapter: false,
+                    power_preference: selection.power_preference,
+                }))
+            });
+        match adapter {
+            Some(adapter) => surface.get_capabilities(&adapter).present_modes,
+            None => Vec::new(),
+        }
+    }
+}
+
+impl Default for GpuConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn instance(backends: wgpu::Backends) -> wgpu::Instance {
+    wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends,
+        ..Default::default()
+    })
+}
+
+// Every adapter on every backend, as `name (backend)`.
+fn available_adapters() -> Vec<String> {
+    instance(wgpu::Backends::all())
+        .enumerate_adapters(wgpu::Backends::all())
+        .iter()
+        .map(|adapter| describe(&adapter.get_info()))
+        .collect()
+}
+
+// `name (backend, device type)` for the log.
+pub fn describe(info: &wgpu::AdapterInfo) -> String {
+    format!("{} ({:?}, {:?})", info.name, info.backend, info.device_type)
+}
+
+impl core::fmt::Display for GpuBackend {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl core::fmt::Display for AdapterPreference {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            AdapterPreference::Default => f.write_str("default"),
+            AdapterPreference::LowPower => f.write_str("low-power"),
+            AdapterPreference::HighPerformance => f.write_str("high-performance"),
+            AdapterPreference::Named(name) => f.write_str(name),
+        }
+    }
+}
+
+impl core::fmt::Display for GpuError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            GpuError::NoAdapter {
+                backend,
+                adapter,
+                available,
+            } => {
+                write!(
+                    f,
+                    "no GPU adapter matching `{}` on the `{}` backend",
+                    adapter, backend
+                )?;
+                if available.is_empty() {
+                    write!(f, ", and no adapters on any backend")
+                } else {
+                    write!(f, "; available: {}", available.join(", "))
+                }
+            }
+            GpuError::Pixels(e) => write!(f, "{}", e),
+            GpuError::Window(e) => write!(f, "couldn't open a window: {}", e),
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use crate::cpu::{MicroCVMCpu, Register};
+use crate::error::VmError;
+use alloc::boxed::Box;
+
+// Handlers are Send so a machine with devices attached can move to another thread.
+pub type HcallHandler = Box<dyn FnMut(&mut HcallContext) -> Result<(), VmError> + Send>;
+
+pub struct HcallContext<'a> {
+    cpu: &'a mut MicroCVMCpu,
+}
+
+impl<'a> HcallContext<'a> {
+    pub fn new(cpu: &'a mut MicroCVMCpu) -> Self {
+        Self { cpu }
+    }
+
+    pub fn reg(&self, reg: Register) -> u16 {
+        self.cpu.get_reg(reg)
+    }
+
+    // Bits above the register width are dropped.
+    pub fn set_reg(&mut self, reg: Register, value: u16) {
+        self.cpu.set_reg(reg, value);
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.cpu.pc
+    }
+
+    pub fn read_mem(&self, addr: u16) -> Result<u8, VmError> {
+        self.cpu.read_mem(addr)
+    }
+
+    pub fn write_mem(&mut self, addr: u16, value: u8) -> Result<(), VmError> {
+        self.cpu.write_mem(addr, value)
+    }
+}
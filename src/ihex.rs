@@ -0,0 +1,50 @@
+use alloc::string::String;
+use core::fmt::Write;
+
+// Data bytes in each record, what most tools write.
+pub const RECORD_LEN: usize = 16;
+
+const DATA: u8 = 0x00;
+const END_OF_FILE: u8 = 0x01;
+const START_SEGMENT_ADDRESS: u8 = 0x03;
+
+/// Writes `chunks`, each bytes and the address they go at, as Intel HEX for EPROM
+/// programmers and other tools that take it. Records hold up to [`RECORD_LEN`] bytes and
+/// don't run past a chunk, so gaps between chunks stay gaps. An `entry` other than 0 gets a
+/// start address record, with a segment of 0, before the end of file record.
+///
+/// ```
+/// use microcvm_rs::ihex::encode;
+///
+/// let hex = encode([(0x0100, &[0x10, 0x00, 0x2A][..])], 0x0100);
+/// assert_eq!(hex, ":0301000010002AC2\n:0400000300000100F8\n:00000001FF\n");
+/// ```
+pub fn encode<'a>(chunks: impl IntoIterator<Item = (u16, &'a [u8])>, entry: u16) -> String {
+    let mut hex = String::new();
+    for (address, bytes) in chunks {
+        for (index, record) in bytes.chunks(RECORD_LEN).enumerate() {
+            let at = address.wrapping_add((index * RECORD_LEN) as u16);
+            push_record(&mut hex, at, DATA, record);
+        }
+    }
+    if entry != 0 {
+        let [high, low] = entry.to_be_bytes();
+        push_record(&mut hex, 0, START_SEGMENT_ADDRESS, &[0, 0, high, low]);
+    }
+    push_record(&mut hex, 0, END_OF_FILE, &[]);
+    hex
+}
+
+fn push_record(hex: &mut String, address: u16, kind: u8, data: &[u8]) {
+    let [high, low] = address.to_be_bytes();
+    let mut sum = (data.len() as u8)
+        .wrapping_add(high)
+        .wrapping_add(low)
+        .wrapping_add(kind);
+    let _ = write!(hex, ":{:02X}{:04X}{:02X}", data.len(), address, kind);
+    for &byte in data {
+        sum = sum.wrapping_add(byte);
+        let _ = write!(hex, "{:02X}", byte);
+    }
+    let _ = writeln!(hex, "{:02X}", sum.wrapping_neg());
+}
@@ -0,0 +1,301 @@
+use alloc::vec::Vec;
+
+#[cfg(feature = "window")]
+use winit::keyboard::KeyCode;
+
+// What a RepeatRate's delay and interval count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepeatUnit {
+    Milliseconds,
+    Frames,
+}
+
+// How often a held key repeats: once `delay` after it went down, then every `interval`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RepeatRate {
+    pub delay: u64,
+    // At least 1.
+    pub interval: u64,
+    pub unit: RepeatUnit,
+}
+
+// Repeats held keys on the host's clock rather than the OS's, whose auto-repeat differs
+// from one platform to the next, so the frontend drops the repeats the OS sends. It only
+// ever sees the time it is ticked with, which is in the rate's unit.
+#[derive(Debug, Clone)]
+pub struct KeyRepeat {
+    pub rate: RepeatRate,
+    // Each held key with when it repeats next.
+    keys: Vec<(u8, u64)>,
+}
+
+impl RepeatRate {
+    // 500 ms, then about 30 a second.
+    pub fn new() -> Self {
+        Self {
+            delay: 500,
+            interval: 33,
+            unit: RepeatUnit::Milliseconds,
+        }
+    }
+}
+
+impl Default for RepeatRate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KeyRepeat {
+    pub fn new(rate: RepeatRate) -> Self {
+        Self {
+            rate,
+            keys: Vec::new(),
+        }
+    }
+
+    // Catches up to `now` with `held` the scancodes down at that point, calling `repeat`
+    // for each one due to repeat. A key first seen held repeats `delay` later, and a key
+    // that was let go starts over the next time it is. A key repeats at most once per
+    // tick, so a host that stalls doesn't get a burst of them afterwards.
+    pub fn tick(&mut self, now: u64, held: &[u8], mut repeat: impl FnMut(u8)) {
+        self.keys.retain(|(scancode, _)| held.contains(scancode));
+        for &scancode in held {
+            if !self.keys.iter().any(|&(key, _)| key == scancode) {
+                self.keys.push((scancode, now + self.rate.delay));
+            }
+        }
+
+        let interval = self.rate.interval.max(1);
+        for (scancode, next) in &mut self.keys {
+            if now >= *next {
+                repeat(*scancode);
+                *next += interval;
+                if *next <= now {
+                    *next = now + interval;
+                }
+            }
+        }
+    }
+
+    // Forgets every held key, for when the window loses focus and won't hear them go up.
+    pub fn reset(&mut self) {
+        self.keys.clear();
+    }
+}
+
+// Which scancode the window sends the guest for each key, and how held keys repeat.
+#[cfg(feature = "window")]
+#[derive(Debug, Clone)]
+pub struct InputConfig {
+    pub key_repeat: Option<RepeatRate>,
+    keys: Vec<(KeyCode, u8)>,
+}
+
+#[cfg(feature = "window")]
+#[derive(Debug)]
+pub enum KeymapError {
+    Io(std::io::Error),
+    // Not a `key = scancode` line.
+    BadLine { line: usize },
+    UnknownKey { line: usize, name: String },
+    BadScancode { line: usize, value: String },
+}
+
+// PC set 1 make codes, which a key's release shares. The arrows use their keypad codes,
+// since the extended ones take two bytes.
+#[cfg(feature = "window")]
+const DEFAULT_KEYMAP: [(KeyCode, u8); 76] = {
+    use KeyCode::*;
+    [
+        (Escape, 0x01),
+        (Digit1, 0x02),
+        (Digit2, 0x03),
+        (Digit3, 0x04),
+        (Digit4, 0x05),
+        (Digit5, 0x06),
+        (Digit6, 0x07),
+        (Digit7, 0x08),
+        (Digit8, 0x09),
+        (Digit9, 0x0A),
+        (Digit0, 0x0B),
+        (Minus, 0x0C),
+        (Equal, 0x0D),
+        (Backspace, 0x0E),
+        (Tab, 0x0F),
+        (KeyQ, 0x10),
+        (KeyW, 0x11),
+        (KeyE, 0x12),
+        (KeyR, 0x13),
+        (KeyT, 0x14),
+        (KeyY, 0x15),
+        (KeyU, 0x16),
+        (KeyI, 0x17),
+        (KeyO, 0x18),
+        (KeyP, 0x19),
+        (BracketLeft, 0x1A),
+        (BracketRight, 0x1B),
+        (Enter, 0x1C),
+        (ControlLeft, 0x1D),
+        (ControlRight, 0x1D),
+        (KeyA, 0x1E),
+        (KeyS, 0x1F),
+        (KeyD, 0x20),
+        (KeyF, 0x21),
+        (KeyG, 0x22),
+        (KeyH, 0x23),
+        (KeyJ, 0x24),
+        (KeyK, 0x25),
+        (KeyL, 0x26),
+        (Semicolon, 0x27),
+        (Quote, 0x28),
+        (Backquote, 0x29),
+        (ShiftLeft, 0x2A),
+        (Backslash, 0x2B),
+        (KeyZ, 0x2C),
+        (KeyX, 0x2D),
+        (KeyC, 0x2E),
+        (KeyV, 0x2F),
+        (KeyB, 0x30),
+        (KeyN, 0x31),
+        (KeyM, 0x32),
+        (Comma, 0x33),
+        (Period, 0x34),
+        (Slash, 0x35),
+        (ShiftRight, 0x36),
+        (AltLeft, 0x38),
+        (AltRight, 0x38),
+        (Space, 0x39),
+        (CapsLock, 0x3A),
+        (F1, 0x3B),
+        (F2, 0x3C),
+        (F3, 0x3D),
+        (F4, 0x3E),
+        (F5, 0x3F),
+        (F6, 0x40),
+        (F7, 0x41),
+        (F8, 0x42),
+        (F9, 0x43),
+        (F10, 0x44),
+        (ArrowUp, 0x48),
+        (ArrowLeft, 0x4B),
+        (ArrowRight, 0x4D),
+        (ArrowDown, 0x50),
+        (Delete, 0x53),
+        (IntlBackslash, 0x56),
+        (F11, 0x57),
+    ]
+};
+
+#[cfg(feature = "window")]
+impl InputConfig {
+    // The PC set 1 layout, repeating keys at the default rate.
+    pub fn new() -> Self {
+        Self {
+            key_repeat: Some(RepeatRate::new()),
+            keys: DEFAULT_KEYMAP.to_vec(),
+        }
+    }
+
+    // Makes `key` send `scancode`, or nothing at all for a scancode of 0.
+    pub fn remap(&mut self, key: KeyCode, scancode: u8) {
+        match self.keys.iter_mut().find(|(mapped, _)| *mapped == key) {
+            Some(entry) => entry.1 = scancode,
+            None => self.keys.push((key, scancode)),
+        }
+    }
+
+    pub fn scancode(&self, key: KeyCode) -> Option<u8> {
+        self.keys
+            .iter()
+            .find(|&&(mapped, _)| mapped == key)
+            .map(|&(_, scancode)| scancode)
+            .filter(|&scancode| scancode != 0)
+    }
+
+    // Applies a keymap of `KeyName = scancode` lines, with names as winit spells them
+    // (`KeyA`, `Digit1`, `ArrowUp`) and `#` starting a comment. That makes it a TOML
+    // table, so it can be written as one.
+    pub fn apply_keymap(&mut self, keymap: &str) -> Result<(), KeymapError> {
+        for (index, line) in keymap.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let Some((name, value)) = line.split_once('=') else {
+                return Err(KeymapError::BadLine { line: line_number });
+            };
+            let (name, value) = (name.trim().trim_matches('"'), value.trim());
+            let Some(key) = key_named(name) else {
+                return Err(KeymapError::UnknownKey {
+                    line: line_number,
+                    name: String::from(name),
+                });
+            };
+            let scancode = match value
+                .strip_prefix("0x")
+                .or_else(|| value.strip_prefix("0X"))
+            {
+                Some(hex) => u8::from_str_radix(hex, 16),
+                None => value.parse(),
+            };
+            let Ok(scancode) = scancode else {
+                return Err(KeymapError::BadScancode {
+                    line: line_number,
+                    value: String::from(value),
+                });
+            };
+            self.remap(key, scancode);
+        }
+        Ok(())
+    }
+
+    pub fn load_keymap(&mut self, path: impl AsRef<std::path::Path>) -> Result<(), KeymapError> {
+        let keymap = std::fs::read_to_string(path).map_err(KeymapError::Io)?;
+        self.apply_keymap(&keymap)
+    }
+}
+
+#[cfg(feature = "window")]
+impl Default for InputConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The keys a keymap can name, the ones the default layout sends.
+#[cfg(feature = "window")]
+fn key_named(name: &str) -> Option<KeyCode> {
+    DEFAULT_KEYMAP
+        .iter()
+        .map(|&(key, _)| key)
+        .find(|key| format!("{:?}", key) == name)
+}
+
+#[cfg(feature = "window")]
+impl core::fmt::Display for KeymapError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            KeymapError::Io(e) => write!(f, "{}", e),
+            KeymapError::BadLine { line } => {
+                write!(f, "line {}: expected `key = scancode`", line)
+            }
+            KeymapError::UnknownKey { line, name } => {
+                write!(f, "line {}: unknown key `{}`, expected one of", line, name)?;
+                for (index, (key, _)) in DEFAULT_KEYMAP.iter().enumerate() {
+                    let separator = if index == 0 { " " } else { ", " };
+                    write!(f, "{}{:?}", separator, key)?;
+                }
+                Ok(())
+            }
+            KeymapError::BadScancode { line, value } => {
+                write!(
+                    f,
+                    "line {}: `{}` is not a scancode from 0 to 255",
+                    line, value
+                )
+            }
+        }
+    }
+}
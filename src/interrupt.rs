@@ -0,0 +1,55 @@
+pub const INT_VECTOR_TABLE: u8 = 0x00; // 2 bytes: guest address of the vector table
+pub const INT_PENDING: u8 = 0x02; // 2 bytes: bit n is set while vector n waits, writing 1 clears it
+pub const INT_REGISTER_COUNT: u8 = 4;
+
+// The vector table holds this many little-endian handler addresses.
+pub const INTERRUPT_VECTOR_COUNT: u8 = 16;
+
+// Raised once per presented frame.
+pub const VECTOR_VBLANK: u8 = 0;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct InterruptController {
+    pub vector_table: u16,
+    pub pending: u16,
+}
+
+impl InterruptController {
+    // Raising a vector that is already pending has no further effect.
+    pub fn raise(&mut self, vector: u8) {
+        self.pending |= 1 << vector;
+    }
+
+    // The lowest pending vector goes first.
+    pub fn next(&self) -> Option<u8> {
+        match self.pending {
+            0 => None,
+            pending => Some(pending.trailing_zeros() as u8),
+        }
+    }
+
+    // Guest address of the table entry for `vector`.
+    pub fn entry(&self, vector: u8) -> u16 {
+        self.vector_table.wrapping_add(vector as u16 * 2)
+    }
+
+    pub fn read(&self, offset: u8) -> u8 {
+        match offset {
+            INT_VECTOR_TABLE => self.vector_table as u8,
+            0x01 => (self.vector_table >> 8) as u8,
+            INT_PENDING => self.pending as u8,
+            0x03 => (self.pending >> 8) as u8,
+            _ => 0,
+        }
+    }
+
+    pub fn write(&mut self, offset: u8, value: u8) {
+        match offset {
+            INT_VECTOR_TABLE => self.vector_table = (self.vector_table & 0xFF00) | value as u16,
+            0x01 => self.vector_table = (self.vector_table & 0x00FF) | (value as u16) << 8,
+            INT_PENDING => self.pending &= !(value as u16),
+            0x03 => self.pending &= !((value as u16) << 8),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use crate::cpu::{OpcodeType, VideoOpcodeType};
+
+use self::OperandKind::{
+    Address, Bit, Fixed, Immediate, Offset, Pair, Pointer, Register, Source, StackOffset,
+    VideoOperation,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperandKind {
+    Register,
+    Immediate,
+    Address,
+    // A register or an immediate, as taken by `reg, src` instructions.
+    Source,
+    // A signed byte added to the pc of the next instruction.
+    Offset,
+    // A bit index, 0 to 7.
+    Bit,
+    // An even register, the high byte of an 8.8 value, and the odd one after it with the
+    // low byte.
+    Pair,
+    // A signed 8.8 value, 16-bit little-endian.
+    Fixed,
+    // A register holding an address, stepped past what is read or written through it: a
+    // pair, high byte first, with 8-bit registers.
+    Pointer,
+    // An unsigned byte added to sp, as `[sp + offset]`.
+    StackOffset,
+    // One of the operations in the video table, by number or by name.
+    VideoOperation,
+}
+
+impl OperandKind {
+    // Encoded size in bytes. Address and fixed-point operands are 16-bit little-endian.
+    pub const fn size(self) -> u16 {
+        match self {
+            OperandKind::Address | OperandKind::Fixed => 2,
+            _ => 1,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            OperandKind::Register => "register",
+            OperandKind::Immediate => "immediate",
+            OperandKind::Address => "address",
+            OperandKind::Source => "source",
+            OperandKind::Offset => "offset",
+            OperandKind::Bit => "bit",
+            OperandKind::Pair => "pair",
+            OperandKind::Fixed => "fixed",
+            OperandKind::Pointer => "pointer",
+            OperandKind::StackOffset => "stack offset",
+            OperandKind::VideoOperation => "video operation",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct InstructionInfo {
+    pub opcode: OpcodeType,
+    pub mnemonic: &'static str,
+    pub operands: &'static [OperandKind],
+    pub length: u16,
+    // Base cost; fill, clear, setpixel, fillrect and DMA transfers add one per pixel,
+    // memset and memcpy one per byte.
+    pub cycles: u32,
+    pub flags: &'static [&'static str],
+}
+
+impl InstructionInfo {
+    pub const fn byte(&self) -> u8 {
+        self.opcode as u8
+    }
+
+    const fn sets(mut self, flags: &'static [&'static str]) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    const fn costs(mut self, cycles: u32) -> Self {
+        self.cycles = cycles;
+        self
+    }
+}
+
+const fn instruction(
+    opcode: OpcodeType,
+    mnemonic: &'static str,
+    operands: &'static [OperandKind],
+) -> InstructionInfo {
+    let mut length = 1;
+    let mut i = 0;
+    while i < operands.len() {
+        length += operands[i].size();
+        i += 1;
+    }
+
+    InstructionInfo {
+        opcode,
+        mnemonic,
+        operands,
+        length,
+        cycles: 1,
+        flags: &[],
+    }
+}
+
+// The decoder, the assembler and the docs all work from this table.
+static INSTRUCTIONS: [InstructionInfo; 45] = [
+    instruction(OpcodeType::Load, "load", &[Register, Address]),
+    instruction(OpcodeType::Store, "store", &[Address, Register]),
+    instruction(OpcodeType::Add, "add", &[Register, Source]).sets(&["Z", "C"]),
+    instruction(OpcodeType::Sub, "sub", &[Register, Source]).sets(&["Z", "C"]),
+    instruction(OpcodeType::Jmp, "jmp", &[Address]),
+    instruction(OpcodeType::Mov, "mov", &[Register, Source]),
+    instruction(OpcodeType::Inc, "inc", &[Register]).sets(&["Z"]),
+    instruction(OpcodeType::Div, "div", &[Register, Source]).sets(&["Z", "C"]),
+    instruction(OpcodeType::Mul, "mul", &[Register, Source]).sets(&["Z", "C"]),
+    instruction(OpcodeType::Hcall, "hcall", &[Immediate]),
+    instruction(OpcodeType::Video, "video", &[VideoOperation, Register]),
+    instruction(OpcodeType::Jr, "jr", &[Offset]),
+    instruction(OpcodeType::Jrz, "jrz", &[Offset]),
+    instruction(OpcodeType::Jrnz, "jrnz", &[Offset]),
+    instruction(OpcodeType::Djnz, "djnz", &[Register, Offset]),
+    instruction(OpcodeType::Test, "test", &[Register, Source]).sets(&["Z"]),
+    instruction(OpcodeType::Bset, "bset", &[Register, Bit]),
+    instruction(OpcodeType::Bclr, "bclr", &[Register, Bit]),
+    instruction(OpcodeType::Btst, "btst", &[Register, Bit]).sets(&["Z"]),
+    instruction(OpcodeType::Memset, "memset", &[Register]),
+    instruction(OpcodeType::Memcpy, "memcpy", &[Register]),
+    instruction(OpcodeType::Call, "call", &[Address]),
+    instruction(OpcodeType::Jtab, "jtab", &[Register, Address]),
+    instruction(OpcodeType::Ret, "ret", &[]),
+    instruction(OpcodeType::Clc, "clc", &[]).sets(&["C"]),
+    instruction(OpcodeType::Stc, "stc", &[]).sets(&["C"]),
+    instruction(OpcodeType::Cmc, "cmc", &[]).sets(&["C"]),
+    instruction(OpcodeType::Pushf, "pushf", &[]),
+    instruction(OpcodeType::Popf, "popf", &[]).sets(&["Z", "C", "I"]),
+    instruction(OpcodeType::Ei, "ei", &[]).sets(&["I"]),
+    instruction(OpcodeType::Di, "di", &[]).sets(&["I"]),
+    instruction(OpcodeType::Int, "int", &[Immediate]).sets(&["I"]),
+    instruction(OpcodeType::Iret, "iret", &[]).sets(&["Z", "C", "I"]),
+    instruction(OpcodeType::FxMul, "fxmul", &[Pair, Pair]).sets(&["Z", "C"]),
+    instruction(OpcodeType::FxDiv, "fxdiv", &[Pair, Pair]).sets(&["Z", "C"]),
+    instruction(OpcodeType::FxMulImm, "fxmuli", &[Pair, Fixed]).sets(&["Z", "C"]),
+    instruction(OpcodeType::Dec, "dec", &[Register]).sets(&["Z", "C"]),
+    instruction(OpcodeType::Clr, "clr", &[Register]),
+    // A cycle for each memory access.
+    instruction(OpcodeType::Movm, "movm", &[Address, Address]).costs(2),
+    // `load` and `store` through a `[rN+]` pointer or at `[sp + offset]`, which the
+    // assembler picks by the operand.
+    instruction(OpcodeType::LoadInc, "load", &[Register, Pointer]),
+    instruction(OpcodeType::StoreInc, "store", &[Pointer, Register]),
+    instruction(OpcodeType::LoadSp, "load", &[Register, StackOffset]),
+    instruction(OpcodeType::StoreSp, "store", &[StackOffset, Register]),
+    instruction(OpcodeType::Nop, "nop", &[]),
+    instruction(OpcodeType::Hlt, "hlt", &[]),
+];
+
+// The longest instruction, movm with its two addresses. The decode cache and anything
+// reading an instruction out of memory before decoding it rely on it.
+pub const MAX_LENGTH: u16 = 5;
+
+// Indexed by opcode byte, so decoding is a single lookup.
+static BY_BYTE: [Option<&InstructionInfo>; 256] = {
+    let mut index = [None; 256];
+    let mut i = 0;
+    while i < INSTRUCTIONS.len() {
+        let byte = INSTRUCTIONS[i].byte() as usize;
+        assert!(index[byte].is_none(), "opcode listed twice");
+        index[byte] = Some(&INSTRUCTIONS[i]);
+        i += 1;
+    }
+    index
+};
+
+static LENGTH_BY_BYTE: [u16; 256] = {
+    let mut lengths = [0; 256];
+    let mut i = 0;
+    while i < INSTRUCTIONS.len() {
+        assert!(
+            INSTRUCTIONS[i].length <= MAX_LENGTH,
+            "instruction longer than MAX_LENGTH"
+        );
+        lengths[INSTRUCTIONS[i].byte() as usize] = INSTRUCTIONS[i].length;
+        i += 1;
+    }
+    lengths
+};
+
+pub fn instruction_table() -> &'static [InstructionInfo] {
+    &INSTRUCTIONS
+}
+
+pub fn info(opcode_type: OpcodeType) -> &'static InstructionInfo {
+    match BY_BYTE[opcode_type as usize] {
+        Some(info) => info,
+        None => unreachable!("{:?} is missing from the instruction table", opcode_type),
+    }
+}
+
+pub fn length(opcode_type: OpcodeType) -> u16 {
+    LENGTH_BY_BYTE[opcode_type as usize]
+}
+
+pub fn decode(byte: u8) -> Option<&'static InstructionInfo> {
+    BY_BYTE[byte as usize]
+}
+
+// The first instruction with `mnemonic`, see `variants` for the rest.
+pub fn from_mnemonic(mnemonic: &str) -> Option<&'static InstructionInfo> {
+    INSTRUCTIONS
+        .iter()
+        .find(|info| info.mnemonic.eq_ignore_ascii_case(mnemonic))
+}
+
+// Every instruction sharing `mnemonic`, which differ in the kinds of operand they take.
+pub fn variants(mnemonic: &str) -> impl Iterator<Item = &'static InstructionInfo> {
+    INSTRUCTIONS
+        .iter()
+        .filter(move |info| info.mnemonic.eq_ignore_ascii_case(mnemonic))
+}
+
+#[derive(Debug)]
+pub struct VideoOperationInfo {
+    pub operation: VideoOpcodeType,
+    pub mnemonic: &'static str,
+    // Read from consecutive registers, starting at the one the instruction names.
+    pub parameters: &'static [&'static str],
+}
+
+const fn video(
+    operation: VideoOpcodeType,
+    mnemonic: &'static str,
+    parameters: &'static [&'static str],
+) -> VideoOperationInfo {
+    VideoOperationInfo {
+        operation,
+        mnemonic,
+        parameters,
+    }
+}
+
+// In operation order, so the operation byte minus one indexes it.
+static VIDEO_OPERATIONS: [VideoOperationInfo; 7] = [
+    video(VideoOpcodeType::Fill, "fill", &["r", "g", "b"]),
+    video(VideoOpcodeType::Clear, "clear", &[]),
+    video(
+        VideoOpcodeType::SetPixel,
+        "setpixel",
+        &["x", "y", "r", "g", "b"],
+    ),
+    video(
+        VideoOpcodeType::FillRect,
+        "fillrect",
+        &["x", "y", "w", "h", "r", "g", "b"],
+    ),
+    video(VideoOpcodeType::Vsync, "vsync", &[]),
+    video(VideoOpcodeType::FadeTo, "fadeto", &["target", "steps"]),
+    video(
+        VideoOpcodeType::CopyRect,
+        "copyrect",
+        &["sx", "sy", "dx", "dy", "w", "h"],
+    ),
+];
+
+const _: () = {
+    let mut i = 0;
+    while i < VIDEO_OPERATIONS.len() {
+        assert!(
+            VIDEO_OPERATIONS[i].operation as usize == i + 1,
+            "video operations out of order"
+        );
+        i += 1;
+    }
+};
+
+pub fn video_operation_table() -> &'static [VideoOperationInfo] {
+    &VIDEO_OPERATIONS
+}
+
+pub fn video_info(operation: VideoOpcodeType) -> &'static VideoOperationInfo {
+    &VIDEO_OPERATIONS[operation as usize - 1]
+}
+
+pub fn decode_video(byte: u8) -> Option<&'static VideoOperationInfo> {
+    VIDEO_OPERATIONS.get((byte as usize).checked_sub(1)?)
+}
+
+pub fn video_from_mnemonic(mnemonic: &str) -> Option<&'static VideoOperationInfo> {
+    VIDEO_OPERATIONS
+        .iter()
+        .find(|info| info.mnemonic.eq_ignore_ascii_case(mnemonic))
+}
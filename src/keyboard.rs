@@ -0,0 +1,87 @@
+use alloc::collections::VecDeque;
+
+use crate::bus::Device;
+
+pub const KEY_STATUS: u8 = 0x00; // bit 0: an event is pending, bit 1: it is a key press. Write to pop it.
+pub const KEY_CODE: u8 = 0x01; // scancode of the pending event
+pub const KEY_CONTROL: u8 = 0x02; // bit 0: raise VECTOR_KEYBOARD whenever an event is queued
+pub const KEY_TIME: u8 = 0x03; // 4 bytes: low 32 bits of the cycle count the pending event was queued at
+pub const KEYBOARD_REGISTER_COUNT: u8 = 7;
+
+pub const KEY_PENDING: u8 = 0x01;
+pub const KEY_PRESSED: u8 = 0x02;
+
+pub const KEY_INTERRUPT_ENABLE: u8 = 0x01;
+
+// Raised for every event queued while KEY_INTERRUPT_ENABLE is set. Vblank's lower vector goes
+// first when both are pending.
+pub const VECTOR_KEYBOARD: u8 = 2;
+
+// Events beyond this are dropped until the guest catches up.
+pub const KEY_QUEUE_LEN: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub scancode: u8,
+    pub pressed: bool,
+    // The CPU's cycle count when the event was queued.
+    pub cycles: u64,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Keyboard {
+    pub events: VecDeque<KeyEvent>,
+    pub control: u8,
+}
+
+impl Keyboard {
+    // Queues an event stamped with `cycles`. Returns whether the guest wants an interrupt
+    // for it, which is for the CPU to raise since the device can't.
+    pub fn push(&mut self, scancode: u8, pressed: bool, cycles: u64) -> bool {
+        if self.events.len() >= KEY_QUEUE_LEN {
+            log::warn!("keyboard queue full, dropped scancode {:#04x}", scancode);
+            return false;
+        }
+        self.events.push_back(KeyEvent {
+            scancode,
+            pressed,
+            cycles,
+        });
+        self.control & KEY_INTERRUPT_ENABLE != 0
+    }
+}
+
+impl Device for Keyboard {
+    fn read(&mut self, offset: u16) -> u8 {
+        self.peek(offset)
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        match offset as u8 {
+            KEY_STATUS => {
+                self.events.pop_front();
+            }
+            KEY_CONTROL => self.control = value,
+            _ => {}
+        }
+    }
+
+    fn peek(&self, offset: u16) -> u8 {
+        let offset = offset as u8;
+        if offset == KEY_CONTROL {
+            return self.control;
+        }
+        let Some(event) = self.events.front() else {
+            return 0;
+        };
+        match offset {
+            KEY_STATUS if event.pressed => KEY_PENDING | KEY_PRESSED,
+            KEY_STATUS => KEY_PENDING,
+            KEY_CODE => event.scancode,
+            KEY_TIME..KEYBOARD_REGISTER_COUNT => {
+                (event.cycles as u32).to_le_bytes()[(offset - KEY_TIME) as usize]
+            }
+            _ => 0,
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use crate::types::Color;
+
+// What fills a window around a frame that doesn't cover it.
+#[derive(Debug, Clone, Copy)]
+pub struct Letterbox {
+    pub color: Color,
+    // Drawn one window pixel wide just outside the frame.
+    pub border: Option<Color>,
+}
+
+// Where a frame goes in a window, in window pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Placement {
+    pub scale: u32,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Letterbox {
+    // Black bars and no border.
+    pub const fn new() -> Self {
+        Self {
+            color: Color::new(0, 0, 0),
+            border: None,
+        }
+    }
+}
+
+impl Default for Letterbox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Placement {
+    // Where a point `x`, `y` in logical window pixels falls on the frame, in frame pixels,
+    // on a display with `scale_factor` physical pixels to the logical one. The placement is
+    // in physical pixels, which is what the window's buffer is. Points over the bars fall
+    // outside 0 to the frame's size.
+    pub fn to_frame(&self, x: f64, y: f64, scale_factor: f64) -> (f64, f64) {
+        let scale = self.scale as f64;
+        (
+            (x * scale_factor - self.x as f64) / scale,
+            (y * scale_factor - self.y as f64) / scale,
+        )
+    }
+}
+
+// A `width` by `height` frame scaled by the largest whole factor that fits the window with
+// room for a border if there is one, and centred. A window too small for the frame at 1x
+// shows its top left corner.
+pub fn place(
+    width: u32,
+    height: u32,
+    window_width: u32,
+    window_height: u32,
+    border: bool,
+) -> Placement {
+    let margin = if border { 2 } else { 0 };
+    let fit = |size: u32, window: u32| window.saturating_sub(margin) / size.max(1);
+    let scale = fit(width, window_width)
+        .min(fit(height, window_height))
+        .max(1);
+    let (width, height) = (width * scale, height * scale);
+    Placement {
+        scale,
+        x: window_width.saturating_sub(width) / 2,
+        y: window_height.saturating_sub(height) / 2,
+        width,
+        height,
+    }
+}
+
+// Fills `out`, an RGBA window `window_width` pixels wide, with the letterbox color, draws
+// the border and copies `frame`, already scaled to the placement's size, into its place.
+pub fn compose(
+    frame: &[u8],
+    placement: &Placement,
+    window_width: u32,
+    letterbox: &Letterbox,
+    out: &mut [u8],
+) {
+    let window_width = window_width as usize;
+    if window_width == 0 {
+        return;
+    }
+    let bar = letterbox.color.to_rgba();
+    for pixel in out.chunks_exact_mut(4) {
+        pixel.copy_from_slice(&bar);
+    }
+    let window_height = out.len() / 4 / window_width;
+    let (x, y) = (placement.x as usize, placement.y as usize);
+    let (width, height) = (placement.width as usize, placement.height as usize);
+
+    if let Some(border) = letterbox.border {
+        let border = border.to_rgba();
+        // Any side with no room left for it is not drawn.
+        let mut set = |column: usize, row: usize| {
+            if column < window_width && row < window_height {
+                out[(row * window_width + column) * 4..][..4].copy_from_slice(&border);
+            }
+        };
+        let (left, top) = (x.checked_sub(1), y.checked_sub(1));
+        for column in x.saturating_sub(1)..=x + width {
+            if let Some(top) = top {
+                set(column, top);
+            }
+            set(column, y + height);
+        }
+        for row in y..y + height {
+            if let Some(left) = left {
+                set(left, row);
+            }
+            set(x + width, row);
+        }
+    }
+
+    let visible = width.min(window_width.saturating_sub(x)) * 4;
+    for (row, line) in frame.chunks_exact(width * 4).enumerate().take(height) {
+        if y + row >= window_height {
+            break;
+        }
+        out[((y + row) * window_width + x) * 4..][..visible].copy_from_slice(&line[..visible]);
+    }
+}
@@ -0,0 +1,84 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod asm;
+pub mod audio;
+#[cfg(feature = "std")]
+pub mod bench;
+pub mod bus;
+#[cfg(feature = "std")]
+pub mod config;
+pub mod coredump;
+pub mod cpu;
+pub mod crc32;
+pub mod crt;
+pub mod debugger;
+pub mod decode_cache;
+pub mod demo;
+pub mod determinism;
+pub mod disasm;
+pub mod disk;
+pub mod dma;
+pub mod error;
+pub mod extension;
+pub mod fault;
+#[cfg(feature = "capi")]
+pub mod ffi;
+#[cfg(feature = "std")]
+pub mod files;
+pub mod fixed;
+pub mod font;
+pub mod framebuffer;
+pub mod gamepad;
+#[cfg(feature = "arbitrary")]
+pub mod generate;
+#[cfg(feature = "std")]
+pub mod golden;
+#[cfg(feature = "window")]
+pub mod gpu;
+pub mod hcall;
+pub mod ihex;
+pub mod input;
+pub mod interrupt;
+pub mod isa;
+pub mod keyboard;
+pub mod letterbox;
+pub mod mailbox;
+#[cfg(feature = "tui")]
+pub mod monitor;
+pub mod mouse;
+#[cfg(feature = "net")]
+pub mod net;
+#[cfg(feature = "std")]
+pub mod nvram;
+pub mod overlay;
+pub mod perf;
+pub mod png;
+pub mod profile;
+pub mod program;
+pub mod protect;
+#[cfg(feature = "std")]
+pub mod record;
+#[cfg(feature = "window")]
+pub mod render;
+pub mod rtc;
+pub mod segment;
+pub mod snapshot;
+pub mod sprite;
+pub mod stdlib;
+#[cfg(feature = "proptest")]
+pub mod strategy;
+pub mod symbols;
+pub mod tilemap;
+pub mod trace;
+pub mod types;
+pub mod video;
+pub mod vm;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod watchdog;
+
+pub use cpu::HaltReason;
+pub use snapshot::SnapshotDiff;
+pub use vm::{MicroCvm, MicroCvmBuilder, Snapshot};
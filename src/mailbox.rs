@@ -0,0 +1,154 @@
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+
+use crate::bus::Device;
+
+pub const MAILBOX_STATUS: u8 = 0x00; // see the flags below, write to clear MAILBOX_OVERFLOW
+pub const MAILBOX_IN: u8 = 0x01; // oldest incoming byte, write to pop it
+pub const MAILBOX_OUT: u8 = 0x02; // write to send a byte
+pub const MAILBOX_REGISTER_COUNT: u8 = 3;
+
+pub const MAILBOX_AVAILABLE: u8 = 0x01;
+pub const MAILBOX_FULL: u8 = 0x02;
+// A send was dropped because the other side had not caught up.
+pub const MAILBOX_OVERFLOW: u8 = 0x04;
+
+pub const DEFAULT_MAILBOX_CAPACITY: usize = 16;
+
+// Single-producer single-consumer queue. Each direction of a pair has exactly one writer
+// and one reader, so the two machines may run on different threads without a lock.
+struct Ring {
+    slots: Box<[AtomicU8]>,
+    // Both only ever increase, the slot is the index modulo the capacity.
+    head: AtomicUsize,
+    tail: AtomicUsize,
+}
+
+// One end of a mailbox pair. Bytes sent on one end arrive at the other.
+pub struct Mailbox {
+    outgoing: Arc<Ring>,
+    incoming: Arc<Ring>,
+    overflow: bool,
+}
+
+impl Ring {
+    fn new(capacity: usize) -> Self {
+        Self {
+            slots: (0..capacity.max(1)).map(|_| AtomicU8::new(0)).collect(),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.tail
+            .load(Ordering::Acquire)
+            .wrapping_sub(self.head.load(Ordering::Acquire))
+    }
+
+    fn is_full(&self) -> bool {
+        self.len() >= self.slots.len()
+    }
+
+    fn push(&self, value: u8) -> bool {
+        let tail = self.tail.load(Ordering::Relaxed);
+        if tail.wrapping_sub(self.head.load(Ordering::Acquire)) >= self.slots.len() {
+            return false;
+        }
+        self.slots[tail % self.slots.len()].store(value, Ordering::Relaxed);
+        self.tail.store(tail.wrapping_add(1), Ordering::Release);
+        true
+    }
+
+    fn front(&self) -> Option<u8> {
+        let head = self.head.load(Ordering::Relaxed);
+        if head == self.tail.load(Ordering::Acquire) {
+            return None;
+        }
+        Some(self.slots[head % self.slots.len()].load(Ordering::Relaxed))
+    }
+
+    fn pop(&self) {
+        let head = self.head.load(Ordering::Relaxed);
+        if head != self.tail.load(Ordering::Acquire) {
+            self.head.store(head.wrapping_add(1), Ordering::Release);
+        }
+    }
+}
+
+impl Mailbox {
+    // Two connected ends, each buffering up to `capacity` bytes in either direction.
+    pub fn pair(capacity: usize) -> (Mailbox, Mailbox) {
+        let a_to_b = Arc::new(Ring::new(capacity));
+        let b_to_a = Arc::new(Ring::new(capacity));
+        (
+            Mailbox {
+                outgoing: a_to_b.clone(),
+                incoming: b_to_a.clone(),
+                overflow: false,
+            },
+            Mailbox {
+                outgoing: b_to_a,
+                incoming: a_to_b,
+                overflow: false,
+            },
+        )
+    }
+
+    // Returns false, dropping the byte, if the other end's buffer is full.
+    pub fn send(&mut self, value: u8) -> bool {
+        let sent = self.outgoing.push(value);
+        self.overflow |= !sent;
+        sent
+    }
+
+    pub fn receive(&mut self) -> Option<u8> {
+        let value = self.incoming.front()?;
+        self.incoming.pop();
+        Some(value)
+    }
+
+    pub fn pending(&self) -> usize {
+        self.incoming.len()
+    }
+}
+
+impl Device for Mailbox {
+    fn read(&mut self, offset: u16) -> u8 {
+        self.peek(offset)
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        let offset = offset as u8;
+        match offset {
+            MAILBOX_STATUS => self.overflow = false,
+            MAILBOX_IN => self.incoming.pop(),
+            MAILBOX_OUT => {
+                self.send(value);
+            }
+            _ => {}
+        }
+    }
+
+    fn peek(&self, offset: u16) -> u8 {
+        let offset = offset as u8;
+        match offset {
+            MAILBOX_STATUS => {
+                let mut status = 0;
+                if self.incoming.front().is_some() {
+                    status |= MAILBOX_AVAILABLE;
+                }
+                if self.outgoing.is_full() {
+                    status |= MAILBOX_FULL;
+                }
+                if self.overflow {
+                    status |= MAILBOX_OVERFLOW;
+                }
+                status
+            }
+            MAILBOX_IN => self.incoming.front().unwrap_or(0),
+            _ => 0,
+        }
+    }
+}
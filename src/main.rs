@@ -1,49 +1,747 @@
-mod cpu;
-mod disk;
-mod render;
-mod types;
+mod cli;
+mod repl;
 
-use winit::event_loop::{ControlFlow, EventLoop};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 
-fn main() {
-    let mut vcpu = cpu::MicroCVMCpu::empty();
-    let vdisk = disk::MicroCVMDisk::empty();
+use microcvm_rs::asm::{AsmError, Assembler};
+use microcvm_rs::bench::{BENCH_MEMORY_PROGRAM, BENCH_PROGRAM};
+use microcvm_rs::coredump::CoreDump;
+use microcvm_rs::cpu::{MicroCVMCpu, RegisterWidth};
+use microcvm_rs::framebuffer::DEFAULT_FRAMEBUFFER_WINDOW_LEN;
+use microcvm_rs::mouse::MOUSE_RELATIVE;
+use microcvm_rs::program::{MAGIC, Program};
+use microcvm_rs::record::Recorder;
+use microcvm_rs::segment::{Segment, SegmentKind, SegmentedProgram};
+use microcvm_rs::symbols::SymbolTable;
+use microcvm_rs::trace::StderrTrace;
+use microcvm_rs::{HaltReason, MicroCvm};
+use microcvm_rs::{demo, disasm, disk, ihex, isa};
 
-    vcpu.memory[0] = cpu::OpcodeType::Mov as u8;
-    vcpu.memory[1] = cpu::Register::R0 as u8;
-    vcpu.memory[2] = 16;
-    vcpu.memory[3] = cpu::OpcodeType::Hlt as u8;
-    // match vcpu.read_memory_from_file("../../tests/mov.bin") {
-    //     Ok(_) => {}
-    //     Err(e) => {
-    //         eprintln!("error reading binary: {}", e);
-    //     }
-    // }
+use cli::{AsmFormat, AsmOptions, Command, DisasmOptions, RunOptions, Source, USAGE};
 
-    loop {
-        let opcode: u8 = vcpu.memory[vcpu.pc as usize];
-        if opcode == cpu::OpcodeType::Hlt as u8 {
-            break;
+fn main() -> ExitCode {
+    // RUST_LOG=microcvm=debug shows machine setup, trace level every instruction.
+    env_logger::init();
+    let command = match cli::parse_args(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
         }
-        vcpu.execute_instruction();
+    };
 
-        vcpu.pc += 1;
+    match command {
+        Command::Help => {
+            println!("{}", USAGE);
+            ExitCode::SUCCESS
+        }
+        Command::Bench {
+            profile,
+            decode_cache,
+        } => {
+            let bench = |program| MicroCVMCpu::bench(program, 50_000_000, profile, decode_cache);
+            println!("{}", bench(BENCH_PROGRAM));
+            println!("\nmemory access:");
+            println!("{}", bench(BENCH_MEMORY_PROGRAM));
+            println!("\ncopying a 64x64 frame into video memory:");
+            for report in MicroCVMCpu::bench_frame_copy(2_000, decode_cache) {
+                println!("{}", report);
+            }
+            ExitCode::SUCCESS
+        }
+        Command::SelfTest => self_test(),
+        Command::PrintConfig => {
+            print!("{}", microcvm_rs::config::TEMPLATE);
+            ExitCode::SUCCESS
+        }
+        Command::Isa { json } => {
+            print_isa(json);
+            ExitCode::SUCCESS
+        }
+        Command::Repl => match repl::run(std::io::stdin().lock(), std::io::stdout()) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                ExitCode::FAILURE
+            }
+        },
+        Command::Inspect { dump } => inspect(&dump),
+        Command::Asm(options) => assemble(options),
+        Command::Disasm(options) => disassemble(options),
+        Command::Run(options) => run(*options),
     }
+}
 
-    println!("{}", vcpu.registers[cpu::Register::R0 as usize]);
+fn inspect(file: &str) -> ExitCode {
+    let dump = match CoreDump::read(Path::new(file)) {
+        Ok(dump) => dump,
+        Err(e) => {
+            eprintln!("error: could not read core dump `{}`: {}", file, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    match repl::inspect(&dump, std::io::stdin().lock(), std::io::stdout()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
 
-    let event_loop = EventLoop::new().unwrap();
+fn assemble(options: AsmOptions) -> ExitCode {
+    let source = match std::fs::read_to_string(&options.input) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("error: could not read `{}`: {}", options.input, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let assembler = includer(&options);
+    let assembled =
+        assemble_as(&assembler, &source, options.format).and_then(|(program, symbols)| {
+            let listing = match options.listing {
+                Some(_) => Some(assembler.listing(&source)?),
+                None => None,
+            };
+            Ok((program, symbols, listing))
+        });
+    let (program, symbols, listing) = match assembled {
+        Ok(assembled) => assembled,
+        Err(e) => {
+            report_asm_error(&options.input, &source, &e);
+            return ExitCode::FAILURE;
+        }
+    };
 
-    event_loop.set_control_flow(ControlFlow::Poll);
+    let mut outputs = vec![(options.output.display().to_string(), program)];
+    if let Some(file) = options.symbols {
+        outputs.push((file, symbols.to_sidecar().into_bytes()));
+    }
+    if let (Some(file), Some(listing)) = (options.listing, listing) {
+        outputs.push((file, listing.into_bytes()));
+    }
+    for (file, bytes) in outputs {
+        if let Err(e) = std::fs::write(&file, bytes) {
+            eprintln!("error: could not write `{}`: {}", file, e);
+            return ExitCode::FAILURE;
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+// An assembler whose `.include` finds files beside the source, then in each `-I` directory.
+fn includer(options: &AsmOptions) -> Assembler {
+    let source_dir = Path::new(&options.input)
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    let dirs: Vec<PathBuf> = std::iter::once(source_dir)
+        .chain(options.include_dirs.iter().cloned())
+        .collect();
+    Assembler::new().resolver(Box::new(move |name| {
+        for dir in &dirs {
+            let path = dir.join(name);
+            match std::fs::read_to_string(&path) {
+                Ok(source) => return Ok(source),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(format!("could not read `{}`: {}", path.display(), e)),
+            }
+        }
+        Err(format!(
+            "no `{}` beside the source or in an `-I` directory",
+            name
+        ))
+    }))
+}
+
+// The program file in `format` and the labels in it.
+fn assemble_as(
+    assembler: &Assembler,
+    source: &str,
+    format: AsmFormat,
+) -> Result<(Vec<u8>, SymbolTable), AsmError> {
+    if format == AsmFormat::Raw {
+        return assembler.assemble_with_symbols(source);
+    }
+    let (program, symbols) = assembler.assemble_segments_with_symbols(source)?;
+    if format == AsmFormat::Header {
+        return Ok((program.build(), symbols));
+    }
+    if program
+        .segments
+        .iter()
+        .any(|segment| segment.kind == SegmentKind::Video)
+    {
+        // The line isn't known once the segments are built.
+        return Err(AsmError {
+            line: 0,
+            message: String::from("Intel HEX has no video memory, use `--format header`"),
+        });
+    }
+    let chunks = program
+        .segments
+        .iter()
+        .filter(|segment| segment.kind != SegmentKind::Bss)
+        .map(|segment| (segment.offset as u16, segment.bytes.as_slice()));
+    Ok((ihex::encode(chunks, program.entry).into_bytes(), symbols))
+}
+
+// `file:line: message` and the line it points at, like a compiler.
+fn report_asm_error(file: &str, source: &str, error: &AsmError) {
+    let Some(text) = error
+        .line
+        .checked_sub(1)
+        .and_then(|index| source.lines().nth(index))
+    else {
+        eprintln!("error: {}: {}", file, error.message);
+        return;
+    };
+    eprintln!("error: {}:{}: {}", file, error.line, error.message);
+    eprintln!("{:>5} | {}", error.line, text);
+}
+
+fn disassemble(options: DisasmOptions) -> ExitCode {
+    let bytes = match std::fs::read(&options.input) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("error: could not read `{}`: {}", options.input, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut listed = match Listed::read(&bytes, &options) {
+        Ok(listed) => listed,
+        Err(e) => {
+            eprintln!("error: `{}`: {}", options.input, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Some((start, end)) = options.range {
+        listed.segments = listed
+            .segments
+            .into_iter()
+            .filter_map(|segment| clip(segment, start as u64, end as u64))
+            .collect();
+        if listed.segments.is_empty() {
+            eprintln!(
+                "error: `{}` has nothing from {:#06x} to {:#06x}",
+                options.input, start, end
+            );
+            return ExitCode::FAILURE;
+        }
+    }
+
+    let mut symbols = match &options.symbols {
+        Some(file) => match load_symbols(file, &bytes) {
+            Ok(symbols) => symbols,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => SymbolTable::new(),
+    };
+    let code: Vec<(u16, &[u8])> = listed
+        .segments
+        .iter()
+        .filter(|segment| matches!(segment.kind, SegmentKind::Code | SegmentKind::Data))
+        .map(|segment| (segment.offset as u16, segment.bytes.as_slice()))
+        .collect();
+    disasm::synthesize_labels(&mut symbols, &code, options.register_width);
+    print!("{}", listed.disassembly(&code, &symbols, &options));
+    ExitCode::SUCCESS
+}
+
+// What `disasm` lists: the segments of a segmented program, or one code segment holding a
+// raw image or the payload of a program file.
+struct Listed {
+    segments: Vec<Segment>,
+    segmented: bool,
+    entry: Option<u16>,
+}
+
+impl Listed {
+    fn read(bytes: &[u8], options: &DisasmOptions) -> Result<Self, String> {
+        let raw = |origin: u16, bytes: Vec<u8>| Listed {
+            segments: vec![Segment::new(SegmentKind::Code, origin as u32, bytes)],
+            segmented: false,
+            entry: None,
+        };
+        if options.raw || !bytes.starts_with(&MAGIC) {
+            return Ok(raw(options.origin.unwrap_or(0), bytes.to_vec()));
+        }
+        let program = Program::parse(bytes).map_err(|e| e.to_string())?;
+        if !program.header.segmented() {
+            let origin = options.origin.unwrap_or(program.header.load_address);
+            let entry = program.header.entry.filter(|&entry| entry != origin);
+            return Ok(Listed {
+                entry,
+                ..raw(origin, program.payload)
+            });
+        }
+        if options.origin.is_some() {
+            return Err(String::from(
+                "a segmented program places its own segments, `--org` can't move them",
+            ));
+        }
+        let program = SegmentedProgram::parse(bytes).map_err(|e| e.to_string())?;
+        Ok(Listed {
+            segments: program.segments,
+            segmented: true,
+            entry: Some(program.entry),
+        })
+    }
+
+    // Source that assembles back to the same bytes, with `.equ` for the symbols the
+    // disassembly can't define as labels.
+    fn disassembly(
+        &self,
+        code: &[(u16, &[u8])],
+        symbols: &SymbolTable,
+        options: &DisasmOptions,
+    ) -> String {
+        let mut text = String::new();
+        if options.register_width == RegisterWidth::Sixteen {
+            text.push_str(".width 16\n");
+        }
+        let defined = |address: u16| {
+            code.iter().any(|&(origin, image)| {
+                (origin as usize..origin as usize + image.len()).contains(&(address as usize))
+            })
+        };
+        for (name, address) in symbols.iter() {
+            // Other names for an address never appear in the disassembly.
+            if !defined(address) && symbols.name(address) == Some(name) {
+                text.push_str(&format!(".equ {}, {:#06x}\n", name, address));
+            }
+        }
+
+        let mut section = SegmentKind::Code;
+        for (index, segment) in self.segments.iter().enumerate() {
+            if self.segmented && segment.kind != section {
+                text.push_str(&format!(".section {}\n", segment.kind.name()));
+                section = segment.kind;
+            }
+            if index > 0 || segment.offset != 0 {
+                text.push_str(&format!(".org {:#06x}\n", segment.offset));
+            }
+            match segment.kind {
+                SegmentKind::Bss => text.push_str(&format!("    .space {}\n", segment.length)),
+                // Pixels, not instructions.
+                SegmentKind::Video => {
+                    for row in segment.bytes.chunks(8) {
+                        let row: Vec<String> =
+                            row.iter().map(|byte| format!("{:#04x}", byte)).collect();
+                        text.push_str(&format!("    .db {}\n", row.join(", ")));
+                    }
+                }
+                SegmentKind::Code | SegmentKind::Data => disasm::disassemble_into(
+                    &mut text,
+                    &segment.bytes,
+                    segment.offset as u16,
+                    symbols,
+                    options.register_width,
+                    options.bytes,
+                ),
+            }
+        }
+        match self.entry {
+            Some(entry) if self.segmented => match symbols.name(entry) {
+                Some(name) => text.push_str(&format!(".entry {}\n", name)),
+                None => text.push_str(&format!(".entry {:#06x}\n", entry)),
+            },
+            // Only a segmented program can say where it starts in source.
+            Some(entry) => text.push_str(&format!("; execution starts at {:#06x}\n", entry)),
+            None => {}
+        }
+        text
+    }
+}
 
-    // for i in 0..2000 {
-    //     vcpu.video_memory[i].r = 255;
-    //     vcpu.video_memory[i].g = 0;
-    //     vcpu.video_memory[i].b = 255;
-    // }
+// The part of a memory segment from `start` up to `end`, or None if it has none there.
+fn clip(mut segment: Segment, start: u64, end: u64) -> Option<Segment> {
+    if segment.kind == SegmentKind::Video {
+        return None;
+    }
+    let from = start.max(segment.offset as u64);
+    let to = end.min(segment.end());
+    if from >= to {
+        return None;
+    }
+    if segment.kind != SegmentKind::Bss {
+        let skip = (from - segment.offset as u64) as usize;
+        segment.bytes = segment.bytes[skip..skip + (to - from) as usize].to_vec();
+    }
+    segment.offset = from as u32;
+    segment.length = (to - from) as u32;
+    Some(segment)
+}
+
+// How many addresses `--profile-out` describes.
+#[cfg(feature = "json")]
+const PROFILE_HOTSPOTS: usize = 32;
+
+fn run(options: RunOptions) -> ExitCode {
+    if cfg!(not(feature = "json")) && options.profile_out.is_some() {
+        eprintln!("error: built without the `json` feature, needed for --profile-out");
+        return ExitCode::FAILURE;
+    }
+    let (name, bytes) = match &options.source {
+        Source::File(file) => match std::fs::read(file) {
+            Ok(bytes) => (file.as_str(), bytes),
+            Err(e) => {
+                eprintln!("error: could not read `{}`: {}", file, e);
+                return ExitCode::FAILURE;
+            }
+        },
+        Source::Demo => ("demo", demo::bounce_program()),
+        Source::ClockDemo => ("clock demo", demo::clock_program()),
+        Source::FramebufferDemo => ("framebuffer demo", demo::framebuffer_program()),
+        Source::TilemapDemo => ("tile map demo", demo::tilemap_program()),
+    };
 
-    let mut app = render::App::new(768, 576, vcpu.video_memory);
+    let mut builder = MicroCvm::builder()
+        .resolution(options.width, options.height)
+        .max_instructions(options.max_instructions.unwrap_or(u64::MAX))
+        .register_width(options.register_width)
+        .decode_cache(options.decode_cache)
+        .verify_checksums(options.verify)
+        .pause_on_focus_loss(options.pause_on_focus_loss);
+    if let Some(start) = options.framebuffer_window {
+        builder = builder.framebuffer_window(start, DEFAULT_FRAMEBUFFER_WINDOW_LEN);
+    }
+    if let Some(file) = &options.core_dump {
+        builder = builder.core_dump_file(file);
+    }
+    let symbols = match &options.symbols {
+        Some(file) if options.trace || options.monitor || options.profile_out.is_some() => {
+            match load_symbols(file, &bytes) {
+                Ok(symbols) => symbols,
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        _ => SymbolTable::new(),
+    };
+    // The monitor shows the trace itself rather than printing it over the terminal.
+    if options.trace && !options.monitor {
+        builder = builder.trace(Box::new(StderrTrace::with_symbols(symbols.clone())));
+    }
+    let mut vm = builder.build();
+    let _vdisk = disk::MicroCVMDisk::empty();
+
+    if let Err(e) = vm.load_program(&bytes) {
+        eprintln!("error: could not load `{}`: {}", name, e);
+        return ExitCode::FAILURE;
+    }
+    if let Some(entry) = options.entry {
+        vm.set_entry(entry);
+    }
+    if options.relative_mouse {
+        vm.cpu_mut().mouse_mut().control |= MOUSE_RELATIVE;
+    }
+    if options.profile_out.is_some() {
+        vm.cpu_mut().enable_profiler();
+    }
+    // After loading, so the saved contents win over anything the image put there.
+    if let Some(file) = &options.nvram {
+        match vm.cpu_mut().attach_nvram(file) {
+            Ok(warning) => {
+                if let Some(warning) = warning {
+                    eprintln!("warning: `{}`: {}", file, warning);
+                }
+            }
+            Err(e) => {
+                eprintln!("error: could not open NVRAM file `{}`: {}", file, e);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    #[cfg(feature = "audio")]
+    let _audio_stream =
+        match microcvm_rs::audio::start_playback(vm.cpu().audio.clone(), vm.cpu().pcm.clone()) {
+            Ok(stream) => Some(stream),
+            Err(e) => {
+                eprintln!("error starting audio playback: {}", e);
+                None
+            }
+        };
+
+    if options.monitor {
+        return open_monitor(vm, symbols, options.cycles_per_frame);
+    }
+
+    if options.headless {
+        let mut recorder = None;
+        if options.recording {
+            vm.set_crt(Some(options.crt).filter(|_| options.crt_enabled));
+            match Recorder::start(options.record.clone()) {
+                Ok(started) => recorder = Some(started),
+                Err(e) => {
+                    eprintln!(
+                        "error: could not record to `{}`: {}",
+                        options.record.dir.display(),
+                        e
+                    );
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        // Presents a frame, as far as the guest can tell, whenever the window would.
+        let result = loop {
+            match vm.run_frame(options.cycles_per_frame) {
+                Ok(HaltReason::FrameComplete) => {
+                    if let Some(recorder) = recorder.as_mut() {
+                        recorder.capture(&mut vm);
+                    }
+                    vm.tick_frame();
+                }
+                other => break other,
+            }
+        };
+        if let Some(recorder) = recorder {
+            match recorder.finish() {
+                Ok(stats) => eprintln!("{} to `{}`", stats, options.record.dir.display()),
+                Err(e) => {
+                    eprintln!(
+                        "error: recording to `{}`: {}",
+                        options.record.dir.display(),
+                        e
+                    );
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        #[cfg(feature = "json")]
+        if let (Some(file), Some(profiler)) = (&options.profile_out, vm.cpu().profiler()) {
+            let json = profiler
+                .report()
+                .to_json(vm.cpu(), &symbols, PROFILE_HOTSPOTS);
+            if let Err(e) = std::fs::write(file, json) {
+                eprintln!("error: could not write the profile to `{}`: {}", file, e);
+                return ExitCode::FAILURE;
+            }
+        }
+        return match result {
+            // The program's own exit code, for scripts to check.
+            Ok(reason @ HaltReason::Halted { code }) => {
+                println!("{}", reason);
+                ExitCode::from(code)
+            }
+            Ok(reason) => {
+                println!("{}", reason);
+                ExitCode::SUCCESS
+            }
+            // The full report has gone to the log.
+            Err(e) => {
+                println!("Fault: {}", e.cause());
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    open_window(vm, &options)
+}
+
+// Reads a symbol file, printing warnings for duplicates and for symbols outside `program`.
+fn load_symbols(file: &str, program: &[u8]) -> Result<SymbolTable, String> {
+    let text =
+        std::fs::read_to_string(file).map_err(|e| format!("could not read `{}`: {}", file, e))?;
+    let (symbols, mut warnings) =
+        SymbolTable::parse(&text).map_err(|e| format!("`{}`: {}", file, e))?;
+    let (start, len) = Program::extent(program);
+    warnings.extend(symbols.check(start, len));
+    for warning in warnings {
+        eprintln!("warning: `{}`: {}", file, warning);
+    }
+    Ok(symbols)
+}
+
+fn print_isa(json: bool) {
+    let table = isa::instruction_table();
+    if !json {
+        println!(
+            "{:<8} {:<6} {:<6} {:<6} operands",
+            "mnemonic", "opcode", "length", "cycles"
+        );
+        for info in table {
+            let operands: Vec<&str> = info.operands.iter().map(|kind| kind.name()).collect();
+            println!(
+                "{:<8} {:#04x}   {:<6} {:<6} {}",
+                info.mnemonic,
+                info.byte(),
+                info.length,
+                info.cycles,
+                operands.join(", ")
+            );
+        }
+        return;
+    }
+
+    // Every string in the table is a plain identifier, so nothing needs escaping.
+    let quoted = |items: Vec<&str>| -> String {
+        let items: Vec<String> = items.iter().map(|item| format!("\"{}\"", item)).collect();
+        format!("[{}]", items.join(", "))
+    };
+    println!("[");
+    for (i, info) in table.iter().enumerate() {
+        let comma = if i + 1 < table.len() { "," } else { "" };
+        println!(
+            "  {{\"mnemonic\": \"{}\", \"opcode\": {}, \"length\": {}, \"operand_count\": {}, \"operands\": {}, \"cycles\": {}, \"flags\": {}}}{}",
+            info.mnemonic,
+            info.byte(),
+            info.length,
+            info.operands.len(),
+            quoted(info.operands.iter().map(|kind| kind.name()).collect()),
+            info.cycles,
+            quoted(info.flags.to_vec()),
+            comma
+        );
+    }
+    println!("]");
+}
+
+fn self_test() -> ExitCode {
+    for width in [RegisterWidth::Eight, RegisterWidth::Sixteen] {
+        let bits = width.bits();
+        let mut vm = demo::self_test_vm(width);
+        match vm.run() {
+            // The ROM halts with the number of the last test that failed.
+            Ok(HaltReason::Halted { code: 0 }) => {}
+            Ok(HaltReason::Halted { code }) => {
+                println!(
+                    "Self-test failed with {}-bit registers: test {}",
+                    bits, code
+                );
+                return ExitCode::FAILURE;
+            }
+            Ok(reason) => {
+                println!(
+                    "Self-test did not finish with {}-bit registers: {}",
+                    bits, reason
+                );
+                return ExitCode::FAILURE;
+            }
+            Err(e) => {
+                println!("Self-test faulted with {}-bit registers: {}", bits, e);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    println!("Self-test passed");
+    ExitCode::SUCCESS
+}
+
+#[cfg(feature = "tui")]
+fn open_monitor(vm: MicroCvm, symbols: SymbolTable, cycles_per_frame: u64) -> ExitCode {
+    use microcvm_rs::monitor::{self, Monitor};
+
+    let mut monitor = Monitor::new(vm, symbols);
+    monitor.cycles_per_frame = cycles_per_frame;
+    match monitor::run(monitor) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(not(feature = "tui"))]
+fn open_monitor(_vm: MicroCvm, _symbols: SymbolTable, _cycles_per_frame: u64) -> ExitCode {
+    eprintln!("error: built without the `tui` feature");
+    ExitCode::FAILURE
+}
+
+#[cfg(feature = "window")]
+fn open_window(vm: MicroCvm, options: &RunOptions) -> ExitCode {
+    use microcvm_rs::gpu::{AdapterPreference, GpuBackend, GpuConfig};
+    use microcvm_rs::input::InputConfig;
+    use microcvm_rs::render;
+    use winit::event_loop::{ControlFlow, EventLoop};
+
+    let event_loop = match EventLoop::new() {
+        Ok(event_loop) => event_loop,
+        Err(e) => {
+            eprintln!("error: could not open a window: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut input = InputConfig::new();
+    input.key_repeat = options.key_repeat;
+    if let Some(keymap) = &options.keymap
+        && let Err(e) = input.load_keymap(keymap)
+    {
+        eprintln!("error: could not load the keymap `{}`: {}", keymap, e);
+        return ExitCode::FAILURE;
+    }
+
+    event_loop.set_control_flow(ControlFlow::Poll);
+
+    let placement = match (options.window_monitor, options.window_position) {
+        (_, Some((x, y))) => render::WindowPlacement::Position(x, y),
+        (Some(index), None) => render::WindowPlacement::Monitor(index),
+        (None, None) => render::WindowPlacement::Primary,
+    };
+    let present_mode = match options.present_mode.as_deref() {
+        None => render::PresentMode::Vsync,
+        Some(name) => match render::PresentMode::from_name(name) {
+            Some(mode) => mode,
+            None => {
+                eprintln!(
+                    "error: unknown present mode `{}`, expected vsync, mailbox or immediate",
+                    name
+                );
+                return ExitCode::FAILURE;
+            }
+        },
+    };
+    let mut gpu = GpuConfig::new();
+    if let Some(name) = &options.gpu_backend {
+        match GpuBackend::from_name(name) {
+            Some(backend) => gpu.backend = backend,
+            None => {
+                eprintln!(
+                    "error: unknown GPU backend `{}`, expected auto, vulkan, gl, metal or dx12",
+                    name
+                );
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    if let Some(adapter) = &options.gpu_adapter {
+        gpu.adapter = AdapterPreference::parse(adapter);
+    }
+    let config = render::WindowConfig {
+        scale: options.scale,
+        crt: options.crt,
+        crt_enabled: options.crt_enabled,
+        debugger: options.debugger,
+        letterbox: options.letterbox,
+        placement,
+        present_mode,
+        gpu,
+        record: options.record.clone(),
+        recording: options.recording,
+        cycles_per_frame: options.cycles_per_frame,
+    };
+    let mut app = render::App::new(vm, config, input);
     let _ = event_loop.run_app(&mut app);
+    if let Some(e) = app.take_error() {
+        eprintln!("error: could not open a window: {}", e);
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
+}
 
-    println!("{}", vcpu.registers[cpu::Register::R0 as usize]);
+#[cfg(not(feature = "window"))]
+fn open_window(_vm: MicroCvm, _options: &RunOptions) -> ExitCode {
+    eprintln!("error: built without the `window` feature, use --headless");
+    ExitCode::FAILURE
 }
@@ -0,0 +1,362 @@
+use std::collections::VecDeque;
+use std::io;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use ratatui::Frame;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph};
+
+use crate::cpu::HaltReason;
+use crate::debugger::{
+    Debugger, DebuggerLine, HEXDUMP_ROW_BYTES, HEXDUMP_ROWS, LineStyle, register_lines,
+};
+use crate::symbols::SymbolTable;
+use crate::trace::{TraceEntry, TraceRecord, TraceSink};
+use crate::vm::{CYCLES_PER_FRAME, MicroCvm};
+
+// Executed instructions the trace pane keeps.
+pub const TRACE_LINES: usize = 64;
+// How long the terminal is polled for a key between two frames of a running program.
+const FRAME_TIME: Duration = Duration::from_millis(16);
+
+// Darkest to brightest, for the framebuffer preview.
+const SHADES: [char; 5] = [' ', '░', '▒', '▓', '█'];
+
+const HELP: &str = "s step  c continue  p pause  b breakpoint  up/down select  \
+                    pgup/pgdn/home memory  f preview  q quit";
+
+// The keys the monitor cares about, apart from the terminal library's own key type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MonitorKey {
+    Char(char),
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    Esc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MonitorCommand {
+    Step,
+    Continue,
+    Pause,
+    // On the selected disassembly line.
+    ToggleBreakpoint,
+    CursorUp,
+    CursorDown,
+    MemoryUp,
+    MemoryDown,
+    // Moves the hexdump to the stack pointer.
+    MemoryToStack,
+    TogglePreview,
+    Quit,
+}
+
+pub fn parse_key(key: MonitorKey) -> Option<MonitorCommand> {
+    match key {
+        MonitorKey::Char('s' | ' ') => Some(MonitorCommand::Step),
+        MonitorKey::Char('c') => Some(MonitorCommand::Continue),
+        MonitorKey::Char('p') => Some(MonitorCommand::Pause),
+        MonitorKey::Char('b') => Some(MonitorCommand::ToggleBreakpoint),
+        MonitorKey::Char('k') | MonitorKey::Up => Some(MonitorCommand::CursorUp),
+        MonitorKey::Char('j') | MonitorKey::Down => Some(MonitorCommand::CursorDown),
+        MonitorKey::PageUp => Some(MonitorCommand::MemoryUp),
+        MonitorKey::PageDown => Some(MonitorCommand::MemoryDown),
+        MonitorKey::Home => Some(MonitorCommand::MemoryToStack),
+        MonitorKey::Char('f') => Some(MonitorCommand::TogglePreview),
+        MonitorKey::Char('q') | MonitorKey::Esc => Some(MonitorCommand::Quit),
+        MonitorKey::Char(_) => None,
+    }
+}
+
+// A trace sink that keeps the last TRACE_LINES instructions. Clones share them, so the
+// monitor reads what the copy installed in the machine recorded.
+#[derive(Clone, Default)]
+pub struct RecentTrace {
+    entries: Arc<Mutex<VecDeque<TraceRecord>>>,
+}
+
+impl RecentTrace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Oldest first, formatted like `--trace` prints them.
+    pub fn lines(&self, symbols: &SymbolTable) -> Vec<String> {
+        let entries = self.entries();
+        entries
+            .iter()
+            .map(|record| record.entry().to_string_with(symbols))
+            .collect()
+    }
+
+    // A panic while the lock was held can only have come between whole pushes and pops,
+    // so the entries are still good to show and the poison is ignored rather than turned
+    // into a second panic on the monitor's thread.
+    fn entries(&self) -> MutexGuard<'_, VecDeque<TraceRecord>> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl TraceSink for RecentTrace {
+    fn trace(&mut self, entry: &TraceEntry) {
+        let mut entries = self.entries();
+        if entries.len() == TRACE_LINES {
+            entries.pop_front();
+        }
+        entries.push_back(TraceRecord::from(entry));
+    }
+}
+
+// The presented frame in `columns` by `rows` characters, each shading the average brightness
+// of the pixels it covers.
+pub fn framebuffer_preview(vm: &mut MicroCvm, columns: usize, rows: usize) -> Vec<String> {
+    let (width, height) = (vm.width() as usize, vm.height() as usize);
+    if columns == 0 || rows == 0 || width == 0 || height == 0 {
+        return Vec::new();
+    }
+    let mut frame = vec![0; width * height * 4];
+    vm.frame_rgba(&mut frame);
+    // Whole pixels per cell, at least one, and the span of a cell along one axis.
+    let span = |cell: usize, cells: usize, pixels: usize| {
+        let start = cell * pixels / cells;
+        start..((cell + 1) * pixels / cells).max(start + 1).min(pixels)
+    };
+    (0..rows.min(height))
+        .map(|row| {
+            (0..columns.min(width))
+                .map(|column| {
+                    let (mut total, mut count) = (0, 0);
+                    for y in span(row, rows, height) {
+                        for x in span(column, columns, width) {
+                            let pixel = &frame[(y * width + x) * 4..][..3];
+                            let (r, g, b) = (pixel[0] as u32, pixel[1] as u32, pixel[2] as u32);
+                            total += (r * 299 + g * 587 + b * 114) / 1000;
+                            count += 1;
+                        }
+                    }
+                    // Rounded up, so a cell with anything lit in it never shows as blank.
+                    let steps = SHADES.len() as u32 - 1;
+                    SHADES[((total / count) * steps).div_ceil(255) as usize]
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// The program and what the panes show, apart from any terminal.
+pub struct Monitor {
+    pub vm: MicroCvm,
+    pub view: Debugger,
+    pub trace: RecentTrace,
+    pub symbols: SymbolTable,
+    // Set while the program runs on its own, until a breakpoint, halt, fault or pause.
+    pub running: bool,
+    pub preview: bool,
+    pub status: String,
+    // How long each frame runs for, CYCLES_PER_FRAME unless `run` was told otherwise.
+    pub cycles_per_frame: u64,
+}
+
+impl Monitor {
+    // Replaces any trace sink `vm` had with the monitor's.
+    pub fn new(mut vm: MicroCvm, symbols: SymbolTable) -> Self {
+        let trace = RecentTrace::new();
+        vm.cpu_mut().set_trace_sink(Some(Box::new(trace.clone())));
+        Self {
+            vm,
+            view: Debugger::new(),
+            trace,
+            symbols,
+            running: false,
+            preview: false,
+            status: String::from("stopped"),
+            cycles_per_frame: CYCLES_PER_FRAME,
+        }
+    }
+
+    // Returns false once the monitor should quit.
+    pub fn apply(&mut self, command: MonitorCommand) -> bool {
+        match command {
+            MonitorCommand::Step => {
+                self.running = false;
+                self.step();
+            }
+            MonitorCommand::Continue if self.vm.halted() => self.status = String::from("halted"),
+            MonitorCommand::Continue => {
+                self.running = true;
+                self.status = String::from("running");
+            }
+            MonitorCommand::Pause => {
+                self.running = false;
+                self.status = String::from("stopped");
+            }
+            MonitorCommand::ToggleBreakpoint => {
+                let addr = self.view.selected(self.vm.cpu());
+                if self.vm.remove_breakpoint(addr) {
+                    self.status = format!("removed breakpoint at {:#06x}", addr);
+                } else {
+                    self.vm.add_breakpoint(addr);
+                    self.status = format!("breakpoint at {:#06x}", addr);
+                }
+            }
+            MonitorCommand::CursorUp => self.view.move_cursor(self.vm.cpu(), -1),
+            MonitorCommand::CursorDown => self.view.move_cursor(self.vm.cpu(), 1),
+            MonitorCommand::MemoryUp => self.view.scroll_memory(-(HEXDUMP_ROWS as i16)),
+            MonitorCommand::MemoryDown => self.view.scroll_memory(HEXDUMP_ROWS as i16),
+            MonitorCommand::MemoryToStack => {
+                self.view.memory_start = self.vm.cpu().sp & !(HEXDUMP_ROW_BYTES as u16 - 1)
+            }
+            MonitorCommand::TogglePreview => self.preview = !self.preview,
+            MonitorCommand::Quit => return false,
+        }
+        true
+    }
+
+    // Runs a frame of the program while it is running, presenting it as the window would.
+    pub fn advance(&mut self) {
+        if !self.running {
+            return;
+        }
+        let pc = |vm: &MicroCvm| vm.cpu().pc;
+        self.status = match self.vm.run_frame(self.cycles_per_frame) {
+            Ok(HaltReason::FrameComplete) => {
+                self.vm.tick_frame();
+                return;
+            }
+            Ok(HaltReason::Breakpoint) => format!("break at {:#06x}", pc(&self.vm)),
+            Ok(HaltReason::Halted { code: 0 }) => format!("halted at {:#06x}", pc(&self.vm)),
+            Ok(HaltReason::Halted { code }) => {
+                format!("halted at {:#06x} with exit code {}", pc(&self.vm), code)
+            }
+            Ok(reason) => reason.to_string(),
+            Err(e) => format!("fault: {}", e.cause()),
+        };
+        self.running = false;
+    }
+
+    fn step(&mut self) {
+        if self.vm.halted() {
+            self.status = String::from("halted");
+            return;
+        }
+        self.status = match self.vm.step() {
+            Ok(()) if self.vm.halted() => format!("halted at {:#06x}", self.vm.cpu().pc),
+            Ok(()) => String::from("stopped"),
+            Err(e) => format!("fault: {}", e.cause()),
+        };
+    }
+}
+
+// Takes over the terminal until the monitor quits, and gives it back even on an error.
+pub fn run(mut monitor: Monitor) -> io::Result<()> {
+    let mut terminal = ratatui::init();
+    let result = (|| loop {
+        terminal.draw(|frame| draw(frame, &mut monitor))?;
+        if event::poll(FRAME_TIME)?
+            && let Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press
+            && let Some(command) = monitor_key(key.code).and_then(parse_key)
+            && !monitor.apply(command)
+        {
+            return Ok(());
+        }
+        monitor.advance();
+    })();
+    ratatui::restore();
+    result
+}
+
+fn monitor_key(code: KeyCode) -> Option<MonitorKey> {
+    match code {
+        KeyCode::Char(c) => Some(MonitorKey::Char(c)),
+        KeyCode::Up => Some(MonitorKey::Up),
+        KeyCode::Down => Some(MonitorKey::Down),
+        KeyCode::PageUp => Some(MonitorKey::PageUp),
+        KeyCode::PageDown => Some(MonitorKey::PageDown),
+        KeyCode::Home => Some(MonitorKey::Home),
+        KeyCode::Esc => Some(MonitorKey::Esc),
+        _ => None,
+    }
+}
+
+fn draw(frame: &mut Frame, monitor: &mut Monitor) {
+    let [main, status] =
+        Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+    let [left, right] =
+        Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(main);
+    let [registers, disassembly] =
+        Layout::vertical([Constraint::Length(5), Constraint::Min(0)]).areas(left);
+    let preview_height = if monitor.preview {
+        Constraint::Percentage(50)
+    } else {
+        Constraint::Length(0)
+    };
+    let [memory, trace, preview] = Layout::vertical([
+        Constraint::Length(HEXDUMP_ROWS as u16 + 2),
+        Constraint::Min(0),
+        preview_height,
+    ])
+    .areas(right);
+
+    let pane = |lines: Vec<DebuggerLine>, title| {
+        Paragraph::new(lines.into_iter().map(styled).collect::<Vec<_>>())
+            .block(Block::bordered().title(title))
+    };
+    let vm = &monitor.vm;
+    frame.render_widget(pane(register_lines(vm), " registers "), registers);
+    frame.render_widget(
+        pane(monitor.view.disassembly_lines(vm), " disassembly "),
+        disassembly,
+    );
+    frame.render_widget(
+        pane(
+            monitor.view.hexdump_lines(vm.cpu(), HEXDUMP_ROWS),
+            " memory ",
+        ),
+        memory,
+    );
+
+    let trace_lines = monitor.trace.lines(&monitor.symbols);
+    let shown = trace_lines
+        .len()
+        .saturating_sub(trace.height.saturating_sub(2) as usize);
+    let trace_lines: Vec<Line> = trace_lines[shown..]
+        .iter()
+        .cloned()
+        .map(Line::from)
+        .collect();
+    frame.render_widget(
+        Paragraph::new(trace_lines).block(Block::bordered().title(" trace ")),
+        trace,
+    );
+
+    if monitor.preview {
+        let inner = Block::bordered().inner(preview);
+        let lines =
+            framebuffer_preview(&mut monitor.vm, inner.width as usize, inner.height as usize);
+        let lines: Vec<Line> = lines.into_iter().map(Line::from).collect();
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(" framebuffer ")),
+            preview,
+        );
+    }
+
+    let text = format!("{}  |  {}", monitor.status, HELP);
+    frame.render_widget(Paragraph::new(text), status);
+}
+
+fn styled(line: DebuggerLine) -> Line<'static> {
+    let style = match line.style {
+        LineStyle::Normal => Style::new(),
+        LineStyle::Current => Style::new().bg(Color::Blue),
+        LineStyle::Selected => Style::new().bg(Color::DarkGray),
+    };
+    Line::styled(line.text, style)
+}
@@ -0,0 +1,108 @@
+use crate::bus::Device;
+
+pub const MOUSE_X: u8 = 0x00; // 2 bytes: pointer column in framebuffer pixels
+pub const MOUSE_Y: u8 = 0x02; // 2 bytes: pointer row
+pub const MOUSE_BUTTONS: u8 = 0x04; // one bit per held button, see MOUSE_LEFT and on
+pub const MOUSE_CONTROL: u8 = 0x05; // bit 0: relative mode, the host captures the pointer
+pub const MOUSE_DX: u8 = 0x06; // signed: motion over the last frame in relative mode, negative is left
+pub const MOUSE_DY: u8 = 0x07; // signed: negative is up
+pub const MOUSE_REGISTER_COUNT: u8 = 8;
+
+pub const MOUSE_LEFT: u8 = 0x01;
+pub const MOUSE_RIGHT: u8 = 0x02;
+pub const MOUSE_MIDDLE: u8 = 0x04;
+
+pub const MOUSE_RELATIVE: u8 = 0x01;
+
+// In absolute mode the guest sees where the pointer is over the framebuffer. In relative
+// mode the window hides and holds the pointer, so it can't hit the edge of the screen, and
+// the guest sees how far it moved over the last frame instead.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Mouse {
+    pub x: u16,
+    pub y: u16,
+    pub buttons: u8,
+    // Written by the guest, or by the host when it starts in relative mode or lets go of the
+    // pointer.
+    pub control: u8,
+    // Motion since the last frame, in framebuffer pixels, with what's left of a pixel
+    // carried over from the frame before.
+    pub motion: [f64; 2],
+    // What MOUSE_DX and MOUSE_DY show.
+    pub delta: [i8; 2],
+}
+
+impl Mouse {
+    pub fn relative(&self) -> bool {
+        self.control & MOUSE_RELATIVE != 0
+    }
+
+    pub fn button(&mut self, mask: u8, pressed: bool) {
+        if pressed {
+            self.buttons |= mask;
+        } else {
+            self.buttons &= !mask;
+        }
+    }
+
+    // Adds pointer motion the host saw, ignored outside relative mode.
+    pub fn add_motion(&mut self, dx: f64, dy: f64) {
+        if self.relative() {
+            self.motion[0] += dx;
+            self.motion[1] += dy;
+        }
+    }
+
+    // Back to absolute mode, for a host that has let go of the pointer. The guest sees bit 0
+    // of MOUSE_CONTROL clear.
+    pub fn release(&mut self) {
+        self.control &= !MOUSE_RELATIVE;
+        self.motion = [0.0; 2];
+        self.delta = [0; 2];
+    }
+
+    // Called once per frame: the frame's motion becomes the delta the guest reads.
+    pub fn end_frame(&mut self) {
+        let [x, y] = &mut self.motion;
+        self.delta = [take_delta(x), take_delta(y)];
+    }
+}
+
+// The whole pixels of `motion` as a register value, leaving the fraction in `motion` for
+// the next frame. Motion past what a byte holds saturates at -128 or 127, and the rest of
+// it is dropped rather than replayed over the frames after.
+pub fn take_delta(motion: &mut f64) -> i8 {
+    // Float to integer casts truncate towards 0 and saturate, and NaN becomes 0.
+    let whole = *motion as i8;
+    let rest = *motion - whole as f64;
+    *motion = if rest.abs() < 1.0 { rest } else { 0.0 };
+    whole
+}
+
+impl Device for Mouse {
+    fn read(&mut self, offset: u16) -> u8 {
+        self.peek(offset)
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        if offset as u8 == MOUSE_CONTROL {
+            self.control = value;
+            if !self.relative() {
+                self.release();
+            }
+        }
+    }
+
+    fn peek(&self, offset: u16) -> u8 {
+        let offset = offset as u8;
+        match offset {
+            MOUSE_X..MOUSE_Y => self.x.to_le_bytes()[(offset - MOUSE_X) as usize],
+            MOUSE_Y..MOUSE_BUTTONS => self.y.to_le_bytes()[(offset - MOUSE_Y) as usize],
+            MOUSE_BUTTONS => self.buttons,
+            MOUSE_CONTROL => self.control,
+            MOUSE_DX => self.delta[0] as u8,
+            MOUSE_DY => self.delta[1] as u8,
+            _ => 0,
+        }
+    }
+}
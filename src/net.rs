@@ -0,0 +1,121 @@
+use std::io::{self, ErrorKind};
+use std::net::{ToSocketAddrs, UdpSocket};
+
+// Command block registers, multi-byte fields little-endian.
+pub const NET_ADDR: u8 = 0x00; // 2 bytes: guest address of the packet buffer
+pub const NET_LEN: u8 = 0x02; // 2 bytes: bytes to send, or the buffer size to receive into
+pub const NET_CTRL: u8 = 0x04; // write a command, reads back the status of the last one
+pub const NET_FLAGS: u8 = 0x05; // bit 0: a datagram is waiting
+pub const NET_RECEIVED: u8 = 0x06; // 2 bytes: full length of the last received datagram
+pub const NET_REGISTER_COUNT: u8 = 8;
+
+pub const NET_SEND: u8 = 0x01;
+pub const NET_RECEIVE: u8 = 0x02;
+
+pub const NET_PACKET_READY: u8 = 0x01;
+
+// Status codes left in NET_CTRL by the last command.
+pub const NET_OK: u8 = 0;
+pub const NET_NO_PACKET: u8 = 1;
+// The datagram was longer than NET_LEN, NET_RECEIVED holds its full length.
+pub const NET_TRUNCATED: u8 = 2;
+pub const NET_BAD_BUFFER: u8 = 3;
+pub const NET_SEND_FAILED: u8 = 4;
+pub const NET_RECEIVE_FAILED: u8 = 5;
+pub const NET_BAD_COMMAND: u8 = 6;
+pub const NET_NOT_ATTACHED: u8 = 7;
+
+// The largest payload a UDP datagram can carry over IPv4.
+pub const MAX_DATAGRAM: usize = 65507;
+
+#[derive(Debug, Default)]
+pub struct UdpDevice {
+    pub addr: u16,
+    pub len: u16,
+    pub status: u8,
+    pub received: u16,
+    socket: Option<UdpSocket>,
+    buffer: Vec<u8>,
+}
+
+impl UdpDevice {
+    // Binds `local` and sends to and receives from `remote` only. The socket never blocks
+    // the CPU thread.
+    pub fn attach(
+        &mut self,
+        local: impl ToSocketAddrs,
+        remote: impl ToSocketAddrs,
+    ) -> io::Result<()> {
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(remote)?;
+        socket.set_nonblocking(true)?;
+        self.socket = Some(socket);
+        Ok(())
+    }
+
+    pub fn detach(&mut self) {
+        self.socket = None;
+    }
+
+    pub fn local_addr(&self) -> Option<std::net::SocketAddr> {
+        self.socket.as_ref()?.local_addr().ok()
+    }
+
+    pub fn read(&self, offset: u8) -> u8 {
+        match offset {
+            NET_ADDR => self.addr as u8,
+            0x01 => (self.addr >> 8) as u8,
+            NET_LEN => self.len as u8,
+            0x03 => (self.len >> 8) as u8,
+            NET_CTRL => self.status,
+            NET_FLAGS if self.packet_ready() => NET_PACKET_READY,
+            NET_RECEIVED => self.received as u8,
+            0x07 => (self.received >> 8) as u8,
+            _ => 0,
+        }
+    }
+
+    // Returns the command when the write issues one.
+    pub fn write(&mut self, offset: u8, value: u8) -> Option<u8> {
+        match offset {
+            NET_ADDR => self.addr = (self.addr & 0xFF00) | value as u16,
+            0x01 => self.addr = (self.addr & 0x00FF) | (value as u16) << 8,
+            NET_LEN => self.len = (self.len & 0xFF00) | value as u16,
+            0x03 => self.len = (self.len & 0x00FF) | (value as u16) << 8,
+            NET_CTRL => return Some(value),
+            _ => {}
+        }
+        None
+    }
+
+    fn packet_ready(&self) -> bool {
+        let Some(socket) = &self.socket else {
+            return false;
+        };
+        // Peeking a single byte is enough to see whether anything is queued.
+        socket.peek(&mut [0; 1]).is_ok()
+    }
+
+    pub fn send(&self, payload: &[u8]) -> u8 {
+        let Some(socket) = &self.socket else {
+            return NET_NOT_ATTACHED;
+        };
+        match socket.send(payload) {
+            Ok(_) => NET_OK,
+            Err(_) => NET_SEND_FAILED,
+        }
+    }
+
+    // Receives the next datagram into the device's own buffer, or the status to report.
+    pub fn receive(&mut self) -> Result<&[u8], u8> {
+        let Some(socket) = &self.socket else {
+            return Err(NET_NOT_ATTACHED);
+        };
+        self.buffer.resize(MAX_DATAGRAM, 0);
+        match socket.recv(&mut self.buffer) {
+            Ok(len) => Ok(&self.buffer[..len]),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => Err(NET_NO_PACKET),
+            Err(_) => Err(NET_RECEIVE_FAILED),
+        }
+    }
+}
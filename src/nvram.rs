@@ -0,0 +1,151 @@
+use core::fmt::Display;
+use core::ops::Range;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+pub const NVRAM_CTRL: u8 = 0x00; // any write commits, reads back the status of the last commit
+pub const NVRAM_START: u8 = 0x01; // 2 bytes: guest address of the region
+pub const NVRAM_LEN: u8 = 0x03; // 2 bytes: size of the region, 0 when none is attached
+pub const NVRAM_REGISTER_COUNT: u8 = 5;
+
+// Status codes left in NVRAM_CTRL by the last commit.
+pub const NVRAM_OK: u8 = 0;
+pub const NVRAM_COMMIT_FAILED: u8 = 1;
+
+// Just below the bank window, out of the way of programs loaded at 0 and of the stack.
+pub const DEFAULT_NVRAM_START: u16 = 0x3F00;
+pub const DEFAULT_NVRAM_LEN: u16 = 256;
+
+// File layout: magic, region length (u16 LE), the contents, then a checksum of the
+// contents (u32 LE).
+const MAGIC: [u8; 4] = *b"MCNV";
+const HEADER_LEN: usize = MAGIC.len() + 2;
+const CHECKSUM_LEN: usize = 4;
+
+// Why a saved file was ignored. The region starts out zeroed instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NvramWarning {
+    BadMagic,
+    WrongSize { expected: usize, found: usize },
+    BadLength,
+    BadChecksum,
+}
+
+// A region of memory that is saved to a host file, so its contents survive from one run
+// to the next.
+#[derive(Debug)]
+pub struct Nvram {
+    pub path: PathBuf,
+    pub start: u16,
+    pub len: u16,
+    pub status: u8,
+    // Physical memory behind the region, fixed when it is attached.
+    pub(crate) physical: Range<usize>,
+}
+
+impl Nvram {
+    pub(crate) fn new(path: PathBuf, start: u16, len: u16, physical: Range<usize>) -> Self {
+        Self {
+            path,
+            start,
+            len,
+            status: NVRAM_OK,
+            physical,
+        }
+    }
+
+    // The saved contents of a `len` byte region. A missing file is a fresh, zeroed region;
+    // a damaged one is zeroed too, with the reason.
+    pub fn load(path: &Path, len: usize) -> io::Result<(Vec<u8>, Option<NvramWarning>)> {
+        let bytes = match fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok((vec![0; len], None)),
+            Err(e) => return Err(e),
+        };
+        Ok(match decode(&bytes, len) {
+            Ok(contents) => (contents, None),
+            Err(warning) => (vec![0; len], Some(warning)),
+        })
+    }
+
+    // Writes to a temporary file first, so a crash mid-save leaves the old contents intact.
+    pub fn save(&self, contents: &[u8]) -> io::Result<()> {
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+        fs::write(&temporary, encode(contents))?;
+        fs::rename(&temporary, &self.path)
+    }
+
+    pub fn read(&self, offset: u8) -> u8 {
+        match offset {
+            NVRAM_CTRL => self.status,
+            NVRAM_START => self.start as u8,
+            0x02 => (self.start >> 8) as u8,
+            NVRAM_LEN => self.len as u8,
+            0x04 => (self.len >> 8) as u8,
+            _ => 0,
+        }
+    }
+
+    // Returns true when the write asks for a commit.
+    pub fn write(&mut self, offset: u8, _value: u8) -> bool {
+        offset == NVRAM_CTRL
+    }
+}
+
+fn encode(contents: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER_LEN + contents.len() + CHECKSUM_LEN);
+    bytes.extend_from_slice(&MAGIC);
+    bytes.extend_from_slice(&(contents.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(contents);
+    bytes.extend_from_slice(&checksum(contents).to_le_bytes());
+    bytes
+}
+
+fn decode(bytes: &[u8], len: usize) -> Result<Vec<u8>, NvramWarning> {
+    if bytes.len() < HEADER_LEN || bytes[..MAGIC.len()] != MAGIC {
+        return Err(NvramWarning::BadMagic);
+    }
+    let stored = u16::from_le_bytes([bytes[4], bytes[5]]) as usize;
+    if stored != len {
+        return Err(NvramWarning::WrongSize {
+            expected: len,
+            found: stored,
+        });
+    }
+    if bytes.len() != HEADER_LEN + len + CHECKSUM_LEN {
+        return Err(NvramWarning::BadLength);
+    }
+    let (contents, sum) = bytes[HEADER_LEN..].split_at(len);
+    if checksum(contents).to_le_bytes() != sum {
+        return Err(NvramWarning::BadChecksum);
+    }
+    Ok(contents.to_vec())
+}
+
+// 32-bit FNV-1a.
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash, &byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+impl Display for NvramWarning {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            NvramWarning::BadMagic => write!(f, "not an NVRAM file, starting from zeroes"),
+            NvramWarning::WrongSize { expected, found } => write!(
+                f,
+                "NVRAM file holds {} bytes but the region is {}, starting from zeroes",
+                found, expected
+            ),
+            NvramWarning::BadLength => {
+                write!(f, "NVRAM file is truncated or padded, starting from zeroes")
+            }
+            NvramWarning::BadChecksum => {
+                write!(f, "NVRAM file is corrupted, starting from zeroes")
+            }
+        }
+    }
+}
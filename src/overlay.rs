@@ -0,0 +1,76 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::cpu::{RegisterWidth, flag_letters};
+use crate::font::{self, GLYPH_SIZE};
+
+// Pixels of darkened background around the text.
+const OVERLAY_PADDING: usize = 2;
+const WHITE: [u8; 4] = [255, 255, 255, 255];
+
+// What the debug overlay shows, copied out of the machine once a frame so drawing it
+// never has to look at the CPU. The host measures the two rates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DebugStats {
+    pub pc: u16,
+    pub sp: u16,
+    pub registers: [u16; 8],
+    pub register_width: RegisterWidth,
+    pub flags: u8,
+    pub instructions_per_second: u64,
+    pub frames_per_second: u32,
+}
+
+// The overlay text, one string per line. Flags show as their letter when set and '-' when
+// clear, registers in as many hex digits as they hold.
+pub fn layout(stats: &DebugStats) -> Vec<String> {
+    let digits = match stats.register_width {
+        RegisterWidth::Eight => 2,
+        RegisterWidth::Sixteen => 4,
+    };
+    let flags = flag_letters(stats.flags);
+    let registers = |range: core::ops::Range<usize>| {
+        range
+            .map(|i| format!("R{} {:0digits$X}", i, stats.registers[i]))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    Vec::from([
+        format!("PC {:04X} SP {:04X} {}", stats.pc, stats.sp, flags),
+        registers(0..4),
+        registers(4..8),
+        format!(
+            "IPS {} FPS {}",
+            stats.instructions_per_second, stats.frames_per_second
+        ),
+    ])
+}
+
+// Draws `lines` in white at the top left of `rgba`, a frame `width` pixels wide, over a
+// darkened box. Whatever doesn't fit is clipped.
+pub fn draw_text(lines: &[String], width: usize, rgba: &mut [u8]) {
+    if width == 0 {
+        return;
+    }
+    let height = rgba.len() / 4 / width;
+    let columns = lines
+        .iter()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0);
+    let box_width = (columns * GLYPH_SIZE + 2 * OVERLAY_PADDING).min(width);
+    let box_height = (lines.len() * GLYPH_SIZE + 2 * OVERLAY_PADDING).min(height);
+    for y in 0..box_height {
+        for pixel in rgba[y * width * 4..][..box_width * 4].chunks_exact_mut(4) {
+            for channel in &mut pixel[..3] {
+                *channel /= 4;
+            }
+        }
+    }
+
+    for (row, line) in lines.iter().enumerate() {
+        let top = OVERLAY_PADDING + row * GLYPH_SIZE;
+        font::draw_text(line, OVERLAY_PADDING, top, WHITE, width, rgba);
+    }
+}
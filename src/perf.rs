@@ -0,0 +1,44 @@
+// Counters a guest reads to time itself. Each field is 4 little-endian bytes, and loading its
+// low byte latches all four, so reading the rest after it never mixes two values.
+pub const PERF_CYCLES: u8 = 0x00; // low 32 bits of the cycle count
+pub const PERF_INSTRUCTIONS: u8 = 0x04; // low 32 bits of the instructions executed
+pub const PERF_DELTA: u8 = 0x08; // cycles since the last load of this field's low byte
+pub const PERF_REGISTER_COUNT: u8 = 12;
+
+const FIELD_BYTES: u8 = 4;
+
+// Read only, writes are dropped. Only the cycle and instruction counts go in, so a
+// deterministic run reads the same values every time.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PerfCounters {
+    // What each field reads until its low byte is loaded again.
+    pub latched: [u32; 3],
+    // The cycle count PERF_DELTA was last latched at.
+    pub delta_from: u64,
+}
+
+impl PerfCounters {
+    pub fn read(&mut self, offset: u8, cycles: u64, instructions: u64) -> u8 {
+        let (field, byte) = ((offset / FIELD_BYTES) as usize, offset % FIELD_BYTES);
+        if byte == 0 {
+            self.latched[field] = match offset {
+                PERF_CYCLES => cycles as u32,
+                PERF_INSTRUCTIONS => instructions as u32,
+                _ => {
+                    let delta = cycles - self.delta_from;
+                    self.delta_from = cycles;
+                    delta.min(u32::MAX as u64) as u32
+                }
+            };
+        }
+        self.peek(offset)
+    }
+
+    // The latched byte, for debuggers and hosts looking at memory.
+    pub fn peek(&self, offset: u8) -> u8 {
+        let (field, byte) = ((offset / FIELD_BYTES) as usize, offset % FIELD_BYTES);
+        self.latched
+            .get(field)
+            .map_or(0, |value| (value >> (byte * 8)) as u8)
+    }
+}
@@ -0,0 +1,496 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::crc32::crc32;
+
+pub const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+// 8-bit RGBA pixels, row by row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PngError {
+    NotPng,
+    BadCrc { chunk: [u8; 4] },
+    // A valid PNG this reader doesn't handle, such as 16-bit or interlaced.
+    Unsupported(&'static str),
+    Corrupt(&'static str),
+}
+
+const COLOR_RGB: u8 = 2;
+const COLOR_RGBA: u8 = 6;
+
+// An RGBA image as a PNG. Without the flate2 feature the image data is stored rather than
+// compressed, which any reader takes but makes files about as big as the pixels.
+pub fn encode(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+    let row_len = width as usize * 4;
+    // Each row starts with its filter type, 0 for none.
+    let mut raw = Vec::with_capacity((row_len + 1) * height as usize);
+    for row in rgba.chunks_exact(row_len.max(1)).take(height as usize) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&width.to_be_bytes());
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    // Bit depth, color type, compression, filter and interlace methods.
+    ihdr.extend_from_slice(&[8, COLOR_RGBA, 0, 0, 0]);
+
+    let mut png = SIGNATURE.to_vec();
+    write_chunk(&mut png, *b"IHDR", &ihdr);
+    write_chunk(&mut png, *b"IDAT", &zlib(&raw));
+    write_chunk(&mut png, *b"IEND", &[]);
+    png
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Image, PngError> {
+    let Some(mut rest) = bytes.strip_prefix(&SIGNATURE) else {
+        return Err(PngError::NotPng);
+    };
+    let mut header = None;
+    let mut data = Vec::new();
+    loop {
+        let Some((length, after)) = rest.split_first_chunk::<4>() else {
+            return Err(PngError::Corrupt("a chunk is cut short"));
+        };
+        let length = u32::from_be_bytes(*length) as usize;
+        let Some((body, after)) = after.split_at_checked(4 + length) else {
+            return Err(PngError::Corrupt("a chunk is cut short"));
+        };
+        let Some((crc, after)) = after.split_first_chunk::<4>() else {
+            return Err(PngError::Corrupt("a chunk is cut short"));
+        };
+        let kind: [u8; 4] = body[..4].try_into().unwrap();
+        if crc32(body) != u32::from_be_bytes(*crc) {
+            return Err(PngError::BadCrc { chunk: kind });
+        }
+        let body = &body[4..];
+        rest = after;
+        match &kind {
+            b"IHDR" => header = Some(parse_header(body)?),
+            b"IDAT" => data.extend_from_slice(body),
+            b"IEND" => break,
+            // Lowercase first letters are chunks a reader may skip.
+            _ if kind[0].is_ascii_lowercase() => {}
+            _ => {
+                return Err(PngError::Unsupported(
+                    "a critical chunk other than the basic ones",
+                ));
+            }
+        }
+    }
+    let Some((width, height, channels)) = header else {
+        return Err(PngError::Corrupt("there is no IHDR chunk"));
+    };
+
+    let raw = unzlib(&data)?;
+    let row_len = width as usize * channels;
+    if raw.len() != (row_len + 1) * height as usize {
+        return Err(PngError::Corrupt("the image data is the wrong size"));
+    }
+    let mut pixels = vec![0u8; row_len * height as usize];
+    for (y, line) in raw.chunks_exact(row_len + 1).enumerate() {
+        let (before, row) = pixels.split_at_mut(y * row_len);
+        let previous = before
+            .get(before.len().saturating_sub(row_len)..)
+            .filter(|_| y > 0);
+        unfilter(line[0], &line[1..], previous, channels, &mut row[..row_len])?;
+    }
+
+    let rgba = if channels == 4 {
+        pixels
+    } else {
+        pixels
+            .chunks_exact(3)
+            .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+            .collect()
+    };
+    Ok(Image {
+        width,
+        height,
+        rgba,
+    })
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: [u8; 4], body: &[u8]) {
+    png.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(&kind);
+    png.extend_from_slice(body);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+// The width, height and bytes per pixel of an 8-bit RGB or RGBA image.
+fn parse_header(body: &[u8]) -> Result<(u32, u32, usize), PngError> {
+    let &[depth, color, compression, filter, interlace] = body.get(8..).unwrap_or_default() else {
+        return Err(PngError::Corrupt("IHDR is the wrong size"));
+    };
+    let width = u32::from_be_bytes(body[..4].try_into().unwrap());
+    let height = u32::from_be_bytes(body[4..8].try_into().unwrap());
+    let channels = match (depth, color) {
+        (8, COLOR_RGB) => 3,
+        (8, COLOR_RGBA) => 4,
+        _ => {
+            return Err(PngError::Unsupported(
+                "only 8-bit RGB and RGBA images are read",
+            ));
+        }
+    };
+    if compression != 0 || filter != 0 {
+        return Err(PngError::Corrupt("unknown compression or filter method"));
+    }
+    if interlace != 0 {
+        return Err(PngError::Unsupported("interlaced images are not read"));
+    }
+    Ok((width, height, channels))
+}
+
+// Undoes a row's filter into `row`, `previous` being the row above already undone.
+fn unfilter(
+    filter: u8,
+    line: &[u8],
+    previous: Option<&[u8]>,
+    channels: usize,
+    row: &mut [u8],
+) -> Result<(), PngError> {
+    for x in 0..line.len() {
+        let left = if x >= channels { row[x - channels] } else { 0 };
+        let up = previous.map_or(0, |previous| previous[x]);
+        let up_left = match previous {
+            Some(previous) if x >= channels => previous[x - channels],
+            _ => 0,
+        };
+        let predicted = match filter {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => ((left as u16 + up as u16) / 2) as u8,
+            4 => paeth(left, up, up_left),
+            _ => return Err(PngError::Corrupt("unknown row filter")),
+        };
+        row[x] = line[x].wrapping_add(predicted);
+    }
+    Ok(())
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - up_left as i16;
+    let (to_left, to_up, to_up_left) = (
+        (estimate - left as i16).abs(),
+        (estimate - up as i16).abs(),
+        (estimate - up_left as i16).abs(),
+    );
+    if to_left <= to_up && to_left <= to_up_left {
+        left
+    } else if to_up <= to_up_left {
+        up
+    } else {
+        up_left
+    }
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in bytes.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(feature = "flate2")]
+fn zlib(raw: &[u8]) -> Vec<u8> {
+    use std::io::Write;
+
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+    encoder.write_all(raw).expect("writing to a Vec can't fail");
+    encoder.finish().expect("writing to a Vec can't fail")
+}
+
+// A zlib stream of stored deflate blocks.
+#[cfg(not(feature = "flate2"))]
+fn zlib(raw: &[u8]) -> Vec<u8> {
+    const BLOCK: usize = 0xFFFF;
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(BLOCK).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        out.push(blocks.peek().is_none() as u8);
+        let length = block.len() as u16;
+        out.extend_from_slice(&length.to_le_bytes());
+        out.extend_from_slice(&(!length).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&adler32(raw).to_be_bytes());
+    out
+}
+
+fn unzlib(stream: &[u8]) -> Result<Vec<u8>, PngError> {
+    let [method, flags, ..] = *stream else {
+        return Err(PngError::Corrupt("the image data is cut short"));
+    };
+    if method & 0x0F != 8 || (u16::from_be_bytes([method, flags]) % 31) != 0 {
+        return Err(PngError::Corrupt("the image data is not a zlib stream"));
+    }
+    if flags & 0x20 != 0 {
+        return Err(PngError::Unsupported("zlib preset dictionaries"));
+    }
+    let mut bits = Bits::new(&stream[2..]);
+    let raw = inflate(&mut bits)?;
+    let checksum = bits.aligned_rest();
+    if checksum.get(..4) != Some(&adler32(&raw).to_be_bytes()[..]) {
+        return Err(PngError::Corrupt("the image data fails its checksum"));
+    }
+    Ok(raw)
+}
+
+// Deflate's bit reader, least significant bit first.
+struct Bits<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl<'a> Bits<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            position: 0,
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    fn take(&mut self, count: u32) -> Result<u32, PngError> {
+        while self.count < count {
+            let Some(&byte) = self.bytes.get(self.position) else {
+                return Err(PngError::Corrupt("the image data is cut short"));
+            };
+            self.position += 1;
+            self.buffer |= (byte as u32) << self.count;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1u64 << count) - 1) as u32;
+        self.buffer >>= count;
+        self.count -= count;
+        Ok(value)
+    }
+
+    // Drops the bits left of the current byte and returns the bytes after it.
+    fn aligned_rest(&mut self) -> &'a [u8] {
+        self.buffer = 0;
+        self.count = 0;
+        &self.bytes[self.position..]
+    }
+}
+
+// A canonical Huffman code: how many codes there are of each length, and the symbols in
+// code order.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0u16; 16];
+        for length in 1..16 {
+            offsets[length] = offsets[length - 1] + counts[length - 1];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        Self { counts, symbols }
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u16, PngError> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.counts[1..] {
+            code |= bits.take(1)? as i32;
+            let count = count as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(PngError::Corrupt("the image data has an invalid code"))
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+// The order code length code lengths come in, in a dynamic block's header.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+fn inflate(bits: &mut Bits) -> Result<Vec<u8>, PngError> {
+    let mut out = Vec::new();
+    loop {
+        let last = bits.take(1)? == 1;
+        match bits.take(2)? {
+            0 => {
+                let rest = bits.aligned_rest();
+                let Some((header, rest)) = rest.split_first_chunk::<4>() else {
+                    return Err(PngError::Corrupt("the image data is cut short"));
+                };
+                let length = u16::from_le_bytes([header[0], header[1]]);
+                if !length != u16::from_le_bytes([header[2], header[3]]) {
+                    return Err(PngError::Corrupt("a stored block's length is damaged"));
+                }
+                let Some(block) = rest.get(..length as usize) else {
+                    return Err(PngError::Corrupt("the image data is cut short"));
+                };
+                out.extend_from_slice(block);
+                bits.position += 4 + length as usize;
+            }
+            1 => {
+                let mut lengths = [0u8; 288 + 30];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..288].fill(8);
+                lengths[288..].fill(5);
+                let (literals, distances) = lengths.split_at(288);
+                inflate_block(
+                    bits,
+                    &Huffman::new(literals),
+                    &Huffman::new(distances),
+                    &mut out,
+                )?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(bits)?;
+                inflate_block(bits, &literals, &distances, &mut out)?;
+            }
+            _ => return Err(PngError::Corrupt("the image data has an invalid block")),
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+fn dynamic_codes(bits: &mut Bits) -> Result<(Huffman, Huffman), PngError> {
+    let literal_count = bits.take(5)? as usize + 257;
+    let distance_count = bits.take(5)? as usize + 1;
+    let code_length_count = bits.take(4)? as usize + 4;
+    let mut code_lengths = [0u8; 19];
+    for &index in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[index] = bits.take(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths);
+
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let (length, repeat) = match code_lengths.decode(bits)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                let Some(&previous) = lengths.last() else {
+                    return Err(PngError::Corrupt("the image data repeats a missing length"));
+                };
+                (previous, 3 + bits.take(2)?)
+            }
+            17 => (0, 3 + bits.take(3)?),
+            _ => (0, 11 + bits.take(7)?),
+        };
+        lengths.extend(core::iter::repeat_n(length, repeat as usize));
+    }
+    if lengths.len() > literal_count + distance_count {
+        return Err(PngError::Corrupt(
+            "the image data has too many code lengths",
+        ));
+    }
+    let (literals, distances) = lengths.split_at(literal_count);
+    Ok((Huffman::new(literals), Huffman::new(distances)))
+}
+
+fn inflate_block(
+    bits: &mut Bits,
+    literals: &Huffman,
+    distances: &Huffman,
+    out: &mut Vec<u8>,
+) -> Result<(), PngError> {
+    loop {
+        let symbol = literals.decode(bits)? as usize;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let index = symbol - 257;
+                if index >= LENGTH_BASE.len() {
+                    return Err(PngError::Corrupt("the image data has an invalid length"));
+                }
+                let length =
+                    LENGTH_BASE[index] as usize + bits.take(LENGTH_EXTRA[index] as u32)? as usize;
+                let index = distances.decode(bits)? as usize;
+                if index >= DISTANCE_BASE.len() {
+                    return Err(PngError::Corrupt("the image data has an invalid distance"));
+                }
+                let distance = DISTANCE_BASE[index] as usize
+                    + bits.take(DISTANCE_EXTRA[index] as u32)? as usize;
+                if distance > out.len() {
+                    return Err(PngError::Corrupt("the image data refers back too far"));
+                }
+                // Copies can overlap what they write, so they go a byte at a time.
+                let start = out.len() - distance;
+                for offset in 0..length {
+                    out.push(out[start + offset]);
+                }
+            }
+        }
+    }
+}
+
+impl core::fmt::Display for PngError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            PngError::NotPng => write!(f, "not a PNG file"),
+            PngError::BadCrc { chunk } => write!(
+                f,
+                "the {} chunk fails its CRC",
+                core::str::from_utf8(chunk).unwrap_or("unnamed")
+            ),
+            PngError::Unsupported(what) => write!(f, "unsupported PNG: {}", what),
+            PngError::Corrupt(what) => write!(f, "corrupt PNG: {}", what),
+        }
+    }
+}
@@ -0,0 +1,264 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Display;
+
+use crate::cpu::{MicroCVMCpu, OpcodeType};
+use crate::disasm::{decode, format_instruction};
+use crate::isa;
+use crate::symbols::SymbolTable;
+
+// Bumped whenever a field of ProfileExport or the structs in it changes.
+pub const PROFILE_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProfileEntry {
+    pub count: u64,
+    pub cycles: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct PcEntry {
+    stats: ProfileEntry,
+    // The opcode last executed here. Only self-modifying code can change it.
+    opcode: u8,
+}
+
+// One slot per guest address, so recording an instruction is a single index.
+pub struct Profiler {
+    by_pc: Vec<PcEntry>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProfileReport {
+    pub total: ProfileEntry,
+    // Both sorted by cycles, most expensive first.
+    pub by_opcode: Vec<(OpcodeType, ProfileEntry)>,
+    pub by_pc: Vec<(u16, ProfileEntry)>,
+}
+
+// A ProfileReport with the hotspots described, as `to_json` writes it. Extension
+// instructions have no opcode entry, so only with none of them run do the opcode counts
+// add up to the total.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProfileExport {
+    // PROFILE_SCHEMA_VERSION.
+    pub version: u32,
+    // Instructions executed and the cycles they took.
+    pub total: ProfileEntry,
+    pub opcodes: Vec<OpcodeProfile>,
+    pub hotspots: Vec<Hotspot>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
+pub struct OpcodeProfile {
+    // The opcode byte tells apart instructions that share a mnemonic, the forms of `load`.
+    pub opcode: u8,
+    pub mnemonic: String,
+    pub count: u64,
+    pub cycles: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
+pub struct Hotspot {
+    pub pc: u16,
+    pub count: u64,
+    pub cycles: u64,
+    // What is at `pc` now, None if it doesn't decode.
+    pub instruction: Option<String>,
+    // The label at `pc`, if the symbols name it.
+    pub symbol: Option<String>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self {
+            by_pc: vec![PcEntry::default(); 0x10000],
+        }
+    }
+
+    pub fn record(&mut self, pc: u16, opcode: u8, cycles: u64) {
+        let entry = &mut self.by_pc[pc as usize];
+        entry.stats.count += 1;
+        entry.stats.cycles += cycles;
+        entry.opcode = opcode;
+    }
+
+    pub fn reset(&mut self) {
+        self.by_pc.fill(PcEntry::default());
+    }
+
+    pub fn report(&self) -> ProfileReport {
+        let mut total = ProfileEntry::default();
+        let mut opcodes = [ProfileEntry::default(); 256];
+        let mut by_pc = Vec::new();
+
+        for (pc, entry) in self.by_pc.iter().enumerate() {
+            if entry.stats.count == 0 {
+                continue;
+            }
+            total.count += entry.stats.count;
+            total.cycles += entry.stats.cycles;
+            let opcode = &mut opcodes[entry.opcode as usize];
+            opcode.count += entry.stats.count;
+            opcode.cycles += entry.stats.cycles;
+            by_pc.push((pc as u16, entry.stats));
+        }
+
+        let mut by_opcode: Vec<_> = opcodes
+            .iter()
+            .enumerate()
+            .filter(|(_, stats)| stats.count > 0)
+            .filter_map(|(byte, stats)| Some((OpcodeType::try_from(byte as u8).ok()?, *stats)))
+            .collect();
+        by_opcode.sort_by_key(|(_, stats)| core::cmp::Reverse(stats.cycles));
+        by_pc.sort_by(|a, b| b.1.cycles.cmp(&a.1.cycles).then(a.0.cmp(&b.0)));
+
+        ProfileReport {
+            total,
+            by_opcode,
+            by_pc,
+        }
+    }
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProfileReport {
+    pub fn top_hotspots(&self, n: usize) -> &[(u16, ProfileEntry)] {
+        &self.by_pc[..n.min(self.by_pc.len())]
+    }
+
+    // The report with its `top` most expensive addresses disassembled from `cpu`'s memory.
+    pub fn export(&self, cpu: &MicroCVMCpu, symbols: &SymbolTable, top: usize) -> ProfileExport {
+        let opcodes = self
+            .by_opcode
+            .iter()
+            .map(|(opcode, stats)| OpcodeProfile {
+                opcode: *opcode as u8,
+                mnemonic: opcode.mnemonic().to_string(),
+                count: stats.count,
+                cycles: stats.cycles,
+            })
+            .collect();
+        let hotspots = self
+            .top_hotspots(top)
+            .iter()
+            .map(|(pc, stats)| {
+                let bytes: Vec<u8> = (0..isa::MAX_LENGTH)
+                    .map_while(|offset| cpu.read_mem(pc.wrapping_add(offset)).ok())
+                    .collect();
+                Hotspot {
+                    pc: *pc,
+                    count: stats.count,
+                    cycles: stats.cycles,
+                    instruction: decode(&bytes, cpu.register_width)
+                        .map(|opcode| format_instruction(&opcode, *pc, symbols)),
+                    symbol: symbols.name(*pc).map(str::to_string),
+                }
+            })
+            .collect();
+        ProfileExport {
+            version: PROFILE_SCHEMA_VERSION,
+            total: self.total,
+            opcodes,
+            hotspots,
+        }
+    }
+
+    /// The report as JSON, in the schema [`ProfileExport`] describes, with the `top` most
+    /// expensive addresses disassembled from `cpu`'s memory and named from `symbols`.
+    ///
+    /// ```
+    /// use microcvm_rs::MicroCvm;
+    /// use microcvm_rs::asm::assemble_with_symbols;
+    /// use microcvm_rs::profile::ProfileExport;
+    ///
+    /// let (program, symbols) = assemble_with_symbols("
+    ///         mov r4, 100
+    /// spin:   djnz r4, spin
+    ///         hlt
+    /// ").unwrap();
+    /// let mut vm = MicroCvm::builder().build();
+    /// vm.load_program(&program).unwrap();
+    /// vm.cpu_mut().enable_profiler();
+    /// vm.run().unwrap();
+    /// let report = vm.cpu().profiler().unwrap().report();
+    ///
+    /// let json = report.to_json(vm.cpu(), &symbols, 1);
+    /// let export: ProfileExport = serde_json::from_str(&json).unwrap();
+    /// assert_eq!(export.total.count, 102);
+    /// assert_eq!(export.opcodes[0].mnemonic, "djnz");
+    /// let hotspot = &export.hotspots[0];
+    /// assert_eq!((hotspot.pc, hotspot.count), (0x0003, 100));
+    /// assert_eq!(hotspot.instruction.as_deref(), Some("djnz r4, spin"));
+    /// assert_eq!(hotspot.symbol.as_deref(), Some("spin"));
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn to_json(&self, cpu: &MicroCVMCpu, symbols: &SymbolTable, top: usize) -> String {
+        serde_json::to_string_pretty(&self.export(cpu, symbols, top))
+            .expect("a profile always serializes")
+    }
+}
+
+// How many addresses the Display impl lists under the per-opcode table.
+const DISPLAY_HOTSPOTS: usize = 10;
+
+impl Display for ProfileReport {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let share = |cycles: u64| {
+            if self.total.cycles == 0 {
+                0.0
+            } else {
+                cycles as f64 * 100.0 / self.total.cycles as f64
+            }
+        };
+
+        write!(
+            f,
+            "{:<8} {:>12} {:>14} {:>7}",
+            "opcode", "count", "cycles", "cycles%"
+        )?;
+        for (opcode, stats) in &self.by_opcode {
+            write!(
+                f,
+                "\n{:<8} {:>12} {:>14} {:>6.1}%",
+                opcode.mnemonic(),
+                stats.count,
+                stats.cycles,
+                share(stats.cycles)
+            )?;
+        }
+        write!(
+            f,
+            "\n{:<8} {:>12} {:>14}",
+            "total", self.total.count, self.total.cycles
+        )?;
+
+        write!(
+            f,
+            "\n\n{:<8} {:>12} {:>14} {:>7}",
+            "pc", "count", "cycles", "cycles%"
+        )?;
+        for (pc, stats) in self.top_hotspots(DISPLAY_HOTSPOTS) {
+            write!(
+                f,
+                "\n{:<8} {:>12} {:>14} {:>6.1}%",
+                format!("{:#06x}", pc),
+                stats.count,
+                stats.cycles,
+                share(stats.cycles)
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,385 @@
+use crate::crc32::crc32;
+use crate::error::VmError;
+use crate::segment::{Segment, SegmentKind, SegmentedProgram};
+use alloc::vec::Vec;
+#[cfg(feature = "flate2")]
+use flate2::{read::GzDecoder, write::GzEncoder};
+
+pub const MAGIC: [u8; 4] = *b"MCVM";
+pub const VERSION: u8 = 3;
+pub const HEADER_LEN: usize = 18;
+// Older headers stop before the fields later versions added: version 1 before the
+// checksum, version 2 before the entry point.
+pub const HEADER_LEN_V1: usize = 12;
+pub const HEADER_LEN_V2: usize = 16;
+
+pub const FLAG_PROTECT_CODE: u8 = 0x01;
+// The payload is the length it decompresses to (u32 LE) followed by a gzip stream.
+pub const FLAG_COMPRESSED: u8 = 0x02;
+pub const COMPRESSED_PREFIX_LEN: usize = 4;
+// The payload is a segment table, see `segment`, and the load address is where execution
+// starts.
+pub const FLAG_SEGMENTED: u8 = 0x04;
+// A segmented program's data and bss segments can't be executed.
+pub const FLAG_NO_EXECUTE_DATA: u8 = 0x08;
+
+// Layout: magic (4) | version (1) | flags (1) | load address (u16 LE) | payload length (u32 LE)
+// | CRC-32 of the payload (u32 LE, from version 2 on) | entry point (u16 LE, from version 3 on)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProgramHeader {
+    pub version: u8,
+    pub flags: u8,
+    pub load_address: u16,
+    pub length: u32,
+    // None for a version 1 header, which has nothing to check the payload against.
+    pub checksum: Option<u32>,
+    // Where execution starts, which loading writes into pc. None before version 3, which
+    // leaves pc alone.
+    pub entry: Option<u16>,
+}
+
+pub struct Program {
+    pub header: ProgramHeader,
+    pub payload: Vec<u8>,
+}
+
+impl ProgramHeader {
+    pub fn parse(bytes: &[u8]) -> Result<Self, VmError> {
+        if bytes.len() < HEADER_LEN_V1 {
+            return Err(VmError::InvalidHeader {
+                reason: "file is shorter than the header",
+            });
+        }
+        if bytes[0..4] != MAGIC {
+            return Err(VmError::InvalidHeader {
+                reason: "missing MCVM magic",
+            });
+        }
+        let version = bytes[4];
+        if !(1..=VERSION).contains(&version) {
+            return Err(VmError::InvalidHeader {
+                reason: "unsupported version",
+            });
+        }
+        if bytes.len() < header_len(version) {
+            return Err(VmError::InvalidHeader {
+                reason: "file is shorter than the header",
+            });
+        }
+
+        Ok(Self {
+            version,
+            flags: bytes[5],
+            load_address: u16::from_le_bytes([bytes[6], bytes[7]]),
+            length: u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]),
+            checksum: (version >= 2)
+                .then(|| u32::from_le_bytes([bytes[12], bytes[13], bytes[14], bytes[15]])),
+            entry: (version >= 3).then(|| u16::from_le_bytes([bytes[16], bytes[17]])),
+        })
+    }
+
+    // Always the current version, with the checksum or 0 if there is none and the entry
+    // point or the load address.
+    pub fn to_bytes(&self) -> [u8; HEADER_LEN] {
+        let mut bytes = [0; HEADER_LEN];
+        bytes[0..4].copy_from_slice(&MAGIC);
+        bytes[4] = VERSION;
+        bytes[5] = self.flags;
+        bytes[6..8].copy_from_slice(&self.load_address.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.length.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.checksum.unwrap_or(0).to_le_bytes());
+        let entry = self.entry.unwrap_or(self.load_address);
+        bytes[16..18].copy_from_slice(&entry.to_le_bytes());
+        bytes
+    }
+
+    // How many bytes of the file the header takes up, which depends on its version.
+    pub fn size(&self) -> usize {
+        header_len(self.version)
+    }
+
+    // Checks `payload` against the header's checksum. A version 1 header passes anything.
+    pub fn verify(&self, payload: &[u8]) -> Result<(), VmError> {
+        let Some(expected) = self.checksum else {
+            return Ok(());
+        };
+        let actual = crc32(payload);
+        if actual != expected {
+            return Err(VmError::ChecksumMismatch { expected, actual });
+        }
+        Ok(())
+    }
+
+    // The header and the payload it describes, borrowed from `bytes` rather than copied.
+    pub fn split(bytes: &[u8]) -> Result<(Self, &[u8]), VmError> {
+        let header = ProgramHeader::parse(bytes)?;
+        let Some(payload) = bytes[header.size()..].get(..header.length as usize) else {
+            return Err(VmError::InvalidHeader {
+                reason: "payload is shorter than the header length",
+            });
+        };
+        Ok((header, payload))
+    }
+
+    // How many bytes `payload`, as split from the file, takes up once loaded.
+    pub fn loaded_length(&self, payload: &[u8]) -> Result<usize, VmError> {
+        if !self.compressed() {
+            return Ok(payload.len());
+        }
+        match payload.first_chunk() {
+            Some(&length) => Ok(u32::from_le_bytes(length) as usize),
+            None => Err(VmError::InvalidHeader {
+                reason: "compressed payload is missing its length",
+            }),
+        }
+    }
+
+    pub fn protect_code(&self) -> bool {
+        self.flags & FLAG_PROTECT_CODE != 0
+    }
+
+    pub fn compressed(&self) -> bool {
+        self.flags & FLAG_COMPRESSED != 0
+    }
+
+    pub fn segmented(&self) -> bool {
+        self.flags & FLAG_SEGMENTED != 0
+    }
+
+    pub fn no_execute_data(&self) -> bool {
+        self.flags & FLAG_NO_EXECUTE_DATA != 0
+    }
+
+    // Where a segmented program starts, which a version 2 header keeps in the load
+    // address, since its segments say where they load.
+    pub fn segmented_entry(&self) -> u16 {
+        self.entry.unwrap_or(self.load_address)
+    }
+}
+
+impl Program {
+    pub fn new(load_address: u16, payload: Vec<u8>) -> Self {
+        Self {
+            header: ProgramHeader {
+                version: VERSION,
+                flags: 0,
+                load_address,
+                length: payload.len() as u32,
+                checksum: Some(crc32(&payload)),
+                entry: Some(load_address),
+            },
+            payload,
+        }
+    }
+
+    // Starts execution at `entry` rather than the load address.
+    pub fn entry(mut self, entry: u16) -> Self {
+        self.header.entry = Some(entry);
+        self
+    }
+
+    pub fn protect_code(mut self, protect: bool) -> Self {
+        if protect {
+            self.header.flags |= FLAG_PROTECT_CODE;
+        } else {
+            self.header.flags &= !FLAG_PROTECT_CODE;
+        }
+        self
+    }
+
+    // Compresses the payload when the program is built.
+    #[cfg(feature = "flate2")]
+    pub fn compress(mut self, compress: bool) -> Self {
+        if compress {
+            self.header.flags |= FLAG_COMPRESSED;
+        } else {
+            self.header.flags &= !FLAG_COMPRESSED;
+        }
+        self
+    }
+
+    // The payload comes out decompressed, and `header.length` still describes the file.
+    pub fn parse(bytes: &[u8]) -> Result<Self, VmError> {
+        let (header, payload) = ProgramHeader::split(bytes)?;
+        let payload = if header.compressed() {
+            unpack(header.loaded_length(payload)?, payload)?
+        } else {
+            payload.to_vec()
+        };
+        Ok(Self { header, payload })
+    }
+
+    // Where a program file lands in memory: its header's load address and loaded length,
+    // the span its memory segments cover for a segmented one, or address 0 for a raw image.
+    pub fn extent(bytes: &[u8]) -> (u16, usize) {
+        if let Ok(program) = SegmentedProgram::parse(bytes) {
+            let memory = program
+                .segments
+                .iter()
+                .filter(|segment| segment.kind != SegmentKind::Video);
+            let start = memory
+                .clone()
+                .map(|segment| segment.offset)
+                .min()
+                .unwrap_or(0);
+            let end = memory.map(Segment::end).max().unwrap_or(0);
+            let length = end.saturating_sub(start as u64) as usize;
+            return (u16::try_from(start).unwrap_or(u16::MAX), length);
+        }
+        match ProgramHeader::split(bytes)
+            .and_then(|(header, payload)| Ok((header, header.loaded_length(payload)?)))
+        {
+            Ok((header, length)) => (header.load_address, length),
+            Err(_) => (0, bytes.len()),
+        }
+    }
+
+    // The file for the program, with a current version header whose length and checksum
+    // describe the payload as it is now.
+    pub fn build(&self) -> Vec<u8> {
+        let packed = self.pack();
+        let stored = packed.as_deref().unwrap_or(&self.payload);
+        let header = ProgramHeader {
+            version: VERSION,
+            flags: match packed {
+                Some(_) => self.header.flags | FLAG_COMPRESSED,
+                None => self.header.flags & !FLAG_COMPRESSED,
+            },
+            length: stored.len() as u32,
+            checksum: Some(crc32(stored)),
+            ..self.header
+        };
+        let mut bytes = Vec::with_capacity(HEADER_LEN + stored.len());
+        bytes.extend_from_slice(&header.to_bytes());
+        bytes.extend_from_slice(stored);
+        bytes
+    }
+
+    // The payload as a compressed file stores it, or None to store it as it is.
+    #[cfg(feature = "flate2")]
+    fn pack(&self) -> Option<Vec<u8>> {
+        use std::io::Write;
+
+        if !self.header.compressed() {
+            return None;
+        }
+        let prefix = (self.payload.len() as u32).to_le_bytes().to_vec();
+        let mut encoder = GzEncoder::new(prefix, flate2::Compression::best());
+        encoder
+            .write_all(&self.payload)
+            .expect("writing to a Vec cannot fail");
+        Some(encoder.finish().expect("writing to a Vec cannot fail"))
+    }
+
+    #[cfg(not(feature = "flate2"))]
+    fn pack(&self) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+fn header_len(version: u8) -> usize {
+    match version {
+        1 => HEADER_LEN_V1,
+        2 => HEADER_LEN_V2,
+        _ => HEADER_LEN,
+    }
+}
+
+// Decompresses a compressed payload, prefix included, that must come to exactly `length`
+// bytes. Never holds more than `length` bytes of output, whatever the stream says.
+#[cfg(feature = "flate2")]
+pub fn unpack(length: usize, payload: &[u8]) -> Result<Vec<u8>, VmError> {
+    use std::io::Read;
+
+    // Grown as the stream turns out to hold the bytes, rather than trusting the length
+    let mut unpacked = Vec::with_capacity(length.min(1 << 20));
+    gzip_decoder(payload)
+        .take(length as u64 + 1)
+        .read_to_end(&mut unpacked)?;
+    if unpacked.len() != length {
+        return Err(length_mismatch(unpacked.len() > length));
+    }
+    Ok(unpacked)
+}
+
+#[cfg(not(feature = "flate2"))]
+pub fn unpack(_length: usize, _payload: &[u8]) -> Result<Vec<u8>, VmError> {
+    Err(NEEDS_FLATE2)
+}
+
+// Reads a compressed payload's data, past its length prefix.
+#[cfg(feature = "flate2")]
+pub fn gzip_decoder(payload: &[u8]) -> impl std::io::Read + '_ {
+    GzDecoder::new(&payload[COMPRESSED_PREFIX_LEN..])
+}
+
+#[cfg(not(feature = "flate2"))]
+pub const NEEDS_FLATE2: VmError = VmError::InvalidHeader {
+    reason: "compressed payloads need the flate2 feature",
+};
+
+// For a compressed payload that decompresses to more or fewer bytes than its prefix says.
+pub fn length_mismatch(longer: bool) -> VmError {
+    VmError::InvalidHeader {
+        reason: if longer {
+            "payload decompresses to more bytes than its length says"
+        } else {
+            "payload decompresses to fewer bytes than its length says"
+        },
+    }
+}
+
+// Hands the contents of the file at `path` to `load`, mapped into memory when the mmap
+// feature is on and the platform allows it, read into a buffer otherwise. A file that
+// changes size while it is being loaded is an error, as is one `load` rejects.
+//
+// A mapping only stays valid while nobody else truncates the file, which nothing can
+// prevent: a file cut short mid-copy can kill the process with SIGBUS. The size checks
+// catch every other change, but not that one, so only map files nothing else is writing.
+#[cfg(feature = "std")]
+pub fn with_program_file<T>(
+    path: &std::path::Path,
+    load: impl FnOnce(&[u8]) -> Result<T, VmError>,
+) -> std::io::Result<T> {
+    use std::io::{Error, ErrorKind, Read};
+
+    let file = std::fs::File::open(path)?;
+    let expected = file.metadata()?.len();
+    let changed = || {
+        Error::new(
+            ErrorKind::UnexpectedEof,
+            alloc::format!("{} changed size while it was loading", path.display()),
+        )
+    };
+    let rejected = |e: VmError| Error::new(ErrorKind::InvalidData, e);
+
+    #[cfg(feature = "mmap")]
+    // SAFETY: the map is only read through the slice, and the length checks below catch
+    // the file changing size under us, short of it being truncated mid-copy.
+    match unsafe { memmap2::Mmap::map(&file) } {
+        Ok(map) => {
+            if map.len() as u64 != expected {
+                return Err(changed());
+            }
+            let loaded = load(&map).map_err(rejected)?;
+            if file.metadata()?.len() != expected {
+                return Err(changed());
+            }
+            log::debug!("mapped {} bytes from {}", map.len(), path.display());
+            return Ok(loaded);
+        }
+        Err(e) => log::debug!(
+            "could not map {}, reading it instead: {}",
+            path.display(),
+            e
+        ),
+    }
+
+    let mut bytes = Vec::with_capacity(expected as usize);
+    (&file).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != expected {
+        return Err(changed());
+    }
+    log::debug!("read {} bytes from {}", bytes.len(), path.display());
+    load(&bytes).map_err(rejected)
+}
@@ -0,0 +1,137 @@
+use alloc::vec::Vec;
+use core::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protection {
+    ReadOnly,
+    // Running an instruction that starts in the range faults.
+    NoExecute,
+}
+
+// Checking for execute protection happens before every instruction, so the pages a
+// PagedRangeSet touches are kept as a bitmap too.
+pub const PAGE_SHIFT: u32 = 8;
+
+// Sorted, non-overlapping, non-adjacent ranges so lookups are a binary search.
+#[derive(Debug, Clone, Default)]
+pub struct RangeSet {
+    ranges: Vec<Range<usize>>,
+}
+
+impl RangeSet {
+    pub fn new() -> Self {
+        Self { ranges: Vec::new() }
+    }
+
+    pub fn contains(&self, addr: usize) -> bool {
+        let idx = self.ranges.partition_point(|r| r.end <= addr);
+        self.ranges.get(idx).is_some_and(|r| r.start <= addr)
+    }
+
+    // The lowest address of `range` that is in the set.
+    pub fn first_in(&self, range: Range<usize>) -> Option<usize> {
+        let idx = self.ranges.partition_point(|r| r.end <= range.start);
+        self.ranges
+            .get(idx)
+            .filter(|r| r.start < range.end)
+            .map(|r| r.start.max(range.start))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    pub fn ranges(&self) -> &[Range<usize>] {
+        &self.ranges
+    }
+
+    pub fn insert(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        let first = self.ranges.partition_point(|r| r.end < range.start);
+        let last = self.ranges.partition_point(|r| r.start <= range.end);
+        let mut merged = range;
+        if first < last {
+            merged.start = merged.start.min(self.ranges[first].start);
+            merged.end = merged.end.max(self.ranges[last - 1].end);
+        }
+        self.ranges.splice(first..last, [merged]);
+    }
+
+    pub fn remove(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        let first = self.ranges.partition_point(|r| r.end <= range.start);
+        let last = self.ranges.partition_point(|r| r.start < range.end);
+        if first >= last {
+            return;
+        }
+        let mut remaining = Vec::with_capacity(2);
+        if self.ranges[first].start < range.start {
+            remaining.push(self.ranges[first].start..range.start);
+        }
+        if self.ranges[last - 1].end > range.end {
+            remaining.push(range.end..self.ranges[last - 1].end);
+        }
+        self.ranges.splice(first..last, remaining);
+    }
+}
+
+// A RangeSet behind a bitmap of the pages it touches, so looking up an address on a page
+// with nothing marked is a bit test rather than a search.
+#[derive(Debug, Clone, Default)]
+pub struct PagedRangeSet {
+    ranges: RangeSet,
+    pages: Vec<u64>,
+}
+
+impl PagedRangeSet {
+    pub fn new() -> Self {
+        Self {
+            ranges: RangeSet::new(),
+            pages: Vec::new(),
+        }
+    }
+
+    #[inline(always)]
+    pub fn contains(&self, addr: usize) -> bool {
+        let page = addr >> PAGE_SHIFT;
+        let marked = self
+            .pages
+            .get(page / 64)
+            .is_some_and(|bits| bits & (1 << (page % 64)) != 0);
+        marked && self.ranges.contains(addr)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    pub fn ranges(&self) -> &[Range<usize>] {
+        self.ranges.ranges()
+    }
+
+    pub fn insert(&mut self, range: Range<usize>) {
+        self.ranges.insert(range);
+        self.mark_pages();
+    }
+
+    pub fn remove(&mut self, range: Range<usize>) {
+        self.ranges.remove(range);
+        self.mark_pages();
+    }
+
+    fn mark_pages(&mut self) {
+        self.pages.clear();
+        for range in self.ranges.ranges() {
+            for page in range.start >> PAGE_SHIFT..=(range.end - 1) >> PAGE_SHIFT {
+                if self.pages.len() <= page / 64 {
+                    self.pages.resize(page / 64 + 1, 0);
+                }
+                self.pages[page / 64] |= 1 << (page % 64);
+            }
+        }
+    }
+}
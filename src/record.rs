@@ -0,0 +1,261 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+
+use crate::png;
+use crate::vm::MicroCvm;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordFormat {
+    // frame-000000.png and on.
+    Png,
+    // frame-000000.rgba and on, with a `file width height frame` line for each in index.txt.
+    Raw,
+}
+
+// Where recorded frames go and which ones are kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordConfig {
+    pub dir: PathBuf,
+    // Every this many frames is recorded, at least 1.
+    pub every: u32,
+    pub format: RecordFormat,
+    // How many frames wait for the writer before new ones are dropped, at least 1.
+    pub queue: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RecordStats {
+    pub written: u64,
+    // Frames the writer couldn't keep up with.
+    pub dropped: u64,
+}
+
+struct Frame {
+    // Counts written frames, so the files number on without gaps.
+    number: u64,
+    // Counts every frame the recorder saw.
+    presented: u64,
+    width: u32,
+    height: u32,
+    rgba: Vec<u8>,
+}
+
+// Writes frames on a thread of its own, so the machine never waits for the disk. A frame
+// that turns up while the queue is full is dropped and counted instead.
+pub struct Recorder {
+    config: RecordConfig,
+    // Frames seen while disabled are still counted, they just aren't recorded.
+    pub enabled: bool,
+    presented: u64,
+    sent: u64,
+    dropped: u64,
+    sender: Option<SyncSender<Frame>>,
+    writer: Option<JoinHandle<io::Result<u64>>>,
+}
+
+impl RecordFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            RecordFormat::Png => "png",
+            RecordFormat::Raw => "raw",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [RecordFormat::Png, RecordFormat::Raw]
+            .into_iter()
+            .find(|format| format.name() == name)
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            RecordFormat::Png => "png",
+            RecordFormat::Raw => "rgba",
+        }
+    }
+}
+
+impl RecordConfig {
+    // Every frame into `frames` as PNGs.
+    pub fn new() -> Self {
+        Self {
+            dir: PathBuf::from("frames"),
+            every: 1,
+            format: RecordFormat::Png,
+            queue: 8,
+        }
+    }
+}
+
+impl Default for RecordConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Recorder {
+    /// Makes `config.dir` if it isn't there and starts the writer, enabled. Call
+    /// [`capture`](Self::capture) once per frame and [`finish`](Self::finish) at the end:
+    ///
+    /// ```
+    /// use microcvm_rs::MicroCvm;
+    /// use microcvm_rs::demo::{DEMO_HEIGHT, DEMO_WIDTH, bounce_program};
+    /// use microcvm_rs::png;
+    /// use microcvm_rs::record::{RecordConfig, RecordFormat, Recorder};
+    /// use microcvm_rs::vm::CYCLES_PER_FRAME;
+    ///
+    /// let dir = std::env::temp_dir().join("microcvm-record-doctest");
+    /// let _ = std::fs::remove_dir_all(&dir);
+    /// let config = RecordConfig {
+    ///     dir: dir.clone(),
+    ///     every: 2,
+    ///     format: RecordFormat::Png,
+    ///     // Room for every frame, so none are dropped however slow the disk.
+    ///     queue: 15,
+    /// };
+    /// let mut vm = MicroCvm::builder().resolution(DEMO_WIDTH, DEMO_HEIGHT).build();
+    /// vm.load_program(&bounce_program()).unwrap();
+    /// let mut recorder = Recorder::start(config).unwrap();
+    /// for _ in 0..30 {
+    ///     vm.run_frame(CYCLES_PER_FRAME).unwrap();
+    ///     recorder.capture(&mut vm);
+    ///     vm.tick_frame();
+    /// }
+    /// let stats = recorder.finish().unwrap();
+    /// assert_eq!((stats.written, stats.dropped), (15, 0));
+    ///
+    /// for number in 0..15 {
+    ///     let bytes = std::fs::read(dir.join(format!("frame-{:06}.png", number))).unwrap();
+    ///     let image = png::decode(&bytes).unwrap();
+    ///     assert_eq!((image.width, image.height), (DEMO_WIDTH, DEMO_HEIGHT));
+    /// }
+    /// assert!(!dir.join("frame-000015.png").exists());
+    /// ```
+    pub fn start(config: RecordConfig) -> io::Result<Self> {
+        std::fs::create_dir_all(&config.dir)?;
+        let index = match config.format {
+            RecordFormat::Png => None,
+            RecordFormat::Raw => Some(BufWriter::new(File::create(config.dir.join("index.txt"))?)),
+        };
+        let (sender, receiver) = mpsc::sync_channel(config.queue.max(1));
+        let (dir, format) = (config.dir.clone(), config.format);
+        let writer = thread::Builder::new()
+            .name(String::from("frame recorder"))
+            .spawn(move || write_frames(&dir, format, index, receiver))?;
+        Ok(Self {
+            config,
+            enabled: true,
+            presented: 0,
+            sent: 0,
+            dropped: 0,
+            sender: Some(sender),
+            writer: Some(writer),
+        })
+    }
+
+    pub fn config(&self) -> &RecordConfig {
+        &self.config
+    }
+
+    // Hands the writer the frame the machine is showing, if it is enabled and the frame is
+    // one of the `every` it keeps.
+    pub fn capture(&mut self, vm: &mut MicroCvm) {
+        let presented = self.presented;
+        self.presented += 1;
+        if !self.enabled || !presented.is_multiple_of(self.config.every.max(1) as u64) {
+            return;
+        }
+        let Some(sender) = &self.sender else {
+            return;
+        };
+        let frame = Frame {
+            number: self.sent,
+            presented,
+            width: vm.width(),
+            height: vm.height(),
+            rgba: vm.render_frame_to_rgba(),
+        };
+        match sender.try_send(frame) {
+            Ok(()) => self.sent += 1,
+            // A writer that has stopped has hit an error, which finish reports.
+            Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) => self.dropped += 1,
+        }
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    // Waits for the writer to write what is queued.
+    pub fn finish(mut self) -> io::Result<RecordStats> {
+        self.stop()
+    }
+
+    fn stop(&mut self) -> io::Result<RecordStats> {
+        self.sender = None;
+        let written = match self.writer.take() {
+            Some(writer) => writer
+                .join()
+                .map_err(|_| io::Error::other("the frame writer panicked"))??,
+            None => 0,
+        };
+        Ok(RecordStats {
+            written,
+            dropped: self.dropped,
+        })
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if self.writer.is_some()
+            && let Err(e) = self.stop()
+        {
+            log::error!("recording to `{}`: {}", self.config.dir.display(), e);
+        }
+    }
+}
+
+fn write_frames(
+    dir: &Path,
+    format: RecordFormat,
+    mut index: Option<BufWriter<File>>,
+    frames: Receiver<Frame>,
+) -> io::Result<u64> {
+    let mut written = 0;
+    for frame in frames {
+        let name = format!("frame-{:06}.{}", frame.number, format.extension());
+        match format {
+            RecordFormat::Png => {
+                let png = png::encode(frame.width, frame.height, &frame.rgba);
+                std::fs::write(dir.join(&name), png)?;
+            }
+            RecordFormat::Raw => std::fs::write(dir.join(&name), &frame.rgba)?,
+        }
+        if let Some(index) = index.as_mut() {
+            writeln!(
+                index,
+                "{} {} {} {}",
+                name, frame.width, frame.height, frame.presented
+            )?;
+        }
+        written += 1;
+    }
+    if let Some(index) = index.as_mut() {
+        index.flush()?;
+    }
+    Ok(written)
+}
+
+impl core::fmt::Display for RecordStats {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} frames written", self.written)?;
+        if self.dropped > 0 {
+            write!(f, ", {} dropped to keep up", self.dropped)?;
+        }
+        Ok(())
+    }
+}
@@ -1,91 +1,986 @@
-use pixels::{Pixels, SurfaceTexture};
+use pixels::{Pixels, SurfaceTexture, wgpu};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use winit::application::ApplicationHandler;
 use winit::dpi::LogicalPosition;
-use winit::dpi::LogicalSize;
-use winit::event::WindowEvent;
-use winit::event_loop::ActiveEventLoop;
-use winit::window::{Window, WindowAttributes, WindowId};
+use winit::dpi::{LogicalSize, PhysicalPosition, PhysicalSize};
+use winit::event::StartCause;
+use winit::event::{DeviceEvent, DeviceId, ElementState, MouseButton, WindowEvent};
+use winit::event_loop::{ActiveEventLoop, ControlFlow};
+use winit::keyboard::{KeyCode, PhysicalKey};
+use winit::monitor::MonitorHandle;
+use winit::window::{CursorGrabMode, Window, WindowAttributes, WindowId};
+
+use crate::cpu::HaltReason;
+use crate::crt::CrtEffect;
+use crate::debugger::{
+    DEBUGGER_HEIGHT, DEBUGGER_WIDTH, Debugger, HEXDUMP_ROW_BYTES, HEXDUMP_ROWS, paint,
+};
+use crate::gamepad::{
+    BUTTON_A, BUTTON_B, BUTTON_DOWN, BUTTON_LEFT, BUTTON_RIGHT, BUTTON_SELECT, BUTTON_START,
+    BUTTON_UP, BUTTON_X, BUTTON_Y,
+};
+use crate::gpu::{GpuConfig, GpuError, describe};
+use crate::input::{InputConfig, KeyRepeat, RepeatUnit};
+use crate::letterbox::{Letterbox, Placement, place};
+use crate::mouse::{MOUSE_LEFT, MOUSE_MIDDLE, MOUSE_RIGHT};
+use crate::overlay::{draw_text, layout};
+use crate::record::{RecordConfig, Recorder};
+use crate::vm::{CYCLES_PER_FRAME, MicroCvm};
 
-#[derive(Default)]
 pub struct App {
     window: Option<Arc<Window>>,
+    // As big as the window in physical pixels, the frame is scaled and letterboxed into it.
     pixels: Option<Pixels<'static>>,
-    width: u32,
-    height: u32,
-    video_memory: Vec<super::types::Color>,
+    window_size: PhysicalSize<u32>,
+    // Physical pixels per logical one on the display the window is on.
+    scale_factor: f64,
+    // Where the frame goes in the window, for mapping the pointer onto it.
+    placement: Placement,
+    // Where the pointer last was over the window. Logical pixels stay put when the window
+    // moves to a display with another scale factor, physical ones don't.
+    cursor: Option<LogicalPosition<f64>>,
+    // What `config.present_mode` came to on this surface.
+    present_mode: PresentMode,
+    // Why the windows couldn't open, for whoever ran the event loop.
+    error: Option<GpuError>,
+    // When the next frame runs if the present mode doesn't pace them.
+    frame_due: Instant,
+    vm: MicroCvm,
+    running: bool,
+    // `crt_enabled` is only where the window starts, the machine holds the effect from then
+    // on so frames drawn without a window match.
+    config: WindowConfig,
+    // Toggled with F3.
+    overlay_enabled: bool,
+    // Started by RECORD_KEY, if the config didn't start it.
+    recorder: Option<Recorder>,
+    rates: Rates,
+    // `None` when the platform has no controller support, the keyboard still works.
+    #[cfg(feature = "gamepad")]
+    controllers: Option<crate::gamepad::Controllers>,
+    debugger: Option<DebuggerWindow>,
+    // Scancodes of the keys down in the VM window, in the order they went down.
+    held: Vec<u8>,
+    // Which scancode each key sends.
+    input: InputConfig,
+    // `None` when held keys don't repeat.
+    key_repeat: Option<KeyRepeat>,
+    started: Instant,
+    // Frames the machine has run, for repeat rates counted in frames.
+    frames: u64,
+    focused: bool,
+    // Whether the pointer is hidden and held in place for the guest's relative mode.
+    pointer_captured: bool,
 }
 
-impl ApplicationHandler for App {
-    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        let window_attributes = WindowAttributes::default()
-            .with_inner_size(LogicalSize::new(self.width, self.height))
-            .with_position(LogicalPosition::new(0, 0))
-            .with_title("Virtual Machine Window");
+// How the VM window looks.
+#[derive(Debug, Clone)]
+pub struct WindowConfig {
+    // What the window opens at, in logical pixels per VM pixel.
+    pub scale: u32,
+    pub crt: CrtEffect,
+    // Whether the window opens with the CRT effect on.
+    pub crt_enabled: bool,
+    // Whether the debugger window opens next to it.
+    pub debugger: bool,
+    // What surrounds the frame when the window isn't a whole multiple of it.
+    pub letterbox: Letterbox,
+    pub placement: WindowPlacement,
+    pub present_mode: PresentMode,
+    pub gpu: GpuConfig,
+    pub record: RecordConfig,
+    // Whether the window records from the start, rather than once RECORD_KEY is pressed.
+    pub recording: bool,
+    // Cycles each frame runs for, CYCLES_PER_FRAME unless `run` was told otherwise.
+    pub cycles_per_frame: u64,
+}
 
-        let window = Arc::new(event_loop.create_window(window_attributes).unwrap());
-        self.window = Some(window.clone());
+// How finished frames reach the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresentMode {
+    // Each frame waits for the display's next refresh, which never tears and paces the
+    // machine at the refresh rate.
+    Vsync,
+    // The newest frame is shown at the next refresh and older ones are dropped: no tearing,
+    // less latency than Vsync.
+    Mailbox,
+    // Frames are shown as soon as they are done, the least latency but they can tear.
+    Immediate,
+}
 
-        let surface_texture = SurfaceTexture::new(self.width / 2, self.height / 2, window);
-        let pixels = Pixels::new(self.width / 2, self.height / 2, surface_texture).unwrap();
+// Where the VM window opens. The debugger window opens to its right.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowPlacement {
+    // Centred on the primary monitor.
+    Primary,
+    // Centred on the monitor at this index in the platform's list, or the primary one if
+    // there is no such monitor.
+    Monitor(usize),
+    // The window's top left corner, in physical desktop pixels.
+    Position(i32, i32),
+}
 
-        self.pixels = Some(pixels);
+// A monitor as window placement sees it, in physical desktop pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MonitorArea {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub scale_factor: f64,
+}
+
+impl WindowConfig {
+    pub fn new() -> Self {
+        Self {
+            scale: 2,
+            crt: CrtEffect::new(),
+            crt_enabled: false,
+            debugger: false,
+            letterbox: Letterbox::new(),
+            placement: WindowPlacement::Primary,
+            present_mode: PresentMode::Vsync,
+            gpu: GpuConfig::new(),
+            record: RecordConfig::new(),
+            recording: false,
+            cycles_per_frame: CYCLES_PER_FRAME,
+        }
+    }
+}
+
+impl PresentMode {
+    pub const ALL: [PresentMode; 3] = [
+        PresentMode::Vsync,
+        PresentMode::Mailbox,
+        PresentMode::Immediate,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PresentMode::Vsync => "vsync",
+            PresentMode::Mailbox => "mailbox",
+            PresentMode::Immediate => "immediate",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|mode| mode.name() == name)
+    }
+
+    // Vsync leaves wgpu to pick between fifo and relaxed fifo, either of which a surface
+    // always has.
+    fn to_wgpu(self) -> wgpu::PresentMode {
+        match self {
+            PresentMode::Vsync => wgpu::PresentMode::AutoVsync,
+            PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+            PresentMode::Immediate => wgpu::PresentMode::Immediate,
+        }
+    }
+
+    fn from_wgpu(mode: wgpu::PresentMode) -> Option<Self> {
+        match mode {
+            wgpu::PresentMode::Fifo | wgpu::PresentMode::FifoRelaxed => Some(PresentMode::Vsync),
+            wgpu::PresentMode::Mailbox => Some(PresentMode::Mailbox),
+            wgpu::PresentMode::Immediate => Some(PresentMode::Immediate),
+            _ => None,
+        }
+    }
+}
+
+impl core::fmt::Display for PresentMode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The present mode used for `requested` on a surface that supports `supported`: the
+/// requested one if there, otherwise the other low-latency mode, otherwise vsync, which every
+/// surface has.
+///
+/// ```
+/// use microcvm_rs::render::{PresentMode, choose_present_mode};
+/// use PresentMode::{Immediate, Mailbox, Vsync};
+///
+/// let surfaces: [&[PresentMode]; 5] = [
+///     &[Vsync, Mailbox, Immediate],
+///     &[Vsync, Mailbox],
+///     &[Vsync, Immediate],
+///     &[Vsync],
+///     &[],
+/// ];
+/// let expected = [
+///     // Vsync, Mailbox, Immediate requested
+///     [Vsync, Mailbox, Immediate],
+///     [Vsync, Mailbox, Mailbox],
+///     [Vsync, Immediate, Immediate],
+///     [Vsync, Vsync, Vsync],
+///     [Vsync, Vsync, Vsync],
+/// ];
+/// for (supported, expected) in surfaces.iter().zip(expected) {
+///     let chosen = [Vsync, Mailbox, Immediate].map(|mode| choose_present_mode(mode, supported));
+///     assert_eq!(chosen, expected, "on {:?}", supported);
+/// }
+/// ```
+pub fn choose_present_mode(requested: PresentMode, supported: &[PresentMode]) -> PresentMode {
+    let fallbacks: &[PresentMode] = match requested {
+        PresentMode::Vsync => &[],
+        PresentMode::Mailbox => &[PresentMode::Immediate],
+        PresentMode::Immediate => &[PresentMode::Mailbox],
+    };
+    core::iter::once(requested)
+        .chain(fallbacks.iter().copied())
+        .find(|mode| supported.contains(mode))
+        .unwrap_or(PresentMode::Vsync)
+}
+
+// The modes of ours a surface for `window` supports.
+fn supported_present_modes(gpu: &GpuConfig, window: &Arc<Window>) -> Vec<PresentMode> {
+    let mut modes = Vec::new();
+    for mode in gpu.supported_present_modes(window) {
+        if let Some(mode) = PresentMode::from_wgpu(mode)
+            && !modes.contains(&mode)
+        {
+            modes.push(mode);
+        }
+    }
+    modes
+}
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MonitorArea {
+    pub fn new(monitor: &MonitorHandle) -> Self {
+        let (position, size) = (monitor.position(), monitor.size());
+        Self {
+            x: position.x,
+            y: position.y,
+            width: size.width,
+            height: size.height,
+            scale_factor: monitor.scale_factor(),
+        }
+    }
+}
+
+// Where a window `size` logical pixels inside goes for `placement`, given the platform's
+// `monitors` and which of them is the primary one. `None` leaves it to the window manager,
+// for a platform that doesn't say which monitor is primary or lists none.
+pub fn window_position(
+    placement: WindowPlacement,
+    monitors: &[MonitorArea],
+    primary: Option<usize>,
+    size: LogicalSize<f64>,
+) -> Option<PhysicalPosition<i32>> {
+    let index = match placement {
+        WindowPlacement::Position(x, y) => return Some(PhysicalPosition::new(x, y)),
+        WindowPlacement::Primary => primary?,
+        WindowPlacement::Monitor(index) if index < monitors.len() => index,
+        WindowPlacement::Monitor(index) => {
+            log::warn!(
+                "there is no monitor {} (found {}); opening on the primary one",
+                index,
+                monitors.len()
+            );
+            primary?
+        }
+    };
+    let monitor = monitors.get(index)?;
+    let size = size.to_physical::<u32>(monitor.scale_factor);
+    let centre = |start: i32, monitor: u32, window: u32| {
+        (start as i64 + (monitor as i64 - window as i64) / 2) as i32
+    };
+    Some(PhysicalPosition::new(
+        centre(monitor.x, monitor.width, size.width).max(monitor.x),
+        centre(monitor.y, monitor.height, size.height).max(monitor.y),
+    ))
+}
+
+// A second window on the same event loop. Keys pressed while it has focus drive the
+// debugger and never reach the guest.
+struct DebuggerWindow {
+    window: Arc<Window>,
+    pixels: Pixels<'static>,
+    view: Debugger,
+}
+
+// Frames and instructions per second, measured over the last full second.
+struct Rates {
+    since: Instant,
+    frames: u32,
+    instructions: u64,
+    frames_per_second: u32,
+    instructions_per_second: u64,
+}
+
+impl Rates {
+    fn new() -> Self {
+        Self {
+            since: Instant::now(),
+            frames: 0,
+            instructions: 0,
+            frames_per_second: 0,
+            instructions_per_second: 0,
+        }
+    }
+
+    // Counts a presented frame, `instructions` is the machine's total so far.
+    fn frame(&mut self, instructions: u64) {
+        self.frames += 1;
+        let elapsed = self.since.elapsed();
+        if elapsed >= RATE_INTERVAL {
+            let seconds = elapsed.as_secs_f64();
+            self.frames_per_second = (self.frames as f64 / seconds).round() as u32;
+            self.instructions_per_second =
+                ((instructions - self.instructions) as f64 / seconds).round() as u64;
+            self.since = Instant::now();
+            self.frames = 0;
+            self.instructions = instructions;
+        }
+    }
+}
+
+const RATE_INTERVAL: Duration = Duration::from_secs(1);
+// How often frames run when the present mode doesn't wait for the display, 60 a second.
+const FRAME_INTERVAL: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+// Toggles the CRT effect.
+const CRT_KEY: KeyCode = KeyCode::F2;
+// Either one pauses and resumes the program.
+const PAUSE_KEYS: [KeyCode; 2] = [KeyCode::KeyP, KeyCode::Pause];
+// Toggles the registers and rates drawn over the frame.
+const OVERLAY_KEY: KeyCode = KeyCode::F3;
+// Pauses and resumes recording, starting it the first time.
+const RECORD_KEY: KeyCode = KeyCode::F4;
+// Lets go of a captured pointer, without the guest seeing the key.
+const RELEASE_POINTER_KEY: KeyCode = KeyCode::Escape;
+
+// Keys that hold gamepad buttons, alongside whatever controllers are connected.
+const GAMEPAD_KEYS: [(KeyCode, u16); 10] = [
+    (KeyCode::ArrowUp, BUTTON_UP),
+    (KeyCode::ArrowDown, BUTTON_DOWN),
+    (KeyCode::ArrowLeft, BUTTON_LEFT),
+    (KeyCode::ArrowRight, BUTTON_RIGHT),
+    (KeyCode::KeyZ, BUTTON_A),
+    (KeyCode::KeyX, BUTTON_B),
+    (KeyCode::KeyA, BUTTON_X),
+    (KeyCode::KeyS, BUTTON_Y),
+    (KeyCode::Enter, BUTTON_START),
+    (KeyCode::ShiftRight, BUTTON_SELECT),
+];
+
+const TITLE: &str = "Virtual Machine Window";
+const DEBUGGER_TITLE: &str = "Debugger";
+
+impl ApplicationHandler for App {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if let Err(e) = self.open_windows(event_loop) {
+            self.error = Some(e);
+            event_loop.exit();
+        }
     }
 
     fn window_event(
         &mut self,
         event_loop: &ActiveEventLoop,
-        _window_id: WindowId,
+        window_id: WindowId,
         event: WindowEvent,
     ) {
+        let debugger = self.debugger.as_ref();
+        if debugger.is_some_and(|debugger| debugger.window.id() == window_id) {
+            self.debugger_event(event_loop, event);
+            return;
+        }
         match event {
             WindowEvent::CloseRequested => {
-                println!("The close button was pressed; stopping");
+                log::info!("The close button was pressed; stopping");
                 event_loop.exit();
             }
+            WindowEvent::KeyboardInput { event, .. }
+                if event.physical_key == PhysicalKey::Code(CRT_KEY)
+                    && event.state == ElementState::Pressed
+                    && !event.repeat =>
+            {
+                let crt = match self.vm.crt() {
+                    Some(_) => None,
+                    None => Some(self.config.crt),
+                };
+                self.vm.set_crt(crt);
+            }
+            WindowEvent::KeyboardInput { event, .. }
+                if PAUSE_KEYS
+                    .map(PhysicalKey::Code)
+                    .contains(&event.physical_key)
+                    && event.state == ElementState::Pressed
+                    && !event.repeat =>
+            {
+                self.toggle_pause();
+            }
+            WindowEvent::KeyboardInput { event, .. }
+                if event.physical_key == PhysicalKey::Code(OVERLAY_KEY)
+                    && event.state == ElementState::Pressed
+                    && !event.repeat =>
+            {
+                self.overlay_enabled = !self.overlay_enabled;
+            }
+            WindowEvent::KeyboardInput { event, .. }
+                if event.physical_key == PhysicalKey::Code(RECORD_KEY)
+                    && event.state == ElementState::Pressed
+                    && !event.repeat =>
+            {
+                self.toggle_recording();
+            }
+            WindowEvent::KeyboardInput { event, .. }
+                if event.physical_key == PhysicalKey::Code(RELEASE_POINTER_KEY)
+                    && event.state == ElementState::Pressed
+                    && self.pointer_captured =>
+            {
+                self.release_pointer();
+            }
+            WindowEvent::KeyboardInput { event, .. } => {
+                let pressed = event.state == ElementState::Pressed;
+                for (key, mask) in GAMEPAD_KEYS {
+                    if event.physical_key == PhysicalKey::Code(key) {
+                        self.vm.cpu_mut().gamepad_mut().key(mask, pressed);
+                    }
+                }
+                // The OS's repeats are dropped, KeyRepeat makes its own.
+                if let PhysicalKey::Code(key) = event.physical_key
+                    && let Some(scancode) = self.input.scancode(key)
+                    && !event.repeat
+                {
+                    self.key(scancode, pressed);
+                }
+            }
+            WindowEvent::Focused(focused) => {
+                self.focused = focused;
+                if !focused {
+                    self.release_keys();
+                    self.release_pointer();
+                }
+                self.vm.focus_changed(focused);
+                self.update_title();
+            }
+            WindowEvent::Resized(size) => self.resize(size),
+            // A Resized to the window's new physical size follows.
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                self.scale_factor = scale_factor;
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = Some(position.to_logical(self.scale_factor));
+                self.move_pointer();
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                let mask = match button {
+                    MouseButton::Left => MOUSE_LEFT,
+                    MouseButton::Right => MOUSE_RIGHT,
+                    MouseButton::Middle => MOUSE_MIDDLE,
+                    _ => return,
+                };
+                let pressed = state == ElementState::Pressed;
+                self.vm.cpu_mut().mouse_mut().button(mask, pressed);
+            }
             WindowEvent::RedrawRequested => {
+                // A paused machine keeps showing its last frame, and no time passes for it.
+                let paused = self.vm.is_paused();
+                #[cfg(feature = "gamepad")]
+                if let Some(controllers) = self.controllers.as_mut() {
+                    self.vm.inject_gamepad_state(controllers.poll());
+                }
+                self.capture_pointer();
+                if !paused {
+                    self.repeat_keys();
+                    self.run_frame();
+                    self.frames += 1;
+                }
+                self.rates.frame(self.vm.instructions());
                 self.render();
-                self.window.as_ref().unwrap().request_redraw();
+                if !paused {
+                    if let Some(recorder) = self.recorder.as_mut() {
+                        recorder.capture(&mut self.vm);
+                    }
+                    self.vm.tick_frame();
+                }
+                self.render_debugger();
+                self.next_frame(event_loop);
             }
             _ => (),
         }
     }
+
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        if let Some(recorder) = self.recorder.take() {
+            let dir = recorder.config().dir.clone();
+            match recorder.finish() {
+                Ok(stats) => log::info!("{} to `{}`", stats, dir.display()),
+                Err(e) => log::error!("recording to `{}`: {}", dir.display(), e),
+            }
+        }
+    }
+
+    // The frame next_frame waited for is due.
+    fn new_events(&mut self, event_loop: &ActiveEventLoop, cause: StartCause) {
+        if let StartCause::ResumeTimeReached { .. } = cause
+            && let Some(window) = self.window.as_ref()
+        {
+            event_loop.set_control_flow(ControlFlow::Wait);
+            window.request_redraw();
+        }
+    }
+
+    // Relative mode reads raw motion, since the held pointer doesn't move.
+    fn device_event(&mut self, _event_loop: &ActiveEventLoop, _id: DeviceId, event: DeviceEvent) {
+        let DeviceEvent::MouseMotion { delta: (dx, dy) } = event else {
+            return;
+        };
+        if !self.pointer_captured {
+            return;
+        }
+        // Host pixels per framebuffer pixel.
+        let ratio = self.placement.scale as f64;
+        self.vm.inject_mouse_motion(dx / ratio, dy / ratio);
+    }
 }
 
 impl App {
+    // Opens the VM window, and the debugger's if it was asked for.
+    fn open_windows(&mut self, event_loop: &ActiveEventLoop) -> Result<(), GpuError> {
+        let size = LogicalSize::new(
+            (self.vm.width() * self.config.scale) as f64,
+            (self.vm.height() * self.config.scale) as f64,
+        );
+        let monitors: Vec<MonitorHandle> = event_loop.available_monitors().collect();
+        let primary = event_loop
+            .primary_monitor()
+            .and_then(|primary| monitors.iter().position(|monitor| *monitor == primary));
+        let areas: Vec<MonitorArea> = monitors.iter().map(MonitorArea::new).collect();
+        let mut window_attributes = WindowAttributes::default()
+            .with_inner_size(size)
+            .with_title(TITLE);
+        if let Some(position) = window_position(self.config.placement, &areas, primary, size) {
+            window_attributes = window_attributes.with_position(position);
+        }
+
+        let window = event_loop
+            .create_window(window_attributes)
+            .map_err(GpuError::Window)?;
+        let window = Arc::new(window);
+        self.window = Some(window.clone());
+
+        let requested = self.config.present_mode;
+        self.present_mode = match requested {
+            PresentMode::Vsync => PresentMode::Vsync,
+            _ => choose_present_mode(
+                requested,
+                &supported_present_modes(&self.config.gpu, &window),
+            ),
+        };
+        if self.present_mode != requested {
+            log::warn!(
+                "the window can't present frames with {}, using {} instead",
+                requested,
+                self.present_mode
+            );
+        }
+
+        let size = window.inner_size();
+        let surface_texture = SurfaceTexture::new(size.width, size.height, window);
+        let pixels = self.config.gpu.build_pixels(
+            size.width,
+            size.height,
+            surface_texture,
+            self.present_mode.to_wgpu(),
+        )?;
+        log::info!("drawing with {}", describe(&pixels.adapter().get_info()));
+
+        self.pixels = Some(pixels);
+        self.scale_factor = self.window.as_ref().unwrap().scale_factor();
+        self.resize(size);
+
+        if self.config.debugger {
+            let mut window_attributes = WindowAttributes::default()
+                .with_inner_size(LogicalSize::new(
+                    DEBUGGER_WIDTH * self.config.scale,
+                    DEBUGGER_HEIGHT * self.config.scale,
+                ))
+                .with_title(DEBUGGER_TITLE);
+            // Some platforms don't let windows know where they are.
+            let vm_window = self.window.as_ref().unwrap();
+            if let Ok(position) = vm_window.outer_position() {
+                let width = vm_window.outer_size().width as i32;
+                window_attributes = window_attributes
+                    .with_position(PhysicalPosition::new(position.x + width, position.y));
+            }
+            let window = event_loop
+                .create_window(window_attributes)
+                .map_err(GpuError::Window)?;
+            let window = Arc::new(window);
+            let size = window.inner_size();
+            let surface_texture = SurfaceTexture::new(size.width, size.height, window.clone());
+            // The same mode as the VM window, so it doesn't hold up the frames Vsync isn't
+            // pacing.
+            let present_mode = match self.present_mode {
+                PresentMode::Vsync => PresentMode::Vsync,
+                mode => {
+                    choose_present_mode(mode, &supported_present_modes(&self.config.gpu, &window))
+                }
+            };
+            let pixels = self.config.gpu.build_pixels(
+                DEBUGGER_WIDTH,
+                DEBUGGER_HEIGHT,
+                surface_texture,
+                present_mode.to_wgpu(),
+            )?;
+            self.debugger = Some(DebuggerWindow {
+                window,
+                pixels,
+                view: Debugger::new(),
+            });
+        }
+        Ok(())
+    }
+
+    // Why the event loop stopped before the windows opened, if it did.
+    pub fn take_error(&mut self) -> Option<GpuError> {
+        self.error.take()
+    }
+
+    // The present mode the window ended up with, which is the one asked for unless the
+    // surface doesn't support it.
+    pub fn actual_present_mode(&self) -> PresentMode {
+        self.present_mode
+    }
+
+    // Vsync paces frames by blocking until the refresh. The other modes return straight
+    // away, so the event loop sleeps until the next frame is due instead, which still hears
+    // input meanwhile.
+    fn next_frame(&mut self, event_loop: &ActiveEventLoop) {
+        if self.actual_present_mode() == PresentMode::Vsync {
+            self.window.as_ref().unwrap().request_redraw();
+            return;
+        }
+        let now = Instant::now();
+        self.frame_due += FRAME_INTERVAL;
+        // Don't rush to catch up after a stall.
+        if self.frame_due < now {
+            self.frame_due = now;
+        }
+        event_loop.set_control_flow(ControlFlow::WaitUntil(self.frame_due));
+    }
+
+    fn run_frame(&mut self) {
+        if !self.running {
+            return;
+        }
+        match self.vm.run_frame(self.config.cycles_per_frame) {
+            Ok(HaltReason::Halted { .. }) => self.running = false,
+            Ok(HaltReason::FrameComplete | HaltReason::Paused) => {}
+            Ok(HaltReason::Breakpoint) => self.set_paused(true),
+            Ok(reason) => {
+                log::info!("{}", reason);
+                self.running = false;
+            }
+            // MicroCvm::step has logged the fault.
+            Err(_) => self.running = false,
+        }
+    }
+
+    fn key(&mut self, scancode: u8, pressed: bool) {
+        let held = self.held.contains(&scancode);
+        if pressed && !held {
+            self.held.push(scancode);
+        } else if !pressed && held {
+            self.held.retain(|&key| key != scancode);
+        } else {
+            return;
+        }
+        self.vm.push_key(scancode, pressed);
+    }
+
+    // Lets go of every held key, since the window won't see them go up once it has lost
+    // focus.
+    fn release_keys(&mut self) {
+        for scancode in core::mem::take(&mut self.held) {
+            self.vm.push_key(scancode, false);
+        }
+        if let Some(repeat) = self.key_repeat.as_mut() {
+            repeat.reset();
+        }
+    }
+
+    // Grabs or lets go of the pointer to match what the guest asked for.
+    fn capture_pointer(&mut self) {
+        let wanted = self.focused && self.vm.cpu().mouse().relative();
+        let Some(window) = self
+            .window
+            .as_ref()
+            .filter(|_| wanted != self.pointer_captured)
+        else {
+            return;
+        };
+        if wanted {
+            // Not every platform can lock the pointer in place, but it can at least keep it
+            // in the window.
+            let grabbed = window
+                .set_cursor_grab(CursorGrabMode::Locked)
+                .or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined));
+            if let Err(e) = grabbed {
+                log::warn!("could not capture the pointer: {}", e);
+                self.vm.cpu_mut().mouse_mut().release();
+                return;
+            }
+        } else if let Err(e) = window.set_cursor_grab(CursorGrabMode::None) {
+            log::warn!("could not release the pointer: {}", e);
+        }
+        window.set_cursor_visible(!wanted);
+        self.pointer_captured = wanted;
+    }
+
+    // Leaves relative mode, so the guest has to ask again before the pointer is captured.
+    fn release_pointer(&mut self) {
+        self.vm.cpu_mut().mouse_mut().release();
+        self.capture_pointer();
+    }
+
+    fn repeat_keys(&mut self) {
+        let Some(repeat) = self.key_repeat.as_mut() else {
+            return;
+        };
+        let now = match repeat.rate.unit {
+            RepeatUnit::Milliseconds => self.started.elapsed().as_millis() as u64,
+            RepeatUnit::Frames => self.frames,
+        };
+        let vm = &mut self.vm;
+        repeat.tick(now, &self.held, |scancode| vm.push_key(scancode, true));
+    }
+
+    fn toggle_recording(&mut self) {
+        match self.recorder.as_mut() {
+            Some(recorder) => {
+                recorder.enabled = !recorder.enabled;
+                let state = if recorder.enabled {
+                    "resumed"
+                } else {
+                    "paused"
+                };
+                log::info!("recording {}", state);
+            }
+            None => self.recorder = start_recording(&self.config.record),
+        }
+    }
+
+    fn toggle_pause(&mut self) {
+        self.set_paused(!self.vm.is_paused());
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        if paused {
+            self.vm.pause();
+        } else {
+            self.vm.resume();
+        }
+        self.update_title();
+    }
+
+    fn update_title(&self) {
+        let Some(window) = self.window.as_ref() else {
+            return;
+        };
+        if self.vm.auto_paused() {
+            window.set_title(&format!("{} (paused while in the background)", TITLE));
+        } else if self.vm.is_paused() {
+            window.set_title(&format!("{} (paused)", TITLE));
+        } else {
+            window.set_title(TITLE);
+        }
+    }
+
+    fn debugger_event(&mut self, event_loop: &ActiveEventLoop, event: WindowEvent) {
+        match event {
+            // Closing either window closes both.
+            WindowEvent::CloseRequested => {
+                log::info!("The debugger was closed; stopping");
+                event_loop.exit();
+            }
+            WindowEvent::KeyboardInput { event, .. } if event.state == ElementState::Pressed => {
+                if let PhysicalKey::Code(key) = event.physical_key {
+                    self.debugger_key(key);
+                    self.render_debugger();
+                }
+            }
+            WindowEvent::RedrawRequested => self.render_debugger(),
+            _ => (),
+        }
+    }
+
+    fn debugger_key(&mut self, key: KeyCode) {
+        let Some(debugger) = self.debugger.as_mut() else {
+            return;
+        };
+        let view = &mut debugger.view;
+        let page = HEXDUMP_ROWS as i16;
+        match key {
+            KeyCode::KeyS | KeyCode::F10 => self.step(),
+            KeyCode::KeyC | KeyCode::F5 => self.set_paused(false),
+            KeyCode::KeyP => self.set_paused(true),
+            KeyCode::KeyB | KeyCode::F9 => {
+                let addr = view.selected(self.vm.cpu());
+                if !self.vm.remove_breakpoint(addr) {
+                    self.vm.add_breakpoint(addr);
+                }
+            }
+            KeyCode::ArrowUp => view.move_cursor(self.vm.cpu(), -1),
+            KeyCode::ArrowDown => view.move_cursor(self.vm.cpu(), 1),
+            KeyCode::PageUp => view.scroll_memory(-page),
+            KeyCode::PageDown => view.scroll_memory(page),
+            KeyCode::Home => view.memory_start = self.vm.cpu().sp & !(HEXDUMP_ROW_BYTES as u16 - 1),
+            _ => (),
+        }
+    }
+
+    // Pauses the program and executes one instruction of it.
+    fn step(&mut self) {
+        self.set_paused(true);
+        if !self.running {
+            return;
+        }
+        match self.vm.step() {
+            Ok(()) if self.vm.halted() => self.running = false,
+            Ok(()) => {}
+            Err(_) => self.running = false,
+        }
+    }
+
+    fn render_debugger(&mut self) {
+        let Some(debugger) = self.debugger.as_mut() else {
+            return;
+        };
+        let lines = debugger.view.lines(&self.vm);
+        paint(&lines, DEBUGGER_WIDTH as usize, debugger.pixels.frame_mut());
+        debugger.pixels.render().unwrap();
+    }
+
+    // Matches the frame buffer to the window, one buffer pixel per physical pixel.
+    fn resize(&mut self, size: PhysicalSize<u32>) {
+        let Some(pixels) = self.pixels.as_mut() else {
+            return;
+        };
+        // A minimized window is 0x0, keep drawing at the old size until it is back.
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+        let resized = pixels
+            .resize_surface(size.width, size.height)
+            .and_then(|()| pixels.resize_buffer(size.width, size.height));
+        if let Err(e) = resized {
+            log::error!(
+                "could not resize the frame to {}x{}: {}",
+                size.width,
+                size.height,
+                e
+            );
+            return;
+        }
+        self.window_size = size;
+        let border = self.config.letterbox.border.is_some();
+        let (width, height) = (self.vm.width(), self.vm.height());
+        self.placement = place(width, height, size.width, size.height, border);
+        // The frame moved under the pointer.
+        self.move_pointer();
+    }
+
+    fn move_pointer(&mut self) {
+        if let Some(cursor) = self.cursor {
+            let placement = self.placement;
+            self.vm
+                .inject_mouse_position(&placement, cursor.x, cursor.y, self.scale_factor);
+        }
+    }
+
     fn render(&mut self) {
         let Some(pixels) = self.pixels.as_mut() else {
             return;
         };
         let frame = pixels.frame_mut();
-
-        if self.video_memory.len() < frame.len() {
-            eprintln!(
-                "Error: Video memory size does not match framebuffer size. Frame size: {}, Video memory size: {}",
+        let PhysicalSize { width, height } = self.window_size;
+        if frame.len() != (width * height * 4) as usize {
+            log::error!(
+                "frame size does not match the window. Frame size: {}, window: {}x{}",
                 frame.len(),
-                self.video_memory.len()
+                width,
+                height
             );
             return;
         }
 
-        for (chunk, color) in frame.chunks_exact_mut(4).zip(&self.video_memory) {
-            let [r, g, b, a] = chunk else { unreachable!() };
-            *r = color.r;
-            *g = color.g;
-            *b = color.b;
-            *a = color.a;
+        let letterbox = self.config.letterbox;
+        self.placement = self
+            .vm
+            .frame_rgba_letterboxed(width, height, &letterbox, frame);
+        if self.overlay_enabled {
+            let mut stats = self.vm.debug_stats();
+            stats.frames_per_second = self.rates.frames_per_second;
+            stats.instructions_per_second = self.rates.instructions_per_second;
+            draw_text(&layout(&stats), width as usize, frame);
         }
 
         pixels.render().unwrap();
     }
 
-    pub fn new(width: u32, height: u32, video_memory: Vec<super::types::Color>) -> Self {
+    pub fn new(mut vm: MicroCvm, config: WindowConfig, input: InputConfig) -> Self {
+        vm.set_crt(Some(config.crt).filter(|_| config.crt_enabled));
+        let recorder = if config.recording {
+            start_recording(&config.record)
+        } else {
+            None
+        };
         Self {
             window: None,
             pixels: None,
-            width,
-            height,
-            video_memory,
+            window_size: PhysicalSize::new(0, 0),
+            scale_factor: 1.0,
+            placement: place(vm.width(), vm.height(), 0, 0, false),
+            cursor: None,
+            present_mode: config.present_mode,
+            error: None,
+            frame_due: Instant::now(),
+            running: !vm.halted(),
+            vm,
+            config,
+            overlay_enabled: false,
+            recorder,
+            rates: Rates::new(),
+            debugger: None,
+            held: Vec::new(),
+            key_repeat: input.key_repeat.map(KeyRepeat::new),
+            input,
+            started: Instant::now(),
+            frames: 0,
+            focused: true,
+            pointer_captured: false,
+            #[cfg(feature = "gamepad")]
+            controllers: match crate::gamepad::Controllers::new() {
+                Ok(controllers) => Some(controllers),
+                Err(e) => {
+                    log::warn!("no gamepad support: {}", e);
+                    None
+                }
+            },
+        }
+    }
+}
+
+fn start_recording(config: &RecordConfig) -> Option<Recorder> {
+    match Recorder::start(config.clone()) {
+        Ok(recorder) => {
+            log::info!("recording to `{}`", config.dir.display());
+            Some(recorder)
+        }
+        Err(e) => {
+            log::error!("could not record to `{}`: {}", config.dir.display(), e);
+            None
         }
     }
 }
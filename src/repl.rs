@@ -0,0 +1,367 @@
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble_instruction;
+use microcvm_rs::coredump::CoreDump;
+use microcvm_rs::cpu::MicroCVMCpu;
+use microcvm_rs::disasm;
+use microcvm_rs::isa;
+use microcvm_rs::program::{MAGIC, Program};
+use microcvm_rs::symbols::SymbolTable;
+
+const HELP: &str = "\
+Type an instruction to assemble it at pc and execute it immediately.
+  :regs                 Show registers, pc and flags
+  :mem <addr> [len]     Hex dump memory (default 16 bytes)
+  :reset                Start over with a fresh machine
+  :load <file>          Load a program image at its load address
+  :symbols <file>       Load a symbol file so addresses can be named
+  :break [addr|name]    Set a breakpoint, or list them
+  :delete <addr|name>   Remove a breakpoint
+  :run [count]          Execute from pc until a breakpoint, halt or fault
+  :dis [addr|name] [n]  Disassemble n instructions (default 8, from pc)
+  :profile [on|off|reset]  Show or control the execution profile
+  :help                 Show this help
+  :quit                 Leave the REPL";
+
+// Instructions `:run` executes when no count is given.
+const DEFAULT_RUN_LIMIT: u64 = 10_000_000;
+
+const READ_ONLY: &str = "read-only: a core dump can be looked at but not run or changed";
+
+struct Session {
+    cpu: MicroCVMCpu,
+    symbols: SymbolTable,
+    breakpoints: BTreeSet<u16>,
+    // Where the last `:load` put the program, to check symbol files against.
+    program: (u16, usize),
+    // Set when inspecting a core dump: nothing may execute or change the machine.
+    read_only: bool,
+}
+
+impl Session {
+    fn new(cpu: MicroCVMCpu, read_only: bool) -> Self {
+        Self {
+            cpu,
+            symbols: SymbolTable::new(),
+            breakpoints: BTreeSet::new(),
+            program: (0, 0),
+            read_only,
+        }
+    }
+}
+
+pub fn run(input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    writeln!(output, "MicroCVM REPL, :help for commands")?;
+    repl(Session::new(MicroCVMCpu::empty(), false), input, output)
+}
+
+// The REPL over the machine in `dump`, stopped at the instruction that faulted.
+pub fn inspect(dump: &CoreDump, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    let mut cpu = MicroCvm::from_core_dump(dump).into_cpu();
+    cpu.pc = dump.pc;
+    writeln!(output, "{}", dump.report)?;
+    writeln!(output, "MicroCVM core dump, read-only, :help for commands")?;
+    repl(Session::new(cpu, true), input, output)
+}
+
+fn repl(mut session: Session, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    write!(output, "{:#06x}> ", session.cpu.pc)?;
+    output.flush()?;
+
+    for line in input.lines() {
+        let line = line?;
+        let line = line.trim();
+
+        match line.strip_prefix(':') {
+            Some(command) => {
+                if !command_line(&mut session, command, &mut output)? {
+                    return Ok(());
+                }
+            }
+            None if line.is_empty() => {}
+            None if session.read_only => writeln!(output, "{}", READ_ONLY)?,
+            None => execute_line(&mut session.cpu, line, &mut output)?,
+        }
+
+        write!(output, "{:#06x}> ", session.cpu.pc)?;
+        output.flush()?;
+    }
+
+    writeln!(output)
+}
+
+fn command_line(session: &mut Session, command: &str, output: &mut impl Write) -> io::Result<bool> {
+    let cpu = &mut session.cpu;
+    let mut words = command.split_whitespace();
+    match words.next().unwrap_or("") {
+        "reset" | "load" | "run" | "profile" if session.read_only => {
+            writeln!(output, "{}", READ_ONLY)?
+        }
+        "regs" => print_registers(cpu, output)?,
+        "mem" => {
+            let addr = words.next().and_then(parse_number);
+            let len = words.next().map_or(Some(16), parse_number);
+            match (addr, len) {
+                (Some(addr), Some(len)) => print_memory(cpu, addr as u16, len as usize, output)?,
+                _ => writeln!(output, "usage: :mem <addr> [len]")?,
+            }
+        }
+        "reset" => {
+            *cpu = MicroCVMCpu::empty();
+            writeln!(output, "machine reset")?;
+        }
+        "load" => match words.next() {
+            Some(path) => match std::fs::read(path) {
+                Ok(bytes) => {
+                    let loaded = if bytes.starts_with(&MAGIC) {
+                        cpu.load_program(&bytes)
+                    } else {
+                        cpu.load_raw(&bytes, 0)
+                    };
+                    match loaded {
+                        Ok(()) => {
+                            session.program = Program::extent(&bytes);
+                            writeln!(output, "loaded {} bytes", bytes.len())?
+                        }
+                        Err(e) => writeln!(output, "error: {}", e)?,
+                    }
+                }
+                Err(e) => writeln!(output, "error: could not read `{}`: {}", path, e)?,
+            },
+            None => writeln!(output, "usage: :load <file>")?,
+        },
+        "profile" => match (words.next(), cpu.profiler_mut()) {
+            (Some("on"), _) => {
+                cpu.enable_profiler();
+                writeln!(output, "profiling on")?;
+            }
+            (Some("off"), _) => {
+                cpu.take_profiler();
+                writeln!(output, "profiling off")?;
+            }
+            (Some("reset"), Some(profiler)) => {
+                profiler.reset();
+                writeln!(output, "profile cleared")?;
+            }
+            (None, Some(profiler)) => writeln!(output, "{}", profiler.report())?,
+            (Some("reset") | None, None) => writeln!(output, "profiling is off, :profile on")?,
+            _ => writeln!(output, "usage: :profile [on|off|reset]")?,
+        },
+        "symbols" => match words.next() {
+            Some(path) => match std::fs::read_to_string(path) {
+                Ok(text) => match SymbolTable::parse(&text) {
+                    Ok((symbols, mut warnings)) => {
+                        let (start, len) = session.program;
+                        if len > 0 {
+                            warnings.extend(symbols.check(start, len));
+                        }
+                        for warning in warnings {
+                            writeln!(output, "warning: {}", warning)?;
+                        }
+                        writeln!(output, "loaded {} symbols", symbols.len())?;
+                        session.symbols = symbols;
+                    }
+                    Err(e) => writeln!(output, "error: `{}`: {}", path, e)?,
+                },
+                Err(e) => writeln!(output, "error: could not read `{}`: {}", path, e)?,
+            },
+            None => writeln!(output, "usage: :symbols <file>")?,
+        },
+        "break" => match words.next() {
+            Some(location) => match resolve_location(&session.symbols, location) {
+                Some(addr) => {
+                    session.breakpoints.insert(addr);
+                    writeln!(output, "breakpoint at {}", describe(&session.symbols, addr))?;
+                }
+                None => writeln!(output, "error: unknown address or symbol `{}`", location)?,
+            },
+            None if session.breakpoints.is_empty() => writeln!(output, "no breakpoints")?,
+            None => {
+                for addr in &session.breakpoints {
+                    writeln!(output, "{}", describe(&session.symbols, *addr))?;
+                }
+            }
+        },
+        "delete" => match words
+            .next()
+            .map(|location| resolve_location(&session.symbols, location))
+        {
+            Some(Some(addr)) if session.breakpoints.remove(&addr) => writeln!(
+                output,
+                "removed breakpoint at {}",
+                describe(&session.symbols, addr)
+            )?,
+            Some(Some(addr)) => writeln!(output, "no breakpoint at {:#06x}", addr)?,
+            Some(None) => writeln!(output, "error: unknown address or symbol")?,
+            None => writeln!(output, "usage: :delete <addr|name>")?,
+        },
+        "run" => match words.next().map_or(Some(DEFAULT_RUN_LIMIT), parse_number) {
+            Some(limit) => run_until_break(session, limit, output)?,
+            None => writeln!(output, "usage: :run [count]")?,
+        },
+        "dis" => {
+            let addr = match words.next() {
+                Some(location) => resolve_location(&session.symbols, location),
+                None => Some(cpu.pc),
+            };
+            let count = words.next().map_or(Some(8), parse_number);
+            match (addr, count) {
+                (Some(addr), Some(count)) => {
+                    print_disassembly(&session.cpu, &session.symbols, addr, count, output)?
+                }
+                _ => writeln!(output, "usage: :dis [addr|name] [n]")?,
+            }
+        }
+        "help" => writeln!(output, "{}", HELP)?,
+        "quit" | "q" | "exit" => return Ok(false),
+        other => writeln!(output, "unknown command `:{}`, see :help", other)?,
+    }
+    Ok(true)
+}
+
+fn execute_line(cpu: &mut MicroCVMCpu, line: &str, output: &mut impl Write) -> io::Result<()> {
+    let opcode = match assemble_instruction(line, cpu.pc, cpu.register_width) {
+        Ok(opcode) => opcode,
+        Err(e) => return writeln!(output, "error: {}", e.message),
+    };
+
+    let bytes = opcode.encode();
+    let start = cpu.translate(cpu.pc);
+    let Some(target) = cpu.memory_mut().get_mut(start..start + bytes.len()) else {
+        return writeln!(output, "error: instruction does not fit in memory at pc");
+    };
+    let overwritten = target.to_vec();
+    target.copy_from_slice(&bytes);
+
+    let registers = cpu.registers;
+    let pc = cpu.pc;
+    let flags = cpu.flags;
+    cpu.halted = false;
+
+    if let Err(e) = cpu.execute_instruction() {
+        // Put back what the instruction overwrote so a fault leaves no trace.
+        cpu.memory_mut()[start..start + bytes.len()].copy_from_slice(&overwritten);
+        return writeln!(output, "fault: {}", e.cause());
+    }
+
+    for (old, (reg, new)) in registers.iter().zip(cpu.registers()) {
+        if *old != new {
+            writeln!(output, "{}: {:#04x} -> {:#04x}", reg, old, new)?;
+        }
+    }
+    if flags != cpu.flags {
+        writeln!(output, "flags: {:#010b} -> {:#010b}", flags, cpu.flags)?;
+    }
+    if cpu.halted {
+        writeln!(output, "halted")?;
+    } else if cpu.pc != pc.wrapping_add(bytes.len() as u16) {
+        writeln!(output, "pc: {:#06x} -> {:#06x}", pc, cpu.pc)?;
+    }
+    Ok(())
+}
+
+// Steps at least once so `:run` continues from a breakpoint it stopped at.
+fn run_until_break(session: &mut Session, limit: u64, output: &mut impl Write) -> io::Result<()> {
+    let cpu = &mut session.cpu;
+    cpu.halted = false;
+    for _ in 0..limit {
+        if let Err(e) = cpu.execute_instruction() {
+            return writeln!(
+                output,
+                "fault at {}: {}",
+                describe(&session.symbols, cpu.pc),
+                e.cause()
+            );
+        }
+        if cpu.halted {
+            return writeln!(output, "halted at {}", describe(&session.symbols, cpu.pc));
+        }
+        if session.breakpoints.contains(&cpu.pc) {
+            return writeln!(output, "break at {}", describe(&session.symbols, cpu.pc));
+        }
+    }
+    writeln!(output, "stopped after {} instructions", limit)
+}
+
+fn print_disassembly(
+    cpu: &MicroCVMCpu,
+    symbols: &SymbolTable,
+    mut addr: u16,
+    count: u64,
+    output: &mut impl Write,
+) -> io::Result<()> {
+    for _ in 0..count {
+        let bytes: Vec<u8> = (0..isa::MAX_LENGTH)
+            .map_while(|offset| cpu.read_mem(addr.wrapping_add(offset)).ok())
+            .collect();
+        if bytes.is_empty() {
+            return writeln!(output, "{:#06x}: out of bounds", addr);
+        }
+        if let Some(name) = symbols.name(addr) {
+            writeln!(output, "{}:", name)?;
+        }
+        let decoded = disasm::decode(&bytes, cpu.register_width)
+            .map(|opcode| {
+                let length = opcode.length();
+                (disasm::format_instruction(&opcode, addr, symbols), length)
+            })
+            .or_else(|| disasm::format_extension(&bytes, cpu.extensions()));
+        let (text, length) = decoded.unwrap_or_else(|| (format!(".db {:#04x}", bytes[0]), 1));
+        writeln!(output, "{:#06x}:  {}", addr, text)?;
+        addr = addr.wrapping_add(length);
+    }
+    Ok(())
+}
+
+fn resolve_location(symbols: &SymbolTable, text: &str) -> Option<u16> {
+    symbols
+        .address(text)
+        .or_else(|| parse_number(text).and_then(|n| u16::try_from(n).ok()))
+}
+
+fn describe(symbols: &SymbolTable, addr: u16) -> String {
+    match symbols.name(addr) {
+        Some(name) => format!("{} ({:#06x})", name, addr),
+        None => format!("{:#06x}", addr),
+    }
+}
+
+fn print_registers(cpu: &MicroCVMCpu, output: &mut impl Write) -> io::Result<()> {
+    for (reg, value) in cpu.registers() {
+        write!(output, "{}={:#04x} ", reg, value)?;
+    }
+    writeln!(
+        output,
+        "\npc={:#06x} sp={:#06x} flags={:#010b} cycles={}",
+        cpu.pc, cpu.sp, cpu.flags, cpu.cycles
+    )
+}
+
+fn print_memory(
+    cpu: &MicroCVMCpu,
+    addr: u16,
+    len: usize,
+    output: &mut impl Write,
+) -> io::Result<()> {
+    for row in 0..len.div_ceil(16) {
+        let row_addr = addr.wrapping_add(row as u16 * 16);
+        write!(output, "{:#06x}:", row_addr)?;
+        for offset in 0..16.min(len - row * 16) {
+            match cpu.read_mem(row_addr.wrapping_add(offset as u16)) {
+                Ok(byte) => write!(output, " {:02x}", byte)?,
+                Err(_) => write!(output, " ??")?,
+            }
+        }
+        writeln!(output)?;
+    }
+    Ok(())
+}
+
+fn parse_number(text: &str) -> Option<u64> {
+    match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
@@ -0,0 +1,144 @@
+use alloc::boxed::Box;
+
+use crate::bus::Device;
+
+pub const RTC_CAPTURE: u8 = 0x00; // any write latches the current time into the registers below
+pub const RTC_SECONDS: u8 = 0x01; // 0-59
+pub const RTC_MINUTES: u8 = 0x02; // 0-59
+pub const RTC_HOURS: u8 = 0x03; // 0-23
+pub const RTC_DAY: u8 = 0x04; // 1-31
+pub const RTC_MONTH: u8 = 0x05; // 1-12
+pub const RTC_YEAR: u8 = 0x06; // 2 bytes, little-endian, e.g. 2024
+pub const RTC_REGISTER_COUNT: u8 = 8;
+
+// Where the RTC gets the time from, in seconds since the Unix epoch. A closure works, which
+// is how tests and recorded runs supply a fixed or replayed time.
+pub trait ClockSource: Send {
+    fn now(&mut self) -> u64;
+}
+
+impl<F: FnMut() -> u64 + Send> ClockSource for F {
+    fn now(&mut self) -> u64 {
+        self()
+    }
+}
+
+#[cfg(feature = "std")]
+pub struct SystemClock;
+
+#[cfg(feature = "std")]
+impl ClockSource for SystemClock {
+    fn now(&mut self) -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs())
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+}
+
+// The guest only ever sees the time latched by the last capture, so reading the fields
+// one at a time can't mix two different seconds.
+pub struct Rtc {
+    clock: Option<Box<dyn ClockSource>>,
+    pub latched: DateTime,
+}
+
+impl DateTime {
+    // UTC. Years past 65535 are not representable and wrap.
+    pub fn from_unix(seconds: u64) -> Self {
+        let days = (seconds / 86_400) as i64;
+        let time = seconds % 86_400;
+
+        // Days to civil date, from Howard Hinnant's date algorithms.
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let day_of_era = z - era * 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_index = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+        let month = if month_index < 10 {
+            month_index + 3
+        } else {
+            month_index - 9
+        };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        Self {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+            hours: (time / 3600) as u8,
+            minutes: (time / 60 % 60) as u8,
+            seconds: (time % 60) as u8,
+        }
+    }
+}
+
+impl Rtc {
+    pub fn new(clock: Option<Box<dyn ClockSource>>) -> Self {
+        Self {
+            clock,
+            latched: DateTime::default(),
+        }
+    }
+
+    pub fn set_clock(&mut self, clock: Option<Box<dyn ClockSource>>) {
+        self.clock = clock;
+    }
+
+    pub fn capture(&mut self) {
+        if let Some(clock) = self.clock.as_mut() {
+            self.latched = DateTime::from_unix(clock.now());
+        }
+    }
+}
+
+impl Device for Rtc {
+    fn read(&mut self, offset: u16) -> u8 {
+        self.peek(offset)
+    }
+
+    fn write(&mut self, offset: u16, _value: u8) {
+        let offset = offset as u8;
+        if offset == RTC_CAPTURE {
+            self.capture();
+        }
+    }
+
+    fn peek(&self, offset: u16) -> u8 {
+        let offset = offset as u8;
+        let time = &self.latched;
+        match offset {
+            RTC_SECONDS => time.seconds,
+            RTC_MINUTES => time.minutes,
+            RTC_HOURS => time.hours,
+            RTC_DAY => time.day,
+            RTC_MONTH => time.month,
+            RTC_YEAR => time.year as u8,
+            0x07 => (time.year >> 8) as u8,
+            _ => 0,
+        }
+    }
+}
+
+// Host time with std. Without it there is no clock until one is set, and every register
+// reads as 0.
+impl Default for Rtc {
+    fn default() -> Self {
+        #[cfg(feature = "std")]
+        return Self::new(Some(Box::new(SystemClock)));
+        #[cfg(not(feature = "std"))]
+        return Self::new(None);
+    }
+}
@@ -0,0 +1,255 @@
+use alloc::vec::Vec;
+
+use crate::error::VmError;
+use crate::program::{FLAG_NO_EXECUTE_DATA, FLAG_PROTECT_CODE, FLAG_SEGMENTED, Program};
+
+// Payload layout for a segmented program: the segment count (u16 LE), then an entry per
+// segment of type (1) | target offset (u32 LE) | length (u32 LE), then the bytes of every
+// segment but bss ones, in table order.
+pub const ENTRY_LEN: usize = 9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentKind {
+    // Instructions, made read-only when the header asks for code protection.
+    Code,
+    Data,
+    // Zeroes its range; the file holds only the length.
+    Bss,
+    // Bytes for video memory rather than memory, in the format of the machine's color
+    // depth.
+    Video,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub kind: SegmentKind,
+    // Into memory, or into video memory for a video segment.
+    pub offset: u32,
+    pub length: u32,
+    // Empty for bss, `length` bytes otherwise.
+    pub bytes: Vec<u8>,
+}
+
+// A program made of segments placed separately, rather than one image copied to its load
+// address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentedProgram {
+    pub entry: u16,
+    pub protect_code: bool,
+    // Marks data and bss segments no-execute once loaded.
+    pub no_execute_data: bool,
+    pub segments: Vec<Segment>,
+}
+
+impl SegmentKind {
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            1 => Some(SegmentKind::Code),
+            2 => Some(SegmentKind::Data),
+            3 => Some(SegmentKind::Bss),
+            4 => Some(SegmentKind::Video),
+            _ => None,
+        }
+    }
+
+    pub fn to_byte(self) -> u8 {
+        match self {
+            SegmentKind::Code => 1,
+            SegmentKind::Data => 2,
+            SegmentKind::Bss => 3,
+            SegmentKind::Video => 4,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SegmentKind::Code => "code",
+            SegmentKind::Data => "data",
+            SegmentKind::Bss => "bss",
+            SegmentKind::Video => "video",
+        }
+    }
+}
+
+impl Segment {
+    // A code, data or video segment holding `bytes`.
+    pub fn new(kind: SegmentKind, offset: u32, bytes: Vec<u8>) -> Self {
+        Self {
+            kind,
+            offset,
+            length: bytes.len() as u32,
+            bytes,
+        }
+    }
+
+    pub fn bss(offset: u32, length: u32) -> Self {
+        Self {
+            kind: SegmentKind::Bss,
+            offset,
+            length,
+            bytes: Vec::new(),
+        }
+    }
+
+    // Exclusive, and wide enough that it can't overflow.
+    pub fn end(&self) -> u64 {
+        self.offset as u64 + self.length as u64
+    }
+}
+
+impl SegmentedProgram {
+    pub fn new(entry: u16) -> Self {
+        Self {
+            entry,
+            protect_code: false,
+            no_execute_data: false,
+            segments: Vec::new(),
+        }
+    }
+
+    pub fn segment(mut self, segment: Segment) -> Self {
+        self.segments.push(segment);
+        self
+    }
+
+    pub fn protect_code(mut self, protect: bool) -> Self {
+        self.protect_code = protect;
+        self
+    }
+
+    pub fn no_execute_data(mut self, no_execute: bool) -> Self {
+        self.no_execute_data = no_execute;
+        self
+    }
+
+    // The program file, with its segment table as the payload of a Program so it can be
+    // compressed like any other.
+    pub fn to_program(&self) -> Program {
+        let mut program = Program::new(0, self.table())
+            .entry(self.entry)
+            .protect_code(self.protect_code);
+        program.header.flags |= FLAG_SEGMENTED;
+        if self.no_execute_data {
+            program.header.flags |= FLAG_NO_EXECUTE_DATA;
+        }
+        program
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        self.to_program().build()
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, VmError> {
+        let program = Program::parse(bytes)?;
+        if !program.header.segmented() {
+            return Err(VmError::InvalidHeader {
+                reason: "program is not segmented",
+            });
+        }
+        Ok(Self {
+            entry: program.header.segmented_entry(),
+            protect_code: program.header.flags & FLAG_PROTECT_CODE != 0,
+            no_execute_data: program.header.no_execute_data(),
+            segments: parse_table(&program.payload)?,
+        })
+    }
+
+    pub fn table(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&(self.segments.len() as u16).to_le_bytes());
+        for segment in &self.segments {
+            bytes.push(segment.kind.to_byte());
+            bytes.extend_from_slice(&segment.offset.to_le_bytes());
+            bytes.extend_from_slice(&segment.length.to_le_bytes());
+        }
+        for segment in &self.segments {
+            bytes.extend_from_slice(&segment.bytes);
+        }
+        bytes
+    }
+}
+
+// The segments a segmented payload describes, in table order.
+pub fn parse_table(payload: &[u8]) -> Result<Vec<Segment>, VmError> {
+    let cut_short = VmError::InvalidHeader {
+        reason: "segment table is cut short",
+    };
+    let Some((&count, mut rest)) = payload.split_first_chunk::<2>() else {
+        return Err(cut_short);
+    };
+    let count = u16::from_le_bytes(count) as usize;
+    let Some((table, data)) = rest.split_at_checked(count * ENTRY_LEN) else {
+        return Err(cut_short);
+    };
+    rest = data;
+
+    let mut segments = Vec::with_capacity(count);
+    for entry in table.chunks_exact(ENTRY_LEN) {
+        let Some(kind) = SegmentKind::from_byte(entry[0]) else {
+            return Err(VmError::InvalidHeader {
+                reason: "unknown segment type",
+            });
+        };
+        let offset = u32::from_le_bytes([entry[1], entry[2], entry[3], entry[4]]);
+        let length = u32::from_le_bytes([entry[5], entry[6], entry[7], entry[8]]);
+        if kind == SegmentKind::Bss {
+            segments.push(Segment::bss(offset, length));
+            continue;
+        }
+        let Some((bytes, data)) = rest.split_at_checked(length as usize) else {
+            return Err(VmError::InvalidHeader {
+                reason: "segment data is cut short",
+            });
+        };
+        rest = data;
+        segments.push(Segment::new(kind, offset, bytes.to_vec()));
+    }
+    if !rest.is_empty() {
+        return Err(VmError::InvalidHeader {
+            reason: "payload runs on past the last segment",
+        });
+    }
+    Ok(segments)
+}
+
+// Refuses segments that run past the end of `memory` or `video` bytes, or that overlap
+// another segment bound for the same place.
+pub fn check_segments(segments: &[Segment], memory: usize, video: usize) -> Result<(), VmError> {
+    for (index, segment) in segments.iter().enumerate() {
+        let capacity = match segment.kind {
+            SegmentKind::Video => video,
+            _ => memory,
+        };
+        if segment.end() > capacity as u64 {
+            return Err(VmError::SegmentOutOfRange {
+                index,
+                kind: segment.kind,
+                offset: segment.offset,
+                length: segment.length,
+                capacity,
+            });
+        }
+    }
+
+    // Sorted by where they start within each target, non-overlapping segments also end in
+    // order, so the first overlap is always with the segment just before.
+    let mut order: Vec<usize> = (0..segments.len())
+        .filter(|&index| segments[index].length > 0)
+        .collect();
+    order.sort_by_key(|&index| {
+        let segment = &segments[index];
+        (segment.kind == SegmentKind::Video, segment.offset)
+    });
+    for pair in order.windows(2) {
+        let (before, after) = (&segments[pair[0]], &segments[pair[1]]);
+        let same_target = (before.kind == SegmentKind::Video) == (after.kind == SegmentKind::Video);
+        if same_target && (after.offset as u64) < before.end() {
+            return Err(VmError::SegmentOverlap {
+                first: pair[0].min(pair[1]),
+                second: pair[0].max(pair[1]),
+                at: after.offset,
+            });
+        }
+    }
+    Ok(())
+}
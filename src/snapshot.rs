@@ -0,0 +1,379 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Display;
+use core::ops::Range;
+
+use crate::types::Color;
+use crate::video::{ColorDepth, VideoMemory};
+use crate::vm::Snapshot;
+
+// How much of a large diff the Display impl prints before summarizing the rest.
+const DISPLAY_RANGES: usize = 16;
+const DISPLAY_BYTES: usize = 8;
+
+// Serialized layout, all little endian: magic, version, the registers, sp, pc, flags, bank,
+// halted, cycles, instructions and call depth, then memory as a u32 length and the bytes,
+// then video memory as its depth in bits, a u32 pixel count, the palette as RGBA and the
+// pixel bytes, which the depth and count fix the length of.
+const MAGIC: [u8; 4] = *b"MCSS";
+pub const SNAPSHOT_VERSION: u8 = 1;
+
+// Why bytes could not be read back as a snapshot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    BadColorDepth(u8),
+    // Cut short, or with bytes left over.
+    BadLength,
+}
+
+// Every difference between two snapshots. An empty diff means the snapshots are equal,
+// down to the cycle counter and every pixel.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SnapshotDiff {
+    // (register index, old, new)
+    pub registers: Vec<(usize, u16, u16)>,
+    pub flags: Option<(u8, u8)>,
+    pub pc: Option<(u16, u16)>,
+    pub sp: Option<(u16, u16)>,
+    pub bank: Option<(u8, u8)>,
+    pub halted: Option<(bool, bool)>,
+    pub cycles: Option<(u64, u64)>,
+    pub instructions: Option<(u64, u64)>,
+    pub call_depth: Option<(u32, u32)>,
+    // Physical memory offsets. Only the common prefix is compared if the sizes differ.
+    pub memory_size: Option<(usize, usize)>,
+    pub memory: Vec<MemoryChange>,
+    // Pixel indices, row by row.
+    pub video_size: Option<(usize, usize)>,
+    pub video: Vec<Range<usize>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryChange {
+    pub start: usize,
+    pub old: Vec<u8>,
+    pub new: Vec<u8>,
+}
+
+impl Snapshot {
+    pub fn diff(&self, other: &Snapshot) -> SnapshotDiff {
+        let registers = self
+            .registers
+            .iter()
+            .zip(other.registers)
+            .enumerate()
+            .filter(|(_, (old, new))| **old != *new)
+            .map(|(index, (old, new))| (index, *old, new))
+            .collect();
+
+        let memory = changed_ranges(self.memory.len().min(other.memory.len()), |i| {
+            self.memory[i] != other.memory[i]
+        })
+        .into_iter()
+        .map(|range| MemoryChange {
+            start: range.start,
+            old: self.memory[range.clone()].to_vec(),
+            new: other.memory[range].to_vec(),
+        })
+        .collect();
+        // Pixels are compared by the color they show, whatever depth and palette produce it.
+        let video = changed_ranges(self.video_memory.len().min(other.video_memory.len()), |i| {
+            self.video_memory.pixel(i).to_rgba() != other.video_memory.pixel(i).to_rgba()
+        });
+
+        SnapshotDiff {
+            registers,
+            flags: changed(self.flags, other.flags),
+            pc: changed(self.pc, other.pc),
+            sp: changed(self.sp, other.sp),
+            bank: changed(self.bank, other.bank),
+            halted: changed(self.halted, other.halted),
+            cycles: changed(self.cycles, other.cycles),
+            instructions: changed(self.instructions, other.instructions),
+            call_depth: changed(self.call_depth, other.call_depth),
+            memory_size: changed(self.memory.len(), other.memory.len()),
+            memory,
+            video_size: changed(self.video_memory.len(), other.video_memory.len()),
+            video,
+        }
+    }
+}
+
+impl Snapshot {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let video = &self.video_memory;
+        let mut bytes = Vec::with_capacity(64 + self.memory.len() + 1024 + video.bytes().len());
+        bytes.extend_from_slice(&MAGIC);
+        bytes.push(SNAPSHOT_VERSION);
+        for register in self.registers {
+            bytes.extend_from_slice(&register.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.sp.to_le_bytes());
+        bytes.extend_from_slice(&self.pc.to_le_bytes());
+        bytes.extend_from_slice(&[self.flags, self.bank, self.halted as u8]);
+        bytes.extend_from_slice(&self.cycles.to_le_bytes());
+        bytes.extend_from_slice(&self.instructions.to_le_bytes());
+        bytes.extend_from_slice(&self.call_depth.to_le_bytes());
+        bytes.extend_from_slice(&(self.memory.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.memory);
+        bytes.push(video.depth.bits());
+        bytes.extend_from_slice(&(video.pixels as u32).to_le_bytes());
+        for color in &video.palette {
+            bytes.extend_from_slice(&[color.r, color.g, color.b, color.a]);
+        }
+        bytes.extend_from_slice(video.bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Snapshot, SnapshotError> {
+        let mut reader = Reader::new(bytes);
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(SnapshotError::BadMagic);
+        }
+        let snapshot = Snapshot::read(&mut reader)?;
+        if !reader.is_empty() {
+            return Err(SnapshotError::BadLength);
+        }
+        Ok(snapshot)
+    }
+
+    // Everything after the magic, for formats that embed a snapshot.
+    pub(crate) fn read(reader: &mut Reader) -> Result<Snapshot, SnapshotError> {
+        let version = reader.u8()?;
+        if version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+        let mut registers = [0; 8];
+        for register in &mut registers {
+            *register = reader.u16()?;
+        }
+        let (sp, pc) = (reader.u16()?, reader.u16()?);
+        let (flags, bank, halted) = (reader.u8()?, reader.u8()?, reader.u8()? != 0);
+        let (cycles, instructions) = (reader.u64()?, reader.u64()?);
+        let call_depth = reader.u32()?;
+        let memory_len = reader.u32()? as usize;
+        let memory = reader.take(memory_len)?.to_vec();
+
+        let bits = reader.u8()?;
+        let depth = ColorDepth::from_bits(bits).ok_or(SnapshotError::BadColorDepth(bits))?;
+        let pixels = reader.u32()? as usize;
+        let mut palette = [Color::new(0, 0, 0); 256];
+        for color in &mut palette {
+            let rgba = reader.take(4)?;
+            *color = Color {
+                r: rgba[0],
+                g: rgba[1],
+                b: rgba[2],
+                a: rgba[3],
+            };
+        }
+        let mut video_memory = VideoMemory::new(pixels, depth);
+        video_memory.palette = palette;
+        video_memory
+            .bytes_mut()
+            .copy_from_slice(reader.take(depth.bytes(pixels))?);
+
+        Ok(Snapshot {
+            registers,
+            sp,
+            pc,
+            flags,
+            bank,
+            halted,
+            cycles,
+            instructions,
+            call_depth,
+            memory,
+            video_memory,
+        })
+    }
+}
+
+// Reads little endian values off the front of a byte slice.
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub(crate) fn take(&mut self, len: usize) -> Result<&'a [u8], SnapshotError> {
+        if self.bytes.len() < len {
+            return Err(SnapshotError::BadLength);
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    pub(crate) fn rest(&mut self) -> &'a [u8] {
+        core::mem::take(&mut self.bytes)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, SnapshotError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16, SnapshotError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, SnapshotError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, SnapshotError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        *self == SnapshotDiff::default()
+    }
+
+    pub fn changed_bytes(&self) -> usize {
+        self.memory.iter().map(|change| change.old.len()).sum()
+    }
+
+    pub fn changed_pixels(&self) -> usize {
+        self.video.iter().map(|range| range.len()).sum()
+    }
+}
+
+impl MemoryChange {
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.start + self.old.len()
+    }
+}
+
+impl Display for SnapshotDiff {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if self.is_empty() {
+            return write!(f, "no changes");
+        }
+
+        let mut lines = Vec::new();
+        for (index, old, new) in &self.registers {
+            lines.push(format!("r{}: {:#04x} -> {:#04x}", index, old, new));
+        }
+        if let Some((old, new)) = self.flags {
+            lines.push(format!("flags: {:#010b} -> {:#010b}", old, new));
+        }
+        if let Some((old, new)) = self.pc {
+            lines.push(format!("pc: {:#06x} -> {:#06x}", old, new));
+        }
+        if let Some((old, new)) = self.sp {
+            lines.push(format!("sp: {:#06x} -> {:#06x}", old, new));
+        }
+        if let Some((old, new)) = self.bank {
+            lines.push(format!("bank: {} -> {}", old, new));
+        }
+        if let Some((old, new)) = self.halted {
+            lines.push(format!("halted: {} -> {}", old, new));
+        }
+        if let Some((old, new)) = self.cycles {
+            lines.push(format!("cycles: {} -> {}", old, new));
+        }
+        if let Some((old, new)) = self.instructions {
+            lines.push(format!("instructions: {} -> {}", old, new));
+        }
+        if let Some((old, new)) = self.call_depth {
+            lines.push(format!("call depth: {} -> {}", old, new));
+        }
+        if let Some((old, new)) = self.memory_size {
+            lines.push(format!("memory size: {} -> {} bytes", old, new));
+        }
+
+        for change in self.memory.iter().take(DISPLAY_RANGES) {
+            let range = change.range();
+            lines.push(format!(
+                "mem[{:#06x}..{:#06x}]: {} -> {}",
+                range.start,
+                range.end,
+                hex_bytes(&change.old),
+                hex_bytes(&change.new)
+            ));
+        }
+        if self.memory.len() > DISPLAY_RANGES {
+            lines.push(format!(
+                "... {} more memory ranges, {} bytes changed in total",
+                self.memory.len() - DISPLAY_RANGES,
+                self.changed_bytes()
+            ));
+        }
+
+        if let Some((old, new)) = self.video_size {
+            lines.push(format!("video size: {} -> {} pixels", old, new));
+        }
+        for range in self.video.iter().take(DISPLAY_RANGES) {
+            lines.push(format!(
+                "video[{}..{}]: {} pixels",
+                range.start,
+                range.end,
+                range.len()
+            ));
+        }
+        if self.video.len() > DISPLAY_RANGES {
+            lines.push(format!(
+                "... {} more video ranges, {} pixels changed in total",
+                self.video.len() - DISPLAY_RANGES,
+                self.changed_pixels()
+            ));
+        }
+
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+fn changed<T: PartialEq>(old: T, new: T) -> Option<(T, T)> {
+    (old != new).then_some((old, new))
+}
+
+// Groups the indices below `len` for which `differs` holds into contiguous ranges.
+fn changed_ranges(len: usize, differs: impl Fn(usize) -> bool) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for i in (0..len).filter(|&i| differs(i)) {
+        match ranges.last_mut() {
+            Some(range) if range.end == i => range.end += 1,
+            _ => ranges.push(i..i + 1),
+        }
+    }
+    ranges
+}
+
+fn hex_bytes(bytes: &[u8]) -> String {
+    let mut text: Vec<String> = bytes
+        .iter()
+        .take(DISPLAY_BYTES)
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    if bytes.len() > DISPLAY_BYTES {
+        text.push(String::from(".."));
+    }
+    text.join(" ")
+}
+
+impl Display for SnapshotError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            SnapshotError::BadMagic => write!(f, "not a snapshot"),
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(f, "unsupported snapshot version {}", version)
+            }
+            SnapshotError::BadColorDepth(bits) => {
+                write!(f, "snapshot has an invalid color depth of {} bits", bits)
+            }
+            SnapshotError::BadLength => write!(f, "snapshot is truncated or padded"),
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use crate::bus::Device;
+
+// Registers of the sprite picked by SPRITE_SELECT, each multi-byte field little-endian.
+pub const SPRITE_SELECT: u8 = 0x00; // which sprite the registers below show, wraps at 16
+pub const SPRITE_FLAGS: u8 = 0x01; // bit 0: the sprite is drawn
+pub const SPRITE_X: u8 = 0x02; // 2 bytes, signed: left edge in pixels
+pub const SPRITE_Y: u8 = 0x04; // 2 bytes, signed: top edge in pixels
+pub const SPRITE_WIDTH: u8 = 0x06; // 0-32 pixels
+pub const SPRITE_HEIGHT: u8 = 0x07; // 0-32 pixels
+pub const SPRITE_SOURCE: u8 = 0x08; // 3 bytes: physical address of packed RGB pixels, row by row
+pub const SPRITE_TRANSPARENT: u8 = 0x0B; // 3 bytes: r, g, b of the color left undrawn
+pub const SPRITE_REGISTER_COUNT: u8 = 14;
+
+pub const SPRITE_ENABLED: u8 = 0x01;
+
+pub const SPRITE_COUNT: usize = 16;
+pub const SPRITE_MAX_SIZE: u8 = 32;
+pub const SPRITE_BYTES_PER_PIXEL: usize = 3;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Sprite {
+    pub enabled: bool,
+    pub x: i16,
+    pub y: i16,
+    pub width: u8,
+    pub height: u8,
+    pub source: u32,
+    pub transparent: [u8; 3],
+}
+
+// Sprites are drawn over the framebuffer when a frame is converted for display, so moving
+// one never touches video memory.
+#[derive(Debug, Default, Clone)]
+pub struct SpriteTable {
+    pub sprites: [Sprite; SPRITE_COUNT],
+    pub selected: u8,
+}
+
+impl SpriteTable {
+    // Draws the enabled sprites over `rgba`, a frame `width` pixels wide that has already
+    // been converted from video memory. Later sprites cover earlier ones. Pixels outside the
+    // frame are clipped, and so are source pixels past the end of `memory`.
+    pub fn composite(&self, memory: &[u8], width: usize, rgba: &mut [u8]) {
+        self.composite_rows(memory, width, 0, rgba);
+    }
+
+    // Like `composite` for a band of the frame whose top row is `first_row`.
+    pub fn composite_rows(&self, memory: &[u8], width: usize, first_row: usize, rgba: &mut [u8]) {
+        if width == 0 {
+            return;
+        }
+        let rows = rgba.len() / 4 / width;
+        let band = first_row..first_row + rows;
+        for sprite in self.sprites.iter().filter(|sprite| sprite.enabled) {
+            for row in 0..sprite.height as usize {
+                let Some(y) = on_screen(sprite.y, row, band.end).filter(|y| band.contains(y))
+                else {
+                    continue;
+                };
+                let y = y - first_row;
+                for column in 0..sprite.width as usize {
+                    let Some(x) = on_screen(sprite.x, column, width) else {
+                        continue;
+                    };
+                    let source = sprite.source as usize
+                        + (row * sprite.width as usize + column) * SPRITE_BYTES_PER_PIXEL;
+                    let Some(rgb) = memory.get(source..source + SPRITE_BYTES_PER_PIXEL) else {
+                        continue;
+                    };
+                    if rgb == sprite.transparent {
+                        continue;
+                    }
+                    let pixel = (y * width + x) * 4;
+                    rgba[pixel..pixel + 4].copy_from_slice(&[rgb[0], rgb[1], rgb[2], 255]);
+                }
+            }
+        }
+    }
+}
+
+impl Device for SpriteTable {
+    fn read(&mut self, offset: u16) -> u8 {
+        self.peek(offset)
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        let offset = offset as u8;
+        if offset == SPRITE_SELECT {
+            self.selected = value % SPRITE_COUNT as u8;
+            return;
+        }
+        let sprite = &mut self.sprites[self.selected as usize];
+        match offset {
+            SPRITE_FLAGS => sprite.enabled = value & SPRITE_ENABLED != 0,
+            SPRITE_X..SPRITE_Y => set_byte(&mut sprite.x, offset - SPRITE_X, value),
+            SPRITE_Y..SPRITE_WIDTH => set_byte(&mut sprite.y, offset - SPRITE_Y, value),
+            SPRITE_WIDTH => sprite.width = value.min(SPRITE_MAX_SIZE),
+            SPRITE_HEIGHT => sprite.height = value.min(SPRITE_MAX_SIZE),
+            SPRITE_SOURCE..SPRITE_TRANSPARENT => {
+                let shift = (offset - SPRITE_SOURCE) * 8;
+                sprite.source = (sprite.source & !(0xFF << shift)) | (value as u32) << shift;
+            }
+            SPRITE_TRANSPARENT..SPRITE_REGISTER_COUNT => {
+                sprite.transparent[(offset - SPRITE_TRANSPARENT) as usize] = value
+            }
+            _ => {}
+        }
+    }
+
+    fn peek(&self, offset: u16) -> u8 {
+        let offset = offset as u8;
+        let sprite = &self.sprites[self.selected as usize];
+        match offset {
+            SPRITE_SELECT => self.selected,
+            SPRITE_FLAGS => sprite.enabled as u8,
+            SPRITE_X..SPRITE_Y => sprite.x.to_le_bytes()[(offset - SPRITE_X) as usize],
+            SPRITE_Y..SPRITE_WIDTH => sprite.y.to_le_bytes()[(offset - SPRITE_Y) as usize],
+            SPRITE_WIDTH => sprite.width,
+            SPRITE_HEIGHT => sprite.height,
+            SPRITE_SOURCE..SPRITE_TRANSPARENT => {
+                (sprite.source >> ((offset - SPRITE_SOURCE) * 8)) as u8
+            }
+            SPRITE_TRANSPARENT..SPRITE_REGISTER_COUNT => {
+                sprite.transparent[(offset - SPRITE_TRANSPARENT) as usize]
+            }
+            _ => 0,
+        }
+    }
+}
+
+fn set_byte(field: &mut i16, byte: u8, value: u8) {
+    let mut bytes = field.to_le_bytes();
+    bytes[byte as usize] = value;
+    *field = i16::from_le_bytes(bytes);
+}
+
+// Where `offset` pixels past `origin` lands on an axis `size` pixels long, if it does.
+fn on_screen(origin: i16, offset: usize, size: usize) -> Option<usize> {
+    usize::try_from(origin as isize + offset as isize)
+        .ok()
+        .filter(|&position| position < size)
+}
@@ -0,0 +1,20 @@
+// The guest standard library: the assembler source modules in stdlib/, built into the crate so
+// `.include "std/<module>"` finds them without any files beside the program. Every routine is
+// called with `call` and follows the calling convention in docs/instruction_set.md: arguments
+// in r0 to r3, results in r0, and r4 to r7 left as the caller had them.
+pub const PREFIX: &str = "std/";
+
+// Each module's name as `.include` takes it, and its source.
+pub const MODULES: [(&str, &str); 4] = [
+    ("std/string.asm", include_str!("../stdlib/string.asm")),
+    ("std/print.asm", include_str!("../stdlib/print.asm")),
+    ("std/video.asm", include_str!("../stdlib/video.asm")),
+    ("std/random.asm", include_str!("../stdlib/random.asm")),
+];
+
+pub fn module(name: &str) -> Option<&'static str> {
+    MODULES
+        .iter()
+        .find(|(module, _)| *module == name)
+        .map(|(_, source)| *source)
+}
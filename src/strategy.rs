@@ -0,0 +1,142 @@
+use alloc::vec::Vec;
+
+use proptest::prelude::*;
+use proptest::sample::select;
+
+use crate::cpu::{Opcode, OpcodeType, Register, RegisterWidth};
+use crate::generate::{Operand, PAIRS, with_operands};
+use crate::isa::{self, OperandKind};
+
+// proptest strategies for the same instructions and programs `generate` builds from
+// arbitrary bytes. These are made of proptest's own strategies rather than of bytes, so a
+// failing case shrinks the way proptest shrinks: whole instructions dropped from a program,
+// then each one's opcode towards the start of the instruction table and its operands
+// towards 0.
+
+fn register() -> impl Strategy<Value = Register> {
+    select(Register::ALL.to_vec())
+}
+
+fn operand(kind: OperandKind, width: RegisterWidth) -> BoxedStrategy<Operand> {
+    let wide = width == RegisterWidth::Sixteen;
+    match kind {
+        OperandKind::Register => register().prop_map(Operand::Register).boxed(),
+        OperandKind::Source if wide => prop_oneof![
+            any::<u16>().prop_map(Operand::WideImmediate),
+            register().prop_map(Operand::Register),
+        ]
+        .boxed(),
+        OperandKind::Source => prop_oneof![
+            any::<u8>().prop_map(Operand::Immediate),
+            register().prop_map(Operand::Register),
+        ]
+        .boxed(),
+        OperandKind::Immediate | OperandKind::StackOffset => {
+            any::<u8>().prop_map(Operand::Immediate).boxed()
+        }
+        OperandKind::Bit => (0..width.bits()).prop_map(Operand::Immediate).boxed(),
+        OperandKind::Address => any::<u16>().prop_map(Operand::Address).boxed(),
+        OperandKind::Offset => any::<i8>().prop_map(Operand::Offset).boxed(),
+        OperandKind::Fixed => any::<u16>().prop_map(Operand::WideImmediate).boxed(),
+        OperandKind::Pair => select(PAIRS.to_vec()).prop_map(Operand::Register).boxed(),
+        OperandKind::Pointer if !wide => select(PAIRS.to_vec()).prop_map(Operand::Register).boxed(),
+        OperandKind::Pointer => register().prop_map(Operand::Register).boxed(),
+        OperandKind::VideoOperation => {
+            let operations = isa::video_operation_table()
+                .iter()
+                .map(|info| info.operation as u8)
+                .collect::<Vec<_>>();
+            select(operations).prop_map(Operand::Immediate).boxed()
+        }
+    }
+}
+
+fn instruction_of(opcodes: Vec<OpcodeType>, width: RegisterWidth) -> impl Strategy<Value = Opcode> {
+    select(opcodes).prop_flat_map(move |opcode_type| {
+        let operands: Vec<_> = isa::info(opcode_type)
+            .operands
+            .iter()
+            .map(|&kind| operand(kind, width))
+            .collect();
+        operands.prop_map(move |operands| with_operands(opcode_type, operands))
+    })
+}
+
+/// Any instruction a CPU with `width` registers could decode, the same ones
+/// [`generate::opcode`](crate::generate::opcode) builds.
+///
+/// ```
+/// use microcvm_rs::cpu::RegisterWidth;
+/// use microcvm_rs::disasm::decode;
+/// use microcvm_rs::strategy::any_opcode;
+/// use proptest::prelude::*;
+///
+/// proptest!(|(opcode in any_opcode(RegisterWidth::Sixteen))| {
+///     prop_assert_eq!(decode(&opcode.encode(), RegisterWidth::Sixteen), Some(opcode));
+/// });
+/// ```
+pub fn any_opcode(width: RegisterWidth) -> impl Strategy<Value = Opcode> {
+    let opcodes = isa::instruction_table()
+        .iter()
+        .map(|info| info.opcode)
+        .collect();
+    instruction_of(opcodes, width)
+}
+
+/// Programs of at most `max_len` bytes for 8-bit registers, valid instructions one after
+/// another ending in `hlt`; see [`any_valid_program_for`].
+pub fn any_valid_program(max_len: usize) -> impl Strategy<Value = Vec<u8>> {
+    any_valid_program_for(max_len, RegisterWidth::Eight)
+}
+
+/// Like [`generate::valid_program`](crate::generate::valid_program), as a strategy: at most
+/// `max_len` bytes of valid instructions for `width` registers, with the only `hlt` on the
+/// end. A failing program shrinks to the few instructions that make it fail.
+///
+/// # Panics
+/// If `max_len` is 0, which leaves no room for the `hlt`.
+///
+/// ```
+/// use microcvm_rs::cpu::{OpcodeType, RegisterWidth};
+/// use microcvm_rs::disasm::decode;
+/// use microcvm_rs::strategy::any_valid_program;
+/// use proptest::prelude::*;
+///
+/// proptest!(|(program in any_valid_program(32))| {
+///     prop_assert!(program.len() <= 32);
+///     let mut offset = 0;
+///     loop {
+///         let opcode = decode(&program[offset..], RegisterWidth::Eight).unwrap();
+///         offset += opcode.length() as usize;
+///         if opcode.opcode_type == OpcodeType::Hlt {
+///             break;
+///         }
+///     }
+///     prop_assert_eq!(offset, program.len());
+/// });
+/// ```
+pub fn any_valid_program_for(
+    max_len: usize,
+    width: RegisterWidth,
+) -> impl Strategy<Value = Vec<u8>> {
+    assert!(max_len > 0, "a program needs a byte for its hlt");
+    let room = max_len - 1;
+    let opcodes = isa::instruction_table()
+        .iter()
+        .map(|info| info.opcode)
+        .filter(|&opcode| opcode != OpcodeType::Hlt)
+        .collect();
+    // Every instruction is at least a byte, so no more than `room` of them can fit.
+    proptest::collection::vec(instruction_of(opcodes, width), 0..=room).prop_map(move |opcodes| {
+        let mut program = Vec::with_capacity(room + 1);
+        for opcode in opcodes {
+            let bytes = opcode.encode();
+            if program.len() + bytes.len() > room {
+                break;
+            }
+            program.extend_from_slice(&bytes);
+        }
+        program.push(OpcodeType::Hlt as u8);
+        program
+    })
+}
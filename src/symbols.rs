@@ -0,0 +1,168 @@
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::Display;
+
+// Labels and the addresses they resolved to. An address with several labels is shown
+// under the first one defined.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolTable {
+    by_name: BTreeMap<String, u16>,
+    by_address: BTreeMap<u16, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolError {
+    pub line: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SymbolWarning {
+    Duplicate { name: String, line: usize },
+    OutsideProgram { name: String, address: u16 },
+}
+
+impl SymbolTable {
+    pub const fn new() -> Self {
+        Self {
+            by_name: BTreeMap::new(),
+            by_address: BTreeMap::new(),
+        }
+    }
+
+    // Returns false, keeping the existing address, if `name` is already defined.
+    pub fn insert(&mut self, name: &str, address: u16) -> bool {
+        if self.by_name.contains_key(name) {
+            return false;
+        }
+        self.by_name.insert(name.to_string(), address);
+        self.by_address
+            .entry(address)
+            .or_insert_with(|| name.to_string());
+        true
+    }
+
+    pub fn address(&self, name: &str) -> Option<u16> {
+        self.by_name.get(name).copied()
+    }
+
+    pub fn name(&self, address: u16) -> Option<&str> {
+        self.by_address.get(&address).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_name.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_name.is_empty()
+    }
+
+    // Sorted by address, then by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, u16)> {
+        let mut symbols: Vec<_> = self
+            .by_name
+            .iter()
+            .map(|(name, address)| (name.as_str(), *address))
+            .collect();
+        symbols.sort_by_key(|(name, address)| (*address, *name));
+        symbols.into_iter()
+    }
+
+    // Reads `name = address` lines. `;` starts a comment and blank lines are skipped.
+    pub fn parse(text: &str) -> Result<(Self, Vec<SymbolWarning>), SymbolError> {
+        let mut table = Self::new();
+        let mut warnings = Vec::new();
+
+        for (index, raw) in text.lines().enumerate() {
+            let line = index + 1;
+            let text = raw.split(';').next().unwrap_or("").trim();
+            if text.is_empty() {
+                continue;
+            }
+            let Some((name, value)) = text.split_once('=') else {
+                return Err(symbol_error(line, "expected `name = address`"));
+            };
+            let (name, value) = (name.trim(), value.trim());
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                return Err(symbol_error(
+                    line,
+                    format!("invalid symbol name `{}`", name),
+                ));
+            }
+            let address = match value.strip_prefix("0x").or(value.strip_prefix("0X")) {
+                Some(hex) => u16::from_str_radix(hex, 16),
+                None => value.parse(),
+            }
+            .map_err(|_| symbol_error(line, format!("invalid address `{}`", value)))?;
+
+            if !table.insert(name, address) {
+                warnings.push(SymbolWarning::Duplicate {
+                    name: name.to_string(),
+                    line,
+                });
+            }
+        }
+
+        Ok((table, warnings))
+    }
+
+    pub fn to_sidecar(&self) -> String {
+        let mut text = String::new();
+        for (name, address) in self.iter() {
+            text.push_str(&format!("{} = {:#06x}\n", name, address));
+        }
+        text
+    }
+
+    // Symbols outside a `len` byte program loaded at `start`. A label right after the
+    // last byte is fine, it usually marks the end.
+    pub fn check(&self, start: u16, len: usize) -> Vec<SymbolWarning> {
+        let end = start as usize + len;
+        self.iter()
+            .filter(|(_, address)| !(start as usize..=end).contains(&(*address as usize)))
+            .map(|(name, address)| SymbolWarning::OutsideProgram {
+                name: name.to_string(),
+                address,
+            })
+            .collect()
+    }
+}
+
+impl Display for SymbolError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl core::error::Error for SymbolError {}
+
+impl Display for SymbolWarning {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            SymbolWarning::Duplicate { name, line } => {
+                write!(
+                    f,
+                    "line {}: `{}` is defined twice, keeping the first",
+                    line, name
+                )
+            }
+            SymbolWarning::OutsideProgram { name, address } => {
+                write!(
+                    f,
+                    "`{}` points outside the program ({:#06x})",
+                    name, address
+                )
+            }
+        }
+    }
+}
+
+fn symbol_error(line: usize, message: impl Into<String>) -> SymbolError {
+    SymbolError {
+        line,
+        message: message.into(),
+    }
+}
@@ -0,0 +1,230 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::bus::Device;
+use crate::video::Rect;
+
+pub const TILE_SET: u8 = 0x00; // 3 bytes: physical address of the tile set
+pub const TILE_MAP: u8 = 0x03; // 3 bytes: physical address of the map, one tile index per byte
+pub const TILE_SCROLL_X: u8 = 0x06; // 2 bytes: map pixel shown at the left edge of the screen
+pub const TILE_SCROLL_Y: u8 = 0x08; // 2 bytes: map pixel shown at the top edge of the screen
+pub const TILE_REGISTER_COUNT: u8 = 10;
+
+// Tiles are 8x8 packed RGB pixels, row by row, one after another in the tile set.
+pub const TILE_SIZE: usize = 8;
+pub const TILE_BYTES: usize = TILE_SIZE * TILE_SIZE * 3;
+pub const TILE_COUNT: usize = 256;
+
+// Cells of the map in each direction: enough to cover `pixels`, plus one so a scrolled
+// screen always has a tile to show at the far edge.
+pub fn map_cells(pixels: usize) -> usize {
+    pixels.div_ceil(TILE_SIZE) + 1
+}
+
+// Replaces the framebuffer with the map while the tile mode bit of the video control
+// register is set. The map is expanded into a cached image that is only redrawn where a
+// map cell or the tile it shows changed since the previous frame.
+#[derive(Debug, Default)]
+pub struct TileLayer {
+    pub tile_set: u32,
+    pub map: u32,
+    pub scroll_x: u16,
+    pub scroll_y: u16,
+    cache: TileCache,
+}
+
+#[derive(Debug, Default)]
+struct TileCache {
+    tile_set: u32,
+    map: u32,
+    columns: usize,
+    rows: usize,
+    // The tile index each cell was drawn with, `None` until it has been drawn.
+    cells: Vec<Option<u8>>,
+    tiles: Vec<u8>,
+    rgba: Vec<u8>,
+    redrawn: Vec<usize>,
+    // The scroll the frame was last drawn at, and what of the screen changed since.
+    scroll: (u16, u16),
+    dirty: Option<Rect>,
+}
+
+impl TileLayer {
+    // Cells redrawn by the last call to `render`, as indices into the map.
+    pub fn redrawn_cells(&self) -> &[usize] {
+        &self.cache.redrawn
+    }
+
+    // The part of the screen the last call to `update` changed: the whole of it after a
+    // scroll, otherwise where the redrawn cells show. `None` if nothing changed.
+    pub fn dirty(&self) -> Option<Rect> {
+        self.cache.dirty
+    }
+
+    // Draws the map into `rgba`, a `width` by `height` frame, scrolled by the scroll
+    // registers and wrapping around at the edges of the map. Bytes past the end of
+    // `memory` read as 0.
+    pub fn render(&mut self, memory: &[u8], width: usize, height: usize, rgba: &mut [u8]) {
+        self.update(memory, width, height);
+        self.draw_rows(0, width, height, rgba);
+    }
+
+    // Brings the cached image up to date with `memory` for a `width` by `height` frame,
+    // which `draw_rows` then copies from.
+    pub fn update(&mut self, memory: &[u8], width: usize, height: usize) {
+        let cache = &mut self.cache;
+        let (columns, rows) = (map_cells(width), map_cells(height));
+        if (cache.tile_set, cache.map, cache.columns, cache.rows)
+            != (self.tile_set, self.map, columns, rows)
+        {
+            *cache = TileCache {
+                tile_set: self.tile_set,
+                map: self.map,
+                columns,
+                rows,
+                cells: vec![None; columns * rows],
+                tiles: vec![0; TILE_COUNT * TILE_BYTES],
+                rgba: vec![0; columns * rows * TILE_SIZE * TILE_SIZE * 4],
+                redrawn: Vec::new(),
+                scroll: (self.scroll_x, self.scroll_y),
+                dirty: None,
+            };
+        }
+        let scrolled = cache.scroll != (self.scroll_x, self.scroll_y);
+        cache.scroll = (self.scroll_x, self.scroll_y);
+
+        // A tile whose pixels changed has to be redrawn in every cell that shows it.
+        let mut changed = [false; TILE_COUNT];
+        for (tile, cached) in cache.tiles.chunks_mut(TILE_BYTES).enumerate() {
+            let start = self.tile_set as usize + tile * TILE_BYTES;
+            for (i, cached) in cached.iter_mut().enumerate() {
+                let byte = byte_at(memory, start + i);
+                if *cached != byte {
+                    *cached = byte;
+                    changed[tile] = true;
+                }
+            }
+        }
+
+        cache.redrawn.clear();
+        let map_width = columns * TILE_SIZE;
+        for cell in 0..columns * rows {
+            let tile = byte_at(memory, self.map as usize + cell);
+            if cache.cells[cell] == Some(tile) && !changed[tile as usize] {
+                continue;
+            }
+            cache.cells[cell] = Some(tile);
+            cache.redrawn.push(cell);
+            let (left, top) = ((cell % columns) * TILE_SIZE, (cell / columns) * TILE_SIZE);
+            let pixels = &cache.tiles[tile as usize * TILE_BYTES..][..TILE_BYTES];
+            for (row, rgb) in pixels.chunks(TILE_SIZE * 3).enumerate() {
+                let start = ((top + row) * map_width + left) * 4;
+                let out = &mut cache.rgba[start..start + TILE_SIZE * 4];
+                for (out, rgb) in out.chunks_mut(4).zip(rgb.chunks(3)) {
+                    out.copy_from_slice(&[rgb[0], rgb[1], rgb[2], 255]);
+                }
+            }
+        }
+
+        cache.dirty = if scrolled {
+            Some(Rect::new(0, 0, width as u32, height as u32)).filter(|rect| !rect.is_empty())
+        } else {
+            let (scroll_x, scroll_y) = (self.scroll_x as usize, self.scroll_y as usize);
+            cache
+                .redrawn
+                .iter()
+                .fold(None, |dirty: Option<Rect>, &cell| {
+                    let x = on_screen(cell % columns * TILE_SIZE, scroll_x, columns, width);
+                    let y = on_screen(cell / columns * TILE_SIZE, scroll_y, rows, height);
+                    let Some(((x, width), (y, height))) = x.zip(y) else {
+                        return dirty;
+                    };
+                    let rect = Rect::new(x as u32, y as u32, width as u32, height as u32);
+                    Some(dirty.map_or(rect, |dirty| dirty.union(rect)))
+                })
+        };
+    }
+
+    // Draws rows of the frame `update` was last called for into `rgba`, starting at row
+    // `first_row`, so separate bands of one frame can be drawn at the same time.
+    pub fn draw_rows(&self, first_row: usize, width: usize, height: usize, rgba: &mut [u8]) {
+        let cache = &self.cache;
+        let map_width = cache.columns * TILE_SIZE;
+        let map_height = cache.rows * TILE_SIZE;
+        if map_width == 0 || map_height == 0 {
+            return;
+        }
+        let rows = height.saturating_sub(first_row);
+        for (y, out) in rgba.chunks_mut(width * 4).take(rows).enumerate() {
+            let map_y = (first_row + y + self.scroll_y as usize) % map_height;
+            let line = &cache.rgba[map_y * map_width * 4..][..map_width * 4];
+            for (x, out) in out.chunks_mut(4).enumerate() {
+                let map_x = (x + self.scroll_x as usize) % map_width;
+                out.copy_from_slice(&line[map_x * 4..map_x * 4 + 4]);
+            }
+        }
+    }
+}
+
+impl Device for TileLayer {
+    fn read(&mut self, offset: u16) -> u8 {
+        self.peek(offset)
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        let offset = offset as u8;
+        match offset {
+            TILE_SET..TILE_MAP => set_byte(&mut self.tile_set, offset - TILE_SET, value),
+            TILE_MAP..TILE_SCROLL_X => set_byte(&mut self.map, offset - TILE_MAP, value),
+            TILE_SCROLL_X..TILE_SCROLL_Y => {
+                let mut bytes = self.scroll_x.to_le_bytes();
+                bytes[(offset - TILE_SCROLL_X) as usize] = value;
+                self.scroll_x = u16::from_le_bytes(bytes);
+            }
+            TILE_SCROLL_Y..TILE_REGISTER_COUNT => {
+                let mut bytes = self.scroll_y.to_le_bytes();
+                bytes[(offset - TILE_SCROLL_Y) as usize] = value;
+                self.scroll_y = u16::from_le_bytes(bytes);
+            }
+            _ => {}
+        }
+    }
+
+    fn peek(&self, offset: u16) -> u8 {
+        let offset = offset as u8;
+        match offset {
+            TILE_SET..TILE_MAP => (self.tile_set >> ((offset - TILE_SET) * 8)) as u8,
+            TILE_MAP..TILE_SCROLL_X => (self.map >> ((offset - TILE_MAP) * 8)) as u8,
+            TILE_SCROLL_X..TILE_SCROLL_Y => {
+                self.scroll_x.to_le_bytes()[(offset - TILE_SCROLL_X) as usize]
+            }
+            TILE_SCROLL_Y..TILE_REGISTER_COUNT => {
+                self.scroll_y.to_le_bytes()[(offset - TILE_SCROLL_Y) as usize]
+            }
+            _ => 0,
+        }
+    }
+}
+
+fn set_byte(field: &mut u32, byte: u8, value: u8) {
+    let shift = byte * 8;
+    *field = (*field & !(0xFF << shift)) | (value as u32) << shift;
+}
+
+// Where the tile starting at map pixel `start` shows along one axis of a `screen` pixel
+// frame scrolled by `scroll`, as a start and a length. The map is at least a tile longer than
+// the screen, so a tile that wraps around the end of the map has its first part off screen
+// and shows in one piece at most.
+fn on_screen(start: usize, scroll: usize, cells: usize, screen: usize) -> Option<(usize, usize)> {
+    let map = cells * TILE_SIZE;
+    let first = (start + map - scroll % map) % map;
+    if first + TILE_SIZE > map {
+        let len = (first + TILE_SIZE - map).min(screen);
+        return Some((0, len)).filter(|_| len > 0);
+    }
+    (first < screen).then(|| (first, TILE_SIZE.min(screen - first)))
+}
+
+fn byte_at(memory: &[u8], addr: usize) -> u8 {
+    memory.get(addr).copied().unwrap_or(0)
+}
@@ -0,0 +1,111 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::Display;
+
+use crate::cpu::Opcode;
+use crate::disasm;
+use crate::symbols::SymbolTable;
+
+#[derive(Debug, Clone, Copy)]
+pub struct TraceEntry<'a> {
+    pub pc: u16,
+    pub cycles: u64,
+    pub opcode: &'a Opcode,
+    pub registers: &'a [u16; 8],
+}
+
+// A TraceEntry that owns what it shows, for keeping after the instruction has run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceRecord {
+    pub pc: u16,
+    pub cycles: u64,
+    pub opcode: Opcode,
+    pub registers: [u16; 8],
+}
+
+impl TraceRecord {
+    pub fn entry(&self) -> TraceEntry<'_> {
+        TraceEntry {
+            pc: self.pc,
+            cycles: self.cycles,
+            opcode: &self.opcode,
+            registers: &self.registers,
+        }
+    }
+}
+
+impl From<&TraceEntry<'_>> for TraceRecord {
+    fn from(entry: &TraceEntry) -> Self {
+        Self {
+            pc: entry.pc,
+            cycles: entry.cycles,
+            opcode: *entry.opcode,
+            registers: *entry.registers,
+        }
+    }
+}
+
+impl Display for TraceRecord {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.entry().fmt(f)
+    }
+}
+
+pub trait TraceSink: Send {
+    fn trace(&mut self, entry: &TraceEntry);
+}
+
+// Prints each entry, naming addresses and jump targets from `symbols` when it has any.
+#[cfg(feature = "std")]
+#[derive(Default)]
+pub struct StderrTrace {
+    pub symbols: SymbolTable,
+}
+
+#[cfg(feature = "std")]
+impl StderrTrace {
+    pub fn with_symbols(symbols: SymbolTable) -> Self {
+        Self { symbols }
+    }
+}
+
+#[cfg(feature = "std")]
+impl TraceSink for StderrTrace {
+    fn trace(&mut self, entry: &TraceEntry) {
+        if self.symbols.is_empty() {
+            eprintln!("{}", entry);
+        } else {
+            eprintln!("{}", entry.to_string_with(&self.symbols));
+        }
+    }
+}
+
+impl TraceEntry<'_> {
+    // The Display output with the instruction disassembled against `symbols`.
+    pub fn to_string_with(&self, symbols: &SymbolTable) -> String {
+        self.format(&disasm::format_instruction(self.opcode, self.pc, symbols))
+    }
+
+    fn format(&self, text: &str) -> String {
+        let bytes: Vec<String> = self
+            .opcode
+            .encode()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        format!(
+            "{:#06x}  {:<12} {:<20} {:?}",
+            self.pc,
+            bytes.join(" "),
+            text,
+            self.registers
+        )
+    }
+}
+
+impl Display for TraceEntry<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(&self.format(&self.opcode.to_string()))
+    }
+}
@@ -1,4 +1,4 @@
-#[derive(Clone, Debug, Copy)]
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
 pub struct Color {
     pub r: u8,
     pub g: u8,
@@ -10,4 +10,31 @@ impl Color {
     pub const fn new(r: u8, g: u8, b: u8) -> Self {
         Self { r, g, b, a: 255 }
     }
+
+    // `rrggbb` in hex, with or without a leading `#`, opaque.
+    pub fn from_hex(text: &str) -> Option<Self> {
+        let hex = text.strip_prefix('#').unwrap_or(text);
+        if hex.len() != 6 || !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return None;
+        }
+        let [_, r, g, b] = u32::from_str_radix(hex, 16).ok()?.to_be_bytes();
+        Some(Self::new(r, g, b))
+    }
+
+    pub const fn to_rgba(self) -> [u8; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+
+    // Each channel times `brightness / 255`, rounded to the nearest value. Alpha is kept.
+    pub const fn scale_brightness(self, brightness: u8) -> Self {
+        const fn scale(channel: u8, brightness: u8) -> u8 {
+            ((channel as u16 * brightness as u16 + 127) / 255) as u8
+        }
+        Self {
+            r: scale(self.r, brightness),
+            g: scale(self.g, brightness),
+            b: scale(self.b, brightness),
+            a: self.a,
+        }
+    }
 }
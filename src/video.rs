@@ -0,0 +1,281 @@
+use alloc::boxed::Box;
+use alloc::vec;
+use core::fmt::Display;
+
+use crate::types::Color;
+
+// Bits per pixel of video memory. At 4 and 8 bits a pixel is an index into the palette, at
+// 1 bit it is black or white, at 24 it is red, green and blue.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorDepth {
+    One,
+    Four,
+    Eight,
+    #[default]
+    TwentyFour,
+}
+
+impl ColorDepth {
+    pub fn bits(self) -> u8 {
+        match self {
+            ColorDepth::One => 1,
+            ColorDepth::Four => 4,
+            ColorDepth::Eight => 8,
+            ColorDepth::TwentyFour => 24,
+        }
+    }
+
+    pub fn from_bits(bits: u8) -> Option<Self> {
+        match bits {
+            1 => Some(ColorDepth::One),
+            4 => Some(ColorDepth::Four),
+            8 => Some(ColorDepth::Eight),
+            24 => Some(ColorDepth::TwentyFour),
+            _ => None,
+        }
+    }
+
+    // Bytes it takes to hold `pixels`, the last byte padded at depths below 8.
+    pub fn bytes(self, pixels: usize) -> usize {
+        (pixels * self.bits() as usize).div_ceil(8)
+    }
+}
+
+impl Display for ColorDepth {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} bpp", self.bits())
+    }
+}
+
+// The xterm colors: the 16 standard ones, a 6x6x6 cube and 24 grays.
+pub fn default_palette() -> [Color; 256] {
+    const STANDARD: [[u8; 3]; 16] = [
+        [0, 0, 0],
+        [128, 0, 0],
+        [0, 128, 0],
+        [128, 128, 0],
+        [0, 0, 128],
+        [128, 0, 128],
+        [0, 128, 128],
+        [192, 192, 192],
+        [128, 128, 128],
+        [255, 0, 0],
+        [0, 255, 0],
+        [255, 255, 0],
+        [0, 0, 255],
+        [255, 0, 255],
+        [0, 255, 255],
+        [255, 255, 255],
+    ];
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    core::array::from_fn(|index| match index {
+        0..16 => {
+            let [r, g, b] = STANDARD[index];
+            Color::new(r, g, b)
+        }
+        16..232 => {
+            let cube = index - 16;
+            Color::new(LEVELS[cube / 36], LEVELS[cube / 6 % 6], LEVELS[cube % 6])
+        }
+        _ => {
+            let gray = 8 + (index - 232) as u8 * 10;
+            Color::new(gray, gray, gray)
+        }
+    })
+}
+
+// A rectangle of screen pixels, `width` by `height` from its top left corner at `x, y`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    // The smallest rectangle holding both. An empty one adds nothing.
+    pub fn union(self, other: Rect) -> Rect {
+        if other.is_empty() {
+            return self;
+        }
+        if self.is_empty() {
+            return other;
+        }
+        let (x, y) = (self.x.min(other.x), self.y.min(other.y));
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+        Rect::new(x, y, right - x, bottom - y)
+    }
+}
+
+impl Display for Rect {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{}x{} at {}, {}",
+            self.width, self.height, self.x, self.y
+        )
+    }
+}
+
+// A brightness change spread over `steps` presented frames, started by `video fadeto`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fade {
+    pub target: u8,
+    pub steps: u8,
+}
+
+impl Fade {
+    // The brightness one frame on from `brightness`, and the fade left after it, if any.
+    // Each step covers an equal share of the distance still to go.
+    pub fn step(self, brightness: u8) -> (u8, Option<Fade>) {
+        if self.steps <= 1 {
+            return (self.target, None);
+        }
+        let distance = self.target as i32 - brightness as i32;
+        let steps = self.steps as i32;
+        let delta = (2 * distance + distance.signum() * steps) / (2 * steps);
+        let rest = Fade {
+            target: self.target,
+            steps: self.steps - 1,
+        };
+        ((brightness as i32 + delta) as u8, Some(rest))
+    }
+}
+
+// The framebuffer, packed at its color depth. Pixels below 8 bits share a byte, the
+// leftmost pixel in the high bits; 24-bit pixels are red, green, blue.
+#[derive(Debug, Clone)]
+pub struct VideoMemory {
+    pub depth: ColorDepth,
+    pub pixels: usize,
+    // Allocated once for the depth, see `bytes` and `bytes_mut`.
+    bytes: Box<[u8]>,
+    pub palette: [Color; 256],
+}
+
+impl VideoMemory {
+    pub fn new(pixels: usize, depth: ColorDepth) -> Self {
+        Self {
+            depth,
+            pixels,
+            bytes: vec![0; depth.bytes(pixels)].into_boxed_slice(),
+            palette: default_palette(),
+        }
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.pixels
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pixels == 0
+    }
+
+    // Switching depth clears the screen, the old contents mean nothing at the new one.
+    pub fn set_depth(&mut self, depth: ColorDepth) {
+        self.depth = depth;
+        self.bytes = vec![0; depth.bytes(self.pixels)].into_boxed_slice();
+    }
+
+    // What a video operation stores for the color operands `r, g, b`: the color itself at
+    // 24 bits, otherwise `r` on its own, as a palette index or as 0 for black and 1 for white.
+    pub fn encode(&self, r: u8, g: u8, b: u8) -> u32 {
+        match self.depth {
+            ColorDepth::One => r as u32 & 0x01,
+            ColorDepth::Four => r as u32 & 0x0F,
+            ColorDepth::Eight => r as u32,
+            ColorDepth::TwentyFour => u32::from_le_bytes([r, g, b, 0]),
+        }
+    }
+
+    // The stored value of `pixel`, as `encode` returns it.
+    pub fn raw(&self, pixel: usize) -> u32 {
+        match self.depth {
+            ColorDepth::One => (self.bytes[pixel / 8] >> (7 - pixel % 8)) as u32 & 0x01,
+            ColorDepth::Four => (self.bytes[pixel / 2] >> (4 - pixel % 2 * 4)) as u32 & 0x0F,
+            ColorDepth::Eight => self.bytes[pixel] as u32,
+            ColorDepth::TwentyFour => {
+                let rgb = &self.bytes[pixel * 3..pixel * 3 + 3];
+                u32::from_le_bytes([rgb[0], rgb[1], rgb[2], 0])
+            }
+        }
+    }
+
+    pub fn set_raw(&mut self, pixel: usize, value: u32) {
+        match self.depth {
+            ColorDepth::One => {
+                let shift = 7 - pixel % 8;
+                let byte = &mut self.bytes[pixel / 8];
+                *byte = (*byte & !(1 << shift)) | ((value as u8 & 0x01) << shift);
+            }
+            ColorDepth::Four => {
+                let shift = 4 - pixel % 2 * 4;
+                let byte = &mut self.bytes[pixel / 2];
+                *byte = (*byte & !(0x0F << shift)) | ((value as u8 & 0x0F) << shift);
+            }
+            ColorDepth::Eight => self.bytes[pixel] = value as u8,
+            ColorDepth::TwentyFour => {
+                let [r, g, b, _] = value.to_le_bytes();
+                self.bytes[pixel * 3..pixel * 3 + 3].copy_from_slice(&[r, g, b]);
+            }
+        }
+    }
+
+    // Sets every pixel in `start..end` to `value`.
+    pub fn fill(&mut self, start: usize, end: usize, value: u32) {
+        match self.depth {
+            ColorDepth::Eight => self.bytes[start..end].fill(value as u8),
+            ColorDepth::TwentyFour => {
+                let [r, g, b, _] = value.to_le_bytes();
+                for rgb in self.bytes[start * 3..end * 3].chunks_exact_mut(3) {
+                    rgb.copy_from_slice(&[r, g, b]);
+                }
+            }
+            _ => (start..end).for_each(|pixel| self.set_raw(pixel, value)),
+        }
+    }
+
+    pub fn pixel(&self, pixel: usize) -> Color {
+        let value = self.raw(pixel);
+        match self.depth {
+            ColorDepth::One if value == 0 => Color::new(0, 0, 0),
+            ColorDepth::One => Color::new(255, 255, 255),
+            ColorDepth::Four | ColorDepth::Eight => self.palette[value as usize],
+            ColorDepth::TwentyFour => {
+                let [r, g, b, _] = value.to_le_bytes();
+                Color::new(r, g, b)
+            }
+        }
+    }
+
+    // Expands pixels from `start` on into `out` as RGBA, stopping when either side runs out.
+    pub fn copy_rgba(&self, start: usize, out: &mut [u8]) {
+        let (rgba, _) = out.as_chunks_mut::<4>();
+        for (pixel, rgba) in (start..self.pixels).zip(rgba) {
+            *rgba = self.pixel(pixel).to_rgba();
+        }
+    }
+}
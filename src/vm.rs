@@ -0,0 +1,2704 @@
+use crate::bus::{BusError, Device};
+use crate::coredump::CoreDump;
+use crate::cpu::{
+    FREE_MEMORY, HaltReason, MMIO_BASE, MicroCVMCpu, RegisterWidth, VIDEO_CONTROL_LETTERBOX,
+    VIDEO_CONTROL_TILES,
+};
+use crate::crt::{CrtEffect, scale_frame};
+use crate::error::{FaultReport, VmError};
+use crate::fault::FaultPolicy;
+use crate::gamepad::GamepadState;
+use crate::hcall::HcallHandler;
+use crate::letterbox::{Letterbox, Placement, compose, place};
+use crate::mailbox::Mailbox;
+use crate::overlay::DebugStats;
+use crate::program::MAGIC;
+use crate::rtc::ClockSource;
+use crate::trace::TraceSink;
+use crate::types::Color;
+use crate::video::{ColorDepth, VideoMemory};
+use crate::watchdog::{Watchdog, WatchdogConfig};
+use alloc::boxed::Box;
+use alloc::collections::BTreeSet;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+pub const DEFAULT_WIDTH: u32 = 384;
+pub const DEFAULT_HEIGHT: u32 = 288;
+// Upper bound on the cycles run between two presented frames for programs that never vsync.
+pub const CYCLES_PER_FRAME: u64 = 500_000;
+// Frames with at least this many pixels are converted in bands on the rayon thread pool.
+// Below it, handing the bands out costs more than it saves.
+#[cfg(feature = "rayon")]
+pub const PARALLEL_FRAME_PIXELS: usize = 256 * 256;
+
+/// A complete machine: CPU, memory, video memory and the devices attached to it.
+///
+/// ```
+/// use microcvm_rs::{HaltReason, MicroCvm};
+///
+/// // mov r0, 42; hlt
+/// let mut vm = MicroCvm::builder().build();
+/// vm.load_program(&[0x06, 0x00, 42, 0xFF]).unwrap();
+/// assert_eq!(vm.run().unwrap(), HaltReason::Halted { code: 42 });
+/// assert_eq!(vm.cpu().registers[0], 42);
+/// ```
+///
+/// Each machine owns all of its state, devices included, so machines are `Send` and
+/// independent ones can run on separate threads.
+pub struct MicroCvm {
+    cpu: MicroCVMCpu,
+    width: u32,
+    height: u32,
+    max_instructions: u64,
+    instructions: u64,
+    paused: PauseHandle,
+    breakpoints: BTreeSet<u16>,
+    // The unscaled frame `frame_rgba_scaled` draws before scaling it, kept from one call to
+    // the next so presenting a frame never allocates.
+    staging: Box<[u8]>,
+    // The scaled frame `frame_rgba_letterboxed` places in the window, grown to fit the
+    // largest scale it has drawn at.
+    scaled: Vec<u8>,
+    // The scanlines every frame conversion draws, None for none.
+    crt: Option<CrtEffect>,
+    #[cfg(feature = "std")]
+    core_dump_file: Option<std::path::PathBuf>,
+    #[cfg(feature = "std")]
+    shut_down: bool,
+    pause_on_focus_loss: bool,
+    // Paused by focus_changed rather than by anyone asking.
+    auto_paused: bool,
+    #[cfg(feature = "rayon")]
+    parallel_frame_pixels: usize,
+}
+
+/// Pauses and resumes a [`MicroCvm`] from any thread, see [`MicroCvm::pause`]. Clones
+/// control the same machine.
+#[derive(Debug, Clone, Default)]
+pub struct PauseHandle(Arc<AtomicBool>);
+
+impl PauseHandle {
+    pub fn pause(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn resume(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// A machine running on a thread of its own, from [`MicroCvm::spawn`]. Dropping it shuts
+/// the machine down the way [`shutdown`](Self::shutdown) does, ignoring what goes wrong.
+#[cfg(feature = "std")]
+pub struct VmThread {
+    stop: Arc<AtomicBool>,
+    paused: PauseHandle,
+    timeout: std::time::Duration,
+    // `None` once shut down.
+    finished: Option<std::sync::mpsc::Receiver<(MicroCvm, Result<HaltReason, VmError>)>>,
+    vm: Option<MicroCvm>,
+}
+
+// How long VmThread::shutdown waits for the machine's thread unless told otherwise.
+#[cfg(feature = "std")]
+pub const DEFAULT_SHUTDOWN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1);
+
+// How often a spawned machine paused from outside looks for being resumed or shut down.
+#[cfg(feature = "std")]
+const PAUSED_POLL: std::time::Duration = std::time::Duration::from_millis(1);
+
+#[cfg(feature = "std")]
+impl VmThread {
+    fn spawn(mut vm: MicroCvm) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let paused = vm.pause_handle();
+        let (done, finished) = std::sync::mpsc::channel();
+        let stopping = stop.clone();
+        std::thread::spawn(move || {
+            let result = loop {
+                let result = vm.run();
+                if stopping.load(Ordering::Relaxed) || !matches!(result, Ok(HaltReason::Paused)) {
+                    break result;
+                }
+                while vm.is_paused() && !stopping.load(Ordering::Relaxed) {
+                    std::thread::sleep(PAUSED_POLL);
+                }
+            };
+            // Nobody is waiting after a shutdown that timed out, and dropping the machine
+            // here still writes out its NVRAM.
+            let _ = done.send((vm, result));
+        });
+        Self {
+            stop,
+            paused,
+            timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+            finished: Some(finished),
+            vm: None,
+        }
+    }
+
+    /// Pausing stops the machine between two instructions until it is resumed, and a
+    /// shutdown ends it either way.
+    pub fn pause_handle(&self) -> PauseHandle {
+        self.paused.clone()
+    }
+
+    /// How long [`shutdown`](Self::shutdown) waits for the thread before leaving it to finish
+    /// on its own.
+    pub fn set_shutdown_timeout(&mut self, timeout: std::time::Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Stops the machine between two instructions, waits for its thread and then
+    /// [shuts the machine down](MicroCvm::shutdown), returning the error that stopped the
+    /// program if one did, or else any from the shutdown. A thread stuck in a host call past
+    /// the timeout is left to finish by itself, and the machine is dropped there, writing
+    /// out its NVRAM, whenever it does; the shutdown returns an [`Io`](VmError::Io) error
+    /// of kind `TimedOut`. Shutting down again does nothing.
+    pub fn shutdown(&mut self) -> Result<(), VmError> {
+        let Some(finished) = self.finished.take() else {
+            return Ok(());
+        };
+        self.stop.store(true, Ordering::Relaxed);
+        self.paused.pause();
+        let (mut vm, result) = match finished.recv_timeout(self.timeout) {
+            Ok(finished) => finished,
+            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
+                log::warn!(
+                    "the machine's thread did not stop within {:?}, leaving it running",
+                    self.timeout
+                );
+                return Err(VmError::Io(std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    "the machine's thread did not stop in time",
+                )));
+            }
+            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
+                return Err(VmError::Io(std::io::Error::other(
+                    "the machine's thread panicked",
+                )));
+            }
+        };
+        let shut_down = vm.shutdown();
+        self.vm = Some(vm);
+        result?;
+        shut_down
+    }
+
+    /// The machine once its thread has been shut down, paused where it stopped.
+    pub fn into_vm(mut self) -> Option<MicroCvm> {
+        self.vm.take()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for VmThread {
+    fn drop(&mut self) {
+        if let Err(e) = self.shutdown() {
+            log::warn!("shutting down the machine's thread: {}", e);
+        }
+    }
+}
+
+const _: () = {
+    const fn assert_send<T: Send>() {}
+    assert_send::<MicroCvm>();
+};
+
+pub struct MicroCvmBuilder {
+    memory_size: usize,
+    width: u32,
+    height: u32,
+    max_instructions: u64,
+    max_call_depth: Option<u32>,
+    register_width: RegisterWidth,
+    framebuffer_window: Option<(u16, u16)>,
+    color_depth: ColorDepth,
+    mailbox: Option<Mailbox>,
+    clock: Option<Box<dyn ClockSource>>,
+    hcalls: Vec<(u8, HcallHandler)>,
+    trace: Option<Box<dyn TraceSink>>,
+    fault_history: usize,
+    fault_policy: FaultPolicy,
+    watchdog: WatchdogConfig,
+    decode_cache: bool,
+    verify_checksums: bool,
+    pause_on_focus_loss: bool,
+    #[cfg(feature = "std")]
+    core_dump_file: Option<std::path::PathBuf>,
+    #[cfg(feature = "rayon")]
+    parallel_frame_pixels: usize,
+}
+
+/// A copy of the machine state that can be restored later. [`diff`](Snapshot::diff) lists
+/// everything that differs between two of them.
+///
+/// ```
+/// use microcvm_rs::MicroCvm;
+///
+/// // mov r0, 0xde; store [0x0140], r0; hlt
+/// let mut vm = MicroCvm::builder().build();
+/// vm.load_program(&[0x06, 0x00, 0xde, 0x02, 0x40, 0x01, 0x00, 0xFF]).unwrap();
+/// let before = vm.snapshot();
+/// vm.run().unwrap();
+/// let diff = before.diff(&vm.snapshot());
+/// assert_eq!(diff.registers, [(0, 0x00, 0xde)]);
+/// assert_eq!(diff.memory[0].range(), 0x0140..0x0141);
+/// assert!(diff.to_string().contains("mem[0x0140..0x0141]: 00 -> de"));
+/// assert!(vm.snapshot().diff(&vm.snapshot()).is_empty());
+/// ```
+#[derive(Clone)]
+pub struct Snapshot {
+    pub registers: [u16; 8],
+    pub sp: u16,
+    pub pc: u16,
+    pub flags: u8,
+    pub bank: u8,
+    pub halted: bool,
+    pub cycles: u64,
+    pub instructions: u64,
+    pub call_depth: u32,
+    pub memory: Vec<u8>,
+    pub video_memory: VideoMemory,
+}
+
+impl MicroCvmBuilder {
+    pub fn new() -> Self {
+        Self {
+            memory_size: FREE_MEMORY,
+            width: DEFAULT_WIDTH,
+            height: DEFAULT_HEIGHT,
+            max_instructions: u64::MAX,
+            max_call_depth: None,
+            register_width: RegisterWidth::Eight,
+            framebuffer_window: None,
+            color_depth: ColorDepth::TwentyFour,
+            mailbox: None,
+            clock: None,
+            hcalls: Vec::new(),
+            trace: None,
+            fault_history: 0,
+            fault_policy: FaultPolicy::Halt,
+            watchdog: WatchdogConfig::default(),
+            decode_cache: true,
+            verify_checksums: true,
+            pause_on_focus_loss: false,
+            #[cfg(feature = "std")]
+            core_dump_file: None,
+            #[cfg(feature = "rayon")]
+            parallel_frame_pixels: PARALLEL_FRAME_PIXELS,
+        }
+    }
+
+    /// Physical memory size in bytes. Banks past the end fault when accessed.
+    pub fn memory_size(mut self, bytes: usize) -> Self {
+        self.memory_size = bytes;
+        self
+    }
+
+    /// Framebuffer size in pixels. Video memory holds exactly one frame.
+    pub fn resolution(mut self, width: u32, height: u32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    /// Total number of instructions the machine may execute over its lifetime.
+    pub fn max_instructions(mut self, limit: u64) -> Self {
+        self.max_instructions = limit;
+        self
+    }
+
+    /// Nested calls beyond `depth` fault with `VmError::CallDepthExceeded`.
+    ///
+    /// ```
+    /// use microcvm_rs::MicroCvm;
+    /// use microcvm_rs::error::VmError;
+    ///
+    /// // forever: call forever
+    /// let mut vm = MicroCvm::builder().max_call_depth(64).build();
+    /// vm.load_program(&[0x16, 0x00, 0x00]).unwrap();
+    /// let error = vm.run().unwrap_err();
+    /// let VmError::CallDepthExceeded { depth, chain, .. } = error.cause() else {
+    ///     panic!("expected the recursion to be caught");
+    /// };
+    /// assert_eq!(*depth, 65);
+    /// assert_eq!(chain[0], 0x0003);
+    /// ```
+    pub fn max_call_depth(mut self, depth: u32) -> Self {
+        self.max_call_depth = Some(depth);
+        self
+    }
+
+    /// Gives the machine 16-bit registers. Programs for it are assembled after `.width 16`,
+    /// which makes immediate operands two bytes long.
+    ///
+    /// ```
+    /// use microcvm_rs::asm::assemble;
+    /// use microcvm_rs::cpu::RegisterWidth;
+    /// use microcvm_rs::MicroCvm;
+    ///
+    /// let program = assemble("
+    ///     .width 16
+    ///     mov r0, 1000
+    ///     mul r0, 60
+    ///     store [0x0100], r0
+    ///     mov r1, 0xFFFF
+    ///     inc r1
+    ///     hlt
+    /// ").unwrap();
+    /// let mut vm = MicroCvm::builder().register_width(RegisterWidth::Sixteen).build();
+    /// vm.load_program(&program).unwrap();
+    /// vm.run().unwrap();
+    /// assert_eq!(vm.cpu().registers[0], 60000);
+    /// assert_eq!(vm.cpu().memory()[0x0100..0x0102], 60000u16.to_le_bytes());
+    /// assert_eq!(vm.cpu().registers[1], 0);
+    /// ```
+    pub fn register_width(mut self, width: RegisterWidth) -> Self {
+        self.register_width = width;
+        self
+    }
+
+    /// Maps `len` bytes at guest address `start` onto video memory, three bytes (red,
+    /// green, blue) per pixel, so programs can draw with plain loads and stores. The window
+    /// shows one `len` byte slice of the framebuffer at a time, picked by writing its
+    /// number to [`FB_BANK_SELECT`](crate::cpu::FB_BANK_SELECT). A window reaching into
+    /// MMIO is cut short at [`MMIO_BASE`](crate::cpu::MMIO_BASE).
+    ///
+    /// Video operations and the window see each other's writes:
+    ///
+    /// ```
+    /// use microcvm_rs::asm::assemble;
+    /// use microcvm_rs::MicroCvm;
+    ///
+    /// let program = assemble("
+    ///         mov r0, 255
+    ///         store [0x8000], r0      ; red channel of pixel 0
+    ///         mov r0, 1               ; setpixel x = 1, y = 0 to (0, 200, 0)
+    ///         mov r1, 0
+    ///         mov r2, 0
+    ///         mov r3, 200
+    ///         mov r4, 0
+    ///         video 0x03, r0
+    ///         load r5, [0x8004]       ; green channel of pixel 1
+    ///         mov r0, 1
+    ///         store [0xFF11], r0      ; the second 256 bytes of video memory
+    ///         mov r0, 7
+    ///         store [0x8004], r0      ; blue channel of pixel 86
+    ///         hlt
+    /// ").unwrap();
+    /// let mut vm = MicroCvm::builder()
+    ///     .resolution(16, 16)
+    ///     .framebuffer_window(0x8000, 0x100)
+    ///     .build();
+    /// vm.load_program(&program).unwrap();
+    /// vm.run().unwrap();
+    /// assert_eq!(vm.framebuffer().pixel(0).r, 255);
+    /// assert_eq!(vm.cpu().registers[5], 200);
+    /// assert_eq!(vm.framebuffer().pixel(86).b, 7);
+    /// // The RAM behind the window is untouched.
+    /// assert_eq!(vm.cpu().memory()[0x8000], 0);
+    /// ```
+    pub fn framebuffer_window(mut self, start: u16, len: u16) -> Self {
+        self.framebuffer_window = Some((start, len.min(MMIO_BASE.saturating_sub(start))));
+        self
+    }
+
+    /// Bits per pixel of video memory, 24 by default. The guest can change it later with
+    /// [`VIDEO_DEPTH`](crate::cpu::VIDEO_DEPTH), which also clears the screen.
+    ///
+    /// Each depth packs pixels its own way and expands them to RGBA differently:
+    ///
+    /// ```
+    /// use microcvm_rs::MicroCvm;
+    /// use microcvm_rs::video::ColorDepth;
+    ///
+    /// // The frame of a 4x2 screen whose video memory holds `bytes`.
+    /// fn frame(depth: ColorDepth, bytes: &[u8]) -> Vec<[u8; 4]> {
+    ///     let mut vm = MicroCvm::builder().resolution(4, 2).color_depth(depth).build();
+    ///     vm.cpu_mut().video_memory.bytes_mut().copy_from_slice(bytes);
+    ///     let mut rgba = vec![0; 4 * 2 * 4];
+    ///     vm.frame_rgba(&mut rgba);
+    ///     rgba.chunks(4).map(|pixel| pixel.try_into().unwrap()).collect()
+    /// }
+    /// let (k, w) = ([0, 0, 0, 255], [255, 255, 255, 255]);
+    /// let (red, blue) = ([255, 0, 0, 255], [0, 0, 255, 255]);
+    ///
+    /// // Black and white, the leftmost pixel in the high bit.
+    /// assert_eq!(frame(ColorDepth::One, &[0b1001_0110]), [w, k, k, w, k, w, w, k]);
+    /// // Palette indices, the leftmost pixel in the high nibble. The palette starts out
+    /// // with the xterm colors.
+    /// assert_eq!(
+    ///     frame(ColorDepth::Four, &[0x9C, 0x0F, 0xF0, 0xC9]),
+    ///     [red, blue, k, w, w, k, blue, red],
+    /// );
+    /// // The cube starts at 16 and the grays at 232.
+    /// assert_eq!(
+    ///     frame(ColorDepth::Eight, &[9, 12, 15, 0, 196, 231, 232, 255]),
+    ///     [red, blue, w, k, red, w, [8, 8, 8, 255], [238, 238, 238, 255]],
+    /// );
+    /// // Red, green and blue bytes.
+    /// let bytes: Vec<u8> = (0..24).collect();
+    /// let expected: Vec<[u8; 4]> = (0..8).map(|i| [i * 3, i * 3 + 1, i * 3 + 2, 255]).collect();
+    /// assert_eq!(frame(ColorDepth::TwentyFour, &bytes), expected);
+    /// ```
+    ///
+    /// Below 24 bits the first color operand of a video operation is the pixel value, so
+    /// this switches to 4 bits, sets palette entry 3 and fills a rectangle with it:
+    ///
+    /// ```
+    /// use microcvm_rs::asm::assemble;
+    /// use microcvm_rs::MicroCvm;
+    /// use microcvm_rs::video::ColorDepth;
+    ///
+    /// let program = assemble("
+    ///         mov r0, 4
+    ///         store [0xFF14], r0      ; 4 bits per pixel
+    ///         mov r0, 3
+    ///         store [0xFF15], r0      ; palette entry 3
+    ///         mov r0, 10
+    ///         store [0xFF16], r0
+    ///         mov r0, 20
+    ///         store [0xFF17], r0
+    ///         mov r0, 30
+    ///         store [0xFF18], r0
+    ///         mov r0, 1               ; fillrect x = 1, y = 0, 2x2 of color 3
+    ///         mov r1, 0
+    ///         mov r2, 2
+    ///         mov r3, 2
+    ///         mov r4, 3
+    ///         video 0x04, r0
+    ///         mov r0, 0               ; setpixel x = 0, y = 1 to color 0x12, which is 2
+    ///         mov r1, 1
+    ///         mov r2, 0x12
+    ///         video 0x03, r0
+    ///         hlt
+    /// ").unwrap();
+    /// let mut vm = MicroCvm::builder().resolution(4, 2).build();
+    /// vm.load_program(&program).unwrap();
+    /// vm.run().unwrap();
+    /// assert_eq!(vm.framebuffer().depth, ColorDepth::Four);
+    /// assert_eq!(vm.framebuffer().bytes(), [0x03, 0x30, 0x23, 0x30]);
+    /// assert_eq!(vm.framebuffer().pixel(1).to_rgba(), [10, 20, 30, 255]);
+    /// ```
+    pub fn color_depth(mut self, depth: ColorDepth) -> Self {
+        self.color_depth = depth;
+        self
+    }
+
+    /// Connects one end of a [`Mailbox`] pair, which the guest sees as registers at
+    /// [`MAILBOX_BASE`](crate::cpu::MAILBOX_BASE). Give the other end to a second machine
+    /// to let the two exchange bytes, from the same thread or from different ones.
+    ///
+    /// ```
+    /// use microcvm_rs::asm::assemble;
+    /// use microcvm_rs::mailbox::Mailbox;
+    /// use microcvm_rs::MicroCvm;
+    ///
+    /// let registers = "
+    ///     .equ status, 0xFF40
+    ///     .equ inbox, 0xFF41
+    ///     .equ outbox, 0xFF42
+    /// ";
+    /// // Sends a counter, waits for it to come back incremented, and does so 100 times.
+    /// let ping = "
+    ///         mov r0, 0
+    ///         mov r3, 100
+    /// send:   store [outbox], r0
+    /// wait:   load r1, [status]
+    ///         btst r1, 0
+    ///         jrz wait
+    ///         load r0, [inbox]
+    ///         store [inbox], r0
+    ///         inc r0
+    ///         djnz r3, send
+    ///         hlt
+    /// ";
+    /// // Sends back every byte it receives, plus one.
+    /// let pong = "
+    ///         mov r3, 100
+    /// wait:   load r1, [status]
+    ///         btst r1, 0
+    ///         jrz wait
+    ///         load r0, [inbox]
+    ///         store [inbox], r0
+    ///         inc r0
+    ///         store [outbox], r0
+    ///         djnz r3, wait
+    ///         hlt
+    /// ";
+    ///
+    /// let (a, b) = Mailbox::pair(4);
+    /// let mut ping_vm = MicroCvm::builder().mailbox(a).build();
+    /// let mut pong_vm = MicroCvm::builder().mailbox(b).build();
+    /// ping_vm.load_program(&assemble(&(registers.to_owned() + ping)).unwrap()).unwrap();
+    /// pong_vm.load_program(&assemble(&(registers.to_owned() + pong)).unwrap()).unwrap();
+    /// while !(ping_vm.halted() && pong_vm.halted()) {
+    ///     ping_vm.run_for(50).unwrap();
+    ///     pong_vm.run_for(50).unwrap();
+    /// }
+    /// assert_eq!(ping_vm.cpu().registers[0], 200);
+    /// assert_eq!(pong_vm.cpu().registers[0], 199);
+    /// ```
+    pub fn mailbox(mut self, mailbox: Mailbox) -> Self {
+        self.mailbox = Some(mailbox);
+        self
+    }
+
+    /// Replaces the host clock behind the real-time clock at
+    /// [`RTC_BASE`](crate::cpu::RTC_BASE), for tests and for replaying a recorded run.
+    /// Any `FnMut() -> u64` returning seconds since the Unix epoch works.
+    ///
+    /// ```
+    /// use microcvm_rs::MicroCvm;
+    ///
+    /// // store [0xFF70], r0; load r1, [0xFF73]; load r2, [0xFF72]; load r3, [0xFF71]; hlt
+    /// let program = [
+    ///     0x02, 0x70, 0xFF, 0x00, 0x01, 0x01, 0x73, 0xFF, 0x01, 0x02, 0x72, 0xFF,
+    ///     0x01, 0x03, 0x71, 0xFF, 0xFF,
+    /// ];
+    /// // 2024-02-29 12:34:56 UTC
+    /// let mut vm = MicroCvm::builder().clock(Box::new(|| 1_709_210_096)).build();
+    /// vm.load_program(&program).unwrap();
+    /// vm.run().unwrap();
+    /// assert_eq!(vm.cpu().registers[1..4], [12, 34, 56]);
+    /// assert_eq!((vm.cpu().rtc().latched.day, vm.cpu().rtc().latched.month), (29, 2));
+    /// ```
+    pub fn clock(mut self, clock: Box<dyn ClockSource>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Attaches a host device reachable through `hcall number`.
+    ///
+    /// ```
+    /// use microcvm_rs::MicroCvm;
+    /// use microcvm_rs::cpu::Register;
+    ///
+    /// // hcall 1; hlt
+    /// let mut vm = MicroCvm::builder()
+    ///     .hcall(1, Box::new(|ctx| {
+    ///         ctx.set_reg(Register::R0, 7);
+    ///         Ok(())
+    ///     }))
+    ///     .build();
+    /// vm.load_program(&[0x0A, 1, 0xFF]).unwrap();
+    /// vm.run().unwrap();
+    /// assert_eq!(vm.cpu().registers[0], 7);
+    /// ```
+    pub fn hcall(mut self, number: u8, handler: HcallHandler) -> Self {
+        self.hcalls.push((number, handler));
+        self
+    }
+
+    pub fn trace(mut self, sink: Box<dyn TraceSink>) -> Self {
+        self.trace = Some(sink);
+        self
+    }
+
+    /// Keeps the last `instructions` instructions executed, so a fault's report shows what
+    /// led up to it. Off by default: like tracing, it takes the slower decoding path.
+    ///
+    /// ```
+    /// use microcvm_rs::MicroCvm;
+    /// use microcvm_rs::error::VmError;
+    ///
+    /// // inc r0; inc r1; div r0, 0
+    /// let mut vm = MicroCvm::builder().fault_history(2).build();
+    /// vm.load_program(&[0x07, 0x00, 0x07, 0x01, 0x08, 0x00, 0x00]).unwrap();
+    /// let error = vm.run().unwrap_err();
+    /// assert!(matches!(error.cause(), VmError::DivisionByZero { pc: 0x0004 }));
+    /// let report = error.report().unwrap();
+    /// assert_eq!(report.pc, 0x0004);
+    /// assert_eq!(report.bytes, [0x08, 0x00, 0x00]);
+    /// assert_eq!(report.registers[..2], [1, 1]);
+    /// let history: Vec<u16> = report.history.iter().map(|record| record.pc).collect();
+    /// assert_eq!(history, [0x0002, 0x0004]);
+    /// assert!(error.to_string().starts_with("Fault at 0x0004: Division by zero"));
+    /// ```
+    pub fn fault_history(mut self, instructions: usize) -> Self {
+        self.fault_history = instructions;
+        self
+    }
+
+    /// Decides what happens when the program faults. [`FaultPolicy::Halt`], the default,
+    /// stops with the error; [`FaultPolicy::Trap`] enters the guest's handler at
+    /// [`VECTOR_FAULT`](crate::fault::VECTOR_FAULT) in the interrupt vector table, which
+    /// reads what happened from [`FAULT_BASE`](crate::cpu::FAULT_BASE) and returns past the
+    /// faulting instruction with `popf; ret`; [`FaultPolicy::Host`] asks a callback.
+    ///
+    /// ```
+    /// use microcvm_rs::asm::assemble;
+    /// use microcvm_rs::fault::{FAULT_DIVISION_BY_ZERO, FaultAction, FaultPolicy};
+    /// use microcvm_rs::MicroCvm;
+    ///
+    /// let program = assemble("
+    ///         mov r0, 0x00
+    ///         store [0xFF90], r0      ; vector table at 0x0200
+    ///         mov r0, 0x02
+    ///         store [0xFF91], r0
+    ///         mov r0, 10
+    ///         div r0, 0
+    ///         inc r1
+    ///         hlt
+    ///
+    /// on_fault:
+    ///         load r2, [0xFFF0]       ; what went wrong
+    ///         load r3, [0xFFF1]       ; and where, low byte
+    ///         popf
+    ///         ret
+    ///
+    ///         .org 0x0200
+    ///         .dw 0, on_fault         ; vblank, fault
+    /// ").unwrap();
+    /// let run = |policy| {
+    ///     let mut vm = MicroCvm::builder().fault_policy(policy).build();
+    ///     vm.load_program(&program).unwrap();
+    ///     (vm.run().is_ok(), vm.cpu().registers)
+    /// };
+    ///
+    /// let (ok, _) = run(FaultPolicy::Halt);
+    /// assert!(!ok);
+    ///
+    /// let (ok, registers) = run(FaultPolicy::Trap);
+    /// assert!(ok);
+    /// assert_eq!(registers[1..4], [1, FAULT_DIVISION_BY_ZERO as u16, 0x11]);
+    ///
+    /// let (ok, registers) = run(FaultPolicy::Host(Box::new(|fault| {
+    ///     assert_eq!((fault.pc, fault.next_pc), (0x0011, 0x0014));
+    ///     FaultAction::Skip
+    /// })));
+    /// assert!(ok);
+    /// assert_eq!(registers[..4], [10, 1, 0, 0]);
+    /// ```
+    pub fn fault_policy(mut self, policy: FaultPolicy) -> Self {
+        self.fault_policy = policy;
+        self
+    }
+
+    /// Sets up the watchdog at [`WATCHDOG_BASE`](crate::cpu::WATCHDOG_BASE), which is
+    /// disarmed by default. Once armed it counts `timeout` cycles down from the last write to
+    /// its feed register, and running out either stops the program with
+    /// [`VmError::WatchdogExpired`] or enters the handler at
+    /// [`VECTOR_WATCHDOG`](crate::watchdog::VECTOR_WATCHDOG), as `action` says. It is checked
+    /// between instructions, so it fires at the first one to start at or after the deadline.
+    ///
+    /// ```
+    /// use microcvm_rs::asm::assemble;
+    /// use microcvm_rs::error::VmError;
+    /// use microcvm_rs::watchdog::{WatchdogAction, WatchdogConfig};
+    /// use microcvm_rs::MicroCvm;
+    ///
+    /// let program = assemble("
+    ///         mov r0, 0x00
+    ///         store [0xFF90], r0      ; vector table at 0x0200
+    ///         mov r0, 0x02
+    ///         store [0xFF91], r0
+    /// stuck:  jr stuck
+    ///
+    /// on_watchdog:
+    ///         load r1, [0xFF0E]       ; where it expired, low byte
+    ///         hlt
+    ///
+    ///         .org 0x0200
+    ///         .dw 0, 0, 0, on_watchdog
+    /// ").unwrap();
+    /// let run = |config| {
+    ///     let mut vm = MicroCvm::builder().watchdog(config).build();
+    ///     vm.load_program(&program).unwrap();
+    ///     (vm.run(), vm.cpu().cycles, vm.cpu().registers[1])
+    /// };
+    ///
+    /// let (result, cycles, _) = run(WatchdogConfig::new(100));
+    /// assert!(matches!(result.unwrap_err().cause(), VmError::WatchdogExpired { pc: 0x000e }));
+    /// assert_eq!(cycles, 100);
+    ///
+    /// let interrupt = WatchdogConfig {
+    ///     action: WatchdogAction::Interrupt,
+    ///     ..WatchdogConfig::new(100)
+    /// };
+    /// let (result, _, expired_at) = run(interrupt);
+    /// assert!(result.is_ok());
+    /// assert_eq!(expired_at, 0x0e);
+    /// ```
+    pub fn watchdog(mut self, config: WatchdogConfig) -> Self {
+        self.watchdog = config;
+        self
+    }
+
+    /// Keeps instructions once they are decoded so running them again is quicker. On by
+    /// default. The CPU's own writes forget whatever instructions they land in, so code that
+    /// modifies itself runs the same either way; hosts writing `memory` directly go through
+    /// [`MicroCvm::cpu_mut`], which flushes the cache.
+    ///
+    /// ```
+    /// use microcvm_rs::asm::assemble;
+    /// use microcvm_rs::MicroCvm;
+    ///
+    /// let program = assemble("
+    ///         mov r4, 2
+    /// loop:   store [patch+2], r4     ; rewrites the immediate below before it runs
+    /// patch:  add r3, 0
+    ///         djnz r4, loop
+    ///         hlt
+    /// ").unwrap();
+    /// for enabled in [true, false] {
+    ///     let mut vm = MicroCvm::builder().decode_cache(enabled).build();
+    ///     vm.load_program(&program).unwrap();
+    ///     vm.run().unwrap();
+    ///     assert_eq!(vm.cpu().registers[3], 2 + 1);
+    /// }
+    /// ```
+    pub fn decode_cache(mut self, enabled: bool) -> Self {
+        self.decode_cache = enabled;
+        self
+    }
+
+    /// Checks each program's payload against the CRC-32 in its header before loading it,
+    /// refusing one that doesn't match with [`VmError::ChecksumMismatch`] and leaving
+    /// memory as it was. On by default; turn it off to run hand-edited binaries. Version 1
+    /// headers have no checksum and always load.
+    ///
+    /// ```
+    /// use microcvm_rs::MicroCvm;
+    /// use microcvm_rs::error::VmError;
+    /// use microcvm_rs::program::{HEADER_LEN, Program};
+    ///
+    /// let mut file = Program::new(0x0100, vec![0x01, 0x19, 0x00, 0xff]).build();
+    /// file[HEADER_LEN + 1] ^= 0x01;
+    ///
+    /// let mut vm = MicroCvm::builder().build();
+    /// let error = vm.load_program(&file).unwrap_err();
+    /// let VmError::ChecksumMismatch { expected, actual } = error else {
+    ///     panic!("loaded a corrupted program: {}", error);
+    /// };
+    /// assert_ne!(expected, actual);
+    /// assert!(vm.cpu().memory()[0x0100..0x0104].iter().all(|&byte| byte == 0));
+    ///
+    /// let mut vm = MicroCvm::builder().verify_checksums(false).build();
+    /// vm.load_program(&file).unwrap();
+    /// assert_eq!(vm.cpu().memory()[0x0100..0x0104], [0x01, 0x18, 0x00, 0xff]);
+    /// ```
+    pub fn verify_checksums(mut self, verify: bool) -> Self {
+        self.verify_checksums = verify;
+        self
+    }
+
+    /// Pauses the machine and mutes its audio while the host's window is in the background,
+    /// off by default. See [`MicroCvm::focus_changed`].
+    pub fn pause_on_focus_loss(mut self, enabled: bool) -> Self {
+        self.pause_on_focus_loss = enabled;
+        self
+    }
+
+    /// Converts frames of at least `pixels` pixels in bands of rows on the rayon thread pool,
+    /// [`PARALLEL_FRAME_PIXELS`] by default. Frames come out the same either way.
+    ///
+    /// ```
+    /// use microcvm_rs::MicroCvm;
+    /// use microcvm_rs::cpu::VIDEO_CONTROL_TILES;
+    ///
+    /// let mut seed = 0x2545_f491u32;
+    /// let mut random = move || {
+    ///     seed ^= seed << 13;
+    ///     seed ^= seed >> 17;
+    ///     seed ^= seed << 5;
+    ///     seed
+    /// };
+    /// let video: Vec<u8> = (0..320 * 200 * 3).map(|_| random() as u8).collect();
+    /// let memory: Vec<u8> = (0..0x20000).map(|_| random() as u8).collect();
+    /// let sprites: Vec<[u32; 3]> = (0..16).map(|_| [random(), random(), random()]).collect();
+    ///
+    /// let frames = [0, usize::MAX].map(|pixels| {
+    ///     let mut vm = MicroCvm::builder()
+    ///         .resolution(320, 200)
+    ///         .parallel_frame_pixels(pixels)
+    ///         .build();
+    ///     let cpu = vm.cpu_mut();
+    ///     cpu.video_memory.bytes_mut().copy_from_slice(&video);
+    ///     cpu.memory_mut()[..memory.len()].copy_from_slice(&memory);
+    ///     cpu.brightness = 180;
+    ///     for (sprite, [position, size, source]) in cpu.sprites_mut().sprites.iter_mut().zip(&sprites) {
+    ///         sprite.enabled = true;
+    ///         sprite.x = (position % 352) as i16 - 16;
+    ///         sprite.y = (position >> 16) as i16 % 232 - 16;
+    ///         sprite.width = (size % 33) as u8;
+    ///         sprite.height = (size >> 8) as u8 % 33;
+    ///         sprite.source = source % 0x10000;
+    ///     }
+    ///     let mut plain = vec![0; 320 * 200 * 4];
+    ///     vm.frame_rgba(&mut plain);
+    ///
+    ///     let cpu = vm.cpu_mut();
+    ///     cpu.video_control |= VIDEO_CONTROL_TILES;
+    ///     let tiles = cpu.tiles_mut();
+    ///     (tiles.tile_set, tiles.map, tiles.scroll_x, tiles.scroll_y) = (0x10000, 0x1F000, 13, 300);
+    ///     let mut tiled = vec![0; 320 * 200 * 4];
+    ///     vm.frame_rgba(&mut tiled);
+    ///     (plain, tiled)
+    /// });
+    /// assert!(frames[0] == frames[1]);
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn parallel_frame_pixels(mut self, pixels: usize) -> Self {
+        self.parallel_frame_pixels = pixels;
+        self
+    }
+
+    /// Writes a [`CoreDump`] to `path` when the program faults, replacing what was there.
+    ///
+    /// ```
+    /// use microcvm_rs::MicroCvm;
+    /// use microcvm_rs::coredump::CoreDump;
+    ///
+    /// let path = std::env::temp_dir().join("microcvm-doctest.dump");
+    /// // inc r0; div r0, 0
+    /// let mut vm = MicroCvm::builder().core_dump_file(&path).build();
+    /// vm.load_program(&[0x07, 0x00, 0x08, 0x00, 0x00]).unwrap();
+    /// assert!(vm.run().is_err());
+    /// let dump = CoreDump::read(&path).unwrap();
+    /// std::fs::remove_file(&path).unwrap();
+    /// assert_eq!(dump.pc, 0x0002);
+    /// assert_eq!(dump.snapshot.registers[0], 1);
+    /// assert!(dump.report.contains("Division by zero"));
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn core_dump_file(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.core_dump_file = Some(path.into());
+        self
+    }
+
+    pub fn build(self) -> MicroCvm {
+        let pixels = self.width as usize * self.height as usize;
+        let mut cpu = MicroCVMCpu::with_memory(self.memory_size, pixels);
+        cpu.video_width = self.width;
+        cpu.max_call_depth = self.max_call_depth;
+        cpu.register_width = self.register_width;
+        if let Some((start, len)) = self.framebuffer_window {
+            // An empty window, the only one left to fail, maps nothing.
+            let _ = cpu.map_framebuffer(start, len);
+        }
+        cpu.video_memory.set_depth(self.color_depth);
+        if let Some(mailbox) = self.mailbox {
+            cpu.attach_mailbox(mailbox)
+                .expect("nothing else is mapped before build");
+        }
+        if let Some(clock) = self.clock {
+            cpu.rtc_mut().set_clock(Some(clock));
+        }
+        for (number, handler) in self.hcalls {
+            cpu.register_hcall(number, handler);
+        }
+        cpu.set_trace_sink(self.trace);
+        cpu.set_fault_history(self.fault_history);
+        cpu.fault_policy = self.fault_policy;
+        cpu.watchdog = Watchdog::new(self.watchdog);
+        cpu.set_decode_cache(self.decode_cache);
+        cpu.verify_checksums = self.verify_checksums;
+        log::debug!(
+            "built a {}x{} machine with {} bytes of memory",
+            self.width,
+            self.height,
+            self.memory_size
+        );
+
+        MicroCvm {
+            cpu,
+            width: self.width,
+            height: self.height,
+            max_instructions: self.max_instructions,
+            instructions: 0,
+            paused: PauseHandle::default(),
+            breakpoints: BTreeSet::new(),
+            staging: vec![0; pixels * 4].into_boxed_slice(),
+            scaled: Vec::new(),
+            crt: None,
+            #[cfg(feature = "std")]
+            core_dump_file: self.core_dump_file,
+            #[cfg(feature = "std")]
+            shut_down: false,
+            pause_on_focus_loss: self.pause_on_focus_loss,
+            auto_paused: false,
+            #[cfg(feature = "rayon")]
+            parallel_frame_pixels: self.parallel_frame_pixels,
+        }
+    }
+}
+
+impl Default for MicroCvmBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MicroCvm {
+    pub fn builder() -> MicroCvmBuilder {
+        MicroCvmBuilder::new()
+    }
+
+    /// Loads an image with an `MCVM` header at its load address, or raw bytes at address 0.
+    /// With the `flate2` feature the payload may be gzip compressed, see
+    /// `Program::compress`. It is decompressed straight into memory, and never past the
+    /// length its prefix claims, which has to fit in memory before anything is written.
+    ///
+    /// A segmented image, built by `SegmentedProgram` or `asm::assemble_segments`, places
+    /// each of its segments instead: code and data are copied into memory, bss zeroes its
+    /// range and video segments go to video memory. Code is write protected if the header
+    /// asks for it, data and bss are no-execute if it asks for that, and execution starts at
+    /// the entry point. Segments are all checked
+    /// before any of them is written, and one that overlaps another or runs past the end of
+    /// memory is refused.
+    ///
+    /// ```
+    /// use microcvm_rs::MicroCvm;
+    /// use microcvm_rs::asm::{assemble, assemble_segments};
+    /// use microcvm_rs::error::VmError;
+    /// use microcvm_rs::segment::{Segment, SegmentKind, SegmentedProgram};
+    ///
+    /// let program = assemble_segments("
+    ///         .entry start
+    ///         .section data
+    ///         .org 0x4000
+    /// value:  .db 41
+    ///         .section bss
+    /// scratch: .space 16
+    ///         .section video
+    ///         .db 0xff, 0x80, 0x00        ; the first pixel, orange
+    ///         .section code
+    ///         .org 0x0100
+    /// start:  load r0, [value]
+    ///         inc r0
+    ///         store [scratch], r0
+    ///         hlt
+    /// ").unwrap();
+    /// let kinds: Vec<_> = program.segments.iter().map(|segment| segment.kind).collect();
+    /// use SegmentKind::*;
+    /// assert_eq!(kinds, [Data, Bss, Video, Code]);
+    /// let file = program.protect_code(true).build();
+    ///
+    /// let mut vm = MicroCvm::builder().build();
+    /// vm.cpu_mut().memory_mut()[..0x6000].fill(0xaa);
+    /// vm.load_program(&file).unwrap();
+    /// let memory = vm.cpu().memory();
+    /// assert_eq!(memory[0x4000], 41);
+    /// assert!(memory[0x4001..0x4011].iter().all(|&byte| byte == 0));
+    /// assert_eq!(memory[0x4011], 0xaa);
+    /// assert_eq!(memory[0x00ff], 0xaa);
+    /// assert_eq!(vm.cpu().video_memory.bytes()[..3], [0xff, 0x80, 0x00]);
+    /// assert_eq!(vm.cpu().pc, 0x0100);
+    ///
+    /// vm.run().unwrap();
+    /// assert_eq!(vm.cpu().memory()[0x4001], 42);
+    ///
+    /// // Code stays read-only after loading
+    /// vm.cpu_mut().pc = 0;
+    /// vm.load_program(&assemble("store [0x0100], r0").unwrap()).unwrap();
+    /// assert!(matches!(vm.step().unwrap_err().cause(), VmError::WriteProtected { .. }));
+    ///
+    /// // A video segment overlapping another video segment, and one past the end of video memory
+    /// let overlapping = SegmentedProgram::new(0)
+    ///     .segment(Segment::new(Video, 0, vec![1; 8]))
+    ///     .segment(Segment::new(Code, 4, vec![2; 8]))
+    ///     .segment(Segment::new(Video, 6, vec![3; 8]))
+    ///     .build();
+    /// let mut vm = MicroCvm::builder().resolution(16, 16).build();
+    /// let error = vm.load_program(&overlapping).unwrap_err();
+    /// assert!(matches!(error, VmError::SegmentOverlap { first: 0, second: 2, at: 6 }));
+    ///
+    /// let too_long = SegmentedProgram::new(0)
+    ///     .segment(Segment::new(Video, 16 * 16 * 3 - 2, vec![1; 8]))
+    ///     .build();
+    /// let error = vm.load_program(&too_long).unwrap_err();
+    /// assert!(matches!(error, VmError::SegmentOutOfRange { index: 0, kind: Video, .. }));
+    /// assert!(vm.cpu().video_memory.bytes().iter().all(|&byte| byte == 0));
+    ///
+    /// // The assembler points at the line whose output lands on earlier output
+    /// let error = assemble_segments("
+    ///         .db 1, 2, 3, 4
+    ///         .section data
+    ///         .org 2
+    ///         .db 5
+    /// ").unwrap_err();
+    /// assert_eq!(error.line, 5);
+    /// ```
+    ///
+    /// ```
+    /// # #[cfg(feature = "flate2")]
+    /// # {
+    /// use microcvm_rs::MicroCvm;
+    /// use microcvm_rs::crc32::crc32;
+    /// use microcvm_rs::error::VmError;
+    /// use microcvm_rs::program::{HEADER_LEN, Program};
+    ///
+    /// // Mostly zeroes, then bytes that don't compress at all
+    /// let mut sparse = vec![0; 0x8000];
+    /// sparse[0x10..0x14].copy_from_slice(&[0x01, 0x19, 0x00, 0xff]);
+    /// let mut state = 0x2545_f491u32;
+    /// let noise: Vec<u8> = (0..0x4000)
+    ///     .map(|_| {
+    ///         state ^= state << 13;
+    ///         state ^= state >> 17;
+    ///         state ^= state << 5;
+    ///         state as u8
+    ///     })
+    ///     .collect();
+    ///
+    /// for payload in [sparse, noise] {
+    ///     let file = Program::new(0x0100, payload.clone()).compress(true).build();
+    ///     let parsed = Program::parse(&file).unwrap();
+    ///     assert!(parsed.header.compressed());
+    ///     assert_eq!(parsed.payload, payload);
+    ///
+    ///     let mut vm = MicroCvm::builder().build();
+    ///     vm.load_program(&file).unwrap();
+    ///     assert_eq!(&vm.cpu().memory()[0x0100..][..payload.len()], &payload[..]);
+    /// }
+    /// let sparse = Program::new(0, vec![0; 0x8000]).compress(true).build();
+    /// assert!(sparse.len() < 0x8000 / 50);
+    ///
+    /// // 64 KiB of zeroes claiming to be 16 bytes stop at the 16th, with the checksum
+    /// // redone so only the length lies
+    /// let mut bomb = Program::new(0x0100, vec![0; 0x1_0000]).compress(true).build();
+    /// bomb[HEADER_LEN..][..4].copy_from_slice(&16u32.to_le_bytes());
+    /// let checksum = crc32(&bomb[HEADER_LEN..]);
+    /// bomb[12..16].copy_from_slice(&checksum.to_le_bytes());
+    ///
+    /// let mut vm = MicroCvm::builder().build();
+    /// vm.cpu_mut().memory_mut().fill(0xaa);
+    /// let error = vm.load_program(&bomb).unwrap_err();
+    /// assert!(matches!(error, VmError::InvalidHeader { .. }), "{}", error);
+    /// assert!(vm.cpu().memory()[0x0100..0x0110].iter().all(|&byte| byte == 0));
+    /// assert!(vm.cpu().memory()[0x0110..].iter().all(|&byte| byte == 0xaa));
+    ///
+    /// // And one claiming more than memory holds is refused before writing anything
+    /// bomb[HEADER_LEN..][..4].copy_from_slice(&u32::MAX.to_le_bytes());
+    /// let checksum = crc32(&bomb[HEADER_LEN..]);
+    /// bomb[12..16].copy_from_slice(&checksum.to_le_bytes());
+    /// let error = vm.load_program(&bomb).unwrap_err();
+    /// assert!(matches!(error, VmError::ProgramTooLarge { .. }), "{}", error);
+    /// # }
+    /// ```
+    pub fn load_program(&mut self, bytes: &[u8]) -> Result<(), VmError> {
+        if bytes.starts_with(&MAGIC) {
+            self.cpu.load_program(bytes)
+        } else {
+            self.cpu.load_raw(bytes, 0)
+        }
+    }
+
+    /// Streams an image from any reader into memory from `offset`, returning its length.
+    /// Unlike [`load_program`](Self::load_program) the bytes land as they are, header or
+    /// not. Nothing is buffered along the way: a reader holding more than fits in memory
+    /// fails with [`VmError::ImageTooLarge`] once it has filled memory to the end.
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use microcvm_rs::MicroCvm;
+    /// use microcvm_rs::error::VmError;
+    ///
+    /// let mut vm = MicroCvm::builder().memory_size(256).build();
+    /// let loaded = vm.load_from_reader(Cursor::new(vec![1, 2, 3, 4]), 0x10).unwrap();
+    /// assert_eq!(loaded, 4);
+    /// assert_eq!(&vm.cpu().memory()[0x0f..0x15], &[0, 1, 2, 3, 4, 0]);
+    ///
+    /// // An endless reader stops at the end of memory rather than reading forever
+    /// let error = vm.load_from_reader(std::io::repeat(0xaa), 0x80).unwrap_err();
+    /// assert!(matches!(error, VmError::ImageTooLarge { offset: 0x80, capacity: 0x80 }));
+    /// assert!(vm.cpu().memory()[0x80..].iter().all(|&byte| byte == 0xaa));
+    ///
+    /// // Filling memory exactly is fine
+    /// let loaded = vm.load_from_reader(Cursor::new(vec![7; 0x40]), 0xc0).unwrap();
+    /// assert_eq!(loaded, 0x40);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn load_from_reader(
+        &mut self,
+        reader: impl std::io::Read,
+        offset: usize,
+    ) -> Result<u64, VmError> {
+        self.cpu.load_from_reader(reader, offset)
+    }
+
+    /// Loads a program file the way [`load_program`](Self::load_program) loads its bytes.
+    /// With the `mmap` feature the file is mapped and copied straight into memory instead of
+    /// being read into a buffer first, which falls back to reading it where mapping fails.
+    /// A file that changes size while loading, or whose contents `load_program` rejects,
+    /// is an error.
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use microcvm_rs::MicroCvm;
+    /// use microcvm_rs::program::Program;
+    ///
+    /// static MAPPED: AtomicUsize = AtomicUsize::new(0);
+    /// static READ: AtomicUsize = AtomicUsize::new(0);
+    ///
+    /// struct Capture;
+    ///
+    /// impl log::Log for Capture {
+    ///     fn enabled(&self, _: &log::Metadata) -> bool {
+    ///         true
+    ///     }
+    ///
+    ///     fn log(&self, record: &log::Record) {
+    ///         let message = record.args().to_string();
+    ///         if message.starts_with("mapped") {
+    ///             MAPPED.fetch_add(1, Ordering::SeqCst);
+    ///         } else if message.starts_with("read") {
+    ///             READ.fetch_add(1, Ordering::SeqCst);
+    ///         }
+    ///     }
+    ///
+    ///     fn flush(&self) {}
+    /// }
+    ///
+    /// log::set_logger(&Capture).unwrap();
+    /// log::set_max_level(log::LevelFilter::Debug);
+    ///
+    /// // 3 MiB of payload at 0x1234, so it runs far past the 64 KiB an address can name
+    /// let payload: Vec<u8> = (0..3 << 20).map(|i: u32| (i % 251) as u8).collect();
+    /// let path = std::env::temp_dir().join(format!("microcvm-mmap-{}.bin", std::process::id()));
+    /// std::fs::write(&path, Program::new(0x1234, payload.clone()).build()).unwrap();
+    ///
+    /// let mut vm = MicroCvm::builder().memory_size(4 << 20).build();
+    /// vm.load_program_mmapped(&path).unwrap();
+    /// std::fs::remove_file(&path).unwrap();
+    ///
+    /// let memory = vm.cpu().memory();
+    /// assert!(memory[..0x1234].iter().all(|&byte| byte == 0));
+    /// assert_eq!(&memory[0x1234..][..payload.len()], &payload[..]);
+    /// assert!(memory[0x1234 + payload.len()..].iter().all(|&byte| byte == 0));
+    ///
+    /// // Without the feature the buffered path loads it
+    /// let mapped = MAPPED.load(Ordering::SeqCst);
+    /// let read = READ.load(Ordering::SeqCst);
+    /// assert_eq!((mapped, read), if cfg!(feature = "mmap") { (1, 0) } else { (0, 1) });
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn load_program_mmapped(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+    ) -> std::io::Result<()> {
+        crate::program::with_program_file(path.as_ref(), |bytes| self.load_program(bytes))
+    }
+
+    /// Starts execution at `entry`, for raw images, which have no header to say where. The
+    /// next run checks that a load put program bytes there, and fails with
+    /// [`VmError::EntryOutsideProgram`] otherwise. Images with a version 3 header set the
+    /// entry point they carry when they load.
+    ///
+    /// ```
+    /// use microcvm_rs::MicroCvm;
+    /// use microcvm_rs::asm::{assemble, assemble_segments};
+    /// use microcvm_rs::error::VmError;
+    ///
+    /// // Tables first, with no jump over them
+    /// let source = "
+    /// squares: .db 0, 1, 4, 9, 16, 25, 36, 49
+    /// message: .db \"hello\", 0
+    ///         .org 0x0200
+    /// main:   load r0, [squares+5]
+    ///         hlt
+    /// ";
+    ///
+    /// let program = assemble_segments(source).unwrap();
+    /// assert_eq!(program.entry, 0x0200);
+    /// let mut vm = MicroCvm::builder().build();
+    /// vm.load_program(&program.build()).unwrap();
+    /// assert_eq!(vm.cpu().pc, 0x0200);
+    /// vm.run().unwrap();
+    /// assert_eq!(vm.cpu().registers[0], 25);
+    ///
+    /// // The same program as a flat image, started by hand
+    /// let mut vm = MicroCvm::builder().build();
+    /// vm.load_program(&assemble(source).unwrap()).unwrap();
+    /// vm.set_entry(0x0200);
+    /// vm.run().unwrap();
+    /// assert_eq!(vm.cpu().registers[0], 25);
+    ///
+    /// // Past the end of the image, where nothing was loaded
+    /// let mut vm = MicroCvm::builder().build();
+    /// vm.load_program(&assemble(source).unwrap()).unwrap();
+    /// vm.set_entry(0x0300);
+    /// let error = vm.run().unwrap_err();
+    /// assert!(matches!(error, VmError::EntryOutsideProgram { entry: 0x0300 }));
+    /// assert_eq!(vm.cpu().registers[0], 0);
+    /// ```
+    pub fn set_entry(&mut self, entry: u16) {
+        self.cpu.set_entry(entry);
+    }
+
+    /// Executes a single instruction. A fault comes back as a [`VmError::Fault`] report,
+    /// which is also logged once at error level.
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use microcvm_rs::MicroCvm;
+    ///
+    /// static ERRORS: AtomicUsize = AtomicUsize::new(0);
+    ///
+    /// struct Capture;
+    ///
+    /// impl log::Log for Capture {
+    ///     fn enabled(&self, _: &log::Metadata) -> bool {
+    ///         true
+    ///     }
+    ///
+    ///     fn log(&self, record: &log::Record) {
+    ///         if record.level() == log::Level::Error {
+    ///             assert!(record.args().to_string().contains("0x0002"));
+    ///             ERRORS.fetch_add(1, Ordering::SeqCst);
+    ///         }
+    ///     }
+    ///
+    ///     fn flush(&self) {}
+    /// }
+    ///
+    /// log::set_logger(&Capture).unwrap();
+    /// log::set_max_level(log::LevelFilter::Trace);
+    ///
+    /// // inc r0; load r0, [0x0101], past the end of 256 bytes of memory
+    /// let mut vm = MicroCvm::builder().memory_size(256).build();
+    /// vm.load_program(&[0x07, 0x00, 0x01, 0x00, 0x01, 0x01]).unwrap();
+    /// assert!(vm.run().is_err());
+    /// assert_eq!(ERRORS.load(Ordering::SeqCst), 1);
+    /// ```
+    pub fn step(&mut self) -> Result<(), VmError> {
+        self.instructions += 1;
+        self.cpu.execute_instruction().inspect_err(|e| {
+            log::error!("{}", e);
+            #[cfg(feature = "std")]
+            self.write_core_dump(e);
+        })
+    }
+
+    #[cfg(feature = "std")]
+    fn write_core_dump(&self, error: &VmError) {
+        let (Some(path), Some(report)) = (&self.core_dump_file, error.report()) else {
+            return;
+        };
+        match self.core_dump(report).write(path) {
+            Ok(()) => log::info!("wrote a core dump to {}", path.display()),
+            Err(e) => log::error!("could not write a core dump to {}: {}", path.display(), e),
+        }
+    }
+
+    /// Runs until the program halts or the instruction limit is used up.
+    ///
+    /// ```
+    /// use microcvm_rs::asm::assemble;
+    /// use microcvm_rs::cpu::{FLAG_CARRY, STACK_TOP};
+    /// use microcvm_rs::error::VmError;
+    /// use microcvm_rs::MicroCvm;
+    ///
+    /// // A system call: the service number goes in r0, which the routine replaces with the
+    /// // result, and everything else comes back as it was.
+    /// let program = assemble("
+    ///         mov r0, 0x00
+    ///         store [0xFF90], r0      ; vector table at 0x0200
+    ///         mov r0, 0x02
+    ///         store [0xFF91], r0
+    ///         mov r0, 1               ; service 1: r1 + r2
+    ///         mov r1, 30
+    ///         mov r2, 12
+    ///         mov r3, 7
+    ///         stc
+    ///         int 1
+    ///         hlt
+    ///
+    /// os:     mov r0, r1
+    ///         add r0, r2              ; clears the caller's carry, until iret
+    ///         iret
+    ///
+    ///         .org 0x0200
+    ///         .dw 0, os
+    /// ").unwrap();
+    /// let mut vm = MicroCvm::builder().build();
+    /// vm.load_program(&program).unwrap();
+    /// vm.run().unwrap();
+    /// assert_eq!(vm.cpu().registers[..4], [42, 30, 12, 7]);
+    /// assert_eq!(vm.cpu().flags & FLAG_CARRY, FLAG_CARRY);
+    /// assert_eq!(vm.cpu().sp, STACK_TOP);
+    ///
+    /// // The table has 16 entries.
+    /// let mut vm = MicroCvm::builder().build();
+    /// vm.load_program(&assemble("int 16").unwrap()).unwrap();
+    /// let error = vm.run().unwrap_err();
+    /// assert!(matches!(error.cause(), VmError::InvalidVector { vector: 16, .. }));
+    /// ```
+    pub fn run(&mut self) -> Result<HaltReason, VmError> {
+        self.run_for(u64::MAX)
+    }
+
+    /// Like [`run`](Self::run), but also stops after `instructions` instructions.
+    ///
+    /// ```
+    /// use microcvm_rs::{HaltReason, MicroCvm};
+    ///
+    /// // inc r0; jmp 0
+    /// let mut vm = MicroCvm::builder().max_instructions(10).build();
+    /// vm.load_program(&[0x07, 0x00, 0x05, 0x00, 0x00]).unwrap();
+    /// assert_eq!(vm.run_for(4).unwrap(), HaltReason::InstructionLimit);
+    /// assert_eq!(vm.run().unwrap(), HaltReason::InstructionLimit);
+    /// assert_eq!(vm.cpu().registers[0], 5);
+    /// ```
+    pub fn run_for(&mut self, instructions: u64) -> Result<HaltReason, VmError> {
+        self.cpu.check_entry()?;
+        let budget = instructions.min(self.remaining_instructions());
+        let start = self.instructions;
+        for _ in 0..budget {
+            if self.cpu.halted {
+                return Ok(self.cpu.halt_reason());
+            }
+            if self.paused.is_paused() {
+                return Ok(HaltReason::Paused);
+            }
+            if self.at_breakpoint(start) {
+                return Ok(HaltReason::Breakpoint);
+            }
+            self.step()?;
+        }
+
+        Ok(if self.cpu.halted {
+            self.cpu.halt_reason()
+        } else {
+            HaltReason::InstructionLimit
+        })
+    }
+
+    /// Runs one frame's worth of the program: until it executes `video vsync`, at least
+    /// `max_cycles` cycles have passed, the program halts or the instruction limit is hit.
+    pub fn run_frame(&mut self, max_cycles: u64) -> Result<HaltReason, VmError> {
+        self.cpu.check_entry()?;
+        let end = self.cpu.cycles.saturating_add(max_cycles);
+        let start = self.instructions;
+        self.cpu.frame_done = false;
+        while self.cpu.cycles < end && !self.cpu.frame_done {
+            if self.cpu.halted {
+                return Ok(self.cpu.halt_reason());
+            }
+            if self.remaining_instructions() == 0 {
+                return Ok(HaltReason::InstructionLimit);
+            }
+            if self.paused.is_paused() {
+                return Ok(HaltReason::Paused);
+            }
+            if self.at_breakpoint(start) {
+                return Ok(HaltReason::Breakpoint);
+            }
+            self.step()?;
+        }
+
+        Ok(if self.cpu.halted {
+            self.cpu.halt_reason()
+        } else {
+            HaltReason::FrameComplete
+        })
+    }
+
+    /// Runs every machine in `vms` to completion, spread over one thread per available
+    /// core. The results are in the same order as `vms`.
+    ///
+    /// ```
+    /// use microcvm_rs::{HaltReason, MicroCvm};
+    ///
+    /// // mov r0, 0; add r0, r1; djnz r2, 3; hlt
+    /// let program = [0x06, 0x00, 0x00, 0x03, 0x80, 0x01, 0x0F, 0x02, 0xFA, 0xFF];
+    /// let mut vms: Vec<MicroCvm> = (0..16u16)
+    ///     .map(|i| {
+    ///         let mut vm = MicroCvm::builder().build();
+    ///         vm.load_program(&program).unwrap();
+    ///         vm.cpu_mut().registers[1] = i;
+    ///         vm.cpu_mut().registers[2] = 10;
+    ///         vm
+    ///     })
+    ///     .collect();
+    /// let results = MicroCvm::run_batch(&mut vms);
+    /// for (i, (vm, result)) in vms.iter().zip(results).enumerate() {
+    ///     let code = (i * 10) as u8;
+    ///     assert_eq!(result.unwrap(), HaltReason::Halted { code });
+    ///     assert_eq!(vm.cpu().registers[0], code as u16);
+    /// }
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn run_batch(vms: &mut [MicroCvm]) -> Vec<Result<HaltReason, VmError>> {
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk_size = vms.len().div_ceil(threads).max(1);
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = vms
+                .chunks_mut(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || chunk.iter_mut().map(MicroCvm::run).collect::<Vec<_>>())
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| {
+                    worker
+                        .join()
+                        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+                })
+                .collect()
+        })
+    }
+
+    /// Makes [`run`](Self::run), [`run_for`](Self::run_for) and [`run_frame`](Self::run_frame)
+    /// return [`HaltReason::Paused`] before the next instruction, and keeps them from running
+    /// any until [`resume`](Self::resume). Nothing else changes: queued key events, pending
+    /// interrupts and the frame stay as they are, and [`step`](Self::step) still works for a
+    /// debugger. Use a [`PauseHandle`] to pause a machine running on another thread.
+    ///
+    /// ```
+    /// use microcvm_rs::{HaltReason, MicroCvm};
+    ///
+    /// // inc r0; jmp 0
+    /// let mut vm = MicroCvm::builder().build();
+    /// vm.load_program(&[0x07, 0x00, 0x05, 0x00, 0x00]).unwrap();
+    /// vm.run_for(100).unwrap();
+    /// vm.push_key(0x1C, true);
+    ///
+    /// vm.pause();
+    /// assert!(vm.is_paused());
+    /// let (instructions, cycles) = (vm.instructions(), vm.cpu().cycles);
+    /// assert_eq!(vm.run_for(100).unwrap(), HaltReason::Paused);
+    /// assert_eq!(vm.run_frame(1000).unwrap(), HaltReason::Paused);
+    /// assert_eq!((vm.instructions(), vm.cpu().cycles), (instructions, cycles));
+    ///
+    /// vm.resume();
+    /// assert_eq!(vm.run_for(100).unwrap(), HaltReason::InstructionLimit);
+    /// assert_eq!(vm.instructions(), instructions + 100);
+    /// assert_eq!(vm.cpu().keyboard().events.len(), 1);
+    ///
+    /// // A run on another thread stops between two instructions once paused.
+    /// let handle = vm.pause_handle();
+    /// let worker = std::thread::spawn(move || (vm.run(), vm));
+    /// std::thread::sleep(std::time::Duration::from_millis(10));
+    /// handle.pause();
+    /// let (result, mut vm) = worker.join().unwrap();
+    /// assert_eq!(result.unwrap(), HaltReason::Paused);
+    /// let stopped_at = vm.instructions();
+    /// assert!(stopped_at > instructions + 100);
+    /// assert_eq!(vm.run().unwrap(), HaltReason::Paused);
+    /// assert_eq!(vm.instructions(), stopped_at);
+    /// ```
+    pub fn pause(&self) {
+        self.paused.pause();
+    }
+
+    pub fn resume(&self) {
+        self.paused.resume();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.is_paused()
+    }
+
+    pub fn pause_handle(&self) -> PauseHandle {
+        self.paused.clone()
+    }
+
+    /// Tells the machine whether the host's window has focus, as the window does whenever
+    /// that changes. Built with
+    /// [`pause_on_focus_loss`](MicroCvmBuilder::pause_on_focus_loss), the machine pauses and
+    /// mutes when focus goes and picks up again when it comes back, unless it was already
+    /// paused, which the host has to undo itself. Guest-visible time stops with it: no
+    /// cycles pass and no frames are presented, so the cycle counter, key stamps and vblank
+    /// count carry on as if the pause never happened. Only the RTC, which reads the host's
+    /// clock, jumps ahead.
+    ///
+    /// ```
+    /// use std::sync::atomic::Ordering;
+    /// use microcvm_rs::{HaltReason, MicroCvm};
+    ///
+    /// // inc r0; jmp 0
+    /// let program = [0x07, 0x00, 0x05, 0x00, 0x00];
+    /// let mut vm = MicroCvm::builder().pause_on_focus_loss(true).build();
+    /// vm.load_program(&program).unwrap();
+    /// vm.run_for(100).unwrap();
+    ///
+    /// vm.focus_changed(false);
+    /// assert!(vm.is_paused() && vm.auto_paused());
+    /// assert!(vm.cpu().audio.muted.load(Ordering::Relaxed));
+    /// let cycles = vm.cpu().cycles;
+    /// assert_eq!(vm.run_for(100).unwrap(), HaltReason::Paused);
+    /// assert_eq!((vm.instructions(), vm.cpu().cycles), (100, cycles));
+    ///
+    /// vm.focus_changed(true);
+    /// assert!(!vm.is_paused() && !vm.cpu().audio.muted.load(Ordering::Relaxed));
+    /// vm.run_for(100).unwrap();
+    /// assert_eq!(vm.instructions(), 200);
+    ///
+    /// // A pause someone asked for outlasts the focus coming back.
+    /// vm.pause();
+    /// vm.focus_changed(false);
+    /// vm.focus_changed(true);
+    /// assert!(vm.is_paused() && !vm.auto_paused());
+    ///
+    /// // Off by default.
+    /// let mut vm = MicroCvm::builder().build();
+    /// vm.load_program(&program).unwrap();
+    /// vm.focus_changed(false);
+    /// vm.run_for(100).unwrap();
+    /// assert_eq!(vm.instructions(), 100);
+    /// ```
+    pub fn focus_changed(&mut self, focused: bool) {
+        if focused {
+            if self.auto_paused {
+                self.auto_paused = false;
+                self.resume();
+                self.cpu.audio.muted.store(false, Ordering::Relaxed);
+            }
+        } else if self.pause_on_focus_loss && !self.is_paused() {
+            self.auto_paused = true;
+            self.pause();
+            self.cpu.audio.muted.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Whether the machine is paused because the window lost focus.
+    pub fn auto_paused(&self) -> bool {
+        self.auto_paused && self.is_paused()
+    }
+
+    /// The registers the debug overlay shows, with both rates left at 0 for the host to
+    /// measure. [`overlay::layout`](crate::overlay::layout) turns them into text and
+    /// [`overlay::draw_text`](crate::overlay::draw_text) draws that over a converted frame.
+    ///
+    /// ```
+    /// use microcvm_rs::MicroCvm;
+    /// use microcvm_rs::overlay::{draw_text, layout};
+    ///
+    /// // mov r0, 0x2a; sub r1, 0; stc; hlt
+    /// let mut vm = MicroCvm::builder().resolution(160, 40).build();
+    /// vm.load_program(&[0x06, 0x00, 0x2a, 0x04, 0x01, 0x00, 0x19, 0xFF]).unwrap();
+    /// vm.run().unwrap();
+    /// let mut stats = vm.debug_stats();
+    /// stats.instructions_per_second = 1_500_000;
+    /// stats.frames_per_second = 60;
+    /// assert_eq!(layout(&stats), [
+    ///     "PC 0007 SP FF00 ZC-",
+    ///     "R0 2A R1 00 R2 00 R3 00",
+    ///     "R4 00 R5 00 R6 00 R7 00",
+    ///     "IPS 1500000 FPS 60",
+    /// ]);
+    ///
+    /// let mut frame = vec![0; 160 * 40 * 4];
+    /// vm.frame_rgba(&mut frame);
+    /// draw_text(&layout(&stats), 160, &mut frame);
+    /// // The top of the 'P' in the corner, after two pixels of padding.
+    /// assert_eq!(frame[(2 * 160 + 2) * 4..][..4], [255, 255, 255, 255]);
+    /// assert!(vm.framebuffer().bytes().iter().all(|&byte| byte == 0));
+    /// ```
+    pub fn debug_stats(&self) -> DebugStats {
+        DebugStats {
+            pc: self.cpu.pc,
+            sp: self.cpu.sp,
+            registers: self.cpu.registers,
+            register_width: self.cpu.register_width,
+            flags: self.cpu.flags,
+            instructions_per_second: 0,
+            frames_per_second: 0,
+        }
+    }
+
+    /// Replaces what the controllers hold, as the window does after polling them every
+    /// frame. Keys held on the keyboard are kept apart in
+    /// [`MicroCVMCpu::gamepad`](crate::cpu::MicroCVMCpu::gamepad) and the guest sees both:
+    /// the buttons of either, and the keyboard's direction keys on a centred left stick.
+    ///
+    /// ```
+    /// use microcvm_rs::asm::assemble;
+    /// use microcvm_rs::gamepad::{BUTTON_A, BUTTON_LEFT, BUTTON_START, GamepadState};
+    /// use microcvm_rs::MicroCvm;
+    ///
+    /// let program = assemble("
+    ///         load r0, [0xFFC0]       ; buttons, low byte
+    ///         load r1, [0xFFC1]       ; buttons, high byte
+    ///         load r2, [0xFFC2]       ; left stick x
+    ///         load r3, [0xFFC5]       ; right stick y
+    ///         hlt
+    /// ").unwrap();
+    /// let mut vm = MicroCvm::builder().build();
+    /// vm.load_program(&program).unwrap();
+    /// vm.inject_gamepad_state(GamepadState {
+    ///     buttons: BUTTON_START,
+    ///     left_stick: [0, 0],
+    ///     right_stick: [0, -64],
+    /// });
+    /// vm.cpu_mut().gamepad_mut().key(BUTTON_A | BUTTON_LEFT, true);
+    /// vm.run().unwrap();
+    /// assert_eq!(vm.cpu().registers[..4], [0x14, 0x01, 0x81, 0xC0]);
+    ///
+    /// // A controller going away leaves the keys alone.
+    /// vm.inject_gamepad_state(GamepadState::default());
+    /// assert_eq!(vm.cpu().gamepad().state().buttons, BUTTON_A | BUTTON_LEFT);
+    /// ```
+    pub fn inject_gamepad_state(&mut self, state: GamepadState) {
+        self.cpu.gamepad_mut().pad = state;
+    }
+
+    /// Adds pointer motion in framebuffer pixels, as the window does while it holds the
+    /// pointer. Only a guest that has set bit 0 of the mouse control register, at
+    /// [`MOUSE_BASE`](crate::cpu::MOUSE_BASE) + 5, sees it: each
+    /// [`tick_frame`](Self::tick_frame) turns the motion since the last one into the signed
+    /// deltas of the two registers after it. Fractions of a pixel carry over to the next
+    /// frame, and motion that doesn't fit in a byte saturates.
+    ///
+    /// ```
+    /// use microcvm_rs::asm::assemble;
+    /// use microcvm_rs::MicroCvm;
+    ///
+    /// // Adds up dx in r2 and dy in r3 over every vblank.
+    /// let program = assemble("
+    ///         mov r0, 0x00
+    ///         store [0xFF90], r0      ; vector table at 0x0200
+    ///         mov r0, 0x02
+    ///         store [0xFF91], r0
+    ///         mov r0, 1
+    ///         store [0xFFE5], r0      ; relative mode
+    ///         ei
+    /// idle:   jmp idle
+    ///
+    /// on_vblank:
+    ///         load r0, [0xFFE6]
+    ///         add r2, r0
+    ///         load r0, [0xFFE7]
+    ///         add r3, r0
+    ///         iret
+    ///
+    ///         .org 0x0200
+    ///         .dw on_vblank
+    /// ").unwrap();
+    /// let mut vm = MicroCvm::builder().build();
+    /// vm.load_program(&program).unwrap();
+    /// vm.run_for(100).unwrap();
+    ///
+    /// for (dx, dy) in [(1.5, -0.75), (1.5, -0.75), (0.25, 0.0)] {
+    ///     vm.inject_mouse_motion(dx, dy);
+    /// }
+    /// vm.tick_frame();
+    /// assert_eq!(vm.cpu().mouse().delta, [3, -1]);
+    /// vm.run_for(100).unwrap();
+    /// assert_eq!(vm.cpu().registers[2..4], [3, 0xFF]);
+    ///
+    /// // The quarter pixel left over from each axis makes a whole one with the next.
+    /// vm.inject_mouse_motion(0.75, -0.5);
+    /// vm.tick_frame();
+    /// assert_eq!(vm.cpu().mouse().delta, [1, -1]);
+    ///
+    /// // A flick too fast for a byte saturates, and the excess is gone by the next frame.
+    /// vm.inject_mouse_motion(1000.0, -1000.0);
+    /// vm.tick_frame();
+    /// assert_eq!(vm.cpu().mouse().delta, [127, -128]);
+    /// vm.tick_frame();
+    /// assert_eq!(vm.cpu().mouse().delta, [0, 0]);
+    ///
+    /// // Back in absolute mode, motion is ignored.
+    /// vm.cpu_mut().mouse_mut().control = 0;
+    /// vm.inject_mouse_motion(5.0, 5.0);
+    /// vm.tick_frame();
+    /// assert_eq!(vm.cpu().mouse().delta, [0, 0]);
+    /// ```
+    pub fn inject_mouse_motion(&mut self, dx: f64, dy: f64) {
+        self.cpu.mouse_mut().add_motion(dx, dy);
+    }
+
+    /// Moves the pointer the guest sees in absolute mode to `x`, `y` in logical window
+    /// pixels, for a window showing the frame at `placement` (as
+    /// [`frame_rgba_letterboxed`](Self::frame_rgba_letterboxed) returns it) on a display with
+    /// `scale_factor` physical pixels to the logical one. A pointer over the bars is held at
+    /// the nearest edge of the frame. Ignored in relative mode.
+    ///
+    /// ```
+    /// use microcvm_rs::MicroCvm;
+    /// use microcvm_rs::letterbox::place;
+    ///
+    /// let mut vm = MicroCvm::builder().resolution(4, 3).build();
+    /// let mut pointer = |scale_factor: f64, x: f64, y: f64| {
+    ///     // A window 10x7 logical pixels, however many physical pixels that is.
+    ///     let (width, height) = ((10.0 * scale_factor) as u32, (7.0 * scale_factor) as u32);
+    ///     let placement = place(4, 3, width, height, false);
+    ///     vm.inject_mouse_position(&placement, x, y, scale_factor);
+    ///     let mouse = vm.cpu().mouse();
+    ///     (placement.scale, mouse.x, mouse.y)
+    /// };
+    ///
+    /// // 10x7 physical fits the frame at 2x, 8x6 at 1, 0.
+    /// assert_eq!(pointer(1.0, 1.0, 0.0), (2, 0, 0));
+    /// assert_eq!(pointer(1.0, 8.9, 5.9), (2, 3, 2));
+    /// // 15x10 physical is 3x, 12x9 at 1, 0.
+    /// assert_eq!(pointer(1.5, 1.0, 0.0), (3, 0, 0));
+    /// assert_eq!(pointer(1.5, 5.0, 3.0), (3, 2, 1));
+    /// // 20x14 physical is 4x, 16x12 at 2, 1.
+    /// assert_eq!(pointer(2.0, 1.0, 0.5), (4, 0, 0));
+    /// assert_eq!(pointer(2.0, 5.0, 3.0), (4, 2, 1));
+    /// // Over the bars, the pointer stops at the edge.
+    /// assert_eq!(pointer(2.0, 0.0, 6.9), (4, 0, 2));
+    /// assert_eq!(pointer(2.0, 9.9, 0.0), (4, 3, 0));
+    /// ```
+    pub fn inject_mouse_position(
+        &mut self,
+        placement: &Placement,
+        x: f64,
+        y: f64,
+        scale_factor: f64,
+    ) {
+        let (x, y) = placement.to_frame(x, y, scale_factor);
+        let (width, height) = (self.width, self.height);
+        let mouse = self.cpu.mouse_mut();
+        if !mouse.relative() {
+            mouse.x = x.clamp(0.0, width.saturating_sub(1) as f64) as u16;
+            mouse.y = y.clamp(0.0, height.saturating_sub(1) as f64) as u16;
+        }
+    }
+
+    /// Makes [`run`](Self::run), [`run_for`](Self::run_for) and [`run_frame`](Self::run_frame)
+    /// return [`HaltReason::Breakpoint`] before executing the instruction at `addr`. A run
+    /// that starts on a breakpoint executes that instruction first, so running again after a
+    /// break moves on. [`step`](Self::step) ignores breakpoints.
+    ///
+    /// ```
+    /// use microcvm_rs::debugger::{DEBUGGER_HEIGHT, DEBUGGER_WIDTH, Debugger, LineStyle, paint};
+    /// use microcvm_rs::{HaltReason, MicroCvm};
+    ///
+    /// // inc r0; jmp 0
+    /// let mut vm = MicroCvm::builder().build();
+    /// vm.load_program(&[0x07, 0x00, 0x05, 0x00, 0x00]).unwrap();
+    /// vm.add_breakpoint(0x0002);
+    /// assert_eq!(vm.run_for(100).unwrap(), HaltReason::Breakpoint);
+    /// assert_eq!((vm.cpu().pc, vm.cpu().registers[0]), (0x0002, 1));
+    /// assert_eq!(vm.run_frame(1000).unwrap(), HaltReason::Breakpoint);
+    /// assert_eq!((vm.cpu().pc, vm.cpu().registers[0]), (0x0002, 2));
+    ///
+    /// // The debugger window marks the instruction at pc and the breakpoint on it.
+    /// let lines = Debugger::new().lines(&vm);
+    /// let current = lines.iter().position(|line| line.style == LineStyle::Current).unwrap();
+    /// assert_eq!(lines[current - 1].text, "  0x0000: inc r0");
+    /// assert_eq!(lines[current].text, ">*0x0002: jmp 0x0000");
+    /// // Painted offscreen, the line at pc sits on its highlight bar.
+    /// let width = DEBUGGER_WIDTH as usize;
+    /// let mut frame = vec![0; width * DEBUGGER_HEIGHT as usize * 4];
+    /// paint(&lines, width, &mut frame);
+    /// let bar_top = 4 + current * 10;
+    /// assert_eq!(frame[(bar_top * width + width - 1) * 4..][..4], [40, 60, 140, 255]);
+    ///
+    /// assert!(vm.remove_breakpoint(0x0002));
+    /// assert_eq!(vm.run_for(100).unwrap(), HaltReason::InstructionLimit);
+    /// ```
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.insert(addr);
+    }
+
+    /// Returns whether there was a breakpoint at `addr`.
+    pub fn remove_breakpoint(&mut self, addr: u16) -> bool {
+        self.breakpoints.remove(&addr)
+    }
+
+    pub fn breakpoints(&self) -> &BTreeSet<u16> {
+        &self.breakpoints
+    }
+
+    // `start` is the instruction count the run began at, it never stops on its first one.
+    fn at_breakpoint(&self, start: u64) -> bool {
+        self.instructions != start && self.breakpoints.contains(&self.cpu.pc)
+    }
+
+    /// Instructions executed since the machine was built.
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    pub fn remaining_instructions(&self) -> u64 {
+        self.max_instructions.saturating_sub(self.instructions)
+    }
+
+    pub fn halted(&self) -> bool {
+        self.cpu.halted
+    }
+
+    /// The code the program halted with, r0 at its `hlt`, or None while it hasn't halted.
+    /// With 16-bit registers an r0 above 255 reads as 255.
+    ///
+    /// ```
+    /// use microcvm_rs::MicroCvm;
+    /// use microcvm_rs::asm::assemble;
+    ///
+    /// let mut vm = MicroCvm::builder().build();
+    /// vm.load_program(&assemble("mov r0, 7\nhlt").unwrap()).unwrap();
+    /// assert_eq!(vm.exit_code(), None);
+    /// vm.run().unwrap();
+    /// assert_eq!(vm.exit_code(), Some(7));
+    /// ```
+    pub fn exit_code(&self) -> Option<u8> {
+        self.cpu.halted.then(|| self.cpu.exit_code())
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// One frame of pixels, row by row, at the current color depth.
+    ///
+    /// `video copyrect` moves pixels around inside it, overlapping or not:
+    ///
+    /// ```
+    /// use microcvm_rs::asm::assemble;
+    /// use microcvm_rs::MicroCvm;
+    /// use microcvm_rs::video::ColorDepth;
+    ///
+    /// // A 4x4 screen of pixels 0 to 15 after copying a `w`x`h` rectangle from `src` to `dst`.
+    /// fn copy(src: (u8, u8), dst: (u8, u8), w: u8, h: u8) -> Vec<u8> {
+    ///     let program = assemble(&format!("
+    ///             mov r0, {}
+    ///             mov r1, {}
+    ///             mov r2, {}
+    ///             mov r3, {}
+    ///             mov r4, {}
+    ///             mov r5, {}
+    ///             video 0x07, r0
+    ///             hlt
+    ///     ", src.0, src.1, dst.0, dst.1, w, h)).unwrap();
+    ///     let mut vm = MicroCvm::builder()
+    ///         .resolution(4, 4)
+    ///         .color_depth(ColorDepth::Eight)
+    ///         .build();
+    ///     let bytes: Vec<u8> = (0..16).collect();
+    ///     vm.cpu_mut().video_memory.bytes_mut().copy_from_slice(&bytes);
+    ///     vm.load_program(&program).unwrap();
+    ///     vm.run().unwrap();
+    ///     vm.framebuffer().bytes().to_vec()
+    /// }
+    ///
+    /// // One pixel right, the last column falls off the screen.
+    /// assert_eq!(copy((0, 0), (1, 0), 4, 4), [
+    ///     0, 0, 1, 2,
+    ///     4, 4, 5, 6,
+    ///     8, 8, 9, 10,
+    ///     12, 12, 13, 14,
+    /// ]);
+    /// // One pixel down.
+    /// assert_eq!(copy((0, 0), (0, 1), 4, 4), [
+    ///     0, 1, 2, 3,
+    ///     0, 1, 2, 3,
+    ///     4, 5, 6, 7,
+    ///     8, 9, 10, 11,
+    /// ]);
+    /// // One pixel right and one down.
+    /// assert_eq!(copy((0, 0), (1, 1), 3, 3), [
+    ///     0, 1, 2, 3,
+    ///     4, 0, 1, 2,
+    ///     8, 4, 5, 6,
+    ///     12, 8, 9, 10,
+    /// ]);
+    /// // Back up and to the left, and a source clipped at the bottom right corner.
+    /// assert_eq!(copy((1, 1), (0, 0), 3, 3)[..4], [5, 6, 7, 3]);
+    /// assert_eq!(copy((2, 2), (0, 0), 4, 4), [
+    ///     10, 11, 2, 3,
+    ///     14, 15, 6, 7,
+    ///     8, 9, 10, 11,
+    ///     12, 13, 14, 15,
+    /// ]);
+    /// ```
+    pub fn framebuffer(&self) -> &VideoMemory {
+        &self.cpu.video_memory
+    }
+
+    /// Converts the framebuffer to RGBA in `out`, four bytes per pixel, draws the enabled
+    /// sprites over it and applies the brightness. This is the frame the window shows; video memory itself
+    /// never contains the sprites.
+    ///
+    /// Two overlapping sprites, the first hanging off the top left corner, the second with
+    /// a transparent hole:
+    ///
+    /// ```
+    /// use microcvm_rs::MicroCvm;
+    /// use microcvm_rs::sprite::Sprite;
+    ///
+    /// let mut vm = MicroCvm::builder().resolution(6, 4).build();
+    /// let cpu = vm.cpu_mut();
+    /// // 3x3 of red at 0x1000, 2x2 of blue with a black top left corner at 0x1100.
+    /// for pixel in cpu.memory_mut()[0x1000..0x1000 + 27].chunks_mut(3) {
+    ///     pixel.copy_from_slice(&[255, 0, 0]);
+    /// }
+    /// cpu.memory_mut()[0x1100..0x1100 + 12].copy_from_slice(&[0, 0, 0, 0, 0, 255, 0, 0, 255, 0, 0, 255]);
+    /// cpu.sprites_mut().sprites[0] = Sprite {
+    ///     enabled: true, x: -1, y: -1, width: 3, height: 3, source: 0x1000, transparent: [1, 2, 3],
+    /// };
+    /// cpu.sprites_mut().sprites[1] = Sprite {
+    ///     enabled: true, x: 1, y: 1, width: 2, height: 2, source: 0x1100, transparent: [0, 0, 0],
+    /// };
+    /// let green = cpu.video_memory.encode(0, 255, 0);
+    /// cpu.video_memory.fill(0, 6 * 4, green);
+    ///
+    /// let mut frame = vec![0; 6 * 4 * 4];
+    /// vm.frame_rgba(&mut frame);
+    /// let expected = [
+    ///     "RRGGGG",
+    ///     "RRBGGG",
+    ///     "GBBGGG",
+    ///     "GGGGGG",
+    /// ];
+    /// for (y, row) in expected.iter().enumerate() {
+    ///     for (x, name) in row.chars().enumerate() {
+    ///         let color = match name {
+    ///             'R' => [255, 0, 0, 255],
+    ///             'G' => [0, 255, 0, 255],
+    ///             _ => [0, 0, 255, 255],
+    ///         };
+    ///         let pixel = (y * 6 + x) * 4;
+    ///         assert_eq!(frame[pixel..pixel + 4], color, "pixel {}, {}", x, y);
+    ///     }
+    /// }
+    /// assert!((0..6 * 4).all(|pixel| vm.framebuffer().pixel(pixel).to_rgba() == [0, 255, 0, 255]));
+    /// ```
+    ///
+    /// With the tile mode bit of [`VIDEO_CONTROL`](crate::cpu::VIDEO_CONTROL) set the map takes
+    /// the place of video memory, and what of it changed since the previous frame joins the
+    /// CPU's [dirty region](crate::cpu::MicroCVMCpu::take_dirty). A 16x8 screen has a map of
+    /// 3x2 tiles, which wraps around when scrolled:
+    ///
+    /// ```
+    /// use microcvm_rs::MicroCvm;
+    /// use microcvm_rs::cpu::VIDEO_CONTROL_TILES;
+    /// use microcvm_rs::tilemap::TILE_BYTES;
+    ///
+    /// fn check(frame: &[u8], expected: [&str; 8]) {
+    ///     for (y, row) in expected.iter().enumerate() {
+    ///         for (x, name) in row.chars().enumerate() {
+    ///             let color = match name {
+    ///                 'R' => [255, 0, 0, 255],
+    ///                 'B' => [0, 0, 255, 255],
+    ///                 _ => [0, 0, 0, 255],
+    ///             };
+    ///             let pixel = (y * 16 + x) * 4;
+    ///             assert_eq!(frame[pixel..pixel + 4], color, "pixel {}, {}", x, y);
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let mut vm = MicroCvm::builder().resolution(16, 8).build();
+    /// let cpu = vm.cpu_mut();
+    /// cpu.video_control = VIDEO_CONTROL_TILES;
+    /// cpu.tiles_mut().tile_set = 0x1000;
+    /// cpu.tiles_mut().map = 0x2000;
+    /// // Tile 0 is black, tile 1 red, tile 2 blue.
+    /// for (tile, rgb) in [(1, [255, 0, 0]), (2, [0, 0, 255])] {
+    ///     for pixel in cpu.memory_mut()[0x1000 + tile * TILE_BYTES..][..TILE_BYTES].chunks_mut(3) {
+    ///         pixel.copy_from_slice(&rgb);
+    ///     }
+    /// }
+    /// cpu.memory_mut()[0x2000..0x2006].copy_from_slice(&[1, 0, 2, 0, 2, 1]);
+    ///
+    /// let mut frame = vec![0; 16 * 8 * 4];
+    /// vm.frame_rgba(&mut frame);
+    /// check(&frame, ["RRRRRRRRKKKKKKKK"; 8]);
+    /// assert_eq!(vm.cpu().tiles().redrawn_cells(), [0, 1, 2, 3, 4, 5]);
+    ///
+    /// // 20 pixels right and 12 down starts inside the last column and the last row.
+    /// vm.cpu_mut().tiles_mut().scroll_x = 20;
+    /// vm.cpu_mut().tiles_mut().scroll_y = 12;
+    /// vm.frame_rgba(&mut frame);
+    /// let top = "RRRRKKKKKKKKBBBB";
+    /// let bottom = "BBBBRRRRRRRRKKKK";
+    /// check(&frame, [top, top, top, top, bottom, bottom, bottom, bottom]);
+    /// assert!(vm.cpu().tiles().redrawn_cells().is_empty());
+    ///
+    /// // Changing one cell redraws only that tile.
+    /// vm.cpu_mut().memory_mut()[0x2004] = 1;
+    /// vm.frame_rgba(&mut frame);
+    /// assert_eq!(vm.cpu().tiles().redrawn_cells(), [4]);
+    /// let top = "RRRRKKKKKKKKRRRR";
+    /// check(&frame, [top, top, top, top, bottom, bottom, bottom, bottom]);
+    /// ```
+    ///
+    /// [`VIDEO_BRIGHTNESS`](crate::cpu::VIDEO_BRIGHTNESS) scales the finished frame, rounding
+    /// to the nearest value, and leaves video memory alone. `video fadeto` moves it to a
+    /// target over a number of presented frames:
+    ///
+    /// ```
+    /// use microcvm_rs::asm::assemble;
+    /// use microcvm_rs::MicroCvm;
+    ///
+    /// let mut vm = MicroCvm::builder().resolution(2, 1).build();
+    /// vm.cpu_mut().video_memory.bytes_mut().copy_from_slice(&[255, 200, 1, 0, 128, 77]);
+    /// let mut frame = [0; 8];
+    ///
+    /// vm.cpu_mut().brightness = 0;
+    /// vm.frame_rgba(&mut frame);
+    /// assert_eq!(frame, [0, 0, 0, 255, 0, 0, 0, 255]);
+    /// assert_eq!(vm.framebuffer().bytes(), [255, 200, 1, 0, 128, 77]);
+    ///
+    /// vm.cpu_mut().brightness = 128;
+    /// vm.frame_rgba(&mut frame);
+    /// assert_eq!(frame, [128, 100, 1, 255, 0, 64, 39, 255]);
+    ///
+    /// // Fade from full brightness to black over four frames.
+    /// let program = assemble("
+    ///         mov r0, 255
+    ///         store [0xFF19], r0
+    ///         mov r0, 0
+    ///         mov r1, 4
+    ///         video 0x06, r0
+    ///         hlt
+    /// ").unwrap();
+    /// vm.load_program(&program).unwrap();
+    /// vm.run().unwrap();
+    /// let mut levels = Vec::new();
+    /// for _ in 0..5 {
+    ///     vm.tick_frame();
+    ///     levels.push(vm.cpu().brightness);
+    /// }
+    /// assert_eq!(levels, [191, 127, 63, 0, 0]);
+    /// ```
+    pub fn frame_rgba(&mut self, out: &mut [u8]) {
+        let (width, height) = (self.width as usize, self.height as usize);
+        let tiled = self.cpu.video_control & VIDEO_CONTROL_TILES != 0;
+        if tiled {
+            self.cpu.update_tiles(width, height);
+        }
+
+        // Rows don't depend on each other, so bands of them can be drawn in parallel.
+        let cpu = &self.cpu;
+        let (tiles, sprites, video) = (cpu.tiles(), cpu.sprites(), &cpu.video_memory);
+        let (memory, brightness) = (cpu.memory(), cpu.brightness);
+        let draw = |first_row: usize, rgba: &mut [u8]| {
+            if tiled {
+                tiles.draw_rows(first_row, width, height, rgba);
+            } else {
+                video.copy_rgba(first_row * width, rgba);
+            }
+            sprites.composite_rows(memory, width, first_row, rgba);
+            if brightness != u8::MAX {
+                for pixel in rgba.as_chunks_mut::<4>().0 {
+                    let [r, g, b, a] = *pixel;
+                    *pixel = Color { r, g, b, a }.scale_brightness(brightness).to_rgba();
+                }
+            }
+        };
+
+        #[cfg(feature = "rayon")]
+        if width > 0 && width * height >= self.parallel_frame_pixels {
+            use rayon::prelude::*;
+            let band_rows = height.div_ceil(rayon::current_num_threads() * 4).max(1);
+            out.par_chunks_mut(band_rows * width * 4)
+                .enumerate()
+                .for_each(|(band, rgba)| draw(band * band_rows, rgba));
+            return;
+        }
+        draw(0, out);
+    }
+
+    /// [`frame_rgba`](Self::frame_rgba) scaled up by `scale` in both directions into `out`,
+    /// `width * scale` by `height * scale` pixels, with `crt` drawn over the scaled lines.
+    ///
+    /// Scanlines are output lines. A 1x2 screen at half brightness on dim lines:
+    ///
+    /// ```
+    /// use microcvm_rs::MicroCvm;
+    /// use microcvm_rs::crt::CrtEffect;
+    ///
+    /// let mut vm = MicroCvm::builder().resolution(1, 2).build();
+    /// vm.cpu_mut().video_memory.bytes_mut().copy_from_slice(&[200, 100, 0, 100, 200, 40]);
+    /// let crt = CrtEffect { scanline_dim: 50, smear: false };
+    /// let (top, bottom) = ([200, 100, 0, 255], [100, 200, 40, 255]);
+    /// let (top_dim, bottom_dim) = ([100, 50, 0, 255], [50, 100, 20, 255]);
+    ///
+    /// let mut lines = |scale: usize| {
+    ///     let mut out = vec![0; scale * scale * 2 * 4];
+    ///     vm.frame_rgba_scaled(scale as u32, Some(crt), &mut out);
+    ///     out.chunks(scale * 4)
+    ///         .map(|line| {
+    ///             assert!(line.chunks(4).all(|pixel| pixel == &line[..4]));
+    ///             <[u8; 4]>::try_from(&line[..4]).unwrap()
+    ///         })
+    ///         .collect::<Vec<_>>()
+    /// };
+    /// assert_eq!(lines(1), [top, bottom_dim]);
+    /// assert_eq!(lines(2), [top, top_dim, bottom, bottom_dim]);
+    /// assert_eq!(lines(3), [top, top, top_dim, bottom, bottom, bottom_dim]);
+    ///
+    /// // Smearing blends the first output pixel of a VM pixel with the one to its left.
+    /// let mut vm = MicroCvm::builder().resolution(2, 1).build();
+    /// vm.cpu_mut().video_memory.bytes_mut().copy_from_slice(&[0, 0, 0, 200, 200, 200]);
+    /// let mut out = vec![0; 4 * 2 * 4];
+    /// vm.frame_rgba_scaled(2, Some(CrtEffect { scanline_dim: 0, smear: true }), &mut out);
+    /// assert_eq!(out[..16], [0, 0, 0, 255, 0, 0, 0, 255, 150, 150, 150, 255, 200, 200, 200, 255]);
+    /// ```
+    ///
+    /// Neither this nor [`frame_rgba`](Self::frame_rgba) allocates once the machine is
+    /// built, apart from the tile layer's cache on the first tiled frame:
+    ///
+    /// ```rust,standalone_crate
+    /// use std::alloc::{GlobalAlloc, Layout, System};
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    ///
+    /// use microcvm_rs::MicroCvm;
+    /// use microcvm_rs::cpu::VIDEO_CONTROL_TILES;
+    /// use microcvm_rs::crt::CrtEffect;
+    ///
+    /// static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+    ///
+    /// struct Counting;
+    ///
+    /// unsafe impl GlobalAlloc for Counting {
+    ///     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    ///         ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    ///         unsafe { System.alloc(layout) }
+    ///     }
+    ///
+    ///     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    ///         unsafe { System.dealloc(ptr, layout) }
+    ///     }
+    /// }
+    ///
+    /// #[global_allocator]
+    /// static ALLOCATOR: Counting = Counting;
+    ///
+    /// fn main() {
+    ///     let mut vm = MicroCvm::builder().resolution(64, 48).build();
+    ///     let sprite = &mut vm.cpu_mut().sprites_mut().sprites[0];
+    ///     (sprite.enabled, sprite.width, sprite.height) = (true, 8, 8);
+    ///     vm.cpu_mut().brightness = 128;
+    ///     let crt = Some(CrtEffect { scanline_dim: 30, smear: true });
+    ///     let mut frame = vec![0; 64 * 48 * 4];
+    ///     let mut scaled = vec![0; 64 * 48 * 4 * 9];
+    ///
+    ///     for tiled in [false, true] {
+    ///         if tiled {
+    ///             vm.cpu_mut().video_control |= VIDEO_CONTROL_TILES;
+    ///             vm.frame_rgba(&mut frame);
+    ///         }
+    ///         let before = ALLOCATIONS.load(Ordering::Relaxed);
+    ///         for _ in 0..10 {
+    ///             vm.frame_rgba(&mut frame);
+    ///             vm.frame_rgba_scaled(3, crt, &mut scaled);
+    ///             vm.tick_frame();
+    ///         }
+    ///         assert_eq!(ALLOCATIONS.load(Ordering::Relaxed), before);
+    ///     }
+    /// }
+    /// ```
+    pub fn frame_rgba_scaled(&mut self, scale: u32, crt: Option<CrtEffect>, out: &mut [u8]) {
+        let mut staging = core::mem::take(&mut self.staging);
+        self.frame_rgba(&mut staging);
+        scale_frame(&staging, self.width as usize, scale as usize, crt, out);
+        self.staging = staging;
+    }
+
+    /// Draws `crt` over every frame from here on, in the window and
+    /// [`render_frame_to_rgba`](Self::render_frame_to_rgba) alike, or nothing for `None`.
+    pub fn set_crt(&mut self, crt: Option<CrtEffect>) {
+        self.crt = crt;
+    }
+
+    pub fn crt(&self) -> Option<CrtEffect> {
+        self.crt
+    }
+
+    /// The frame as the window would show it at 1x, with no window: the pipeline that
+    /// [`frame_rgba_letterboxed`](Self::frame_rgba_letterboxed) draws to the window at a
+    /// bigger scale, [`crt`](Self::set_crt) included. For golden image tests, with
+    /// [`golden::check`](crate::golden::check):
+    ///
+    /// ```
+    /// use microcvm_rs::MicroCvm;
+    /// use microcvm_rs::crt::CrtEffect;
+    /// use microcvm_rs::demo::{DEMO_HEIGHT, DEMO_WIDTH, bounce_program};
+    /// use microcvm_rs::golden::{self, GoldenError, Tolerance};
+    /// use microcvm_rs::vm::CYCLES_PER_FRAME;
+    ///
+    /// let mut vm = MicroCvm::builder().resolution(DEMO_WIDTH, DEMO_HEIGHT).build();
+    /// vm.load_program(&bounce_program()).unwrap();
+    /// for _ in 0..1000 {
+    ///     vm.run_frame(CYCLES_PER_FRAME).unwrap();
+    ///     vm.tick_frame();
+    /// }
+    /// vm.set_crt(Some(CrtEffect { scanline_dim: 25, smear: false }));
+    /// let mut frame = vm.render_frame_to_rgba();
+    /// let golden = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/bounce-1000.png");
+    /// golden::check(golden, DEMO_WIDTH, DEMO_HEIGHT, &frame, Tolerance::Exact).unwrap();
+    ///
+    /// // One channel off by 2 fails an exact match but not a tolerant one.
+    /// frame[0] ^= 2;
+    /// let scratch = std::env::temp_dir().join("microcvm-golden-doctest.png");
+    /// std::fs::copy(golden, &scratch).unwrap();
+    /// let exact = golden::check(&scratch, DEMO_WIDTH, DEMO_HEIGHT, &frame, Tolerance::Exact);
+    /// let Err(GoldenError::Mismatch { difference, .. }) = exact else {
+    ///     panic!("expected a mismatch, got {:?}", exact);
+    /// };
+    /// assert_eq!((difference.pixels, difference.first, difference.largest), (1, (0, 0), 2));
+    /// golden::check(&scratch, DEMO_WIDTH, DEMO_HEIGHT, &frame, Tolerance::Within(2)).unwrap();
+    /// ```
+    pub fn render_frame_to_rgba(&mut self) -> Vec<u8> {
+        let mut out = vec![0; self.width as usize * self.height as usize * 4];
+        self.frame_rgba_scaled(1, self.crt, &mut out);
+        out
+    }
+
+    /// Draws the frame into `out`, an RGBA window `window_width` by `window_height` pixels,
+    /// at the largest whole scale that fits and centred, with `letterbox` filling the rest
+    /// and the [`crt`](Self::set_crt) effect over the scaled lines.
+    /// The border, if there is one, is a 1-pixel line just outside the frame and costs the
+    /// frame the room for it. While the guest has set
+    /// [`VIDEO_CONTROL_LETTERBOX`](crate::cpu::VIDEO_CONTROL_LETTERBOX) the bars take the
+    /// color in [`VIDEO_LETTERBOX`](crate::cpu::VIDEO_LETTERBOX) instead. Returns where the
+    /// frame went.
+    ///
+    /// ```
+    /// use microcvm_rs::MicroCvm;
+    /// use microcvm_rs::asm::assemble;
+    /// use microcvm_rs::letterbox::Letterbox;
+    /// use microcvm_rs::types::Color;
+    ///
+    /// let mut vm = MicroCvm::builder().resolution(2, 2).build();
+    /// vm.cpu_mut().video_memory.bytes_mut().fill(255);
+    /// let letterbox = Letterbox { color: Color::new(0, 0, 80), border: Some(Color::new(9, 9, 9)) };
+    /// let pixel = |out: &[u8], width: usize, x: usize, y: usize| -> [u8; 4] {
+    ///     out[(y * width + x) * 4..][..4].try_into().unwrap()
+    /// };
+    /// let (white, bar, border) = ([255; 4], [0, 0, 80, 255], [9, 9, 9, 255]);
+    ///
+    /// // 6x6 leaves room for the frame at 2x inside its border, which fills the edge.
+    /// let mut out = vec![0; 6 * 6 * 4];
+    /// let placement = vm.frame_rgba_letterboxed(6, 6, &letterbox, &mut out);
+    /// assert_eq!((placement.scale, placement.x, placement.y), (2, 1, 1));
+    /// for i in 0..6 {
+    ///     for (x, y) in [(i, 0), (i, 5), (0, i), (5, i)] {
+    ///         assert_eq!(pixel(&out, 6, x, y), border);
+    ///     }
+    /// }
+    /// assert_eq!(pixel(&out, 6, 1, 1), white);
+    /// assert_eq!(pixel(&out, 6, 4, 4), white);
+    ///
+    /// // 9x4 is too short for a border at 2x, so the frame is 1x with bars either side.
+    /// let mut out = vec![0; 9 * 4 * 4];
+    /// let placement = vm.frame_rgba_letterboxed(9, 4, &letterbox, &mut out);
+    /// assert_eq!((placement.scale, placement.x, placement.y), (1, 3, 1));
+    /// assert_eq!(pixel(&out, 9, 0, 0), bar);
+    /// assert_eq!(pixel(&out, 9, 8, 3), bar);
+    /// assert_eq!(pixel(&out, 9, 2, 1), border);
+    /// assert_eq!(pixel(&out, 9, 3, 0), border);
+    /// assert_eq!(pixel(&out, 9, 3, 1), white);
+    ///
+    /// // The guest's color replaces the host's once it sets bit 1 of VIDEO_CONTROL.
+    /// let program = assemble("
+    ///         mov r0, 0x40
+    ///         store [0xFF1A], r0
+    ///         mov r0, 0
+    ///         store [0xFF1B], r0
+    ///         store [0xFF1C], r0
+    ///         mov r0, 0x02
+    ///         store [0xFF13], r0
+    ///         hlt
+    /// ").unwrap();
+    /// vm.load_program(&program).unwrap();
+    /// vm.run().unwrap();
+    /// vm.frame_rgba_letterboxed(9, 4, &letterbox, &mut out);
+    /// assert_eq!(pixel(&out, 9, 0, 0), [0x40, 0, 0, 255]);
+    /// assert_eq!(pixel(&out, 9, 2, 1), border);
+    /// ```
+    pub fn frame_rgba_letterboxed(
+        &mut self,
+        window_width: u32,
+        window_height: u32,
+        letterbox: &Letterbox,
+        out: &mut [u8],
+    ) -> Placement {
+        let placement = place(
+            self.width,
+            self.height,
+            window_width,
+            window_height,
+            letterbox.border.is_some(),
+        );
+        let mut scaled = core::mem::take(&mut self.scaled);
+        scaled.resize((placement.width * placement.height * 4) as usize, 0);
+        self.frame_rgba_scaled(placement.scale, self.crt, &mut scaled);
+
+        let mut letterbox = *letterbox;
+        if self.cpu.video_control & VIDEO_CONTROL_LETTERBOX != 0 {
+            let [r, g, b] = self.cpu.letterbox;
+            letterbox.color = Color::new(r, g, b);
+        }
+        compose(&scaled, &placement, window_width, &letterbox, out);
+        self.scaled = scaled;
+        placement
+    }
+
+    /// Tells the guest a frame has been presented: sets the vblank bit of
+    /// [`VIDEO_STATUS`](crate::cpu::VIDEO_STATUS) and raises the vblank interrupt. The window
+    /// and the web frontend call this after every frame they draw, and headless hosts call it
+    /// at whatever rate they present at.
+    ///
+    /// ```
+    /// use microcvm_rs::asm::assemble;
+    /// use microcvm_rs::MicroCvm;
+    ///
+    /// // Makes pixel 0 one step redder on every vblank.
+    /// let program = assemble("
+    ///         mov r0, 0x00
+    ///         store [0xFF90], r0      ; vector table at 0x0200
+    ///         mov r0, 0x02
+    ///         store [0xFF91], r0
+    ///         mov r0, 0
+    ///         ei
+    /// idle:   jmp idle
+    ///
+    /// on_vblank:
+    ///         inc r2
+    ///         video 0x03, r0          ; setpixel 0, 0 to (r2, r3, r4)
+    ///         popf
+    ///         ret
+    ///
+    ///         .org 0x0200
+    ///         .dw on_vblank
+    /// ").unwrap();
+    /// let mut vm = MicroCvm::builder().resolution(16, 16).build();
+    /// vm.load_program(&program).unwrap();
+    /// for _ in 0..10 {
+    ///     vm.run_for(100).unwrap();
+    ///     vm.tick_frame();
+    /// }
+    /// vm.run_for(100).unwrap();
+    /// assert_eq!(vm.framebuffer().pixel(0).r, 10);
+    /// ```
+    pub fn tick_frame(&mut self) {
+        self.cpu.tick_frame();
+    }
+
+    /// Queues a key event for the guest, stamped with the current cycle count so it can tell
+    /// how long a key was held. If the guest has set bit 0 of the keyboard control register,
+    /// this also raises the keyboard interrupt,
+    /// [`VECTOR_KEYBOARD`](crate::keyboard::VECTOR_KEYBOARD). Pending vblank goes first, as
+    /// the lower vector.
+    ///
+    /// ```
+    /// use microcvm_rs::asm::assemble;
+    /// use microcvm_rs::interrupt::VECTOR_VBLANK;
+    /// use microcvm_rs::keyboard::VECTOR_KEYBOARD;
+    /// use microcvm_rs::MicroCvm;
+    ///
+    /// // Keeps the stamps of the last two events: the newest at 0x0300, the one before at
+    /// // 0x0304.
+    /// let program = assemble("
+    ///         mov r0, 0x00
+    ///         store [0xFF90], r0      ; vector table at 0x0200
+    ///         mov r0, 0x02
+    ///         store [0xFF91], r0
+    ///         mov r0, 1
+    ///         store [0xFF32], r0      ; interrupt on key events
+    ///         ei
+    /// idle:   jmp idle
+    ///
+    /// on_key: load r0, [0xFF30]
+    ///         btst r0, 0
+    ///         jrz done
+    ///         load r1, [0x0300]
+    ///         store [0x0304], r1
+    ///         load r1, [0x0301]
+    ///         store [0x0305], r1
+    ///         load r1, [0x0302]
+    ///         store [0x0306], r1
+    ///         load r1, [0x0303]
+    ///         store [0x0307], r1
+    ///         load r1, [0xFF33]
+    ///         store [0x0300], r1
+    ///         load r1, [0xFF34]
+    ///         store [0x0301], r1
+    ///         load r1, [0xFF35]
+    ///         store [0x0302], r1
+    ///         load r1, [0xFF36]
+    ///         store [0x0303], r1
+    ///         store [0xFF30], r0      ; pop the event
+    ///         jr on_key
+    /// done:   iret
+    ///
+    ///         .org 0x0200
+    ///         .dw 0, 0, on_key
+    /// ").unwrap();
+    /// let mut vm = MicroCvm::builder().build();
+    /// vm.load_program(&program).unwrap();
+    /// vm.run_for(100).unwrap();
+    /// let pressed = vm.cpu().cycles;
+    /// vm.push_key(0x1E, true);
+    /// vm.run_for(300).unwrap();
+    /// let released = vm.cpu().cycles;
+    /// vm.push_key(0x1E, false);
+    /// vm.run_for(100).unwrap();
+    ///
+    /// let memory = vm.cpu().memory();
+    /// assert_eq!(memory[0x0300..0x0304], (released as u32).to_le_bytes());
+    /// assert_eq!(memory[0x0304..0x0308], (pressed as u32).to_le_bytes());
+    /// assert!(vm.cpu().keyboard().events.is_empty());
+    ///
+    /// // Masked, the event waits in the queue without an interrupt.
+    /// vm.cpu_mut().keyboard_mut().control = 0;
+    /// vm.push_key(0x1E, true);
+    /// assert_eq!(vm.cpu().interrupts.pending, 0);
+    ///
+    /// // With both pending, vblank is delivered first.
+    /// vm.cpu_mut().keyboard_mut().control = 1;
+    /// vm.push_key(0x1E, false);
+    /// vm.tick_frame();
+    /// assert_eq!(vm.cpu().interrupts.pending, 1 << VECTOR_VBLANK | 1 << VECTOR_KEYBOARD);
+    /// assert_eq!(vm.cpu().interrupts.next(), Some(VECTOR_VBLANK));
+    /// ```
+    pub fn push_key(&mut self, scancode: u8, pressed: bool) {
+        self.cpu.push_key(scancode, pressed);
+    }
+
+    /// Maps `device` at `len` addresses from `start`, where the program's loads and stores
+    /// reach it before RAM or any other register. Ranges that overlap a device already on
+    /// the bus, such as the keyboard at [`KEYBOARD_BASE`](crate::cpu::KEYBOARD_BASE), are
+    /// refused.
+    ///
+    /// ```
+    /// use microcvm_rs::asm::assemble;
+    /// use microcvm_rs::bus::{BusError, Device};
+    /// use microcvm_rs::cpu::KEYBOARD_BASE;
+    /// use microcvm_rs::MicroCvm;
+    ///
+    /// // Reads as 0x10 plus the offset and counts the stores it sees.
+    /// struct Counter {
+    ///     stores: u32,
+    /// }
+    ///
+    /// impl Device for Counter {
+    ///     fn read(&mut self, offset: u16) -> u8 {
+    ///         self.peek(offset)
+    ///     }
+    ///
+    ///     fn write(&mut self, _offset: u16, _value: u8) {
+    ///         self.stores += 1;
+    ///     }
+    ///
+    ///     fn peek(&self, offset: u16) -> u8 {
+    ///         0x10 + offset as u8
+    ///     }
+    /// }
+    ///
+    /// let mut vm = MicroCvm::builder().build();
+    /// vm.map_device(0x3000, 4, Box::new(Counter { stores: 0 })).unwrap();
+    /// vm.load_program(&assemble("
+    ///         mov r0, 0xAA
+    ///         store [0x2FFF], r0
+    ///         store [0x3004], r0
+    ///         store [0x3000], r0
+    ///         store [0x3003], r0
+    ///         load r1, [0x2FFF]
+    ///         load r2, [0x3000]
+    ///         load r3, [0x3003]
+    ///         load r4, [0x3004]
+    ///         hlt
+    /// ").unwrap()).unwrap();
+    /// vm.run().unwrap();
+    /// assert_eq!(vm.cpu().registers[1..5], [0xAA, 0x10, 0x13, 0xAA]);
+    /// assert_eq!(vm.cpu().memory()[0x3000..0x3004], [0; 4]);
+    /// assert_eq!(vm.cpu().device::<Counter>().unwrap().stores, 2);
+    ///
+    /// let counter = || Box::new(Counter { stores: 0 });
+    /// assert_eq!(
+    ///     vm.map_device(0x2FFD, 4, counter()),
+    ///     Err(BusError::Overlap { start: 0x2FFD, existing: 0x3000 }),
+    /// );
+    /// assert!(vm.map_device(KEYBOARD_BASE + 1, 1, counter()).is_err());
+    /// assert!(vm.map_device(0xFFFF, 2, counter()).is_err());
+    /// assert!(vm.map_device(0x3004, 1, counter()).is_ok());
+    /// ```
+    pub fn map_device(
+        &mut self,
+        start: u16,
+        len: u16,
+        device: Box<dyn Device>,
+    ) -> Result<(), BusError> {
+        self.cpu.map_device(start, len, device)
+    }
+
+    /// A machine in the state `dump` was taken in, with the same screen and register width.
+    /// Devices, host calls and the instruction limit start out as the builder's defaults.
+    pub fn from_core_dump(dump: &CoreDump) -> MicroCvm {
+        let mut vm = MicroCvm::builder()
+            .resolution(dump.width, dump.height)
+            .register_width(dump.register_width)
+            .build();
+        vm.restore(&dump.snapshot);
+        vm
+    }
+
+    /// Runs the machine on a thread of its own until the program stops or the returned
+    /// [`VmThread`] is shut down.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use microcvm_rs::asm::assemble;
+    /// use microcvm_rs::error::VmError;
+    /// use microcvm_rs::hcall::HcallContext;
+    /// use microcvm_rs::nvram::{DEFAULT_NVRAM_LEN, Nvram};
+    /// use microcvm_rs::MicroCvm;
+    ///
+    /// fn spawn(source: &str) -> microcvm_rs::vm::VmThread {
+    ///     let sleepy = |_: &mut HcallContext| {
+    ///         std::thread::sleep(Duration::from_millis(200));
+    ///         Ok(())
+    ///     };
+    ///     let mut vm = MicroCvm::builder().hcall(1, Box::new(sleepy)).build();
+    ///     vm.load_program(&assemble(source).unwrap()).unwrap();
+    ///     let thread = vm.spawn();
+    ///     std::thread::sleep(Duration::from_millis(20));
+    ///     thread
+    /// }
+    ///
+    /// // Waiting for a key that never comes, after saving a high score to NVRAM.
+    /// let nvram = std::env::temp_dir().join(format!("microcvm-spawn-{}.nvram", std::process::id()));
+    /// let mut vm = MicroCvm::builder().build();
+    /// vm.cpu_mut().attach_nvram(&nvram).unwrap();
+    /// vm.load_program(&assemble("
+    ///         mov r0, 0x5A
+    ///         store [0x3F00], r0
+    /// wait:   load r0, [0xFF30]
+    ///         btst r0, 0
+    ///         jrz wait
+    ///         hlt
+    /// ").unwrap()).unwrap();
+    /// let mut thread = vm.spawn();
+    /// std::thread::sleep(Duration::from_millis(20));
+    /// thread.shutdown().unwrap();
+    /// thread.shutdown().unwrap();
+    /// let (saved, _) = Nvram::load(&nvram, DEFAULT_NVRAM_LEN as usize).unwrap();
+    /// assert_eq!(saved[0], 0x5A);
+    /// let vm = thread.into_vm().unwrap();
+    /// assert!(vm.instructions() > 0 && !vm.halted() && vm.is_paused());
+    /// std::fs::remove_file(&nvram).unwrap();
+    ///
+    /// // Copying the same pixels over and over with DMA.
+    /// let mut thread = spawn("
+    ///         mov r0, 0x10
+    ///         store [0xFF27], r0      ; 4096 pixels
+    ///         mov r0, 1
+    /// copy:   store [0xFF29], r0
+    ///         jmp copy
+    /// ");
+    /// thread.shutdown().unwrap();
+    /// assert!(!thread.into_vm().unwrap().halted());
+    ///
+    /// // Already halted, or stopped by a fault, which the shutdown returns.
+    /// let mut thread = spawn("hlt");
+    /// thread.shutdown().unwrap();
+    /// assert!(thread.into_vm().unwrap().halted());
+    /// let mut thread = spawn("div r0, 0");
+    /// let error = thread.shutdown().unwrap_err();
+    /// assert!(matches!(error.cause(), VmError::DivisionByZero { .. }));
+    ///
+    /// // Stuck in a host call: the shutdown gives up, and so does dropping the thread.
+    /// let mut thread = spawn("hcall 1\nhlt");
+    /// thread.set_shutdown_timeout(Duration::from_millis(10));
+    /// let error = thread.shutdown().unwrap_err();
+    /// assert!(matches!(&error, VmError::Io(e) if e.kind() == std::io::ErrorKind::TimedOut));
+    /// thread.shutdown().unwrap();
+    /// assert!(thread.into_vm().is_none());
+    /// let mut thread = spawn("hcall 1\nhlt");
+    /// thread.set_shutdown_timeout(Duration::from_millis(10));
+    /// drop(thread);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn spawn(self) -> VmThread {
+        VmThread::spawn(self)
+    }
+
+    /// Pauses the machine and writes out what it holds for the host: the NVRAM region goes
+    /// to its file and the guest's open files are closed. Shutting down again does nothing.
+    #[cfg(feature = "std")]
+    pub fn shutdown(&mut self) -> Result<(), VmError> {
+        if self.shut_down {
+            return Ok(());
+        }
+        self.shut_down = true;
+        self.pause();
+        self.cpu.files.detach();
+        Ok(self.cpu.commit_nvram()?)
+    }
+
+    /// Gives up the facade for the CPU behind it.
+    pub fn into_cpu(self) -> MicroCVMCpu {
+        self.cpu
+    }
+
+    /// The machine as it is now, with `report` saying what stopped it.
+    pub fn core_dump(&self, report: &FaultReport) -> CoreDump {
+        CoreDump {
+            pc: report.pc,
+            register_width: self.cpu.register_width,
+            width: self.width,
+            height: self.height,
+            report: report.to_string(),
+            snapshot: self.snapshot(),
+        }
+    }
+
+    /// ```
+    /// use microcvm_rs::MicroCvm;
+    ///
+    /// // inc r0; hlt
+    /// let mut vm = MicroCvm::builder().build();
+    /// vm.load_program(&[0x07, 0x00, 0xFF]).unwrap();
+    /// let before = vm.snapshot();
+    /// vm.run().unwrap();
+    /// vm.restore(&before);
+    /// assert_eq!(vm.cpu().registers[0], 0);
+    /// assert_eq!(vm.cpu().pc, 0);
+    /// ```
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            registers: self.cpu.registers,
+            sp: self.cpu.sp,
+            pc: self.cpu.pc,
+            flags: self.cpu.flags,
+            bank: self.cpu.bank,
+            halted: self.cpu.halted,
+            cycles: self.cpu.cycles,
+            instructions: self.instructions,
+            call_depth: self.cpu.call_depth,
+            memory: self.cpu.memory().to_vec(),
+            video_memory: self.cpu.video_memory.clone(),
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.cpu.registers = snapshot.registers;
+        self.cpu.sp = snapshot.sp;
+        self.cpu.pc = snapshot.pc;
+        self.cpu.flags = snapshot.flags;
+        self.cpu.bank = snapshot.bank;
+        self.cpu.halted = snapshot.halted;
+        self.cpu.cycles = snapshot.cycles;
+        self.instructions = snapshot.instructions;
+        self.cpu.call_depth = snapshot.call_depth;
+        self.cpu.replace_memory(&snapshot.memory);
+        self.cpu.video_memory.clone_from(&snapshot.video_memory);
+        self.cpu.mark_screen_dirty();
+        self.cpu.flush_decode_cache();
+    }
+
+    pub fn cpu(&self) -> &MicroCVMCpu {
+        &self.cpu
+    }
+
+    /// Flushes the decode cache first, since whatever the caller writes to `memory`
+    /// directly could be code the CPU has already decoded.
+    pub fn cpu_mut(&mut self) -> &mut MicroCVMCpu {
+        self.cpu.flush_decode_cache();
+        &mut self.cpu
+    }
+}
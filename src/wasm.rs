@@ -0,0 +1,57 @@
+use wasm_bindgen::prelude::*;
+
+use crate::vm::MicroCvm;
+
+#[wasm_bindgen]
+pub struct WasmVm {
+    vm: MicroCvm,
+}
+
+#[wasm_bindgen]
+impl WasmVm {
+    #[wasm_bindgen(constructor)]
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            vm: MicroCvm::builder().resolution(width, height).build(),
+        }
+    }
+
+    // Accepts either an image with an MCVM header or raw bytes loaded at address 0.
+    pub fn load_program(&mut self, bytes: &[u8]) -> Result<(), JsError> {
+        self.vm
+            .load_program(bytes)
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    // Runs until the program vsyncs, at least `max_cycles` cycles have elapsed or it
+    // halts. Returns false once the program has halted.
+    pub fn run_frame(&mut self, max_cycles: u32) -> Result<bool, JsError> {
+        self.vm
+            .run_frame(max_cycles as u64)
+            .map_err(|e| JsError::new(&e.to_string()))?;
+        Ok(!self.vm.halted())
+    }
+
+    // Call after drawing each frame, so the guest sees vblank.
+    pub fn tick_frame(&mut self) {
+        self.vm.tick_frame();
+    }
+
+    pub fn framebuffer_rgba(&mut self) -> Vec<u8> {
+        let mut rgba = vec![0; self.vm.framebuffer().len() * 4];
+        self.vm.frame_rgba(&mut rgba);
+        rgba
+    }
+
+    pub fn inject_key(&mut self, scancode: u8, pressed: bool) {
+        self.vm.push_key(scancode, pressed);
+    }
+
+    pub fn width(&self) -> u32 {
+        self.vm.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.vm.height()
+    }
+}
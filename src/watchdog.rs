@@ -0,0 +1,139 @@
+// A countdown the guest has to restart by writing WDT_FEED before it runs out. It counts
+// the CPU's cycles rather than time, so a program that stops feeding it expires at the same
+// cycle on every run. Multi-byte fields are little-endian.
+pub const WDT_CONTROL: u8 = 0x00; // bit 0: armed, bit 1: expired since the guest last armed it
+pub const WDT_FEED: u8 = 0x01; // any write restarts the countdown while armed
+pub const WDT_TIMEOUT: u8 = 0x02; // 4 bytes: cycles from a feed to expiry
+pub const WDT_REMAINING: u8 = 0x06; // 4 bytes: cycles left, 0 while disarmed (read only)
+pub const WDT_EXPIRED_PC: u8 = 0x0A; // 2 bytes: the pc it last expired at (read only)
+pub const WDT_REGISTER_COUNT: u8 = 12;
+
+pub const WDT_ARMED: u8 = 0x01;
+pub const WDT_EXPIRED: u8 = 0x02;
+
+// The interrupt vector whose handler WatchdogAction::Interrupt enters.
+pub const VECTOR_WATCHDOG: u8 = 3;
+
+pub const DEFAULT_WATCHDOG_TIMEOUT: u32 = 1_000_000;
+
+// What happens when the countdown runs out.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum WatchdogAction {
+    // Stops with VmError::WatchdogExpired, whatever the fault policy says, since no
+    // instruction faulted.
+    #[default]
+    Fault,
+    // Enters the guest's handler at VECTOR_WATCHDOG whatever the I flag says, returning to
+    // the instruction it would have run. Stops like Fault if the table entry is 0 or
+    // entering it faults.
+    Interrupt,
+}
+
+// How the host sets the watchdog up before the program runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchdogConfig {
+    pub timeout: u32,
+    // Counting down from cycle 0, rather than waiting for the guest to arm it.
+    pub armed: bool,
+    pub action: WatchdogAction,
+}
+
+impl WatchdogConfig {
+    // Armed from the start, faulting `timeout` cycles after the last feed.
+    pub fn new(timeout: u32) -> Self {
+        Self {
+            timeout,
+            armed: true,
+            action: WatchdogAction::Fault,
+        }
+    }
+}
+
+// Every machine has one, disarmed until the guest arms it.
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_WATCHDOG_TIMEOUT,
+            armed: false,
+            action: WatchdogAction::Fault,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Watchdog {
+    pub timeout: u32,
+    pub action: WatchdogAction,
+    // The cycle count it expires at, u64::MAX while disarmed so checking costs a compare.
+    pub deadline: u64,
+    pub expired: bool,
+    pub expired_pc: u16,
+}
+
+impl Watchdog {
+    pub fn new(config: WatchdogConfig) -> Self {
+        let mut watchdog = Self {
+            timeout: config.timeout,
+            action: config.action,
+            deadline: u64::MAX,
+            expired: false,
+            expired_pc: 0,
+        };
+        if config.armed {
+            watchdog.arm(0);
+        }
+        watchdog
+    }
+
+    pub fn armed(&self) -> bool {
+        self.deadline != u64::MAX
+    }
+
+    pub fn arm(&mut self, cycles: u64) {
+        self.deadline = cycles.saturating_add(self.timeout as u64).min(u64::MAX - 1);
+        self.expired = false;
+    }
+
+    // Disarms it, so it fires once until the guest arms it again.
+    pub fn expire(&mut self, pc: u16) {
+        self.deadline = u64::MAX;
+        self.expired = true;
+        self.expired_pc = pc;
+    }
+
+    pub fn read(&self, offset: u8, cycles: u64) -> u8 {
+        let remaining = if self.armed() {
+            self.deadline.saturating_sub(cycles).min(u32::MAX as u64) as u32
+        } else {
+            0
+        };
+        match offset {
+            WDT_CONTROL => (self.armed() as u8 * WDT_ARMED) | (self.expired as u8 * WDT_EXPIRED),
+            WDT_TIMEOUT..WDT_REMAINING => (self.timeout >> ((offset - WDT_TIMEOUT) * 8)) as u8,
+            WDT_REMAINING..WDT_EXPIRED_PC => (remaining >> ((offset - WDT_REMAINING) * 8)) as u8,
+            WDT_EXPIRED_PC => self.expired_pc as u8,
+            0x0B => (self.expired_pc >> 8) as u8,
+            _ => 0,
+        }
+    }
+
+    // A new timeout takes effect at the next feed.
+    pub fn write(&mut self, offset: u8, value: u8, cycles: u64) {
+        match offset {
+            WDT_CONTROL if value & WDT_ARMED == 0 => self.deadline = u64::MAX,
+            WDT_CONTROL if !self.armed() => self.arm(cycles),
+            WDT_FEED if self.armed() => self.arm(cycles),
+            WDT_TIMEOUT..WDT_REMAINING => {
+                let shift = (offset - WDT_TIMEOUT) * 8;
+                self.timeout = (self.timeout & !(0xFF << shift)) | (value as u32) << shift;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Default for Watchdog {
+    fn default() -> Self {
+        Self::new(WatchdogConfig::default())
+    }
+}
@@ -0,0 +1,81 @@
+; std/print.asm: sending text and numbers over serial.
+;
+; Every byte goes out with `hcall STD_SERIAL`, 1, with the byte in r0. The embedder has to
+; register a handler for it that sends the byte on, as the runner for tests/programs does.
+;
+;   std_print_char      r0
+;   std_print_newline   a 10
+;   std_print_str       the 0-terminated string at r2:r3, leaving r2:r3 past its 0
+;   std_print_dec8      r0 in decimal, without leading zeros
+;   std_print_hex8      r0 as two lowercase hex digits
+;
+; They change r0 to r3 and the Z and C flags, and nothing else.
+
+        .include "std/string.asm"
+
+        .equ STD_SERIAL, 1
+
+std_print_char:
+        hcall STD_SERIAL
+        ret
+
+std_print_newline:
+        mov r0, 10
+        hcall STD_SERIAL
+        ret
+
+std_print_str:
+        load r0, [r2+]
+        test r0, r0
+        jrz std_print_str_done
+        hcall STD_SERIAL
+        jr std_print_str
+std_print_str_done:
+        ret
+
+; r2 keeps what's left to print, r1 the digit.
+std_print_dec8:
+        mov r2, r0
+        mov r1, r0
+        div r1, 100
+        jrz std_print_dec8_tens
+        mov r0, r1
+        add r0, '0'
+        hcall STD_SERIAL
+        mul r1, 100
+        sub r2, r1
+        mov r1, r2
+        div r1, 10
+        jr std_print_dec8_tens_digit    ; a 0 in the middle still counts
+std_print_dec8_tens:
+        mov r1, r2
+        div r1, 10
+        jrz std_print_dec8_units
+std_print_dec8_tens_digit:
+        mov r0, r1
+        add r0, '0'
+        hcall STD_SERIAL
+        mul r1, 10
+        sub r2, r1
+std_print_dec8_units:
+        mov r0, r2
+        add r0, '0'
+        hcall STD_SERIAL
+        ret
+
+std_print_hex8:
+        mov r2, r0
+        mov r1, r0
+        div r1, 16
+        call std_hex_digit
+        mov r0, r1
+        hcall STD_SERIAL
+        mov r1, r2
+        div r1, 16
+        mul r1, 16
+        sub r2, r1                      ; the low nibble
+        mov r1, r2
+        call std_hex_digit
+        mov r0, r1
+        hcall STD_SERIAL
+        ret
@@ -0,0 +1,26 @@
+; std/random.asm: pseudo-random bytes.
+;
+; A linear congruential generator, state * 141 + 3 mod 256, which visits every byte once
+; every 256 calls in the same order from the same seed. Its low bits repeat far sooner, bit 0
+; just alternates, so narrow a result down with `div` rather than by masking it.
+;
+;   std_srand   starts the sequence again from the seed in r0
+;   std_rand    r0 = the next byte
+;
+; The state lives in a data section beside the code. They change r0 and the Z and C flags,
+; and nothing else.
+
+std_srand:
+        store [std_rand_state], r0
+        ret
+
+std_rand:
+        load r0, [std_rand_state]
+        mul r0, 141
+        add r0, 3
+        store [std_rand_state], r0
+        ret
+
+        .section data
+std_rand_state:
+        .db 1
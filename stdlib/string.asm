@@ -0,0 +1,77 @@
+; std/string.asm: formatting numbers into memory.
+;
+; The formatting routines write text through the pointer in r2:r3, high byte first as for
+; `[r2+]`, and leave it just past what they wrote, so calls in a row build up a line. None of
+; them writes a terminating 0.
+;
+;   std_fmt_dec8    r0 in decimal, without leading zeros
+;   std_fmt_hex8    r0 as two lowercase hex digits
+;   std_hex_digit   r1, 0 to 15, turned into its hex digit in place
+;   std_strlen      r0 = the length of the 0-terminated string at r2:r3, up to 255
+;
+; They change r0 to r3 and the Z and C flags, and nothing else.
+
+std_fmt_dec8:
+        mov r1, r0
+        div r1, 100
+        jrz std_fmt_dec8_tens
+        add r1, '0'
+        store [r2+], r1
+        sub r1, '0'
+        mul r1, 100
+        sub r0, r1                      ; what's left under 100
+        mov r1, r0
+        div r1, 10
+        jr std_fmt_dec8_tens_digit      ; a 0 in the middle still counts
+std_fmt_dec8_tens:
+        mov r1, r0
+        div r1, 10
+        jrz std_fmt_dec8_units
+std_fmt_dec8_tens_digit:
+        add r1, '0'
+        store [r2+], r1
+        sub r1, '0'
+        mul r1, 10
+        sub r0, r1
+std_fmt_dec8_units:
+        add r0, '0'
+        store [r2+], r0
+        ret
+
+std_fmt_hex8:
+        mov r1, r0
+        div r1, 16
+        call std_hex_digit
+        store [r2+], r1
+        mov r1, r0
+        div r1, 16
+        mul r1, 16
+        sub r0, r1                      ; the low nibble
+        mov r1, r0
+        call std_hex_digit
+        store [r2+], r1
+        ret
+
+; Only changes r1, so the other routines can keep their own values in r0 meanwhile. Adding 6
+; carries into bit 4 from 10 up, which tells the letters from the digits.
+std_hex_digit:
+        add r1, 6
+        btst r1, 4
+        jrz std_hex_digit_decimal
+        add r1, 'a' - 16
+        ret
+std_hex_digit_decimal:
+        add r1, '0' - 6
+        ret
+
+; Leaves r2:r3 just past the terminating 0.
+std_strlen:
+        clr r0
+std_strlen_next:
+        load r1, [r2+]
+        test r1, r1
+        jrz std_strlen_done
+        inc r0
+        jr std_strlen_next
+std_strlen_done:
+        ret
@@ -0,0 +1,63 @@
+; std/video.asm: pixels and lines over the video operations.
+;
+; Coordinates and sizes are in r0 to r3 like the operations' own parameters, and the color is
+; in r4, r5 and r6 for every routine, red, green and blue. Below 24-bit color only r4 counts,
+; holding the pixel value. Anything off the screen is clipped, as it is for `video`.
+;
+;   std_pixel   the pixel at r0, r1
+;   std_hline   r2 pixels from r0, r1 rightwards
+;   std_vline   r2 pixels from r0, r1 downwards
+;   std_box     the outline of the r2 by r3 rectangle at r0, r1, both sizes at least 1
+;
+; They change r0 to r3 and the Z and C flags, and nothing else.
+
+std_pixel:
+        mov r2, r0
+        mov r3, r1
+        video setpixel, r2
+        ret
+
+std_hline:
+        mov r3, 1
+        video fillrect, r0
+        ret
+
+std_vline:
+        mov r3, r2
+        mov r2, 1
+        video fillrect, r0
+        ret
+
+; The width and height wait in two stack slots while their registers draw each side.
+std_box:
+        pushf
+        pushf
+        store [sp], r2
+        store [sp + 1], r3
+        mov r3, 1
+        video fillrect, r0              ; top
+        mov r2, 1
+        load r3, [sp + 1]
+        video fillrect, r0              ; left
+        load r2, [sp]
+        add r0, r2
+        dec r0
+        mov r2, 1
+        video fillrect, r0              ; right
+        load r2, [sp]
+        sub r0, r2
+        inc r0
+        add r1, r3
+        dec r1
+        mov r3, 1
+        video fillrect, r0              ; bottom
+        ; The slots go back to being flags before they're popped, so the I flag comes back
+        ; as it was rather than as a size.
+        pushf
+        load r2, [sp]
+        popf
+        store [sp], r2
+        store [sp + 1], r2
+        popf
+        popf
+        ret
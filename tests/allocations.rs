@@ -0,0 +1,98 @@
+// Once a machine is built, drawing a frame must not allocate: not converting it, not scaling
+// it with the CRT effect on, not compositing sprites or tiles, and not ticking the frame over.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::cpu::VIDEO_CONTROL_TILES;
+use microcvm_rs::crt::CrtEffect;
+
+thread_local! {
+    // Per thread, so tests running alongside don't count against each other.
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+struct Counting;
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+fn allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+fn machine() -> MicroCvm {
+    let mut vm = MicroCvm::builder().resolution(64, 48).build();
+    for (index, sprite) in vm.cpu_mut().sprites_mut().sprites[..4]
+        .iter_mut()
+        .enumerate()
+    {
+        (sprite.enabled, sprite.width, sprite.height) = (true, 8, 8);
+        sprite.x = index as i16 * 20 - 4;
+    }
+    vm.cpu_mut().brightness = 128;
+    vm
+}
+
+#[test]
+fn frames_do_not_allocate() {
+    let mut vm = machine();
+    let crt = Some(CrtEffect {
+        scanline_dim: 30,
+        smear: true,
+    });
+    let mut frame = vec![0; 64 * 48 * 4];
+    let mut scaled = vec![0; 64 * 48 * 4 * 9];
+    for tiled in [false, true] {
+        if tiled {
+            // The tile layer's cache is made on the first tiled frame.
+            vm.cpu_mut().video_control |= VIDEO_CONTROL_TILES;
+            vm.frame_rgba(&mut frame);
+        }
+        let count = allocations(|| {
+            for _ in 0..10 {
+                vm.frame_rgba(&mut frame);
+                vm.frame_rgba_scaled(3, crt, &mut scaled);
+                vm.frame_rgba_scaled(1, None, &mut frame);
+                vm.tick_frame();
+            }
+        });
+        assert_eq!(count, 0, "tiled: {}", tiled);
+    }
+}
+
+#[test]
+fn the_framebuffer_is_there_without_copying() {
+    let mut vm = machine();
+    vm.cpu_mut().video_memory.bytes_mut()[..3].copy_from_slice(&[1, 2, 3]);
+    let count = allocations(|| {
+        assert_eq!(vm.framebuffer().bytes()[..3], [1, 2, 3]);
+        vm.cpu_mut().video_memory.fill(0, 64, 0x09_09_09);
+    });
+    assert_eq!(count, 0);
+    assert_eq!(vm.framebuffer().bytes()[..3], [9, 9, 9]);
+}
+
+#[test]
+fn the_counter_sees_allocations() {
+    assert_eq!(allocations(|| drop(vec![0u8; 16])), 1);
+}
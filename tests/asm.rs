@@ -0,0 +1,267 @@
+// Runs `microcvm asm` on the sources in tests/asm and compares every file it writes, the
+// program and its symbols and listing, byte for byte with the file of the same name beside
+// the source. With UPDATE_GOLDEN=1 those files are written from what it produces instead.
+// `microcvm disasm` has to turn those programs back into source that assembles to them, and
+// relative jumps have to encode their offsets, or fail to assemble when they can't reach.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::cpu::{OpcodeType, Register};
+
+const UPDATE_VAR: &str = "UPDATE_GOLDEN";
+
+// The source, the format and the extension its expected output has.
+const CASES: [(&str, &str, &str); 4] = [
+    ("program", "raw", "bin"),
+    ("program", "header", "mcvm"),
+    ("program", "ihex", "hex"),
+    ("segments", "header", "mcvm"),
+];
+
+fn fixtures() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/asm")
+}
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("microcvm-asm-test-{}", name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn asm(source: &Path, args: &[&str]) -> Output {
+    microcvm("asm", source, args)
+}
+
+fn microcvm(command: &str, file: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_microcvm-rs"))
+        .arg(command)
+        .arg(file)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn outputs_match() {
+    let update = std::env::var_os(UPDATE_VAR).is_some();
+    let mut failures = Vec::new();
+    for (name, format, extension) in CASES {
+        let dir = scratch(&format!("{}-{}", name, format));
+        let outputs = [
+            format!("{}.{}", name, extension),
+            format!("{}.sym", name),
+            format!("{}.lst", name),
+        ];
+        let path = |file: &str| dir.join(file).to_str().unwrap().to_owned();
+        let output = asm(
+            &fixtures().join(format!("{}.asm", name)),
+            &[
+                "--format",
+                format,
+                "-o",
+                &path(&outputs[0]),
+                "--symbols",
+                &path(&outputs[1]),
+                "--listing",
+                &path(&outputs[2]),
+            ],
+        );
+        assert!(
+            output.status.success(),
+            "{} as {}: {}",
+            name,
+            format,
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        for file in &outputs {
+            let written = std::fs::read(dir.join(file)).unwrap();
+            let expected = fixtures().join(file);
+            if update {
+                std::fs::write(&expected, &written).unwrap();
+            } else if std::fs::read(&expected).ok().as_ref() != Some(&written) {
+                failures.push(format!("{} from --format {}", file, format));
+            }
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "output differs from tests/asm: {}\nset {}=1 to accept what it writes now",
+        failures.join(", "),
+        UPDATE_VAR
+    );
+}
+
+#[test]
+fn errors_point_at_the_line() {
+    let dir = scratch("errors");
+    let output_file = dir.join("error.bin");
+    let output = asm(
+        &fixtures().join("error.asm"),
+        &["-o", output_file.to_str().unwrap()],
+    );
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("error.asm:3: undefined symbol `nowhere`"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("    3 |         jmp nowhere"), "{}", stderr);
+    assert!(!output_file.exists());
+}
+
+#[test]
+fn flat_formats_refuse_video_sections() {
+    let dir = scratch("flat");
+    for format in ["raw", "ihex"] {
+        let output_file = dir.join(format);
+        let output = asm(
+            &fixtures().join("segments.asm"),
+            &["--format", format, "-o", output_file.to_str().unwrap()],
+        );
+        assert!(!output.status.success(), "--format {} succeeded", format);
+        assert!(!output_file.exists());
+    }
+}
+
+#[test]
+fn disassembly_round_trips() {
+    // The program, the format it is in and the register width it was assembled for.
+    let programs = [
+        ("program.bin", "raw", "8"),
+        ("program.mcvm", "header", "8"),
+        ("segments.mcvm", "header", "16"),
+    ];
+    let dir = scratch("disasm");
+    for (file, format, width) in programs {
+        let program = fixtures().join(file);
+        let symbols = program.with_extension("sym");
+        let symbols = symbols.to_str().unwrap();
+        // Without symbols the jump targets get made-up labels, with them their names.
+        for args in [
+            vec![],
+            vec!["--bytes"],
+            vec!["--symbols", symbols],
+            vec!["--symbols", symbols, "--bytes"],
+        ] {
+            let mut args = args;
+            args.extend(["--register-width", width]);
+            let output = microcvm("disasm", &program, &args);
+            assert!(
+                output.status.success(),
+                "disasm {} {:?}: {}",
+                file,
+                args,
+                String::from_utf8_lossy(&output.stderr)
+            );
+            let source = dir.join("disassembly.asm");
+            std::fs::write(&source, &output.stdout).unwrap();
+
+            let reassembled = dir.join("reassembled");
+            let output = asm(
+                &source,
+                &["--format", format, "-o", reassembled.to_str().unwrap()],
+            );
+            let text = String::from_utf8_lossy(&output.stderr);
+            assert!(output.status.success(), "{} {:?}: {}", file, args, text);
+            assert!(
+                std::fs::read(&reassembled).unwrap() == std::fs::read(&program).unwrap(),
+                "{} disassembled with {:?} assembles to something else",
+                file,
+                args
+            );
+        }
+    }
+}
+
+#[test]
+fn disassembly_synthesizes_labels() {
+    let output = microcvm(
+        "disasm",
+        &fixtures().join("program.bin"),
+        &["--range", "0:0x1d"],
+    );
+    let text = String::from_utf8_lossy(&output.stdout);
+    for line in ["L_0006:", "djnz r1, L_0006", "call L_0014", "jrz L_001c"] {
+        assert!(text.contains(line), "no `{}` in\n{}", line, text);
+    }
+    // Addresses outside the range stay numbers.
+    assert!(text.contains("store [0x0040], r0"), "{}", text);
+}
+
+// The offset is counted from the instruction after the jump.
+#[test]
+fn relative_jumps_reach_backwards_and_forwards() {
+    let program = assemble(
+        "
+start:  nop
+        jr start
+        jrz end
+        jrnz start
+end:    hlt
+",
+    )
+    .unwrap();
+    let (jr, jrz, jrnz) = (
+        OpcodeType::Jr as u8,
+        OpcodeType::Jrz as u8,
+        OpcodeType::Jrnz as u8,
+    );
+    assert_eq!(
+        program[1..7],
+        [jr, (-3i8) as u8, jrz, 2, jrnz, (-7i8) as u8]
+    );
+
+    // The furthest either way.
+    let program = assemble(".org 0x0200\njr 0x0182\njr 0x0283\nhlt").unwrap();
+    assert_eq!(program[0x200..0x204], [jr, 0x80, jr, 0x7F]);
+
+    // And a loop that counts down with one, and skips ahead with another.
+    let mut vm = MicroCvm::builder().build();
+    vm.load_program(
+        &assemble(
+            "
+        mov r0, 5
+        clr r1
+again:  inc r1
+        dec r0
+        jrnz again
+        jr done
+        mov r1, 0xFF
+done:   hlt
+",
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.cpu()[Register::R1], 5);
+}
+
+#[test]
+fn relative_jumps_out_of_range_fail() {
+    for (source, distance) in [
+        (".org 0x0200\njr 0x0181", -129),
+        (".org 0x0200\njr 0x0282", 128),
+        ("nop\nnop\njrz far\n.org 0x0100\nfar: hlt", 252),
+    ] {
+        let error = assemble(source).unwrap_err();
+        assert_eq!(
+            error.line,
+            source.lines().position(|line| line.contains("jr")).unwrap() + 1
+        );
+        assert!(
+            error.message.contains(&format!(
+                "is {} bytes away, out of range for a relative jump",
+                distance
+            )),
+            "{}",
+            error
+        );
+    }
+}
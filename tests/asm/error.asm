@@ -0,0 +1,4 @@
+; The assembler points at the line that is wrong.
+        mov r0, 1
+        jmp nowhere
+        hlt
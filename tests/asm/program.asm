@@ -0,0 +1,44 @@
+; A flat program touching most of the assembler: constants, labels, relative jumps, a gap
+; left by .org, and data.
+        .equ count, 5
+        .equ screen, 0x0100
+
+start:  mov r0, 0
+        mov r1, count
+loop:   add r0, r1
+        djnz r1, loop
+        store [total], r0
+        call show
+        hlt
+
+show:   load r2, [message]
+        jrz done
+        inc r2
+done:   ret
+
+        .org 0x40
+total:  .byte 0
+message:
+        .byte "sum of 1 to 5", 0
+table:  .word screen, screen + 2, 0xBEEF
+scratch:
+        .space 6
+        .byte 0xFF
+
+; 8.8 fixed point on register pairs.
+scale:  fxmuli r2, #fx(-1.5) + fx(0.25)
+        fxdiv r2, r4
+
+; Memory to memory, no register in between.
+copy:   movm [scratch], [total]
+
+; Walking a pointer in r4:r5.
+walk:   load r2, [r4+]
+        store [r4+], r2
+
+; A local two bytes up the stack.
+frame:  load r1, [sp + 2]
+        store [sp + count], r1
+
+; Dispatch through the words at `table`.
+        jtab r1, table
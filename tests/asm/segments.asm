@@ -0,0 +1,19 @@
+; Code, data, bss and video sections, starting at an entry point past a data table.
+        .section data
+        .org 0x0200
+colors: .byte 0x10, 0x20, 0x30
+
+        .section code
+        .org 0x0100
+        .width 16
+boot:   mov r0, 0x1234
+        mov r1, colors
+        hlt
+
+        .section bss
+buffer: .space 64
+
+        .section video
+sprite: .byte 0xFF, 0x81, 0x81, 0xFF
+
+        .entry boot
@@ -0,0 +1,208 @@
+// The square voice has to swing between +volume and -volume once every `divider` ticks of the
+// audio clock, whatever the output rate. PCM samples have to be held for as long as their rate
+// says, added on top of whatever is already playing, picked up from the start of the ring after
+// its end, and stop with an underrun when the guest falls behind.
+
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+
+use microcvm_rs::audio::{
+    AUDIO_CLOCK_HZ, AUDIO_DIVIDER_HI, AUDIO_DIVIDER_LO, AUDIO_GATE, AUDIO_VOLUME, AudioRegisters,
+    PCM_CONTROL, PCM_PLAY, PCM_RATE, PCM_SIZE, PCM_STATUS, PCM_UNDERRUN, PCM_WRITE, PcmMix,
+    PcmRegisters, PcmVoice, SquareVoice, mix_pcm,
+};
+
+fn voice(divider: u16, volume: u8, sample_rate: u32) -> SquareVoice {
+    gated(divider, volume, 1, sample_rate)
+}
+
+fn gated(divider: u16, volume: u8, gate: u8, sample_rate: u32) -> SquareVoice {
+    let registers = Arc::new(AudioRegisters::default());
+    registers.write(AUDIO_DIVIDER_LO, divider as u8);
+    registers.write(AUDIO_DIVIDER_HI, (divider >> 8) as u8);
+    registers.write(AUDIO_VOLUME, volume);
+    registers.write(AUDIO_GATE, gate);
+    SquareVoice::new(registers, sample_rate)
+}
+
+// Where the wave goes from low to high.
+fn rising_edges(samples: &[f32]) -> Vec<usize> {
+    (1..samples.len())
+        .filter(|&i| samples[i - 1] < 0.0 && samples[i] > 0.0)
+        .collect()
+}
+
+#[test]
+fn the_period_follows_the_divider() {
+    // 1 kHz at 50 kHz is 50 samples a period, half of them high.
+    let mut samples = vec![0.0; 500];
+    voice(1000, 255, 50_000).render_audio(&mut samples);
+    for (period, chunk) in samples.chunks(50).enumerate() {
+        assert!(chunk[..25].iter().all(|&s| s == 1.0), "period {}", period);
+        assert!(chunk[25..].iter().all(|&s| s == -1.0), "period {}", period);
+    }
+
+    // Periods that aren't a whole number of samples still average out to the frequency.
+    for divider in [3000, 1234, 77] {
+        let rate = 48_000;
+        let mut samples = vec![0.0; rate as usize];
+        voice(divider, 128, rate).render_audio(&mut samples);
+        let edges = rising_edges(&samples);
+        let (first, last) = (edges[0], edges[edges.len() - 1]);
+        let measured = (edges.len() - 1) as f64 * rate as f64 / (last - first) as f64;
+        let expected = AUDIO_CLOCK_HZ as f64 / divider as f64;
+        assert!(
+            (measured - expected).abs() < expected * 0.001,
+            "divider {}: {} Hz for {} Hz",
+            divider,
+            measured,
+            expected
+        );
+        assert!(samples.iter().all(|&s| s.abs() == 128.0 / 255.0));
+    }
+}
+
+#[test]
+fn the_phase_carries_across_buffers() {
+    let mut whole = vec![0.0; 300];
+    voice(700, 200, 44_100).render_audio(&mut whole);
+    let mut split = voice(700, 200, 44_100);
+    let mut parts = vec![0.0; 300];
+    for chunk in parts.chunks_mut(37) {
+        split.render_audio(chunk);
+    }
+    assert_eq!(parts, whole);
+}
+
+#[test]
+fn silent_without_gate_divider_or_volume() {
+    for (divider, volume, gate) in [(1000, 255, 0), (0, 255, 1), (1000, 0, 1)] {
+        let mut voice = gated(divider, volume, gate, 48_000);
+        let mut samples = vec![1.0; 64];
+        voice.render_audio(&mut samples);
+        assert!(
+            samples.iter().all(|&s| s == 0.0),
+            "{} {} {}",
+            divider,
+            volume,
+            gate
+        );
+    }
+}
+
+#[test]
+fn pcm_samples_are_held_for_their_rate() {
+    // At the output rate each sample is one output sample, 0x80 being silence.
+    let mut out = [0.0; 4];
+    let mix = mix_pcm(&[0x80, 0xC0, 0x40, 0x00], 8000, 8000, 0, &mut out);
+    assert_eq!(out, [0.0, 0.5, -0.5, -1.0]);
+    let expected = PcmMix {
+        consumed: 4,
+        phase: 0,
+        underrun: false,
+    };
+    assert_eq!(mix, expected);
+
+    // At a third of it each is held for three, and a call that stops partway through a
+    // sample carries the phase over to the next one.
+    let samples = [0xC0, 0x40];
+    let mut out = [0.0; 4];
+    let first = mix_pcm(&samples, 1000, 3000, 0, &mut out);
+    assert_eq!(out, [0.5, 0.5, 0.5, -0.5]);
+    assert_eq!((first.consumed, first.phase), (1, 1000));
+    let mut out = [0.0; 2];
+    let second = mix_pcm(
+        &samples[first.consumed..],
+        1000,
+        3000,
+        first.phase,
+        &mut out,
+    );
+    assert_eq!(out, [-0.5, -0.5]);
+    assert_eq!(
+        (second.consumed, second.phase, second.underrun),
+        (1, 0, false)
+    );
+
+    // No rate on either side plays nothing and uses nothing up.
+    let mut out = [0.25; 2];
+    assert_eq!(mix_pcm(&[0xFF], 0, 8000, 5, &mut out).consumed, 0);
+    assert_eq!(out, [0.25; 2]);
+}
+
+#[test]
+fn running_out_of_samples_is_an_underrun() {
+    let mut out = [0.25; 5];
+    let mix = mix_pcm(&[0xC0, 0xC0], 8000, 8000, 0, &mut out);
+    let expected = PcmMix {
+        consumed: 2,
+        phase: 0,
+        underrun: true,
+    };
+    assert_eq!(mix, expected);
+    // What was already there stays, with the samples added to the start.
+    assert_eq!(out, [0.75, 0.75, 0.25, 0.25, 0.25]);
+
+    // The voice flags it to the guest.
+    let registers = Arc::new(PcmRegisters::new());
+    registers.write(PCM_SIZE, 16);
+    registers.write(PCM_RATE, 0x40);
+    registers.write(PCM_RATE + 1, 0x1F);
+    registers.write(PCM_CONTROL, PCM_PLAY);
+    registers.write(PCM_WRITE, 2);
+    registers.write(PCM_WRITE + 1, 0);
+    registers.commit(&[0x90; 16]);
+    let mut voice = PcmVoice::new(registers.clone(), 8000);
+    voice.render_audio(&mut [0.0; 8]);
+    assert_eq!(registers.read(PCM_STATUS), PCM_UNDERRUN);
+    assert_eq!(registers.remaining(), 0);
+}
+
+#[test]
+fn the_ring_wraps_around() {
+    // An 8 sample ring at the start of memory, played at the output rate.
+    let registers = Arc::new(PcmRegisters::new());
+    registers.write(PCM_SIZE, 8);
+    registers.write(PCM_RATE, 0x40);
+    registers.write(PCM_RATE + 1, 0x1F);
+    registers.write(PCM_CONTROL, PCM_PLAY);
+    let mut voice = PcmVoice::new(registers.clone(), 8000);
+    let hand_over = |memory: &[u8], write: u8| {
+        registers.write(PCM_WRITE, write);
+        registers.write(PCM_WRITE + 1, 0);
+        registers.commit(memory)
+    };
+
+    let memory = [0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0xC0, 0x40];
+    assert_eq!(hand_over(&memory, 6), 6);
+    voice.render_audio(&mut [0.0; 6]);
+    assert_eq!(registers.read.load(Ordering::Relaxed), 6);
+
+    // The guest writes the last two samples and goes on from the start of the ring.
+    let memory = [0xA0, 0x60, 0xFF, 0x80, 0x80, 0x80, 0xC0, 0x40];
+    assert_eq!(hand_over(&memory, 3), 5);
+    assert_eq!(registers.remaining(), 5);
+    let mut out = [0.0; 5];
+    voice.render_audio(&mut out);
+    assert_eq!(out, [0.5, -0.5, 0.25, -0.25, 127.0 / 128.0]);
+    assert_eq!(registers.read.load(Ordering::Relaxed), 3);
+    assert_eq!(registers.read(PCM_STATUS), 0);
+}
+
+#[test]
+fn pcm_adds_to_the_square_voice() {
+    // 1 kHz at full volume over 8 kHz: four samples at +1, then four at -1.
+    let mut out = [0.0; 8];
+    voice(1000, 255, 8000).render_audio(&mut out);
+    assert_eq!(out, [1.0, 1.0, 1.0, 1.0, -1.0, -1.0, -1.0, -1.0]);
+    let mix = mix_pcm(&[0xC0; 8], 8000, 8000, 0, &mut out);
+    assert!(!mix.underrun);
+    // The high half clips, the low half comes up by the sample.
+    assert_eq!(out, [1.0, 1.0, 1.0, 1.0, -0.5, -0.5, -0.5, -0.5]);
+
+    let mut out = [0.0; 8];
+    voice(1000, 64, 8000).render_audio(&mut out);
+    mix_pcm(&[0x00; 8], 8000, 8000, 0, &mut out);
+    let high = 64.0 / 255.0 - 1.0;
+    assert_eq!(out, [high, high, high, high, -1.0, -1.0, -1.0, -1.0]);
+}
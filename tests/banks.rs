@@ -0,0 +1,95 @@
+// Everything written through the bank window has to land in the bank BANK_SELECT picked, so two
+// banks filled through the same window each keep their own bytes, and a bank number past the
+// last bank wraps around instead of reaching beyond physical memory.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::cpu::{BANK_COUNT, BANK_SELECT, BANK_SIZE, BANK_WINDOW_START, Register};
+
+fn run(vm: &mut MicroCvm, source: &str) {
+    vm.load_program(&assemble(source).unwrap()).unwrap();
+    vm.cpu_mut().pc = 0;
+    vm.cpu_mut().halted = false;
+    vm.run().unwrap();
+}
+
+// Selects `bank`, then fills the whole window with `value` and stores `value + 1` to its last
+// byte with a plain store.
+fn fill_bank(vm: &mut MicroCvm, bank: usize, value: u8) {
+    run(
+        vm,
+        &format!(
+            "
+        mov r0, {bank}
+        store [{select:#x}], r0
+        mov r0, 0
+        mov r1, {window:#x}
+        mov r2, {value}
+        mov r3, 0
+        mov r4, {size:#x}
+        memset r0
+        mov r0, {next}
+        store [{last:#x}], r0
+        hlt
+",
+            bank = bank as u8,
+            select = BANK_SELECT,
+            window = BANK_WINDOW_START >> 8,
+            value = value,
+            size = BANK_SIZE >> 8,
+            next = value + 1,
+            last = BANK_WINDOW_START as usize + BANK_SIZE - 1,
+        ),
+    );
+}
+
+fn bank(vm: &MicroCvm, bank: usize) -> &[u8] {
+    &vm.cpu().memory()[bank * BANK_SIZE..(bank + 1) * BANK_SIZE]
+}
+
+#[test]
+fn two_banks_through_one_window() {
+    let mut vm = MicroCvm::builder().build();
+    fill_bank(&mut vm, 5, 0x11);
+    fill_bank(&mut vm, 9, 0x22);
+    for (number, value) in [(5, 0x11), (9, 0x22)] {
+        let bytes = bank(&vm, number);
+        assert!(
+            bytes[..BANK_SIZE - 1].iter().all(|&b| b == value),
+            "bank {}",
+            number
+        );
+        assert_eq!(bytes[BANK_SIZE - 1], value + 1, "bank {}", number);
+    }
+    // The banks either side were left alone.
+    for number in [4, 6, 8, 10] {
+        assert!(bank(&vm, number).iter().all(|&b| b == 0), "bank {}", number);
+    }
+
+    // Switching back shows the first bank's bytes through the window again.
+    run(
+        &mut vm,
+        &format!(
+            "mov r0, 5\nstore [{:#x}], r0\nload r0, [{:#x}]\nhlt",
+            BANK_SELECT, BANK_WINDOW_START
+        ),
+    );
+    assert_eq!(vm.cpu()[Register::R0], 0x11);
+}
+
+#[test]
+fn bank_numbers_wrap_around_the_bank_count() {
+    let mut vm = MicroCvm::builder().build();
+    for selected in [BANK_COUNT + 3, 2 * BANK_COUNT - 1] {
+        let wrapped = selected % BANK_COUNT;
+        run(
+            &mut vm,
+            &format!(
+                "mov r0, {}\nstore [{:#x}], r0\nmov r1, 0x5A\nstore [{:#x}], r1\nload r2, [{:#x}]\nhlt",
+                selected as u8, BANK_SELECT, BANK_WINDOW_START, BANK_SELECT
+            ),
+        );
+        assert_eq!(vm.cpu()[Register::R2] as usize, wrapped, "{}", selected);
+        assert_eq!(vm.cpu().memory()[wrapped * BANK_SIZE], 0x5A, "{}", selected);
+    }
+}
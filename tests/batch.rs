@@ -0,0 +1,75 @@
+// Machines have to be independent of each other: run side by side on as many threads as there
+// are, each one has to end where it would have on its own, with nothing shared between them.
+
+use microcvm_rs::asm::assemble;
+use microcvm_rs::{HaltReason, MicroCvm};
+
+const MACHINES: usize = 64;
+const INSTRUCTIONS: u64 = 1_000_000;
+
+// Never halts: a xorshift in r0 and a counter across r2 and r3, stepped by a seed in r1.
+const PROGRAM: &str = "
+loop:   mov r4, r0
+        add r4, r4
+        add r0, r4
+        add r0, r1
+        add r2, 1
+        jrnz loop
+        inc r3
+        jmp loop
+";
+
+fn machine(seed: u16) -> MicroCvm {
+    let mut vm = MicroCvm::builder().max_instructions(INSTRUCTIONS).build();
+    vm.load_program(&assemble(PROGRAM).unwrap()).unwrap();
+    vm.cpu_mut().registers[0] = seed;
+    vm.cpu_mut().registers[1] = seed | 1;
+    vm
+}
+
+#[test]
+fn a_batch_ends_where_each_machine_would_alone() {
+    let mut batch: Vec<MicroCvm> = (0..MACHINES as u16).map(machine).collect();
+    let results = MicroCvm::run_batch(&mut batch);
+    assert_eq!(results.len(), MACHINES);
+
+    let mut finals = Vec::new();
+    for (seed, (vm, result)) in batch.iter().zip(results).enumerate() {
+        assert_eq!(result.unwrap(), HaltReason::InstructionLimit, "{}", seed);
+        assert_eq!(vm.instructions(), INSTRUCTIONS, "{}", seed);
+
+        // A copy run on this thread alone agrees on everything.
+        let mut alone = machine(seed as u16);
+        alone.run().unwrap();
+        assert!(
+            alone.snapshot().diff(&vm.snapshot()).is_empty(),
+            "{}: {}",
+            seed,
+            alone.snapshot().diff(&vm.snapshot())
+        );
+        finals.push(vm.cpu().registers[0]);
+    }
+    // And they didn't all end up in the same place.
+    finals.sort();
+    finals.dedup();
+    assert!(finals.len() > MACHINES / 2, "{:?}", finals);
+}
+
+#[test]
+fn threads_of_their_own_work_too() {
+    let handles: Vec<_> = (0..8u16)
+        .map(|seed| {
+            let mut vm = machine(seed);
+            std::thread::spawn(move || {
+                vm.run_for(10_000).unwrap();
+                vm
+            })
+        })
+        .collect();
+    for (seed, handle) in handles.into_iter().enumerate() {
+        let vm = handle.join().unwrap();
+        let mut alone = machine(seed as u16);
+        alone.run_for(10_000).unwrap();
+        assert_eq!(vm.cpu().registers, alone.cpu().registers, "{}", seed);
+    }
+}
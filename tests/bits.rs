@@ -0,0 +1,124 @@
+// `djnz` has to count a register down to 0 without touching the flags, `test` and `btst` have
+// to set only the zero flag, and a bit index past the register's width has to fault rather
+// than wrap.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::cpu::{FLAG_CARRY, FLAG_ZERO, RegisterWidth};
+use microcvm_rs::error::VmError;
+
+const WIDTHS: [RegisterWidth; 2] = [RegisterWidth::Eight, RegisterWidth::Sixteen];
+
+fn run(width: RegisterWidth, source: &str) -> MicroCvm {
+    let source = match width {
+        RegisterWidth::Eight => String::from(source),
+        RegisterWidth::Sixteen => format!(".width 16\n{}", source),
+    };
+    let mut vm = MicroCvm::builder().register_width(width).build();
+    vm.load_program(&assemble(&source).unwrap()).unwrap();
+    vm.run().unwrap();
+    vm
+}
+
+#[test]
+fn djnz_counts_down_and_leaves_the_flags_alone() {
+    for width in WIDTHS {
+        let vm = run(
+            width,
+            "mov r0, 0\nmov r1, 5\nloop: inc r0\ndjnz r1, loop\nhlt",
+        );
+        assert_eq!(vm.cpu().registers[..2], [5, 0]);
+
+        // Z and C are set going in and counting to 0 doesn't clear them.
+        let vm = run(
+            width,
+            "mov r2, 1\nsub r2, 1\nstc\nmov r1, 5\nloop: djnz r1, loop\nhlt",
+        );
+        assert_eq!(vm.cpu().registers[1], 0);
+        assert_eq!(
+            vm.cpu().flags & (FLAG_ZERO | FLAG_CARRY),
+            FLAG_ZERO | FLAG_CARRY
+        );
+
+        // Nor does it set them.
+        let vm = run(width, "mov r1, 3\nloop: djnz r1, loop\nhlt");
+        assert_eq!(vm.cpu().flags & (FLAG_ZERO | FLAG_CARRY), 0);
+    }
+}
+
+#[test]
+fn djnz_from_zero_goes_all_the_way_round() {
+    let vm = run(
+        RegisterWidth::Eight,
+        "mov r0, 0\nmov r1, 0\nloop: inc r0\ndjnz r1, loop\nhlt",
+    );
+    // 256 times round, so r0 wrapped back to 0 with it.
+    assert_eq!(vm.cpu().registers[..2], [0, 0]);
+    let vm = run(
+        RegisterWidth::Sixteen,
+        "mov r0, 0\nmov r1, 0x100\nloop: inc r0\ndjnz r1, loop\nhlt",
+    );
+    assert_eq!(vm.cpu().registers[..2], [0x100, 0]);
+}
+
+#[test]
+fn test_and_btst_only_set_the_zero_flag() {
+    for (source, zero) in [
+        ("mov r0, 0b1010\ntest r0, 0b0101\nhlt", true),
+        ("mov r0, 0b1010\ntest r0, 0b0010\nhlt", false),
+        ("mov r0, 0b1010\nmov r1, 0b1000\ntest r0, r1\nhlt", false),
+        ("mov r0, 0b1010\nbtst r0, 0\nhlt", true),
+        ("mov r0, 0b1010\nbtst r0, 3\nhlt", false),
+    ] {
+        for width in WIDTHS {
+            let vm = run(width, &format!("stc\n{}", source));
+            assert_eq!(vm.cpu().flags & FLAG_ZERO != 0, zero, "{}", source);
+            assert_ne!(vm.cpu().flags & FLAG_CARRY, 0, "{}", source);
+            // The register is left as it was.
+            assert_eq!(vm.cpu().registers[0], 0b1010, "{}", source);
+        }
+    }
+}
+
+#[test]
+fn bset_and_bclr_change_one_bit() {
+    let vm = run(
+        RegisterWidth::Eight,
+        "mov r0, 0\nbset r0, 7\nbset r0, 0\nbset r0, 0\nmov r1, 0xFF\nbclr r1, 4\nbclr r1, 4\nhlt",
+    );
+    assert_eq!(vm.cpu().registers[..2], [0x81, 0xEF]);
+    let vm = run(
+        RegisterWidth::Sixteen,
+        "mov r0, 0\nbset r0, 15\nmov r1, 0xFFFF\nbclr r1, 8\nbtst r1, 8\nhlt",
+    );
+    assert_eq!(vm.cpu().registers[..2], [0x8000, 0xFEFF]);
+    assert_ne!(vm.cpu().flags & FLAG_ZERO, 0);
+}
+
+#[test]
+fn bit_indices_past_the_width_fault() {
+    for (width, bit) in [
+        (RegisterWidth::Eight, 8),
+        (RegisterWidth::Eight, 0xFF),
+        (RegisterWidth::Sixteen, 16),
+    ] {
+        // bset, bclr and btst r0 with `bit`.
+        for opcode in [0x11, 0x12, 0x13] {
+            let mut vm = MicroCvm::builder().register_width(width).build();
+            vm.load_program(&[opcode, 0x00, bit, 0xFF]).unwrap();
+            let error = vm.run().unwrap_err();
+            assert!(
+                matches!(error.cause(), &VmError::InvalidBitIndex { bit: faulted, pc: 0 } if faulted == bit),
+                "{:?} {:#04x} {}: {:?}",
+                width,
+                opcode,
+                bit,
+                error
+            );
+            // Before it changed anything.
+            assert_eq!(vm.cpu().registers[0], 0);
+        }
+    }
+    // The assembler won't write one either.
+    assert!(assemble("bset r0, 8\nhlt").is_err());
+}
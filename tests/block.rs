@@ -0,0 +1,114 @@
+// `memset` and `memcpy` have to copy as memmove does when their ranges overlap, do nothing
+// at all for a length of 0, and fault naming the range when it runs past the end of memory,
+// before writing any of it.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::error::VmError;
+
+// Loads r0 onwards with `operands`, then runs `op r0` from a fresh start.
+fn block(vm: &mut MicroCvm, op: &str, operands: &[u8]) -> Result<(), VmError> {
+    let mut source = String::new();
+    for (index, byte) in operands.iter().enumerate() {
+        source.push_str(&format!("mov r{}, {:#04x}\n", index, byte));
+    }
+    source.push_str(&format!("{} r0\nhlt", op));
+    vm.load_program(&assemble(&source).unwrap()).unwrap();
+    vm.cpu_mut().pc = 0;
+    vm.cpu_mut().halted = false;
+    vm.run().map(|_| ())
+}
+
+fn memset(vm: &mut MicroCvm, dst: u16, value: u8, len: u16) -> Result<(), VmError> {
+    let ([dst_lo, dst_hi], [len_lo, len_hi]) = (dst.to_le_bytes(), len.to_le_bytes());
+    block(vm, "memset", &[dst_lo, dst_hi, value, len_lo, len_hi])
+}
+
+fn memcpy(vm: &mut MicroCvm, dst: u16, src: u16, len: u16) -> Result<(), VmError> {
+    let operands: Vec<u8> = [dst, src, len]
+        .iter()
+        .flat_map(|n| n.to_le_bytes())
+        .collect();
+    block(vm, "memcpy", &operands)
+}
+
+// 1, 2, 3 and so on from `at`.
+fn counting(vm: &mut MicroCvm, at: usize, len: usize) {
+    for (offset, byte) in vm.cpu_mut().memory_mut()[at..at + len]
+        .iter_mut()
+        .enumerate()
+    {
+        *byte = (offset as u8).wrapping_add(1);
+    }
+}
+
+fn out_of_bounds(error: &VmError, start: u16, len: u16) -> bool {
+    matches!(
+        error.cause(),
+        VmError::RangeOutOfBounds { start: s, len: l, .. } if (*s, *l) == (start, len)
+    )
+}
+
+#[test]
+fn overlapping_copies_move_the_bytes() {
+    let mut vm = MicroCvm::builder().build();
+    // Forwards, onto the end of its own source.
+    counting(&mut vm, 0x1000, 8);
+    memcpy(&mut vm, 0x1004, 0x1000, 8).unwrap();
+    assert_eq!(
+        vm.cpu().memory()[0x1000..0x100C],
+        [1, 2, 3, 4, 1, 2, 3, 4, 5, 6, 7, 8]
+    );
+
+    // Backwards, onto its start.
+    counting(&mut vm, 0x2004, 8);
+    memcpy(&mut vm, 0x2000, 0x2004, 8).unwrap();
+    assert_eq!(
+        vm.cpu().memory()[0x2000..0x200C],
+        [1, 2, 3, 4, 5, 6, 7, 8, 5, 6, 7, 8]
+    );
+}
+
+#[test]
+fn zero_lengths_do_nothing() {
+    let mut vm = MicroCvm::builder().memory_size(0x1000).build();
+    counting(&mut vm, 0x0800, 0x800);
+    let before = vm.cpu().memory()[0x0800..].to_vec();
+    // Even where a range of any length would fault: past the end of memory and in MMIO.
+    for addr in [0x0800, 0x0FFF, 0x8000, 0xFF00, 0xFFFF] {
+        memset(&mut vm, addr, 0xEE, 0).unwrap();
+        memcpy(&mut vm, addr, 0x0800, 0).unwrap();
+        memcpy(&mut vm, 0x0800, addr, 0).unwrap();
+        assert!(vm.cpu().memory()[0x0800..] == before[..], "{:#x}", addr);
+    }
+}
+
+#[test]
+fn ranges_past_the_end_fault_with_the_range() {
+    let mut vm = MicroCvm::builder().memory_size(0x1000).build();
+    counting(&mut vm, 0x0F00, 0x100);
+    let before = vm.cpu().memory()[0x0F00..].to_vec();
+
+    let error = memset(&mut vm, 0x0F00, 0xEE, 0x101).unwrap_err();
+    assert!(out_of_bounds(&error, 0x0F00, 0x101), "{:?}", error);
+    assert_eq!(
+        error.cause().to_string(),
+        format!(
+            "Memory range out of bounds: 257 bytes at 0x0f00 (pc {:#06x})",
+            vm.cpu().pc
+        )
+    );
+    // The destination runs off the end.
+    let error = memcpy(&mut vm, 0x0FF0, 0x0F00, 0x20).unwrap_err();
+    assert!(out_of_bounds(&error, 0x0FF0, 0x20), "{:?}", error);
+    // The source does.
+    let error = memcpy(&mut vm, 0x0F00, 0x0FF0, 0x20).unwrap_err();
+    assert!(out_of_bounds(&error, 0x0FF0, 0x20), "{:?}", error);
+    assert!(vm.cpu().memory()[0x0F00..] == before[..]);
+
+    // Into MMIO, with memory to spare.
+    let mut vm = MicroCvm::builder().build();
+    let error = memset(&mut vm, 0xFEF0, 0xEE, 0x20).unwrap_err();
+    assert!(out_of_bounds(&error, 0xFEF0, 0x20), "{:?}", error);
+    assert!(vm.cpu().memory()[0xFEF0..0xFF00].iter().all(|&b| b == 0));
+}
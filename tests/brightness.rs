@@ -0,0 +1,124 @@
+// Brightness has to scale the presented frame and nothing else: black at 0, every channel
+// halved and rounded at 128, with video memory holding its pixels throughout, and a fade has
+// to reach its target in the frames it was given.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::cpu::VIDEO_BRIGHTNESS;
+use microcvm_rs::types::Color;
+
+const PIXELS: [u8; 9] = [255, 200, 1, 0, 128, 77, 3, 254, 127];
+
+fn machine() -> MicroCvm {
+    let mut vm = MicroCvm::builder().resolution(3, 1).build();
+    vm.cpu_mut()
+        .video_memory
+        .bytes_mut()
+        .copy_from_slice(&PIXELS);
+    vm
+}
+
+fn frame(vm: &mut MicroCvm) -> [u8; 12] {
+    let mut frame = [0; 12];
+    vm.frame_rgba(&mut frame);
+    frame
+}
+
+#[test]
+fn brightness_scales_only_what_is_presented() {
+    let mut vm = machine();
+    assert_eq!(
+        frame(&mut vm),
+        [255, 200, 1, 255, 0, 128, 77, 255, 3, 254, 127, 255]
+    );
+
+    vm.cpu_mut().brightness = 0;
+    assert_eq!(frame(&mut vm), [0, 0, 0, 255, 0, 0, 0, 255, 0, 0, 0, 255]);
+    assert_eq!(vm.framebuffer().bytes(), PIXELS);
+
+    // Halved and rounded: 1 * 128 / 255 is 0.502 and 254 * 128 / 255 is 127.498.
+    vm.cpu_mut().brightness = 128;
+    assert_eq!(
+        frame(&mut vm),
+        [128, 100, 1, 255, 0, 64, 39, 255, 2, 127, 64, 255]
+    );
+    assert_eq!(vm.framebuffer().bytes(), PIXELS);
+
+    vm.cpu_mut().brightness = 255;
+    assert_eq!(frame(&mut vm)[..4], [255, 200, 1, 255]);
+}
+
+#[test]
+fn scaling_rounds_to_the_nearest_value() {
+    for channel in 0..=255u8 {
+        for brightness in [0, 1, 64, 128, 254, 255] {
+            let scaled = Color::new(channel, channel, channel).scale_brightness(brightness);
+            let exact = channel as f64 * brightness as f64 / 255.0;
+            assert!(
+                (scaled.r as f64 - exact).abs() <= 0.5,
+                "{} at {}: {}",
+                channel,
+                brightness,
+                scaled.r
+            );
+            assert_eq!(scaled.a, 255);
+        }
+    }
+}
+
+#[test]
+fn a_fade_takes_the_frames_it_was_given() {
+    // From full brightness to black over four frames, then back up over two.
+    let mut vm = machine();
+    let source = format!(
+        "
+        mov r0, 255
+        store [{:#x}], r0
+        mov r0, 0
+        mov r1, 4
+        video fadeto, r0
+        hlt
+",
+        VIDEO_BRIGHTNESS
+    );
+    vm.load_program(&assemble(&source).unwrap()).unwrap();
+    vm.run().unwrap();
+    let mut levels = Vec::new();
+    for _ in 0..5 {
+        vm.tick_frame();
+        levels.push(vm.cpu().brightness);
+    }
+    assert_eq!(levels, [191, 127, 63, 0, 0]);
+    assert_eq!(vm.framebuffer().bytes(), PIXELS);
+
+    vm.load_program(&assemble("mov r0, 200\nmov r1, 2\nvideo fadeto, r0\nhlt").unwrap())
+        .unwrap();
+    vm.cpu_mut().pc = 0;
+    vm.cpu_mut().halted = false;
+    vm.run().unwrap();
+    vm.tick_frame();
+    vm.tick_frame();
+    assert_eq!(vm.cpu().brightness, 200);
+}
+
+#[test]
+fn writing_the_brightness_ends_a_fade() {
+    let mut vm = machine();
+    let source = format!(
+        "
+        mov r0, 0
+        mov r1, 10
+        video fadeto, r0
+        mov r0, 50
+        store [{:#x}], r0
+        hlt
+",
+        VIDEO_BRIGHTNESS
+    );
+    vm.load_program(&assemble(&source).unwrap()).unwrap();
+    vm.run().unwrap();
+    for _ in 0..3 {
+        vm.tick_frame();
+        assert_eq!(vm.cpu().brightness, 50);
+    }
+}
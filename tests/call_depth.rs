@@ -0,0 +1,84 @@
+// Runaway recursion has to stop at the call depth it is given, with the return addresses it
+// came through, and without a limit it has to be caught as a stack overflow before the stack
+// tramples anything under it.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble_with_symbols;
+use microcvm_rs::error::VmError;
+
+// `recurse` calls itself with no way out.
+const RECURSION: &str = "
+        call recurse
+        hlt
+recurse:
+        inc r0
+        call recurse
+        ret
+";
+
+#[test]
+fn recursion_stops_at_the_maximum_depth() {
+    let (program, symbols) = assemble_with_symbols(RECURSION).unwrap();
+    let recurse = symbols.address("recurse").unwrap();
+    let mut vm = MicroCvm::builder().max_call_depth(16).build();
+    vm.load_program(&program).unwrap();
+    let error = vm.run().unwrap_err();
+    let VmError::CallDepthExceeded { depth, pc, chain } = error.cause() else {
+        panic!("{:?}", error);
+    };
+    assert_eq!(*depth, 17);
+    // The call that went too far, inside `recurse`.
+    assert_eq!(*pc, recurse + 2);
+    // Innermost first, back to the first call.
+    assert_eq!(chain.len(), 16);
+    assert!(chain[..15].iter().all(|&addr| addr == recurse + 5));
+    assert_eq!(chain[15], 0x0003);
+    assert_eq!(vm.cpu().registers[0], 16);
+    assert_eq!(vm.cpu().call_depth(), 16);
+    assert!(error.to_string().contains("returning to"), "{}", error);
+}
+
+#[test]
+fn ret_brings_the_depth_back_down() {
+    let (program, _) = assemble_with_symbols(
+        "
+        call one
+        hlt
+one:    call two
+        ret
+two:    call three
+        ret
+three:  ret
+",
+    )
+    .unwrap();
+    // Exactly as deep as it goes is allowed.
+    let mut vm = MicroCvm::builder().max_call_depth(3).build();
+    vm.load_program(&program).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.cpu().call_depth(), 0);
+
+    let mut vm = MicroCvm::builder().max_call_depth(2).build();
+    vm.load_program(&program).unwrap();
+    let error = vm.run().unwrap_err();
+    assert!(
+        matches!(error.cause(), VmError::CallDepthExceeded { depth: 3, .. }),
+        "{:?}",
+        error
+    );
+}
+
+#[test]
+fn without_a_limit_the_stack_overflows() {
+    let (program, _) = assemble_with_symbols(RECURSION).unwrap();
+    let mut vm = MicroCvm::builder().build();
+    vm.load_program(&program).unwrap();
+    let error = vm.run().unwrap_err();
+    assert!(
+        matches!(error.cause(), VmError::StackOverflow { .. }),
+        "{:?}",
+        error
+    );
+    // Nothing below the stack was written: the program is still there.
+    assert_eq!(&vm.cpu().memory()[..program.len()], &program[..]);
+}
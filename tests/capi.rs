@@ -0,0 +1,179 @@
+// Every C entry point has to refuse null pointers with an error code rather than crash, turn a
+// panic into MICROCVM_ERR_PANIC rather than unwind into the host, and map each fault it runs
+// into to the code for that error.
+#![cfg(feature = "capi")]
+
+use std::cell::Cell;
+use std::ptr;
+
+use log::{LevelFilter, Log, Metadata, Record};
+use microcvm_rs::asm::assemble;
+use microcvm_rs::ffi::*;
+use microcvm_rs::isa;
+
+thread_local! {
+    static PANIC_ON_LOG: Cell<bool> = const { Cell::new(false) };
+}
+
+// Stands in for a host logger that panics, on the threads that ask it to.
+struct Panicking;
+
+impl Log for Panicking {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        if PANIC_ON_LOG.with(Cell::get) {
+            panic!("logger panicked on `{}`", record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static PANICKING: Panicking = Panicking;
+
+// A fresh machine with `source` loaded at 0.
+fn machine(source: &str) -> *mut MicroCvm {
+    let vm = microcvm_new();
+    assert!(!vm.is_null());
+    let program = assemble(source).unwrap();
+    let status = unsafe { microcvm_load(vm, program.as_ptr(), program.len(), 0) };
+    assert_eq!(status, MICROCVM_OK);
+    vm
+}
+
+fn register(vm: *mut MicroCvm, index: u8) -> Result<u8, i32> {
+    let mut value = 0;
+    match unsafe { microcvm_get_register(vm, index, &mut value) } {
+        MICROCVM_OK => Ok(value),
+        error => Err(error),
+    }
+}
+
+#[test]
+fn a_program_runs_to_its_halt() {
+    let vm = machine("mov r0, 6\ninc r0\nstore [0x0100], r0\nhlt");
+    unsafe {
+        assert_eq!(microcvm_step(vm), MICROCVM_OK);
+        assert_eq!(register(vm, 0), Ok(6));
+        assert_eq!(microcvm_run(vm, 1), MICROCVM_OK);
+        assert_eq!(microcvm_run(vm, 100), MICROCVM_HALTED);
+        assert_eq!(microcvm_step(vm), MICROCVM_HALTED);
+        assert_eq!(register(vm, 0), Ok(7));
+
+        let mut memory = [0; 2];
+        assert_eq!(
+            microcvm_read_mem(vm, 0x0100, memory.as_mut_ptr(), 2),
+            MICROCVM_OK
+        );
+        assert_eq!(memory, [7, 0]);
+
+        let pixels = microcvm_framebuffer_len(vm);
+        assert!(pixels > 0);
+        let mut rgba = [0xAA; 8];
+        assert_eq!(
+            microcvm_read_framebuffer(vm, pixels - 2, rgba.as_mut_ptr(), 2),
+            MICROCVM_OK
+        );
+        assert_eq!(rgba, [0, 0, 0, 255, 0, 0, 0, 255]);
+        microcvm_free(vm);
+    }
+}
+
+#[test]
+fn null_pointers_are_refused() {
+    let mut byte = 0;
+    unsafe {
+        let null = ptr::null_mut();
+        assert_eq!(microcvm_load(null, [0u8].as_ptr(), 1, 0), MICROCVM_ERR_NULL);
+        assert_eq!(microcvm_step(null), MICROCVM_ERR_NULL);
+        assert_eq!(microcvm_run(null, 10), MICROCVM_ERR_NULL);
+        assert_eq!(microcvm_get_register(null, 0, &mut byte), MICROCVM_ERR_NULL);
+        assert_eq!(microcvm_read_mem(null, 0, &mut byte, 1), MICROCVM_ERR_NULL);
+        assert_eq!(
+            microcvm_read_framebuffer(null, 0, &mut byte, 0),
+            MICROCVM_ERR_NULL
+        );
+        assert_eq!(microcvm_framebuffer_len(ptr::null()), 0);
+        microcvm_free(null);
+
+        // A null buffer is fine for nothing at all, and refused for anything more.
+        let vm = microcvm_new();
+        assert_eq!(microcvm_load(vm, ptr::null(), 0, 0), MICROCVM_OK);
+        assert_eq!(microcvm_load(vm, ptr::null(), 1, 0), MICROCVM_ERR_NULL);
+        assert_eq!(microcvm_read_mem(vm, 0, ptr::null_mut(), 0), MICROCVM_OK);
+        assert_eq!(
+            microcvm_read_mem(vm, 0, ptr::null_mut(), 1),
+            MICROCVM_ERR_NULL
+        );
+        assert_eq!(
+            microcvm_read_framebuffer(vm, 0, ptr::null_mut(), 1),
+            MICROCVM_ERR_NULL
+        );
+        assert_eq!(
+            microcvm_get_register(vm, 0, ptr::null_mut()),
+            MICROCVM_ERR_NULL
+        );
+        microcvm_free(vm);
+    }
+}
+
+#[test]
+fn faults_map_to_their_error_codes() {
+    for (source, expected) in [
+        ("mov r0, 1\ndiv r0, 0", MICROCVM_ERR_DIVISION_BY_ZERO),
+        ("hcall 200", MICROCVM_ERR_UNREGISTERED_HCALL),
+        ("ret", MICROCVM_ERR_STACK_UNDERFLOW),
+        ("int 16", MICROCVM_ERR_INVALID_VECTOR),
+    ] {
+        let vm = machine(source);
+        assert_eq!(unsafe { microcvm_run(vm, 10) }, expected, "{}", source);
+        unsafe { microcvm_free(vm) };
+    }
+
+    unsafe {
+        let vm = microcvm_new();
+        let opcode = [(0..=255).find(|&byte| isa::decode(byte).is_none()).unwrap()];
+        assert_eq!(microcvm_load(vm, opcode.as_ptr(), 1, 0), MICROCVM_OK);
+        assert_eq!(microcvm_step(vm), MICROCVM_ERR_INVALID_OPCODE);
+
+        // Bigger than all of memory, not just the 64K the guest addresses.
+        let image = vec![0; 4 << 20];
+        assert_eq!(
+            microcvm_load(vm, image.as_ptr(), image.len(), 0),
+            MICROCVM_ERR_PROGRAM_TOO_LARGE
+        );
+        assert_eq!(register(vm, 8), Err(MICROCVM_ERR_INVALID_REGISTER));
+
+        let pixels = microcvm_framebuffer_len(vm);
+        let mut rgba = [0; 8];
+        for (start, count) in [(pixels, 1), (pixels - 1, 2), (usize::MAX, 2)] {
+            let status = microcvm_read_framebuffer(vm, start, rgba.as_mut_ptr(), count);
+            assert_eq!(status, MICROCVM_ERR_INVALID_ARGUMENT, "{} {}", start, count);
+        }
+        microcvm_free(vm);
+    }
+}
+
+#[test]
+fn panics_do_not_unwind_into_the_host() {
+    let _ = log::set_logger(&PANICKING);
+    log::set_max_level(LevelFilter::Trace);
+    let vm = microcvm_new();
+    let program = [0x00];
+    PANIC_ON_LOG.with(|panic| panic.set(true));
+    // Loading logs what it loaded, and the logger panics.
+    let status = unsafe { microcvm_load(vm, program.as_ptr(), 1, 0) };
+    PANIC_ON_LOG.with(|panic| panic.set(false));
+    assert_eq!(status, MICROCVM_ERR_PANIC);
+
+    // The machine is still there to use and free.
+    assert_eq!(
+        unsafe { microcvm_load(vm, program.as_ptr(), 1, 0) },
+        MICROCVM_OK
+    );
+    assert!(unsafe { microcvm_framebuffer_len(vm) } > 0);
+    unsafe { microcvm_free(vm) };
+}
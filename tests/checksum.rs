@@ -0,0 +1,126 @@
+// Programs carry a CRC-32 of their payload, so one bit flipped in transit, or a file cut
+// short, has to be refused with both checksums before any of it reaches memory, unless
+// checking is turned off for a binary edited by hand.
+
+use std::process::Command;
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::crc32::crc32;
+use microcvm_rs::error::VmError;
+use microcvm_rs::program::{HEADER_LEN, Program, ProgramHeader};
+
+fn file() -> (Vec<u8>, Vec<u8>) {
+    let payload = assemble("mov r0, 7\nhlt").unwrap();
+    (Program::new(0x0100, payload.clone()).build(), payload)
+}
+
+// A machine whose memory is all 0xAA, to see that nothing was written.
+fn machine() -> MicroCvm {
+    let mut vm = MicroCvm::builder().build();
+    vm.cpu_mut().memory_mut().fill(0xAA);
+    vm
+}
+
+fn untouched(vm: &MicroCvm) -> bool {
+    vm.cpu().memory().iter().all(|&byte| byte == 0xAA) && vm.cpu().pc == 0
+}
+
+#[test]
+fn crc32_is_the_zlib_one() {
+    assert_eq!(crc32(b""), 0);
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    assert_eq!(
+        crc32(b"The quick brown fox jumps over the lazy dog"),
+        0x414F_A339
+    );
+}
+
+#[test]
+fn building_records_the_payload_checksum() {
+    let (file, payload) = file();
+    let header = ProgramHeader::parse(&file).unwrap();
+    assert_eq!(header.checksum, Some(crc32(&payload)));
+    let mut vm = machine();
+    vm.load_program(&file).unwrap();
+    assert_eq!(vm.cpu().memory()[0x0100..][..payload.len()], payload[..]);
+}
+
+#[test]
+fn any_flipped_payload_bit_is_refused_without_touching_memory() {
+    let (file, payload) = file();
+    for index in 0..payload.len() {
+        for bit in [0x01, 0x80] {
+            let mut corrupted = file.clone();
+            corrupted[HEADER_LEN + index] ^= bit;
+            let mut vm = machine();
+            let error = vm.load_program(&corrupted).unwrap_err();
+            let VmError::ChecksumMismatch { expected, actual } = error else {
+                panic!("loaded a corrupted program: {}", error);
+            };
+            assert_eq!(expected, crc32(&payload));
+            assert_eq!(actual, crc32(&corrupted[HEADER_LEN..]));
+            assert!(untouched(&vm), "byte {} bit {:#x}", index, bit);
+        }
+    }
+}
+
+#[test]
+fn a_truncated_file_is_refused() {
+    let (file, _) = file();
+    let mut vm = machine();
+    assert!(vm.load_program(&file[..file.len() - 1]).is_err());
+    assert!(untouched(&vm));
+}
+
+#[test]
+fn checking_can_be_turned_off() {
+    let (mut file, _) = file();
+    // mov r0, 7 becomes mov r0, 6.
+    file[HEADER_LEN + 2] ^= 0x01;
+    let mut vm = MicroCvm::builder().verify_checksums(false).build();
+    vm.load_program(&file).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.cpu().registers[0], 6);
+}
+
+#[test]
+fn the_binary_refuses_a_corrupted_file_unless_told_not_to_verify() {
+    let dir = std::env::temp_dir().join("microcvm-checksum-test");
+    std::fs::create_dir_all(&dir).unwrap();
+    let source = dir.join("seven.asm");
+    std::fs::write(&source, ".org 0x0100\nmov r0, 7\nhlt\n").unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_microcvm-rs"))
+        .arg("asm")
+        .arg(&source)
+        .args(["--format", "header"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let program = dir.join("seven.mcvm");
+    let mut bytes = std::fs::read(&program).unwrap();
+    assert_eq!(
+        ProgramHeader::parse(&bytes).unwrap().checksum,
+        Some(crc32(&bytes[HEADER_LEN..]))
+    );
+    let immediate = bytes.len() - 2;
+    bytes[immediate] ^= 0x01;
+    std::fs::write(&program, bytes).unwrap();
+
+    let run = |extra: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_microcvm-rs"))
+            .arg("run")
+            .arg(&program)
+            .arg("--headless")
+            .args(extra)
+            .output()
+            .unwrap()
+    };
+    let output = run(&[]);
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Program checksum mismatch"), "{}", stderr);
+
+    let output = run(&["--no-verify"]);
+    assert_eq!(output.status.code(), Some(6), "{:?}", output);
+}
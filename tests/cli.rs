@@ -0,0 +1,89 @@
+// `microcvm run --headless` has to run a program without a window, print how it halted and
+// exit with its code, and a mistake on the command line has to explain itself and exit
+// nonzero.
+
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+use microcvm_rs::asm::assemble;
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("microcvm-cli-test-{}", name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn microcvm(dir: &PathBuf, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_microcvm-rs"))
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn headless_prints_the_halt_reason() {
+    let dir = scratch("headless");
+    std::fs::write(dir.join("seven.bin"), assemble("mov r0, 7\nhlt").unwrap()).unwrap();
+    std::fs::write(dir.join("spin.bin"), assemble("spin: jr spin").unwrap()).unwrap();
+
+    let output = microcvm(&dir, &["run", "seven.bin", "--headless"]);
+    assert_eq!(output.status.code(), Some(7), "{:?}", output);
+    assert_eq!(stdout(&output), "Halted with exit code 7\n");
+
+    let output = microcvm(
+        &dir,
+        &["run", "spin.bin", "--headless", "--max-instructions", "100"],
+    );
+    assert_eq!(output.status.code(), Some(0), "{:?}", output);
+    assert_eq!(stdout(&output), "Instruction limit reached\n");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn unknown_flags_fail_with_the_usage() {
+    let dir = scratch("unknown-flag");
+    let output = microcvm(&dir, &["run", "program.bin", "--headles"]);
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    let message = stderr(&output);
+    assert!(
+        message.starts_with("error: unknown option `--headles`"),
+        "{}",
+        message
+    );
+    assert!(message.contains("--headless"), "{}", message);
+    assert!(stdout(&output).is_empty());
+
+    let output = microcvm(&dir, &["frobnicate"]);
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    assert!(
+        stderr(&output).starts_with("error: unknown command `frobnicate`"),
+        "{:?}",
+        output
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn missing_files_fail_naming_the_file() {
+    let dir = scratch("missing-file");
+    let output = microcvm(&dir, &["run", "nowhere.bin", "--headless"]);
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    let message = stderr(&output);
+    assert!(
+        message.starts_with("error: could not read `nowhere.bin`"),
+        "{}",
+        message
+    );
+    assert!(stdout(&output).is_empty());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
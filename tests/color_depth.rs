@@ -0,0 +1,178 @@
+// Video memory has to be read the way its depth packs it, 1 bit per pixel up to 24, with the
+// palette behind 4 and 8 bits, and video operations and the guest's depth and palette
+// registers have to write it the same way.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::cpu::{PALETTE_DATA, PALETTE_INDEX, VIDEO_DEPTH};
+use microcvm_rs::video::{ColorDepth, VideoMemory};
+
+const K: [u8; 4] = [0, 0, 0, 255];
+const W: [u8; 4] = [255, 255, 255, 255];
+const RED: [u8; 4] = [255, 0, 0, 255];
+const BLUE: [u8; 4] = [0, 0, 255, 255];
+
+const DEPTHS: [ColorDepth; 4] = [
+    ColorDepth::One,
+    ColorDepth::Four,
+    ColorDepth::Eight,
+    ColorDepth::TwentyFour,
+];
+
+// The frame of a 4x2 screen whose video memory holds `bytes`.
+fn frame(depth: ColorDepth, bytes: &[u8]) -> Vec<[u8; 4]> {
+    let mut vm = MicroCvm::builder()
+        .resolution(4, 2)
+        .color_depth(depth)
+        .build();
+    vm.cpu_mut().video_memory.bytes_mut().copy_from_slice(bytes);
+    let mut rgba = vec![0; 4 * 2 * 4];
+    vm.frame_rgba(&mut rgba);
+    rgba.chunks(4)
+        .map(|pixel| pixel.try_into().unwrap())
+        .collect()
+}
+
+#[test]
+fn each_depth_converts_from_its_own_packing() {
+    // The leftmost pixel in the high bit.
+    assert_eq!(
+        frame(ColorDepth::One, &[0b1001_0110]),
+        [W, K, K, W, K, W, W, K]
+    );
+    // Palette indices, the leftmost pixel in the high nibble. The palette starts out with
+    // the xterm colors.
+    assert_eq!(
+        frame(ColorDepth::Four, &[0x9C, 0x0F, 0xF0, 0xC9]),
+        [RED, BLUE, K, W, W, K, BLUE, RED]
+    );
+    // The cube starts at 16 and the grays at 232.
+    assert_eq!(
+        frame(ColorDepth::Eight, &[9, 12, 15, 0, 196, 231, 232, 255]),
+        [
+            RED,
+            BLUE,
+            W,
+            K,
+            RED,
+            W,
+            [8, 8, 8, 255],
+            [238, 238, 238, 255]
+        ]
+    );
+    // Red, green and blue bytes.
+    let bytes: Vec<u8> = (0..24).collect();
+    let expected: Vec<[u8; 4]> = (0..8).map(|i| [i * 3, i * 3 + 1, i * 3 + 2, 255]).collect();
+    assert_eq!(frame(ColorDepth::TwentyFour, &bytes), expected);
+}
+
+#[test]
+fn pixels_pack_into_as_few_bytes_as_the_depth_needs() {
+    for (depth, bytes) in [
+        (ColorDepth::One, 2),
+        (ColorDepth::Four, 5),
+        (ColorDepth::Eight, 9),
+        (ColorDepth::TwentyFour, 27),
+    ] {
+        let mut memory = VideoMemory::new(9, depth);
+        assert_eq!(memory.bytes().len(), bytes, "{}", depth);
+        // Every pixel keeps its own value, whatever its neighbours hold.
+        let top = (1u32 << depth.bits()) - 1;
+        for pixel in 0..9 {
+            memory.set_raw(pixel, top);
+            memory.set_raw(pixel, pixel as u32 & top);
+        }
+        for pixel in 0..9 {
+            assert_eq!(memory.raw(pixel), pixel as u32 & top, "{} {}", depth, pixel);
+        }
+        // A fill stays inside its range, on byte boundaries or not.
+        memory.fill(3, 6, 1);
+        let raw: Vec<u32> = (0..9).map(|pixel| memory.raw(pixel)).collect();
+        let expected: Vec<u32> = (0..9)
+            .map(|pixel| match pixel {
+                3..6 => 1,
+                _ => pixel & top,
+            })
+            .collect();
+        assert_eq!(raw, expected, "{}", depth);
+    }
+}
+
+#[test]
+fn video_operations_write_at_the_current_depth() {
+    for depth in DEPTHS {
+        // setpixel 3, 0 and fillrect 1, 1 2x1, both with r = 0x11, g = 0x22, b = 0x33.
+        let source = "
+            mov r0, 3
+            mov r1, 0
+            mov r2, 0x11
+            mov r3, 0x22
+            mov r4, 0x33
+            video setpixel, r0
+            mov r0, 1
+            mov r1, 1
+            mov r2, 2
+            mov r3, 1
+            mov r4, 0x11
+            mov r5, 0x22
+            mov r6, 0x33
+            video fillrect, r0
+            hlt
+        ";
+        let mut vm = MicroCvm::builder()
+            .resolution(4, 2)
+            .color_depth(depth)
+            .build();
+        vm.load_program(&assemble(source).unwrap()).unwrap();
+        vm.run().unwrap();
+        let video = vm.framebuffer();
+        let value = video.encode(0x11, 0x22, 0x33);
+        let raw: Vec<u32> = (0..8).map(|pixel| video.raw(pixel)).collect();
+        assert_eq!(raw, [0, 0, 0, value, 0, value, value, 0], "{}", depth);
+    }
+    // Cut to the depth.
+    let video = VideoMemory::new(1, ColorDepth::One);
+    assert_eq!(video.encode(0x11, 0x22, 0x33), 1);
+    let video = VideoMemory::new(1, ColorDepth::Four);
+    assert_eq!(video.encode(0x11, 0x22, 0x33), 1);
+    let video = VideoMemory::new(1, ColorDepth::Eight);
+    assert_eq!(video.encode(0x11, 0x22, 0x33), 0x11);
+}
+
+#[test]
+fn the_guest_picks_the_depth_and_the_palette() {
+    let source = format!(
+        "
+        mov r0, 8
+        store [{depth:#x}], r0
+        mov r0, 16                      ; an invalid depth is ignored
+        store [{depth:#x}], r0
+        load r7, [{depth:#x}]
+        mov r0, 200
+        store [{index:#x}], r0
+        mov r0, 10
+        store [{data:#x}], r0
+        mov r0, 20
+        store [{data:#x} + 1], r0
+        mov r0, 30
+        store [{data:#x} + 2], r0
+        mov r0, 200
+        mov r1, 0
+        mov r2, 0
+        video fill, r0
+        hlt
+",
+        depth = VIDEO_DEPTH,
+        index = PALETTE_INDEX,
+        data = PALETTE_DATA
+    );
+    let mut vm = MicroCvm::builder().resolution(4, 2).build();
+    vm.load_program(&assemble(&source).unwrap()).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.cpu().registers[7], 8);
+    assert_eq!(vm.framebuffer().depth, ColorDepth::Eight);
+    assert_eq!(vm.framebuffer().bytes(), [200; 8]);
+    let mut rgba = vec![0; 4 * 2 * 4];
+    vm.frame_rgba(&mut rgba);
+    assert!(rgba.chunks(4).all(|pixel| pixel == [10, 20, 30, 255]));
+}
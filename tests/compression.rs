@@ -0,0 +1,138 @@
+// A compressed payload has to come out exactly as it went in, whether it compresses well or
+// not at all, and a header lying about its decompressed size can't get more written than it
+// claims, or anything at all when that is more than memory holds.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::error::VmError;
+#[cfg(not(feature = "flate2"))]
+use microcvm_rs::program::FLAG_COMPRESSED;
+use microcvm_rs::program::Program;
+
+// A machine whose memory is all 0xAA, to see what was written.
+fn machine() -> MicroCvm {
+    let mut vm = MicroCvm::builder().build();
+    vm.cpu_mut().memory_mut().fill(0xAA);
+    vm
+}
+
+#[cfg(feature = "flate2")]
+mod flate2 {
+    use super::*;
+    use microcvm_rs::crc32::crc32;
+    use microcvm_rs::program::HEADER_LEN;
+
+    // xorshift32 bytes, which don't compress at all.
+    fn noise(len: usize) -> Vec<u8> {
+        let mut state = 0x2545_f491u32;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect()
+    }
+
+    // Rewrites how long a compressed file says its payload decompresses to, with the checksum
+    // redone so only the length lies.
+    fn claim(file: &mut [u8], length: u32) {
+        file[HEADER_LEN..][..4].copy_from_slice(&length.to_le_bytes());
+        let checksum = crc32(&file[HEADER_LEN..]);
+        file[12..16].copy_from_slice(&checksum.to_le_bytes());
+    }
+
+    #[test]
+    fn payloads_round_trip_however_well_they_compress() {
+        let mut sparse = vec![0; 0x8000];
+        sparse[0x10..0x14].copy_from_slice(&[0x01, 0x19, 0x00, 0xFF]);
+        for payload in [sparse, noise(0x4000), Vec::new()] {
+            let file = Program::new(0x0100, payload.clone()).compress(true).build();
+            let parsed = Program::parse(&file).unwrap();
+            assert!(parsed.header.compressed());
+            assert!(parsed.payload == payload);
+
+            let mut vm = machine();
+            vm.load_program(&file).unwrap();
+            let memory = vm.cpu().memory();
+            assert!(memory[..0x0100].iter().all(|&byte| byte == 0xAA));
+            assert!(memory[0x0100..][..payload.len()] == payload[..]);
+            assert!(
+                memory[0x0100 + payload.len()..]
+                    .iter()
+                    .all(|&byte| byte == 0xAA)
+            );
+            assert_eq!(Program::extent(&file), (0x0100, payload.len()));
+        }
+        // Mostly zeroes compress better than 50:1.
+        let sparse = Program::new(0, vec![0; 0x8000]).compress(true).build();
+        assert!(sparse.len() < 0x8000 / 50, "{}", sparse.len());
+    }
+
+    #[test]
+    fn a_stream_longer_than_it_claims_stops_at_the_claim() {
+        let mut bomb = Program::new(0x0100, vec![0; 0x1_0000])
+            .compress(true)
+            .build();
+        claim(&mut bomb, 16);
+        let mut vm = machine();
+        let error = vm.load_program(&bomb).unwrap_err();
+        assert!(matches!(error, VmError::InvalidHeader { .. }), "{}", error);
+        let memory = vm.cpu().memory();
+        assert!(memory[0x0100..0x0110].iter().all(|&byte| byte == 0));
+        assert!(memory[0x0110..].iter().all(|&byte| byte == 0xAA));
+    }
+
+    #[test]
+    fn a_claim_past_the_end_of_memory_writes_nothing() {
+        let mut bomb = Program::new(0x0100, vec![0; 0x100]).compress(true).build();
+        // One byte more than fits, and everything a length can say.
+        let room = machine().cpu().memory().len() as u32 - 0x0100;
+        for length in [room + 1, u32::MAX] {
+            claim(&mut bomb, length);
+            let mut vm = machine();
+            let error = vm.load_program(&bomb).unwrap_err();
+            assert!(
+                matches!(error, VmError::ProgramTooLarge { .. }),
+                "{}",
+                error
+            );
+            assert!(vm.cpu().memory().iter().all(|&byte| byte == 0xAA));
+        }
+    }
+
+    #[test]
+    fn a_stream_shorter_than_it_claims_is_refused() {
+        let mut file = Program::new(0x0100, vec![1; 0x20]).compress(true).build();
+        claim(&mut file, 0x40);
+        let error = machine().load_program(&file).unwrap_err();
+        assert!(matches!(error, VmError::InvalidHeader { .. }), "{}", error);
+    }
+}
+
+#[cfg(not(feature = "flate2"))]
+#[test]
+fn compressed_payloads_need_the_feature() {
+    // A length prefix of 4, then what would be the gzip stream.
+    let mut file = Program::new(0x0100, vec![4, 0, 0, 0, 1, 2, 3]).build();
+    file[5] |= FLAG_COMPRESSED;
+    let mut vm = machine();
+    let error = vm.load_program(&file).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        VmError::InvalidHeader {
+            reason: "compressed payloads need the flate2 feature"
+        }
+        .to_string()
+    );
+    assert!(vm.cpu().memory().iter().all(|&byte| byte == 0xAA));
+}
+
+#[test]
+fn uncompressed_payloads_are_left_as_they_are() {
+    let file = Program::new(0x0100, vec![1, 2, 3]).build();
+    assert!(!Program::parse(&file).unwrap().header.compressed());
+    let mut vm = machine();
+    vm.load_program(&file).unwrap();
+    assert_eq!(vm.cpu().memory()[0x00FF..0x0104], [0xAA, 1, 2, 3, 0xAA]);
+}
@@ -0,0 +1,84 @@
+// Runs `microcvm run` with config files: what they set has to apply, options on the command
+// line have to win over them, and a mistake in one has to stop the run with its line.
+
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+fn scratch(name: &str, config: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("microcvm-config-test-{}", name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("microcvm.toml"), config).unwrap();
+    dir
+}
+
+fn run(dir: &PathBuf, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_microcvm-rs"))
+        .current_dir(dir)
+        .args(["run", "--demo", "--headless"])
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+// The instructions the trace shows, one line each.
+fn traced(output: &Output) -> usize {
+    String::from_utf8_lossy(&output.stderr)
+        .lines()
+        .filter(|line| line.starts_with("0x"))
+        .count()
+}
+
+#[test]
+fn options_win_over_the_file() {
+    let dir = scratch(
+        "precedence",
+        "[vm]\nmax_instructions = 10\n\n[trace]\nenabled = true\n",
+    );
+    // Found in the current directory.
+    let output = run(&dir, &[]);
+    assert!(output.status.success());
+    assert_eq!(traced(&output), 10);
+
+    let output = run(&dir, &["--max-instructions", "3"]);
+    assert!(output.status.success());
+    assert_eq!(traced(&output), 3);
+
+    // Named, in place of the one in the current directory.
+    std::fs::write(dir.join("other.toml"), "[vm]\nmax_instructions = 4\n").unwrap();
+    let output = run(&dir, &["--config", "other.toml", "--trace"]);
+    assert!(output.status.success());
+    assert_eq!(traced(&output), 4);
+}
+
+#[test]
+fn mistakes_stop_the_run() {
+    let dir = scratch(
+        "mistakes",
+        "[vm]\nmax_instructions = 10\n\n[window]\nscalefactor = 3\n",
+    );
+    let output = run(&dir, &[]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(
+            "`microcvm.toml` line 5: unknown key `scalefactor` in [window], did you mean `scale`?"
+        ),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn the_default_file_is_the_defaults() {
+    let dir = scratch("default", "");
+    let output = Command::new(env!("CARGO_BIN_EXE_microcvm-rs"))
+        .args(["config", "--print-default"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    std::fs::write(dir.join("microcvm.toml"), &output.stdout).unwrap();
+    let output = run(&dir, &["--max-instructions", "1000"]);
+    assert!(output.status.success());
+    assert_eq!(traced(&output), 0);
+}
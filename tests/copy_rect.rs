@@ -0,0 +1,110 @@
+// `copyrect` has to move a rectangle as if through a temporary buffer however it overlaps
+// itself, clip its source and destination to the screen separately, and dirty only what it wrote.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::video::Rect;
+
+const WIDTH: usize = 16;
+
+// A 16x16 screen where every pixel holds its own number plus one.
+fn numbered() -> MicroCvm {
+    let mut vm = MicroCvm::builder().resolution(WIDTH as u32, 16).build();
+    let video = &mut vm.cpu_mut().video_memory;
+    for pixel in 0..video.len() {
+        video.set_raw(pixel, pixel as u32 + 1);
+    }
+    vm
+}
+
+fn at(vm: &MicroCvm, x: usize, y: usize) -> u32 {
+    vm.cpu().video_memory.raw(y * WIDTH + x)
+}
+
+fn original(x: usize, y: usize) -> u32 {
+    (y * WIDTH + x) as u32 + 1
+}
+
+#[test]
+fn shifting_one_pixel_right() {
+    let mut vm = numbered();
+    vm.cpu_mut().copy_rect((2, 2), (3, 2), 4, 3);
+    for y in 2..5 {
+        // The first column stays, every other one holds what was just left of it.
+        assert_eq!(at(&vm, 2, y), original(2, y));
+        for x in 3..7 {
+            assert_eq!(at(&vm, x, y), original(x - 1, y), "{}, {}", x, y);
+        }
+        assert_eq!(at(&vm, 7, y), original(7, y));
+    }
+    assert_eq!(vm.cpu_mut().take_dirty(), Some(Rect::new(3, 2, 4, 3)));
+}
+
+#[test]
+fn shifting_one_pixel_down() {
+    let mut vm = numbered();
+    vm.cpu_mut().copy_rect((2, 2), (2, 3), 4, 3);
+    for x in 2..6 {
+        assert_eq!(at(&vm, x, 2), original(x, 2));
+        for y in 3..6 {
+            assert_eq!(at(&vm, x, y), original(x, y - 1), "{}, {}", x, y);
+        }
+        assert_eq!(at(&vm, x, 6), original(x, 6));
+    }
+    assert_eq!(vm.cpu_mut().take_dirty(), Some(Rect::new(2, 3, 4, 3)));
+}
+
+#[test]
+fn shifting_left_and_up() {
+    let mut vm = numbered();
+    vm.cpu_mut().copy_rect((3, 2), (2, 2), 4, 3);
+    for y in 2..5 {
+        for x in 2..6 {
+            assert_eq!(at(&vm, x, y), original(x + 1, y), "{}, {}", x, y);
+        }
+    }
+
+    let mut vm = numbered();
+    vm.cpu_mut().copy_rect((2, 3), (2, 2), 4, 3);
+    for y in 2..5 {
+        for x in 2..6 {
+            assert_eq!(at(&vm, x, y), original(x, y + 1), "{}, {}", x, y);
+        }
+    }
+}
+
+#[test]
+fn only_the_destination_is_dirtied() {
+    let mut vm = numbered();
+    // 3x2 from 1, 1 to 10, 12, well apart.
+    let program = assemble(
+        "
+        mov r0, 1
+        mov r1, 1
+        mov r2, 10
+        mov r3, 12
+        mov r4, 3
+        mov r5, 2
+        video copyrect, r0
+        hlt
+",
+    )
+    .unwrap();
+    vm.load_program(&program).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.cpu_mut().take_dirty(), Some(Rect::new(10, 12, 3, 2)));
+    assert_eq!(at(&vm, 12, 13), original(3, 2));
+    // The source is as it was.
+    assert_eq!(at(&vm, 3, 2), original(3, 2));
+
+    // Clipped at the destination, only what landed on screen is dirty.
+    vm.cpu_mut().copy_rect((0, 0), (14, 15), 4, 4);
+    assert_eq!(vm.cpu_mut().take_dirty(), Some(Rect::new(14, 15, 2, 1)));
+    // Clipped at the source, the destination shrinks with it.
+    vm.cpu_mut().copy_rect((13, 14), (0, 0), 5, 5);
+    assert_eq!(vm.cpu_mut().take_dirty(), Some(Rect::new(0, 0, 3, 2)));
+    // Nothing left after clipping dirties nothing.
+    vm.cpu_mut().copy_rect((16, 0), (0, 0), 4, 4);
+    vm.cpu_mut().copy_rect((0, 0), (3, 3), 0, 4);
+    assert_eq!(vm.cpu().dirty(), None);
+}
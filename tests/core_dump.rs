@@ -0,0 +1,122 @@
+// A fault has to leave a dump behind that reads back as the machine it stopped, and
+// `microcvm inspect` has to open it at the faulting instruction without letting anything run.
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::coredump::{CoreDump, DUMP_VERSION, DumpError};
+
+// inc r0; div r0, 0
+const DIVIDE_BY_ZERO: [u8; 5] = [0x07, 0x00, 0x08, 0x00, 0x00];
+
+fn dump_file(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("microcvm-core-dump-test-{}", name));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("crash.dump");
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn crash(path: &PathBuf) {
+    let mut vm = MicroCvm::builder()
+        .resolution(32, 16)
+        .core_dump_file(path)
+        .build();
+    vm.load_program(&DIVIDE_BY_ZERO).unwrap();
+    assert!(vm.run().is_err());
+}
+
+fn inspect(path: &PathBuf, script: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_microcvm-rs"))
+        .arg("inspect")
+        .arg(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(script.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn a_fault_writes_a_dump_of_the_machine() {
+    let path = dump_file("write");
+    crash(&path);
+    let dump = CoreDump::read(&path).unwrap();
+    assert_eq!(dump.pc, 0x0002);
+    assert_eq!((dump.width, dump.height), (32, 16));
+    assert_eq!(dump.snapshot.registers[0], 1);
+    assert!(dump.report.contains("Division by zero"), "{}", dump.report);
+
+    // It comes back the same through bytes, and the machine it makes is the one that stopped.
+    let again = CoreDump::from_bytes(&dump.to_bytes()).unwrap();
+    assert_eq!((again.pc, again.report), (dump.pc, dump.report.clone()));
+    let vm = MicroCvm::from_core_dump(&dump);
+    assert_eq!(vm.cpu().registers[0], 1);
+    assert_eq!(vm.cpu().memory()[..5], DIVIDE_BY_ZERO);
+}
+
+#[test]
+fn no_dump_without_a_fault() {
+    let path = dump_file("clean");
+    let mut vm = MicroCvm::builder().core_dump_file(&path).build();
+    vm.load_program(&assemble("inc r0\nhlt").unwrap()).unwrap();
+    vm.run().unwrap();
+    assert!(!path.exists());
+}
+
+#[test]
+fn inspect_opens_read_only_at_the_faulting_instruction() {
+    let path = dump_file("inspect");
+    crash(&path);
+    let transcript = inspect(&path, ":regs\n:run\ninc r0\n:quit\n");
+    let (report, session) = transcript
+        .split_once("MicroCVM core dump, read-only, :help for commands\n")
+        .unwrap();
+    assert!(report.contains("Division by zero"), "{}", report);
+    assert!(session.starts_with("0x0002> r0=0x01 "), "{}", session);
+    assert!(session.contains("\npc=0x0002 "), "{}", session);
+    let refused = "read-only: a core dump can be looked at but not run or changed\n0x0002> ";
+    assert_eq!(session.matches(refused).count(), 2, "{}", session);
+}
+
+#[test]
+fn files_that_are_not_dumps_are_refused() {
+    let path = dump_file("valid");
+    crash(&path);
+    let mut bytes = std::fs::read(&path).unwrap();
+    bytes[4] = DUMP_VERSION + 1;
+    assert!(matches!(
+        CoreDump::from_bytes(&bytes),
+        Err(DumpError::UnsupportedVersion(version)) if version == DUMP_VERSION + 1
+    ));
+    bytes[0] = b'X';
+    assert_eq!(
+        CoreDump::from_bytes(&bytes).err(),
+        Some(DumpError::BadMagic)
+    );
+
+    std::fs::write(&path, b"MCVD").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_microcvm-rs"))
+        .arg("inspect")
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.starts_with("error: could not read core dump"),
+        "{}",
+        stderr
+    );
+}
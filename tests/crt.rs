@@ -0,0 +1,116 @@
+// The CRT effect has to dim output lines rather than VM rows, so every scale keeps its bright
+// lines and a dim one per row, smear only within a line, and leave video memory alone.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::crt::{CrtEffect, scale_frame};
+
+// One pixel wide, two rows: an orange one and a green one.
+const TOP: [u8; 4] = [200, 100, 0, 255];
+const BOTTOM: [u8; 4] = [100, 200, 40, 255];
+const SOURCE: [u8; 8] = [200, 100, 0, 255, 100, 200, 40, 255];
+
+const HALF: CrtEffect = CrtEffect {
+    scanline_dim: 50,
+    smear: false,
+};
+
+fn dim(pixel: [u8; 4], percent: u16) -> [u8; 4] {
+    let keep = |channel: u8| ((channel as u16 * (100 - percent) + 50) / 100) as u8;
+    [keep(pixel[0]), keep(pixel[1]), keep(pixel[2]), 255]
+}
+
+// The first pixel of each output line of the 1x2 source at `scale`, checking the rest of the
+// line matches it.
+fn lines(scale: usize, crt: Option<CrtEffect>) -> Vec<[u8; 4]> {
+    let mut out = vec![0; scale * scale * 2 * 4];
+    scale_frame(&SOURCE, 1, scale, crt, &mut out);
+    out.chunks(scale * 4)
+        .map(|line| {
+            assert!(
+                line.chunks(4).all(|pixel| pixel == &line[..4]),
+                "{:?}",
+                line
+            );
+            line[..4].try_into().unwrap()
+        })
+        .collect()
+}
+
+#[test]
+fn dim_lines_are_output_lines_at_every_scale() {
+    let (top_dim, bottom_dim) = (dim(TOP, 50), dim(BOTTOM, 50));
+    assert_eq!(lines(1, Some(HALF)), [TOP, bottom_dim]);
+    assert_eq!(lines(2, Some(HALF)), [TOP, top_dim, BOTTOM, bottom_dim]);
+    assert_eq!(
+        lines(3, Some(HALF)),
+        [TOP, TOP, top_dim, BOTTOM, BOTTOM, bottom_dim]
+    );
+    for scale in 1..=3 {
+        let plain: Vec<[u8; 4]> = [TOP, BOTTOM]
+            .iter()
+            .flat_map(|&row| std::iter::repeat_n(row, scale))
+            .collect();
+        assert_eq!(lines(scale, None), plain, "{}", scale);
+        // Dimming by nothing is no effect at all, and by more than 100 is black.
+        let none = CrtEffect {
+            scanline_dim: 0,
+            smear: false,
+        };
+        assert_eq!(lines(scale, Some(none)), plain, "{}", scale);
+        let all = CrtEffect {
+            scanline_dim: 250,
+            smear: false,
+        };
+        let last = *lines(scale, Some(all)).last().unwrap();
+        assert_eq!(last, [0, 0, 0, 255], "{}", scale);
+    }
+}
+
+#[test]
+fn smearing_blends_with_the_left_neighbour_within_a_line() {
+    // Black then gray, two rows, at 2x.
+    let source = [
+        0, 0, 0, 255, 200, 200, 200, 255, 200, 200, 200, 255, 0, 0, 0, 255,
+    ];
+    let smear = CrtEffect {
+        scanline_dim: 0,
+        smear: true,
+    };
+    let mut out = vec![0; 4 * 4 * 4];
+    scale_frame(&source, 2, 2, Some(smear), &mut out);
+    let pixels: Vec<u8> = out.chunks(4).map(|pixel| pixel[0]).collect();
+    assert_eq!(
+        pixels,
+        [
+            0, 0, 150, 200, // the first gray pixel takes a quarter of the black
+            0, 0, 150, 200, //
+            200, 200, 50, 0, // and the first black one a quarter of the gray
+            200, 200, 50, 0,
+        ]
+    );
+    assert!(out.chunks(4).all(|pixel| pixel[3] == 255));
+}
+
+#[test]
+fn the_machine_draws_the_effect_it_is_given() {
+    let mut vm = MicroCvm::builder().resolution(1, 2).build();
+    vm.cpu_mut()
+        .video_memory
+        .bytes_mut()
+        .copy_from_slice(&[200, 100, 0, 100, 200, 40]);
+    assert_eq!(vm.render_frame_to_rgba(), SOURCE);
+
+    vm.set_crt(Some(HALF));
+    assert_eq!(vm.crt(), Some(HALF));
+    let frame = vm.render_frame_to_rgba();
+    assert_eq!(frame[..4], TOP);
+    assert_eq!(frame[4..], dim(BOTTOM, 50));
+    let mut scaled = vec![0; 3 * 3 * 2 * 4];
+    vm.frame_rgba_scaled(3, vm.crt(), &mut scaled);
+    assert_eq!(scaled[3 * 4 * 2..3 * 4 * 2 + 4], dim(TOP, 50));
+    // Video memory never sees it.
+    assert_eq!(vm.framebuffer().bytes(), [200, 100, 0, 100, 200, 40]);
+
+    vm.set_crt(None);
+    assert_eq!(vm.render_frame_to_rgba(), SOURCE);
+}
@@ -0,0 +1,166 @@
+// The debugger window has to show the registers, the instructions around pc with pc and the
+// breakpoints marked, and a hexdump that pages through memory, and paint it all offscreen
+// with the line at pc on its highlight bar.
+
+use microcvm_rs::asm::assemble;
+use microcvm_rs::debugger::{
+    DEBUGGER_HEIGHT, DEBUGGER_WIDTH, DISASSEMBLY_AFTER, DISASSEMBLY_BEFORE, Debugger,
+    HEXDUMP_ROW_BYTES, HEXDUMP_ROWS, LineStyle, paint, register_lines,
+};
+use microcvm_rs::{HaltReason, MicroCvm};
+
+// Where paint puts the lines and the colors of their bars.
+const PADDING: usize = 4;
+const LINE_HEIGHT: usize = 10;
+const BACKGROUND: [u8; 4] = [16, 16, 24, 255];
+const CURRENT: [u8; 4] = [40, 60, 140, 255];
+const SELECTED: [u8; 4] = [64, 64, 64, 255];
+
+const PROGRAM: &str = "
+        mov r0, 1
+        mov r1, 2
+        inc r0
+        inc r1
+loop:   inc r2
+        jmp loop
+        .org 0x0100
+        .db \"Hi!\"
+";
+
+fn stopped_at(addr: u16) -> MicroCvm {
+    let mut vm = MicroCvm::builder().build();
+    vm.load_program(&assemble(PROGRAM).unwrap()).unwrap();
+    vm.add_breakpoint(addr);
+    assert_eq!(vm.run_for(1000).unwrap(), HaltReason::Breakpoint);
+    vm
+}
+
+fn texts(lines: &[microcvm_rs::debugger::DebuggerLine]) -> Vec<&str> {
+    lines.iter().map(|line| line.text.as_str()).collect()
+}
+
+#[test]
+fn the_disassembly_marks_pc_and_breakpoints() {
+    let mut vm = stopped_at(0x0008);
+    vm.add_breakpoint(0x000A);
+    let lines = Debugger::new().disassembly_lines(&vm);
+    // Only three instructions come before pc.
+    assert_eq!(lines.len(), 3 + DISASSEMBLY_AFTER);
+    assert_eq!(
+        texts(&lines)[..6],
+        [
+            "  0x0000: mov r0, 1",
+            "  0x0003: mov r1, 2",
+            "  0x0006: inc r0",
+            ">*0x0008: inc r1",
+            " *0x000a: inc r2",
+            "  0x000c: jmp 0x000a",
+        ]
+    );
+    let styles: Vec<_> = lines.iter().map(|line| line.style).collect();
+    assert_eq!(styles[3], LineStyle::Current);
+    assert!(
+        styles
+            .iter()
+            .filter(|&&style| style != LineStyle::Normal)
+            .count()
+            == 1
+    );
+
+    // Further in, only DISASSEMBLY_BEFORE lines come before pc.
+    let mut vm = stopped_at(0x000C);
+    vm.remove_breakpoint(0x000C);
+    // Round the loop once, back to the jmp.
+    vm.step().unwrap();
+    vm.step().unwrap();
+    assert_eq!(vm.cpu().pc, 0x000C);
+    let lines = Debugger::new().disassembly_lines(&vm);
+    assert_eq!(lines[DISASSEMBLY_BEFORE].style, LineStyle::Current);
+    assert_eq!(lines[0].text, "  0x0003: mov r1, 2");
+}
+
+#[test]
+fn the_cursor_selects_a_line_and_stays_on_screen() {
+    let vm = stopped_at(0x0008);
+    let mut view = Debugger::new();
+    assert_eq!(view.selected(vm.cpu()), 0x0008);
+    view.move_cursor(vm.cpu(), 2);
+    assert_eq!(view.selected(vm.cpu()), 0x000C);
+    let lines = view.disassembly_lines(&vm);
+    assert_eq!(lines[5].style, LineStyle::Selected);
+    view.move_cursor(vm.cpu(), -100);
+    assert_eq!(view.selected(vm.cpu()), 0x0000);
+    view.move_cursor(vm.cpu(), 100);
+    let last = view.disassembly_lines(&vm).len() - 1;
+    assert_eq!(view.disassembly_lines(&vm)[last].style, LineStyle::Selected);
+}
+
+#[test]
+fn the_hexdump_pages_through_memory() {
+    let vm = stopped_at(0x0008);
+    let mut view = Debugger::new();
+    view.memory_start = 0x0100;
+    let lines = view.hexdump_lines(vm.cpu(), 2);
+    assert_eq!(
+        texts(&lines),
+        [
+            "0100: 48 69 21 00 00 00 00 00 Hi!.....",
+            "0108: 00 00 00 00 00 00 00 00 ........"
+        ]
+    );
+    view.scroll_memory(-32);
+    assert_eq!(view.memory_start, 0x0100 - 32 * HEXDUMP_ROW_BYTES as u16);
+    // Paging wraps around the address space.
+    let mut view = Debugger::new();
+    view.scroll_memory(-1);
+    assert!(
+        view.hexdump_lines(vm.cpu(), 1)[0]
+            .text
+            .starts_with("FFF8: ")
+    );
+
+    // Registers, disassembly, hexdump and help, with gaps between.
+    let lines = Debugger::new().lines(&vm);
+    assert_eq!(texts(&lines)[..3], texts(&register_lines(&vm))[..]);
+    assert_eq!(
+        lines.len(),
+        3 + 1 + 3 + DISASSEMBLY_AFTER + 1 + HEXDUMP_ROWS + 1 + 2
+    );
+}
+
+#[test]
+fn painting_puts_the_line_at_pc_on_its_bar() {
+    let vm = stopped_at(0x0008);
+    let mut view = Debugger::new();
+    view.move_cursor(vm.cpu(), 1);
+    let lines = view.lines(&vm);
+    let current = lines
+        .iter()
+        .position(|line| line.style == LineStyle::Current)
+        .unwrap();
+    let selected = lines
+        .iter()
+        .position(|line| line.style == LineStyle::Selected)
+        .unwrap();
+    assert_eq!(selected, current + 1);
+
+    let width = DEBUGGER_WIDTH as usize;
+    let mut frame = vec![0; width * DEBUGGER_HEIGHT as usize * 4];
+    paint(&lines, width, &mut frame);
+    let pixel =
+        |x: usize, y: usize| <[u8; 4]>::try_from(&frame[(y * width + x) * 4..][..4]).unwrap();
+    for (line, bar) in [(current, CURRENT), (selected, SELECTED)] {
+        let top = PADDING + line * LINE_HEIGHT;
+        for y in top..top + LINE_HEIGHT {
+            assert_eq!(pixel(width - 1, y), bar, "{}", y);
+        }
+        // Text on the bar, in white.
+        let row: Vec<_> = (0..width).map(|x| pixel(x, top + 2)).collect();
+        assert!(row.contains(&[255, 255, 255, 255]));
+    }
+    assert_eq!(pixel(width - 1, 0), BACKGROUND);
+    assert_eq!(pixel(width - 1, PADDING), BACKGROUND);
+    // A short surface clips what doesn't fit.
+    let mut short = vec![0; width * 20 * 4];
+    paint(&lines, width, &mut short);
+}
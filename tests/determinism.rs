@@ -0,0 +1,164 @@
+// Two runs of the same program on the same inputs have to match, and when they don't, the check
+// has to name the instruction after which they went apart, and what differed there, whichever
+// interval it checks at.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::demo::bounce_program;
+use microcvm_rs::determinism::{
+    DETERMINISTIC_TIME, Divergence, Input, ScriptedInput, deterministic_builder,
+    verify_deterministic, verify_deterministic_with,
+};
+use microcvm_rs::gamepad::{BUTTON_A, GamepadState};
+use microcvm_rs::mouse::MOUSE_LEFT;
+
+// Waits a while, then reads the seconds and the year from the real-time clock.
+const CLOCK_READER: &str = "
+        mov r2, 200
+spin:   djnz r2, spin
+        store [0xFF70], r0
+        load r1, [0xFF71]
+        load r3, [0xFF76]
+        hlt
+";
+
+// Checks `steps` instructions of CLOCK_READER on machines whose clock reads `time(boot)`, the
+// first machine built being boot 1. The machines are small, since every check snapshots them.
+fn clock_divergence(time: fn(u64) -> u64, steps: u64, interval: u64) -> Result<(), Divergence> {
+    let program = assemble(CLOCK_READER).unwrap();
+    let mut boots = 0;
+    let build = || {
+        boots += 1;
+        let time = time(boots);
+        let mut vm = MicroCvm::builder()
+            .memory_size(0x1_0000)
+            .resolution(16, 16)
+            .clock(Box::new(move || time))
+            .build();
+        vm.load_program(&program).unwrap();
+        vm
+    };
+    verify_deterministic_with(build, &[], steps, interval)
+}
+
+#[test]
+fn the_same_inputs_give_the_same_run() {
+    let press = GamepadState {
+        buttons: BUTTON_A,
+        ..GamepadState::default()
+    };
+    let inputs = [
+        ScriptedInput::new(0, Input::MousePosition { x: 3, y: 4 }),
+        ScriptedInput::new(
+            2,
+            Input::Key {
+                scancode: 0x1E,
+                pressed: true,
+            },
+        ),
+        ScriptedInput::new(3, Input::Gamepad(press)),
+        ScriptedInput::new(
+            4,
+            Input::MouseButton {
+                mask: MOUSE_LEFT,
+                pressed: true,
+            },
+        ),
+        ScriptedInput::new(
+            5,
+            Input::Key {
+                scancode: 0x1E,
+                pressed: false,
+            },
+        ),
+    ];
+    assert_eq!(
+        verify_deterministic(&bounce_program(), &inputs, 20_000),
+        Ok(())
+    );
+    assert_eq!(verify_deterministic(&bounce_program(), &[], 0), Ok(()));
+    // A program that doesn't load is the same empty machine both times.
+    assert_eq!(verify_deterministic(b"MCVM\x63", &inputs, 1_000), Ok(()));
+}
+
+#[test]
+fn the_clock_is_pinned_in_deterministic_runs() {
+    let program = assemble(CLOCK_READER).unwrap();
+    assert_eq!(verify_deterministic(&program, &[], 10_000), Ok(()));
+
+    let mut vm = deterministic_builder().build();
+    vm.load_program(&program).unwrap();
+    vm.run().unwrap();
+    // 2024-01-01 00:00:00.
+    assert_eq!(DETERMINISTIC_TIME % 60, 0);
+    assert_eq!(vm.cpu().registers[1], 0);
+    assert_eq!(vm.cpu().registers[3] & 0xFF, 2024 & 0xFF);
+}
+
+#[test]
+fn a_divergence_names_the_instruction_and_the_difference() {
+    // A second apart on every boot, so the replay differs as well.
+    let later = |boot| DETERMINISTIC_TIME + boot;
+    for interval in [1, 7, 64, 203, 204, 1024, 1_000_000] {
+        let divergence = clock_divergence(later, 10_000, interval).unwrap_err();
+        // mov, 200 times djnz, store, then the load sees a different second.
+        assert_eq!(divergence.instruction, 203, "every {}", interval);
+        assert!(divergence.exact);
+        // Found by replaying on the third and fourth machines.
+        assert_eq!(divergence.diff.registers, [(1, 3, 4)], "every {}", interval);
+        assert_eq!(divergence.diff.memory, []);
+    }
+    let divergence = clock_divergence(later, 10_000, 64).unwrap_err();
+    assert_eq!(
+        divergence.to_string(),
+        "the runs went apart after 203 instructions:\nr1: 0x03 -> 0x04"
+    );
+}
+
+#[test]
+fn runs_that_start_apart_diverge_at_zero() {
+    let program = assemble("inc r0\nhlt").unwrap();
+    let mut boots = 0u8;
+    let build = || {
+        boots += 1;
+        let mut vm = deterministic_builder().build();
+        vm.load_program(&program).unwrap();
+        vm.cpu_mut().memory_mut()[0x0400] = boots;
+        vm
+    };
+    let divergence = verify_deterministic_with(build, &[], 100, 16).unwrap_err();
+    assert_eq!(divergence.instruction, 0);
+    assert!(divergence.exact);
+    assert_eq!(divergence.diff.memory.len(), 1);
+    let change = &divergence.diff.memory[0];
+    assert_eq!(
+        (change.start, &change.old[..], &change.new[..]),
+        (0x0400, &[3][..], &[4][..])
+    );
+}
+
+#[test]
+fn a_divergence_the_replay_does_not_repeat_is_the_check_that_saw_it() {
+    // Only the second machine is off, so the replay on the third and fourth agrees throughout.
+    let second_off = |boot| DETERMINISTIC_TIME + u64::from(boot == 2);
+    let divergence = clock_divergence(second_off, 10_000, 64).unwrap_err();
+    assert!(!divergence.exact);
+    assert_eq!(divergence.instruction, 256);
+    assert_eq!(divergence.diff.registers, [(1, 0, 1)]);
+    assert!(
+        divergence
+            .to_string()
+            .starts_with("the runs had gone apart by 256 instructions:\n"),
+        "{}",
+        divergence
+    );
+}
+
+#[test]
+fn nothing_past_the_steps_is_compared() {
+    let later = |boot| DETERMINISTIC_TIME + boot;
+    for (steps, diverges) in [(100, false), (202, false), (203, true)] {
+        let result = clock_divergence(later, steps, 64);
+        assert_eq!(result.is_err(), diverges, "{} steps", steps);
+    }
+}
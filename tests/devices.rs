@@ -0,0 +1,53 @@
+// Host devices have to map next to the built-in ones without displacing them, so the keyboard,
+// tiles and the rest stay reachable from the host and the guest whatever else gets mapped.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::bus::{BusError, Device};
+use microcvm_rs::cpu::{KEYBOARD_BASE, TILE_BASE};
+use microcvm_rs::keyboard::Keyboard;
+use microcvm_rs::tilemap::TileLayer;
+
+struct Latch(u8);
+
+impl Device for Latch {
+    fn read(&mut self, _offset: u16) -> u8 {
+        self.0
+    }
+
+    fn write(&mut self, _offset: u16, value: u8) {
+        self.0 = value;
+    }
+}
+
+#[test]
+fn built_in_devices_cannot_be_displaced() {
+    let mut vm = MicroCvm::builder().resolution(8, 8).build();
+    let cpu = vm.cpu_mut();
+    for start in [KEYBOARD_BASE, TILE_BASE] {
+        assert_eq!(
+            cpu.map_device(start, 1, Box::new(Latch(0))),
+            Err(BusError::Overlap {
+                start,
+                existing: start
+            })
+        );
+    }
+    cpu.map_device(0x3000, 1, Box::new(Latch(5))).unwrap();
+    assert_eq!(cpu.device::<Latch>().unwrap().0, 5);
+    assert!(cpu.device::<Keyboard>().is_some());
+    assert!(cpu.device_mut::<TileLayer>().is_some());
+
+    // Everything that goes through the built-in devices still works.
+    vm.push_key(0x1C, true);
+    vm.cpu_mut().tiles_mut().scroll_x = 3;
+    vm.frame_rgba(&mut [0; 8 * 8 * 4]);
+    let source = format!(
+        "load r0, [{:#x}]\nmov r1, 9\nstore [0x3000], r1\nload r2, [0x3000]\nhlt",
+        KEYBOARD_BASE + 1
+    );
+    vm.load_program(&assemble(&source).unwrap()).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.cpu().registers[..3], [0x1C, 9, 9]);
+    assert_eq!(vm.cpu().device::<Latch>().unwrap().0, 9);
+}
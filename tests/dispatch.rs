@@ -0,0 +1,121 @@
+// The fast path, with and without the decode cache, has to end every program exactly where
+// the traced path does: the self-test ROM with both register widths and everything in
+// tests/programs, compared on how the run ended, the registers, flags and memory.
+
+use std::path::{Path, PathBuf};
+
+use microcvm_rs::asm::assemble;
+use microcvm_rs::cpu::{MicroCVMCpu, RegisterWidth};
+use microcvm_rs::crc32::crc32;
+use microcvm_rs::demo::{DEMO_HEIGHT, DEMO_WIDTH, self_test_vm};
+use microcvm_rs::determinism::deterministic_builder;
+use microcvm_rs::error::VmError;
+use microcvm_rs::trace::{TraceEntry, TraceSink};
+use microcvm_rs::vm::CYCLES_PER_FRAME;
+use microcvm_rs::{HaltReason, MicroCvm};
+
+const MAX_INSTRUCTIONS: u64 = 10_000_000;
+
+struct Discard;
+
+impl TraceSink for Discard {
+    fn trace(&mut self, _entry: &TraceEntry) {}
+}
+
+const PATHS: [&str; 3] = ["fast", "uncached", "traced"];
+
+fn take(path: &str, cpu: &mut MicroCVMCpu) {
+    match path {
+        "fast" => cpu.set_decode_cache(true),
+        "uncached" => cpu.set_decode_cache(false),
+        _ => cpu.set_trace_sink(Some(Box::new(Discard))),
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Outcome {
+    halt: String,
+    registers: [u16; 8],
+    pc: u16,
+    sp: u16,
+    flags: u8,
+    cycles: u64,
+    memory: u32,
+    framebuffer: u32,
+}
+
+fn outcome(vm: &mut MicroCvm, result: Result<HaltReason, VmError>) -> Outcome {
+    let framebuffer = crc32(&vm.render_frame_to_rgba());
+    let cpu = vm.cpu();
+    Outcome {
+        halt: match result {
+            Ok(reason) => reason.to_string(),
+            Err(e) => format!("fault: {}", e.cause()),
+        },
+        registers: cpu.registers,
+        pc: cpu.pc,
+        sp: cpu.sp,
+        flags: cpu.flags,
+        cycles: cpu.cycles,
+        memory: crc32(cpu.memory()),
+        framebuffer,
+    }
+}
+
+// Every path has to agree with the first.
+fn agree(name: &str, mut run: impl FnMut(&str) -> Outcome) {
+    let expected = run(PATHS[0]);
+    for path in &PATHS[1..] {
+        assert_eq!(run(path), expected, "{} on the {} path", name, path);
+    }
+}
+
+#[test]
+fn the_self_test_runs_the_same_on_every_path() {
+    for width in [RegisterWidth::Eight, RegisterWidth::Sixteen] {
+        agree(&format!("self-test {:?}", width), |path| {
+            let mut vm = self_test_vm(width);
+            take(path, vm.cpu_mut());
+            let result = vm.run();
+            outcome(&mut vm, result)
+        });
+    }
+}
+
+#[test]
+fn test_programs_run_the_same_on_every_path() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs");
+    let mut sources: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "asm"))
+        .collect();
+    sources.sort();
+    assert!(!sources.is_empty());
+
+    for source in &sources {
+        let program = assemble(&std::fs::read_to_string(source).unwrap()).unwrap();
+        agree(&source.display().to_string(), |path| {
+            // Serial output goes nowhere, it only has to be accepted.
+            let mut vm = deterministic_builder()
+                .resolution(DEMO_WIDTH, DEMO_HEIGHT)
+                .max_instructions(MAX_INSTRUCTIONS)
+                .hcall(1, Box::new(|_| Ok(())))
+                .build();
+            take(path, vm.cpu_mut());
+            let result = load_and_run(&mut vm, &program);
+            outcome(&mut vm, result)
+        });
+    }
+}
+
+// Frames end the way they do headless, as in tests/programs.rs.
+fn load_and_run(vm: &mut MicroCvm, program: &[u8]) -> Result<HaltReason, VmError> {
+    vm.load_program(program)?;
+    loop {
+        match vm.run_frame(CYCLES_PER_FRAME)? {
+            HaltReason::FrameComplete => vm.tick_frame(),
+            reason => return Ok(reason),
+        }
+    }
+}
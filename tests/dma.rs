@@ -0,0 +1,167 @@
+// A DMA transfer has to copy packed RGB into video memory as the framebuffer window would,
+// clip a length that runs past either buffer without panicking and say it did, and charge a
+// cycle for each pixel it actually copied.
+
+use microcvm_rs::asm::assemble;
+use microcvm_rs::bench::COPY_BENCH_PIXELS;
+use microcvm_rs::cpu::{DMA_BASE, MicroCVMCpu};
+use microcvm_rs::dma::{
+    DMA_CTRL, DMA_CYCLES_PER_PIXEL, DMA_DST, DMA_LEN, DMA_SRC, DMA_STATUS_CLIPPED,
+};
+
+const MEMORY: usize = 0x1_0000;
+const PIXELS: usize = 16;
+
+fn machine() -> MicroCVMCpu {
+    let mut cpu = MicroCVMCpu::with_memory(MEMORY, PIXELS);
+    let rgb: Vec<u8> = (0..MEMORY).map(|i| (i * 7 % 251) as u8).collect();
+    cpu.memory_mut().copy_from_slice(&rgb);
+    cpu
+}
+
+fn run(cpu: &mut MicroCVMCpu, source: &str) {
+    let program = assemble(source).unwrap();
+    cpu.memory_mut()[..program.len()].copy_from_slice(&program);
+    (cpu.pc, cpu.halted) = (0, false);
+    while !cpu.halted {
+        cpu.execute_instruction().unwrap();
+    }
+}
+
+// Starts a transfer from the guest and returns the status it reads back and the cycles the
+// run took.
+fn transfer(cpu: &mut MicroCVMCpu, src: u32, dst: u32, len: u32) -> (u16, u64) {
+    let mut source = String::new();
+    for (register, value) in [(DMA_SRC, src), (DMA_DST, dst), (DMA_LEN, len)] {
+        for byte in 0..3 {
+            let address = DMA_BASE + (register + byte) as u16;
+            source += &format!(
+                "mov r0, {}\nstore [{:#x}], r0\n",
+                (value >> (byte * 8)) as u8,
+                address
+            );
+        }
+    }
+    let control = DMA_BASE + DMA_CTRL as u16;
+    source += &format!(
+        "mov r0, 1\nstore [{:#x}], r0\nload r7, [{:#x}]\nhlt",
+        control, control
+    );
+    let cycles = cpu.cycles;
+    run(cpu, &source);
+    (cpu.registers[7], cpu.cycles - cycles)
+}
+
+// What a transfer of nothing costs, the instructions setting it up.
+fn setup_cycles() -> u64 {
+    transfer(&mut machine(), 0, 0, 0).1
+}
+
+fn rgb(cpu: &MicroCVMCpu, pixel: usize) -> [u8; 3] {
+    let color = cpu.video_memory.pixel(pixel);
+    [color.r, color.g, color.b]
+}
+
+#[test]
+fn a_transfer_copies_packed_rgb() {
+    let mut cpu = machine();
+    let (status, cycles) = transfer(&mut cpu, 0x2000, 3, 4);
+    assert_eq!(status, 0);
+    assert_eq!(cycles - setup_cycles(), 4 * DMA_CYCLES_PER_PIXEL);
+    for pixel in 0..PIXELS {
+        let expected = match pixel {
+            3..7 => {
+                let at = 0x2000 + (pixel - 3) * 3;
+                cpu.memory()[at..at + 3].try_into().unwrap()
+            }
+            _ => [0; 3],
+        };
+        assert_eq!(rgb(&cpu, pixel), expected, "pixel {}", pixel);
+    }
+}
+
+#[test]
+fn overruns_are_clipped_and_charged_for_what_was_copied() {
+    let setup = setup_cycles();
+    for (src, dst, len, copied) in [
+        // Past the end of video memory.
+        (0x2000, 14, 4, 2),
+        (0x2000, 0, 0xFF_FFFF, PIXELS),
+        (0x2000, PIXELS as u32, 1, 0),
+        (0x2000, 0xFF_FFFF, 0xFF_FFFF, 0),
+        // Past the end of memory, with a pixel cut short at the very end.
+        (MEMORY as u32 - 6, 0, 4, 2),
+        (MEMORY as u32 - 7, 0, 4, 2),
+        (MEMORY as u32 - 2, 0, 1, 0),
+        (0xFF_FFFF, 0, 4, 0),
+        // Both at once.
+        (MEMORY as u32 - 9, 13, 0xFF_FFFF, 3),
+    ] {
+        let mut cpu = machine();
+        let case = format!("{:#x} to {} for {}", src, dst, len);
+        let (status, cycles) = transfer(&mut cpu, src, dst, len);
+        assert_eq!(status, DMA_STATUS_CLIPPED as u16, "{}", case);
+        assert_eq!(
+            cycles - setup,
+            copied as u64 * DMA_CYCLES_PER_PIXEL,
+            "{}",
+            case
+        );
+        let written = (0..PIXELS).filter(|&pixel| rgb(&cpu, pixel) != [0; 3]);
+        assert!(written.count() <= copied, "{}", case);
+    }
+
+    // A transfer that fits clears the flag again.
+    let mut cpu = machine();
+    assert_eq!(
+        transfer(&mut cpu, 0x2000, 14, 4).0,
+        DMA_STATUS_CLIPPED as u16
+    );
+    assert_eq!(transfer(&mut cpu, 0x2000, 0, 0), (0, setup));
+}
+
+#[test]
+fn dma_matches_a_store_loop_through_the_window() {
+    let mut looped = machine();
+    looped.map_framebuffer(0x8000, PIXELS as u16 * 3).unwrap();
+    run(
+        &mut looped,
+        "
+        mov r0, 0x20
+        mov r1, 0x00
+        mov r2, 0x80
+        mov r3, 0x00
+        mov r4, 48
+copy:   load r6, [r0+]
+        store [r2+], r6
+        djnz r4, copy
+        hlt
+        ",
+    );
+    let mut dma = machine();
+    assert_eq!(transfer(&mut dma, 0x2000, 0, PIXELS as u32).0, 0);
+    assert_eq!(dma.video_memory.bytes(), looped.video_memory.bytes());
+    // A cycle a pixel against three instructions a byte.
+    assert_eq!(
+        dma.cycles - setup_cycles(),
+        PIXELS as u64 * DMA_CYCLES_PER_PIXEL
+    );
+    assert!(looped.cycles > PIXELS as u64 * 3 * 3);
+}
+
+#[test]
+fn the_frame_copy_benchmark_counts_both_ways() {
+    for decode_cache in [true, false] {
+        let [store_loop, dma] = MicroCVMCpu::bench_frame_copy(3, decode_cache);
+        assert_eq!((store_loop.name, dma.name), ("store loop", "dma"));
+        // Setup, three instructions per two bytes, and the hlt.
+        let loop_instructions = 3 + COPY_BENCH_PIXELS as u64 * 3 / 2 * 3 + 1;
+        assert_eq!(store_loop.instructions, 3 * loop_instructions);
+        assert_eq!(dma.instructions, 3 * 7);
+        assert_eq!(
+            dma.cycles,
+            dma.instructions + 3 * COPY_BENCH_PIXELS as u64 * DMA_CYCLES_PER_PIXEL
+        );
+        assert!(dma.to_string().starts_with("dma        "), "{}", dma);
+    }
+}
@@ -0,0 +1,133 @@
+// A program has to be able to put its data first and start wherever its code is: at `.entry`,
+// at its first instruction without one, or where the header or `set_entry` says, and running
+// from an address nothing was loaded at has to fail before anything executes.
+
+use std::process::Command;
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::{assemble, assemble_segments};
+use microcvm_rs::error::VmError;
+use microcvm_rs::program::Program;
+
+// Tables first, with no jump over them.
+const TABLES_FIRST: &str = "
+squares: .db 0, 1, 4, 9, 16, 25, 36, 49
+message: .db \"hello\", 0
+        .org 0x0200
+main:   load r0, [squares+5]
+        load r1, [message+1]
+        hlt
+";
+
+fn run_file(file: &[u8]) -> MicroCvm {
+    let mut vm = MicroCvm::builder().build();
+    vm.load_program(file).unwrap();
+    vm.run().unwrap();
+    vm
+}
+
+#[test]
+fn data_tables_first_and_main_at_0x0200() {
+    let program = assemble_segments(TABLES_FIRST).unwrap();
+    assert_eq!(program.entry, 0x0200);
+    let mut vm = MicroCvm::builder().build();
+    vm.load_program(&program.build()).unwrap();
+    assert_eq!(vm.cpu().pc, 0x0200);
+    vm.run().unwrap();
+    assert_eq!(vm.cpu().registers[..2], [25, b'e' as u16]);
+}
+
+#[test]
+fn the_entry_directive_wins_over_the_first_instruction() {
+    let source = "
+        .entry main
+helper: mov r1, 5
+        ret
+main:   call helper
+        mov r0, r1
+        hlt
+";
+    let program = assemble_segments(source).unwrap();
+    assert_eq!(program.entry, 0x0004);
+    let vm = run_file(&program.build());
+    assert_eq!(vm.cpu().registers[..2], [5, 5]);
+
+    // Data sections come before the first instruction however they're laid out.
+    let source = "
+        .section data
+table:  .db 3, 2, 1
+        .section code
+start:  load r0, [table]
+        hlt
+";
+    assert_eq!(assemble_segments(source).unwrap().entry, 0x0003);
+    // A flat image always starts at 0, so it has nowhere to put one.
+    assert!(assemble(".entry 0x10\nhlt").is_err());
+}
+
+#[test]
+fn the_header_entry_is_where_loading_leaves_pc() {
+    let payload = assemble("mov r0, 1\nhlt\nmov r0, 2\nhlt").unwrap();
+    let vm = run_file(&Program::new(0x0100, payload.clone()).build());
+    assert_eq!(vm.cpu().registers[0], 1);
+    let vm = run_file(&Program::new(0x0100, payload.clone()).entry(0x0104).build());
+    assert_eq!(vm.cpu().registers[0], 2);
+
+    // Before the load address there is nothing to run.
+    let mut vm = MicroCvm::builder().build();
+    vm.load_program(&Program::new(0x0100, payload).entry(0x00FF).build())
+        .unwrap();
+    for _ in 0..2 {
+        let error = vm.run().unwrap_err();
+        assert!(
+            matches!(error, VmError::EntryOutsideProgram { entry: 0x00FF }),
+            "{}",
+            error
+        );
+        assert_eq!(vm.cpu().cycles, 0);
+    }
+}
+
+#[test]
+fn set_entry_starts_a_raw_image_anywhere_it_loaded() {
+    let image = assemble(TABLES_FIRST).unwrap();
+    let mut vm = MicroCvm::builder().build();
+    vm.load_program(&image).unwrap();
+    vm.set_entry(0x0200);
+    vm.run().unwrap();
+    assert_eq!(vm.cpu().registers[0], 25);
+
+    let mut vm = MicroCvm::builder().build();
+    vm.load_program(&image).unwrap();
+    vm.set_entry(image.len() as u16);
+    let error = vm.run().unwrap_err();
+    assert!(
+        matches!(error, VmError::EntryOutsideProgram { entry } if entry as usize == image.len())
+    );
+    assert_eq!(vm.cpu().registers[0], 0);
+
+    // Loading the bytes it points at afterwards makes it good.
+    vm.load_from_reader(&[0x07, 0x00, 0xFF][..], image.len())
+        .unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.cpu().registers[0], 1);
+}
+
+#[test]
+fn the_binary_takes_an_entry_for_raw_images() {
+    let dir = std::env::temp_dir().join("microcvm-entry-test");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("tables.bin");
+    std::fs::write(&path, assemble(TABLES_FIRST).unwrap()).unwrap();
+    let run = |entry: &str| {
+        Command::new(env!("CARGO_BIN_EXE_microcvm-rs"))
+            .arg("run")
+            .arg(&path)
+            .args(["--headless", "--entry", entry])
+            .output()
+            .unwrap()
+    };
+    assert_eq!(run("0x0200").status.code(), Some(25));
+    let output = run("0x0300");
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+}
@@ -0,0 +1,61 @@
+// `hlt` reports r0 as the exit code, through `run()` and, headless, as the status the binary
+// exits the host process with.
+
+use std::process::Command;
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::cpu::{HaltReason, RegisterWidth};
+
+fn run(source: &str, width: RegisterWidth) -> HaltReason {
+    let source = match width {
+        RegisterWidth::Eight => source.to_string(),
+        RegisterWidth::Sixteen => format!(".width 16\n{}", source),
+    };
+    let mut vm = MicroCvm::builder().register_width(width).build();
+    vm.load_program(&assemble(&source).unwrap()).unwrap();
+    vm.run().unwrap()
+}
+
+#[test]
+fn run_returns_the_exit_code() {
+    for width in [RegisterWidth::Eight, RegisterWidth::Sixteen] {
+        assert_eq!(run("mov r0, 0\nhlt", width), HaltReason::Halted { code: 0 });
+        assert_eq!(run("mov r0, 7\nhlt", width), HaltReason::Halted { code: 7 });
+    }
+    // Clamped, rather than wrapping round to 0.
+    assert_eq!(
+        run("mov r0, 0x0100\nhlt", RegisterWidth::Sixteen),
+        HaltReason::Halted { code: 255 }
+    );
+}
+
+#[test]
+fn headless_runs_exit_with_it() {
+    let dir = std::env::temp_dir().join(format!("microcvm-exit-code-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    for (code, source) in [(0, "mov r0, 0\nhlt"), (7, "mov r0, 7\nhlt")] {
+        let image = dir.join(format!("exit{}.bin", code));
+        std::fs::write(&image, assemble(source).unwrap()).unwrap();
+        let output = Command::new(env!("CARGO_BIN_EXE_microcvm-rs"))
+            .arg("run")
+            .arg(&image)
+            .arg("--headless")
+            .current_dir(&dir)
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(code), "{:?}", output);
+    }
+    // A fault exits with 1.
+    let image = dir.join("fault.bin");
+    std::fs::write(&image, assemble("mov r1, 0\ndiv r0, r1\nhlt").unwrap()).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_microcvm-rs"))
+        .arg("run")
+        .arg(&image)
+        .arg("--headless")
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
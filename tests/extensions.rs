@@ -0,0 +1,136 @@
+// Registers an extension instruction that swaps the bytes at the addresses in two registers,
+// and runs a guest program using it down each of the CPU's execution paths.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::cpu::Register;
+use microcvm_rs::disasm::format_extension;
+use microcvm_rs::error::VmError;
+use microcvm_rs::extension::{ExtensionDef, ExtensionError, Extensions};
+use microcvm_rs::trace::{TraceEntry, TraceSink};
+
+const SWAPB: u8 = 0xE3;
+
+const PROGRAM: &str = "
+        mov r1, 0x40
+        mov r2, 0x41
+        .db 0xe3, 1, 2          ; swapb r1, r2
+        .db 0xe3, 1, 1          ; swapb r1, r1
+        load r3, [0x0040]
+        hlt
+        .org 0x40
+        .db 0x11, 0x22
+";
+
+fn swapb() -> ExtensionDef {
+    ExtensionDef::new(
+        2,
+        Box::new(|cpu, operands| {
+            let reg = |byte: u8| Register::try_from(byte).map_err(VmError::InvalidRegister);
+            let (a, b) = (cpu.reg(reg(operands[0])?), cpu.reg(reg(operands[1])?));
+            let (first, second) = (cpu.read_mem(a)?, cpu.read_mem(b)?);
+            cpu.write_mem(a, second)?;
+            cpu.write_mem(b, first)
+        }),
+    )
+    .with_mnemonic("swapb")
+}
+
+struct Discard;
+
+impl TraceSink for Discard {
+    fn trace(&mut self, _entry: &TraceEntry) {}
+}
+
+fn run(mut vm: MicroCvm) -> MicroCvm {
+    vm.cpu_mut().register_extension(SWAPB, swapb()).unwrap();
+    vm.load_program(&assemble(PROGRAM).unwrap()).unwrap();
+    vm.run().unwrap();
+    vm
+}
+
+#[test]
+fn guest_programs_run_extensions() {
+    let vms = [
+        ("fast", run(MicroCvm::builder().build())),
+        (
+            "uncached",
+            run(MicroCvm::builder().decode_cache(false).build()),
+        ),
+        (
+            "traced",
+            run(MicroCvm::builder().trace(Box::new(Discard)).build()),
+        ),
+    ];
+    for (path, vm) in vms {
+        let cpu = vm.cpu();
+        assert_eq!(cpu.memory()[0x40..0x42], [0x22, 0x11], "{}", path);
+        assert_eq!(cpu.registers[3], 0x22, "{}", path);
+        assert_eq!(cpu.cycles, 6, "{}", path);
+    }
+}
+
+#[test]
+fn registering_checks_the_opcode() {
+    let mut vm = MicroCvm::builder().build();
+    let cpu = vm.cpu_mut();
+    assert_eq!(
+        cpu.register_extension(0xD0, swapb()).unwrap_err(),
+        ExtensionError::OutOfRange(0xD0)
+    );
+    cpu.register_extension(SWAPB, swapb()).unwrap();
+    assert_eq!(
+        cpu.register_extension(SWAPB, swapb()).unwrap_err(),
+        ExtensionError::AlreadyRegistered(SWAPB)
+    );
+    let long = ExtensionDef::new(4, Box::new(|_, _| Ok(())));
+    assert_eq!(
+        cpu.register_extension(0xE4, long).unwrap_err(),
+        ExtensionError::TooLong {
+            opcode: 0xE4,
+            operand_bytes: 4
+        }
+    );
+}
+
+#[test]
+fn unregistered_and_failing_extensions_fault() {
+    let mut vm = MicroCvm::builder().build();
+    vm.load_program(&[0xE4, 0xFF]).unwrap();
+    let error = vm.run().unwrap_err();
+    assert!(
+        error.to_string().contains("Invalid Opcode: 228"),
+        "{}",
+        error
+    );
+
+    // swapb r1, r9
+    let mut vm = MicroCvm::builder().build();
+    vm.cpu_mut().register_extension(SWAPB, swapb()).unwrap();
+    vm.load_program(&[SWAPB, 1, 9, 0xFF]).unwrap();
+    let error = vm.run().unwrap_err();
+    assert!(
+        error.to_string().contains("Invalid Register: 9"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn disassembly_names_extensions() {
+    let mut extensions = Extensions::new();
+    extensions.register(SWAPB, swapb()).unwrap();
+    let anonymous = ExtensionDef::new(1, Box::new(|_, _| Ok(())));
+    extensions.register(0xE0, anonymous).unwrap();
+    assert_eq!(
+        format_extension(&[SWAPB, 1, 2, 0xFF], &extensions),
+        Some((String::from("swapb 0x01, 0x02"), 3))
+    );
+    assert_eq!(
+        format_extension(&[0xE0, 0x7F], &extensions),
+        Some((String::from("ext 0xe0 0x7f"), 2))
+    );
+    // Unregistered, and cut short.
+    assert_eq!(format_extension(&[0xE1], &extensions), None);
+    assert_eq!(format_extension(&[SWAPB, 1], &extensions), None);
+}
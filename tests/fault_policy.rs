@@ -0,0 +1,156 @@
+// The same faulting program has to stop under Halt, run the guest's own handler under Trap
+// with the frame and fault registers it documents, and do what the host says under Host.
+
+use std::sync::{Arc, Mutex};
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::cpu::{FLAG_CARRY, STACK_TOP};
+use microcvm_rs::error::VmError;
+use microcvm_rs::fault::{FAULT_DIVISION_BY_ZERO, FaultAction, FaultPolicy};
+
+// div r0, 0 is at 0x0011 and the inc after it at 0x0014.
+const FAULTING: &str = "
+        mov r0, 0x00
+        store [0xFF90], r0      ; vector table at 0x0200
+        mov r0, 0x02
+        store [0xFF91], r0
+        mov r0, 10
+        div r0, 0
+        inc r1
+        hlt
+
+on_fault:
+        load r2, [0xFFF0]
+        load r3, [0xFFF1]
+        load r4, [0xFFF2]
+        popf
+        ret
+
+        .org 0x0200
+        .dw 0, on_fault
+";
+
+// The error the run stopped with, if any, as it prints.
+fn run(policy: FaultPolicy) -> (Option<String>, [u16; 8]) {
+    let mut vm = MicroCvm::builder().fault_policy(policy).build();
+    vm.load_program(&assemble(FAULTING).unwrap()).unwrap();
+    let result = vm.run().err().map(|e| e.cause().to_string());
+    (result, vm.cpu().registers)
+}
+
+// The pc and next pc of each fault a Host policy was asked about.
+type Asked = Arc<Mutex<Vec<(u16, u16)>>>;
+
+// A Host policy that answers with `actions` in turn and records what it was asked.
+fn host(actions: Vec<FaultAction>) -> (FaultPolicy, Asked) {
+    let asked = Arc::new(Mutex::new(Vec::new()));
+    let (mut actions, record) = (actions.into_iter(), Arc::clone(&asked));
+    let policy = FaultPolicy::Host(Box::new(move |fault| {
+        assert!(matches!(fault.error, VmError::DivisionByZero { .. }));
+        assert_eq!(fault.registers[0], 10);
+        record.lock().unwrap().push((fault.pc, fault.next_pc));
+        actions.next().unwrap()
+    }));
+    (policy, asked)
+}
+
+#[test]
+fn halt_stops_at_the_fault() {
+    let (result, registers) = run(FaultPolicy::Halt);
+    assert_eq!(result.as_deref(), Some("Division by zero (pc 0x0011)"));
+    assert_eq!(registers[..3], [10, 0, 0]);
+}
+
+#[test]
+fn trap_runs_the_guest_handler_with_the_fault_registers() {
+    let (result, registers) = run(FaultPolicy::Trap);
+    assert_eq!(result, None);
+    // Back after the div, with the handler having read what happened where.
+    assert_eq!(
+        registers[..5],
+        [10, 1, FAULT_DIVISION_BY_ZERO as u16, 0x11, 0x00]
+    );
+}
+
+#[test]
+fn the_trap_frame_is_the_flags_then_the_return_address() {
+    let program = assemble(
+        "
+        mov r0, 0x00
+        store [0xFF90], r0
+        mov r0, 0x02
+        store [0xFF91], r0
+        stc
+        div r0, 0
+        hlt
+on_fault:
+        hlt
+        .org 0x0200
+        .dw 0, on_fault
+",
+    )
+    .unwrap();
+    let mut vm = MicroCvm::builder().fault_policy(FaultPolicy::Trap).build();
+    vm.load_program(&program).unwrap();
+    vm.run().unwrap();
+    let cpu = vm.cpu();
+    let flags = cpu.memory()[cpu.sp as usize];
+    assert_eq!(cpu.sp, STACK_TOP - 3);
+    assert_eq!(
+        cpu.memory()[cpu.sp as usize + 1..STACK_TOP as usize],
+        [0x12, 0x00]
+    );
+    // The carry set before the fault is in the frame, for popf to put back.
+    assert_eq!(flags & FLAG_CARRY, FLAG_CARRY);
+}
+
+#[test]
+fn trap_without_a_handler_halts() {
+    let mut vm = MicroCvm::builder().fault_policy(FaultPolicy::Trap).build();
+    // The table at 0x0200 is all zeros.
+    let program = assemble(
+        "
+        mov r0, 0x00
+        store [0xFF90], r0
+        mov r0, 0x02
+        store [0xFF91], r0
+        div r0, 0
+        hlt
+",
+    )
+    .unwrap();
+    vm.load_program(&program).unwrap();
+    let error = vm.run().unwrap_err();
+    assert!(
+        matches!(error.cause(), VmError::DivisionByZero { pc: 0x000E }),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn the_host_can_skip_retry_or_halt() {
+    let (policy, asked) = host(vec![FaultAction::Skip]);
+    let (result, registers) = run(policy);
+    assert_eq!(result, None);
+    assert_eq!(registers[..4], [10, 1, 0, 0]);
+    assert_eq!(*asked.lock().unwrap(), [(0x0011, 0x0014)]);
+
+    // Each retry faults again, until the host gives up on it.
+    let (policy, asked) = host(vec![
+        FaultAction::Retry,
+        FaultAction::Retry,
+        FaultAction::Skip,
+    ]);
+    let (result, registers) = run(policy);
+    assert_eq!(result, None);
+    assert_eq!(registers[..2], [10, 1]);
+    assert_eq!(*asked.lock().unwrap(), [(0x0011, 0x0014); 3]);
+
+    let (policy, asked) = host(vec![FaultAction::Halt]);
+    let (result, registers) = run(policy);
+    assert_eq!(result.as_deref(), Some("Division by zero (pc 0x0011)"));
+    assert_eq!(registers[..2], [10, 0]);
+    assert_eq!(asked.lock().unwrap().len(), 1);
+}
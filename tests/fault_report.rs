@@ -0,0 +1,124 @@
+// A fault has to carry the machine as it stood when the instruction failed: its bytes, the
+// registers and flags, the instructions leading up to it, and a crash report showing them.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::cpu::{FLAG_CARRY, FLAG_ZERO};
+use microcvm_rs::error::VmError;
+use microcvm_rs::isa;
+
+// Sets the carry and zero flags, then divides by the zero in r1 at 0x000d.
+const DIVIDE_BY_ZERO: &str = "
+    mov r0, 7
+    mov r1, 0
+    mov r2, 0x12
+    stc
+    test r1, r1
+    div r0, r1
+    hlt
+";
+
+const REGISTERS: [u16; 8] = [7, 0, 0x12, 0, 0, 0, 0, 0];
+
+fn fault(program: &[u8], history: usize) -> VmError {
+    let mut vm = MicroCvm::builder()
+        .resolution(16, 16)
+        .fault_history(history)
+        .build();
+    vm.load_program(program).unwrap();
+    vm.run().unwrap_err()
+}
+
+#[test]
+fn the_report_holds_the_instruction_registers_and_flags() {
+    let error = fault(&assemble(DIVIDE_BY_ZERO).unwrap(), 3);
+    assert!(matches!(
+        error.cause(),
+        VmError::DivisionByZero { pc: 0x000d }
+    ));
+    let report = error.report().unwrap();
+    assert_eq!(report.pc, 0x000d);
+    assert_eq!(report.bytes, [0x08, 0x80, 0x01]);
+    assert_eq!(report.registers, REGISTERS);
+    assert_eq!(report.sp, 0xFF00);
+    assert_eq!(report.flags, FLAG_ZERO | FLAG_CARRY);
+}
+
+#[test]
+fn the_history_ends_at_the_faulting_instruction() {
+    let error = fault(&assemble(DIVIDE_BY_ZERO).unwrap(), 3);
+    let history = &error.report().unwrap().history;
+    let entries: Vec<(u16, u64, String)> = history
+        .iter()
+        .map(|record| (record.pc, record.cycles, record.opcode.to_string()))
+        .collect();
+    assert_eq!(
+        entries,
+        [
+            (0x0009, 3, "stc".to_string()),
+            (0x000a, 4, "test r1, r1".to_string()),
+            (0x000d, 5, "div r0, r1".to_string()),
+        ]
+    );
+    assert!(history.iter().all(|record| record.registers == REGISTERS));
+
+    // A longer history than the program ran keeps everything, and none keeps nothing.
+    let error = fault(&assemble(DIVIDE_BY_ZERO).unwrap(), 16);
+    let pcs: Vec<u16> = error
+        .report()
+        .unwrap()
+        .history
+        .iter()
+        .map(|r| r.pc)
+        .collect();
+    assert_eq!(pcs, [0x0000, 0x0003, 0x0006, 0x0009, 0x000a, 0x000d]);
+    let error = fault(&assemble(DIVIDE_BY_ZERO).unwrap(), 0);
+    assert!(error.report().unwrap().history.is_empty());
+}
+
+#[test]
+fn the_crash_report_shows_all_of_it() {
+    let error = fault(&assemble(DIVIDE_BY_ZERO).unwrap(), 3);
+    assert_eq!(
+        error.to_string(),
+        "\
+Fault at 0x000d: Division by zero (pc 0x000d)
+  instruction: 08 80 01
+  registers: r0 0007 r1 0000 r2 0012 r3 0000 r4 0000 r5 0000 r6 0000 r7 0000
+  sp ff00 flags ZC-
+  last 3 instructions:
+    0x0009  19           stc                  [7, 0, 18, 0, 0, 0, 0, 0]
+    0x000a  10 81 01     test r1, r1          [7, 0, 18, 0, 0, 0, 0, 0]
+    0x000d  08 80 01     div r0, r1           [7, 0, 18, 0, 0, 0, 0, 0]"
+    );
+    // Without a history the report stops at the flags.
+    let error = fault(&assemble(DIVIDE_BY_ZERO).unwrap(), 0);
+    assert!(
+        error.to_string().ends_with("sp ff00 flags ZC-"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn an_undecodable_instruction_reports_every_byte_it_could_take() {
+    let invalid = (0..=255).find(|&b| isa::decode(b).is_none()).unwrap();
+    let error = fault(&[0x07, 0x03, invalid], 2);
+    let report = error.report().unwrap();
+    assert_eq!(report.pc, 0x0002);
+    // Its length is unknown, so the report holds as much as any instruction could take.
+    let mut bytes = vec![0; isa::MAX_LENGTH as usize];
+    bytes[0] = invalid;
+    assert_eq!(report.bytes, bytes);
+    assert_eq!(report.registers[3], 1);
+    assert_eq!(report.flags, 0);
+    let pcs: Vec<u16> = report.history.iter().map(|r| r.pc).collect();
+    assert_eq!(pcs, [0x0000]);
+    assert!(
+        error
+            .to_string()
+            .contains(&format!("  instruction: {:02x} 00 00", invalid)),
+        "{}",
+        error
+    );
+}
@@ -0,0 +1,111 @@
+// The file device has to refuse any path that leaves its root, whether through `..`, an
+// absolute path or a symlink, before it touches the file, and read and write what is inside.
+
+use std::path::PathBuf;
+
+use microcvm_rs::files::{FS_BAD_PATH, FS_DENIED, FS_NOT_FOUND, FS_OK, FS_READ_ONLY, FileDevice};
+
+// A root with `inside.txt` in it, beside a `secret.txt` that must stay out of reach.
+fn scratch(name: &str) -> (PathBuf, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("microcvm-files-test-{}", name));
+    let _ = std::fs::remove_dir_all(&dir);
+    let root = dir.join("root");
+    std::fs::create_dir_all(root.join("sub")).unwrap();
+    std::fs::write(root.join("inside.txt"), "inside").unwrap();
+    std::fs::write(dir.join("secret.txt"), "secret").unwrap();
+    (dir, root)
+}
+
+fn attached(root: &PathBuf, read_only: bool) -> FileDevice {
+    let mut device = FileDevice::default();
+    device.attach(root, read_only).unwrap();
+    device
+}
+
+#[test]
+fn paths_out_of_the_root_are_refused() {
+    let (dir, root) = scratch("escape");
+    let secret = dir.join("secret.txt");
+    let mut device = attached(&root, false);
+    for (path, status) in [
+        ("../secret.txt", FS_DENIED),
+        ("sub/../../secret.txt", FS_DENIED),
+        // Even one that would come back inside.
+        ("sub/../inside.txt", FS_DENIED),
+        (secret.to_str().unwrap(), FS_DENIED),
+        ("/etc/passwd", FS_DENIED),
+        ("", FS_BAD_PATH),
+        ("sub\\..\\..\\secret.txt", FS_BAD_PATH),
+        ("missing.txt", FS_NOT_FOUND),
+    ] {
+        assert_eq!(device.open(path.as_bytes(), false), status, "{}", path);
+        // Creating is refused the same way, and creates nothing.
+        if status == FS_DENIED {
+            assert_eq!(device.open(path.as_bytes(), true), FS_DENIED, "{}", path);
+        }
+    }
+    assert_eq!(std::fs::read_to_string(&secret).unwrap(), "secret");
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks_out_of_the_root_are_refused() {
+    let (dir, root) = scratch("symlink");
+    let secret = dir.join("secret.txt");
+    std::os::unix::fs::symlink(&secret, root.join("link.txt")).unwrap();
+    std::os::unix::fs::symlink(&dir, root.join("sub/up")).unwrap();
+    std::os::unix::fs::symlink(dir.join("planted.txt"), root.join("dangling.txt")).unwrap();
+
+    let mut device = attached(&root, false);
+    for path in ["link.txt", "sub/up/secret.txt"] {
+        assert_eq!(device.open(path.as_bytes(), false), FS_DENIED, "{}", path);
+        assert_eq!(device.open(path.as_bytes(), true), FS_DENIED, "{}", path);
+    }
+    // Creating through a link to nowhere would put the file outside.
+    assert_ne!(device.open(b"dangling.txt", true), FS_OK);
+    assert!(!dir.join("planted.txt").exists());
+    assert_eq!(std::fs::read_to_string(&secret).unwrap(), "secret");
+
+    // A link that stays inside is followed.
+    std::os::unix::fs::symlink(root.join("inside.txt"), root.join("sub/alias.txt")).unwrap();
+    assert_eq!(device.open(b"sub/alias.txt", false), FS_OK);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn files_inside_the_root_read_and_write() {
+    let (dir, root) = scratch("inside");
+    let mut device = attached(&root, false);
+
+    assert_eq!(device.open(b"inside.txt", false), FS_OK);
+    let mut buffer = [0; 16];
+    assert_eq!(device.read_file(&mut buffer), FS_OK);
+    assert_eq!(&buffer[..device.result as usize], b"inside");
+    device.pos = 2;
+    assert_eq!(device.seek(), FS_OK);
+    assert_eq!(device.read_file(&mut buffer[..3]), FS_OK);
+    assert_eq!(&buffer[..3], b"sid");
+    assert_eq!(device.close(), FS_OK);
+
+    assert_eq!(device.open(b"./sub/new.txt", true), FS_OK);
+    assert_eq!(device.write_file(b"written"), FS_OK);
+    assert_eq!(device.result, 7);
+    assert_eq!(device.close(), FS_OK);
+    assert_eq!(
+        std::fs::read_to_string(root.join("sub/new.txt")).unwrap(),
+        "written"
+    );
+
+    // Read-only roots still read.
+    let mut device = attached(&root, true);
+    assert_eq!(device.open(b"sub/other.txt", true), FS_READ_ONLY);
+    assert_eq!(device.open(b"sub/new.txt", false), FS_OK);
+    assert_eq!(device.write_file(b"over"), FS_READ_ONLY);
+    assert_eq!(
+        std::fs::read_to_string(root.join("sub/new.txt")).unwrap(),
+        "written"
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
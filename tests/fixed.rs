@@ -0,0 +1,108 @@
+// Runs fxmul, fxdiv and fxmuli over a grid of 8.8 values, negatives included, and checks each
+// result against f64 arithmetic: within one 256th, with the carry flag set exactly when the
+// rounded result doesn't fit. Every case runs down the fast path and the traced one.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::cpu::{FLAG_CARRY, FLAG_ZERO, OpcodeType};
+use microcvm_rs::error::VmError;
+use microcvm_rs::fixed::to_f64;
+use microcvm_rs::trace::{TraceEntry, TraceSink};
+
+struct Discard;
+
+impl TraceSink for Discard {
+    fn trace(&mut self, _entry: &TraceEntry) {}
+}
+
+fn grid() -> Vec<i16> {
+    let mut values: Vec<i16> = (i16::MIN..=i16::MAX).step_by(1021).collect();
+    values.extend([
+        0,
+        1,
+        -1,
+        0x80,
+        -0x80,
+        0x100,
+        -0x100,
+        0x180,
+        -0x240,
+        i16::MAX,
+        i16::MIN,
+    ]);
+    values
+}
+
+// Runs `opcode` on the pair at r0 with `b`, in r2:r3 or as the immediate, and returns the
+// pair and flags after it, or the error it faulted with.
+fn run(vm: &mut MicroCvm, opcode: OpcodeType, a: i16, b: i16) -> Result<(i16, u8), VmError> {
+    let [b_low, b_high] = b.to_le_bytes();
+    let program = match opcode {
+        OpcodeType::FxMulImm => vec![opcode as u8, 0, b_low, b_high, 0xFF],
+        _ => vec![opcode as u8, 0, 2, 0xFF],
+    };
+    let cpu = vm.cpu_mut();
+    cpu.memory_mut()[..program.len()].copy_from_slice(&program);
+    let [a_high, a_low] = a.to_be_bytes();
+    cpu.registers = [0; 8];
+    cpu.registers[..4].copy_from_slice(&[a_high, a_low, b_high, b_low].map(u16::from));
+    cpu.flags = 0;
+    cpu.pc = 0;
+    cpu.halted = false;
+    cpu.run().map_err(|error| match error {
+        VmError::Fault(report) => report.error,
+        error => error,
+    })?;
+    let result = i16::from_be_bytes([cpu.registers[0] as u8, cpu.registers[1] as u8]);
+    Ok((result, cpu.flags))
+}
+
+#[test]
+fn results_match_f64_within_one_lsb() {
+    let mut vms = [
+        ("fast", MicroCvm::builder().build()),
+        (
+            "traced",
+            MicroCvm::builder().trace(Box::new(Discard)).build(),
+        ),
+    ];
+    let ops = [OpcodeType::FxMul, OpcodeType::FxMulImm, OpcodeType::FxDiv];
+    let values = grid();
+    for (path, vm) in &mut vms {
+        for opcode in ops {
+            for &a in &values {
+                for &b in &values {
+                    let case = format!("{} {:?} {} {}", path, opcode, to_f64(a), to_f64(b));
+                    let outcome = run(vm, opcode, a, b);
+                    let exact = match opcode {
+                        OpcodeType::FxDiv if b == 0 => {
+                            assert!(
+                                matches!(outcome, Err(VmError::DivisionByZero { .. })),
+                                "{}: {:?}",
+                                case,
+                                outcome
+                            );
+                            continue;
+                        }
+                        OpcodeType::FxDiv => to_f64(a) / to_f64(b),
+                        _ => to_f64(a) * to_f64(b),
+                    };
+                    let (result, flags) = outcome.unwrap();
+                    let rounded = (exact * 256.0).round();
+                    let overflow = !(i16::MIN as f64..=i16::MAX as f64).contains(&rounded);
+                    assert_eq!(flags & FLAG_CARRY != 0, overflow, "{}: carry", case);
+                    assert_eq!(flags & FLAG_ZERO != 0, result == 0, "{}: zero", case);
+                    if !overflow {
+                        let error = (to_f64(result) - exact).abs();
+                        assert!(
+                            error <= 1.0 / 256.0,
+                            "{}: got {}, expected {}",
+                            case,
+                            to_f64(result),
+                            exact
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,76 @@
+// `popf` restores the I flag along with the others, so `pushf` ... `popf` around a critical
+// section gives back the caller's interrupt state: enabled again if it was, and still
+// disabled if it wasn't, whatever ran in between.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::{assemble, assemble_with_symbols};
+use microcvm_rs::cpu::{FLAG_CARRY, FLAG_INTERRUPT_ENABLE, Register};
+
+fn flags_after(source: &str) -> u8 {
+    let mut vm = MicroCvm::builder().build();
+    vm.load_program(&assemble(source).unwrap()).unwrap();
+    vm.run().unwrap();
+    vm.cpu().flags
+}
+
+#[test]
+fn popf_brings_back_the_interrupt_flag_either_way() {
+    let flags = flags_after("ei\nstc\npushf\ndi\nclc\npopf\nhlt");
+    assert_eq!(flags, FLAG_INTERRUPT_ENABLE | FLAG_CARRY);
+    let flags = flags_after("di\npushf\nei\npopf\nhlt");
+    assert_eq!(flags & FLAG_INTERRUPT_ENABLE, 0);
+    // What was pushed is the whole flags byte.
+    let mut vm = MicroCvm::builder().build();
+    vm.load_program(&assemble("ei\nstc\npushf\nhlt").unwrap())
+        .unwrap();
+    vm.run().unwrap();
+    let sp = vm.cpu().sp as usize;
+    assert_eq!(vm.cpu().memory()[sp], FLAG_INTERRUPT_ENABLE | FLAG_CARRY);
+}
+
+#[test]
+fn an_interrupt_held_off_in_a_critical_section_arrives_at_popf() {
+    let (program, symbols) = assemble_with_symbols(
+        "
+        mov r0, 0x00
+        store [0xFF90], r0              ; vector table at 0x0200
+        mov r0, 0x02
+        store [0xFF91], r0
+        ei
+        pushf
+        di
+held:   nop
+        nop
+        popf
+after:  hlt
+
+handler:
+        inc r7
+        iret
+
+        .org 0x0200
+        .dw 0, handler
+",
+    )
+    .unwrap();
+    let mut vm = MicroCvm::builder().build();
+    vm.load_program(&program).unwrap();
+    let held = symbols.address("held").unwrap();
+    while vm.cpu().pc != held {
+        vm.step().unwrap();
+    }
+    vm.cpu_mut().interrupts.raise(1);
+    // Through the section without it.
+    vm.step().unwrap();
+    vm.step().unwrap();
+    assert_eq!(vm.cpu()[Register::R7], 0);
+    assert_eq!(vm.cpu().interrupts.pending, 1 << 1);
+    // The popf, then the handler's first instruction before anything else.
+    vm.step().unwrap();
+    vm.step().unwrap();
+    assert_eq!(vm.cpu()[Register::R7], 1);
+    assert_eq!(vm.cpu().interrupts.pending, 0);
+    vm.run().unwrap();
+    assert_eq!(vm.cpu().pc, symbols.address("after").unwrap());
+    assert_eq!(vm.cpu()[Register::R7], 1);
+}
@@ -0,0 +1,76 @@
+// With pausing in the background turned on, losing focus has to stop the machine, guest
+// time included, and mute it until focus comes back, without ending a pause someone else
+// asked for. Turned off, which it is by default, focus changes nothing.
+
+use std::sync::atomic::Ordering;
+
+use microcvm_rs::asm::assemble;
+use microcvm_rs::{HaltReason, MicroCvm};
+
+fn spinning(pause_on_focus_loss: bool) -> MicroCvm {
+    let mut vm = MicroCvm::builder()
+        .pause_on_focus_loss(pause_on_focus_loss)
+        .build();
+    vm.load_program(&assemble("spin: inc r0\njmp spin").unwrap())
+        .unwrap();
+    vm.run_for(100).unwrap();
+    vm
+}
+
+fn muted(vm: &MicroCvm) -> bool {
+    vm.cpu().audio.muted.load(Ordering::Relaxed)
+}
+
+#[test]
+fn losing_focus_stalls_the_machine_until_it_comes_back() {
+    let mut vm = spinning(true);
+    for round in 1..=3 {
+        vm.focus_changed(false);
+        assert!(vm.is_paused() && vm.auto_paused() && muted(&vm));
+        let (instructions, cycles) = (vm.instructions(), vm.cpu().cycles);
+        for _ in 0..3 {
+            assert_eq!(vm.run_for(100).unwrap(), HaltReason::Paused);
+            assert_eq!(vm.run_frame(1000).unwrap(), HaltReason::Paused);
+        }
+        // Guest time stands still along with everything else.
+        assert_eq!((vm.instructions(), vm.cpu().cycles), (instructions, cycles));
+        // Losing it again while in the background changes nothing.
+        vm.focus_changed(false);
+        assert!(vm.auto_paused());
+
+        vm.focus_changed(true);
+        assert!(!vm.is_paused() && !vm.auto_paused() && !muted(&vm));
+        vm.run_for(100).unwrap();
+        assert_eq!(vm.instructions(), 100 * (round + 1));
+        assert!(vm.cpu().cycles > cycles);
+    }
+}
+
+#[test]
+fn a_pause_someone_asked_for_outlasts_the_focus() {
+    let mut vm = spinning(true);
+    vm.pause();
+    vm.focus_changed(false);
+    assert!(vm.is_paused() && !vm.auto_paused() && !muted(&vm));
+    vm.focus_changed(true);
+    assert!(vm.is_paused());
+    vm.resume();
+    vm.run_for(100).unwrap();
+    assert_eq!(vm.instructions(), 200);
+
+    // Pausing while in the background, then resuming, leaves the focus nothing to do.
+    vm.focus_changed(false);
+    vm.resume();
+    assert!(!vm.auto_paused());
+    vm.focus_changed(true);
+    assert!(!vm.is_paused());
+}
+
+#[test]
+fn focus_changes_nothing_when_turned_off() {
+    let mut vm = spinning(false);
+    vm.focus_changed(false);
+    assert!(!vm.is_paused() && !vm.auto_paused() && !muted(&vm));
+    vm.run_for(100).unwrap();
+    assert_eq!(vm.instructions(), 200);
+}
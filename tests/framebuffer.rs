@@ -0,0 +1,161 @@
+// Bytes written through the framebuffer window have to be the pixels video operations see and
+// the other way round, and both have to add exactly the pixels they changed to the dirty region.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::types::Color;
+use microcvm_rs::video::{ColorDepth, Rect};
+
+const WINDOW: u16 = 0x8000;
+
+fn windowed(depth: ColorDepth, width: u32, height: u32) -> MicroCvm {
+    MicroCvm::builder()
+        .resolution(width, height)
+        .color_depth(depth)
+        .framebuffer_window(WINDOW, 0x100)
+        .build()
+}
+
+// Runs `source` from the top, keeping whatever the screen already shows.
+fn run(vm: &mut MicroCvm, source: &str) {
+    vm.load_program(&assemble(source).unwrap()).unwrap();
+    vm.cpu_mut().pc = 0;
+    vm.cpu_mut().halted = false;
+    vm.run().unwrap();
+}
+
+#[test]
+fn video_operations_show_through_the_window() {
+    let mut vm = windowed(ColorDepth::TwentyFour, 16, 16);
+    // A 2x2 rectangle at 3, 1, then the three bytes of its top left pixel, number 19.
+    run(
+        &mut vm,
+        "
+        mov r0, 3
+        mov r1, 1
+        mov r2, 2
+        mov r3, 2
+        mov r4, 10
+        mov r5, 20
+        mov r6, 30
+        video fillrect, r0
+        load r0, [0x8039]
+        load r1, [0x803A]
+        load r2, [0x803B]
+        load r3, [0x8036]
+        hlt
+",
+    );
+    assert_eq!(vm.cpu().registers[..4], [10, 20, 30, 0]);
+}
+
+#[test]
+fn window_writes_show_in_video_operations() {
+    let mut vm = windowed(ColorDepth::TwentyFour, 16, 16);
+    // Pixel 0 set through the window, copied to 5, 5 by `copyrect` and read back through
+    // the window from the second bank, where pixel 85 starts at byte 255.
+    run(
+        &mut vm,
+        "
+        mov r0, 40
+        store [0x8000], r0
+        mov r0, 50
+        store [0x8001], r0
+        mov r0, 60
+        store [0x8002], r0
+        mov r0, 0
+        mov r1, 0
+        mov r2, 5
+        mov r3, 5
+        mov r4, 1
+        mov r5, 1
+        video copyrect, r0
+        load r0, [0x80FF]
+        mov r1, 1
+        store [0xFF11], r1
+        load r1, [0x8000]
+        load r2, [0x8001]
+        hlt
+",
+    );
+    assert_eq!(vm.cpu().registers[..3], [40, 50, 60]);
+    assert_eq!(vm.framebuffer().pixel(5 * 16 + 5), Color::new(40, 50, 60));
+    // The RAM behind the window is untouched.
+    assert!(vm.cpu().memory()[0x8000..0x8100].iter().all(|&b| b == 0));
+}
+
+#[test]
+fn window_writes_dirty_the_pixels_in_their_byte() {
+    // One channel of a 24-bit pixel is that pixel alone.
+    let mut vm = windowed(ColorDepth::TwentyFour, 16, 16);
+    assert_eq!(vm.cpu().dirty(), None);
+    run(&mut vm, "mov r0, 1\nstore [0x803A], r0\nhlt");
+    assert_eq!(vm.cpu_mut().take_dirty(), Some(Rect::new(3, 1, 1, 1)));
+    assert_eq!(vm.cpu_mut().take_dirty(), None);
+
+    // Reading changes nothing.
+    run(&mut vm, "load r0, [0x803A]\nload r0, [0x8000]\nhlt");
+    assert_eq!(vm.cpu().dirty(), None);
+
+    // At 1 bit a byte is eight pixels along a row.
+    let mut vm = windowed(ColorDepth::One, 16, 16);
+    run(&mut vm, "mov r0, 0xFF\nstore [0x8003], r0\nhlt");
+    assert_eq!(vm.cpu_mut().take_dirty(), Some(Rect::new(8, 1, 8, 1)));
+
+    // A byte that runs on to the next row dirties both rows across the whole width.
+    let mut vm = windowed(ColorDepth::One, 12, 4);
+    run(&mut vm, "mov r0, 0xFF\nstore [0x8001], r0\nhlt");
+    assert_eq!(vm.cpu_mut().take_dirty(), Some(Rect::new(0, 0, 12, 2)));
+
+    // The second bank of the window picks up where the first left off: byte 0x100 holds the
+    // blue channel of pixel 85, at 5, 5.
+    let mut vm = windowed(ColorDepth::TwentyFour, 16, 16);
+    run(
+        &mut vm,
+        "mov r0, 1\nstore [0xFF11], r0\nstore [0x8000], r0\nhlt",
+    );
+    assert_eq!(vm.cpu_mut().take_dirty(), Some(Rect::new(5, 5, 1, 1)));
+}
+
+#[test]
+fn the_dirty_region_covers_window_writes_and_video_operations_together() {
+    let mut vm = windowed(ColorDepth::TwentyFour, 16, 16);
+    // `setpixel` at 1, 1 and a window write to pixel 53, at 5, 3.
+    run(
+        &mut vm,
+        "
+        mov r0, 1
+        mov r1, 1
+        mov r2, 255
+        video setpixel, r0
+        store [0x809F], r2
+        hlt
+",
+    );
+    assert_eq!(vm.cpu_mut().take_dirty(), Some(Rect::new(1, 1, 5, 3)));
+
+    // Only the part of a rectangle that lies on screen is dirty.
+    run(
+        &mut vm,
+        "
+        mov r0, 14
+        mov r1, 14
+        mov r2, 5
+        mov r3, 5
+        video fillrect, r0
+        hlt
+",
+    );
+    assert_eq!(vm.cpu_mut().take_dirty(), Some(Rect::new(14, 14, 2, 2)));
+
+    // Operations that change every pixel dirty the whole screen.
+    for source in ["video clear, r0\nhlt", "mov r0, 8\nstore [0xFF14], r0\nhlt"] {
+        run(&mut vm, source);
+        assert_eq!(
+            vm.cpu_mut().take_dirty(),
+            Some(Rect::new(0, 0, 16, 16)),
+            "{}",
+            source
+        );
+    }
+}
@@ -0,0 +1,130 @@
+// The gamepad registers have to show what the host injects merged with the buttons held on
+// the keyboard, the keyboard's direction keys standing in for a centred left stick, and a
+// controller coming or going must not drop a held key.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::bus::Device;
+use microcvm_rs::cpu::GAMEPAD_BASE;
+use microcvm_rs::gamepad::{
+    AXIS_FULL, BUTTON_A, BUTTON_B, BUTTON_DOWN, BUTTON_LEFT, BUTTON_RIGHT, BUTTON_SELECT,
+    BUTTON_START, BUTTON_UP, Gamepad, GamepadState, PAD_BUTTONS, PAD_LEFT_X, PAD_LEFT_Y,
+    PAD_REGISTER_COUNT, PAD_RIGHT_X, PAD_RIGHT_Y, axis_byte,
+};
+
+// The six gamepad registers as the guest reads them, into r0 to r5, from the top each time.
+fn guest_reads(vm: &mut MicroCvm) -> [u16; 6] {
+    let mut source = String::new();
+    for offset in 0..PAD_REGISTER_COUNT {
+        source += &format!("load r{}, [{:#x}]\n", offset, GAMEPAD_BASE + offset as u16);
+    }
+    vm.load_program(&assemble(&(source + "hlt")).unwrap())
+        .unwrap();
+    vm.cpu_mut().pc = 0;
+    vm.cpu_mut().halted = false;
+    vm.run().unwrap();
+    vm.cpu().registers[..6].try_into().unwrap()
+}
+
+#[test]
+fn injected_state_and_held_keys_merge() {
+    let mut vm = MicroCvm::builder().build();
+    assert_eq!(guest_reads(&mut vm), [0; 6]);
+
+    vm.inject_gamepad_state(GamepadState {
+        buttons: BUTTON_START,
+        left_stick: [0, 0],
+        right_stick: [100, -64],
+    });
+    vm.cpu_mut().gamepad_mut().key(BUTTON_A | BUTTON_LEFT, true);
+    // Start is 0x0100, A and left 0x0014, and left pushes the centred stick all the way.
+    assert_eq!(guest_reads(&mut vm), [0x14, 0x01, 0x81, 0x00, 100, 0xC0]);
+
+    // Either one holding a button is enough.
+    vm.inject_gamepad_state(GamepadState {
+        buttons: BUTTON_A | BUTTON_SELECT,
+        ..GamepadState::default()
+    });
+    assert_eq!(
+        vm.cpu().gamepad().state().buttons,
+        BUTTON_A | BUTTON_LEFT | BUTTON_SELECT
+    );
+    vm.cpu_mut().gamepad_mut().key(BUTTON_A, false);
+    assert_eq!(
+        vm.cpu().gamepad().state().buttons,
+        BUTTON_A | BUTTON_LEFT | BUTTON_SELECT
+    );
+}
+
+#[test]
+fn a_pushed_stick_wins_over_the_direction_keys() {
+    let mut pad = Gamepad::default();
+    pad.key(BUTTON_LEFT | BUTTON_DOWN, true);
+    assert_eq!(pad.state().left_stick, [-AXIS_FULL, AXIS_FULL]);
+
+    pad.pad.left_stick = [30, 0];
+    assert_eq!(pad.state().left_stick, [30, AXIS_FULL]);
+
+    // Opposite keys cancel out.
+    pad.pad.left_stick = [0, 0];
+    pad.key(BUTTON_RIGHT | BUTTON_UP, true);
+    assert_eq!(pad.state().left_stick, [0, 0]);
+    // The right stick is the controller's alone.
+    assert_eq!(pad.state().right_stick, [0, 0]);
+}
+
+#[test]
+fn controllers_coming_and_going_keep_the_keys() {
+    let mut vm = MicroCvm::builder().build();
+    vm.cpu_mut().gamepad_mut().key(BUTTON_B | BUTTON_UP, true);
+    for _ in 0..3 {
+        vm.inject_gamepad_state(GamepadState {
+            buttons: BUTTON_START,
+            left_stick: [-5, 7],
+            right_stick: [1, 2],
+        });
+        assert_eq!(
+            vm.cpu().gamepad().state().buttons,
+            BUTTON_B | BUTTON_UP | BUTTON_START
+        );
+        // Pulled out.
+        vm.inject_gamepad_state(GamepadState::default());
+        let state = vm.cpu().gamepad().state();
+        assert_eq!(state.buttons, BUTTON_B | BUTTON_UP);
+        assert_eq!(state.left_stick, [0, -AXIS_FULL]);
+        assert_eq!(state.right_stick, [0, 0]);
+    }
+}
+
+#[test]
+fn the_registers_are_read_only() {
+    let mut pad = Gamepad::default();
+    pad.pad.buttons = 0x0201;
+    pad.pad.right_stick = [-1, 1];
+    for offset in 0..PAD_REGISTER_COUNT as u16 {
+        pad.write(offset, 0x55);
+    }
+    assert_eq!(pad.read(PAD_BUTTONS as u16), 0x01);
+    assert_eq!(pad.read(PAD_BUTTONS as u16 + 1), 0x02);
+    assert_eq!(pad.read(PAD_LEFT_X as u16), 0);
+    assert_eq!(pad.read(PAD_LEFT_Y as u16), 0);
+    assert_eq!(pad.read(PAD_RIGHT_X as u16), 0xFF);
+    assert_eq!(pad.read(PAD_RIGHT_Y as u16), 0x01);
+    assert_eq!(pad.read(PAD_REGISTER_COUNT as u16), 0);
+}
+
+#[test]
+fn stick_positions_become_signed_bytes() {
+    for (value, byte) in [
+        (0.0, 0),
+        (1.0, AXIS_FULL),
+        (-1.0, -AXIS_FULL),
+        (0.5, 63),
+        (-0.5, -63),
+        (7.0, AXIS_FULL),
+        (-7.0, -AXIS_FULL),
+        (f32::NAN, 0),
+    ] {
+        assert_eq!(axis_byte(value), byte, "{}", value);
+    }
+}
@@ -0,0 +1,115 @@
+// A frame rendered without a window has to match its golden PNG exactly, be let through by a
+// tolerance that covers how far it's off and no further, and leave what it drew next to the
+// golden image when it doesn't match.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::crt::CrtEffect;
+use microcvm_rs::demo::{DEMO_HEIGHT, DEMO_WIDTH, bounce_program};
+use microcvm_rs::golden::{self, GoldenError, Tolerance};
+use microcvm_rs::png;
+use microcvm_rs::vm::CYCLES_PER_FRAME;
+use std::path::{Path, PathBuf};
+
+const GOLDEN: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/bounce-1000.png");
+
+// The bounce demo 1000 frames in, with scanlines, as tests/golden/bounce-1000.png has it.
+fn bounce_frame() -> Vec<u8> {
+    let mut vm = MicroCvm::builder()
+        .resolution(DEMO_WIDTH, DEMO_HEIGHT)
+        .build();
+    vm.load_program(&bounce_program()).unwrap();
+    for _ in 0..1000 {
+        vm.run_frame(CYCLES_PER_FRAME).unwrap();
+        vm.tick_frame();
+    }
+    vm.set_crt(Some(CrtEffect {
+        scanline_dim: 25,
+        smear: false,
+    }));
+    vm.render_frame_to_rgba()
+}
+
+// A copy of the golden image to fail against, so a mismatch isn't written next to the real one.
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("microcvm-golden-test-{}", name));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("bounce.png");
+    std::fs::copy(GOLDEN, &path).unwrap();
+    let _ = std::fs::remove_file(dir.join("bounce.png.actual.png"));
+    path
+}
+
+fn check(path: &Path, frame: &[u8], tolerance: Tolerance) -> Result<(), GoldenError> {
+    golden::check(path, DEMO_WIDTH, DEMO_HEIGHT, frame, tolerance)
+}
+
+#[test]
+fn the_bounce_demo_matches_its_golden_image() {
+    let frame = bounce_frame();
+    assert_eq!(frame.len(), DEMO_WIDTH as usize * DEMO_HEIGHT as usize * 4);
+    golden::check(GOLDEN, DEMO_WIDTH, DEMO_HEIGHT, &frame, Tolerance::Exact).unwrap();
+    // Running it again draws the same frame.
+    assert_eq!(bounce_frame(), frame);
+}
+
+#[test]
+fn a_tolerance_covers_small_differences_and_no_more() {
+    let path = scratch("tolerance");
+    let mut frame = bounce_frame();
+    frame[0] ^= 2;
+    let last = frame.len() - 2;
+    frame[last] = frame[last].wrapping_add(3);
+
+    let Err(GoldenError::Mismatch { difference, actual }) = check(&path, &frame, Tolerance::Exact)
+    else {
+        panic!("expected a mismatch");
+    };
+    assert_eq!(difference.pixels, 2);
+    assert_eq!(difference.first, (0, 0));
+    assert_eq!(difference.largest, 3);
+    // What was drawn is there to look at.
+    assert_eq!(actual, path.with_file_name("bounce.png.actual.png"));
+    let written = png::decode(&std::fs::read(&actual).unwrap()).unwrap();
+    assert_eq!((written.width, written.height), (DEMO_WIDTH, DEMO_HEIGHT));
+    assert_eq!(written.rgba, frame);
+
+    let Err(GoldenError::Mismatch { difference, .. }) = check(&path, &frame, Tolerance::Within(2))
+    else {
+        panic!("expected a mismatch");
+    };
+    assert_eq!(difference.pixels, 1);
+    assert_eq!(difference.first, (DEMO_WIDTH - 1, DEMO_HEIGHT - 1));
+    check(&path, &frame, Tolerance::Within(3)).unwrap();
+    check(&path, &frame, Tolerance::Within(255)).unwrap();
+}
+
+#[test]
+fn a_frame_of_the_wrong_size_or_a_missing_image_fails() {
+    let frame = bounce_frame();
+    let error = golden::check(
+        GOLDEN,
+        DEMO_WIDTH,
+        DEMO_HEIGHT - 1,
+        &frame,
+        Tolerance::Exact,
+    )
+    .unwrap_err();
+    assert!(
+        matches!(
+            error,
+            GoldenError::Size { expected, actual }
+                if expected == (DEMO_WIDTH, DEMO_HEIGHT) && actual == (DEMO_WIDTH, DEMO_HEIGHT - 1)
+        ),
+        "{:?}",
+        error
+    );
+
+    let missing = std::env::temp_dir().join("microcvm-golden-test-missing/none.png");
+    let error = check(&missing, &frame, Tolerance::Exact).unwrap_err();
+    assert!(matches!(error, GoldenError::Io { .. }), "{:?}", error);
+
+    let broken = scratch("broken").with_file_name("broken.png");
+    std::fs::write(&broken, b"not a png").unwrap();
+    let error = check(&broken, &frame, Tolerance::Exact).unwrap_err();
+    assert!(matches!(error, GoldenError::Png { .. }), "{:?}", error);
+}
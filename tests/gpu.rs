@@ -0,0 +1,116 @@
+// Choosing a GPU has to look only at the adapters on the backend asked for, find a named one
+// whatever its case, and say what it was asked for and what there is when nothing fits.
+#![cfg(feature = "window")]
+
+use microcvm_rs::gpu::{AdapterPreference, GpuBackend, GpuConfig, GpuError};
+use pixels::wgpu::{AdapterInfo, Backend, Backends, DeviceType, PowerPreference};
+
+fn adapter(name: &str, backend: Backend, device_type: DeviceType) -> AdapterInfo {
+    AdapterInfo {
+        name: String::from(name),
+        vendor: 0,
+        device: 0,
+        device_type,
+        driver: String::new(),
+        driver_info: String::new(),
+        backend,
+    }
+}
+
+fn adapters() -> Vec<AdapterInfo> {
+    vec![
+        adapter("Intel UHD 620", Backend::Vulkan, DeviceType::IntegratedGpu),
+        adapter("NVIDIA RTX 3060", Backend::Vulkan, DeviceType::DiscreteGpu),
+        adapter("llvmpipe", Backend::Gl, DeviceType::Cpu),
+    ]
+}
+
+fn config(backend: GpuBackend, adapter: AdapterPreference) -> GpuConfig {
+    GpuConfig { backend, adapter }
+}
+
+#[test]
+fn named_adapters_are_found_on_their_backend() {
+    let named = |name: &str| AdapterPreference::Named(String::from(name));
+    let selection = config(GpuBackend::Vulkan, named("rtx"))
+        .select_from(&adapters())
+        .unwrap();
+    assert_eq!(selection.name.as_deref(), Some("NVIDIA RTX 3060"));
+    assert_eq!(selection.backends, Backends::VULKAN);
+    assert_eq!(selection.power_preference, PowerPreference::HighPerformance);
+
+    // An integrated GPU is asked for as the low power one.
+    let selection = config(GpuBackend::Vulkan, named("intel"))
+        .select_from(&adapters())
+        .unwrap();
+    assert_eq!(selection.power_preference, PowerPreference::LowPower);
+
+    let selection = config(GpuBackend::Gl, named("LLVMPIPE"))
+        .select_from(&adapters())
+        .unwrap();
+    assert_eq!(selection.backends, Backends::GL);
+}
+
+#[test]
+fn power_preferences_keep_the_backend() {
+    for (adapter, power) in [
+        (AdapterPreference::LowPower, PowerPreference::LowPower),
+        (
+            AdapterPreference::HighPerformance,
+            PowerPreference::HighPerformance,
+        ),
+    ] {
+        let selection = config(GpuBackend::Vulkan, adapter)
+            .select_from(&adapters())
+            .unwrap();
+        assert_eq!(selection.backends, Backends::VULKAN);
+        assert_eq!(selection.power_preference, power);
+        assert_eq!(selection.name, None);
+    }
+}
+
+#[test]
+fn no_adapter_names_the_request_and_what_there_is() {
+    // The RTX is there, but not on GL.
+    let error = config(
+        GpuBackend::Gl,
+        AdapterPreference::Named(String::from("rtx")),
+    )
+    .select_from(&adapters())
+    .unwrap_err();
+    assert!(
+        matches!(
+            &error,
+            GpuError::NoAdapter { backend: GpuBackend::Gl, adapter: AdapterPreference::Named(name), available }
+                if name == "rtx" && available.len() == 3
+        ),
+        "{:?}",
+        error
+    );
+    assert_eq!(
+        error.to_string(),
+        "no GPU adapter matching `rtx` on the `gl` backend; available: \
+         Intel UHD 620 (Vulkan, IntegratedGpu), NVIDIA RTX 3060 (Vulkan, DiscreteGpu), \
+         llvmpipe (Gl, Cpu)"
+    );
+
+    // Nothing at all on the backend fails whatever the preference.
+    let error = config(GpuBackend::Metal, AdapterPreference::HighPerformance)
+        .select_from(&adapters())
+        .unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .starts_with("no GPU adapter matching `high-performance` on the `metal` backend; "),
+        "{}",
+        error
+    );
+
+    let error = config(GpuBackend::Vulkan, AdapterPreference::Default)
+        .select_from(&[])
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "no GPU adapter matching `default` on the `vulkan` backend, and no adapters on any backend"
+    );
+}
@@ -0,0 +1,91 @@
+// A host call has to run the handler registered under its number with the guest's registers
+// and memory to hand, and a number with no handler has to stop the guest with an error that
+// names it.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::cpu::{HaltReason, Register};
+use microcvm_rs::error::VmError;
+use microcvm_rs::fault::{FAULT_UNREGISTERED_HCALL, fault_code};
+
+const SUM: u8 = 7;
+
+const PROGRAM: &str = "
+        mov r1, 40
+        mov r2, 2
+        hcall 7
+        mov r0, r3
+        hlt
+";
+
+#[test]
+fn a_handler_sums_two_registers_into_a_third() {
+    let mut vm = MicroCvm::builder()
+        .hcall(
+            SUM,
+            Box::new(|ctx| {
+                let sum = ctx.reg(Register::R1) + ctx.reg(Register::R2);
+                ctx.set_reg(Register::R3, sum);
+                Ok(())
+            }),
+        )
+        .build();
+    vm.load_program(&assemble(PROGRAM).unwrap()).unwrap();
+    assert_eq!(vm.run().unwrap(), HaltReason::Halted { code: 42 });
+    assert_eq!(vm.cpu()[Register::R3], 42);
+}
+
+#[test]
+fn handlers_reach_memory_and_can_be_registered_late() {
+    let mut vm = MicroCvm::builder().build();
+    vm.load_program(&assemble("hcall 3\nload r0, [0x2001]\nhlt").unwrap())
+        .unwrap();
+    vm.cpu_mut().memory_mut()[0x2000] = 0x20;
+    vm.cpu_mut().register_hcall(
+        3,
+        Box::new(|ctx| {
+            let value = ctx.read_mem(0x2000)?;
+            ctx.write_mem(0x2001, value + 1)
+        }),
+    );
+    assert_eq!(vm.run().unwrap(), HaltReason::Halted { code: 0x21 });
+}
+
+#[test]
+fn unregistered_numbers_fault() {
+    let mut vm = MicroCvm::builder().hcall(SUM, Box::new(|_| Ok(()))).build();
+    vm.load_program(&assemble("nop\nhcall 9\nhlt").unwrap())
+        .unwrap();
+    let error = vm.run().unwrap_err();
+    assert!(
+        matches!(
+            error.cause(),
+            VmError::UnregisteredHcall { number: 9, pc: 1 }
+        ),
+        "{:?}",
+        error
+    );
+    assert_eq!(
+        error.cause().to_string(),
+        "Unregistered host call: 9 (pc 0x0001)"
+    );
+    assert_eq!(fault_code(&error), FAULT_UNREGISTERED_HCALL);
+    assert_eq!(vm.cpu().pc, 1);
+}
+
+#[test]
+fn handler_errors_stop_the_guest() {
+    let mut vm = MicroCvm::builder()
+        .hcall(
+            SUM,
+            Box::new(|ctx| Err(VmError::DivisionByZero { pc: ctx.pc() })),
+        )
+        .build();
+    vm.load_program(&assemble(PROGRAM).unwrap()).unwrap();
+    let error = vm.run().unwrap_err();
+    assert!(
+        matches!(error.cause(), VmError::DivisionByZero { pc: 6 }),
+        "{:?}",
+        error
+    );
+}
@@ -0,0 +1,93 @@
+// On a display with more than one physical pixel to the logical one, the frame has to be
+// scaled in physical pixels and the pointer mapped onto it through the scale factor, so it
+// lands on the framebuffer pixel under it at 1.0, 1.5 and 2.0 alike.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::letterbox::{Placement, place};
+use microcvm_rs::mouse::MOUSE_RELATIVE;
+
+// A window `width` x `height` logical pixels on a display at `scale_factor`, in the
+// physical pixels it really has.
+fn physical_placement(width: f64, height: f64, scale_factor: f64) -> Placement {
+    let (width, height) = (width * scale_factor, height * scale_factor);
+    place(320, 200, width as u32, height as u32, false)
+}
+
+#[test]
+fn the_frame_scales_in_physical_pixels() {
+    // 640x400 logical is 2x at 1.0, 3x at 1.5 and 4x at 2.0.
+    for (scale_factor, scale) in [(1.0, 2), (1.5, 3), (2.0, 4)] {
+        let placement = physical_placement(640.0, 400.0, scale_factor);
+        assert_eq!(placement.scale, scale);
+        assert_eq!((placement.x, placement.y), (0, 0));
+        assert_eq!(placement.width, 320 * scale);
+    }
+    // 1.5 of 700x400 is 1050x600, 3x with 45 physical pixels either side.
+    let placement = physical_placement(700.0, 400.0, 1.5);
+    assert_eq!((placement.scale, placement.x, placement.y), (3, 45, 0));
+}
+
+#[test]
+fn pointers_land_on_the_pixel_under_them() {
+    for scale_factor in [1.0, 1.5, 2.0] {
+        let placement = physical_placement(700.0, 400.0, scale_factor);
+        // The logical pixel the frame starts at, and how many logical pixels a frame pixel is.
+        let left = placement.x as f64 / scale_factor;
+        let pixel = placement.scale as f64 / scale_factor;
+        for (column, row) in [(0, 0), (1, 1), (160, 100), (319, 199)] {
+            // The middle of the frame pixel, in logical window pixels.
+            let x = left + (column as f64 + 0.5) * pixel;
+            let y = (row as f64 + 0.5) * pixel;
+            let (fx, fy) = placement.to_frame(x, y, scale_factor);
+            assert_eq!(
+                (fx as u32, fy as u32),
+                (column, row),
+                "{} at {}",
+                scale_factor,
+                column
+            );
+        }
+    }
+}
+
+#[test]
+fn the_mouse_reads_the_mapped_position() {
+    let mut vm = MicroCvm::builder().resolution(4, 3).build();
+    let mut pointer = |scale_factor: f64, x: f64, y: f64| {
+        // A window 10x7 logical pixels, however many physical pixels that is.
+        let placement = place(
+            4,
+            3,
+            (10.0 * scale_factor) as u32,
+            (7.0 * scale_factor) as u32,
+            false,
+        );
+        vm.inject_mouse_position(&placement, x, y, scale_factor);
+        let mouse = vm.cpu().mouse();
+        (placement.scale, mouse.x, mouse.y)
+    };
+
+    // 10x7 physical fits the frame at 2x, 8x6 at 1, 0.
+    assert_eq!(pointer(1.0, 1.0, 0.0), (2, 0, 0));
+    assert_eq!(pointer(1.0, 8.9, 5.9), (2, 3, 2));
+    // 15x10 physical is 3x, 12x9 at 1, 0.
+    assert_eq!(pointer(1.5, 1.0, 0.0), (3, 0, 0));
+    assert_eq!(pointer(1.5, 5.0, 3.0), (3, 2, 1));
+    // 20x14 physical is 4x, 16x12 at 2, 1.
+    assert_eq!(pointer(2.0, 1.0, 0.5), (4, 0, 0));
+    assert_eq!(pointer(2.0, 5.0, 3.0), (4, 2, 1));
+    // Over the bars, the pointer stops at the edge.
+    assert_eq!(pointer(2.0, 0.0, 6.9), (4, 0, 2));
+    assert_eq!(pointer(2.0, 9.9, 0.0), (4, 3, 0));
+}
+
+#[test]
+fn relative_mode_ignores_the_position() {
+    let mut vm = MicroCvm::builder().resolution(4, 3).build();
+    let placement = place(4, 3, 8, 6, false);
+    vm.inject_mouse_position(&placement, 5.0, 3.0, 1.0);
+    vm.cpu_mut().mouse_mut().control = MOUSE_RELATIVE;
+    vm.inject_mouse_position(&placement, 0.0, 0.0, 2.0);
+    let mouse = vm.cpu().mouse();
+    assert_eq!((mouse.x, mouse.y), (2, 1));
+}
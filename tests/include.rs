@@ -0,0 +1,155 @@
+// `.include` has to assemble a file as if its lines were where the directive is, once however
+// often it's named, and point errors inside it at the file and line as well as the
+// `.include`. `microcvm asm` looks beside the source and then in each `-I` directory.
+
+use std::path::Path;
+use std::process::Command;
+
+use microcvm_rs::asm::{Assembler, assemble, listing};
+use microcvm_rs::segment::SegmentKind;
+
+// A resolver over named in-memory files.
+fn files(files: &'static [(&'static str, &'static str)]) -> Assembler {
+    Assembler::new().resolver(Box::new(move |name| {
+        files
+            .iter()
+            .find(|(file, _)| *file == name)
+            .map(|(_, source)| source.to_string())
+            .ok_or_else(|| format!("no `{}`", name))
+    }))
+}
+
+#[test]
+fn included_lines_assemble_in_place() {
+    let assembler = files(&[
+        ("inc.asm", "inc r0\n.include \"twice.asm\"\nret"),
+        ("twice.asm", "bump: inc r1\n.include \"inc.asm\""),
+    ]);
+    let source = "call bump\n.include \"inc.asm\"\n.include \"twice.asm\"\nhlt";
+    // inc.asm's own include of twice.asm is the only one that counts.
+    assert_eq!(
+        assembler.assemble(source).unwrap(),
+        [0x16, 0x05, 0x00, 0x07, 0x00, 0x07, 0x01, 0x17, 0xFF]
+    );
+
+    let text = assembler.listing(source).unwrap();
+    let rows: Vec<&str> = text.lines().collect();
+    assert_eq!(
+        rows,
+        [
+            "    1   0000  16 05 00     call bump",
+            "    2   0003  07 00        .include \"inc.asm\"",
+            "        0005  07 01",
+            "        0007  17",
+            "    3                      .include \"twice.asm\"",
+            "    4   0008  ff           hlt",
+        ]
+    );
+}
+
+#[test]
+fn includes_keep_their_width_and_section() {
+    let assembler = files(&[("wide.asm", ".width 16\n.section data\nmov r0, 300")]);
+    let error = assembler
+        .assemble_segments(".include \"wide.asm\"\nmov r0, 300")
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "line 2: value 300 is out of range (-128..=255)"
+    );
+    let program = assembler
+        .assemble_segments(".include \"wide.asm\"\nhlt")
+        .unwrap();
+    let kinds: Vec<SegmentKind> = program
+        .segments
+        .iter()
+        .map(|segment| segment.kind)
+        .collect();
+    assert_eq!(kinds, [SegmentKind::Data, SegmentKind::Code]);
+}
+
+#[test]
+fn errors_point_into_the_included_file() {
+    let assembler = files(&[
+        ("outer.asm", "nop\n.include \"inner.asm\""),
+        ("inner.asm", "nop\nnop\njmp nowhere"),
+        ("bad.asm", "frob r0"),
+    ]);
+    for (source, expected) in [
+        (
+            "hlt\n.include \"outer.asm\"",
+            "line 2: in `outer.asm` line 2: in `inner.asm` line 3: undefined symbol `nowhere`",
+        ),
+        (
+            ".include \"bad.asm\"",
+            "line 1: in `bad.asm` line 1: unknown instruction `frob`",
+        ),
+        ("nop\n.include \"gone.asm\"", "line 2: no `gone.asm`"),
+        (
+            ".include \"std/gone.asm\"",
+            "line 1: the standard library has no `std/gone.asm`",
+        ),
+        (
+            ".include gone.asm",
+            "line 1: `.include` takes a file name in quotes",
+        ),
+    ] {
+        let error = assembler.assemble(source).unwrap_err();
+        assert_eq!(error.to_string(), expected, "{}", source);
+    }
+
+    // Without a resolver only the standard library is there.
+    let error = assemble(".include \"outer.asm\"").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "line 1: only the standard library can be included, not `outer.asm`"
+    );
+    assert!(listing(".include \"std/print.asm\"").is_ok());
+}
+
+#[test]
+fn the_command_line_searches_its_directories() {
+    let dir = std::env::temp_dir().join("microcvm-include-test");
+    let _ = std::fs::remove_dir_all(&dir);
+    let lib = dir.join("lib");
+    std::fs::create_dir_all(&lib).unwrap();
+    let write = |path: &Path, text: &str| std::fs::write(path, text).unwrap();
+    write(
+        &dir.join("main.asm"),
+        ".include \"beside.asm\"\n.include \"found.asm\"\nhlt\n",
+    );
+    write(&dir.join("beside.asm"), "inc r0\n");
+    write(&lib.join("found.asm"), "inc r1\n");
+    // The one beside the source wins.
+    write(&lib.join("beside.asm"), "inc r2\n");
+
+    let asm = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_microcvm-rs"))
+            .arg("asm")
+            .arg(dir.join("main.asm"))
+            .arg("-o")
+            .arg(dir.join("main.bin"))
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    let output = asm(&["-I", lib.to_str().unwrap()]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        std::fs::read(dir.join("main.bin")).unwrap(),
+        [0x07, 0x00, 0x07, 0x01, 0xFF]
+    );
+
+    let output = asm(&[]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("main.asm:2: no `found.asm` beside the source or in an `-I` directory"),
+        "{}",
+        stderr
+    );
+}
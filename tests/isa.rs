@@ -0,0 +1,138 @@
+// The instruction table is what the decoder, the assembler and `microcvm isa` all read, so every
+// instruction has to be in it exactly once, and decode back to itself from its own byte.
+
+use std::process::Command;
+
+use microcvm_rs::cpu::{OpcodeType, VideoOpcodeType};
+use microcvm_rs::isa::{decode, info, instruction_table, variants, video_operation_table};
+
+const OPCODES: [OpcodeType; 45] = [
+    OpcodeType::Load,
+    OpcodeType::Store,
+    OpcodeType::Add,
+    OpcodeType::Sub,
+    OpcodeType::Jmp,
+    OpcodeType::Hlt,
+    OpcodeType::Mov,
+    OpcodeType::Inc,
+    OpcodeType::Div,
+    OpcodeType::Mul,
+    OpcodeType::Hcall,
+    OpcodeType::Video,
+    OpcodeType::Jr,
+    OpcodeType::Jrz,
+    OpcodeType::Jrnz,
+    OpcodeType::Djnz,
+    OpcodeType::Test,
+    OpcodeType::Bset,
+    OpcodeType::Bclr,
+    OpcodeType::Btst,
+    OpcodeType::Memset,
+    OpcodeType::Memcpy,
+    OpcodeType::Call,
+    OpcodeType::Ret,
+    OpcodeType::Clc,
+    OpcodeType::Stc,
+    OpcodeType::Cmc,
+    OpcodeType::Pushf,
+    OpcodeType::Popf,
+    OpcodeType::Ei,
+    OpcodeType::Di,
+    OpcodeType::Int,
+    OpcodeType::Iret,
+    OpcodeType::FxMul,
+    OpcodeType::FxDiv,
+    OpcodeType::FxMulImm,
+    OpcodeType::Dec,
+    OpcodeType::Clr,
+    OpcodeType::Movm,
+    OpcodeType::LoadInc,
+    OpcodeType::StoreInc,
+    OpcodeType::LoadSp,
+    OpcodeType::StoreSp,
+    OpcodeType::Jtab,
+    OpcodeType::Nop,
+];
+
+// Doesn't build when an instruction is added without going into `OPCODES`.
+#[allow(dead_code)]
+fn listed(opcode: OpcodeType) {
+    use OpcodeType::*;
+    match opcode {
+        Load | Store | Add | Sub | Jmp | Hlt | Mov | Inc | Div | Mul | Hcall | Video | Jr | Jrz
+        | Jrnz | Djnz | Test | Bset | Bclr | Btst | Memset | Memcpy | Call | Ret | Clc | Stc
+        | Cmc | Pushf | Popf | Ei | Di | Int | Iret | FxMul | FxDiv | FxMulImm | Dec | Clr
+        | Movm | LoadInc | StoreInc | LoadSp | StoreSp | Jtab | Nop => {}
+    }
+}
+
+#[test]
+fn every_instruction_is_in_the_table_once() {
+    for opcode in OPCODES {
+        let entries = instruction_table()
+            .iter()
+            .filter(|info| info.opcode == opcode)
+            .count();
+        assert_eq!(entries, 1, "{:?}", opcode);
+        assert_eq!(info(opcode).byte(), opcode as u8);
+        assert_eq!(decode(opcode as u8).map(|info| info.opcode), Some(opcode));
+        assert_eq!(OpcodeType::try_from(opcode as u8).ok(), Some(opcode));
+        // `load` and `store` have more than one variant, told apart by their operands.
+        assert!(variants(opcode.mnemonic()).any(|info| info.opcode == opcode));
+    }
+    assert_eq!(instruction_table().len(), OPCODES.len());
+
+    // Nothing else decodes.
+    for byte in 0..=u8::MAX {
+        let known = OPCODES.iter().any(|&opcode| opcode as u8 == byte);
+        assert_eq!(decode(byte).is_some(), known, "{:#04x}", byte);
+    }
+}
+
+#[test]
+fn every_video_operation_is_in_its_table_once() {
+    let operations = [
+        VideoOpcodeType::Fill,
+        VideoOpcodeType::Clear,
+        VideoOpcodeType::SetPixel,
+        VideoOpcodeType::FillRect,
+        VideoOpcodeType::Vsync,
+        VideoOpcodeType::FadeTo,
+        VideoOpcodeType::CopyRect,
+    ];
+    for operation in operations {
+        let entries = video_operation_table()
+            .iter()
+            .filter(|info| info.operation == operation)
+            .count();
+        assert_eq!(entries, 1, "{:?}", operation);
+        assert_eq!(
+            VideoOpcodeType::try_from(operation as u8).ok(),
+            Some(operation)
+        );
+    }
+    assert_eq!(video_operation_table().len(), operations.len());
+}
+
+#[test]
+fn isa_json_lists_the_table() {
+    let output = Command::new(env!("CARGO_BIN_EXE_microcvm-rs"))
+        .args(["isa", "--json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let json = String::from_utf8(output.stdout).unwrap();
+    assert!(json.trim_start().starts_with('[') && json.trim_end().ends_with(']'));
+    assert_eq!(
+        json.matches("\"mnemonic\"").count(),
+        instruction_table().len()
+    );
+    assert!(
+        json.contains(
+            "{\"mnemonic\": \"djnz\", \"opcode\": 15, \"length\": 3, \"operand_count\": 2, \
+             \"operands\": [\"register\", \"offset\"]"
+        ),
+        "{}",
+        json
+    );
+}
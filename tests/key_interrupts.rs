@@ -0,0 +1,138 @@
+// Every queued key event has to raise the keyboard interrupt unless the guest masks it, carry
+// the cycle count it was queued at for the handler to read back, and wait behind vblank when
+// both are pending.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::interrupt::VECTOR_VBLANK;
+use microcvm_rs::keyboard::{KEY_INTERRUPT_ENABLE, VECTOR_KEYBOARD};
+
+// Interrupts on key events. The handler copies each event's stamp, scancode and status to
+// 0x0300 onwards, one event after another, counting them in r6; vblank counts in r5. r7 hands
+// out the order they ran in: r4 for the last vblank, r3 for the last key handler.
+const RECORDER: &str = "
+        mov r0, 0x00
+        store [0xFF90], r0      ; vector table at 0x0200
+        mov r0, 0x02
+        store [0xFF91], r0
+        mov r0, 1
+        store [0xFF32], r0      ; interrupt on key events
+        mov r7, 1
+        ei
+idle:   jmp idle
+
+on_key: mov r3, r7
+        inc r7
+next:   load r0, [0xFF30]
+        btst r0, 0
+        jrz done
+        load r1, [0xFF33]
+        store [0x0300], r1
+        load r1, [0xFF34]
+        store [0x0301], r1
+        load r1, [0xFF35]
+        store [0x0302], r1
+        load r1, [0xFF36]
+        store [0x0303], r1
+        load r1, [0xFF31]
+        store [0x0304], r1
+        store [0x0305], r0
+        inc r6
+        store [0xFF30], r0      ; pop the event
+        jr next
+done:   iret
+
+on_vblank:
+        mov r4, r7
+        inc r7
+        inc r5
+        iret
+
+        .org 0x0200
+        .dw on_vblank, 0, on_key
+";
+
+fn recorder() -> MicroCvm {
+    let mut vm = MicroCvm::builder().build();
+    vm.load_program(&assemble(RECORDER).unwrap()).unwrap();
+    vm.run_for(100).unwrap();
+    vm
+}
+
+// The stamp, scancode and status the handler last copied out.
+fn recorded(vm: &MicroCvm) -> (u32, u8, u8) {
+    let memory = vm.cpu().memory();
+    let stamp = u32::from_le_bytes(memory[0x0300..0x0304].try_into().unwrap());
+    (stamp, memory[0x0304], memory[0x0305])
+}
+
+#[test]
+fn the_handler_reads_the_cycle_each_event_was_queued_at() {
+    let mut vm = recorder();
+    for (count, (scancode, pressed, wait)) in
+        [(0x1E, true, 0), (0x1E, false, 37), (0x30, true, 1000)]
+            .into_iter()
+            .enumerate()
+    {
+        vm.run_for(wait).unwrap();
+        let queued = vm.cpu().cycles;
+        vm.push_key(scancode, pressed);
+        assert_eq!(vm.cpu().keyboard().events[0].cycles, queued);
+        vm.run_for(200).unwrap();
+        let status = if pressed { 0b11 } else { 0b01 };
+        assert_eq!(recorded(&vm), (queued as u32, scancode, status));
+        assert_eq!(vm.cpu().registers[6], count as u16 + 1);
+        assert!(vm.cpu().keyboard().events.is_empty());
+    }
+}
+
+#[test]
+fn stamps_are_the_low_32_bits_of_the_cycle_count() {
+    let mut vm = recorder();
+    vm.cpu_mut().cycles += 0x1_2345_6789;
+    let queued = vm.cpu().cycles;
+    vm.push_key(0x1C, true);
+    vm.run_for(200).unwrap();
+    assert_eq!(recorded(&vm).0, queued as u32);
+}
+
+#[test]
+fn a_masked_keyboard_queues_without_interrupting() {
+    let mut vm = recorder();
+    vm.cpu_mut().keyboard_mut().control &= !KEY_INTERRUPT_ENABLE;
+    for scancode in [0x10, 0x11] {
+        vm.push_key(scancode, true);
+        vm.run_for(50).unwrap();
+    }
+    assert_eq!(vm.cpu().interrupts.pending, 0);
+    assert_eq!(vm.cpu().registers[6], 0);
+    let events = &vm.cpu().keyboard().events;
+    assert_eq!(events.len(), 2);
+    assert!(events[0].cycles < events[1].cycles);
+
+    // Unmasking doesn't interrupt for what's already queued; the next event brings them all.
+    vm.cpu_mut().keyboard_mut().control = KEY_INTERRUPT_ENABLE;
+    vm.run_for(50).unwrap();
+    assert_eq!(vm.cpu().registers[6], 0);
+    vm.push_key(0x12, true);
+    vm.run_for(300).unwrap();
+    assert_eq!(vm.cpu().registers[6], 3);
+    assert_eq!(recorded(&vm).1, 0x12);
+}
+
+#[test]
+fn vblank_goes_before_the_keyboard() {
+    let mut vm = recorder();
+    vm.push_key(0x1E, true);
+    vm.tick_frame();
+    assert_eq!(
+        vm.cpu().interrupts.pending,
+        1 << VECTOR_VBLANK | 1 << VECTOR_KEYBOARD
+    );
+    vm.run_for(300).unwrap();
+    let registers = vm.cpu().registers;
+    // Vblank ran first, then the key handler, once each.
+    assert_eq!((registers[4], registers[3]), (1, 2));
+    assert_eq!((registers[5], registers[6]), (1, 1));
+    assert_eq!(vm.cpu().interrupts.pending, 0);
+}
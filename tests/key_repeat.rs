@@ -0,0 +1,82 @@
+// A held key has to repeat once its delay is up and then once every interval, on nothing but the
+// clock it is ticked with, and start over after it is let go or the window loses focus.
+
+use microcvm_rs::input::{KeyRepeat, RepeatRate, RepeatUnit};
+
+const A: u8 = 0x1E;
+const B: u8 = 0x30;
+
+// The times in `ticks` at which `held` repeated anything, with what repeated.
+fn repeats(
+    repeat: &mut KeyRepeat,
+    held: &[u8],
+    ticks: impl IntoIterator<Item = u64>,
+) -> Vec<(u64, u8)> {
+    let mut out = Vec::new();
+    for now in ticks {
+        repeat.tick(now, held, |scancode| out.push((now, scancode)));
+    }
+    out
+}
+
+#[test]
+fn the_first_repeat_waits_for_the_delay_then_follows_the_interval() {
+    let mut repeat = KeyRepeat::new(RepeatRate::new());
+    assert_eq!(repeats(&mut repeat, &[A], [0, 100, 499]), []);
+    assert_eq!(repeats(&mut repeat, &[A], [500]), [(500, A)]);
+    // Every 33 ms from there.
+    assert_eq!(
+        repeats(&mut repeat, &[A], 501..=600),
+        [(533, A), (566, A), (599, A)]
+    );
+}
+
+#[test]
+fn a_stalled_host_gets_one_repeat_not_a_burst() {
+    let mut repeat = KeyRepeat::new(RepeatRate::new());
+    repeats(&mut repeat, &[A], [0, 500]);
+    assert_eq!(repeats(&mut repeat, &[A], [2000]), [(2000, A)]);
+    // And the next comes an interval after the late one.
+    assert_eq!(repeats(&mut repeat, &[A], [2032, 2033]), [(2033, A)]);
+}
+
+#[test]
+fn letting_go_starts_the_delay_over() {
+    let mut repeat = KeyRepeat::new(RepeatRate::new());
+    repeats(&mut repeat, &[A], [0, 500]);
+    assert_eq!(repeats(&mut repeat, &[], [510]), []);
+    assert_eq!(repeats(&mut repeat, &[A], [520, 533, 1019]), []);
+    assert_eq!(repeats(&mut repeat, &[A], [1020]), [(1020, A)]);
+}
+
+#[test]
+fn losing_focus_forgets_held_keys() {
+    let mut repeat = KeyRepeat::new(RepeatRate::new());
+    repeats(&mut repeat, &[A], [0, 400]);
+    repeat.reset();
+    // Still down as far as the next tick knows, but held from now on.
+    assert_eq!(repeats(&mut repeat, &[A], [500, 899]), []);
+    assert_eq!(repeats(&mut repeat, &[A], [1000]), [(1000, A)]);
+}
+
+#[test]
+fn keys_repeat_on_their_own_timers_in_frames() {
+    let rate = RepeatRate {
+        delay: 10,
+        interval: 4,
+        unit: RepeatUnit::Frames,
+    };
+    let mut repeat = KeyRepeat::new(rate);
+    repeats(&mut repeat, &[A], 0..3);
+    // B goes down on frame 3 and A stays held.
+    let both = repeats(&mut repeat, &[A, B], 3..=18);
+    assert_eq!(both, [(10, A), (13, B), (14, A), (17, B), (18, A)]);
+
+    // An interval of 0 repeats every tick rather than forever within one.
+    let mut repeat = KeyRepeat::new(RepeatRate {
+        delay: 0,
+        interval: 0,
+        unit: RepeatUnit::Frames,
+    });
+    assert_eq!(repeats(&mut repeat, &[A], 0..3), [(0, A), (1, A), (2, A)]);
+}
@@ -0,0 +1,118 @@
+// A keymap file has to change the scancodes the guest reads for the keys it names and leave the
+// rest of the layout alone, and a key it can't name has to be refused with the names it can.
+#![cfg(feature = "window")]
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::cpu::KEYBOARD_BASE;
+use microcvm_rs::input::{InputConfig, KeymapError};
+use microcvm_rs::keyboard::{KEY_CODE, KEY_STATUS};
+use winit::keyboard::KeyCode;
+
+fn keymap_file(name: &str, contents: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("microcvm-keymap-test-{}", name));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("keymap.toml");
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+// Presses `keys` the way the window does and returns the codes the guest reads, one event at
+// a time.
+fn guest_codes(input: &InputConfig, keys: &[KeyCode]) -> Vec<u16> {
+    let mut vm = MicroCvm::builder().build();
+    for &key in keys {
+        if let Some(scancode) = input.scancode(key) {
+            vm.push_key(scancode, true);
+        }
+    }
+    let mut source = String::new();
+    for register in 0..keys.len() {
+        source += &format!(
+            "load r{}, [{:#x}]\nstore [{:#x}], r7\n",
+            register,
+            KEYBOARD_BASE + KEY_CODE as u16,
+            KEYBOARD_BASE + KEY_STATUS as u16
+        );
+    }
+    vm.load_program(&assemble(&(source + "hlt")).unwrap())
+        .unwrap();
+    vm.run().unwrap();
+    vm.cpu().registers[..keys.len()].to_vec()
+}
+
+#[test]
+fn a_keymap_changes_what_the_guest_reads() {
+    let keys = [
+        KeyCode::KeyA,
+        KeyCode::KeyW,
+        KeyCode::Space,
+        KeyCode::ArrowUp,
+    ];
+    let mut input = InputConfig::new();
+    assert_eq!(guest_codes(&input, &keys), [0x1E, 0x11, 0x39, 0x48]);
+
+    let path = keymap_file(
+        "swap",
+        "# WASD as arrows\n\
+         KeyW = 0x48\n\
+         \"ArrowUp\" = 17   # and the arrow as W\n\
+         \n\
+         Space = 0\n",
+    );
+    input.load_keymap(&path).unwrap();
+    assert_eq!(input.scancode(KeyCode::Space), None);
+    // Space sends nothing now, so the guest reads three events.
+    let codes = guest_codes(&input, &keys);
+    assert_eq!(codes, [0x1E, 0x48, 0x11, 0]);
+}
+
+#[test]
+fn unknown_keys_list_the_names_a_keymap_can_use() {
+    let mut input = InputConfig::new();
+    let path = keymap_file("unknown", "KeyA = 0x20\nKeyQQ = 0x10\n");
+    let error = input.load_keymap(&path).unwrap_err();
+    assert!(
+        matches!(&error, KeymapError::UnknownKey { line: 2, name } if name == "KeyQQ"),
+        "{:?}",
+        error
+    );
+    let message = error.to_string();
+    assert!(
+        message.starts_with("line 2: unknown key `KeyQQ`, expected one of Escape, Digit1, "),
+        "{}",
+        message
+    );
+    for name in ["KeyA", "ArrowUp", "Space", "F11"] {
+        assert!(
+            message.contains(&format!(" {},", name)) || message.ends_with(name),
+            "{}",
+            name
+        );
+    }
+    // The lines before the bad one were applied.
+    assert_eq!(input.scancode(KeyCode::KeyA), Some(0x20));
+}
+
+#[test]
+fn other_mistakes_name_their_line() {
+    for (keymap, expected) in [
+        ("KeyA 0x1E", "line 1: expected `key = scancode`"),
+        (
+            "\nKeyA = 256",
+            "line 2: `256` is not a scancode from 0 to 255",
+        ),
+        (
+            "KeyA = 0xZZ",
+            "line 1: `0xZZ` is not a scancode from 0 to 255",
+        ),
+    ] {
+        let error = InputConfig::new().apply_keymap(keymap).unwrap_err();
+        assert_eq!(error.to_string(), expected, "{:?}", keymap);
+    }
+    let missing = std::env::temp_dir().join("microcvm-keymap-test-missing/none.toml");
+    assert!(matches!(
+        InputConfig::new().load_keymap(missing),
+        Err(KeymapError::Io(_))
+    ));
+}
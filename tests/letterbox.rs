@@ -0,0 +1,133 @@
+// The frame has to go in the middle of the window at the largest whole scale that fits, with
+// every other window pixel the letterbox color, or the border's right around the frame, and
+// the guest's color in place of the host's once it asks for that.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::letterbox::{Letterbox, Placement, place};
+use microcvm_rs::types::Color;
+
+const WHITE: [u8; 4] = [255; 4];
+const BAR: [u8; 4] = [0, 0, 80, 255];
+const BORDER: [u8; 4] = [9, 9, 9, 255];
+
+const LETTERBOX: Letterbox = Letterbox {
+    color: Color::new(0, 0, 80),
+    border: Some(Color::new(9, 9, 9)),
+};
+
+// A white `width` x `height` machine.
+fn white(width: u32, height: u32) -> MicroCvm {
+    let mut vm = MicroCvm::builder().resolution(width, height).build();
+    vm.cpu_mut().video_memory.bytes_mut().fill(255);
+    vm
+}
+
+// The window as a picture: `#` for the frame, `+` for the border, `.` for the bars.
+fn picture(vm: &mut MicroCvm, width: u32, height: u32, letterbox: &Letterbox) -> Vec<String> {
+    let mut out = vec![0; (width * height * 4) as usize];
+    vm.frame_rgba_letterboxed(width, height, letterbox, &mut out);
+    out.chunks_exact(width as usize * 4)
+        .map(|row| {
+            row.chunks_exact(4)
+                .map(|pixel| match pixel.try_into().unwrap() {
+                    WHITE => '#',
+                    BORDER => '+',
+                    BAR => '.',
+                    other => panic!("{:?}", other),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn the_frame_is_centred_at_the_largest_scale_that_fits() {
+    let placement = |window_width, window_height, border| {
+        let Placement {
+            scale, x, y, width, ..
+        } = place(320, 200, window_width, window_height, border);
+        (scale, x, y, width)
+    };
+    assert_eq!(placement(320, 200, false), (1, 0, 0, 320));
+    assert_eq!(placement(1280, 720, false), (3, 160, 60, 960));
+    assert_eq!(placement(1920, 1080, false), (5, 160, 40, 1600));
+    // Room for the border costs a scale step when the fit is exact.
+    assert_eq!(placement(640, 400, false), (2, 0, 0, 640));
+    assert_eq!(placement(640, 400, true), (1, 160, 100, 320));
+    // Too small for 1x, only the top left corner shows.
+    assert_eq!(placement(100, 50, false), (1, 0, 0, 320));
+}
+
+#[test]
+fn bars_and_border_carry_their_colors() {
+    let mut vm = white(2, 2);
+    // Room for 2x inside the border, which fills the window's edge.
+    assert_eq!(
+        picture(&mut vm, 6, 6, &LETTERBOX),
+        ["++++++", "+####+", "+####+", "+####+", "+####+", "++++++"]
+    );
+    // Too short for a border at 2x, so 1x with bars either side.
+    assert_eq!(
+        picture(&mut vm, 9, 4, &LETTERBOX),
+        ["..++++...", "..+##+...", "..+##+...", "..++++..."]
+    );
+    // No border, just bars.
+    let letterbox = Letterbox {
+        border: None,
+        ..LETTERBOX
+    };
+    assert_eq!(
+        picture(&mut vm, 7, 3, &letterbox),
+        ["..##...", "..##...", "......."].map(String::from)
+    );
+    // Black and borderless by default.
+    let mut out = vec![1; 5 * 2 * 4];
+    vm.frame_rgba_letterboxed(5, 2, &Letterbox::new(), &mut out);
+    assert_eq!(out[..4], [0, 0, 0, 255]);
+    assert_eq!(out[4..8], WHITE);
+}
+
+#[test]
+fn a_border_with_no_room_is_left_off_that_side() {
+    let mut vm = white(4, 2);
+    assert_eq!(
+        picture(&mut vm, 5, 3, &LETTERBOX),
+        ["####+", "####+", "+++++"].map(String::from)
+    );
+}
+
+#[test]
+fn the_guest_can_set_the_color() {
+    let mut vm = white(2, 2);
+    let program = assemble(
+        "
+        mov r0, 0x40
+        store [0xFF1A], r0
+        mov r0, 0
+        store [0xFF1B], r0
+        store [0xFF1C], r0      ; 40 00 00
+        mov r0, 0x02
+        store [0xFF13], r0      ; and use it
+        hlt
+",
+    )
+    .unwrap();
+    vm.load_program(&program).unwrap();
+    vm.run().unwrap();
+    let mut out = vec![0; 9 * 4 * 4];
+    vm.frame_rgba_letterboxed(9, 4, &LETTERBOX, &mut out);
+    assert_eq!(out[..4], [0x40, 0, 0, 255]);
+    // The border stays the host's.
+    assert_eq!(out[(9 + 2) * 4..][..4], BORDER);
+    assert_eq!(vm.cpu().letterbox, [0x40, 0, 0]);
+
+    // Until it clears the bit again.
+    vm.load_program(&assemble("mov r0, 0\nstore [0xFF13], r0\nhlt").unwrap())
+        .unwrap();
+    vm.cpu_mut().pc = 0;
+    vm.cpu_mut().halted = false;
+    vm.run().unwrap();
+    vm.frame_rgba_letterboxed(9, 4, &LETTERBOX, &mut out);
+    assert_eq!(out[..4], BAR);
+}
@@ -0,0 +1,95 @@
+// Diagnostics go through `log`, so an embedder can capture them: a fault has to come out as
+// exactly one error record naming the pc it happened at, and a clean run as none.
+
+use std::cell::RefCell;
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::fault::FaultPolicy;
+
+thread_local! {
+    static RECORDS: RefCell<Vec<(Level, String)>> = const { RefCell::new(Vec::new()) };
+}
+
+// Keeps each test's records on its own thread, since the tests share the one logger.
+struct Capture;
+
+impl Log for Capture {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        RECORDS.with(|records| {
+            records
+                .borrow_mut()
+                .push((record.level(), record.args().to_string()))
+        });
+    }
+
+    fn flush(&self) {}
+}
+
+static CAPTURE: Capture = Capture;
+
+// The error records `vm` logs while it runs, and whether it ran without faulting.
+fn errors(mut vm: MicroCvm, program: &[u8]) -> (bool, Vec<String>) {
+    // Only the first test to get here installs it.
+    let _ = log::set_logger(&CAPTURE);
+    log::set_max_level(LevelFilter::Trace);
+    RECORDS.with(|records| records.borrow_mut().clear());
+    vm.load_program(program).unwrap();
+    let ok = vm.run().is_ok();
+    let errors = RECORDS.with(|records| {
+        records
+            .borrow()
+            .iter()
+            .filter(|(level, _)| *level == Level::Error)
+            .map(|(_, message)| message.clone())
+            .collect()
+    });
+    (ok, errors)
+}
+
+#[test]
+fn a_memory_fault_is_one_error_with_its_pc() {
+    // inc r0; load r0, [0x0101], past the end of 256 bytes of memory
+    let vm = MicroCvm::builder().memory_size(256).build();
+    let (ok, errors) = errors(vm, &[0x07, 0x00, 0x01, 0x00, 0x01, 0x01]);
+    assert!(!ok);
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert!(errors[0].contains("0x0002"), "{}", errors[0]);
+}
+
+#[test]
+fn a_clean_run_logs_no_errors() {
+    let program = assemble("mov r0, 3\nloop: djnz r0, loop\nhlt").unwrap();
+    let (ok, errors) = errors(MicroCvm::builder().build(), &program);
+    assert!(ok);
+    assert_eq!(errors, Vec::<String>::new());
+}
+
+#[test]
+fn a_fault_the_guest_handles_is_not_an_error() {
+    let program = assemble(
+        "
+        mov r0, 0x00
+        store [0xFF90], r0
+        mov r0, 0x02
+        store [0xFF91], r0
+        div r0, 0
+        hlt
+on_fault:
+        popf
+        ret
+        .org 0x0200
+        .dw 0, on_fault
+",
+    )
+    .unwrap();
+    let vm = MicroCvm::builder().fault_policy(FaultPolicy::Trap).build();
+    let (ok, errors) = errors(vm, &program);
+    assert!(ok);
+    assert_eq!(errors, Vec::<String>::new());
+}
@@ -0,0 +1,128 @@
+// Two machines joined by a mailbox pair have to hand bytes to each other in order, and a full
+// mailbox has to drop what's sent to it and say so rather than stall the sender.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::bus::Device;
+use microcvm_rs::mailbox::{
+    MAILBOX_AVAILABLE, MAILBOX_FULL, MAILBOX_IN, MAILBOX_OUT, MAILBOX_OVERFLOW, MAILBOX_STATUS,
+    Mailbox,
+};
+
+const REGISTERS: &str = "
+        .equ status, 0xFF40
+        .equ inbox, 0xFF41
+        .equ outbox, 0xFF42
+";
+
+// Sends a counter, waits for it to come back incremented, and does so 100 times.
+const PING: &str = "
+        mov r0, 0
+        mov r3, 100
+send:   store [outbox], r0
+wait:   load r1, [status]
+        btst r1, 0
+        jrz wait
+        load r0, [inbox]
+        store [inbox], r0
+        inc r0
+        djnz r3, send
+        hlt
+";
+
+// Sends back every byte it receives, plus one.
+const PONG: &str = "
+        mov r3, 100
+wait:   load r1, [status]
+        btst r1, 0
+        jrz wait
+        load r0, [inbox]
+        store [inbox], r0
+        inc r0
+        store [outbox], r0
+        djnz r3, wait
+        hlt
+";
+
+fn machine(mailbox: Mailbox, source: &str) -> MicroCvm {
+    let mut vm = MicroCvm::builder().mailbox(mailbox).build();
+    vm.load_program(&assemble(&(String::from(REGISTERS) + source)).unwrap())
+        .unwrap();
+    vm
+}
+
+#[test]
+fn two_machines_ping_pong_a_counter() {
+    // However the two are interleaved.
+    for slice in [1, 7, 50] {
+        let (a, b) = Mailbox::pair(4);
+        let mut ping = machine(a, PING);
+        let mut pong = machine(b, PONG);
+        while !(ping.halted() && pong.halted()) {
+            ping.run_for(slice).unwrap();
+            pong.run_for(slice).unwrap();
+        }
+        assert_eq!(ping.cpu().registers[0], 200, "{}", slice);
+        assert_eq!(pong.cpu().registers[0], 199, "{}", slice);
+        assert_eq!(ping.cpu().mailbox().unwrap().pending(), 0);
+        assert_eq!(pong.cpu().mailbox().unwrap().pending(), 0);
+    }
+}
+
+#[test]
+fn two_threads_ping_pong_a_counter() {
+    let (a, b) = Mailbox::pair(1);
+    let mut ping = machine(a, PING);
+    let mut pong = machine(b, PONG);
+    let pong = std::thread::spawn(move || {
+        while !pong.halted() {
+            pong.run_for(100).unwrap();
+        }
+        pong
+    });
+    while !ping.halted() {
+        ping.run_for(100).unwrap();
+    }
+    assert_eq!(ping.cpu().registers[0], 200);
+    assert_eq!(pong.join().unwrap().cpu().registers[0], 199);
+}
+
+#[test]
+fn a_full_mailbox_drops_bytes_and_says_so() {
+    let (mut a, mut b) = Mailbox::pair(2);
+    assert_eq!(a.read(MAILBOX_STATUS as u16), 0);
+    a.write(MAILBOX_OUT as u16, 1);
+    a.write(MAILBOX_OUT as u16, 2);
+    assert_eq!(a.read(MAILBOX_STATUS as u16), MAILBOX_FULL);
+    a.write(MAILBOX_OUT as u16, 3);
+    assert_eq!(
+        a.read(MAILBOX_STATUS as u16),
+        MAILBOX_FULL | MAILBOX_OVERFLOW
+    );
+    assert!(!a.send(4));
+
+    // The other end got the first two.
+    assert_eq!(b.read(MAILBOX_STATUS as u16), MAILBOX_AVAILABLE);
+    assert_eq!(b.read(MAILBOX_IN as u16), 1);
+    b.write(MAILBOX_IN as u16, 0);
+    assert_eq!(b.receive(), Some(2));
+    assert_eq!(b.receive(), None);
+    assert_eq!(b.read(MAILBOX_STATUS as u16), 0);
+    assert_eq!(b.read(MAILBOX_IN as u16), 0);
+
+    // Room again, but the overflow stays until it's cleared.
+    assert_eq!(a.read(MAILBOX_STATUS as u16), MAILBOX_OVERFLOW);
+    a.write(MAILBOX_STATUS as u16, 0);
+    assert_eq!(a.read(MAILBOX_STATUS as u16), 0);
+}
+
+#[test]
+fn without_a_mailbox_the_registers_read_empty() {
+    let mut vm = MicroCvm::builder().build();
+    let source = String::from(REGISTERS)
+        + "mov r0, 5\nstore [outbox], r0\nload r1, [status]\nload r2, [inbox]\nhlt";
+    vm.load_program(&assemble(&source).unwrap()).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.cpu().registers[1..3], [0, 0]);
+    assert!(vm.cpu().mailbox().is_none());
+}
@@ -0,0 +1,127 @@
+// A program file has to land at its load address whichever way it is read: mapped with the
+// mmap feature, read into a buffer without it. A broken file, one too big for memory or one
+// that changes size while it loads has to be refused.
+
+use std::cell::RefCell;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use log::{LevelFilter, Log, Metadata, Record};
+use microcvm_rs::MicroCvm;
+use microcvm_rs::program::Program;
+
+thread_local! {
+    static MESSAGES: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+struct Capture;
+
+impl Log for Capture {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        MESSAGES.with(|messages| messages.borrow_mut().push(record.args().to_string()));
+    }
+
+    fn flush(&self) {}
+}
+
+static CAPTURE: Capture = Capture;
+
+fn temp_file(name: &str, contents: &[u8]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("microcvm-mmap-test-{}", name));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("program.bin");
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+// How the loads on this thread so far read their files: "mapped" or "read" each.
+fn load_paths() -> Vec<String> {
+    MESSAGES.with(|messages| {
+        messages
+            .borrow()
+            .iter()
+            .filter_map(|message| message.split_whitespace().next())
+            .filter(|&word| word == "mapped" || word == "read")
+            .map(String::from)
+            .collect()
+    })
+}
+
+#[test]
+fn a_multi_megabyte_file_lands_at_its_load_address() {
+    let _ = log::set_logger(&CAPTURE);
+    log::set_max_level(LevelFilter::Debug);
+
+    // 3 MiB at 0x1234, so it runs far past the 64 KiB an address can name.
+    let payload: Vec<u8> = (0..3 << 20).map(|i: u32| (i % 251) as u8).collect();
+    let path = temp_file("large", &Program::new(0x1234, payload.clone()).build());
+    let mut vm = MicroCvm::builder().memory_size(4 << 20).build();
+    vm.load_program_mmapped(&path).unwrap();
+
+    let memory = vm.cpu().memory();
+    assert!(memory[..0x1234].iter().all(|&byte| byte == 0));
+    assert!(memory[0x1234..][..payload.len()] == payload[..]);
+    assert!(
+        memory[0x1234 + payload.len()..]
+            .iter()
+            .all(|&byte| byte == 0)
+    );
+    // Without the feature it goes the buffered way.
+    let expected = if cfg!(feature = "mmap") {
+        "mapped"
+    } else {
+        "read"
+    };
+    assert_eq!(load_paths(), [expected]);
+}
+
+#[test]
+fn broken_program_files_are_refused() {
+    let mut vm = MicroCvm::builder().build();
+    // The magic, then a version there has never been.
+    let path = temp_file("garbage", b"MCVM\x63 and whatever else");
+    let error = vm.load_program_mmapped(&path).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+    assert!(vm.cpu().memory().iter().all(|&byte| byte == 0));
+
+    let missing = path.with_file_name("missing.bin");
+    let error = vm.load_program_mmapped(&missing).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::NotFound);
+}
+
+#[test]
+fn a_program_too_big_for_memory_is_refused() {
+    let path = temp_file("too-big", &Program::new(0, vec![1; 0x2000]).build());
+    let mut vm = MicroCvm::builder().memory_size(0x1000).build();
+    let error = vm.load_program_mmapped(&path).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+    assert!(vm.cpu().memory().iter().all(|&byte| byte == 0));
+}
+
+// Only a mapping can see the file grow under it; a buffered read has it all by then.
+#[cfg(feature = "mmap")]
+#[test]
+fn a_file_that_changes_size_while_it_loads_is_refused() {
+    use std::io::Write;
+
+    let path = temp_file("grows", &Program::new(0, vec![1, 2, 3]).build());
+    let error = microcvm_rs::program::with_program_file(&path, |bytes| {
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(&[0; 16]).unwrap();
+        Ok(bytes.len())
+    })
+    .unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+    assert!(
+        error
+            .to_string()
+            .ends_with("changed size while it was loading")
+    );
+}
@@ -0,0 +1,184 @@
+// Every key the terminal monitor knows has to map to its command, and each command has to do
+// to the machine and the panes what the help line says, with a status that tells what happened.
+#![cfg(feature = "tui")]
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::debugger::{DISASSEMBLY_AFTER, DISASSEMBLY_BEFORE, HEXDUMP_ROWS};
+use microcvm_rs::monitor::{
+    Monitor, MonitorCommand, MonitorKey, RecentTrace, TRACE_LINES, parse_key,
+};
+use microcvm_rs::symbols::SymbolTable;
+
+// `mov` takes three bytes and `inc` two, so the instructions start at 0, 3, 5 and 7.
+const PROGRAM: &str = "
+        mov r1, 5
+        inc r1
+        inc r1
+        hlt
+";
+
+fn load(source: &str) -> Monitor {
+    let mut vm = MicroCvm::builder().build();
+    vm.load_program(&assemble(source).unwrap()).unwrap();
+    Monitor::new(vm, SymbolTable::new())
+}
+
+#[test]
+fn keys_map_to_commands() {
+    for (key, command) in [
+        (MonitorKey::Char('s'), MonitorCommand::Step),
+        (MonitorKey::Char(' '), MonitorCommand::Step),
+        (MonitorKey::Char('c'), MonitorCommand::Continue),
+        (MonitorKey::Char('p'), MonitorCommand::Pause),
+        (MonitorKey::Char('b'), MonitorCommand::ToggleBreakpoint),
+        (MonitorKey::Char('k'), MonitorCommand::CursorUp),
+        (MonitorKey::Up, MonitorCommand::CursorUp),
+        (MonitorKey::Char('j'), MonitorCommand::CursorDown),
+        (MonitorKey::Down, MonitorCommand::CursorDown),
+        (MonitorKey::PageUp, MonitorCommand::MemoryUp),
+        (MonitorKey::PageDown, MonitorCommand::MemoryDown),
+        (MonitorKey::Home, MonitorCommand::MemoryToStack),
+        (MonitorKey::Char('f'), MonitorCommand::TogglePreview),
+        (MonitorKey::Char('q'), MonitorCommand::Quit),
+        (MonitorKey::Esc, MonitorCommand::Quit),
+    ] {
+        assert_eq!(parse_key(key), Some(command), "{:?}", key);
+    }
+    for c in ['x', 'S', 'Q', '1', '\n'] {
+        assert_eq!(parse_key(MonitorKey::Char(c)), None, "{:?}", c);
+    }
+}
+
+#[test]
+fn stepping_reports_where_it_stopped() {
+    let mut monitor = load(PROGRAM);
+    assert!(monitor.apply(MonitorCommand::Step));
+    assert_eq!(monitor.vm.cpu().pc, 3);
+    assert_eq!(monitor.status, "stopped");
+    for _ in 0..3 {
+        monitor.apply(MonitorCommand::Step);
+    }
+    assert_eq!(monitor.status, "halted at 0x0007");
+    assert_eq!(monitor.vm.cpu().registers[1], 7);
+    monitor.apply(MonitorCommand::Step);
+    assert_eq!(monitor.status, "halted");
+    monitor.apply(MonitorCommand::Continue);
+    assert_eq!(
+        (monitor.running, monitor.status.as_str()),
+        (false, "halted")
+    );
+    assert_eq!(monitor.trace.lines(&monitor.symbols).len(), 4);
+}
+
+#[test]
+fn breakpoints_toggle_on_the_selected_line() {
+    let mut monitor = load(PROGRAM);
+    monitor.apply(MonitorCommand::CursorDown);
+    monitor.apply(MonitorCommand::CursorDown);
+    monitor.apply(MonitorCommand::ToggleBreakpoint);
+    assert_eq!(monitor.status, "breakpoint at 0x0005");
+    assert!(monitor.vm.breakpoints().contains(&5));
+
+    // Continuing runs on the next frame and stops at the breakpoint.
+    monitor.apply(MonitorCommand::Continue);
+    assert_eq!(
+        (monitor.running, monitor.status.as_str()),
+        (true, "running")
+    );
+    monitor.advance();
+    assert_eq!(
+        (monitor.running, monitor.status.as_str()),
+        (false, "break at 0x0005")
+    );
+
+    // The selection follows pc, so it is now on `hlt`, two lines on.
+    monitor.apply(MonitorCommand::CursorUp);
+    monitor.apply(MonitorCommand::CursorUp);
+    monitor.apply(MonitorCommand::ToggleBreakpoint);
+    assert_eq!(monitor.status, "removed breakpoint at 0x0005");
+    assert!(monitor.vm.breakpoints().is_empty());
+
+    monitor.apply(MonitorCommand::Continue);
+    monitor.advance();
+    assert_eq!(monitor.status, "halted at 0x0007");
+}
+
+#[test]
+fn the_selection_stays_on_the_disassembly() {
+    let mut monitor = load(PROGRAM);
+    for _ in 0..DISASSEMBLY_AFTER * 2 {
+        monitor.apply(MonitorCommand::CursorDown);
+    }
+    assert_eq!(monitor.view.cursor, DISASSEMBLY_AFTER as isize - 1);
+    // There is nothing before pc 0 to move up to.
+    for _ in 0..DISASSEMBLY_AFTER * 2 + DISASSEMBLY_BEFORE {
+        monitor.apply(MonitorCommand::CursorUp);
+    }
+    assert_eq!(monitor.view.cursor, 0);
+    assert_eq!(monitor.view.selected(monitor.vm.cpu()), 0);
+}
+
+#[test]
+fn memory_pages_and_jumps_to_the_stack() {
+    let mut monitor = load("pushf\npushf\npushf\nhlt");
+    let page = (HEXDUMP_ROWS * 8) as u16;
+    monitor.apply(MonitorCommand::MemoryDown);
+    assert_eq!(monitor.view.memory_start, page);
+    monitor.apply(MonitorCommand::MemoryUp);
+    monitor.apply(MonitorCommand::MemoryUp);
+    assert_eq!(monitor.view.memory_start, 0u16.wrapping_sub(page));
+
+    for _ in 0..4 {
+        monitor.apply(MonitorCommand::Step);
+    }
+    let sp = monitor.vm.cpu().sp;
+    monitor.apply(MonitorCommand::MemoryToStack);
+    assert_eq!(monitor.view.memory_start, sp & !7);
+}
+
+#[test]
+fn faults_pause_and_show_in_the_status() {
+    let mut monitor = load("mov r1, 0\ndiv r0, r1\nhlt");
+    monitor.apply(MonitorCommand::Continue);
+    monitor.advance();
+    assert!(!monitor.running);
+    assert!(
+        monitor.status.starts_with("fault: Division by zero"),
+        "{}",
+        monitor.status
+    );
+
+    // Pausing stops a running program before its next frame.
+    let mut monitor = load("again: jmp again");
+    monitor.apply(MonitorCommand::Continue);
+    monitor.apply(MonitorCommand::Pause);
+    monitor.advance();
+    assert_eq!(
+        (monitor.running, monitor.status.as_str()),
+        (false, "stopped")
+    );
+    assert_eq!(monitor.vm.cpu().pc, 0);
+}
+
+#[test]
+fn preview_toggles_and_quit_ends_the_loop() {
+    let mut monitor = load(PROGRAM);
+    assert!(monitor.apply(MonitorCommand::TogglePreview));
+    assert!(monitor.preview);
+    monitor.apply(MonitorCommand::TogglePreview);
+    assert!(!monitor.preview);
+    assert!(!monitor.apply(MonitorCommand::Quit));
+}
+
+#[test]
+fn the_trace_keeps_the_latest_lines() {
+    let mut monitor = load("again: inc r1\njmp again");
+    monitor.apply(MonitorCommand::Continue);
+    monitor.advance();
+    let lines = monitor.trace.lines(&monitor.symbols);
+    assert_eq!(lines.len(), TRACE_LINES);
+    // Clones share what was recorded.
+    let copy: RecentTrace = monitor.trace.clone();
+    assert_eq!(copy.lines(&monitor.symbols), lines);
+}
@@ -0,0 +1,135 @@
+// In relative mode the guest has to see each frame's motion as signed bytes that saturate
+// instead of wrapping, with fractions carried over to the next frame and the excess of a
+// flick dropped. Outside it, or once the pointer is let go, motion has to count for nothing.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::mouse::{MOUSE_LEFT, MOUSE_RELATIVE, MOUSE_RIGHT, Mouse, take_delta};
+
+// The deltas `motions` make, one per frame.
+fn deltas(motions: &[f64]) -> Vec<i8> {
+    let mut motion = 0.0;
+    motions
+        .iter()
+        .map(|&moved| {
+            motion += moved;
+            take_delta(&mut motion)
+        })
+        .collect()
+}
+
+fn relative() -> Mouse {
+    Mouse {
+        control: MOUSE_RELATIVE,
+        ..Mouse::default()
+    }
+}
+
+#[test]
+fn fractions_carry_over_to_the_next_frame() {
+    assert_eq!(deltas(&[0.4, 0.4, 0.4]), [0, 0, 1]);
+    assert_eq!(deltas(&[1.5, 1.5, 0.25]), [1, 2, 0]);
+    // Towards 0 either way, so left and right are the same.
+    assert_eq!(deltas(&[-0.4, -0.4, -0.4]), [0, 0, -1]);
+    assert_eq!(deltas(&[-1.5, -1.5]), [-1, -2]);
+}
+
+#[test]
+fn large_motion_saturates_and_is_not_replayed() {
+    assert_eq!(deltas(&[1000.0, 0.0]), [127, 0]);
+    assert_eq!(deltas(&[-1000.0, 0.0]), [-128, 0]);
+    // Right at the limit the fraction still carries.
+    assert_eq!(deltas(&[127.5, 0.5]), [127, 1]);
+    assert_eq!(deltas(&[-128.75, -0.25]), [-128, -1]);
+    assert_eq!(deltas(&[f64::INFINITY, 0.0]), [127, 0]);
+    assert_eq!(deltas(&[f64::NAN]), [0]);
+}
+
+#[test]
+fn motion_counts_only_in_relative_mode() {
+    let mut mouse = Mouse::default();
+    mouse.add_motion(5.0, 5.0);
+    mouse.end_frame();
+    assert_eq!(mouse.delta, [0, 0]);
+
+    let mut mouse = relative();
+    mouse.add_motion(2.5, -3.0);
+    mouse.add_motion(0.75, 0.0);
+    mouse.end_frame();
+    assert_eq!(mouse.delta, [3, -3]);
+    // A frame without motion reads 0, apart from what was carried.
+    mouse.end_frame();
+    assert_eq!(mouse.delta, [0, 0]);
+    mouse.add_motion(0.75, 0.0);
+    mouse.end_frame();
+    assert_eq!(mouse.delta, [1, 0]);
+
+    // Letting go forgets everything, carried fractions too.
+    mouse.add_motion(10.0, 0.5);
+    mouse.release();
+    assert!(!mouse.relative());
+    assert_eq!((mouse.motion, mouse.delta), ([0.0; 2], [0, 0]));
+}
+
+#[test]
+fn the_guest_turns_relative_mode_on_and_reads_signed_deltas() {
+    // Adds up dx in r2 and dy in r3 over every vblank, and copies the buttons to r5.
+    let program = assemble(
+        "
+        mov r0, 0x00
+        store [0xFF90], r0      ; vector table at 0x0200
+        mov r0, 0x02
+        store [0xFF91], r0
+        mov r0, 1
+        store [0xFFE5], r0      ; relative mode
+        ei
+idle:   jmp idle
+
+on_vblank:
+        load r0, [0xFFE6]
+        add r2, r0
+        load r0, [0xFFE7]
+        add r3, r0
+        load r0, [0xFFE4]
+        mov r5, r0
+        iret
+
+        .org 0x0200
+        .dw on_vblank
+",
+    )
+    .unwrap();
+    let mut vm = MicroCvm::builder().build();
+    vm.load_program(&program).unwrap();
+    vm.run_for(100).unwrap();
+    assert!(vm.cpu().mouse().relative());
+
+    vm.inject_mouse_motion(3.0, -1.0);
+    vm.cpu_mut()
+        .mouse_mut()
+        .button(MOUSE_LEFT | MOUSE_RIGHT, true);
+    vm.tick_frame();
+    vm.run_for(100).unwrap();
+    assert_eq!(vm.cpu().registers[2..4], [3, 0xFF]);
+    assert_eq!(vm.cpu().registers[5], (MOUSE_LEFT | MOUSE_RIGHT) as u16);
+
+    vm.inject_mouse_motion(-500.0, 500.0);
+    vm.tick_frame();
+    vm.run_for(100).unwrap();
+    assert_eq!(
+        vm.cpu().registers[2..4],
+        [3u8.wrapping_sub(128) as u16, 0x7E]
+    );
+
+    // Writing 0 to the control register lets go, as a host does on Escape or losing focus.
+    vm.cpu_mut().mouse_mut().add_motion(4.0, 4.0);
+    let absolute = assemble("mov r0, 0\nstore [0xFFE5], r0\nhlt").unwrap();
+    vm.load_from_reader(&absolute[..], 0x0300).unwrap();
+    vm.cpu_mut().pc = 0x0300;
+    vm.run().unwrap();
+    let mouse = vm.cpu().mouse();
+    assert_eq!((mouse.control, mouse.motion), (0, [0.0; 2]));
+    vm.inject_mouse_motion(4.0, 4.0);
+    vm.tick_frame();
+    assert_eq!(vm.cpu().mouse().delta, [0, 0]);
+}
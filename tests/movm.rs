@@ -0,0 +1,73 @@
+// movm has to encode and decode back to itself, and copy between RAM and MMIO registers
+// through the same accessors loads and stores use, down each of the CPU's execution paths.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::cpu::{OpcodeArg1, OpcodeArg2, OpcodeType, RegisterWidth};
+use microcvm_rs::disasm::{decode, format_instruction};
+use microcvm_rs::symbols::SymbolTable;
+use microcvm_rs::trace::{TraceEntry, TraceSink};
+
+// Copies the pending key's code into RAM, then pops the event by copying RAM into the key
+// status register, and copies the status after that.
+const PROGRAM: &str = "
+        movm [0x0200], [0xFF31]
+        movm [0xFF30], [0x0200]
+        movm [0x0201], [0xFF30]
+        movm [0x0202], [0xFF31]
+        hlt
+";
+
+struct Discard;
+
+impl TraceSink for Discard {
+    fn trace(&mut self, _entry: &TraceEntry) {}
+}
+
+#[test]
+fn encoding_round_trips() {
+    let bytes = assemble("movm [0x1234], [0xFF41]").unwrap();
+    assert_eq!(bytes, [0x26, 0x34, 0x12, 0x41, 0xFF]);
+    for width in [RegisterWidth::Eight, RegisterWidth::Sixteen] {
+        let opcode = decode(&bytes, width).unwrap();
+        assert_eq!(opcode.opcode_type, OpcodeType::Movm);
+        assert_eq!(opcode.arg1, Some(OpcodeArg1::Address(0x1234)));
+        assert_eq!(opcode.arg2, Some(OpcodeArg2::Address(0xFF41)));
+        assert_eq!(opcode.length(), 5);
+        assert_eq!(opcode.encode(), bytes);
+        let text = format_instruction(&opcode, 0, &SymbolTable::new());
+        assert_eq!(text, "movm [0x1234], [0xff41]");
+        assert_eq!(assemble(&text).unwrap(), bytes);
+    }
+    // Cut short.
+    assert_eq!(decode(&bytes[..4], RegisterWidth::Eight), None);
+}
+
+fn run(mut vm: MicroCvm) -> MicroCvm {
+    vm.load_program(&assemble(PROGRAM).unwrap()).unwrap();
+    vm.push_key(0x1E, true);
+    vm.run().unwrap();
+    vm
+}
+
+#[test]
+fn copies_between_mmio_and_ram() {
+    let vms = [
+        ("fast", run(MicroCvm::builder().build())),
+        (
+            "uncached",
+            run(MicroCvm::builder().decode_cache(false).build()),
+        ),
+        (
+            "traced",
+            run(MicroCvm::builder().trace(Box::new(Discard)).build()),
+        ),
+    ];
+    for (path, vm) in vms {
+        let cpu = vm.cpu();
+        // The code, then an empty queue's status and code.
+        assert_eq!(cpu.memory()[0x200..0x203], [0x1E, 0, 0], "{}", path);
+        assert_eq!(cpu.registers, [0; 8], "{}", path);
+        assert_eq!(cpu.cycles, 9, "{}", path);
+    }
+}
@@ -0,0 +1,120 @@
+// Two VMs on localhost ports have to exchange a datagram through the UDP device, and one too
+// long for the receive buffer has to be cut to it with its full length reported.
+#![cfg(feature = "net")]
+
+use std::net::{SocketAddr, UdpSocket};
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::cpu::{NET_BASE, Register};
+use microcvm_rs::net::{
+    NET_ADDR, NET_CTRL, NET_FLAGS, NET_LEN, NET_NO_PACKET, NET_OK, NET_RECEIVE, NET_RECEIVED,
+    NET_SEND, NET_TRUNCATED,
+};
+
+const MESSAGE: &[u8] = b"hello, net";
+const SENT_FROM: u16 = 0x2000;
+const RECEIVED_INTO: u16 = 0x3000;
+
+// Two free ports, let go of again for the devices to bind.
+fn free_ports() -> (SocketAddr, SocketAddr) {
+    let a = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let b = UdpSocket::bind("127.0.0.1:0").unwrap();
+    (a.local_addr().unwrap(), b.local_addr().unwrap())
+}
+
+// Sets the buffer registers, issues `command` and halts with its status in r0.
+fn command(buffer: u16, len: u16, command: u8) -> String {
+    let register = |offset: u8| NET_BASE + offset as u16;
+    format!(
+        "
+        mov r0, {addr_lo}
+        store [{addr:#x}], r0
+        mov r0, {addr_hi}
+        store [{addr_1:#x}], r0
+        mov r0, {len_lo}
+        store [{len:#x}], r0
+        mov r0, {len_hi}
+        store [{len_1:#x}], r0
+        mov r0, {command}
+        store [{ctrl:#x}], r0
+        load r0, [{ctrl:#x}]
+        load r1, [{received:#x}]
+        hlt
+",
+        addr_lo = buffer & 0xFF,
+        addr_hi = buffer >> 8,
+        len_lo = len & 0xFF,
+        len_hi = len >> 8,
+        command = command,
+        addr = register(NET_ADDR),
+        addr_1 = register(NET_ADDR + 1),
+        len = register(NET_LEN),
+        len_1 = register(NET_LEN + 1),
+        ctrl = register(NET_CTRL),
+        received = register(NET_RECEIVED),
+    )
+}
+
+// Waits for a datagram, then receives up to `len` bytes of it.
+fn receive(len: u16) -> String {
+    format!(
+        "wait:   load r0, [{flags:#x}]\n        test r0, r0\n        jrz wait\n{}",
+        command(RECEIVED_INTO, len, NET_RECEIVE),
+        flags = NET_BASE + NET_FLAGS as u16,
+    )
+}
+
+fn run(vm: &mut MicroCvm, source: &str) -> (u8, u8) {
+    vm.load_program(&assemble(source).unwrap()).unwrap();
+    vm.cpu_mut().pc = 0;
+    vm.cpu_mut().halted = false;
+    vm.run_for(1_000_000).unwrap();
+    assert!(vm.cpu().halted, "still waiting");
+    (vm.cpu()[Register::R0] as u8, vm.cpu()[Register::R1] as u8)
+}
+
+#[test]
+fn two_devices_exchange_datagrams() {
+    let (a, b) = free_ports();
+    let mut sender = MicroCvm::builder().build();
+    sender.cpu_mut().net.attach(a, b).unwrap();
+    let mut receiver = MicroCvm::builder().build();
+    receiver.cpu_mut().net.attach(b, a).unwrap();
+    let start = SENT_FROM as usize;
+    sender.cpu_mut().memory_mut()[start..start + MESSAGE.len()].copy_from_slice(MESSAGE);
+
+    // Nothing has been sent yet.
+    let (status, _) = run(&mut receiver, &command(RECEIVED_INTO, 64, NET_RECEIVE));
+    assert_eq!(status, NET_NO_PACKET);
+
+    let send = command(SENT_FROM, MESSAGE.len() as u16, NET_SEND);
+    assert_eq!(run(&mut sender, &send).0, NET_OK);
+    let (status, received) = run(&mut receiver, &receive(64));
+    assert_eq!((status, received as usize), (NET_OK, MESSAGE.len()));
+    let at = RECEIVED_INTO as usize;
+    assert_eq!(receiver.cpu().memory()[at..at + MESSAGE.len()], *MESSAGE);
+
+    // Four bytes of room: those arrive, the rest is dropped, the length is the real one.
+    receiver.cpu_mut().memory_mut()[at..at + 64].fill(0);
+    assert_eq!(run(&mut sender, &send).0, NET_OK);
+    let (status, received) = run(&mut receiver, &receive(4));
+    assert_eq!((status, received as usize), (NET_TRUNCATED, MESSAGE.len()));
+    assert_eq!(receiver.cpu().memory()[at..at + 4], MESSAGE[..4]);
+    assert!(
+        receiver.cpu().memory()[at + 4..at + 64]
+            .iter()
+            .all(|&b| b == 0)
+    );
+
+    // And the other way.
+    let start = SENT_FROM as usize;
+    receiver.cpu_mut().memory_mut()[start..start + 3].copy_from_slice(b"ack");
+    assert_eq!(
+        run(&mut receiver, &command(SENT_FROM, 3, NET_SEND)).0,
+        NET_OK
+    );
+    let (status, received) = run(&mut sender, &receive(64));
+    assert_eq!((status, received), (NET_OK, 3));
+    assert_eq!(sender.cpu().memory()[at..at + 3], *b"ack");
+}
@@ -0,0 +1,103 @@
+// A program missing its `hlt` runs off the end of its code into a data table. Marked
+// no-execute, the table has to stop it with ExecuteProtected down each of the CPU's execution
+// paths, rather than running whatever the data decodes to.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::{assemble, assemble_segments};
+use microcvm_rs::cpu::HaltReason;
+use microcvm_rs::error::VmError;
+use microcvm_rs::fault::{FAULT_EXECUTE_PROTECTED, fault_code};
+use microcvm_rs::protect::Protection;
+use microcvm_rs::segment::SegmentedProgram;
+use microcvm_rs::trace::{TraceEntry, TraceSink};
+
+// The table decodes as `nop; nop; hlt`, so run as code it halts as if nothing were wrong.
+const PROGRAM: &str = "
+        .section code
+start:  mov r0, 1
+        inc r0
+        .section data
+        .org 0x0005
+table:  .db 0x90, 0x90, 0xff
+";
+const TABLE: u16 = 0x0005;
+
+struct Discard;
+
+impl TraceSink for Discard {
+    fn trace(&mut self, _entry: &TraceEntry) {}
+}
+
+fn builders() -> [(&'static str, MicroCvm); 3] {
+    [
+        ("fast", MicroCvm::builder().build()),
+        ("uncached", MicroCvm::builder().decode_cache(false).build()),
+        (
+            "traced",
+            MicroCvm::builder().trace(Box::new(Discard)).build(),
+        ),
+    ]
+}
+
+fn program() -> SegmentedProgram {
+    assemble_segments(PROGRAM).unwrap()
+}
+
+#[test]
+fn running_into_data_faults() {
+    let file = program().no_execute_data(true).build();
+    assert_eq!(
+        SegmentedProgram::parse(&file).unwrap(),
+        program().no_execute_data(true)
+    );
+    for (path, mut vm) in builders() {
+        vm.load_program(&file).unwrap();
+        let error = vm.run().unwrap_err();
+        assert!(
+            matches!(error.cause(), VmError::ExecuteProtected { pc: TABLE }),
+            "{}: {:?}",
+            path,
+            error
+        );
+        assert_eq!(fault_code(&error), FAULT_EXECUTE_PROTECTED, "{}", path);
+        assert_eq!(vm.cpu().registers[0], 2, "{}", path);
+        assert_eq!(vm.cpu().pc, TABLE, "{}", path);
+    }
+}
+
+#[test]
+fn data_runs_without_the_flag() {
+    let file = program().build();
+    for (path, mut vm) in builders() {
+        vm.load_program(&file).unwrap();
+        assert_eq!(
+            vm.run().unwrap(),
+            HaltReason::Halted { code: 2 },
+            "{}",
+            path
+        );
+    }
+}
+
+#[test]
+fn hosts_mark_and_unmark_ranges() {
+    let image = assemble(PROGRAM).unwrap();
+    for (path, mut vm) in builders() {
+        vm.load_program(&image).unwrap();
+        vm.cpu_mut().protect(0x0006..0x0007, Protection::NoExecute);
+        let error = vm.run().unwrap_err();
+        assert!(
+            matches!(error.cause(), VmError::ExecuteProtected { pc: 0x0006 }),
+            "{}: {:?}",
+            path,
+            error
+        );
+        vm.cpu_mut().unprotect(0x0000..0x0100);
+        assert_eq!(
+            vm.run().unwrap(),
+            HaltReason::Halted { code: 2 },
+            "{}",
+            path
+        );
+    }
+}
@@ -0,0 +1,22 @@
+// The core has to keep building without std, so the suite checks the library with no
+// features at all, into a target directory of its own to leave this build's alone.
+
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn the_core_builds_without_default_features() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let output = Command::new(env!("CARGO"))
+        .current_dir(manifest_dir)
+        .args(["check", "--lib", "--no-default-features", "--quiet"])
+        .arg("--target-dir")
+        .arg(manifest_dir.join("target").join("no-default-features"))
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
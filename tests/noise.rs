@@ -0,0 +1,71 @@
+// Whatever memory holds and wherever pc points, a step has to either run an instruction or
+// return an error, on the fast path and on the traced one alike.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::trace::{TraceEntry, TraceSink};
+
+const STEPS: usize = 100_000;
+
+// xorshift32, so every run sees the same noise.
+struct Noise(u32);
+
+impl Noise {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+}
+
+struct Count(Arc<AtomicUsize>);
+
+impl TraceSink for Count {
+    fn trace(&mut self, _: &TraceEntry) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+// Steps through noise, starting somewhere new whenever the guest faults or halts. Returns how
+// many steps faulted.
+fn step_through_noise(vm: &mut MicroCvm, seed: u32) -> usize {
+    let mut noise = Noise(seed);
+    for byte in vm.cpu_mut().memory_mut() {
+        *byte = noise.next() as u8;
+    }
+    let mut faults = 0;
+    for _ in 0..STEPS {
+        if vm.cpu().halted || vm.step().is_err() {
+            faults += 1;
+            let cpu = vm.cpu_mut();
+            cpu.halted = false;
+            cpu.pc = noise.next() as u16;
+        }
+    }
+    faults
+}
+
+#[test]
+fn noise_never_panics_on_the_fast_path() {
+    for seed in [0x2545_f491, 0xdead_beef] {
+        let mut vm = MicroCvm::builder().build();
+        let faults = step_through_noise(&mut vm, seed);
+        assert!(faults < STEPS, "seed {:#x}", seed);
+    }
+}
+
+#[test]
+fn noise_never_panics_on_the_traced_path() {
+    for seed in [0x2545_f491, 0xdead_beef] {
+        let traced = Arc::new(AtomicUsize::new(0));
+        let mut vm = MicroCvm::builder()
+            .trace(Box::new(Count(traced.clone())))
+            .build();
+        let faults = step_through_noise(&mut vm, seed);
+        assert!(faults < STEPS, "seed {:#x}", seed);
+        assert!(traced.load(Ordering::Relaxed) > 0, "seed {:#x}", seed);
+    }
+}
@@ -0,0 +1,122 @@
+// A boot counter kept in NVRAM has to count up across separate VMs sharing the file, and a
+// file that is damaged or saved for a region of another size has to leave the region zeroed,
+// with a warning, instead of stopping the boot.
+
+use std::path::{Path, PathBuf};
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::cpu::{NVRAM_BASE, Register};
+use microcvm_rs::nvram::{DEFAULT_NVRAM_LEN, DEFAULT_NVRAM_START, Nvram, NvramWarning};
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("microcvm-nvram-test-{}", name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// Counts itself in the first NVRAM byte, leaving the count in r0.
+fn boot_counter() -> Vec<u8> {
+    assemble(&format!(
+        "load r0, [{at:#x}]\ninc r0\nstore [{at:#x}], r0\nhlt",
+        at = DEFAULT_NVRAM_START
+    ))
+    .unwrap()
+}
+
+// Boots a fresh VM on `file`, returning the count and what the attach warned about.
+fn boot(file: &Path) -> (u16, Option<NvramWarning>) {
+    let mut vm = MicroCvm::builder().build();
+    vm.load_program(&boot_counter()).unwrap();
+    let warning = vm.cpu_mut().attach_nvram(file).unwrap();
+    vm.run().unwrap();
+    (vm.cpu()[Register::R0], warning)
+}
+
+#[test]
+fn a_boot_counter_counts_across_vms() {
+    let dir = scratch("counter");
+    let file = dir.join("save.nvram");
+    for expected in 1..=3 {
+        assert_eq!(boot(&file), (expected, None));
+    }
+    // Saved with its length and checksum, the rest of the region still zeroed.
+    let (contents, warning) = Nvram::load(&file, DEFAULT_NVRAM_LEN as usize).unwrap();
+    assert_eq!(warning, None);
+    assert_eq!(contents[0], 3);
+    assert!(contents[1..].iter().all(|&b| b == 0));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn a_commit_saves_before_the_halt() {
+    let dir = scratch("commit");
+    let file = dir.join("save.nvram");
+    let mut vm = MicroCvm::builder().build();
+    let program = assemble(&format!(
+        "mov r0, 0x42\nstore [{:#x}], r0\nstore [{:#x}], r0\nspin: jr spin",
+        DEFAULT_NVRAM_START, NVRAM_BASE
+    ))
+    .unwrap();
+    vm.load_program(&program).unwrap();
+    vm.cpu_mut().attach_nvram(&file).unwrap();
+    vm.run_for(100).unwrap();
+    let (contents, _) = Nvram::load(&file, DEFAULT_NVRAM_LEN as usize).unwrap();
+    assert_eq!(contents[0], 0x42);
+    // The status of the commit reads back as 0.
+    assert_eq!(vm.cpu().read_mem(NVRAM_BASE).unwrap(), 0);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn damaged_files_start_from_zeroes() {
+    let dir = scratch("damaged");
+    let file = dir.join("save.nvram");
+    boot(&file);
+    boot(&file);
+    let good = std::fs::read(&file).unwrap();
+
+    let mut flipped = good.clone();
+    flipped[6] ^= 0xFF;
+    let mut truncated = good.clone();
+    truncated.pop();
+    let mut padded = good.clone();
+    padded.push(0);
+    let cases = [
+        (b"not an nvram file".to_vec(), NvramWarning::BadMagic),
+        (Vec::new(), NvramWarning::BadMagic),
+        (flipped, NvramWarning::BadChecksum),
+        (truncated, NvramWarning::BadLength),
+        (padded, NvramWarning::BadLength),
+    ];
+    for (bytes, expected) in cases {
+        std::fs::write(&file, &bytes).unwrap();
+        // The counter starts over, and the next boot finds what that one saved.
+        assert_eq!(boot(&file), (1, Some(expected.clone())), "{:?}", expected);
+        assert_eq!(boot(&file), (2, None), "{:?}", expected);
+    }
+
+    // Saved by a region half the size.
+    let mut vm = MicroCvm::builder().build();
+    vm.cpu_mut()
+        .attach_nvram_at(&file, DEFAULT_NVRAM_START, DEFAULT_NVRAM_LEN / 2)
+        .unwrap();
+    vm.cpu_mut().commit_nvram().unwrap();
+    drop(vm);
+    let (count, warning) = boot(&file);
+    assert_eq!(count, 1);
+    let warning = warning.unwrap();
+    assert_eq!(
+        warning,
+        NvramWarning::WrongSize {
+            expected: DEFAULT_NVRAM_LEN as usize,
+            found: DEFAULT_NVRAM_LEN as usize / 2
+        }
+    );
+    assert_eq!(
+        warning.to_string(),
+        "NVRAM file holds 128 bytes but the region is 256, starting from zeroes"
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,98 @@
+// The debug overlay has to lay out pc, sp, the flags and the registers at the machine's
+// register width, and draw them over the output frame only: glyphs in white on a darkened
+// box, clipped at the frame's edge, with video memory untouched.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::cpu::RegisterWidth;
+use microcvm_rs::font::{GLYPH_SIZE, glyph};
+use microcvm_rs::overlay::{draw_text, layout};
+
+fn halted(width: RegisterWidth, source: &str) -> MicroCvm {
+    let mut vm = MicroCvm::builder()
+        .resolution(160, 40)
+        .register_width(width)
+        .build();
+    vm.load_program(&assemble(source).unwrap()).unwrap();
+    vm.run().unwrap();
+    vm
+}
+
+#[test]
+fn the_layout_follows_the_machine() {
+    let vm = halted(
+        RegisterWidth::Eight,
+        "mov r0, 0x2a\nmov r7, 0xff\nsub r1, 0\nstc\nhlt",
+    );
+    let mut stats = vm.debug_stats();
+    stats.instructions_per_second = 1_500_000;
+    stats.frames_per_second = 60;
+    assert_eq!(
+        layout(&stats),
+        [
+            "PC 000A SP FF00 ZC-",
+            "R0 2A R1 00 R2 00 R3 00",
+            "R4 00 R5 00 R6 00 R7 FF",
+            "IPS 1500000 FPS 60",
+        ]
+    );
+
+    let vm = halted(RegisterWidth::Sixteen, ".width 16\nmov r3, 0xbeef\nei\nhlt");
+    let lines = layout(&vm.debug_stats());
+    assert_eq!(lines[0], "PC 0005 SP FF00 --I");
+    assert_eq!(lines[1], "R0 0000 R1 0000 R2 0000 R3 BEEF");
+    assert_eq!(lines[3], "IPS 0 FPS 0");
+}
+
+#[test]
+fn text_is_drawn_over_a_darkened_box() {
+    let width = 160;
+    let mut frame = vec![200; width * 40 * 4];
+    let lines = [String::from("P")];
+    draw_text(&lines, width, &mut frame);
+    let pixel =
+        |x: usize, y: usize| <[u8; 4]>::try_from(&frame[(y * width + x) * 4..][..4]).unwrap();
+
+    // The 'P' after two pixels of padding, set bits white and the rest darkened.
+    let glyph = glyph('P');
+    for (dy, bits) in glyph.iter().enumerate() {
+        for dx in 0..GLYPH_SIZE {
+            let expected = if bits & (0x80 >> dx) != 0 {
+                [255, 255, 255, 255]
+            } else {
+                [50, 50, 50, 200]
+            };
+            assert_eq!(pixel(2 + dx, 2 + dy), expected, "{}, {}", dx, dy);
+        }
+    }
+    // The box is one glyph and its padding, and the rest of the frame is left alone.
+    assert_eq!(pixel(0, 0), [50, 50, 50, 200]);
+    assert_eq!(pixel(11, 11), [50, 50, 50, 200]);
+    assert_eq!(pixel(12, 0), [200; 4]);
+    assert_eq!(pixel(0, 12), [200; 4]);
+}
+
+#[test]
+fn the_overlay_is_clipped_to_the_frame() {
+    let vm = halted(RegisterWidth::Eight, "hlt");
+    let lines = layout(&vm.debug_stats());
+    // Narrower and shorter than the text.
+    let (width, height) = (20, 6);
+    let mut frame = vec![0; width * height * 4];
+    draw_text(&lines, width, &mut frame);
+    assert!(frame.chunks(4).any(|pixel| pixel == [255, 255, 255, 255]));
+    draw_text(&lines, 0, &mut []);
+}
+
+#[test]
+fn video_memory_never_sees_the_overlay() {
+    let mut vm = halted(RegisterWidth::Eight, "hlt");
+    let mut frame = vec![0; 160 * 40 * 4];
+    vm.frame_rgba(&mut frame);
+    draw_text(&layout(&vm.debug_stats()), 160, &mut frame);
+    assert!(frame.chunks(4).any(|pixel| pixel == [255, 255, 255, 255]));
+    assert!(vm.framebuffer().bytes().iter().all(|&byte| byte == 0));
+    let mut again = vec![0; 160 * 40 * 4];
+    vm.frame_rgba(&mut again);
+    assert!(again.chunks(4).all(|pixel| pixel == [0, 0, 0, 255]));
+}
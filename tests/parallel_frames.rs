@@ -0,0 +1,79 @@
+// Frames converted in parallel bands have to come out byte for byte the same as the
+// sequential ones, over random video memory with sprites, brightness and scrolled tiles, at
+// sizes that don't split into bands evenly.
+#![cfg(feature = "rayon")]
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::cpu::VIDEO_CONTROL_TILES;
+
+// xorshift32, so every run sees the same "random" machine.
+struct Random(u32);
+
+impl Random {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+}
+
+// The plain and the tiled frame of a random `width` x `height` machine, converted in parallel
+// from `parallel_pixels` up.
+fn frames(width: u32, height: u32, parallel_pixels: usize, seed: u32) -> (Vec<u8>, Vec<u8>) {
+    let mut random = Random(seed);
+    let mut vm = MicroCvm::builder()
+        .resolution(width, height)
+        .parallel_frame_pixels(parallel_pixels)
+        .build();
+    let cpu = vm.cpu_mut();
+    for byte in cpu.video_memory.bytes_mut() {
+        *byte = random.next() as u8;
+    }
+    for byte in &mut cpu.memory_mut()[..0x20000] {
+        *byte = random.next() as u8;
+    }
+    cpu.brightness = 180;
+    for sprite in &mut cpu.sprites_mut().sprites {
+        let [position, size, source] = [random.next(), random.next(), random.next()];
+        sprite.enabled = true;
+        sprite.x = (position % (width + 32)) as i16 - 16;
+        sprite.y = ((position >> 16) % (height + 32)) as i16 - 16;
+        sprite.width = (size % 33) as u8;
+        sprite.height = (size >> 8) as u8 % 33;
+        sprite.source = source % 0x10000;
+    }
+    let pixels = (width * height) as usize;
+    let mut plain = vec![0; pixels * 4];
+    vm.frame_rgba(&mut plain);
+
+    let cpu = vm.cpu_mut();
+    cpu.video_control |= VIDEO_CONTROL_TILES;
+    let tiles = cpu.tiles_mut();
+    (tiles.tile_set, tiles.map) = (0x10000, 0x1F000);
+    (tiles.scroll_x, tiles.scroll_y) = (13, 300);
+    let mut tiled = vec![0; pixels * 4];
+    vm.frame_rgba(&mut tiled);
+    (plain, tiled)
+}
+
+#[test]
+fn parallel_frames_match_sequential_ones() {
+    for (width, height) in [(1280, 720), (320, 200), (333, 97), (1, 300), (300, 1)] {
+        for seed in [1, 0x2545_f491] {
+            let parallel = frames(width, height, 0, seed);
+            let sequential = frames(width, height, usize::MAX, seed);
+            assert!(parallel.0 == sequential.0, "{}x{} plain", width, height);
+            assert!(parallel.1 == sequential.1, "{}x{} tiled", width, height);
+        }
+    }
+}
+
+#[test]
+fn the_cutoff_keeps_small_frames_sequential_and_the_same() {
+    // Just either side of the default cutoff.
+    for (width, height) in [(256, 255), (256, 256)] {
+        let default = frames(width, height, microcvm_rs::vm::PARALLEL_FRAME_PIXELS, 7);
+        assert!(default == frames(width, height, usize::MAX, 7));
+    }
+}
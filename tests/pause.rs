@@ -0,0 +1,96 @@
+// A paused machine has to stop taking instructions and cycles wherever it's run from, the
+// headless frame loop included, keep everything queued for it, and pick up where it stopped
+// once resumed.
+
+use microcvm_rs::asm::assemble;
+use microcvm_rs::vm::CYCLES_PER_FRAME;
+use microcvm_rs::{HaltReason, MicroCvm};
+
+// Counts in r0 forever.
+fn counter() -> MicroCvm {
+    let mut vm = MicroCvm::builder().build();
+    vm.load_program(&assemble("loop: inc r0\njr loop").unwrap())
+        .unwrap();
+    vm
+}
+
+// Frames the way `--headless` runs them.
+fn run_frames(vm: &mut MicroCvm, frames: u32) {
+    for _ in 0..frames {
+        match vm.run_frame(CYCLES_PER_FRAME).unwrap() {
+            HaltReason::FrameComplete => vm.tick_frame(),
+            HaltReason::Paused => return,
+            reason => panic!("{:?}", reason),
+        }
+    }
+}
+
+#[test]
+fn the_counters_stop_while_paused() {
+    let mut vm = counter();
+    run_frames(&mut vm, 2);
+    let running = vm.instructions();
+    assert!(running > 0);
+
+    vm.pause();
+    assert!(vm.is_paused());
+    let (instructions, cycles, r0) = (vm.instructions(), vm.cpu().cycles, vm.cpu().registers[0]);
+    for _ in 0..3 {
+        run_frames(&mut vm, 5);
+        assert_eq!(vm.run().unwrap(), HaltReason::Paused);
+        assert_eq!(vm.run_for(100).unwrap(), HaltReason::Paused);
+    }
+    assert_eq!(
+        (vm.instructions(), vm.cpu().cycles, vm.cpu().registers[0]),
+        (instructions, cycles, r0)
+    );
+
+    vm.resume();
+    assert!(!vm.is_paused());
+    assert_eq!(vm.run_for(10).unwrap(), HaltReason::InstructionLimit);
+    assert_eq!(vm.instructions(), instructions + 10);
+    run_frames(&mut vm, 1);
+    assert!(vm.instructions() > instructions + 10);
+}
+
+#[test]
+fn queued_input_waits_out_a_pause() {
+    let mut vm = counter();
+    vm.push_key(0x1C, true);
+    vm.push_key(0x1C, false);
+    vm.pause();
+    run_frames(&mut vm, 3);
+    assert_eq!(vm.cpu().keyboard().events.len(), 2);
+    vm.resume();
+    assert_eq!(vm.cpu().keyboard().events.len(), 2);
+}
+
+#[test]
+fn pausing_and_resuming_twice_changes_nothing() {
+    let mut vm = counter();
+    vm.pause();
+    vm.pause();
+    vm.resume();
+    vm.resume();
+    assert!(!vm.is_paused());
+    assert_eq!(vm.run_for(5).unwrap(), HaltReason::InstructionLimit);
+}
+
+#[test]
+fn a_handle_pauses_a_machine_on_another_thread() {
+    let mut vm = counter();
+    let handle = vm.pause_handle();
+    let worker = std::thread::spawn(move || (vm.run(), vm));
+    std::thread::sleep(std::time::Duration::from_millis(10));
+    handle.pause();
+    let (result, mut vm) = worker.join().unwrap();
+    assert_eq!(result.unwrap(), HaltReason::Paused);
+    assert!(vm.is_paused());
+    let stopped_at = vm.instructions();
+    assert_eq!(vm.run().unwrap(), HaltReason::Paused);
+    assert_eq!(vm.instructions(), stopped_at);
+
+    handle.resume();
+    assert_eq!(vm.run_for(3).unwrap(), HaltReason::InstructionLimit);
+    assert_eq!(vm.instructions(), stopped_at + 3);
+}
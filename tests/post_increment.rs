@@ -0,0 +1,162 @@
+// A memcpy written in guest assembly with `[rN+]` pointers on both sides has to leave the
+// same bytes behind as copying them on the host, down each of the CPU's execution paths and
+// with either register width, and fault on the same bad operand. Both ranges cross a page, so
+// the pointers carry.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::cpu::RegisterWidth;
+use microcvm_rs::disasm::{decode, format_instruction};
+use microcvm_rs::symbols::SymbolTable;
+use microcvm_rs::trace::{TraceEntry, TraceSink};
+
+const SRC: u16 = 0x12F0;
+const DST: u16 = 0x20F8;
+const LEN: u16 = 40;
+
+// r0:r1 to r2:r3, r4 bytes.
+const MEMCPY_8: &str = "
+        mov r0, 0x12
+        mov r1, 0xF0
+        mov r2, 0x20
+        mov r3, 0xF8
+        mov r4, 40
+copy:   load r6, [r0+]
+        store [r2+], r6
+        djnz r4, copy
+        hlt
+";
+
+// r1 to r2, r4 words.
+const MEMCPY_16: &str = "
+        .width 16
+        mov r1, 0x12F0
+        mov r2, 0x20F8
+        mov r4, 20
+copy:   load r6, [r1+]
+        store [r2+], r6
+        djnz r4, copy
+        hlt
+";
+
+struct Discard;
+
+impl TraceSink for Discard {
+    fn trace(&mut self, _entry: &TraceEntry) {}
+}
+
+fn source_bytes() -> Vec<u8> {
+    (0..LEN)
+        .map(|i| (i as u8).wrapping_mul(37) ^ 0x5A)
+        .collect()
+}
+
+fn run(width: RegisterWidth, mut vm: MicroCvm) -> MicroCvm {
+    let source = if width == RegisterWidth::Eight {
+        MEMCPY_8
+    } else {
+        MEMCPY_16
+    };
+    vm.load_program(&assemble(source).unwrap()).unwrap();
+    let memory = vm.cpu_mut().memory_mut();
+    memory[SRC as usize..][..LEN as usize].copy_from_slice(&source_bytes());
+    vm.run().unwrap();
+    vm
+}
+
+#[test]
+fn guest_memcpy_matches_the_host() {
+    for width in [RegisterWidth::Eight, RegisterWidth::Sixteen] {
+        let builder = || MicroCvm::builder().register_width(width);
+        let vms = [
+            ("fast", run(width, builder().build())),
+            (
+                "uncached",
+                run(width, builder().decode_cache(false).build()),
+            ),
+            (
+                "traced",
+                run(width, builder().trace(Box::new(Discard)).build()),
+            ),
+        ];
+        let mut expected = vms[0].1.cpu().memory().to_vec();
+        expected.copy_within(SRC as usize..(SRC + LEN) as usize, DST as usize);
+        for (path, vm) in vms {
+            let cpu = vm.cpu();
+            let case = format!("{:?} {}", width, path);
+            assert_eq!(
+                cpu.memory()[DST as usize..][..LEN as usize],
+                expected[DST as usize..][..LEN as usize],
+                "{}",
+                case
+            );
+            let (src, dst) = match width {
+                RegisterWidth::Eight => (
+                    cpu.registers[0] << 8 | cpu.registers[1],
+                    cpu.registers[2] << 8 | cpu.registers[3],
+                ),
+                RegisterWidth::Sixteen => (cpu.registers[1], cpu.registers[2]),
+            };
+            assert_eq!((src, dst), (SRC + LEN, DST + LEN), "{}", case);
+        }
+    }
+}
+
+#[test]
+fn pointers_round_trip_through_the_disassembler() {
+    for (source, width, bytes) in [
+        ("load r1, [r2+]", RegisterWidth::Eight, [0x27, 1, 2]),
+        ("store [r4+], r7", RegisterWidth::Eight, [0x28, 4, 7]),
+        (
+            ".width 16\nload r1, [r3+]",
+            RegisterWidth::Sixteen,
+            [0x27, 1, 3],
+        ),
+    ] {
+        assert_eq!(assemble(source).unwrap(), bytes, "{}", source);
+        let opcode = decode(&bytes, width).unwrap();
+        assert_eq!(opcode.encode(), bytes);
+        let text = format_instruction(&opcode, 0, &SymbolTable::new());
+        assert!(source.ends_with(&text), "{}: {}", source, text);
+    }
+    // An odd pointer is only a register of its own with 16-bit registers.
+    assert_eq!(decode(&[0x27, 1, 3], RegisterWidth::Eight), None);
+    let error = assemble("load r1, [r3+]").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "line 1: with 8-bit registers a pointer is a register pair, and `r3` can't start one"
+    );
+}
+
+#[test]
+fn bad_operands_fault_the_same_way_down_every_path() {
+    // Both bad, one bad each way round, and an odd pointer that is only bad in pairs.
+    let operands = [[0x22, 0x95], [0x22, 0x02], [0x01, 0x95], [0x01, 0x03]];
+    for width in [RegisterWidth::Eight, RegisterWidth::Sixteen] {
+        for opcode in [0x27, 0x28] {
+            for [first, second] in operands {
+                let program = [opcode, first, second];
+                let builder = || MicroCvm::builder().register_width(width);
+                let errors = [
+                    builder().build(),
+                    builder().decode_cache(false).build(),
+                    builder().trace(Box::new(Discard)).build(),
+                ]
+                .map(|mut vm| {
+                    vm.load_program(&program).unwrap();
+                    vm.run().unwrap_err().cause().to_string()
+                });
+                let case = format!("{:?} {:02x?}", width, program);
+                assert_eq!(errors[0], errors[1], "{}", case);
+                assert_eq!(errors[0], errors[2], "{}", case);
+            }
+        }
+    }
+    // The destination of a load comes first, as in the order it's written.
+    let mut vm = MicroCvm::builder().build();
+    vm.load_program(&[0x27, 0x22, 0x95]).unwrap();
+    assert_eq!(
+        vm.run().unwrap_err().cause().to_string(),
+        "Invalid Register: 34"
+    );
+}
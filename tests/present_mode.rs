@@ -0,0 +1,44 @@
+// Every present mode asked for has to come back as itself where the surface has it, as the other
+// low-latency mode where only that one is there, and as vsync, which every surface has, otherwise.
+#![cfg(feature = "window")]
+
+use microcvm_rs::render::PresentMode::{self, Immediate, Mailbox, Vsync};
+use microcvm_rs::render::choose_present_mode;
+
+#[test]
+fn each_request_on_each_surface() {
+    let surfaces: [&[PresentMode]; 7] = [
+        &[Vsync, Mailbox, Immediate],
+        &[Vsync, Mailbox],
+        &[Vsync, Immediate],
+        &[Vsync],
+        &[],
+        // The order a surface lists its modes in doesn't matter.
+        &[Immediate, Mailbox, Vsync],
+        &[Mailbox, Immediate],
+    ];
+    let expected = [
+        // Vsync, Mailbox, Immediate requested
+        [Vsync, Mailbox, Immediate],
+        [Vsync, Mailbox, Mailbox],
+        [Vsync, Immediate, Immediate],
+        [Vsync, Vsync, Vsync],
+        [Vsync, Vsync, Vsync],
+        [Vsync, Mailbox, Immediate],
+        [Vsync, Mailbox, Immediate],
+    ];
+    for (supported, expected) in surfaces.iter().zip(expected) {
+        let chosen = PresentMode::ALL.map(|mode| choose_present_mode(mode, supported));
+        assert_eq!(chosen, expected, "on {:?}", supported);
+    }
+}
+
+#[test]
+fn names_round_trip() {
+    for mode in PresentMode::ALL {
+        assert_eq!(PresentMode::from_name(mode.name()), Some(mode));
+        assert_eq!(mode.to_string(), mode.name());
+    }
+    assert_eq!(PresentMode::from_name("fifo"), None);
+    assert_eq!(PresentMode::from_name("Vsync"), None);
+}
@@ -0,0 +1,108 @@
+// A profiled run exported as JSON has to read back into ProfileExport, with every count and
+// cycle accounted for, and `run --profile-out` has to write the same schema.
+#![cfg(feature = "json")]
+
+use std::process::Command;
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble_with_symbols;
+use microcvm_rs::profile::{PROFILE_SCHEMA_VERSION, ProfileExport};
+
+// Nested loops, a call and a memory operand, so several opcodes and addresses show up.
+const PROGRAM: &str = "
+        mov r4, 20
+outer:  mov r5, 30
+inner:  call bump
+        djnz r5, inner
+        djnz r4, outer
+        clr r0
+        hlt
+
+bump:   load r0, [count]
+        inc r0
+        store [count], r0
+        ret
+
+count:  .db 0
+";
+
+fn check(export: &ProfileExport, instructions: u64, cycles: u64, top: usize) {
+    assert_eq!(export.version, PROFILE_SCHEMA_VERSION);
+    assert_eq!(export.total.count, instructions);
+    assert_eq!(export.total.cycles, cycles);
+    let count: u64 = export.opcodes.iter().map(|opcode| opcode.count).sum();
+    let opcode_cycles: u64 = export.opcodes.iter().map(|opcode| opcode.cycles).sum();
+    assert_eq!((count, opcode_cycles), (instructions, cycles));
+    assert!(
+        export
+            .opcodes
+            .windows(2)
+            .all(|pair| pair[0].cycles >= pair[1].cycles)
+    );
+    assert!(export.hotspots.len() <= top);
+    assert!(
+        export
+            .hotspots
+            .windows(2)
+            .all(|pair| pair[0].cycles >= pair[1].cycles)
+    );
+    for hotspot in &export.hotspots {
+        assert!(
+            hotspot.count > 0 && hotspot.cycles >= hotspot.count,
+            "{:?}",
+            hotspot
+        );
+        assert!(hotspot.instruction.is_some(), "{:?}", hotspot);
+    }
+}
+
+#[test]
+fn exported_json_reads_back() {
+    let (program, symbols) = assemble_with_symbols(PROGRAM).unwrap();
+    let mut vm = MicroCvm::builder().build();
+    vm.load_program(&program).unwrap();
+    vm.cpu_mut().enable_profiler();
+    vm.run().unwrap();
+    let report = vm.cpu().profiler().unwrap().report();
+
+    let json = report.to_json(vm.cpu(), &symbols, 5);
+    let export: ProfileExport = serde_json::from_str(&json).unwrap();
+    check(&export, vm.cpu().instructions, vm.cpu().cycles, 5);
+    assert_eq!(export, report.export(vm.cpu(), &symbols, 5));
+    // Each of the 600 calls runs the four instructions of `bump`.
+    let named: Vec<_> = export
+        .hotspots
+        .iter()
+        .filter_map(|hotspot| Some((hotspot.symbol.as_deref()?, hotspot.count)))
+        .collect();
+    assert!(named.contains(&("bump", 600)), "{:?}", named);
+}
+
+#[test]
+fn run_writes_the_profile() {
+    let dir = std::env::temp_dir().join(format!("microcvm-profile-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (program, _) = assemble_with_symbols(PROGRAM).unwrap();
+    let image = dir.join("program.bin");
+    std::fs::write(&image, &program).unwrap();
+    let out = dir.join("stats.json");
+    let status = Command::new(env!("CARGO_BIN_EXE_microcvm-rs"))
+        .arg("run")
+        .arg(&image)
+        .arg("--headless")
+        .arg("--profile-out")
+        .arg(&out)
+        .current_dir(&dir)
+        .output()
+        .unwrap()
+        .status;
+    assert!(status.success(), "{}", status);
+
+    let export: ProfileExport =
+        serde_json::from_str(&std::fs::read_to_string(&out).unwrap()).unwrap();
+    let mut vm = MicroCvm::builder().build();
+    vm.load_program(&program).unwrap();
+    vm.run().unwrap();
+    check(&export, vm.cpu().instructions, vm.cpu().cycles, 32);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,66 @@
+// The profiler has to count every instruction a known loop runs, per address and per opcode,
+// with the cycles adding up to what the CPU counted.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble_with_symbols;
+use microcvm_rs::cpu::OpcodeType;
+use microcvm_rs::profile::ProfileEntry;
+
+const LOOP: &str = "
+        mov r4, 10
+again:  inc r1
+        djnz r4, again
+        hlt
+";
+
+#[test]
+fn a_loop_is_counted_instruction_by_instruction() {
+    let (program, symbols) = assemble_with_symbols(LOOP).unwrap();
+    let mut vm = MicroCvm::builder().build();
+    vm.load_program(&program).unwrap();
+    vm.cpu_mut().enable_profiler();
+    vm.run().unwrap();
+    let report = vm.cpu().profiler().unwrap().report();
+
+    assert_eq!(report.total.count, 22);
+    assert_eq!(report.total.count, vm.cpu().instructions);
+    assert_eq!(report.total.cycles, vm.cpu().cycles);
+
+    let again = symbols.address("again").unwrap();
+    let count_at = |pc: u16| {
+        report
+            .by_pc
+            .iter()
+            .find(|(at, _)| *at == pc)
+            .map(|(_, entry)| entry.count)
+    };
+    assert_eq!(count_at(0), Some(1));
+    assert_eq!(count_at(again), Some(10));
+    assert_eq!(count_at(again + 2), Some(10));
+    assert_eq!(report.by_pc.len(), 4);
+
+    let by_opcode = |opcode: OpcodeType| {
+        report
+            .by_opcode
+            .iter()
+            .find(|(op, _)| *op == opcode)
+            .map(|(_, entry)| entry.count)
+    };
+    assert_eq!(by_opcode(OpcodeType::Inc), Some(10));
+    assert_eq!(by_opcode(OpcodeType::Djnz), Some(10));
+    assert_eq!(by_opcode(OpcodeType::Hlt), Some(1));
+
+    // The loop body is the hottest, and each entry costs at least a cycle an instruction.
+    let top: Vec<u16> = report.top_hotspots(2).iter().map(|(pc, _)| *pc).collect();
+    assert!(top.contains(&again), "{:?}", top);
+    assert!(
+        report
+            .by_pc
+            .iter()
+            .all(|(_, ProfileEntry { count, cycles })| cycles >= count)
+    );
+
+    // Resetting starts the counts over.
+    vm.cpu_mut().profiler_mut().unwrap().reset();
+    assert_eq!(vm.cpu().profiler().unwrap().report().total.count, 0);
+}
@@ -0,0 +1,293 @@
+// Runs every `.asm` file in tests/programs and checks what it did against the `.expect` file
+// beside it, made of `key = value` lines with `#` starting a comment. Only the keys a file
+// lists are checked:
+//
+//     halt = Halted              # how the run ended, or `fault: ` and the error
+//     r0 = 0x002a                # r0 to r7, pc, sp and flags, in hex or decimal
+//     serial = "hi\n"            # the bytes the program sent, r0 at each `hcall 1`
+//     framebuffer = 0x1c2f3e4d   # the crc32 of the frame as RGBA
+//
+// With UPDATE_GOLDEN=1 the expectations are written from what the programs do instead: the
+// keys an existing file lists, or all of them for a new one.
+
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use microcvm_rs::asm::assemble;
+use microcvm_rs::crc32::crc32;
+use microcvm_rs::demo::{DEMO_HEIGHT, DEMO_WIDTH};
+use microcvm_rs::determinism::deterministic_builder;
+use microcvm_rs::vm::CYCLES_PER_FRAME;
+use microcvm_rs::{HaltReason, MicroCvm};
+
+const UPDATE_VAR: &str = "UPDATE_GOLDEN";
+// Enough for any test program; one that runs out has most likely hung.
+const MAX_INSTRUCTIONS: u64 = 10_000_000;
+const SERIAL_HCALL: u8 = 1;
+
+const REGISTERS: [&str; 8] = ["r0", "r1", "r2", "r3", "r4", "r5", "r6", "r7"];
+const KEYS: [&str; 14] = [
+    "halt",
+    "r0",
+    "r1",
+    "r2",
+    "r3",
+    "r4",
+    "r5",
+    "r6",
+    "r7",
+    "pc",
+    "sp",
+    "flags",
+    "serial",
+    "framebuffer",
+];
+
+struct Outcome {
+    halt: String,
+    registers: [u16; 8],
+    pc: u16,
+    sp: u16,
+    flags: u8,
+    serial: Vec<u8>,
+    framebuffer: u32,
+}
+
+#[test]
+fn programs() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs");
+    let mut sources: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "asm"))
+        .collect();
+    sources.sort();
+    assert!(!sources.is_empty(), "no programs in `{}`", dir.display());
+
+    let update = std::env::var_os(UPDATE_VAR).is_some();
+    let (mut failed, mut failures) = (0, String::new());
+    for source in &sources {
+        if let Err(report) = check(source, update) {
+            let name = source.strip_prefix(&dir).unwrap_or(source).display();
+            let _ = writeln!(failures, "{}:\n{}", name, report);
+            failed += 1;
+        }
+    }
+    assert!(
+        failed == 0,
+        "{} of {} programs failed:\n\n{}set {}=1 to accept what they do now",
+        failed,
+        sources.len(),
+        failures,
+        UPDATE_VAR
+    );
+}
+
+// Runs one program and checks it or updates its expectation, with the lines of the report
+// if it fails.
+fn check(source: &Path, update: bool) -> Result<(), String> {
+    let text = std::fs::read_to_string(source).map_err(|e| format!("  {}\n", e))?;
+    let program = assemble(&text).map_err(|e| format!("  {}\n", e))?;
+    let outcome = run(&program);
+    let expect = source.with_extension("expect");
+    let expected = match std::fs::read_to_string(&expect) {
+        Ok(expected) => Some(expected),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(format!("  `{}`: {}\n", expect.display(), e)),
+    };
+
+    if update {
+        let keys: Vec<&str> = match &expected {
+            Some(expected) => parse(expected)?.into_iter().map(|(key, _)| key).collect(),
+            None => KEYS.to_vec(),
+        };
+        let mut written = String::new();
+        for key in keys {
+            let _ = writeln!(written, "{} = {}", key, value(&outcome, key));
+        }
+        return std::fs::write(&expect, written).map_err(|e| format!("  {}\n", e));
+    }
+
+    let Some(expected) = expected else {
+        return Err(format!("  there is no `{}`\n", expect.display()));
+    };
+    let mut report = String::new();
+    for (key, raw) in parse(&expected)? {
+        let expected = canonical(key, raw).map_err(|e| format!("  {}: {}\n", key, e))?;
+        let actual = value(&outcome, key);
+        if expected != actual {
+            let _ = writeln!(report, "  {}: expected {}, got {}", key, expected, actual);
+        }
+    }
+    if report.is_empty() {
+        Ok(())
+    } else {
+        Err(report)
+    }
+}
+
+fn run(program: &[u8]) -> Outcome {
+    let serial = Arc::new(Mutex::new(Vec::new()));
+    let sent = serial.clone();
+    let mut vm = deterministic_builder()
+        .resolution(DEMO_WIDTH, DEMO_HEIGHT)
+        .max_instructions(MAX_INSTRUCTIONS)
+        .hcall(
+            SERIAL_HCALL,
+            Box::new(move |ctx| {
+                let byte = ctx.reg(microcvm_rs::cpu::Register::R0) as u8;
+                sent.lock().unwrap().push(byte);
+                Ok(())
+            }),
+        )
+        .build();
+    let halt = match load_and_run(&mut vm, program) {
+        Ok(reason) => reason.to_string(),
+        Err(e) => format!("fault: {}", e.cause()),
+    };
+    let cpu = vm.cpu();
+    let (registers, pc, sp, flags) = (cpu.registers, cpu.pc, cpu.sp, cpu.flags);
+    let framebuffer = crc32(&vm.render_frame_to_rgba());
+    let serial = serial.lock().unwrap().clone();
+    Outcome {
+        halt,
+        registers,
+        pc,
+        sp,
+        flags,
+        serial,
+        framebuffer,
+    }
+}
+
+// Frames end the way they do headless.
+fn load_and_run(
+    vm: &mut MicroCvm,
+    program: &[u8],
+) -> Result<HaltReason, microcvm_rs::error::VmError> {
+    vm.load_program(program)?;
+    loop {
+        match vm.run_frame(CYCLES_PER_FRAME)? {
+            HaltReason::FrameComplete => vm.tick_frame(),
+            reason => return Ok(reason),
+        }
+    }
+}
+
+// The `key = value` lines of an expectation, in order.
+fn parse(text: &str) -> Result<Vec<(&str, &str)>, String> {
+    let mut lines = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(format!("  line {}: expected `key = value`\n", index + 1));
+        };
+        let key = key.trim();
+        let Some(&key) = KEYS.iter().find(|&&known| known == key) else {
+            return Err(format!("  line {}: unknown key `{}`\n", index + 1, key));
+        };
+        lines.push((key, value.trim()));
+    }
+    Ok(lines)
+}
+
+// Leaves a `#` inside the serial string alone.
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    let mut escaped = false;
+    for (index, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..index],
+            _ => {}
+        }
+    }
+    line
+}
+
+// What the program did for `key`, written the way update writes it.
+fn value(outcome: &Outcome, key: &str) -> String {
+    if let Some(index) = REGISTERS.iter().position(|&name| name == key) {
+        return format!("{:#06x}", outcome.registers[index]);
+    }
+    match key {
+        "halt" => outcome.halt.clone(),
+        "pc" => format!("{:#06x}", outcome.pc),
+        "sp" => format!("{:#06x}", outcome.sp),
+        "flags" => format!("{:#04x}", outcome.flags),
+        "serial" => quote(&outcome.serial),
+        "framebuffer" => format!("{:#010x}", outcome.framebuffer),
+        _ => unreachable!("parse only lets known keys through"),
+    }
+}
+
+// An expected value written the way value writes it, so hand-written ones compare equal.
+fn canonical(key: &str, raw: &str) -> Result<String, String> {
+    let number = || {
+        let parsed = match raw.strip_prefix("0x").or_else(|| raw.strip_prefix("0X")) {
+            Some(hex) => u64::from_str_radix(hex, 16),
+            None => raw.parse(),
+        };
+        parsed.map_err(|_| format!("`{}` is not a number", raw))
+    };
+    Ok(match key {
+        "halt" => String::from(raw),
+        "serial" => quote(&unquote(raw)?),
+        "flags" => format!("{:#04x}", number()?),
+        "framebuffer" => format!("{:#010x}", number()?),
+        _ => format!("{:#06x}", number()?),
+    })
+}
+
+fn quote(bytes: &[u8]) -> String {
+    let mut quoted = String::from("\"");
+    for &byte in bytes {
+        match byte {
+            b'"' => quoted.push_str("\\\""),
+            b'\\' => quoted.push_str("\\\\"),
+            b'\n' => quoted.push_str("\\n"),
+            b'\t' => quoted.push_str("\\t"),
+            b' '..=b'~' => quoted.push(byte as char),
+            _ => {
+                let _ = write!(quoted, "\\x{:02x}", byte);
+            }
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn unquote(raw: &str) -> Result<Vec<u8>, String> {
+    let bad = || format!("`{}` is not a quoted string", raw);
+    let inner = raw
+        .strip_prefix('"')
+        .and_then(|raw| raw.strip_suffix('"'))
+        .ok_or_else(bad)?;
+    let mut bytes = Vec::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buffer = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            continue;
+        }
+        match chars.next().ok_or_else(bad)? {
+            'n' => bytes.push(b'\n'),
+            't' => bytes.push(b'\t'),
+            '"' => bytes.push(b'"'),
+            '\\' => bytes.push(b'\\'),
+            'x' => {
+                let hex: String = chars.by_ref().take(2).collect();
+                bytes.push(u8::from_str_radix(&hex, 16).map_err(|_| bad())?);
+            }
+            _ => return Err(bad()),
+        }
+    }
+    Ok(bytes)
+}
@@ -0,0 +1,13 @@
+; dec wraps 0 around to 255 and borrows, setting carry like `sub r, 1`; clr zeroes a
+; register and leaves the flags alone like `mov r, 0`.
+
+        mov r0, 0
+        dec r0                  ; 255, and C
+        pushf
+        load r2, [0xFEFF]       ; the flags dec left
+        popf
+        mov r1, 1
+        dec r1                  ; 0, and Z without C
+        mov r3, 7
+        clr r3                  ; still Z
+        hlt
@@ -0,0 +1,7 @@
+; A table slot past the end of the address space faults instead of wrapping to the start.
+
+        .equ table, 0xFF80
+
+        mov r1, 200
+        jtab r1, table
+        hlt
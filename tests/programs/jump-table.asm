@@ -0,0 +1,29 @@
+; Dispatches on each index of a 4-entry jump table in turn. Every handler sends its index's
+; digit through the serial port and goes back for the next one. The handlers are laid out
+; out of order, so only the table says which one an index reaches.
+
+        .equ SERIAL, 1
+
+        mov r1, 0
+next:   jtab r1, handlers
+back:   inc r1
+        mov r2, r1
+        sub r2, 4
+        jrnz next
+        hlt
+
+two:    mov r0, '2'
+        hcall SERIAL
+        jr back
+zero:   mov r0, '0'
+        hcall SERIAL
+        jr back
+three:  mov r0, '3'
+        hcall SERIAL
+        jr back
+one:    mov r0, '1'
+        hcall SERIAL
+        jr back
+
+handlers:
+        .jumptable zero, one, two, three
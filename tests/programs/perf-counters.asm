@@ -0,0 +1,35 @@
+; Times a loop of 1000 djnz's with the performance counters and sends the cycle count it
+; measured over the serial port, low byte first. It halts with exit code 0 if that was the
+; 1010 cycles the loop takes, counting the outer loop and the load that reads the count,
+; or 1 if not. r5 ends up with how many instructions ran before the last load.
+
+        .equ SERIAL, 1
+        .equ PERF_INSTRUCTIONS, 0xFF98
+        .equ PERF_DELTA, 0xFF9C
+
+        load r0, [PERF_DELTA]           ; starts the count from here
+        mov r4, 4
+outer:  mov r5, 250
+inner:  djnz r5, inner
+        djnz r4, outer
+        load r0, [PERF_DELTA]           ; latches all four bytes
+        load r1, [PERF_DELTA+1]
+        load r2, [PERF_DELTA+2]
+        load r3, [PERF_DELTA+3]
+        mov r7, r0
+        hcall SERIAL
+        mov r0, r1
+        hcall SERIAL
+
+        mov r0, 1
+        sub r7, 0xF2
+        jrnz done
+        sub r1, 0x03
+        jrnz done
+        test r2, r2
+        jrnz done
+        test r3, r3
+        jrnz done
+        mov r0, 0
+done:   load r5, [PERF_INSTRUCTIONS]
+        hlt
@@ -0,0 +1,30 @@
+; Calls `sum` with its two arguments on the stack and gets their sum back in r0. No
+; instruction pushes a register, so the caller makes room with a pushf per byte, fills the
+; slots in with `store [sp + n]` and drops them again with popf once the call returns.
+; Offsets are from sp as it is when each instruction runs, so inside `sum` the arguments
+; sit above the return address, and a byte further up after its own pushf.
+
+        .equ first, 2
+        .equ second, 3
+
+        mov r1, 30
+        mov r2, 12
+        pushf
+        pushf
+        store [sp], r1
+        store [sp + 1], r2
+        call sum
+        ; Leave flags of 0 behind rather than the arguments.
+        clr r1
+        store [sp], r1
+        store [sp + 1], r1
+        popf
+        popf
+        hlt
+
+sum:    load r0, [sp + first]
+        pushf
+        load r3, [sp + second + 1]
+        popf
+        add r0, r3
+        ret
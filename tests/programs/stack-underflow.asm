@@ -0,0 +1,4 @@
+; Nothing has been pushed yet, so there is no slot at sp to load from.
+
+        load r0, [sp]
+        hlt
@@ -0,0 +1,115 @@
+// A read-only range has to refuse a store to its first and last byte and let through the bytes
+// just outside it, and `unprotect` has to lift exactly the range it's given, splitting a range
+// it lands in the middle of.
+
+use std::ops::Range;
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::error::VmError;
+use microcvm_rs::protect::{Protection, RangeSet};
+
+const PROTECTED: Range<usize> = 0x2010..0x2020;
+
+// Stores r1 at `addr` from the top of a fresh run, returning what the store did.
+fn store_at(vm: &mut MicroCvm, addr: u16) -> Result<(), VmError> {
+    let program = assemble(&format!("mov r1, 0xAB\nstore [{:#x}], r1\nhlt", addr)).unwrap();
+    vm.load_program(&program).unwrap();
+    vm.cpu_mut().pc = 0;
+    vm.cpu_mut().halted = false;
+    vm.run().map(|_| ())
+}
+
+fn protected_vm() -> MicroCvm {
+    let mut vm = MicroCvm::builder().build();
+    vm.cpu_mut().protect(PROTECTED, Protection::ReadOnly);
+    vm
+}
+
+#[test]
+fn stores_at_the_edges() {
+    let mut vm = protected_vm();
+    for addr in [PROTECTED.start, PROTECTED.end - 1] {
+        let error = store_at(&mut vm, addr as u16).unwrap_err();
+        assert!(
+            matches!(error.cause(), VmError::WriteProtected { addr: a, pc: 3 } if *a as usize == addr),
+            "{:#x}: {:?}",
+            addr,
+            error
+        );
+        assert_eq!(vm.cpu().memory()[addr], 0, "{:#x}", addr);
+    }
+    for addr in [PROTECTED.start - 1, PROTECTED.end] {
+        store_at(&mut vm, addr as u16).unwrap();
+        assert_eq!(vm.cpu().memory()[addr], 0xAB, "{:#x}", addr);
+    }
+}
+
+#[test]
+fn block_writes_fault_at_the_first_protected_byte() {
+    let mut vm = protected_vm();
+    // memset from 0x2008 for 0x10 bytes reaches 8 bytes into the range.
+    let program = assemble(
+        "
+        mov r0, 0x08
+        mov r1, 0x20
+        mov r2, 0xCD
+        mov r3, 0x10
+        mov r4, 0
+        memset r0
+        hlt
+",
+    )
+    .unwrap();
+    vm.load_program(&program).unwrap();
+    let error = vm.run().unwrap_err();
+    assert!(
+        matches!(error.cause(), VmError::WriteProtected { addr: 0x2010, .. }),
+        "{:?}",
+        error
+    );
+    // Nothing was written, not even the bytes before the range.
+    assert!(vm.cpu().memory()[0x2008..0x2018].iter().all(|&b| b == 0));
+}
+
+#[test]
+fn unprotect_lifts_only_its_range() {
+    let mut vm = protected_vm();
+    vm.cpu_mut().unprotect(0x2014..0x2018);
+    for addr in [0x2013, 0x2018] {
+        assert!(store_at(&mut vm, addr).is_err(), "{:#x}", addr);
+    }
+    for addr in [0x2014, 0x2017] {
+        store_at(&mut vm, addr).unwrap();
+    }
+    vm.cpu_mut().unprotect(0x0000..0x10000);
+    for addr in [PROTECTED.start, PROTECTED.end - 1] {
+        store_at(&mut vm, addr as u16).unwrap();
+    }
+}
+
+#[test]
+fn range_sets_merge_and_split() {
+    let mut set = RangeSet::new();
+    set.insert(10..20);
+    set.insert(30..40);
+    // Adjacent and overlapping ranges merge.
+    set.insert(20..25);
+    set.insert(24..31);
+    assert_eq!(set.ranges().to_vec(), vec![10..40]);
+    assert!(!set.contains(9));
+    assert!(set.contains(10));
+    assert!(set.contains(39));
+    assert!(!set.contains(40));
+
+    set.remove(15..18);
+    assert_eq!(set.ranges(), [10..15, 18..40]);
+    assert_eq!(set.first_in(0..12), Some(10));
+    assert_eq!(set.first_in(15..18), None);
+    assert_eq!(set.first_in(16..100), Some(18));
+    set.remove(0..100);
+    assert!(set.is_empty());
+    // Empty ranges change nothing.
+    set.insert(5..5);
+    assert!(set.is_empty());
+}
@@ -0,0 +1,138 @@
+// Any io::Read has to load from the offset it is given and say how much it loaded, and one
+// holding more than fits in memory has to stop at the end of memory with an error rather
+// than being read on and on.
+
+use std::io::{Cursor, Error, ErrorKind, Read};
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::error::VmError;
+
+fn machine() -> MicroCvm {
+    MicroCvm::builder().memory_size(256).build()
+}
+
+// Reads `data` a few bytes at a time, getting interrupted before each chunk, and counts what
+// was asked of it.
+struct Trickle {
+    data: Cursor<Vec<u8>>,
+    interrupt: bool,
+    read: usize,
+}
+
+impl Read for Trickle {
+    fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+        self.interrupt = !self.interrupt;
+        if self.interrupt {
+            return Err(Error::from(ErrorKind::Interrupted));
+        }
+        let length = buffer.len().min(3);
+        let read = self.data.read(&mut buffer[..length])?;
+        self.read += read;
+        Ok(read)
+    }
+}
+
+#[test]
+fn a_cursor_loads_at_its_offset() {
+    let mut vm = machine();
+    let loaded = vm
+        .load_from_reader(Cursor::new(vec![1, 2, 3, 4]), 0x10)
+        .unwrap();
+    assert_eq!(loaded, 4);
+    assert_eq!(vm.cpu().memory()[0x0F..0x15], [0, 1, 2, 3, 4, 0]);
+
+    // Nothing to read is fine, and so is filling memory exactly.
+    assert_eq!(vm.load_from_reader(std::io::empty(), 0x20).unwrap(), 0);
+    assert_eq!(
+        vm.load_from_reader(Cursor::new(vec![7; 0x40]), 0xC0)
+            .unwrap(),
+        0x40
+    );
+    assert!(vm.cpu().memory()[0xC0..].iter().all(|&byte| byte == 7));
+    assert_eq!(vm.load_from_reader(std::io::empty(), 0x100).unwrap(), 0);
+}
+
+#[test]
+fn short_and_interrupted_reads_are_carried_on_with() {
+    let mut vm = machine();
+    let data: Vec<u8> = (1..=100).collect();
+    let mut reader = Trickle {
+        data: Cursor::new(data.clone()),
+        interrupt: false,
+        read: 0,
+    };
+    assert_eq!(vm.load_from_reader(&mut reader, 0).unwrap(), 100);
+    assert_eq!(vm.cpu().memory()[..100], data[..]);
+}
+
+#[test]
+fn an_oversized_reader_stops_at_the_end_of_memory() {
+    let mut vm = machine();
+    let error = vm
+        .load_from_reader(Cursor::new(vec![0xAA; 0x81]), 0x80)
+        .unwrap_err();
+    assert!(
+        matches!(
+            error,
+            VmError::ImageTooLarge {
+                offset: 0x80,
+                capacity: 0x80
+            }
+        ),
+        "{}",
+        error
+    );
+    assert!(vm.cpu().memory()[..0x80].iter().all(|&byte| byte == 0));
+    assert!(vm.cpu().memory()[0x80..].iter().all(|&byte| byte == 0xAA));
+
+    // An endless one is read no further than one byte past what fits.
+    let mut reader = Trickle {
+        data: Cursor::new(vec![0x55; 1 << 20]),
+        interrupt: false,
+        read: 0,
+    };
+    let error = vm.load_from_reader(&mut reader, 0x10).unwrap_err();
+    assert!(matches!(error, VmError::ImageTooLarge { offset: 0x10, .. }));
+    assert_eq!(reader.read, 0xF0 + 1);
+    assert!(matches!(
+        vm.load_from_reader(std::io::repeat(1), 0x100),
+        Err(VmError::ImageTooLarge { capacity: 0, .. })
+    ));
+}
+
+#[test]
+fn read_errors_come_back_as_io_errors() {
+    struct Broken;
+
+    impl Read for Broken {
+        fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+            Err(Error::other("cable unplugged"))
+        }
+    }
+
+    let error = machine().load_from_reader(Broken, 0).unwrap_err();
+    assert!(matches!(&error, VmError::Io(e) if e.to_string() == "cable unplugged"));
+}
+
+#[test]
+fn files_load_at_address_0_into_the_memory_there_is() {
+    let dir = std::env::temp_dir().join("microcvm-reader-test");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("image.bin");
+    std::fs::write(&path, [9, 8, 7]).unwrap();
+
+    let mut vm = machine();
+    let cpu = vm.cpu_mut();
+    assert_eq!(
+        cpu.read_memory_from_file(path.to_str().unwrap()).unwrap(),
+        3
+    );
+    assert_eq!(cpu.memory()[..4], [9, 8, 7, 0]);
+    assert_eq!(cpu.memory().len(), 256);
+
+    let missing = dir.join("missing.bin");
+    assert!(matches!(
+        cpu.read_memory_from_file(missing.to_str().unwrap()),
+        Err(VmError::Io(e)) if e.kind() == ErrorKind::NotFound
+    ));
+}
@@ -0,0 +1,173 @@
+// Recording has to write one numbered file per kept frame, each the size of the screen and what
+// it showed, list raw frames in index.txt, and count what it couldn't write rather than stall.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::demo::{DEMO_HEIGHT, DEMO_WIDTH, bounce_program};
+use microcvm_rs::png;
+use microcvm_rs::record::{RecordConfig, RecordFormat, Recorder};
+use microcvm_rs::vm::CYCLES_PER_FRAME;
+use std::path::PathBuf;
+
+fn empty_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("microcvm-record-test-{}", name));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+fn bounce() -> MicroCvm {
+    let mut vm = MicroCvm::builder()
+        .resolution(DEMO_WIDTH, DEMO_HEIGHT)
+        .build();
+    vm.load_program(&bounce_program()).unwrap();
+    vm
+}
+
+// Runs 30 frames through `recorder` and returns what each of them showed.
+fn record_30(vm: &mut MicroCvm, recorder: &mut Recorder) -> Vec<Vec<u8>> {
+    let mut shown = Vec::new();
+    for _ in 0..30 {
+        vm.run_frame(CYCLES_PER_FRAME).unwrap();
+        recorder.capture(vm);
+        shown.push(vm.render_frame_to_rgba());
+        vm.tick_frame();
+    }
+    shown
+}
+
+#[test]
+fn thirty_frames_as_pngs() {
+    let dir = empty_dir("png");
+    let config = RecordConfig {
+        dir: dir.clone(),
+        every: 1,
+        format: RecordFormat::Png,
+        // Room for every frame, so none are dropped however slow the disk.
+        queue: 30,
+    };
+    let mut vm = bounce();
+    let mut recorder = Recorder::start(config).unwrap();
+    let shown = record_30(&mut vm, &mut recorder);
+    let stats = recorder.finish().unwrap();
+    assert_eq!((stats.written, stats.dropped), (30, 0));
+    assert_eq!(stats.to_string(), "30 frames written");
+
+    for (number, shown) in shown.iter().enumerate() {
+        let bytes = std::fs::read(dir.join(format!("frame-{:06}.png", number))).unwrap();
+        let image = png::decode(&bytes).unwrap();
+        assert_eq!((image.width, image.height), (DEMO_WIDTH, DEMO_HEIGHT));
+        assert_eq!(&image.rgba, shown, "frame {}", number);
+    }
+    assert!(!dir.join("frame-000030.png").exists());
+    // The ball moves, so the frames aren't all the same one.
+    assert_ne!(shown[0], shown[29]);
+    assert!(!dir.join("index.txt").exists());
+}
+
+#[test]
+fn raw_frames_are_listed_in_the_index() {
+    let dir = empty_dir("raw");
+    let config = RecordConfig {
+        dir: dir.clone(),
+        every: 3,
+        format: RecordFormat::Raw,
+        queue: 10,
+    };
+    let mut vm = bounce();
+    let mut recorder = Recorder::start(config).unwrap();
+    let shown = record_30(&mut vm, &mut recorder);
+    let stats = recorder.finish().unwrap();
+    assert_eq!((stats.written, stats.dropped), (10, 0));
+
+    let index = std::fs::read_to_string(dir.join("index.txt")).unwrap();
+    let lines: Vec<&str> = index.lines().collect();
+    assert_eq!(lines.len(), 10);
+    for (number, line) in lines.iter().enumerate() {
+        // Numbered without gaps, with the frame they were out of all those presented.
+        let name = format!("frame-{:06}.rgba", number);
+        let presented = number * 3;
+        assert_eq!(
+            *line,
+            format!("{} {} {} {}", name, DEMO_WIDTH, DEMO_HEIGHT, presented)
+        );
+        let rgba = std::fs::read(dir.join(&name)).unwrap();
+        assert_eq!(rgba.len(), DEMO_WIDTH as usize * DEMO_HEIGHT as usize * 4);
+        assert_eq!(rgba, shown[presented], "frame {}", number);
+    }
+}
+
+#[test]
+fn disabled_frames_count_towards_every_but_are_not_written() {
+    let dir = empty_dir("disabled");
+    let config = RecordConfig {
+        dir: dir.clone(),
+        every: 2,
+        format: RecordFormat::Raw,
+        queue: 30,
+    };
+    let mut vm = bounce();
+    let mut recorder = Recorder::start(config).unwrap();
+    for frame in 0..30 {
+        recorder.enabled = !(10..20).contains(&frame);
+        vm.run_frame(CYCLES_PER_FRAME).unwrap();
+        recorder.capture(&mut vm);
+        vm.tick_frame();
+    }
+    let stats = recorder.finish().unwrap();
+    assert_eq!((stats.written, stats.dropped), (10, 0));
+    let index = std::fs::read_to_string(dir.join("index.txt")).unwrap();
+    let presented: Vec<u64> = index
+        .lines()
+        .map(|line| line.rsplit(' ').next().unwrap().parse().unwrap())
+        .collect();
+    assert_eq!(presented, [0, 2, 4, 6, 8, 20, 22, 24, 26, 28]);
+}
+
+#[test]
+fn frames_the_writer_cannot_take_are_dropped_and_counted() {
+    let dir = empty_dir("dropped");
+    let config = RecordConfig {
+        dir: dir.clone(),
+        every: 1,
+        format: RecordFormat::Png,
+        queue: 1,
+    };
+    let mut vm = bounce();
+    let mut recorder = Recorder::start(config).unwrap();
+    // The writer fails on the first frame and stops, so at most that one and the one queued
+    // behind it get to it.
+    std::fs::remove_dir_all(&dir).unwrap();
+    record_30(&mut vm, &mut recorder);
+    assert!(recorder.dropped() >= 28, "{}", recorder.dropped());
+    let error = recorder.finish().unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+
+    // Without a failure, everything captured is either written or dropped.
+    let dir = empty_dir("dropped-queue");
+    let config = RecordConfig {
+        dir,
+        every: 1,
+        format: RecordFormat::Png,
+        queue: 1,
+    };
+    let mut recorder = Recorder::start(config).unwrap();
+    record_30(&mut vm, &mut recorder);
+    let dropped = recorder.dropped();
+    let stats = recorder.finish().unwrap();
+    assert_eq!(stats.dropped, dropped);
+    assert_eq!(stats.written + stats.dropped, 30);
+    if stats.dropped > 0 {
+        assert!(
+            stats.to_string().ends_with(" dropped to keep up"),
+            "{}",
+            stats
+        );
+    }
+}
+
+#[test]
+fn format_names_round_trip() {
+    for format in [RecordFormat::Png, RecordFormat::Raw] {
+        assert_eq!(RecordFormat::from_name(format.name()), Some(format));
+    }
+    assert_eq!(RecordFormat::from_name("rgba"), None);
+}
@@ -0,0 +1,45 @@
+// Typed register access has to reach the same slots as the raw array, and every register's
+// name has to parse back to it.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::parse_register;
+use microcvm_rs::cpu::{Register, RegisterWidth};
+
+#[test]
+fn index_reaches_the_register_file() {
+    let mut vm = MicroCvm::builder().build();
+    let cpu = vm.cpu_mut();
+    for (i, reg) in Register::ALL.into_iter().enumerate() {
+        cpu[reg] = 0x10 + i as u16;
+        assert_eq!(reg as usize, i);
+    }
+    assert_eq!(
+        cpu.registers,
+        [0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17]
+    );
+    assert_eq!(cpu.get_reg(Register::R5), 0x15);
+    let pairs: Vec<(Register, u16)> = cpu.registers().collect();
+    assert_eq!(pairs[7], (Register::R7, 0x17));
+    assert_eq!(pairs.len(), 8);
+
+    // set_reg keeps to the register width, while the index writes what it's given.
+    cpu.set_reg(Register::R1, 0x1234);
+    assert_eq!(cpu[Register::R1], 0x34);
+    let mut vm = MicroCvm::builder()
+        .register_width(RegisterWidth::Sixteen)
+        .build();
+    vm.cpu_mut().set_reg(Register::R1, 0x1234);
+    assert_eq!(vm.cpu()[Register::R1], 0x1234);
+}
+
+#[test]
+fn names_round_trip_through_the_parser() {
+    for reg in Register::ALL {
+        assert_eq!(parse_register(reg.name()), Some(reg));
+        assert_eq!(parse_register(&reg.name().to_uppercase()), Some(reg));
+        assert_eq!(reg.to_string(), reg.name());
+    }
+    for text in ["r8", "r", "r01", "x1", ""] {
+        assert_eq!(parse_register(text), None, "{}", text);
+    }
+}
@@ -0,0 +1,61 @@
+// `microcvm repl` driven from a script: instructions run as they're typed, and a line that
+// doesn't assemble or an instruction that faults is reported without changing the machine,
+// so what follows carries on from where it was.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn repl(script: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_microcvm-rs"))
+        .arg("repl")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(script.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn errors_and_faults_leave_the_machine_alone() {
+    let transcript = repl(
+        "mov r1, 5
+mov r2, 0
+bogus r1
+div r1, r2
+:mem 6 3
+:regs
+mov r3, 7
+:mem 0 9
+:quit
+",
+    );
+    assert_eq!(
+        transcript,
+        "MicroCVM REPL, :help for commands
+0x0000> r1: 0x00 -> 0x05
+0x0003> 0x0006> error: unknown instruction `bogus`
+0x0006> fault: Division by zero (pc 0x0006)
+0x0006> 0x0006: 00 00 00
+0x0006> r0=0x00 r1=0x05 r2=0x00 r3=0x00 r4=0x00 r5=0x00 r6=0x00 r7=0x00 \npc=0x0006 sp=0xff00 flags=0b00000000 cycles=3
+0x0006> r3: 0x00 -> 0x07
+0x0009> 0x0000: 06 01 05 06 02 00 06 03 07
+0x0009> "
+    );
+}
+
+#[test]
+fn end_of_input_leaves_the_repl() {
+    let transcript = repl("inc r0\n");
+    assert_eq!(
+        transcript,
+        "MicroCVM REPL, :help for commands\n0x0000> r0: 0x00 -> 0x01\n0x0002> \n"
+    );
+}
@@ -0,0 +1,85 @@
+// The executor's decoder has to read back exactly what `Opcode::encode` wrote, for every
+// instruction in the table with every kind of operand it takes, and anything it decodes from
+// arbitrary bytes has to encode back to the bytes it consumed.
+#![cfg(feature = "arbitrary")]
+
+use arbitrary::Unstructured;
+use microcvm_rs::MicroCvm;
+use microcvm_rs::cpu::RegisterWidth;
+use microcvm_rs::disasm::decode;
+use microcvm_rs::generate::instruction;
+use microcvm_rs::isa::instruction_table;
+
+const WIDTHS: [RegisterWidth; 2] = [RegisterWidth::Eight, RegisterWidth::Sixteen];
+const AT: u16 = 0x0100;
+
+// xorshift32, so every run sees the same bytes.
+fn noise(seed: u32, len: usize) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect()
+}
+
+fn scratch(width: RegisterWidth, bytes: &[u8]) -> MicroCvm {
+    let mut vm = MicroCvm::builder().register_width(width).build();
+    let at = AT as usize;
+    vm.cpu_mut().memory_mut()[at..at + bytes.len()].copy_from_slice(bytes);
+    vm.cpu_mut().pc = AT;
+    vm
+}
+
+#[test]
+fn every_instruction_decodes_to_what_it_encoded() {
+    for width in WIDTHS {
+        for info in instruction_table() {
+            // Enough draws that each operand sees registers, immediates and its extremes.
+            for seed in 1..=64u32 {
+                let data = noise(seed.wrapping_mul(0x9E37_79B9), 64);
+                let opcode =
+                    instruction(&mut Unstructured::new(&data), info.opcode, width).unwrap();
+                let bytes = opcode.encode();
+                assert_eq!(bytes.len(), opcode.length() as usize, "{:?}", opcode);
+                let decoded = scratch(width, &bytes).cpu_mut().create_opcode();
+                assert_eq!(decoded.ok(), Some(opcode), "{:?} {:02x?}", width, bytes);
+                assert_eq!(
+                    decode(&bytes, width),
+                    Some(opcode),
+                    "{:?} {:02x?}",
+                    width,
+                    bytes
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn decoded_bytes_encode_back() {
+    for width in WIDTHS {
+        let mut decoded = 0;
+        for seed in 1..=4096 {
+            let bytes = noise(seed, 8);
+            let Ok(opcode) = scratch(width, &bytes).cpu_mut().create_opcode() else {
+                continue;
+            };
+            decoded += 1;
+            let encoded = opcode.encode();
+            assert_eq!(
+                encoded,
+                bytes[..encoded.len()],
+                "{:?} {:?} from {:02x?}",
+                width,
+                opcode,
+                bytes
+            );
+        }
+        // Noise has to decode often enough for the property to mean something.
+        assert!(decoded > 200, "{:?}: {}", width, decoded);
+    }
+}
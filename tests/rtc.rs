@@ -0,0 +1,124 @@
+// The real-time clock has to show the guest the time it was last told to capture, all of it
+// from the same second, taken from whatever clock the host gives it, including a pinned one
+// in deterministic runs.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::bus::Device;
+use microcvm_rs::cpu::RTC_BASE;
+use microcvm_rs::determinism::{DETERMINISTIC_TIME, deterministic_builder};
+use microcvm_rs::rtc::{
+    ClockSource, DateTime, RTC_CAPTURE, RTC_DAY, RTC_HOURS, RTC_MINUTES, RTC_MONTH, RTC_SECONDS,
+    RTC_YEAR, Rtc,
+};
+
+// 2024-02-29 23:59:59 UTC.
+const LEAP_DAY: u64 = 1_709_251_199;
+
+// Captures, then reads the fields into r0 to r6: seconds, minutes, hours, day, month and the
+// year low byte first.
+fn read_clock(vm: &mut MicroCvm) -> [u16; 7] {
+    let mut source = format!("store [{:#x}], r0\n", RTC_BASE + RTC_CAPTURE as u16);
+    for (register, field) in [
+        RTC_SECONDS,
+        RTC_MINUTES,
+        RTC_HOURS,
+        RTC_DAY,
+        RTC_MONTH,
+        RTC_YEAR,
+        RTC_YEAR + 1,
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        source += &format!("load r{}, [{:#x}]\n", register, RTC_BASE + field as u16);
+    }
+    vm.load_program(&assemble(&(source + "hlt")).unwrap())
+        .unwrap();
+    vm.run().unwrap();
+    vm.cpu().registers[..7].try_into().unwrap()
+}
+
+fn fake_clock(start: u64) -> (Arc<AtomicU64>, Box<dyn ClockSource>) {
+    let now = Arc::new(AtomicU64::new(start));
+    let read = now.clone();
+    (now, Box::new(move || read.load(Ordering::SeqCst)))
+}
+
+#[test]
+fn the_guest_reads_the_captured_time() {
+    let (_, clock) = fake_clock(LEAP_DAY);
+    let mut vm = MicroCvm::builder().clock(clock).build();
+    assert_eq!(read_clock(&mut vm), [59, 59, 23, 29, 2, 0xE8, 0x07]);
+}
+
+#[test]
+fn reads_only_change_at_a_capture() {
+    let (now, clock) = fake_clock(LEAP_DAY);
+    let mut rtc = Rtc::new(Some(clock));
+    rtc.write(RTC_CAPTURE as u16, 0);
+    // The clock ticks over into March between reads of the fields.
+    assert_eq!(rtc.read(RTC_SECONDS as u16), 59);
+    now.store(LEAP_DAY + 1, Ordering::SeqCst);
+    assert_eq!(rtc.read(RTC_MINUTES as u16), 59);
+    assert_eq!(rtc.read(RTC_HOURS as u16), 23);
+    assert_eq!(rtc.read(RTC_DAY as u16), 29);
+    assert_eq!(rtc.read(RTC_MONTH as u16), 2);
+
+    rtc.write(RTC_CAPTURE as u16, 0);
+    let fields = [RTC_SECONDS, RTC_MINUTES, RTC_HOURS, RTC_DAY, RTC_MONTH];
+    let read: Vec<u8> = fields.iter().map(|&field| rtc.read(field as u16)).collect();
+    assert_eq!(read, [0, 0, 0, 1, 3]);
+    // Writing anything else doesn't capture.
+    now.store(LEAP_DAY + 61, Ordering::SeqCst);
+    rtc.write(RTC_SECONDS as u16, 0);
+    assert_eq!(rtc.read(RTC_MINUTES as u16), 0);
+}
+
+#[test]
+fn dates_convert_from_unix_time() {
+    let at = |year, month, day, hours, minutes, seconds| DateTime {
+        year,
+        month,
+        day,
+        hours,
+        minutes,
+        seconds,
+    };
+    for (seconds, expected) in [
+        (0, at(1970, 1, 1, 0, 0, 0)),
+        (LEAP_DAY, at(2024, 2, 29, 23, 59, 59)),
+        (DETERMINISTIC_TIME, at(2024, 1, 1, 0, 0, 0)),
+        // The last second of a century year that isn't a leap year, and the next.
+        (4_107_542_399, at(2100, 2, 28, 23, 59, 59)),
+        (4_107_542_400, at(2100, 3, 1, 0, 0, 0)),
+        (1_735_689_599, at(2024, 12, 31, 23, 59, 59)),
+    ] {
+        assert_eq!(DateTime::from_unix(seconds), expected, "{}", seconds);
+    }
+}
+
+#[test]
+fn deterministic_runs_see_a_pinned_time() {
+    for _ in 0..2 {
+        let mut vm = deterministic_builder().build();
+        assert_eq!(read_clock(&mut vm), [0, 0, 0, 1, 1, 0xE8, 0x07]);
+    }
+}
+
+#[test]
+fn without_a_clock_everything_reads_zero() {
+    let mut rtc = Rtc::new(None);
+    rtc.capture();
+    assert_eq!(rtc.latched, DateTime::default());
+    for offset in 0..8 {
+        assert_eq!(rtc.read(offset), 0);
+    }
+    // Until one is set.
+    rtc.set_clock(Some(Box::new(|| LEAP_DAY)));
+    rtc.capture();
+    assert_eq!(rtc.latched.year, 2024);
+}
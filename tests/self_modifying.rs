@@ -0,0 +1,82 @@
+// The decode cache must never run a stale instruction: code the guest patches ahead of the pc,
+// by a store or a block write, and code the host patches between runs has to run as patched,
+// the same with the cache on as off.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+
+fn run(enabled: bool, program: &[u8]) -> MicroCvm {
+    let mut vm = MicroCvm::builder().decode_cache(enabled).build();
+    vm.load_program(program).unwrap();
+    vm.run().unwrap();
+    vm
+}
+
+// r3 for `program` with the cache on and off.
+fn both(program: &str) -> [u16; 2] {
+    let program = assemble(program).unwrap();
+    [true, false].map(|enabled| run(enabled, &program).cpu().registers[3])
+}
+
+#[test]
+fn a_store_patches_an_immediate_before_it_runs() {
+    let program = "
+        mov r4, 2
+loop:   store [patch+2], r4     ; rewrites the immediate below before it runs
+patch:  add r3, 0
+        djnz r4, loop
+        hlt
+";
+    assert_eq!(both(program), [2 + 1; 2]);
+}
+
+#[test]
+fn a_store_swaps_an_instruction_that_already_ran() {
+    let dec = assemble("dec r0").unwrap()[0];
+    let program = format!(
+        "
+        mov r4, 3
+        mov r5, {:#04x}
+loop:
+patch:  inc r3                  ; the first time round, and dec after that
+        store [patch], r5
+        djnz r4, loop
+        hlt
+",
+        dec
+    );
+    assert_eq!(both(&program), [0xFF; 2]);
+}
+
+#[test]
+fn a_block_write_patches_code_it_lands_in() {
+    let program = "
+        mov r5, 2
+loop:
+patch:  add r3, 1
+        mov r0, patch+2
+        mov r1, 0
+        mov r2, 10
+        mov r4, 0
+        mov r6, r3
+        mov r3, 1
+        memset r0               ; the immediate becomes 10
+        mov r3, r6
+        djnz r5, loop
+        hlt
+";
+    assert_eq!(both(program), [1 + 10; 2]);
+}
+
+#[test]
+fn code_the_host_patches_runs_as_patched() {
+    let program = assemble("add r3, 1\nhlt").unwrap();
+    let mut vm = run(true, &program);
+    assert_eq!(vm.cpu().registers[3], 1);
+
+    let cpu = vm.cpu_mut();
+    cpu.memory_mut()[2] = 5;
+    (cpu.pc, cpu.halted) = (0, false);
+    vm.run().unwrap();
+    assert_eq!(vm.cpu().registers[3], 1 + 5);
+}
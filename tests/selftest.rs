@@ -0,0 +1,20 @@
+// The self-test ROM exercises every opcode and halts with the number of the first test that
+// failed, so with either register width it has to halt with 0.
+
+use microcvm_rs::HaltReason;
+use microcvm_rs::cpu::{Register, RegisterWidth};
+use microcvm_rs::demo::self_test_vm;
+
+#[test]
+fn the_self_test_passes_with_both_widths() {
+    for width in [RegisterWidth::Eight, RegisterWidth::Sixteen] {
+        let mut vm = self_test_vm(width);
+        assert_eq!(
+            vm.run().unwrap(),
+            HaltReason::Halted { code: 0 },
+            "{:?}",
+            width
+        );
+        assert_eq!(vm.cpu()[Register::R0], 0, "{:?}", width);
+    }
+}
@@ -0,0 +1,166 @@
+// Shutting a machine down has to stop it between two instructions wherever it is (waiting
+// for input, in the middle of DMA, already halted or faulted), write out its NVRAM, and do
+// nothing the second time. A thread stuck in a host call can't hold the shutdown up past
+// its timeout.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::error::VmError;
+use microcvm_rs::hcall::HcallContext;
+use microcvm_rs::nvram::{DEFAULT_NVRAM_LEN, Nvram};
+use microcvm_rs::vm::VmThread;
+
+// Saves a high score to NVRAM, then waits for a key that never comes.
+const WAITING: &str = "
+        mov r0, 0x5A
+        store [0x3F00], r0
+wait:   load r0, [0xFF30]
+        btst r0, 0
+        jrz wait
+        hlt
+";
+
+fn nvram_file(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("microcvm-shutdown-test-{}", name));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("save.nvram");
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn saved(path: &Path) -> u8 {
+    Nvram::load(path, DEFAULT_NVRAM_LEN as usize).unwrap().0[0]
+}
+
+// A machine running `source` on its own thread, with hcall 1 sleeping for `sleep`.
+fn spawn(source: &str, nvram: Option<&Path>, sleep: Duration) -> VmThread {
+    let sleepy = move |_: &mut HcallContext| {
+        std::thread::sleep(sleep);
+        Ok(())
+    };
+    let mut vm = MicroCvm::builder().hcall(1, Box::new(sleepy)).build();
+    if let Some(path) = nvram {
+        vm.cpu_mut().attach_nvram(path).unwrap();
+    }
+    vm.load_program(&assemble(source).unwrap()).unwrap();
+    let thread = vm.spawn();
+    std::thread::sleep(Duration::from_millis(20));
+    thread
+}
+
+#[test]
+fn a_machine_waiting_for_input_stops_and_saves() {
+    let path = nvram_file("waiting");
+    let mut thread = spawn(WAITING, Some(&path), Duration::ZERO);
+    thread.shutdown().unwrap();
+    assert_eq!(saved(&path), 0x5A);
+    // The second time there's nothing left to do.
+    thread.shutdown().unwrap();
+    let vm = thread.into_vm().unwrap();
+    assert!(vm.instructions() > 0 && !vm.halted() && vm.is_paused());
+}
+
+#[test]
+fn a_machine_in_the_middle_of_dma_stops() {
+    let mut thread = spawn(
+        "
+        mov r0, 0x10
+        store [0xFF27], r0      ; 4096 pixels
+        mov r0, 1
+copy:   store [0xFF29], r0
+        jmp copy
+",
+        None,
+        Duration::ZERO,
+    );
+    thread.shutdown().unwrap();
+    let vm = thread.into_vm().unwrap();
+    assert!(!vm.halted() && vm.instructions() > 3);
+}
+
+#[test]
+fn halted_and_faulted_machines_shut_down_with_how_they_ended() {
+    let mut thread = spawn("hlt", None, Duration::ZERO);
+    thread.shutdown().unwrap();
+    assert!(thread.into_vm().unwrap().halted());
+
+    let mut thread = spawn("div r0, 0", None, Duration::ZERO);
+    let error = thread.shutdown().unwrap_err();
+    assert!(matches!(error.cause(), VmError::DivisionByZero { .. }));
+    thread.shutdown().unwrap();
+    assert!(thread.into_vm().is_some());
+}
+
+#[test]
+fn a_paused_machine_shuts_down() {
+    let mut thread = spawn("spin: jmp spin", None, Duration::ZERO);
+    thread.pause_handle().pause();
+    std::thread::sleep(Duration::from_millis(10));
+    thread.shutdown().unwrap();
+    assert!(thread.into_vm().unwrap().is_paused());
+}
+
+#[test]
+fn a_wedged_thread_is_left_behind_after_the_timeout() {
+    let path = nvram_file("wedged");
+    let mut thread = spawn(
+        "
+        mov r0, 0x5A
+        store [0x3F00], r0
+        hcall 1
+        hlt
+",
+        Some(&path),
+        Duration::from_millis(500),
+    );
+    thread.set_shutdown_timeout(Duration::from_millis(50));
+    let started = Instant::now();
+    let error = thread.shutdown().unwrap_err();
+    assert!(started.elapsed() < Duration::from_millis(400));
+    assert!(
+        matches!(&error, VmError::Io(e) if e.kind() == std::io::ErrorKind::TimedOut),
+        "{}",
+        error
+    );
+    thread.shutdown().unwrap();
+    assert!(thread.into_vm().is_none());
+
+    // Its NVRAM is still written once the host call lets go.
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !path.exists() && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    std::thread::sleep(Duration::from_millis(20));
+    assert_eq!(saved(&path), 0x5A);
+}
+
+#[test]
+fn dropping_a_thread_shuts_it_down_without_panicking() {
+    let path = nvram_file("dropped");
+    drop(spawn(WAITING, Some(&path), Duration::ZERO));
+    assert_eq!(saved(&path), 0x5A);
+
+    // Wedged or not.
+    let mut thread = spawn("hcall 1\nhlt", None, Duration::from_millis(300));
+    thread.set_shutdown_timeout(Duration::from_millis(10));
+    drop(thread);
+}
+
+#[test]
+fn a_machine_on_this_thread_shuts_down_once() {
+    let path = nvram_file("local");
+    let mut vm = MicroCvm::builder().build();
+    vm.cpu_mut().attach_nvram(&path).unwrap();
+    vm.load_program(&assemble(WAITING).unwrap()).unwrap();
+    vm.run_for(50).unwrap();
+    vm.shutdown().unwrap();
+    assert!(vm.is_paused());
+    assert_eq!(saved(&path), 0x5A);
+
+    std::fs::remove_file(&path).unwrap();
+    vm.shutdown().unwrap();
+    assert!(!path.exists());
+}
@@ -0,0 +1,127 @@
+// A snapshot diff has to list exactly what changed between two snapshots, memory grouped into
+// contiguous ranges, be empty only for snapshots that are equal in every byte and pixel, and
+// summarize a large diff rather than print all of it.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+
+fn machine(source: &str) -> MicroCvm {
+    let mut vm = MicroCvm::builder().build();
+    vm.load_program(&assemble(source).unwrap()).unwrap();
+    vm
+}
+
+#[test]
+fn a_diff_lists_what_the_program_changed() {
+    let mut vm = machine(
+        "
+        mov r0, 0xde
+        store [0x0140], r0
+        mov r0, 0xad
+        store [0x0141], r0
+        mov r1, 0xef
+        store [0x0143], r1
+        store [0x0150], r1
+        hlt
+",
+    );
+    let before = vm.snapshot();
+    vm.run().unwrap();
+    let after = vm.snapshot();
+    let diff = before.diff(&after);
+
+    assert_eq!(diff.registers, [(0, 0x00, 0xad), (1, 0x00, 0xef)]);
+    let ranges: Vec<_> = diff.memory.iter().map(|change| change.range()).collect();
+    assert_eq!(ranges, [0x0140..0x0142, 0x0143..0x0144, 0x0150..0x0151]);
+    assert_eq!(diff.memory[0].old, [0x00, 0x00]);
+    assert_eq!(diff.memory[0].new, [0xde, 0xad]);
+    assert_eq!(diff.changed_bytes(), 4);
+    assert_eq!(diff.halted, Some((false, true)));
+    assert!(diff.pc.is_some() && diff.instructions.is_some() && diff.cycles.is_some());
+    assert_eq!((diff.sp, diff.bank, diff.call_depth), (None, None, None));
+    assert!(diff.video.is_empty());
+
+    let text = diff.to_string();
+    for line in [
+        "r0: 0x00 -> 0xad",
+        "mem[0x0140..0x0142]: 00 00 -> de ad",
+        "mem[0x0143..0x0144]: 00 -> ef",
+        "halted: false -> true",
+    ] {
+        assert!(text.lines().any(|l| l == line), "{:?} in\n{}", line, text);
+    }
+
+    // Going the other way swaps old and new.
+    let back = after.diff(&before);
+    assert_eq!(back.registers, [(0, 0xad, 0x00), (1, 0xef, 0x00)]);
+    assert_eq!(back.memory[0].new, [0x00, 0x00]);
+}
+
+#[test]
+fn only_equal_snapshots_have_an_empty_diff() {
+    let mut vm = machine("mov r0, 1\nhlt");
+    let snapshot = vm.snapshot();
+    assert!(snapshot.diff(&vm.snapshot()).is_empty());
+    assert_eq!(snapshot.diff(&snapshot).to_string(), "no changes");
+
+    // One bit anywhere is a difference.
+    let mut changed = snapshot.clone();
+    changed.memory[0x4000] ^= 0x10;
+    let diff = snapshot.diff(&changed);
+    assert!(!diff.is_empty());
+    assert_eq!(diff.changed_bytes(), 1);
+
+    let mut changed = snapshot.clone();
+    changed.cycles += 1;
+    assert_eq!(snapshot.diff(&changed).cycles, Some((0, 1)));
+
+    // Restoring a snapshot gives back a machine with nothing to tell them apart.
+    vm.run().unwrap();
+    vm.restore(&snapshot);
+    assert!(snapshot.diff(&vm.snapshot()).is_empty());
+}
+
+#[test]
+fn pixels_count_by_the_color_they_show() {
+    let mut vm = machine("mov r0, 0x1F\nmov r1, 0x00\nmov r2, 0x00\nvideo fill, r0\nhlt");
+    let before = vm.snapshot();
+    vm.run().unwrap();
+    let diff = before.diff(&vm.snapshot());
+    let pixels = vm.snapshot().video_memory.len();
+    assert_eq!(diff.video, vec![0..pixels]);
+    assert_eq!(diff.changed_pixels(), pixels);
+    assert!(
+        diff.to_string()
+            .contains(&format!("video[0..{}]: {} pixels", pixels, pixels))
+    );
+}
+
+#[test]
+fn large_diffs_are_summarized() {
+    let mut vm = machine("hlt");
+    let before = vm.snapshot();
+    let mut after = before.clone();
+    // 40 separate ranges, the first one long.
+    for i in 0..40 {
+        after.memory[0x1000 + i * 4] = 0xAA;
+    }
+    after.memory[0x0F00..0x0F20].fill(0x55);
+    vm.restore(&after);
+    let diff = before.diff(&vm.snapshot());
+    assert_eq!(diff.memory.len(), 41);
+    assert_eq!(diff.changed_bytes(), 40 + 32);
+
+    let text = diff.to_string();
+    assert_eq!(text.lines().filter(|l| l.starts_with("mem[")).count(), 16);
+    assert!(
+        text.lines().any(|l| l
+            == "mem[0x0f00..0x0f20]: 00 00 00 00 00 00 00 00 .. -> 55 55 55 55 55 55 55 55 .."),
+        "{}",
+        text
+    );
+    assert!(
+        text.ends_with("... 25 more memory ranges, 72 bytes changed in total"),
+        "{}",
+        text
+    );
+}
@@ -0,0 +1,155 @@
+// `int` has to enter its handler through the same frame a hardware interrupt builds, so a
+// guest "OS" routine can service a request and `iret` back with everything but the result
+// register as it was, and a vector past the table has to fault.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::cpu::{FLAG_CARRY, FLAG_INTERRUPT_ENABLE, FLAG_ZERO, STACK_TOP};
+use microcvm_rs::error::VmError;
+
+// Points the vector table at 0x0200, where `table` goes.
+fn with_table(body: &str, table: &str) -> Vec<u8> {
+    let source = format!(
+        "
+        mov r0, 0x00
+        store [0xFF90], r0
+        mov r0, 0x02
+        store [0xFF91], r0
+{}
+        .org 0x0200
+        .dw {}
+",
+        body, table
+    );
+    assemble(&source).unwrap()
+}
+
+fn run(program: &[u8]) -> MicroCvm {
+    let mut vm = MicroCvm::builder().build();
+    vm.load_program(program).unwrap();
+    vm.run().unwrap();
+    vm
+}
+
+#[test]
+fn an_os_routine_services_int_1_and_preserves_the_caller() {
+    // Service 1 adds r1 and r2, service 2 doubles r1; the result comes back in r0.
+    let program = with_table(
+        "
+        mov r1, 30
+        mov r2, 12
+        mov r3, 7
+        mov r7, 0x55
+        mov r0, 1
+        stc
+        int 1
+        mov r4, r0
+        mov r0, 2
+        int 1
+        mov r5, r0
+        hlt
+
+os:     djnz r0, double         ; service 1 falls through
+        mov r0, r1
+        add r0, r2              ; clears the caller's carry, until iret
+        iret
+double: mov r0, r1
+        add r0, r1
+        iret
+",
+        "0, os",
+    );
+    let vm = run(&program);
+    let cpu = vm.cpu();
+    assert_eq!(cpu.registers, [60, 30, 12, 7, 42, 60, 0, 0x55]);
+    assert_eq!(cpu.flags & (FLAG_CARRY | FLAG_ZERO), FLAG_CARRY);
+    assert_eq!(cpu.sp, STACK_TOP);
+    assert_eq!(cpu.call_depth(), 0);
+}
+
+#[test]
+fn int_builds_the_same_frame_as_a_hardware_interrupt() {
+    // Each handler stops where it starts, so the frame can be looked at.
+    let program = with_table(
+        "
+        stc
+        int 2
+        hlt
+handler: hlt
+",
+        "0, 0, handler",
+    );
+    let mut vm = MicroCvm::builder().build();
+    vm.load_program(&program).unwrap();
+    vm.run().unwrap();
+    let int_frame = frame(&vm);
+    // The flags as they were, then the instruction after the int at 0x000F.
+    assert_eq!(int_frame, [FLAG_CARRY, 0x11, 0x00]);
+    assert_eq!(vm.cpu().flags & FLAG_INTERRUPT_ENABLE, 0);
+
+    let program = with_table(
+        "
+        stc
+        ei
+wait:   jmp wait
+handler: hlt
+",
+        "handler",
+    );
+    let mut vm = MicroCvm::builder().build();
+    vm.load_program(&program).unwrap();
+    vm.run_for(100).unwrap();
+    vm.tick_frame();
+    vm.run_for(100).unwrap();
+    let [flags, lo, hi] = frame(&vm);
+    assert_eq!(flags, FLAG_CARRY | FLAG_INTERRUPT_ENABLE);
+    assert_eq!(u16::from_le_bytes([lo, hi]), 0x0010);
+}
+
+// The three bytes a handler finds from sp up.
+fn frame(vm: &MicroCvm) -> [u8; 3] {
+    let cpu = vm.cpu();
+    assert_eq!(cpu.sp, STACK_TOP - 3);
+    cpu.memory()[cpu.sp as usize..STACK_TOP as usize]
+        .try_into()
+        .unwrap()
+}
+
+#[test]
+fn iret_gives_back_the_interrupt_flag() {
+    let program = with_table(
+        "
+        ei
+        int 4
+        hlt
+handler: iret
+",
+        "0, 0, 0, 0, handler",
+    );
+    let flags = run(&program).cpu().flags;
+    assert_eq!(flags & FLAG_INTERRUPT_ENABLE, FLAG_INTERRUPT_ENABLE);
+}
+
+#[test]
+fn vectors_past_the_table_fault() {
+    // Entry 15 is the last one there is.
+    let table = format!("{}handler", "0, ".repeat(15));
+    let vm = run(&with_table("int 15\nhlt\nhandler: iret", &table));
+    assert_eq!(vm.cpu().sp, STACK_TOP);
+
+    for vector in [16, 255] {
+        let mut vm = MicroCvm::builder().build();
+        vm.load_program(&assemble(&format!("nop\nint {}", vector)).unwrap())
+            .unwrap();
+        let error = vm.run().unwrap_err();
+        assert!(
+            matches!(
+                error.cause(),
+                VmError::InvalidVector { vector: v, pc: 0x0001 } if *v == vector
+            ),
+            "{}",
+            error
+        );
+        assert_eq!(vm.cpu().sp, STACK_TOP);
+    }
+}
@@ -0,0 +1,170 @@
+// Sprites have to be drawn over the converted frame in table order, clipped at every edge of
+// the screen, with their transparent color left undrawn, and never written into video memory.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::cpu::SPRITE_BASE;
+use microcvm_rs::sprite::{SPRITE_COUNT, Sprite};
+
+const WIDTH: u32 = 6;
+const HEIGHT: u32 = 4;
+
+const RED: [u8; 3] = [255, 0, 0];
+const GREEN: [u8; 3] = [0, 255, 0];
+const BLUE: [u8; 3] = [0, 0, 255];
+const BLACK: [u8; 3] = [0, 0, 0];
+
+// A green screen with `width`x`height` sprite images of `rgb` at 0x1000, 0x1100 and on.
+fn machine(images: &[&[[u8; 3]]]) -> MicroCvm {
+    let mut vm = MicroCvm::builder().resolution(WIDTH, HEIGHT).build();
+    let cpu = vm.cpu_mut();
+    for (i, image) in images.iter().enumerate() {
+        let start = 0x1000 + i * 0x100;
+        for (pixel, rgb) in cpu.memory_mut()[start..].chunks_mut(3).zip(image.iter()) {
+            pixel.copy_from_slice(rgb);
+        }
+    }
+    let green = cpu.video_memory.encode(GREEN[0], GREEN[1], GREEN[2]);
+    cpu.video_memory.fill(0, (WIDTH * HEIGHT) as usize, green);
+    vm
+}
+
+fn sprite(x: i16, y: i16, width: u8, height: u8, image: u32, transparent: [u8; 3]) -> Sprite {
+    Sprite {
+        enabled: true,
+        x,
+        y,
+        width,
+        height,
+        source: 0x1000 + image * 0x100,
+        transparent,
+    }
+}
+
+// The frame as rows of R, G, B and K for black.
+fn picture(vm: &mut MicroCvm) -> Vec<String> {
+    let mut frame = vec![0; (WIDTH * HEIGHT * 4) as usize];
+    vm.frame_rgba(&mut frame);
+    frame
+        .chunks(WIDTH as usize * 4)
+        .map(|row| {
+            row.chunks(4)
+                .map(|pixel| match [pixel[0], pixel[1], pixel[2]] {
+                    RED => 'R',
+                    GREEN => 'G',
+                    BLUE => 'B',
+                    BLACK => 'K',
+                    other => panic!("unexpected color {:?}", other),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn video_untouched(vm: &MicroCvm) -> bool {
+    (0..(WIDTH * HEIGHT) as usize)
+        .all(|pixel| vm.framebuffer().pixel(pixel).to_rgba() == [0, 255, 0, 255])
+}
+
+#[test]
+fn overlapping_sprites_draw_in_table_order() {
+    let red = [RED; 9];
+    let blue = [BLACK, BLUE, BLUE, BLUE];
+    let mut vm = machine(&[&red, &blue]);
+    let sprites = &mut vm.cpu_mut().sprites_mut().sprites;
+    sprites[0] = sprite(-1, -1, 3, 3, 0, [1, 2, 3]);
+    sprites[1] = sprite(1, 1, 2, 2, 1, BLACK);
+    // Blue's black corner lets red through.
+    assert_eq!(
+        picture(&mut vm),
+        ["RRGGGG", "RRBGGG", "GBBGGG", "GGGGGG"].map(String::from)
+    );
+    let sprites = &mut vm.cpu_mut().sprites_mut().sprites;
+    sprites[1].x = 0;
+    sprites[1].y = 0;
+    assert_eq!(
+        picture(&mut vm),
+        ["RBGGGG", "BBGGGG", "GGGGGG", "GGGGGG"].map(String::from)
+    );
+
+    // The other way round, red covers blue wherever both are.
+    vm.cpu_mut().sprites_mut().sprites.swap(0, 1);
+    assert_eq!(
+        picture(&mut vm),
+        ["RRGGGG", "RRGGGG", "GGGGGG", "GGGGGG"].map(String::from)
+    );
+    assert!(video_untouched(&vm));
+}
+
+#[test]
+fn sprites_clip_at_every_edge() {
+    let blue = [BLUE; 9];
+    let mut vm = machine(&[&blue]);
+    for (x, y, expected) in [
+        (4, 2, ["GGGGGG", "GGGGGG", "GGGGBB", "GGGGBB"]),
+        (-2, 3, ["GGGGGG", "GGGGGG", "GGGGGG", "BGGGGG"]),
+        (5, -2, ["GGGGGB", "GGGGGG", "GGGGGG", "GGGGGG"]),
+        // All the way off, on each side.
+        (6, 0, ["GGGGGG", "GGGGGG", "GGGGGG", "GGGGGG"]),
+        (-3, 0, ["GGGGGG", "GGGGGG", "GGGGGG", "GGGGGG"]),
+        (0, 4, ["GGGGGG", "GGGGGG", "GGGGGG", "GGGGGG"]),
+        (i16::MIN, i16::MAX, ["GGGGGG", "GGGGGG", "GGGGGG", "GGGGGG"]),
+    ] {
+        vm.cpu_mut().sprites_mut().sprites[0] = sprite(x, y, 3, 3, 0, BLACK);
+        assert_eq!(picture(&mut vm), expected.map(String::from), "{}, {}", x, y);
+    }
+    assert!(video_untouched(&vm));
+}
+
+#[test]
+fn disabled_sprites_are_not_drawn() {
+    let blue = [BLUE; 4];
+    let mut vm = machine(&[&blue]);
+    for slot in 0..SPRITE_COUNT {
+        vm.cpu_mut().sprites_mut().sprites[slot] = Sprite {
+            enabled: false,
+            ..sprite(0, 0, 2, 2, 0, BLACK)
+        };
+    }
+    assert_eq!(picture(&mut vm), ["GGGGGG"; 4].map(String::from));
+}
+
+#[test]
+fn the_guest_sets_sprites_up_through_their_registers() {
+    let blue = [BLUE; 4];
+    let mut vm = machine(&[&blue]);
+    let source = format!(
+        "
+        .equ sprites, {:#x}
+        mov r0, 15
+        store [sprites], r0             ; the last sprite
+        mov r0, 0xFF
+        store [sprites + 2], r0         ; x = -1
+        store [sprites + 3], r0
+        mov r0, 2
+        store [sprites + 4], r0         ; y = 2
+        store [sprites + 6], r0         ; 2x2
+        store [sprites + 7], r0
+        mov r0, 0x00
+        store [sprites + 8], r0         ; at 0x001000
+        mov r0, 0x10
+        store [sprites + 9], r0
+        mov r0, 1
+        store [sprites + 1], r0         ; drawn
+        load r1, [sprites + 2]
+        hlt
+",
+        SPRITE_BASE
+    );
+    vm.load_program(&assemble(&source).unwrap()).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.cpu().registers[1], 0xFF);
+    assert_eq!(
+        vm.cpu().sprites().sprites[15],
+        sprite(-1, 2, 2, 2, 0, BLACK)
+    );
+    assert_eq!(
+        picture(&mut vm),
+        ["GGGGGG", "GGGGGG", "BGGGGG", "BGGGGG"].map(String::from)
+    );
+}
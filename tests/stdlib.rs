@@ -0,0 +1,282 @@
+// Assembles programs calling each standard library routine and checks what they sent over
+// serial, wrote to memory or drew against the same thing worked out on the host. The loops
+// keep their counter in r4, so every routine also has to leave r4 to r7 alone.
+
+use std::sync::{Arc, Mutex};
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::cpu::{FLAG_INTERRUPT_ENABLE, Register};
+use microcvm_rs::stdlib::MODULES;
+use microcvm_rs::types::Color;
+
+const SERIAL_HCALL: u8 = 1;
+const MAX_INSTRUCTIONS: u64 = 1_000_000;
+const SIZE: u32 = 16;
+// Where the programs that write to memory start writing, 0x2000.
+const BUFFER: usize = 0x2000;
+
+struct Run {
+    vm: MicroCvm,
+    serial: Vec<u8>,
+}
+
+// Runs `program` with every module included after it, as the library asks.
+fn run(program: &str) -> Run {
+    let mut source = String::from(program);
+    for (name, _) in MODULES {
+        source.push_str(&format!("\n        .include \"{}\"", name));
+    }
+    let image = assemble(&source).unwrap_or_else(|e| panic!("{}", e));
+
+    let serial = Arc::new(Mutex::new(Vec::new()));
+    let sent = serial.clone();
+    let mut vm = MicroCvm::builder()
+        .resolution(SIZE, SIZE)
+        .max_instructions(MAX_INSTRUCTIONS)
+        .hcall(
+            SERIAL_HCALL,
+            Box::new(move |ctx| {
+                sent.lock().unwrap().push(ctx.reg(Register::R0) as u8);
+                Ok(())
+            }),
+        )
+        .build();
+    vm.load_program(&image).unwrap();
+    vm.run().unwrap();
+    let serial = serial.lock().unwrap().clone();
+    Run { vm, serial }
+}
+
+fn every_byte(routine: &str) -> String {
+    format!(
+        "
+        clr r4
+next:   mov r0, r4
+        call {}
+        mov r0, ' '
+        call std_print_char
+        inc r4
+        jrnz next
+        hlt
+",
+        routine
+    )
+}
+
+#[test]
+fn prints_numbers() {
+    let expected: String = (0..=255u8).map(|value| format!("{} ", value)).collect();
+    assert_eq!(
+        String::from_utf8(run(&every_byte("std_print_dec8")).serial).unwrap(),
+        expected
+    );
+    let expected: String = (0..=255u8).map(|value| format!("{:02x} ", value)).collect();
+    assert_eq!(
+        String::from_utf8(run(&every_byte("std_print_hex8")).serial).unwrap(),
+        expected
+    );
+}
+
+#[test]
+fn prints_strings() {
+    let run = run("
+        mov r2, 0x20
+        mov r3, 0x10
+        call std_print_str
+        call std_print_newline
+        mov r0, '!'
+        call std_print_char
+        hlt
+
+        .org 0x2010
+        .db \"Hello, serial\", 0
+");
+    assert_eq!(run.serial, b"Hello, serial\n!");
+    // Past the 0.
+    let cpu = run.vm.cpu();
+    assert_eq!((cpu.registers[2], cpu.registers[3]), (0x20, 0x1E));
+}
+
+fn formatted(routine: &str) -> Vec<u8> {
+    let run = run(&format!(
+        "
+        mov r2, 0x20
+        clr r3
+        clr r4
+        mov r5, ' '
+next:   mov r0, r4
+        call {}
+        store [r2+], r5
+        inc r4
+        jrnz next
+        hlt
+",
+        routine
+    ));
+    let cpu = run.vm.cpu();
+    let end = (cpu.registers[2] << 8 | cpu.registers[3]) as usize;
+    cpu.memory()[BUFFER..end].to_vec()
+}
+
+#[test]
+fn formats_numbers_into_memory() {
+    let expected: String = (0..=255u8).map(|value| format!("{} ", value)).collect();
+    assert_eq!(formatted("std_fmt_dec8"), expected.as_bytes());
+    let expected: String = (0..=255u8).map(|value| format!("{:02x} ", value)).collect();
+    assert_eq!(formatted("std_fmt_hex8"), expected.as_bytes());
+}
+
+#[test]
+fn hex_digits_and_lengths() {
+    let run = run("
+        clr r4
+        mov r2, 0x20
+        clr r3
+next:   mov r1, r4
+        call std_hex_digit
+        store [r2+], r1
+        inc r4
+        mov r0, r4
+        sub r0, 16
+        jrnz next
+        mov r2, 0x21
+        clr r3
+        call std_strlen
+        hlt
+
+        .org 0x2100
+        .db \"seventeen bytes!!\", 0
+");
+    let cpu = run.vm.cpu();
+    assert_eq!(&cpu.memory()[BUFFER..BUFFER + 16], b"0123456789abcdef");
+    assert_eq!(cpu.registers[0], 17);
+    assert_eq!((cpu.registers[2], cpu.registers[3]), (0x21, 18));
+}
+
+#[test]
+fn random_bytes_follow_the_generator() {
+    let run = run("
+        mov r0, 42
+        call std_srand
+        mov r2, 0x20
+        clr r3
+        clr r4
+next:   call std_rand
+        store [r2+], r0
+        djnz r4, next
+        mov r0, 42
+        call std_srand
+        call std_rand
+        hlt
+");
+    let mut state = 42u8;
+    let expected: Vec<u8> = (0..256)
+        .map(|_| {
+            state = state.wrapping_mul(141).wrapping_add(3);
+            state
+        })
+        .collect();
+    let cpu = run.vm.cpu();
+    assert_eq!(cpu.memory()[BUFFER..BUFFER + 256], expected);
+    // Every byte once, and the same sequence again from the same seed.
+    let mut sorted = expected.clone();
+    sorted.sort();
+    assert_eq!(sorted, (0..=255).collect::<Vec<u8>>());
+    assert_eq!(cpu.registers[0], expected[0] as u16);
+}
+
+#[test]
+fn draws_pixels_and_lines() {
+    let red = Color::new(255, 0, 0);
+    let run = run("
+        mov r4, 255
+        clr r5
+        clr r6
+        mov r0, 1
+        mov r1, 1
+        call std_pixel
+        mov r0, 3
+        mov r1, 0
+        mov r2, 4
+        call std_hline
+        mov r0, 0
+        mov r1, 3
+        mov r2, 5
+        call std_vline
+        mov r0, 4
+        mov r1, 6
+        mov r2, 6
+        mov r3, 4
+        call std_box
+        hlt
+");
+    let box_edge = |x: u32, y: u32| {
+        (4..10).contains(&x) && (6..10).contains(&y) && (x == 4 || x == 9 || y == 6 || y == 9)
+    };
+    let lit = |x: u32, y: u32| {
+        (x, y) == (1, 1)
+            || (y == 0 && (3..7).contains(&x))
+            || (x == 0 && (3..8).contains(&y))
+            || box_edge(x, y)
+    };
+    let framebuffer = run.vm.framebuffer();
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let expected = if lit(x, y) { red } else { Color::new(0, 0, 0) };
+            let pixel = framebuffer.pixel((y * SIZE + x) as usize);
+            assert_eq!(pixel, expected, "{}, {}", x, y);
+        }
+    }
+}
+
+#[test]
+fn routines_keep_the_callers_registers() {
+    let routines = [
+        "std_fmt_dec8",
+        "std_fmt_hex8",
+        "std_hex_digit",
+        "std_strlen",
+        "std_print_char",
+        "std_print_newline",
+        "std_print_str",
+        "std_print_dec8",
+        "std_print_hex8",
+        "std_pixel",
+        "std_hline",
+        "std_vline",
+        "std_box",
+        "std_srand",
+        "std_rand",
+    ];
+    for routine in routines {
+        let run = run(&format!(
+            "
+        ei
+        mov r4, 0x44
+        mov r5, 0x55
+        mov r6, 0x66
+        mov r7, 0x77
+        mov r0, 200
+        mov r1, 3
+        mov r2, 0x20
+        mov r3, 4
+        call {}
+        hlt
+",
+            routine
+        ));
+        let cpu = run.vm.cpu();
+        assert_eq!(cpu.registers[4..], [0x44, 0x55, 0x66, 0x77], "{}", routine);
+        assert_eq!(cpu.sp, MicroCvm::builder().build().cpu().sp, "{}", routine);
+        assert_ne!(cpu.flags & FLAG_INTERRUPT_ENABLE, 0, "{}", routine);
+    }
+}
+
+#[test]
+fn modules_include_alone_or_together() {
+    for (name, _) in MODULES {
+        let source = format!(".include \"{}\"\n.include \"{}\"", name, name);
+        assemble(&source).unwrap_or_else(|e| panic!("{}: {}", name, e));
+    }
+}
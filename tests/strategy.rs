@@ -0,0 +1,105 @@
+// Programs from the proptest strategies have to fit their limit and decode instruction by
+// instruction to the one `hlt` on their end, instructions have to survive encoding and the
+// disassembler and assembler, and a failing program has to shrink to the instruction that fails.
+#![cfg(feature = "proptest")]
+
+use arbitrary::Unstructured;
+use microcvm_rs::asm::assemble_instruction;
+use microcvm_rs::cpu::{Opcode, OpcodeType, RegisterWidth};
+use microcvm_rs::disasm::{decode, format_instruction};
+use microcvm_rs::generate::valid_program;
+use microcvm_rs::strategy::{any_opcode, any_valid_program, any_valid_program_for};
+use microcvm_rs::symbols::SymbolTable;
+use proptest::prelude::*;
+use proptest::sample::select;
+use proptest::strategy::ValueTree;
+use proptest::test_runner::{Config, TestError, TestRunner};
+
+const WIDTHS: [RegisterWidth; 2] = [RegisterWidth::Eight, RegisterWidth::Sixteen];
+
+fn instructions(program: &[u8], width: RegisterWidth) -> Vec<Opcode> {
+    let mut offset = 0;
+    let mut opcodes = Vec::new();
+    while offset < program.len() {
+        let opcode = decode(&program[offset..], width).unwrap();
+        offset += opcode.length() as usize;
+        opcodes.push(opcode);
+    }
+    assert_eq!(offset, program.len());
+    opcodes
+}
+
+proptest! {
+    #[test]
+    fn programs_fit_and_end_in_their_only_hlt(
+        (max_len, width, program) in (1..200usize, select(WIDTHS.to_vec()))
+            .prop_flat_map(|(max_len, width)| {
+                (Just(max_len), Just(width), any_valid_program_for(max_len, width))
+            })
+    ) {
+        prop_assert!(program.len() <= max_len);
+        let opcodes = instructions(&program, width);
+        let (last, rest) = opcodes.split_last().unwrap();
+        prop_assert_eq!(last.opcode_type, OpcodeType::Hlt);
+        prop_assert!(rest.iter().all(|opcode| opcode.opcode_type != OpcodeType::Hlt));
+    }
+
+    #[test]
+    fn instructions_round_trip(
+        opcode in any_opcode(RegisterWidth::Eight),
+        wide_opcode in any_opcode(RegisterWidth::Sixteen),
+        address in any::<u16>(),
+    ) {
+        for (opcode, width) in [(opcode, RegisterWidth::Eight), (wide_opcode, RegisterWidth::Sixteen)] {
+            prop_assert_eq!(decode(&opcode.encode(), width), Some(opcode));
+            let text = format_instruction(&opcode, address, &SymbolTable::new());
+            prop_assert_eq!(assemble_instruction(&text, address, width), Ok(opcode), "`{}`", text);
+        }
+    }
+}
+
+#[test]
+fn a_failing_program_shrinks_to_the_instruction_that_fails() {
+    for width in WIDTHS {
+        let mut runner = TestRunner::new(Config {
+            failure_persistence: None,
+            ..Config::default()
+        });
+        // Fails on any program with a division in it.
+        let result = runner.run(&any_valid_program_for(128, width), |program| {
+            let divides = instructions(&program, width)
+                .iter()
+                .any(|opcode| opcode.opcode_type == OpcodeType::Div);
+            prop_assert!(!divides);
+            Ok(())
+        });
+        let Err(TestError::Fail(_, program)) = result else {
+            panic!("{:?}: no program with a division in it", width);
+        };
+        let opcodes = instructions(&program, width);
+        let kinds: Vec<_> = opcodes.iter().map(|opcode| opcode.opcode_type).collect();
+        assert_eq!(kinds, [OpcodeType::Div, OpcodeType::Hlt], "{:?}", width);
+    }
+}
+
+#[test]
+fn a_program_needs_room_for_its_hlt() {
+    let mut runner = TestRunner::deterministic();
+    for _ in 0..32 {
+        let program = any_valid_program(1)
+            .new_tree(&mut runner)
+            .unwrap()
+            .current();
+        assert_eq!(program, [OpcodeType::Hlt as u8]);
+    }
+    let data = [0xFF; 16];
+    for width in WIDTHS {
+        assert!(valid_program(&mut Unstructured::new(&data), 0, width).is_err());
+        assert_eq!(
+            valid_program(&mut Unstructured::new(&data), 1, width).unwrap(),
+            [OpcodeType::Hlt as u8]
+        );
+    }
+    let zero = std::panic::catch_unwind(|| any_valid_program(0));
+    assert!(zero.is_err());
+}
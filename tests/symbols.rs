@@ -0,0 +1,121 @@
+// The labels `assemble_with_symbols` resolves have to survive the sidecar file, written and
+// read back, and then name addresses wherever the disassembler or a trace shows them.
+
+use std::sync::{Arc, Mutex};
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::{assemble, assemble_with_symbols};
+use microcvm_rs::cpu::RegisterWidth;
+use microcvm_rs::disasm::disassemble;
+use microcvm_rs::symbols::{SymbolTable, SymbolWarning};
+use microcvm_rs::trace::{TraceEntry, TraceSink};
+
+const PROGRAM: &str = "
+start:  mov r0, 3
+again:  call draw_sprite
+        djnz r0, again
+        hlt
+
+draw_sprite:
+        load r1, [sprite]
+        ret
+
+sprite: .db 0x5A
+";
+
+const LABELS: [&str; 4] = ["start", "again", "draw_sprite", "sprite"];
+
+// Each entry as a trace would print it, with `symbols`.
+struct Lines {
+    symbols: SymbolTable,
+    lines: Arc<Mutex<Vec<String>>>,
+}
+
+impl TraceSink for Lines {
+    fn trace(&mut self, entry: &TraceEntry) {
+        let line = entry.to_string_with(&self.symbols);
+        self.lines.lock().unwrap().push(line);
+    }
+}
+
+// The sidecar, read back, with whatever it warned about.
+fn round_trip(symbols: &SymbolTable) -> SymbolTable {
+    let (parsed, warnings) = SymbolTable::parse(&symbols.to_sidecar()).unwrap();
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    parsed
+}
+
+#[test]
+fn the_sidecar_round_trips() {
+    let (program, symbols) = assemble_with_symbols(PROGRAM).unwrap();
+    let parsed = round_trip(&symbols);
+    assert_eq!(
+        parsed.iter().collect::<Vec<_>>(),
+        symbols.iter().collect::<Vec<_>>()
+    );
+    for label in LABELS {
+        assert!(parsed.address(label).is_some(), "{}", label);
+    }
+    assert_eq!(parsed.address("start"), Some(0));
+    // The label on the last byte is still inside the program.
+    assert!(parsed.check(0, program.len()).is_empty());
+}
+
+#[test]
+fn names_show_in_the_disassembly_and_the_trace() {
+    let (program, symbols) = assemble_with_symbols(PROGRAM).unwrap();
+    let symbols = round_trip(&symbols);
+
+    let source = disassemble(&program, 0, &symbols, RegisterWidth::Eight);
+    for label in LABELS {
+        assert!(
+            source.contains(&format!("{}:", label)),
+            "{}\n{}",
+            label,
+            source
+        );
+    }
+    assert!(source.contains("call draw_sprite"), "{}", source);
+    assert!(source.contains("djnz r0, again"), "{}", source);
+    assert!(source.contains("[sprite]"), "{}", source);
+    // It assembles back to the same program.
+    assert_eq!(assemble(&source).unwrap(), program);
+
+    let lines = Arc::new(Mutex::new(Vec::new()));
+    let mut vm = MicroCvm::builder()
+        .trace(Box::new(Lines {
+            symbols,
+            lines: lines.clone(),
+        }))
+        .build();
+    vm.load_program(&program).unwrap();
+    vm.run().unwrap();
+    let lines = lines.lock().unwrap();
+    let calls = lines
+        .iter()
+        .filter(|line| line.contains("call draw_sprite"));
+    assert_eq!(calls.count(), 3, "{:#?}", lines);
+    assert!(lines.iter().any(|line| line.contains("load r1, [sprite]")));
+}
+
+#[test]
+fn duplicates_and_strays_warn() {
+    let (symbols, warnings) =
+        SymbolTable::parse("start = 0x0000\nend = 0x0010\nstart = 0x0004\nfar = 0x8000\n").unwrap();
+    assert_eq!(
+        warnings,
+        [SymbolWarning::Duplicate {
+            name: "start".to_string(),
+            line: 3
+        }]
+    );
+    // The first definition wins.
+    assert_eq!(symbols.address("start"), Some(0));
+    assert_eq!(
+        symbols.check(0, 0x10),
+        [SymbolWarning::OutsideProgram {
+            name: "far".to_string(),
+            address: 0x8000
+        }]
+    );
+}
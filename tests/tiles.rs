@@ -0,0 +1,169 @@
+// The tile layer has to place every pixel of the map exactly, wrapping around both edges at any
+// scroll, and dirty only the part of the screen where a changed cell or tile shows.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::cpu::VIDEO_CONTROL_TILES;
+use microcvm_rs::tilemap::{TILE_BYTES, TILE_SIZE, map_cells};
+use microcvm_rs::video::Rect;
+
+const WIDTH: usize = 16;
+const HEIGHT: usize = 8;
+const TILE_SET: usize = 0x10000;
+const MAP: usize = 0x20000;
+// A 16x8 screen has a map of 3x2 cells.
+const COLUMNS: usize = 3;
+const ROWS: usize = 2;
+
+// Every pixel of tile `t` at `x, y` is red `t`, green `x` and blue `y`, so each pixel on
+// screen says where on the map it came from.
+fn tiled(map: [u8; COLUMNS * ROWS]) -> MicroCvm {
+    assert_eq!((map_cells(WIDTH), map_cells(HEIGHT)), (COLUMNS, ROWS));
+    let mut vm = MicroCvm::builder()
+        .resolution(WIDTH as u32, HEIGHT as u32)
+        .build();
+    let cpu = vm.cpu_mut();
+    cpu.video_control = VIDEO_CONTROL_TILES;
+    cpu.tiles_mut().tile_set = TILE_SET as u32;
+    cpu.tiles_mut().map = MAP as u32;
+    for tile in 0..8 {
+        let pixels = &mut cpu.memory_mut()[TILE_SET + tile * TILE_BYTES..][..TILE_BYTES];
+        for (i, rgb) in pixels.chunks_mut(3).enumerate() {
+            rgb.copy_from_slice(&[tile as u8, (i % TILE_SIZE) as u8, (i / TILE_SIZE) as u8]);
+        }
+    }
+    cpu.memory_mut()[MAP..MAP + map.len()].copy_from_slice(&map);
+    vm
+}
+
+fn frame(vm: &mut MicroCvm) -> Vec<u8> {
+    let mut frame = vec![0; WIDTH * HEIGHT * 4];
+    vm.frame_rgba(&mut frame);
+    frame
+}
+
+fn scroll(vm: &mut MicroCvm, x: u16, y: u16) {
+    let tiles = vm.cpu_mut().tiles_mut();
+    (tiles.scroll_x, tiles.scroll_y) = (x, y);
+}
+
+#[test]
+fn scrolling_wraps_around_pixel_for_pixel() {
+    let map = [1, 2, 3, 4, 5, 6];
+    let mut vm = tiled(map);
+    let (map_width, map_height) = (COLUMNS * TILE_SIZE, ROWS * TILE_SIZE);
+    for (scroll_x, scroll_y) in [
+        (0, 0),
+        (1, 0),
+        (0, 1),
+        (7, 3),
+        (8, 8),
+        (20, 12),
+        (23, 15),
+        (24, 16),
+        (300, 1001),
+        (u16::MAX, u16::MAX),
+    ] {
+        scroll(&mut vm, scroll_x, scroll_y);
+        let frame = frame(&mut vm);
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let map_x = (x + scroll_x as usize) % map_width;
+                let map_y = (y + scroll_y as usize) % map_height;
+                let tile = map[map_y / TILE_SIZE * COLUMNS + map_x / TILE_SIZE];
+                let expected = [
+                    tile,
+                    (map_x % TILE_SIZE) as u8,
+                    (map_y % TILE_SIZE) as u8,
+                    255,
+                ];
+                let pixel = (y * WIDTH + x) * 4;
+                assert_eq!(
+                    frame[pixel..pixel + 4],
+                    expected,
+                    "pixel {}, {} scrolled by {}, {}",
+                    x,
+                    y,
+                    scroll_x,
+                    scroll_y
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn one_cell_dirties_only_where_its_tile_shows() {
+    let mut vm = tiled([1, 2, 3, 4, 5, 6]);
+    // The first frame draws everything.
+    frame(&mut vm);
+    assert_eq!(
+        vm.cpu_mut().take_dirty(),
+        Some(Rect::new(0, 0, WIDTH as u32, HEIGHT as u32))
+    );
+    frame(&mut vm);
+    assert_eq!(vm.cpu().tiles().dirty(), None);
+    assert_eq!(vm.cpu_mut().take_dirty(), None);
+
+    // The second cell of the top row.
+    vm.cpu_mut().memory_mut()[MAP + 1] = 7;
+    frame(&mut vm);
+    assert_eq!(vm.cpu().tiles().redrawn_cells(), [1]);
+    assert_eq!(vm.cpu_mut().take_dirty(), Some(Rect::new(8, 0, 8, 8)));
+
+    // Cells below the screen are redrawn but dirty nothing.
+    vm.cpu_mut().memory_mut()[MAP + 4] = 7;
+    frame(&mut vm);
+    assert_eq!(vm.cpu().tiles().redrawn_cells(), [4]);
+    assert_eq!(vm.cpu_mut().take_dirty(), None);
+
+    // Scrolling dirties the whole screen.
+    scroll(&mut vm, 20, 12);
+    frame(&mut vm);
+    assert_eq!(
+        vm.cpu_mut().take_dirty(),
+        Some(Rect::new(0, 0, WIDTH as u32, HEIGHT as u32))
+    );
+
+    // Scrolled 20 right and 12 down, the middle cell of the bottom row shows in the top
+    // right corner, and the last one wraps around to the top left.
+    vm.cpu_mut().memory_mut()[MAP + 4] = 0;
+    frame(&mut vm);
+    assert_eq!(vm.cpu_mut().take_dirty(), Some(Rect::new(12, 0, 4, 4)));
+    vm.cpu_mut().memory_mut()[MAP + 5] = 0;
+    frame(&mut vm);
+    assert_eq!(vm.cpu_mut().take_dirty(), Some(Rect::new(0, 0, 4, 4)));
+}
+
+#[test]
+fn a_changed_tile_dirties_every_cell_showing_it() {
+    // Scrolled 4 right and 4 down, the first two cells of the top row show in the top half
+    // of the screen, the first one wrapped around to the left edge, and the second cell of
+    // the bottom row shows below them.
+    let mut vm = tiled([3, 3, 2, 4, 1, 6]);
+    scroll(&mut vm, 4, 4);
+    frame(&mut vm);
+    vm.cpu_mut().take_dirty();
+    vm.cpu_mut().memory_mut()[TILE_SET + 3 * TILE_BYTES] = 0xFF;
+    frame(&mut vm);
+    assert_eq!(vm.cpu().tiles().redrawn_cells(), [0, 1]);
+    assert_eq!(vm.cpu_mut().take_dirty(), Some(Rect::new(0, 0, 12, 4)));
+    vm.cpu_mut().memory_mut()[TILE_SET + TILE_BYTES] = 0xFF;
+    frame(&mut vm);
+    assert_eq!(vm.cpu().tiles().redrawn_cells(), [4]);
+    assert_eq!(vm.cpu_mut().take_dirty(), Some(Rect::new(4, 4, 8, 4)));
+}
+
+#[test]
+fn switching_modes_dirties_the_screen() {
+    let mut vm = tiled([0; 6]);
+    frame(&mut vm);
+    vm.cpu_mut().take_dirty();
+    let program = assemble("mov r0, 0\nstore [0xFF13], r0\nstore [0xFF13], r0\nhlt").unwrap();
+    vm.load_program(&program).unwrap();
+    vm.run().unwrap();
+    assert_eq!(
+        vm.cpu_mut().take_dirty(),
+        Some(Rect::new(0, 0, WIDTH as u32, HEIGHT as u32))
+    );
+}
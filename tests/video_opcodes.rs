@@ -0,0 +1,131 @@
+// Every video operation has to assemble by name, decode back to itself and print as the same
+// text, and an operation byte outside the table has to be refused by the assembler, the
+// disassembler and each of the CPU's execution paths alike.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::cpu::{
+    MicroCVMCpu, OpcodeArg1, OpcodeArg2, OpcodeType, Register, RegisterWidth, VideoOpcode,
+    VideoOpcodeType,
+};
+use microcvm_rs::disasm::{decode, format_instruction};
+use microcvm_rs::error::VmError;
+use microcvm_rs::fault::{FAULT_INVALID_VIDEO_OPCODE, fault_code};
+use microcvm_rs::symbols::SymbolTable;
+use microcvm_rs::trace::{TraceEntry, TraceSink};
+
+const OPERATIONS: [(VideoOpcodeType, &str, u8); 7] = [
+    (VideoOpcodeType::Fill, "fill", 3),
+    (VideoOpcodeType::Clear, "clear", 0),
+    (VideoOpcodeType::SetPixel, "setpixel", 5),
+    (VideoOpcodeType::FillRect, "fillrect", 7),
+    (VideoOpcodeType::Vsync, "vsync", 0),
+    (VideoOpcodeType::FadeTo, "fadeto", 2),
+    (VideoOpcodeType::CopyRect, "copyrect", 6),
+];
+
+struct Discard;
+
+impl TraceSink for Discard {
+    fn trace(&mut self, _entry: &TraceEntry) {}
+}
+
+#[test]
+fn every_operation_round_trips() {
+    for (operation, mnemonic, count) in OPERATIONS {
+        let byte = operation as u8;
+        assert_eq!(VideoOpcodeType::try_from(byte).unwrap(), operation);
+        assert_eq!(operation.mnemonic(), mnemonic);
+        assert_eq!(VideoOpcodeType::from_mnemonic(mnemonic), Some(operation));
+        assert_eq!(
+            MicroCVMCpu::get_video_opcode_argument_count(operation),
+            count,
+            "{}",
+            mnemonic
+        );
+        assert_eq!(
+            MicroCVMCpu::create_video_opcode(byte, 1).unwrap(),
+            VideoOpcode {
+                opcode_type: operation,
+                base: Register::R1,
+            }
+        );
+
+        let source = format!("video {}, r1", mnemonic);
+        let bytes = assemble(&source).unwrap();
+        assert_eq!(bytes, [OpcodeType::Video as u8, byte, 1], "{}", source);
+        // The number still assembles to the same bytes.
+        assert_eq!(assemble(&format!("video {}, r1", byte)).unwrap(), bytes);
+        for width in [RegisterWidth::Eight, RegisterWidth::Sixteen] {
+            let opcode = decode(&bytes, width).unwrap();
+            assert_eq!(opcode.arg1, Some(OpcodeArg1::Immediate(byte)));
+            assert_eq!(opcode.arg2, Some(OpcodeArg2::Register(Register::R1)));
+            assert_eq!(opcode.encode(), bytes);
+            let text = format_instruction(&opcode, 0, &SymbolTable::new());
+            assert_eq!(text, source);
+        }
+    }
+}
+
+#[test]
+fn symbols_win_over_operation_names() {
+    // The demos name operations with `.equ`, and a symbol called `clear` means the symbol.
+    let bytes = assemble(".equ clear, 5\nvideo clear, r0").unwrap();
+    assert_eq!(bytes, [OpcodeType::Video as u8, 5, 0]);
+    assert_eq!(assemble("video FILLRECT, r0").unwrap()[1], 4);
+}
+
+fn run(mut vm: MicroCvm) -> (MicroCvm, VmError) {
+    vm.load_program(&[OpcodeType::Video as u8, 8, 0, 0xFF])
+        .unwrap();
+    let error = vm.run().unwrap_err();
+    (vm, error)
+}
+
+#[test]
+fn unknown_operations_are_refused() {
+    for byte in [0, 8, 0xFF] {
+        let error = MicroCVMCpu::create_video_opcode(byte, 0).unwrap_err();
+        assert!(
+            matches!(error, VmError::InvalidVideoOpcode(invalid) if invalid.0 == byte),
+            "{}: {:?}",
+            byte,
+            error
+        );
+        assert_eq!(
+            decode(&[OpcodeType::Video as u8, byte, 0], RegisterWidth::Eight),
+            None
+        );
+    }
+    let error = assemble("video 8, r0").unwrap_err();
+    assert_eq!(error.to_string(), "line 1: 8 is not a video operation");
+
+    let vms = [
+        ("fast", run(MicroCvm::builder().build())),
+        (
+            "uncached",
+            run(MicroCvm::builder().decode_cache(false).build()),
+        ),
+        (
+            "traced",
+            run(MicroCvm::builder().trace(Box::new(Discard)).build()),
+        ),
+    ];
+    for (path, (vm, error)) in vms {
+        assert!(
+            matches!(error.cause(), VmError::InvalidVideoOpcode(invalid) if invalid.0 == 8),
+            "{}: {:?}",
+            path,
+            error
+        );
+        assert_eq!(
+            error.cause().to_string(),
+            "Invalid Video Opcode: 8",
+            "{}",
+            path
+        );
+        assert_eq!(fault_code(&error), FAULT_INVALID_VIDEO_OPCODE, "{}", path);
+        // Refused while decoding, so it never ran.
+        assert_eq!(vm.cpu().cycles, 0, "{}", path);
+    }
+}
@@ -0,0 +1,108 @@
+// The watchdog counts cycles down from the last feed, so a guest that keeps feeding it runs to
+// completion, and one that stops expires at exactly the cycle the timeout says, down each of
+// the CPU's execution paths.
+
+use microcvm_rs::MicroCvm;
+use microcvm_rs::asm::assemble;
+use microcvm_rs::error::VmError;
+use microcvm_rs::trace::{TraceEntry, TraceSink};
+use microcvm_rs::watchdog::{WatchdogAction, WatchdogConfig};
+
+// Feeds the dog five times, a store and a djnz apiece, then runs one of the endings below.
+const FEED: &str = "
+        mov r4, 5
+feed:   store [0xFF05], r0
+        djnz r4, feed
+";
+const HALT: &str = "hlt";
+// Spins without feeding it.
+const SPIN: &str = "stuck: jr stuck";
+
+const STUCK: u16 = 0x000a;
+
+struct Discard;
+
+impl TraceSink for Discard {
+    fn trace(&mut self, _entry: &TraceEntry) {}
+}
+
+fn paths(config: WatchdogConfig) -> [(&'static str, MicroCvm); 3] {
+    let builder = || MicroCvm::builder().watchdog(config);
+    [
+        ("fast", builder().build()),
+        ("uncached", builder().decode_cache(false).build()),
+        ("traced", builder().trace(Box::new(Discard)).build()),
+    ]
+}
+
+fn run(vm: &mut MicroCvm, source: &str) -> Result<(), VmError> {
+    vm.load_program(&assemble(source).unwrap()).unwrap();
+    vm.run().map(|_| ())
+}
+
+#[test]
+fn a_fed_dog_lets_the_program_finish() {
+    // Each feed comes two cycles after the last, well inside the timeout, though the run
+    // takes longer than it.
+    for (path, mut vm) in paths(WatchdogConfig::new(4)) {
+        run(&mut vm, &format!("{}{}", FEED, HALT)).unwrap();
+        assert!(vm.cpu().cycles > 4, "{}", path);
+        assert!(vm.cpu().watchdog.armed(), "{}", path);
+    }
+}
+
+#[test]
+fn a_starved_dog_fires_on_time() {
+    for (path, mut vm) in paths(WatchdogConfig::new(20)) {
+        let error = run(&mut vm, &format!("{}{}", FEED, SPIN)).unwrap_err();
+        assert!(
+            matches!(error.cause(), VmError::WatchdogExpired { pc: STUCK }),
+            "{}: {}",
+            path,
+            error
+        );
+        // The last feed is the store in the fifth pass, at cycle 10.
+        assert_eq!(vm.cpu().cycles, 10 + 20, "{}", path);
+        assert_eq!(error.report().unwrap().pc, STUCK, "{}", path);
+        let expired_pc = [0xFF0E, 0xFF0F].map(|addr| vm.cpu().read_mem(addr).unwrap());
+        assert_eq!(u16::from_le_bytes(expired_pc), STUCK, "{}", path);
+    }
+}
+
+#[test]
+fn the_guest_can_arm_it_and_take_the_interrupt() {
+    let program = "
+        mov r0, 0x00
+        store [0xFF90], r0      ; vector table at 0x0200
+        store [0xFF07], r0
+        store [0xFF08], r0
+        store [0xFF09], r0
+        mov r0, 0x02
+        store [0xFF91], r0
+        mov r0, 8
+        store [0xFF06], r0      ; a timeout of 8 cycles
+        mov r0, 1
+        store [0xFF04], r0      ; armed at cycle 11
+stuck:  jr stuck
+
+on_watchdog:
+        load r1, [0xFF04]       ; disarmed and expired
+        hlt
+
+        .org 0x0200
+        .dw 0, 0, 0, on_watchdog
+    ";
+    let config = WatchdogConfig {
+        action: WatchdogAction::Interrupt,
+        ..WatchdogConfig::default()
+    };
+    for (path, mut vm) in paths(config) {
+        run(&mut vm, program).unwrap();
+        let cpu = vm.cpu();
+        assert_eq!(cpu.registers[1], 0x02, "{}", path);
+        // Expiring at 19, entering the handler, its load and the hlt.
+        assert_eq!(cpu.cycles, 19 + 1 + 2, "{}", path);
+        // The handler's return address is the spin it interrupted.
+        assert_eq!(cpu.memory()[cpu.sp as usize + 1], 0x28, "{}", path);
+    }
+}
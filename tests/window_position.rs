@@ -0,0 +1,103 @@
+// The window has to open centred on the monitor asked for, physical pixels and all, fall back to
+// the primary monitor when asked for one that isn't there, and open exactly where it's told to.
+#![cfg(feature = "window")]
+
+use microcvm_rs::render::{MonitorArea, WindowPlacement, window_position};
+use winit::dpi::{LogicalSize, PhysicalPosition};
+
+const MONITORS: [MonitorArea; 3] = [
+    MonitorArea {
+        x: 0,
+        y: 0,
+        width: 1920,
+        height: 1080,
+        scale_factor: 1.0,
+    },
+    // Left of the first one, at twice the pixel density.
+    MonitorArea {
+        x: -2560,
+        y: -360,
+        width: 2560,
+        height: 1440,
+        scale_factor: 2.0,
+    },
+    MonitorArea {
+        x: 1920,
+        y: 0,
+        width: 1280,
+        height: 1024,
+        scale_factor: 1.0,
+    },
+];
+
+const SIZE: LogicalSize<f64> = LogicalSize::new(640.0, 400.0);
+
+fn place(placement: WindowPlacement, primary: Option<usize>) -> Option<PhysicalPosition<i32>> {
+    window_position(placement, &MONITORS, primary, SIZE)
+}
+
+#[test]
+fn centred_on_the_primary_monitor() {
+    assert_eq!(
+        place(WindowPlacement::Primary, Some(0)),
+        Some(PhysicalPosition::new(640, 340))
+    );
+    // 640x400 logical is 1280x800 physical at a scale of 2.
+    assert_eq!(
+        place(WindowPlacement::Primary, Some(1)),
+        Some(PhysicalPosition::new(-2560 + 640, -360 + 320))
+    );
+    // Without a primary monitor the window manager decides.
+    assert_eq!(place(WindowPlacement::Primary, None), None);
+    assert_eq!(
+        window_position(WindowPlacement::Primary, &[], Some(0), SIZE),
+        None
+    );
+}
+
+#[test]
+fn centred_on_the_monitor_asked_for() {
+    assert_eq!(
+        place(WindowPlacement::Monitor(2), Some(0)),
+        Some(PhysicalPosition::new(1920 + 320, 312))
+    );
+    assert_eq!(
+        place(WindowPlacement::Monitor(1), Some(0)),
+        Some(PhysicalPosition::new(-1920, -40))
+    );
+    // A window bigger than the monitor keeps its top left corner on it.
+    let big = LogicalSize::new(1600.0, 1200.0);
+    assert_eq!(
+        window_position(WindowPlacement::Monitor(2), &MONITORS, Some(0), big),
+        Some(PhysicalPosition::new(1920, 0))
+    );
+}
+
+#[test]
+fn a_missing_monitor_falls_back_to_the_primary_one() {
+    for index in [3, 100, usize::MAX] {
+        assert_eq!(
+            place(WindowPlacement::Monitor(index), Some(2)),
+            place(WindowPlacement::Monitor(2), Some(2)),
+            "{}",
+            index
+        );
+        assert_eq!(place(WindowPlacement::Monitor(index), None), None);
+    }
+}
+
+#[test]
+fn an_explicit_position_is_kept_as_it_is() {
+    for (x, y) in [(0, 0), (100, -50), (-3000, 5000)] {
+        for primary in [Some(0), Some(1), None] {
+            assert_eq!(
+                place(WindowPlacement::Position(x, y), primary),
+                Some(PhysicalPosition::new(x, y))
+            );
+        }
+        assert_eq!(
+            window_position(WindowPlacement::Position(x, y), &[], None, SIZE),
+            Some(PhysicalPosition::new(x, y))
+        );
+    }
+}